@@ -170,13 +170,25 @@ impl From<CacheError> for JsScrapingResult {
             CacheError::PermissionDenied(details) => {
                 (JsScrapingFailureReason::PermissionDenied, details)
             }
+            CacheError::Blocked(details) => (JsScrapingFailureReason::Blocked, details),
             CacheError::Timeout(duration) => (
                 JsScrapingFailureReason::Timeout,
                 format!("Timeout after {}", humantime::format_duration(duration)),
             ),
             CacheError::DownloadError(details) => (JsScrapingFailureReason::DownloadError, details),
+            CacheError::Unavailable(_) => {
+                (JsScrapingFailureReason::DownloadError, value.to_string())
+            }
+            CacheError::ChecksumMismatch(_) => {
+                (JsScrapingFailureReason::DownloadError, value.to_string())
+            }
             CacheError::Malformed(details) => (JsScrapingFailureReason::Other, details),
             CacheError::Unsupported(details) => (JsScrapingFailureReason::Other, details),
+            CacheError::SizeLimitExceeded(_) => (JsScrapingFailureReason::Other, value.to_string()),
+            CacheError::NoMatchingArchiveMember(details) => {
+                (JsScrapingFailureReason::NotFound, details)
+            }
+            CacheError::NoMatchingArch(_) => (JsScrapingFailureReason::NotFound, value.to_string()),
             CacheError::InternalError => (JsScrapingFailureReason::Other, String::new()),
         };
 
@@ -197,6 +209,8 @@ pub enum JsScrapingFailureReason {
     InvalidHost,
     /// Permission to access the file was denied.
     PermissionDenied,
+    /// The file's host is one of the blocked destinations.
+    Blocked,
     /// The scraping attempt timed out.
     Timeout,
     /// There was a non-timeout error while downloading.
@@ -775,6 +775,7 @@ async fn test_manual_processing() {
         ))
         .unwrap(),
         token: token.to_string(),
+        organizations: Vec::new(),
     };
 
     let request = make_js_request(source, frames, modules, release, dist);
@@ -2,12 +2,16 @@
 //!
 //! This service downloads and caches the `PList` and [`BcSymbolMap`] used to un-obfuscate
 //! debug symbols for obfuscated Apple bitcode builds.
+//!
+//! Both files are looked up on all sources, also inside of zip archives like the
+//! `BCSymbolMaps` folder of an Xcode archive. Archive members are found by the names Xcode
+//! gives to these files.
 
 use std::fmt::{self, Display};
 use std::sync::Arc;
 
 use anyhow::Context;
-use futures::future::{self, BoxFuture};
+use futures::future::{self, BoxFuture, FutureExt};
 use sentry::{Hub, SentryFutureExt};
 
 use symbolic::common::{ByteView, DebugId};
@@ -17,10 +21,13 @@ use symbolicator_service::caching::{
     Cache, CacheEntry, CacheError, CacheItemRequest, CacheKey, CacheVersions, Cacher,
     SharedCacheRef,
 };
-use symbolicator_service::download::{fetch_file, DownloadService};
+use symbolicator_service::download::{fetch_file_member, DownloadService};
 use symbolicator_service::metric;
+use symbolicator_service::objects::{
+    AllObjectCandidates, CandidateRejection, ObjectCandidate, ObjectDownloadInfo,
+};
 use symbolicator_service::types::Scope;
-use symbolicator_sources::{FileType, RemoteFile, SourceConfig};
+use symbolicator_sources::{FileType, RemoteFile, RemoteFileUri, SourceConfig, SourceId};
 use tempfile::NamedTempFile;
 
 /// Handle to a valid BCSymbolMap.
@@ -41,6 +48,18 @@ impl BcSymbolMapHandle {
     }
 }
 
+/// The result of looking up the [`BcSymbolMap`] of an object.
+#[derive(Debug, Clone, Default)]
+pub struct BcSymbolMapLookup {
+    /// The `BCSymbolMap`, if one was found.
+    pub handle: Option<BcSymbolMapHandle>,
+    /// The locations at which the `BCSymbolMap` was looked up in vain.
+    ///
+    /// These are only reported if the `PList` of the object refers to a `BCSymbolMap`, which
+    /// means that the hidden names of the object remain unresolved.
+    pub candidates: AllObjectCandidates,
+}
+
 /// The handle to be returned by [`CacheItemRequest`].
 ///
 /// This trait requires us to return a handle regardless of its cache status.
@@ -81,11 +100,20 @@ struct FetchFileRequest {
 }
 
 impl FetchFileRequest {
+    /// The name Xcode gives to the file, which is extracted from archives.
+    fn member_name(&self) -> String {
+        match self.kind {
+            AuxDifKind::BcSymbolMap => format!("{}.bcsymbolmap", self.uuid.uuid()),
+            AuxDifKind::UuidMap => format!("{}.plist", self.uuid.uuid()),
+        }
+    }
+
     #[tracing::instrument(skip(self, temp_file), fields(kind = %self.kind))]
     async fn fetch_auxdif(&self, temp_file: &mut NamedTempFile) -> CacheEntry {
-        fetch_file(
+        fetch_file_member(
             self.download_svc.clone(),
             self.file_source.clone(),
+            &self.member_name(),
             temp_file,
         )
         .await?;
@@ -154,17 +182,20 @@ impl BitcodeService {
         }
     }
 
-    /// Returns a `BCSymbolMap` if one is found for the `uuid`.
+    /// Looks up the `BCSymbolMap` for the `uuid`.
     pub async fn fetch_bcsymbolmap(
         &self,
         uuid: DebugId,
         scope: Scope,
         sources: Arc<[SourceConfig]>,
-    ) -> Option<BcSymbolMapHandle> {
+    ) -> BcSymbolMapLookup {
         // First find the PList.
-        let plist_handle = self
+        let (plist_handle, _) = self
             .fetch_file_from_all_sources(uuid, AuxDifKind::UuidMap, scope.clone(), sources.clone())
-            .await?;
+            .await;
+        let Some(plist_handle) = plist_handle else {
+            return BcSymbolMapLookup::default();
+        };
 
         let uuid_mapping = UuidMapping::parse_plist(uuid, &plist_handle.data)
             .context("Failed to parse plist")
@@ -172,33 +203,53 @@ impl BitcodeService {
                 tracing::warn!("{}: {:?}", err, err.source());
                 sentry::capture_error(&*err);
                 err
-            })
-            .ok()?;
+            });
+        let Ok(uuid_mapping) = uuid_mapping else {
+            return BcSymbolMapLookup::default();
+        };
 
         // Next find the BCSymbolMap.
-        let symbolmap_handle = self
+        let (symbolmap_handle, candidates) = self
             .fetch_file_from_all_sources(
                 uuid_mapping.original_uuid(),
                 AuxDifKind::BcSymbolMap,
                 scope,
                 sources,
             )
-            .await?;
+            .await;
 
-        Some(BcSymbolMapHandle {
-            file: symbolmap_handle.file.clone(),
-            uuid: symbolmap_handle.uuid,
-            data: symbolmap_handle.data.clone(),
-        })
+        match symbolmap_handle {
+            Some(symbolmap_handle) => BcSymbolMapLookup {
+                handle: Some(BcSymbolMapHandle {
+                    file: symbolmap_handle.file.clone(),
+                    uuid: symbolmap_handle.uuid,
+                    data: symbolmap_handle.data.clone(),
+                }),
+                candidates: AllObjectCandidates::default(),
+            },
+            None => {
+                tracing::debug!(
+                    "BCSymbolMap {} not found, hidden names remain unresolved",
+                    uuid_mapping.original_uuid()
+                );
+                BcSymbolMapLookup {
+                    handle: None,
+                    candidates,
+                }
+            }
+        }
     }
 
+    /// Fetches the file from the first source that has it.
+    ///
+    /// Also returns the candidates for all the locations the file was looked up at.
     async fn fetch_file_from_all_sources(
         &self,
         uuid: DebugId,
         dif_kind: AuxDifKind,
         scope: Scope,
         sources: Arc<[SourceConfig]>,
-    ) -> Option<Arc<CacheHandle>> {
+    ) -> (Option<Arc<CacheHandle>>, AllObjectCandidates) {
         let file_type = match dif_kind {
             AuxDifKind::BcSymbolMap => &[FileType::BcSymbolMap],
             AuxDifKind::UuidMap => &[FileType::UuidMap],
@@ -208,6 +259,20 @@ impl BitcodeService {
             .list_files(&sources, file_type, &uuid.into())
             .await;
 
+        // Create a NotFound entry for each source on which no file was listed.
+        let mut candidates: Vec<_> = sources
+            .iter()
+            .filter(|source| !files.iter().any(|file| file.source_id() == source.id()))
+            .map(|source| ObjectCandidate {
+                source: source.id().clone(),
+                location: RemoteFileUri::new(format!("No {dif_kind} listed on this source")),
+                download: ObjectDownloadInfo::NotFound,
+                rejection: None,
+                unwind: Default::default(),
+                debug: Default::default(),
+            })
+            .collect();
+
         let fetch_jobs = files.into_iter().map(|file_source| {
             let scope = if file_source.is_public() {
                 Scope::Global
@@ -227,12 +292,60 @@ impl BitcodeService {
                 download_svc: self.download_svc.clone(),
             };
             let cache_key = CacheKey::from_scoped_file(&scope, &request.file_source);
+            let source = request.file_source.source_id().clone();
+            let location = request.file_source.uri();
             self.cache
                 .compute_memoized(request, cache_key)
+                .map(|result| (source, location, result))
                 .bind_hub(hub)
         });
 
         let all_results = future::join_all(fetch_jobs).await;
-        all_results.into_iter().find_map(Result::ok)
+        let mut found = None;
+        for (source, location, result) in all_results {
+            candidates.push(candidate_for_result(source, location, &result));
+            if found.is_none() {
+                found = result.ok();
+            }
+        }
+
+        (found, candidates.into())
+    }
+}
+
+/// Creates an [`ObjectCandidate`] for the result of fetching an auxiliary file.
+fn candidate_for_result<T>(
+    source: SourceId,
+    location: RemoteFileUri,
+    result: &CacheEntry<T>,
+) -> ObjectCandidate {
+    // Same logic as in `create_candidate_info`.
+    let download = match result {
+        Ok(_) => ObjectDownloadInfo::Ok {
+            features: Default::default(),
+        },
+        Err(CacheError::NotFound) => ObjectDownloadInfo::NotFound,
+        Err(CacheError::PermissionDenied(details)) => ObjectDownloadInfo::NoPerm {
+            details: details.clone(),
+        },
+        Err(CacheError::Blocked(details)) => ObjectDownloadInfo::Blocked {
+            details: details.clone(),
+        },
+        Err(CacheError::Malformed(_)) => ObjectDownloadInfo::Malformed,
+        Err(err) => ObjectDownloadInfo::Error {
+            details: err.to_string(),
+        },
+    };
+
+    ObjectCandidate {
+        source,
+        location,
+        download,
+        rejection: result
+            .as_ref()
+            .err()
+            .and_then(CandidateRejection::from_error),
+        unwind: Default::default(),
+        debug: Default::default(),
     }
 }
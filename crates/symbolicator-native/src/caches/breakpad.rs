@@ -0,0 +1,319 @@
+//! Generation of Breakpad symbol files from object files.
+//!
+//! The generated files follow the text format described at
+//! <https://chromium.googlesource.com/breakpad/breakpad/+/HEAD/docs/symbol_files.md>, for tools
+//! which consume Breakpad symbols rather than the original debug files.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use tempfile::NamedTempFile;
+
+use symbolic::cfi::AsciiCfiWriter;
+use symbolic::common::{join_path, ByteView, Name};
+use symbolic::debuginfo::{FileFormat, Function, Object};
+use symbolic::demangle::Demangle;
+use symbolicator_service::caches::versions::BREAKPAD_CACHE_VERSIONS;
+use symbolicator_service::caching::{
+    Cache, CacheEntry, CacheError, CacheItemRequest, CacheKey, CacheVersions, Cacher,
+    SharedCacheRef,
+};
+use symbolicator_service::objects::{
+    CandidateStatus, FindObject, ObjectHandle, ObjectMetaHandle, ObjectPurpose, ObjectsActor,
+};
+use symbolicator_service::types::Scope;
+use symbolicator_service::utils::sentry::ConfigureScope;
+use symbolicator_sources::{FileType, ObjectId, SourceConfig};
+
+use crate::symbolication::demangle::DEMANGLE_OPTIONS;
+
+use super::derived::{derive_from_object_handle, DerivedCache};
+
+/// Information for generating the Breakpad symbols of a debug file.
+#[derive(Debug, Clone)]
+pub struct FetchBreakpadSymbols {
+    pub identifier: ObjectId,
+    pub sources: Arc<[SourceConfig]>,
+    pub scope: Scope,
+}
+
+#[derive(Clone, Debug)]
+pub struct BreakpadSymbolsActor {
+    breakpad: Arc<Cacher<FetchBreakpadSymbolsInternal>>,
+    objects: ObjectsActor,
+}
+
+impl BreakpadSymbolsActor {
+    pub fn new(cache: Cache, shared_cache: SharedCacheRef, objects: ObjectsActor) -> Self {
+        Self {
+            breakpad: Arc::new(Cacher::new(cache, shared_cache)),
+            objects,
+        }
+    }
+
+    /// Fetches the Breakpad symbols generated from the debug file with the given identifier.
+    ///
+    /// The debug file is selected like for symbolication. Its unwind information is included
+    /// if it has any, but is not looked up in separate code files.
+    pub async fn fetch(&self, request: FetchBreakpadSymbols) -> DerivedCache<ByteView<'static>> {
+        let found_object = self
+            .objects
+            .find(FindObject {
+                filetypes: FileType::from_object_type(request.identifier.object_type),
+                identifier: request.identifier,
+                sources: request.sources,
+                scope: request.scope,
+                purpose: ObjectPurpose::Debug,
+            })
+            .await;
+
+        derive_from_object_handle(found_object, CandidateStatus::Debug, |object_meta| {
+            let cache_key = object_meta.cache_key();
+            let request = FetchBreakpadSymbolsInternal {
+                objects_actor: self.objects.clone(),
+                object_meta,
+            };
+            self.breakpad.compute_memoized(request, cache_key)
+        })
+        .await
+    }
+
+    /// Removes the Breakpad symbols generated from the object file with the given cache key.
+    pub async fn purge(&self, cache_key: &CacheKey, shared_cache: bool) -> usize {
+        self.breakpad.purge(cache_key, shared_cache).await
+    }
+}
+
+#[derive(Clone, Debug)]
+struct FetchBreakpadSymbolsInternal {
+    /// The objects actor, used to fetch original DIF objects from.
+    objects_actor: ObjectsActor,
+
+    /// ObjectMeta handle of the original DIF object to fetch.
+    object_meta: Arc<ObjectMetaHandle>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn compute_breakpad_symbols(
+    temp_file: &mut NamedTempFile,
+    objects_actor: &ObjectsActor,
+    object_meta: Arc<ObjectMetaHandle>,
+) -> CacheEntry {
+    let object_handle = objects_actor.fetch(object_meta).await?;
+
+    write_breakpad_symbols(temp_file.as_file_mut(), &object_handle)
+}
+
+impl CacheItemRequest for FetchBreakpadSymbolsInternal {
+    type Item = ByteView<'static>;
+
+    const VERSIONS: CacheVersions = BREAKPAD_CACHE_VERSIONS;
+
+    fn compute<'a>(&'a self, temp_file: &'a mut NamedTempFile) -> BoxFuture<'a, CacheEntry> {
+        Box::pin(compute_breakpad_symbols(
+            temp_file,
+            &self.objects_actor,
+            self.object_meta.clone(),
+        ))
+    }
+
+    fn load(&self, data: ByteView<'static>) -> CacheEntry<Self::Item> {
+        Ok(data)
+    }
+
+    fn weight(item: &Self::Item) -> u32 {
+        item.len().try_into().unwrap_or(u32::MAX)
+    }
+}
+
+/// Writes the Breakpad symbols of the object file.
+///
+/// Breakpad symbol files are written as they are.
+#[tracing::instrument(skip_all)]
+fn write_breakpad_symbols(file: &mut File, object_handle: &ObjectHandle) -> CacheEntry {
+    object_handle.configure_scope();
+
+    tracing::debug!(
+        "Converting breakpad symbols for {}",
+        object_handle.cache_key
+    );
+
+    let mut writer = BufWriter::new(file);
+    match object_handle.object() {
+        Object::Breakpad(_) => writer.write_all(object_handle.data())?,
+        object => {
+            let object_id = &object_handle.object_id;
+            let name = object_id
+                .validated_debug_file_basename()
+                .or_else(|| object_id.validated_code_file_basename())
+                .unwrap_or("<unknown>");
+            write_symbol_file(&mut writer, object, name)?;
+        }
+    }
+
+    let file = writer.into_inner().map_err(io::Error::from)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Writes the `MODULE`, `INFO`, `FILE`, `FUNC`, `PUBLIC` and `STACK` records of an object.
+///
+/// Functions are written without their inlinees, and their line records point into
+/// the lines of inlined functions as well.
+fn write_symbol_file<W: Write>(writer: &mut W, object: &Object<'_>, name: &str) -> CacheEntry {
+    let malformed = |e: &(dyn std::error::Error + 'static)| {
+        tracing::error!(error = e, "Could not generate Breakpad symbols");
+        CacheError::Malformed(e.to_string())
+    };
+
+    let os = match object.file_format() {
+        FileFormat::Pe | FileFormat::Pdb => "windows",
+        FileFormat::MachO => "mac",
+        FileFormat::Elf => "Linux",
+        _ => "unknown",
+    };
+    writeln!(
+        writer,
+        "MODULE {os} {} {} {name}",
+        object.arch().name(),
+        object.debug_id().breakpad()
+    )?;
+    if let Some(code_id) = object.code_id() {
+        writeln!(writer, "INFO CODE_ID {}", code_id.as_str().to_uppercase())?;
+    }
+
+    // `FILE` records have to precede the `FUNC` records referring to them.
+    let mut files = FileTable::default();
+    let mut functions = Vec::new();
+    let mut function_addrs = BTreeSet::new();
+    let session = object.debug_session().map_err(|e| malformed(&e))?;
+    for function in session.functions() {
+        let function = function.map_err(|e| malformed(&e))?;
+        function_addrs.insert(function.address);
+        write_function(&mut functions, &mut files, &function)?;
+    }
+
+    for (index, path) in files.paths.iter().enumerate() {
+        writeln!(writer, "FILE {index} {path}")?;
+    }
+    writer.write_all(&functions)?;
+
+    for symbol in object.symbol_map().iter() {
+        if function_addrs.contains(&symbol.address) {
+            continue;
+        }
+        let Some(name) = symbol.name() else {
+            continue;
+        };
+        let name = Name::from(name);
+        let name = name.try_demangle(DEMANGLE_OPTIONS);
+        writeln!(writer, "PUBLIC {:x} 0 {name}", symbol.address)?;
+    }
+
+    if object.has_unwind_info() {
+        let mut cfi = Vec::new();
+        AsciiCfiWriter::new(&mut cfi)
+            .process(object)
+            .map_err(|e| malformed(&e))?;
+        // The module records were written above already.
+        for line in cfi.split_inclusive(|b| *b == b'\n') {
+            if line.starts_with(b"STACK ") {
+                writer.write_all(line)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the `FUNC` record of a function and its line records.
+fn write_function(
+    writer: &mut Vec<u8>,
+    files: &mut FileTable,
+    function: &Function<'_>,
+) -> io::Result<()> {
+    let name = function.name.try_demangle(DEMANGLE_OPTIONS);
+    writeln!(
+        writer,
+        "FUNC {:x} {:x} 0 {name}",
+        function.address, function.size
+    )?;
+
+    let compilation_dir = String::from_utf8_lossy(function.compilation_dir);
+    let end = function.end_address();
+    for (index, line) in function.lines.iter().enumerate() {
+        // Lines without a size extend up to the next line, or the end of the function.
+        let next = function
+            .lines
+            .get(index + 1)
+            .map_or(end, |next| next.address);
+        let size = line
+            .size
+            .unwrap_or_else(|| next.saturating_sub(line.address));
+        if size == 0 {
+            continue;
+        }
+
+        let path = join_path(&compilation_dir, &line.file.path_str());
+        let file = files.index(path);
+        writeln!(writer, "{:x} {size:x} {} {file}", line.address, line.line)?;
+    }
+
+    Ok(())
+}
+
+/// The files referred to by line records, numbered in order of appearance.
+#[derive(Debug, Default)]
+struct FileTable {
+    paths: Vec<String>,
+    indices: HashMap<String, usize>,
+}
+
+impl FileTable {
+    /// Returns the number of the file, adding it to the table if necessary.
+    fn index(&mut self, path: String) -> usize {
+        if let Some(index) = self.indices.get(&path) {
+            return *index;
+        }
+        let index = self.paths.len();
+        self.paths.push(path.clone());
+        self.indices.insert(path, index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_test::read_fixture;
+
+    use super::*;
+
+    #[test]
+    fn test_write_symbol_file() {
+        let data =
+            read_fixture("symbols/crash.pdb/3249D99D0C4049318610F4E4FB0B6936ffffffff/crash.pdb");
+        let object = Object::parse(&data).unwrap();
+
+        let mut symbols = Vec::new();
+        write_symbol_file(&mut symbols, &object, "crash.pdb").unwrap();
+        let symbols = String::from_utf8(symbols).unwrap();
+
+        assert_eq!(
+            symbols.lines().next(),
+            Some("MODULE windows x86 3249D99D0C4049318610F4E4FB0B69361 crash.pdb")
+        );
+        assert!(symbols
+            .lines()
+            .any(|line| line == "FUNC 2910 15e 0 main(int, char**)"));
+
+        // The generated symbols can be parsed again.
+        let parsed = Object::parse(symbols.as_bytes()).unwrap();
+        assert_eq!(parsed.file_format(), FileFormat::Breakpad);
+        assert_eq!(parsed.debug_id(), object.debug_id());
+        assert!(parsed.has_debug_info());
+    }
+}
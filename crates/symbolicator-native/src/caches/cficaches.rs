@@ -12,7 +12,8 @@ use symbolic::common::ByteView;
 use symbolic::debuginfo::breakpad::BreakpadModuleRecord;
 use symbolicator_service::caches::versions::CFICACHE_VERSIONS;
 use symbolicator_service::caching::{
-    Cache, CacheEntry, CacheError, CacheItemRequest, CacheVersions, Cacher, SharedCacheRef,
+    Cache, CacheEntry, CacheError, CacheItemRequest, CacheKey, CacheVersions, Cacher,
+    SharedCacheRef,
 };
 use symbolicator_service::objects::{
     CandidateStatus, FindObject, ObjectHandle, ObjectMetaHandle, ObjectPurpose, ObjectsActor,
@@ -166,6 +167,11 @@ impl CfiCacheActor {
         })
         .await
     }
+
+    /// Removes the CFI cache derived from the object file with the given cache key.
+    pub async fn purge(&self, cache_key: &CacheKey, shared_cache: bool) -> usize {
+        self.cficaches.purge(cache_key, shared_cache).await
+    }
 }
 
 /// Extracts the CFI from an object file, writing it to a CFI file.
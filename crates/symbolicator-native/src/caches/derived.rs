@@ -10,11 +10,13 @@ use symbolicator_service::objects::{
 ///
 /// This has the requested [`CacheEntry`], as well as [`AllObjectCandidates`] that were considered
 /// and the [`ObjectFeatures`] of the primarily used object file.
+/// `loose_match` marks object files which only loosely match the requested identifier.
 #[derive(Clone, Debug)]
 pub struct DerivedCache<T> {
     pub cache: CacheEntry<T>,
     pub candidates: AllObjectCandidates,
     pub features: ObjectFeatures,
+    pub loose_match: bool,
 }
 
 /// Derives a [`DerivedCache`] from the provided object handle and derive function.
@@ -26,6 +28,7 @@ pub async fn derive_from_object_handle<T, Derive, Fut>(
     FindResult {
         meta,
         mut candidates,
+        loose_match,
     }: FindResult,
     candidate_status: CandidateStatus,
     derive: Derive,
@@ -40,6 +43,7 @@ where
             cache: Err(CacheError::NotFound),
             candidates,
             features: ObjectFeatures::default(),
+            loose_match: false,
         };
     };
 
@@ -65,8 +69,10 @@ where
             let object_info = match &error {
                 CacheError::NotFound
                 | CacheError::PermissionDenied(_)
+                | CacheError::Blocked(_)
                 | CacheError::Timeout(_)
-                | CacheError::DownloadError(_) => {
+                | CacheError::DownloadError(_)
+                | CacheError::Unavailable(_) => {
                     // NOTE: all download related errors are already exposed as the candidates
                     // `ObjectDownloadInfo`. It is not necessary to duplicate that into the
                     // `ObjectUseInfo`.
@@ -87,5 +93,6 @@ where
         cache,
         candidates,
         features,
+        loose_match,
     }
 }
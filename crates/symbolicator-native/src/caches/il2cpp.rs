@@ -3,10 +3,13 @@
 //! This service downloads and caches the [`LineMapping`] used to map
 //! generated C++ source files back to the original C# sources.
 
+use std::collections::BTreeMap;
+use std::io::{Seek, Write};
 use std::sync::Arc;
 
 use futures::future::{self, BoxFuture};
 use sentry::{Hub, SentryFutureExt};
+use serde::Deserialize;
 
 use symbolic::common::{ByteView, DebugId};
 use symbolic::il2cpp::LineMapping;
@@ -61,17 +64,74 @@ impl FetchFileRequest {
         .await?;
 
         let view = ByteView::map_file_ref(temp_file.as_file())?;
+        let converted = convert_line_mapping(&view);
 
-        if LineMapping::parse(&view).is_none() {
+        if LineMapping::parse(converted.as_deref().unwrap_or(view.as_slice())).is_none() {
             metric!(counter("services.il2cpp.loaderrror") += 1);
             tracing::debug!("Failed to parse il2cpp");
             return Err(CacheError::Malformed("Failed to parse il2cpp".to_string()));
         }
 
+        // The cache holds the converted line mapping, which can be parsed directly.
+        if let Some(converted) = converted {
+            drop(view);
+            let file = temp_file.as_file_mut();
+            file.set_len(0)?;
+            file.rewind()?;
+            file.write_all(&converted)?;
+        }
+
         Ok(())
     }
 }
 
+/// The line mapping of newer Unity versions, which lists the lines of each generated method.
+#[derive(Debug, Deserialize)]
+struct MethodLineMappings {
+    methods: Vec<MethodLineMapping>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MethodLineMapping {
+    #[serde(alias = "cppPath")]
+    cpp_file: String,
+    #[serde(alias = "sourcePath")]
+    cs_file: String,
+    lines: Vec<MethodLine>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MethodLine {
+    cpp_line: u32,
+    #[serde(alias = "sourceLine")]
+    cs_line: u32,
+}
+
+/// Converts a per-method line mapping into the format read by [`LineMapping`].
+///
+/// Unity versions up to 2021 map each C++ file to the C# files and lines it was generated
+/// from, which is read as is and returns `None`. Unity 2022 lists the lines of every
+/// generated method instead, and Unity 6 renames some of the fields of these entries.
+fn convert_line_mapping(data: &[u8]) -> Option<Vec<u8>> {
+    let mappings: MethodLineMappings = serde_json::from_slice(data).ok()?;
+
+    let mut files = BTreeMap::<&str, BTreeMap<&str, BTreeMap<String, u32>>>::new();
+    for method in &mappings.methods {
+        let lines = files
+            .entry(&method.cpp_file)
+            .or_default()
+            .entry(&method.cs_file)
+            .or_default();
+        for line in &method.lines {
+            lines.insert(line.cpp_line.to_string(), line.cs_line);
+        }
+    }
+
+    serde_json::to_vec(&files).ok()
+}
+
 impl CacheItemRequest for FetchFileRequest {
     type Item = Il2cppHandle;
 
@@ -152,3 +212,45 @@ impl Il2cppService {
         all_results.into_iter().find_map(Result::ok)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_test::read_fixture;
+
+    use super::*;
+
+    const CPP_FILE: &str =
+        "/Users/unity/Game/Library/Bee/artifacts/iOS/il2cppOutput/cpp/Assembly-CSharp.cpp";
+    const CS_FILE: &str = "/Users/unity/Game/Assets/Scripts/Player.cs";
+
+    fn parse_fixture(path: &str) -> LineMapping {
+        let data = read_fixture(path);
+        let converted = convert_line_mapping(&data);
+        LineMapping::parse(converted.as_deref().unwrap_or(&data)).unwrap()
+    }
+
+    #[test]
+    fn test_line_mapping_unity_2021() {
+        let data = read_fixture("il2cpp/unity-2021.json");
+        assert!(convert_line_mapping(&data).is_none());
+
+        let mapping = parse_fixture("il2cpp/unity-2021.json");
+        assert_eq!(mapping.lookup(CPP_FILE, 21), Some((CS_FILE, 12)));
+        assert_eq!(mapping.lookup(CPP_FILE, 22), Some((CS_FILE, 13)));
+    }
+
+    #[test]
+    fn test_line_mapping_unity_2022() {
+        let mapping = parse_fixture("il2cpp/unity-2022.json");
+        assert_eq!(mapping.lookup(CPP_FILE, 21), Some((CS_FILE, 12)));
+        assert_eq!(mapping.lookup(CPP_FILE, 22), Some((CS_FILE, 13)));
+    }
+
+    #[test]
+    fn test_line_mapping_unity_6() {
+        // The lines of a file are merged from all of its methods.
+        let mapping = parse_fixture("il2cpp/unity-6.json");
+        assert_eq!(mapping.lookup(CPP_FILE, 21), Some((CS_FILE, 12)));
+        assert_eq!(mapping.lookup(CPP_FILE, 22), Some((CS_FILE, 13)));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod bitcode;
+pub mod breakpad;
 pub mod cficaches;
 pub mod derived;
 pub mod il2cpp;
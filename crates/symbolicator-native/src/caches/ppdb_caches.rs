@@ -10,7 +10,8 @@ use symbolic::debuginfo::Object;
 use symbolic::ppdb::{PortablePdbCache, PortablePdbCacheConverter};
 use symbolicator_service::caches::versions::PPDB_CACHE_VERSIONS;
 use symbolicator_service::caching::{
-    Cache, CacheEntry, CacheError, CacheItemRequest, CacheVersions, Cacher, SharedCacheRef,
+    Cache, CacheEntry, CacheError, CacheItemRequest, CacheKey, CacheVersions, Cacher,
+    SharedCacheRef,
 };
 use symbolicator_service::objects::{
     CandidateStatus, FindObject, ObjectHandle, ObjectMetaHandle, ObjectPurpose, ObjectsActor,
@@ -75,6 +76,11 @@ impl PortablePdbCacheActor {
         })
         .await
     }
+
+    /// Removes the Portable PDB cache derived from the object file with the given cache key.
+    pub async fn purge(&self, cache_key: &CacheKey, shared_cache: bool) -> usize {
+        self.ppdb_caches.purge(cache_key, shared_cache).await
+    }
 }
 
 #[derive(Clone, Debug)]
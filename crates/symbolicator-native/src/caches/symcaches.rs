@@ -8,19 +8,23 @@ use sentry::{Hub, SentryFutureExt};
 use tempfile::NamedTempFile;
 
 use symbolic::common::{ByteView, SelfCell};
+use symbolic::debuginfo::Object;
 use symbolic::symcache::{SymCache, SymCacheConverter};
 use symbolicator_service::caches::versions::SYMCACHE_VERSIONS;
 use symbolicator_service::caching::{
-    Cache, CacheEntry, CacheError, CacheItemRequest, CacheVersions, Cacher, SharedCacheRef,
+    Cache, CacheEntry, CacheError, CacheItemRequest, CacheKey, CacheVersions, Cacher,
+    SharedCacheRef,
 };
+use symbolicator_service::config::SymCacheShardingConfig;
 use symbolicator_service::objects::{
+    dwp_object_id, merge_go_pclntab, merge_mini_debuginfo, merge_split_dwarf, AllObjectCandidates,
     CandidateStatus, FindObject, ObjectHandle, ObjectMetaHandle, ObjectPurpose, ObjectsActor,
 };
 use symbolicator_service::types::Scope;
 use symbolicator_service::utils::sentry::ConfigureScope;
-use symbolicator_sources::{FileType, ObjectId, ObjectType, SourceConfig};
+use symbolicator_sources::{FileType, ObjectId, ObjectType, RemoteFile, SourceConfig};
 
-use super::bitcode::{BcSymbolMapHandle, BitcodeService};
+use super::bitcode::{BcSymbolMapHandle, BcSymbolMapLookup, BitcodeService};
 use super::derived::{derive_from_object_handle, DerivedCache};
 use super::il2cpp::{Il2cppHandle, Il2cppService};
 
@@ -32,6 +36,7 @@ pub struct SymCacheActor {
     objects: ObjectsActor,
     bitcode_svc: BitcodeService,
     il2cpp_svc: Il2cppService,
+    sharding: SymCacheShardingConfig,
 }
 
 impl SymCacheActor {
@@ -41,14 +46,61 @@ impl SymCacheActor {
         objects: ObjectsActor,
         bitcode_svc: BitcodeService,
         il2cpp_svc: Il2cppService,
+        sharding: SymCacheShardingConfig,
     ) -> Self {
         SymCacheActor {
             symcaches: Arc::new(Cacher::new(cache, shared_cache)),
             objects,
             bitcode_svc,
             il2cpp_svc,
+            sharding,
         }
     }
+
+    /// Returns the shard of a module's SymCache which covers the given relative address.
+    ///
+    /// Returns `None` if the SymCache of the module is not sharded.
+    pub fn shard_for(&self, image_size: Option<u64>, addr: u64) -> Option<SymCacheShard> {
+        shard_for(&self.sharding, image_size?, addr)
+    }
+}
+
+/// A range of addresses covered by the SymCache of a sharded module.
+///
+/// Addresses are relative to the image address of the module, like the addresses looked up in
+/// a SymCache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymCacheShard {
+    /// The first address in the shard.
+    pub start: u64,
+    /// The first address after the shard.
+    pub end: u64,
+}
+
+impl SymCacheShard {
+    /// Whether the given address is in this shard.
+    pub fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// Whether the range of `size` bytes at `start` overlaps this shard.
+    ///
+    /// Empty ranges are treated like a single byte.
+    fn overlaps(&self, start: u64, size: u64) -> bool {
+        start < self.end && start.saturating_add(size.max(1)) > self.start
+    }
+}
+
+fn shard_for(config: &SymCacheShardingConfig, image_size: u64, addr: u64) -> Option<SymCacheShard> {
+    if !config.enabled || config.shard_size == 0 || image_size < config.min_image_size {
+        return None;
+    }
+
+    let start = addr - addr % config.shard_size;
+    Some(SymCacheShard {
+        start,
+        end: start.saturating_add(config.shard_size),
+    })
 }
 
 #[derive(Clone, Debug)]
@@ -61,6 +113,9 @@ struct FetchSymCacheInternal {
 
     /// ObjectMeta handle of the original DIF object to fetch.
     object_meta: Arc<ObjectMetaHandle>,
+
+    /// The address range the SymCache is restricted to, if it is sharded.
+    shard: Option<SymCacheShard>,
 }
 
 #[tracing::instrument(name = "compute_symcache", skip_all)]
@@ -69,10 +124,16 @@ async fn compute_symcache(
     objects_actor: &ObjectsActor,
     object_meta: Arc<ObjectMetaHandle>,
     secondary_sources: &SecondarySymCacheSources,
+    shard: Option<SymCacheShard>,
 ) -> CacheEntry {
     let object_handle = objects_actor.fetch(object_meta).await?;
 
-    write_symcache(temp_file.as_file_mut(), &object_handle, secondary_sources)
+    write_symcache(
+        temp_file.as_file_mut(),
+        &object_handle,
+        secondary_sources,
+        shard,
+    )
 }
 
 impl CacheItemRequest for FetchSymCacheInternal {
@@ -86,6 +147,7 @@ impl CacheItemRequest for FetchSymCacheInternal {
             &self.objects_actor,
             self.object_meta.clone(),
             &self.secondary_sources,
+            self.shard,
         ))
     }
 
@@ -104,6 +166,11 @@ impl CacheItemRequest for FetchSymCacheInternal {
             })
         })
     }
+
+    fn weight(item: &Self::Item) -> u32 {
+        let size = item.owner().len().max(std::mem::size_of::<Self::Item>());
+        size.try_into().unwrap_or(u32::MAX)
+    }
 }
 
 /// Information for fetching the symbols for this symcache
@@ -113,6 +180,8 @@ pub struct FetchSymCache {
     pub identifier: ObjectId,
     pub sources: Arc<[SourceConfig]>,
     pub scope: Scope,
+    /// The address range to restrict the SymCache to, see [`SymCacheActor::shard_for`].
+    pub shard: Option<SymCacheShard>,
 }
 
 impl SymCacheActor {
@@ -128,90 +197,164 @@ impl SymCacheActor {
             })
             .await;
 
-        derive_from_object_handle(found_object, CandidateStatus::Debug, |handle| async move {
-            // TODO: while there is some caching *internally* in the bitcode_svc, the *complete*
-            // fetch request is not cached
-            let fetch_bcsymbolmap = async {
-                match handle.object_id().debug_id {
-                    Some(debug_id) => {
-                        self.bitcode_svc
-                            .fetch_bcsymbolmap(
-                                debug_id,
-                                handle.scope().clone(),
-                                request.sources.clone(),
-                            )
-                            .bind_hub(Hub::new_from_top(Hub::current()))
-                            .await
+        // The `BCSymbolMap` candidates are reported if hidden names remain unresolved.
+        let mut bcsymbolmap_candidates = AllObjectCandidates::default();
+        let unresolved_candidates = &mut bcsymbolmap_candidates;
+
+        let mut derived =
+            derive_from_object_handle(found_object, CandidateStatus::Debug, |handle| async move {
+                // TODO: while there is some caching *internally* in the bitcode_svc, the *complete*
+                // fetch request is not cached
+                let fetch_bcsymbolmap = async {
+                    match handle.object_id().debug_id {
+                        Some(debug_id) => {
+                            self.bitcode_svc
+                                .fetch_bcsymbolmap(
+                                    debug_id,
+                                    handle.scope().clone(),
+                                    request.sources.clone(),
+                                )
+                                .bind_hub(Hub::new_from_top(Hub::current()))
+                                .await
+                        }
+                        None => BcSymbolMapLookup::default(),
                     }
-                    None => None,
-                }
-            };
-
-            let fetch_il2cpp = async {
-                match handle.object_id().debug_id {
-                    Some(debug_id) => {
-                        tracing::trace!("Fetching line mapping");
-                        self.il2cpp_svc
-                            .fetch_line_mapping(
-                                handle.object_id(),
-                                debug_id,
-                                handle.scope().clone(),
-                                request.sources.clone(),
-                            )
+                };
+
+                let fetch_il2cpp = async {
+                    match handle.object_id().debug_id {
+                        Some(debug_id) => {
+                            tracing::trace!("Fetching line mapping");
+                            self.il2cpp_svc
+                                .fetch_line_mapping(
+                                    handle.object_id(),
+                                    debug_id,
+                                    handle.scope().clone(),
+                                    request.sources.clone(),
+                                )
+                                .bind_hub(Hub::new_from_top(Hub::current()))
+                                .await
+                        }
+                        None => None,
+                    }
+                };
+
+                let fetch_dwp = async {
+                    if handle.has_split_dwarf() {
+                        tracing::trace!("Fetching DWARF package");
+                        self.fetch_dwp(&handle, request.sources.clone())
                             .bind_hub(Hub::new_from_top(Hub::current()))
                             .await
+                    } else {
+                        None
                     }
-                    None => None,
-                }
-            };
+                };
 
-            let (bcsymbolmap_handle, il2cpp_handle) =
-                futures::future::join(fetch_bcsymbolmap, fetch_il2cpp).await;
+                let (bcsymbolmap, il2cpp_handle, dwp_handle) =
+                    futures::future::join3(fetch_bcsymbolmap, fetch_il2cpp, fetch_dwp).await;
+                let bcsymbolmap_handle = bcsymbolmap.handle;
+                *unresolved_candidates = bcsymbolmap.candidates;
 
-            let mut builder = handle.cache_key_builder();
-            if let Some(handle) = &bcsymbolmap_handle {
-                builder.write_str("\nbcsymbolmap:\n").unwrap();
-                builder.write_file_meta(&handle.file).unwrap();
-            }
-            if let Some(handle) = &il2cpp_handle {
-                builder.write_str("\nil2cpp:\n").unwrap();
-                builder.write_file_meta(&handle.file).unwrap();
-            }
+                let mut builder = handle.cache_key_builder();
+                if let Some(handle) = &bcsymbolmap_handle {
+                    builder.write_str("\nbcsymbolmap:\n").unwrap();
+                    builder.write_file_meta(&handle.file).unwrap();
+                }
+                if let Some(handle) = &il2cpp_handle {
+                    builder.write_str("\nil2cpp:\n").unwrap();
+                    builder.write_file_meta(&handle.file).unwrap();
+                }
+                if let Some(handle) = &dwp_handle {
+                    builder.write_str("\ndwp:\n").unwrap();
+                    builder.write_file_meta(&handle.file).unwrap();
+                }
+                if let Some(shard) = request.shard {
+                    write!(builder, "\nshard:\n{}-{}", shard.start, shard.end).unwrap();
+                }
 
-            let cache_key = builder.build();
+                let cache_key = builder.build();
+
+                let secondary_sources = SecondarySymCacheSources {
+                    bcsymbolmap_handle,
+                    il2cpp_handle,
+                    dwp_handle,
+                };
+
+                let request = FetchSymCacheInternal {
+                    objects_actor: self.objects.clone(),
+                    secondary_sources,
+                    object_meta: Arc::clone(&handle),
+                    shard: request.shard,
+                };
+                self.symcaches.compute_memoized(request, cache_key).await
+            })
+            .await;
 
-            let secondary_sources = SecondarySymCacheSources {
-                bcsymbolmap_handle,
-                il2cpp_handle,
-            };
+        derived.candidates.merge(&bcsymbolmap_candidates);
+        derived
+    }
+
+    /// Returns the DWARF package holding the split DWARF of the given object, if one is found.
+    async fn fetch_dwp(
+        &self,
+        handle: &ObjectMetaHandle,
+        sources: Arc<[SourceConfig]>,
+    ) -> Option<DwpHandle> {
+        let found_dwp = self
+            .objects
+            .find(FindObject {
+                filetypes: &[FileType::Dwp],
+                identifier: dwp_object_id(handle.object_id()),
+                sources,
+                scope: handle.scope().clone(),
+                purpose: ObjectPurpose::Debug,
+            })
+            .await;
 
-            let request = FetchSymCacheInternal {
-                objects_actor: self.objects.clone(),
-                secondary_sources,
-                object_meta: Arc::clone(&handle),
-            };
-            self.symcaches.compute_memoized(request, cache_key).await
+        let meta = found_dwp.meta?;
+        let object = self.objects.fetch(meta.handle.ok()?).await.ok()?;
+        Some(DwpHandle {
+            file: meta.file_source,
+            object,
         })
-        .await
+    }
+
+    /// Removes the SymCache derived from the object file with the given cache key.
+    ///
+    /// SymCaches which also include a `BCSymbolMap`, an IL2CPP line mapping or a DWARF package,
+    /// and the shards of sharded SymCaches have keys of their own, and are not removed.
+    pub async fn purge(&self, cache_key: &CacheKey, shared_cache: bool) -> usize {
+        self.symcaches.purge(cache_key, shared_cache).await
     }
 }
 
+/// Handle to a DWARF package holding the split DWARF of an object.
+#[derive(Clone, Debug)]
+struct DwpHandle {
+    file: RemoteFile,
+    object: Arc<ObjectHandle>,
+}
+
 /// Encapsulation of all the source artifacts that are being used to create SymCaches.
 #[derive(Clone, Debug, Default)]
 struct SecondarySymCacheSources {
     bcsymbolmap_handle: Option<BcSymbolMapHandle>,
     il2cpp_handle: Option<Il2cppHandle>,
+    dwp_handle: Option<DwpHandle>,
 }
 
 /// Computes and writes the symcache.
 ///
 /// It is assumed that the `object_handle` contains a positive cache.
 /// Any secondary source can only exist for a positive cache so does not have this issue.
+///
+/// With a `shard`, only the functions and symbols covering its addresses are written.
 #[tracing::instrument(skip_all)]
 fn write_symcache(
     file: &mut File,
     object_handle: &ObjectHandle,
     secondary_sources: &SecondarySymCacheSources,
+    shard: Option<SymCacheShard>,
 ) -> CacheEntry {
     object_handle.configure_scope();
 
@@ -259,12 +402,71 @@ fn write_symcache(
         converter.add_transformer(linemapping);
     }
 
-    converter.process_object(symbolic_object).map_err(|e| {
-        let dynerr: &dyn std::error::Error = &e; // tracing expects a `&dyn Error`
-        tracing::error!(error = dynerr, "Could not process SymCache");
+    let in_shard = |start, size| shard.is_none_or(|shard| shard.overlaps(start, size));
 
-        CacheError::Malformed(e.to_string())
-    })?;
+    match shard {
+        Some(shard) => {
+            tracing::debug!(
+                "Restricting symcache to {:#x}-{:#x}",
+                shard.start,
+                shard.end
+            );
+            process_object_shard(&mut converter, symbolic_object, shard)?;
+        }
+        None => converter.process_object(symbolic_object).map_err(|e| {
+            let dynerr: &dyn std::error::Error = &e; // tracing expects a `&dyn Error`
+            tracing::error!(error = dynerr, "Could not process SymCache");
+
+            CacheError::Malformed(e.to_string())
+        })?,
+    }
+
+    // Objects with split DWARF lack functions, which are merged in from their DWARF package.
+    if let Some(handle) = secondary_sources.dwp_handle.as_ref() {
+        tracing::debug!("Adding DWARF package to object {}", object_handle);
+        let merged = merge_split_dwarf(symbolic_object, handle.object.object(), |function| {
+            if in_shard(function.address, function.size) {
+                converter.process_symbolic_function(function)
+            }
+        });
+        if let Err(e) = merged {
+            let dynerr: &dyn std::error::Error = &e; // tracing expects a `&dyn Error`
+            tracing::warn!(error = dynerr, "Could not merge DWARF package");
+        }
+    }
+
+    // Stripped objects may still embed symbols, which fill the gaps between known functions.
+    // Symbols extend up to the next symbol, so the ones before a shard are kept.
+    let merged = merge_mini_debuginfo(symbolic_object, |symbol| {
+        if shard.is_none_or(|shard| symbol.address < shard.end) {
+            converter.process_symbolic_symbol(symbol)
+        }
+    });
+    match merged {
+        Ok(0) => {}
+        Ok(symbols) => tracing::debug!("Added {symbols} symbols from MiniDebugInfo"),
+        Err(e) => {
+            let dynerr: &dyn std::error::Error = &e; // tracing expects a `&dyn Error`
+            tracing::warn!(error = dynerr, "Could not read MiniDebugInfo");
+        }
+    }
+
+    // Without DWARF, the line tables of the Go runtime provide functions with lines.
+    if !symbolic_object.has_debug_info() {
+        let merged = merge_go_pclntab(symbolic_object, |function| {
+            if in_shard(function.address, function.size) {
+                converter.process_symbolic_function(function)
+            }
+        });
+        match merged {
+            Ok(0) => {}
+            Ok(functions) => tracing::debug!("Added {functions} functions from Go line table"),
+            Err(e) => {
+                let dynerr: &dyn std::error::Error = &e; // tracing expects a `&dyn Error`
+                tracing::warn!(error = dynerr, "Could not read Go line table");
+            }
+        }
+    }
 
     let mut writer = BufWriter::new(file);
     converter.serialize(&mut writer)?;
@@ -274,6 +476,50 @@ fn write_symcache(
     Ok(())
 }
 
+/// Processes the functions and symbols of an object which overlap the given shard.
+///
+/// This is the counterpart of [`SymCacheConverter::process_object`] for sharded SymCaches.
+/// Symbols extend up to the next symbol, so the last symbol before the shard is kept.
+fn process_object_shard(
+    converter: &mut SymCacheConverter,
+    object: &Object<'_>,
+    shard: SymCacheShard,
+) -> Result<(), CacheError> {
+    let malformed = |e: &(dyn std::error::Error + 'static)| {
+        tracing::error!(error = e, "Could not process SymCache");
+        CacheError::Malformed(e.to_string())
+    };
+
+    converter.set_arch(object.arch());
+    converter.set_debug_id(object.debug_id());
+
+    let session = object.debug_session().map_err(|e| malformed(&e))?;
+    for function in session.functions() {
+        let function = function.map_err(|e| malformed(&e))?;
+        if shard.overlaps(function.address, function.size) {
+            converter.process_symbolic_function(&function);
+        }
+    }
+
+    let symbol_map = object.symbol_map();
+    let mut previous = None;
+    let mut symbols = Vec::new();
+    for symbol in symbol_map.iter() {
+        if symbol.address < shard.start {
+            previous = Some(symbol);
+        } else if symbol.address < shard.end {
+            symbols.push(symbol);
+        } else {
+            break;
+        }
+    }
+    for symbol in previous.into_iter().chain(symbols) {
+        converter.process_symbolic_symbol(symbol);
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -313,11 +559,19 @@ mod tests {
             caches.objects,
             shared_cache.clone(),
             downloader.clone(),
+            Default::default(),
         );
         let bitcode = BitcodeService::new(caches.auxdifs, shared_cache.clone(), downloader.clone());
         let il2cpp = Il2cppService::new(caches.il2cpp, shared_cache.clone(), downloader);
 
-        SymCacheActor::new(caches.symcaches, shared_cache, objects, bitcode, il2cpp)
+        SymCacheActor::new(
+            caches.symcaches,
+            shared_cache,
+            objects,
+            bitcode,
+            il2cpp,
+            Default::default(),
+        )
     }
 
     /// Tests that a symcache is regenerated when it was created without a BcSymbolMap
@@ -373,6 +627,7 @@ mod tests {
             identifier,
             sources: Arc::new([source]),
             scope: Scope::Global,
+            shard: None,
         };
 
         let symcache_actor = symcache_actor(cache_dir.path().to_owned(), TIMEOUT).await;
@@ -437,4 +692,286 @@ mod tests {
         );
         assert_eq!(sl.function().name(), "-[SentryMessage initWithFormatted:]");
     }
+
+    /// Tests that the functions of an object with split DWARF are merged in from its DWARF
+    /// package, which is found next to the debug file.
+    #[tokio::test]
+    async fn test_symcache_split_dwarf() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let symbol_dir = test::tempdir();
+
+        // The object has the build id `fe4f51b335e68299e1342d12f4b4203acb0720a8`.
+        let object_dir = symbol_dir.path().join("fe");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::copy(
+            fixture("symbols/split-dwarf/split"),
+            object_dir.join("4f51b335e68299e1342d12f4b4203acb0720a8.debug"),
+        )
+        .unwrap();
+        fs::copy(
+            fixture("symbols/split-dwarf/split.dwp"),
+            object_dir.join("4f51b335e68299e1342d12f4b4203acb0720a8.dwp"),
+        )
+        .unwrap();
+
+        let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+            id: SourceId::new("local"),
+            path: symbol_dir.path().to_owned(),
+            files: CommonSourceConfig::with_layout(DirectoryLayoutType::Native),
+        }));
+
+        let fetch_symcache = FetchSymCache {
+            object_type: ObjectType::Elf,
+            identifier: ObjectId {
+                code_id: Some("fe4f51b335e68299e1342d12f4b4203acb0720a8".parse().unwrap()),
+                object_type: ObjectType::Elf,
+                ..Default::default()
+            },
+            sources: Arc::new([source]),
+            scope: Scope::Global,
+            shard: None,
+        };
+
+        let timeout = Duration::from_secs(60);
+        let symcache_actor = symcache_actor(cache_dir.path().to_owned(), timeout).await;
+        let symcache = symcache_actor.fetch(fetch_symcache).await.cache.unwrap();
+
+        // The skeleton unit only has the line table, the function is in the DWARF package.
+        let sl = symcache.get().lookup(0x1133).next().unwrap();
+        assert_eq!(sl.function().name(), "add");
+        assert_eq!(sl.file().unwrap().full_path(), "/tmp/sd/split.c");
+        assert_eq!(sl.line(), 2);
+    }
+
+    /// Tests that the symbols of a stripped object are read from its MiniDebugInfo.
+    #[tokio::test]
+    async fn test_symcache_mini_debuginfo() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let symbol_dir = test::tempdir();
+
+        // The object has the build id `7dcf0ee9592703014dee5153531d90b7b2cf605a`.
+        let object_dir = symbol_dir.path().join("7d");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::copy(
+            fixture("symbols/mini-debuginfo/mini"),
+            object_dir.join("cf0ee9592703014dee5153531d90b7b2cf605a"),
+        )
+        .unwrap();
+
+        let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+            id: SourceId::new("local"),
+            path: symbol_dir.path().to_owned(),
+            files: CommonSourceConfig::with_layout(DirectoryLayoutType::Native),
+        }));
+
+        let fetch_symcache = FetchSymCache {
+            object_type: ObjectType::Elf,
+            identifier: ObjectId {
+                code_id: Some("7dcf0ee9592703014dee5153531d90b7b2cf605a".parse().unwrap()),
+                object_type: ObjectType::Elf,
+                ..Default::default()
+            },
+            sources: Arc::new([source]),
+            scope: Scope::Global,
+            shard: None,
+        };
+
+        let timeout = Duration::from_secs(60);
+        let symcache_actor = symcache_actor(cache_dir.path().to_owned(), timeout).await;
+        let symcache = symcache_actor.fetch(fetch_symcache).await.cache.unwrap();
+
+        // `add` is neither in the stripped symbol table nor exported.
+        let sl = symcache.get().lookup(0x1129).next().unwrap();
+        assert_eq!(sl.function().name(), "add");
+    }
+
+    /// Tests that the debug file a stripped object refers to with its debug link is used.
+    #[tokio::test]
+    async fn test_symcache_debug_link() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let symbol_dir = test::tempdir();
+
+        // The object has the build id `7255ccc7006bb9c9f36449746025f585f3161e14`. Its debug file
+        // is not at the location of the build id, but next to the object.
+        let object_dir = symbol_dir.path().join("72");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::copy(
+            fixture("symbols/debug-link/link"),
+            object_dir.join("55ccc7006bb9c9f36449746025f585f3161e14"),
+        )
+        .unwrap();
+        fs::copy(
+            fixture("symbols/debug-link/link.debug"),
+            object_dir.join("link.debug"),
+        )
+        .unwrap();
+
+        let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+            id: SourceId::new("local"),
+            path: symbol_dir.path().to_owned(),
+            files: CommonSourceConfig::with_layout(DirectoryLayoutType::Native),
+        }));
+
+        let fetch_symcache = FetchSymCache {
+            object_type: ObjectType::Elf,
+            identifier: ObjectId {
+                code_id: Some("7255ccc7006bb9c9f36449746025f585f3161e14".parse().unwrap()),
+                object_type: ObjectType::Elf,
+                ..Default::default()
+            },
+            sources: Arc::new([source]),
+            scope: Scope::Global,
+            shard: None,
+        };
+
+        let timeout = Duration::from_secs(60);
+        let symcache_actor = symcache_actor(cache_dir.path().to_owned(), timeout).await;
+        let symcache = symcache_actor.fetch(fetch_symcache).await.cache.unwrap();
+
+        let sl = symcache.get().lookup(0x1129).next().unwrap();
+        assert_eq!(sl.function().name(), "add");
+        assert_eq!(sl.file().unwrap().full_path(), "/tmp/dl/link.c");
+        assert_eq!(sl.line(), 1);
+    }
+
+    /// Tests that stripped Go binaries are symbolicated from the line tables of the Go runtime.
+    #[tokio::test]
+    async fn test_symcache_go_pclntab() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let symbol_dir = test::tempdir();
+
+        // The object has the build id `1f391e931c1b72a3495c34db975fd27e67a80164`.
+        let object_dir = symbol_dir.path().join("1f");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::copy(
+            fixture("symbols/go-pclntab/gopcln"),
+            object_dir.join("391e931c1b72a3495c34db975fd27e67a80164"),
+        )
+        .unwrap();
+
+        let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+            id: SourceId::new("local"),
+            path: symbol_dir.path().to_owned(),
+            files: CommonSourceConfig::with_layout(DirectoryLayoutType::Native),
+        }));
+
+        let fetch_symcache = FetchSymCache {
+            object_type: ObjectType::Elf,
+            identifier: ObjectId {
+                code_id: Some("1f391e931c1b72a3495c34db975fd27e67a80164".parse().unwrap()),
+                object_type: ObjectType::Elf,
+                ..Default::default()
+            },
+            sources: Arc::new([source]),
+            scope: Scope::Global,
+            shard: None,
+        };
+
+        let timeout = Duration::from_secs(60);
+        let symcache_actor = symcache_actor(cache_dir.path().to_owned(), timeout).await;
+        let symcache = symcache_actor.fetch(fetch_symcache).await.cache.unwrap();
+
+        let sl = symcache.get().lookup(0x1130).next().unwrap();
+        assert_eq!(sl.function().name(), "main.add");
+        assert_eq!(sl.file().unwrap().full_path(), "/tmp/go/main.go");
+        assert_eq!(sl.line(), 4);
+    }
+
+    #[test]
+    fn test_shard_for() {
+        let config = SymCacheShardingConfig {
+            enabled: true,
+            min_image_size: 0x10000,
+            shard_size: 0x1000,
+        };
+
+        let shard = shard_for(&config, 0x10000, 0x1130).unwrap();
+        assert_eq!(
+            shard,
+            SymCacheShard {
+                start: 0x1000,
+                end: 0x2000
+            }
+        );
+        assert!(shard.contains(0x1fff));
+        assert!(!shard.contains(0x2000));
+
+        // Modules below the minimum image size are not sharded.
+        assert_eq!(shard_for(&config, 0xffff, 0x1130), None);
+
+        let config = SymCacheShardingConfig {
+            enabled: false,
+            ..config
+        };
+        assert_eq!(shard_for(&config, 0x10000, 0x1130), None);
+    }
+
+    /// Tests that a sharded SymCache only covers the functions in its shard.
+    #[tokio::test]
+    async fn test_symcache_shard() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let symbol_dir = test::tempdir();
+
+        let object_dir = symbol_dir.path().join("1f");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::copy(
+            fixture("symbols/go-pclntab/gopcln"),
+            object_dir.join("391e931c1b72a3495c34db975fd27e67a80164"),
+        )
+        .unwrap();
+
+        let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+            id: SourceId::new("local"),
+            path: symbol_dir.path().to_owned(),
+            files: CommonSourceConfig::with_layout(DirectoryLayoutType::Native),
+        }));
+
+        let fetch_symcache = |shard| FetchSymCache {
+            object_type: ObjectType::Elf,
+            identifier: ObjectId {
+                code_id: Some("1f391e931c1b72a3495c34db975fd27e67a80164".parse().unwrap()),
+                object_type: ObjectType::Elf,
+                ..Default::default()
+            },
+            sources: Arc::new([source.clone()]),
+            scope: Scope::Global,
+            shard: Some(shard),
+        };
+
+        let timeout = Duration::from_secs(60);
+        let symcache_actor = symcache_actor(cache_dir.path().to_owned(), timeout).await;
+
+        let shard = SymCacheShard {
+            start: 0x1000,
+            end: 0x2000,
+        };
+        let symcache = symcache_actor
+            .fetch(fetch_symcache(shard))
+            .await
+            .cache
+            .unwrap();
+        let sl = symcache.get().lookup(0x1130).next().unwrap();
+        assert_eq!(sl.function().name(), "main.add");
+
+        let shard = SymCacheShard {
+            start: 0x2000,
+            end: 0x3000,
+        };
+        let symcache = symcache_actor
+            .fetch(fetch_symcache(shard))
+            .await
+            .cache
+            .unwrap();
+        assert!(symcache.get().lookup(0x1130).next().is_none());
+    }
 }
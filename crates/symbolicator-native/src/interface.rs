@@ -9,12 +9,13 @@ use serde::de::{self, Deserializer};
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
 use symbolic::common::{Arch, CodeId, DebugId, Language};
+use symbolicator_service::config::UnwindingOptions;
 use symbolicator_service::objects::{AllObjectCandidates, ObjectFeatures};
 use symbolicator_service::types::{
     ObjectFileStatus, Platform, RawObjectInfo, Scope, ScrapingConfig,
 };
 use symbolicator_service::utils::hex::HexValue;
-use symbolicator_sources::SourceConfig;
+use symbolicator_sources::{ObjectId, SourceConfig};
 use thiserror::Error;
 
 pub use crate::metrics::StacktraceOrigin;
@@ -96,6 +97,14 @@ pub struct CompletedSymbolicationResponse {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub crash_reason: Option<String>,
 
+    /// If the process crashed accessing memory, the kind of memory region the address was in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crash_address: Option<CrashAddress>,
+
+    /// A heuristic rating of how likely the crash can be exploited, for crashed minidumps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exploitability: Option<Exploitability>,
+
     /// A detailed explanation of the crash, potentially in human readable form. This may
     /// include a string representation of the crash reason or application-specific info.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -106,6 +115,18 @@ pub struct CompletedSymbolicationResponse {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub assertion: Option<String>,
 
+    /// The metadata of the Windows Error Reporting archive the minidump was extracted from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wer_metadata: Option<BTreeMap<String, String>>,
+
+    /// The handles, module versions and drivers of the process, if requested for a minidump.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_resources: Option<ProcessResources>,
+
+    /// Problems with a minidump which was processed in best-effort mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<MinidumpWarning>,
+
     /// The threads containing symbolicated stack frames.
     pub stacktraces: Vec<CompleteStacktrace>,
 
@@ -113,6 +134,205 @@ pub struct CompletedSymbolicationResponse {
     pub modules: Vec<CompleteObjectInfo>,
 }
 
+/// An analysis of the address at which a process crashed.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CrashAddress {
+    /// The address which caused the crash.
+    pub address: HexValue,
+
+    /// The kind of memory region the address is in.
+    pub region: MemoryRegion,
+
+    /// The code file of the module containing the address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+
+    /// The offset of the address within its module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<HexValue>,
+}
+
+/// A problem with a truncated or corrupt minidump, which was processed nonetheless.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct MinidumpWarning {
+    /// The kind of data which is missing.
+    pub kind: MinidumpWarningKind,
+
+    /// The type of the stream which is affected, such as `3` for the thread list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_type: Option<u32>,
+
+    /// A description of the problem.
+    pub message: String,
+}
+
+/// The kind of a [`MinidumpWarning`].
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MinidumpWarningKind {
+    /// A stream, or the memory it points to, extends beyond the end of the file, so its data is
+    /// incomplete.
+    TruncatedStream,
+    /// The threads could not be stackwalked as a whole, so the response only has the threads
+    /// which could be read from the file.
+    MissingThreads,
+    /// The module list could not be read, so the response has no modules.
+    MissingModules,
+}
+
+/// The resources of a process, as recorded in full Windows minidumps.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ProcessResources {
+    /// The handles which the process had open.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub handles: Vec<HandleInfo>,
+
+    /// The file versions of all modules of the process which have a version resource.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub module_versions: Vec<ModuleVersion>,
+
+    /// The kernel drivers among the modules of the process.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drivers: Vec<ModuleVersion>,
+}
+
+/// A handle which a process had open.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct HandleInfo {
+    /// The value of the handle.
+    pub handle: HexValue,
+
+    /// The type of the object, such as `Mutant`, `Event` or `File`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+
+    /// The name of the object, such as the path of a file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_name: Option<String>,
+
+    /// The access rights granted for the handle.
+    pub granted_access: HexValue,
+
+    /// The number of handles to the object.
+    pub handle_count: u32,
+
+    /// The number of references to the object.
+    pub pointer_count: u32,
+}
+
+/// The file version of a module.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ModuleVersion {
+    /// The code file of the module.
+    pub code_file: String,
+
+    /// The address at which the module was loaded.
+    pub image_addr: HexValue,
+
+    /// The file version of the module, such as `10.0.19041.1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// The kind of memory region containing a crash address.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryRegion {
+    /// The first pages of the address space, usually accessed by dereferencing null pointers.
+    NullPage,
+    /// The guard pages below a stack, usually accessed when the stack overflows.
+    StackGuard,
+    /// The stack of a thread.
+    Stack,
+    /// Memory allocated by the process, such as the heap.
+    Heap,
+    /// The image of a loaded module.
+    Module,
+    /// A memory-mapped file which is not a module.
+    Mapped,
+    /// Memory that is not mapped into the process.
+    Unmapped,
+    /// The minidump does not contain enough information to classify the address.
+    Unknown,
+}
+
+/// A heuristic rating of how likely a crash can be exploited, along with the reasons for it.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Exploitability {
+    /// The rating of the most severe reason.
+    pub rating: ExploitabilityRating,
+
+    /// The observations about the crash which the rating is based on.
+    pub reasons: Vec<ExploitabilityReason>,
+}
+
+/// How likely a crash can be exploited.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ExploitabilityRating {
+    /// The crash was caused deliberately, for example by an assertion.
+    None,
+    /// The crash is unlikely to be exploitable, such as a null pointer dereference.
+    Low,
+    /// The crash points at memory corruption, which may be exploitable.
+    Medium,
+    /// The crash indicates control over memory writes or the control flow.
+    High,
+}
+
+/// An observation about a crash which affects its [`ExploitabilityRating`].
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExploitabilityReason {
+    /// The process aborted itself, for example after a failed assertion.
+    Abort,
+    /// An arithmetic error such as a division by zero.
+    Arithmetic,
+    /// A breakpoint or trap instruction was hit.
+    Breakpoint,
+    /// Memory in the null page was accessed.
+    NullDereference,
+    /// The stack overflowed into its guard pages.
+    StackOverflow,
+    /// Invalid memory was read.
+    InvalidRead,
+    /// Invalid memory was accessed, without knowing whether it was read or written.
+    BadAccess,
+    /// An invalid or privileged instruction was executed.
+    IllegalInstruction,
+    /// The heap manager detected a corrupted heap.
+    HeapCorruption,
+    /// A stack buffer overrun was detected, for example by a stack cookie check.
+    StackBufferOverrun,
+    /// Invalid memory was written.
+    InvalidWrite,
+    /// The crash happened executing non-executable or invalid memory.
+    InvalidExecute,
+    /// The instruction pointer of the crashing frame is outside of all modules.
+    InstructionPointerOutsideModules,
+    /// A return address recovered from unwind information is outside of all modules.
+    CorruptedReturnAddress,
+}
+
+impl ExploitabilityReason {
+    /// Returns the rating that this reason implies on its own.
+    pub fn rating(self) -> ExploitabilityRating {
+        match self {
+            Self::Abort | Self::Arithmetic | Self::Breakpoint => ExploitabilityRating::None,
+            Self::NullDereference | Self::StackOverflow => ExploitabilityRating::Low,
+            Self::InvalidRead
+            | Self::BadAccess
+            | Self::IllegalInstruction
+            | Self::HeapCorruption
+            | Self::StackBufferOverrun => ExploitabilityRating::Medium,
+            Self::InvalidWrite
+            | Self::InvalidExecute
+            | Self::InstructionPointerOutsideModules
+            | Self::CorruptedReturnAddress => ExploitabilityRating::High,
+        }
+    }
+}
+
 /// OS-specific crash signal value.
 // TODO(markus): Also accept POSIX signal name as defined in signal.h
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
@@ -151,6 +371,11 @@ pub struct SymbolicatedFrame {
     ///     this might occur within recursion, so the instruction address is not a good
     pub original_index: Option<usize>,
 
+    /// Whether this frame was symbolicated with a debug file that only loosely matches its
+    /// module, and may therefore be inexact.
+    #[serde(default, skip_serializing_if = "is_default_value")]
+    pub inexact: bool,
+
     #[serde(flatten)]
     pub raw: RawFrame,
 }
@@ -176,17 +401,57 @@ pub struct CompleteStacktrace {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_requesting: Option<bool>,
 
+    /// The state of the thread, if the minidump records any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_state: Option<ThreadState>,
+
     /// Registers, only useful when returning a processed minidump.
     #[serde(default, skip_serializing_if = "Registers::is_empty")]
     pub registers: Registers,
 
     /// Frames of this stack trace.
     pub frames: Vec<SymbolicatedFrame>,
+
+    /// The logical callers of the innermost Swift async function of this stack trace.
+    ///
+    /// See [`RawStacktrace::async_frames`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub async_frames: Vec<SymbolicatedFrame>,
 }
 
 /// A map of register values.
 pub type Registers = BTreeMap<String, HexValue>;
 
+/// The platform-specific state of a thread at the time a minidump was written.
+///
+/// Values which are not set by the platform are omitted.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ThreadState {
+    /// How often the thread has been suspended.
+    #[serde(default, skip_serializing_if = "is_default_value")]
+    pub suspend_count: u32,
+
+    /// The priority class of the process on Windows, such as `0x20` for normal priority.
+    #[serde(default, skip_serializing_if = "is_default_value")]
+    pub priority_class: u32,
+
+    /// The priority of the thread relative to its priority class.
+    #[serde(default, skip_serializing_if = "is_default_value")]
+    pub priority: u32,
+
+    /// The address of the thread environment block (TEB) on Windows.
+    ///
+    /// Minidumps of other platforms may store the address of thread-local data here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teb: Option<HexValue>,
+
+    /// The last error value of the thread on Windows, as returned by `GetLastError`.
+    ///
+    /// This is only known if the minidump contains the memory of the thread environment block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<u32>,
+}
+
 fn is_default_value<T: Default + PartialEq>(value: &T) -> bool {
     *value == T::default()
 }
@@ -296,6 +561,51 @@ pub struct RawFrame {
     /// Information about how the raw frame was created.
     #[serde(default, skip_serializing_if = "is_default_value")]
     pub trust: FrameTrust,
+
+    /// The registers recovered for this frame during stackwalking.
+    ///
+    /// Only set if requested with [`StackwalkOptions::frame_registers`].
+    #[serde(default, skip_serializing_if = "Registers::is_empty")]
+    pub registers: Registers,
+
+    /// The tag in the top byte of the original instruction address.
+    ///
+    /// Pointers on devices with memory tagging (MTE) carry a tag in their top byte, which is
+    /// removed from [`instruction_addr`](Self::instruction_addr) so that it falls into its
+    /// module. The memory tag itself is stored in the lower four bits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_tag: Option<u8>,
+
+    /// The arguments and local variables of the function, recovered during stackwalking.
+    ///
+    /// Only set if requested with [`StackwalkOptions::frame_variables`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<FrameVariable>,
+}
+
+/// An argument or local variable of the function of a frame.
+///
+/// Variables are recovered from the DWARF debug information of modules, by evaluating their
+/// locations against the registers and memory captured in a minidump.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FrameVariable {
+    /// The name of the variable.
+    pub name: String,
+    /// Whether the variable is an argument or a local variable.
+    pub kind: VariableKind,
+    /// The name of the type of the variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+    /// The value of the variable, or `None` if it was not live or could not be recovered.
+    pub value: Option<HexValue>,
+}
+
+/// Whether a [`FrameVariable`] is an argument or a local variable.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableKind {
+    Argument,
+    Local,
 }
 
 /// How trustworth the instruction pointer of the frame is.
@@ -357,6 +667,10 @@ pub struct RawStacktrace {
     #[serde(default)]
     pub is_requesting: Option<bool>,
 
+    /// The platform-specific state of the thread.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_state: Option<ThreadState>,
+
     /// Values of CPU registers in the top frame in the trace.
     #[serde(default)]
     pub registers: Registers,
@@ -365,6 +679,14 @@ pub struct RawStacktrace {
     ///
     /// The first entry in the list is the active frame, with its callers below.
     pub frames: Vec<RawFrame>,
+
+    /// The logical callers of the innermost Swift async function of this stack trace.
+    ///
+    /// Swift async functions do not return to their callers on the stack, but resume them through
+    /// the continuations of their async contexts. These frames are the resume functions of that
+    /// chain of continuations, and logically follow the async function in [`frames`](Self::frames).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub async_frames: Vec<RawFrame>,
 }
 /// Normalized [`RawObjectInfo`] with status attached.
 ///
@@ -384,6 +706,20 @@ pub struct CompleteObjectInfo {
     /// Features available during symbolication.
     pub features: ObjectFeatures,
 
+    /// Whether the object was symbolicated from its symbol table alone.
+    ///
+    /// This is the case if no file with debug info was found. Frames of such objects have
+    /// function names, but no file names, line numbers or inline frames.
+    #[serde(default, skip_serializing_if = "is_default_value")]
+    pub symbols_only: bool,
+
+    /// Whether the debug file of the object only loosely matches its identifier.
+    ///
+    /// This is the case for PDBs with the same GUID, but a different age, which are found on
+    /// sources with `loose_matching` enabled.
+    #[serde(default, skip_serializing_if = "is_default_value")]
+    pub loose_match: bool,
+
     /// Actual architecture of this debug file.
     pub arch: Arch,
 
@@ -404,6 +740,397 @@ pub struct CompleteObjectInfo {
     pub candidates: AllObjectCandidates,
 }
 
+/// A request for symbolication of a MetricKit diagnostic payload.
+#[derive(Debug, Clone)]
+pub struct SymbolicateMetricKitPayload {
+    /// The event's platform.
+    pub platform: Option<Platform>,
+    /// The scope of this request which determines access to cached files.
+    pub scope: Scope,
+    /// A list of external sources to load debug files.
+    pub sources: Arc<[SourceConfig]>,
+    /// The diagnostic payload to symbolicate.
+    pub payload: MetricKitPayload,
+    /// Whether to apply source context for the stack frames.
+    pub apply_source_context: bool,
+    /// Scraping configuration controling authenticated requests.
+    pub scraping: ScrapingConfig,
+}
+
+/// A diagnostic payload delivered by MetricKit.
+///
+/// This is the JSON representation of an `MXDiagnosticPayload`, of which only the call stack
+/// trees and metadata of the diagnostics are used.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricKitPayload {
+    #[serde(default)]
+    pub crash_diagnostics: Vec<MetricKitDiagnostic>,
+    #[serde(default)]
+    pub hang_diagnostics: Vec<MetricKitDiagnostic>,
+    #[serde(default)]
+    pub cpu_exception_diagnostics: Vec<MetricKitDiagnostic>,
+    #[serde(default)]
+    pub disk_write_exception_diagnostics: Vec<MetricKitDiagnostic>,
+    #[serde(default)]
+    pub app_launch_diagnostics: Vec<MetricKitDiagnostic>,
+}
+
+/// A single diagnostic of a MetricKit payload.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricKitDiagnostic {
+    /// The call stacks captured for the diagnostic.
+    pub call_stack_tree: MetricKitCallStackTree,
+    /// Metadata about the diagnostic and the device, such as the OS version or the signal.
+    #[serde(default)]
+    pub diagnostic_meta_data: BTreeMap<String, serde_json::Value>,
+}
+
+/// The call stacks of a MetricKit diagnostic.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricKitCallStackTree {
+    /// Whether there is one call stack per thread, rather than call stacks aggregated from
+    /// samples.
+    #[serde(default)]
+    pub call_stack_per_thread: bool,
+    #[serde(default)]
+    pub call_stacks: Vec<MetricKitCallStack>,
+}
+
+/// A call stack of a MetricKit diagnostic.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricKitCallStack {
+    /// Whether this call stack is attributed to the diagnostic, such as the crashed thread.
+    #[serde(default)]
+    pub thread_attributed: bool,
+    #[serde(default)]
+    pub call_stack_root_frames: Vec<MetricKitFrame>,
+}
+
+/// A frame in the call stack tree of a MetricKit diagnostic.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricKitFrame {
+    /// The UUID of the binary containing the frame.
+    #[serde(default, rename = "binaryUUID")]
+    pub binary_uuid: Option<String>,
+    /// The name of the binary containing the frame.
+    #[serde(default)]
+    pub binary_name: Option<String>,
+    /// The absolute address of the frame.
+    pub address: u64,
+    /// The offset of the address into the text segment of the binary.
+    #[serde(default)]
+    pub offset_into_binary_text_segment: u64,
+    /// The number of samples in which this frame was seen.
+    #[serde(default)]
+    pub sample_count: u64,
+    /// The frames below this one in the tree.
+    #[serde(default)]
+    pub sub_frames: Vec<MetricKitFrame>,
+}
+
+/// The response to a MetricKit symbolication request.
+///
+/// This contains the call stack trees of all diagnostics, with symbolicated stack frames
+/// added to each node.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct CompletedMetricKitResponse {
+    /// The symbolicated diagnostics, ordered by their type.
+    pub diagnostics: Vec<SymbolicatedMetricKitDiagnostic>,
+
+    /// A list of images, extended with status information.
+    pub modules: Vec<CompleteObjectInfo>,
+}
+
+/// The kind of diagnostic reported by MetricKit.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKitDiagnosticType {
+    Crash,
+    Hang,
+    CpuException,
+    DiskWriteException,
+    AppLaunch,
+}
+
+/// A diagnostic of a MetricKit payload with a symbolicated call stack tree.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SymbolicatedMetricKitDiagnostic {
+    /// The kind of diagnostic.
+    #[serde(rename = "type")]
+    pub ty: MetricKitDiagnosticType,
+    /// The `diagnosticMetaData` of the diagnostic, as it was sent.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    /// Whether there is one call stack per thread, rather than call stacks aggregated from
+    /// samples.
+    pub call_stack_per_thread: bool,
+    /// The symbolicated call stacks.
+    pub call_stacks: Vec<SymbolicatedMetricKitCallStack>,
+}
+
+/// A symbolicated call stack of a MetricKit diagnostic.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct SymbolicatedMetricKitCallStack {
+    /// Whether this call stack is attributed to the diagnostic, such as the crashed thread.
+    pub thread_attributed: bool,
+    /// The root frames of the call stack tree.
+    pub root_frames: Vec<SymbolicatedMetricKitFrame>,
+}
+
+/// A node of a MetricKit call stack tree, along with its symbolicated stack frames.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct SymbolicatedMetricKitFrame {
+    /// The UUID of the binary containing the frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_uuid: Option<String>,
+    /// The name of the binary containing the frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_name: Option<String>,
+    /// The absolute address of the frame.
+    pub address: HexValue,
+    /// The offset of the address into the text segment of the binary.
+    pub offset_into_binary_text_segment: HexValue,
+    /// The number of samples in which this frame was seen.
+    pub sample_count: u64,
+    /// The symbolicated stack frames at this address.
+    ///
+    /// There is more than one frame if functions were inlined at the address. The first
+    /// entry is the innermost inlined function.
+    pub frames: Vec<SymbolicatedFrame>,
+    /// The frames below this one in the tree.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_frames: Vec<SymbolicatedMetricKitFrame>,
+}
+
+/// A request to stackwalk and symbolicate threads from their raw stack memory.
+#[derive(Debug, Clone)]
+pub struct StackwalkStackMemory {
+    /// The event's platform.
+    pub platform: Option<Platform>,
+    /// The scope of this request which determines access to cached files.
+    pub scope: Scope,
+    /// A list of external sources to load debug files.
+    pub sources: Arc<[SourceConfig]>,
+    /// The threads and modules of the process.
+    pub process: RawProcess,
+    /// Scraping configuration controling authenticated requests.
+    pub scraping: ScrapingConfig,
+    /// Options controlling the stackwalking of the threads.
+    pub options: StackwalkOptions,
+}
+
+/// Options controlling how the threads of a minidump are stackwalked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StackwalkOptions {
+    /// Overrides the unwinding options configured for the scope.
+    pub unwinding: Option<UnwindingOptions>,
+    /// Whether the registers recovered for every frame are added to the frames.
+    pub frame_registers: bool,
+    /// The number of top frames of the crashing thread whose variables are recovered.
+    ///
+    /// Defaults to `0`, which does not recover any variables.
+    pub frame_variables: usize,
+    /// Whether the handles, module versions and drivers of the process are added to the response.
+    pub process_resources: bool,
+    /// Whether truncated and corrupt minidumps are processed as far as possible.
+    ///
+    /// Instead of failing the request, the response contains whatever could be read, along
+    /// with warnings about the missing data.
+    pub best_effort: bool,
+}
+
+/// Options controlling how the function names of symbolicated frames are demangled.
+///
+/// Frames are demangled with the default options during symbolication. Other options are applied
+/// to the completed response with [`CompletedSymbolicationResponse::apply_demangling`].
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DemanglingOptions {
+    /// The language of symbols which do not have one.
+    ///
+    /// Symbols from debug files usually have a language. Otherwise, the language is detected
+    /// from the mangled name, unless this hint is set.
+    pub language: Option<Language>,
+    /// How much of the mangled information is kept.
+    pub verbosity: DemanglingVerbosity,
+    /// Whether the hash of Rust symbols is kept, like `::h0123456789abcdef`.
+    ///
+    /// Only Rust symbols in the legacy mangling scheme have a trailing hash.
+    pub keep_hash: bool,
+}
+
+/// How much of the mangled information is kept in demangled names.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DemanglingVerbosity {
+    /// Names with their parameters, template arguments and return types.
+    Full,
+    /// Names with their parameters and template arguments.
+    #[default]
+    Standard,
+    /// Names without parameters or template arguments.
+    Compact,
+}
+
+/// The state of a process, given by the registers and stack memory of its threads.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RawProcess {
+    /// The CPU architecture of the process.
+    pub arch: Arch,
+    /// The operating system the process ran on.
+    pub os: ProcessOs,
+    /// The threads of the process.
+    pub threads: Vec<RawThread>,
+    /// The modules which were loaded into the process.
+    #[serde(default)]
+    pub modules: Vec<RawObjectInfo>,
+    /// The exception which caused a crash, if the process crashed.
+    #[serde(default)]
+    pub exception: Option<RawException>,
+}
+
+/// The operating systems of processes which can be stackwalked from their stack memory.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessOs {
+    Windows,
+    Macos,
+    Ios,
+    Linux,
+    Android,
+}
+
+/// A thread of a process, with its registers and stack memory.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RawThread {
+    /// The OS-dependent identifier of the thread.
+    pub thread_id: u32,
+    /// Values of CPU registers when the thread was suspended.
+    #[serde(default)]
+    pub registers: Registers,
+    /// The address of the start of the stack memory, which defaults to the stack pointer.
+    #[serde(default)]
+    pub stack_start: Option<HexValue>,
+    /// The contents of the stack memory, which is uploaded separately.
+    #[serde(skip)]
+    pub stack_memory: Vec<u8>,
+}
+
+/// The exception which caused a process to crash.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RawException {
+    /// The identifier of the crashed thread.
+    pub thread_id: u32,
+    /// The OS-dependent exception code, such as the signal number.
+    pub code: u32,
+    /// The address which caused the exception, which defaults to the instruction pointer.
+    #[serde(default)]
+    pub address: Option<HexValue>,
+}
+
+/// A request to prefetch the caches of a single module.
+#[derive(Debug, Clone)]
+pub struct PrefetchModule {
+    /// The scope which determines access to cached files.
+    pub scope: Scope,
+    /// A list of external sources to load debug files.
+    pub sources: Arc<[SourceConfig]>,
+    /// The module to prefetch.
+    pub module: RawObjectInfo,
+}
+
+/// The outcome of prefetching the caches of a module.
+#[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq, Deserialize)]
+pub struct PrefetchedModule {
+    /// Status for fetching the file with debug info.
+    pub debug_status: ObjectFileStatus,
+
+    /// Status for fetching the file with unwind info.
+    ///
+    /// This is `None` for modules without unwind info, like .NET assemblies.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub unwind_status: Option<ObjectFileStatus>,
+}
+
+/// A request to purge all cached files of a debug file.
+#[derive(Debug, Clone)]
+pub struct PurgeCaches {
+    /// The scope of the cached files from private sources.
+    pub scope: Scope,
+    /// The sources on which the debug file is looked up.
+    pub sources: Arc<[SourceConfig]>,
+    /// The identifiers of the debug file.
+    pub identifier: ObjectId,
+    /// Whether the shared cache is purged as well.
+    pub shared_cache: bool,
+}
+
+/// The number of removed entries of every cache, see [`PurgeCaches`].
+#[derive(Debug, Clone, Copy, Default, Serialize, Eq, PartialEq, Deserialize)]
+pub struct PurgedCaches {
+    pub object_meta: usize,
+    pub objects: usize,
+    pub symcaches: usize,
+    pub cficaches: usize,
+    pub ppdb_caches: usize,
+    pub breakpad: usize,
+}
+
+/// A request to inspect the features of a debug file.
+#[derive(Debug, Clone)]
+pub struct InspectObject {
+    /// The scope of the cached files from private sources.
+    pub scope: Scope,
+    /// The sources on which the debug file is looked up.
+    pub sources: Arc<[SourceConfig]>,
+    /// The identifiers of the debug file.
+    pub identifier: ObjectId,
+}
+
+/// A request to generate the Breakpad symbols of a debug file.
+#[derive(Debug, Clone)]
+pub struct DumpBreakpadSymbols {
+    /// The scope of the cached files from private sources.
+    pub scope: Scope,
+    /// The sources on which the debug file is looked up.
+    pub sources: Arc<[SourceConfig]>,
+    /// The identifiers of the debug file.
+    pub identifier: ObjectId,
+}
+
+/// The parsed features of a debug file, see [`InspectObject`].
+///
+/// This describes the file that would be used for symbolication. All other files found on the
+/// sources are listed in the `candidates`, along with their features.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InspectedObject {
+    /// Status for fetching the debug file.
+    pub status: ObjectFileStatus,
+
+    /// Features available in the debug file.
+    pub features: ObjectFeatures,
+
+    /// Actual architecture of the debug file.
+    pub arch: Arch,
+
+    /// The file format of the debug file, like `macho` or `pdb`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_format: Option<String>,
+
+    /// The size of the debug file in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+
+    /// The files which were considered on the sources.
+    #[serde(default, skip_serializing_if = "AllObjectCandidates::is_empty")]
+    pub candidates: AllObjectCandidates,
+}
+
 impl CompleteObjectInfo {
     /// Given an absolute address converts it into a relative one.
     ///
@@ -454,6 +1181,8 @@ impl From<RawObjectInfo> for CompleteObjectInfo {
             debug_status: ObjectFileStatus::Unused,
             unwind_status: None,
             features: ObjectFeatures::default(),
+            symbols_only: false,
+            loose_match: false,
             arch: Arch::Unknown,
             raw,
             candidates: AllObjectCandidates::default(),
@@ -17,6 +17,8 @@ pub enum StacktraceOrigin {
     Minidump,
     /// The stack traces came from an Apple Crash Report.
     AppleCrashReport,
+    /// The stack traces came from a MetricKit diagnostic payload.
+    MetricKit,
 }
 
 impl std::fmt::Display for StacktraceOrigin {
@@ -25,6 +27,7 @@ impl std::fmt::Display for StacktraceOrigin {
             StacktraceOrigin::Symbolicate => "symbolicate",
             StacktraceOrigin::Minidump => "minidump",
             StacktraceOrigin::AppleCrashReport => "applecrashreport",
+            StacktraceOrigin::MetricKit => "metrickit",
         })
     }
 }
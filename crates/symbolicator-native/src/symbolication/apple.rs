@@ -1,4 +1,5 @@
 use std::fs::File;
+use std::io::Read;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
@@ -17,63 +18,41 @@ use crate::interface::{
 };
 use crate::metrics::StacktraceOrigin;
 
+use super::ips::{is_ips_crash_report, parse_ips_crash_report};
+use super::pointer_auth::strip_pointer_auth;
 use super::symbolicate::SymbolicationActor;
 
+/// The modules, threads and metadata of a parsed crash report.
+pub(super) type ParsedCrashReport = (
+    Vec<CompleteObjectInfo>,
+    Vec<RawStacktrace>,
+    AppleCrashReportState,
+);
+
 impl SymbolicationActor {
     #[tracing::instrument(skip_all)]
     fn parse_apple_crash_report(
         &self,
         platform: Option<Platform>,
         scope: Scope,
-        report: File,
+        mut report: File,
         sources: Arc<[SourceConfig]>,
         scraping: ScrapingConfig,
     ) -> Result<(SymbolicateStacktraces, AppleCrashReportState)> {
-        let report =
-            AppleCrashReport::from_reader(report).context("failed to parse apple crash report")?;
-        let mut metadata = report.metadata;
-
-        let arch = report
-            .code_type
-            .as_ref()
-            .and_then(|code_type| code_type.split(' ').next())
-            .and_then(|word| word.parse().ok())
-            .unwrap_or_default();
+        let mut data = Vec::new();
+        report.read_to_end(&mut data)?;
 
-        let modules = report
-            .binary_images
-            .into_iter()
-            .map(map_apple_binary_image)
-            .collect();
-
-        let mut stacktraces = Vec::with_capacity(report.threads.len());
-
-        for thread in report.threads {
-            let registers = thread
-                .registers
-                .unwrap_or_default()
-                .into_iter()
-                .map(|(name, addr)| (name, HexValue(addr.0)))
-                .collect();
-
-            let frames = thread
-                .frames
-                .into_iter()
-                .map(|frame| RawFrame {
-                    instruction_addr: HexValue(frame.instruction_addr.0),
-                    package: frame.module,
-                    ..RawFrame::default()
-                })
-                .collect();
-
-            stacktraces.push(RawStacktrace {
-                thread_id: Some(thread.id),
-                thread_name: thread.name,
-                is_requesting: Some(thread.crashed),
-                registers,
-                frames,
-            });
-        }
+        let (modules, mut stacktraces, state) = if is_ips_crash_report(&data) {
+            parse_ips_crash_report(&data).context("failed to parse .ips crash report")?
+        } else {
+            parse_text_crash_report(&data)?
+        };
+        strip_pointer_auth(
+            &self.pointer_auth,
+            state.system_info.cpu_arch,
+            &modules,
+            &mut stacktraces,
+        );
 
         let request = SymbolicateStacktraces {
             platform,
@@ -87,40 +66,6 @@ impl SymbolicationActor {
             scraping,
         };
 
-        let mut system_info = SystemInfo {
-            os_name: metadata.remove("OS Version").unwrap_or_default(),
-            device_model: metadata.remove("Hardware Model").unwrap_or_default(),
-            cpu_arch: arch,
-            ..SystemInfo::default()
-        };
-
-        if let Some(captures) = OS_MACOS_REGEX.captures(&system_info.os_name) {
-            system_info.os_version = captures
-                .name("version")
-                .map(|m| m.as_str().to_string())
-                .unwrap_or_default();
-            system_info.os_build = captures
-                .name("build")
-                .map(|m| m.as_str().to_string())
-                .unwrap_or_default();
-            system_info.os_name = "macOS".to_string();
-        }
-
-        // https://developer.apple.com/library/archive/technotes/tn2151/_index.html
-        let crash_reason = metadata.remove("Exception Type");
-        let crash_details = report
-            .application_specific_information
-            .or_else(|| metadata.remove("Exception Message"))
-            .or_else(|| metadata.remove("Exception Subtype"))
-            .or_else(|| metadata.remove("Exception Codes"));
-
-        let state = AppleCrashReportState {
-            timestamp: report.timestamp,
-            system_info,
-            crash_reason,
-            crash_details,
-        };
-
         Ok((request, state))
     }
 
@@ -141,17 +86,103 @@ impl SymbolicationActor {
     }
 }
 
+/// Parses a crash report in the legacy text format.
+fn parse_text_crash_report(data: &[u8]) -> Result<ParsedCrashReport> {
+    let report =
+        AppleCrashReport::from_reader(data).context("failed to parse apple crash report")?;
+    let mut metadata = report.metadata;
+
+    let arch = report
+        .code_type
+        .as_ref()
+        .and_then(|code_type| code_type.split(' ').next())
+        .and_then(|word| word.parse().ok())
+        .unwrap_or_default();
+
+    let modules = report
+        .binary_images
+        .into_iter()
+        .map(map_apple_binary_image)
+        .collect();
+
+    let mut stacktraces = Vec::with_capacity(report.threads.len());
+
+    for thread in report.threads {
+        let registers = thread
+            .registers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, addr)| (name, HexValue(addr.0)))
+            .collect();
+
+        let frames = thread
+            .frames
+            .into_iter()
+            .map(|frame| RawFrame {
+                instruction_addr: HexValue(frame.instruction_addr.0),
+                package: frame.module,
+                ..RawFrame::default()
+            })
+            .collect();
+
+        stacktraces.push(RawStacktrace {
+            thread_id: Some(thread.id),
+            thread_name: thread.name,
+            is_requesting: Some(thread.crashed),
+            registers,
+            frames,
+            ..Default::default()
+        });
+    }
+
+    let mut system_info = SystemInfo {
+        os_name: metadata.remove("OS Version").unwrap_or_default(),
+        device_model: metadata.remove("Hardware Model").unwrap_or_default(),
+        cpu_arch: arch,
+        ..SystemInfo::default()
+    };
+
+    if let Some(captures) = OS_MACOS_REGEX.captures(&system_info.os_name) {
+        system_info.os_version = captures
+            .name("version")
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        system_info.os_build = captures
+            .name("build")
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        system_info.os_name = "macOS".to_string();
+    }
+
+    // https://developer.apple.com/library/archive/technotes/tn2151/_index.html
+    let crash_reason = metadata.remove("Exception Type");
+    let crash_details = report
+        .application_specific_information
+        .or_else(|| metadata.remove("Exception Message"))
+        .or_else(|| metadata.remove("Exception Subtype"))
+        .or_else(|| metadata.remove("Exception Codes"));
+
+    let state = AppleCrashReportState {
+        timestamp: report.timestamp,
+        system_info,
+        crash_reason,
+        crash_details,
+    };
+
+    Ok((modules, stacktraces, state))
+}
+
 /// Format sent by Unreal Engine on macOS
 static OS_MACOS_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^Mac OS X (?P<version>\d+\.\d+\.\d+)( \((?P<build>[a-fA-F0-9]+)\))?$").unwrap()
 });
 
 #[derive(Debug)]
-struct AppleCrashReportState {
-    timestamp: Option<DateTime<Utc>>,
-    system_info: SystemInfo,
-    crash_reason: Option<String>,
-    crash_details: Option<String>,
+pub(super) struct AppleCrashReportState {
+    pub(super) timestamp: Option<DateTime<Utc>>,
+    pub(super) system_info: SystemInfo,
+    pub(super) crash_reason: Option<String>,
+    pub(super) crash_details: Option<String>,
 }
 
 impl AppleCrashReportState {
@@ -0,0 +1,34 @@
+//! Generation of Breakpad symbols for a debug file.
+
+use symbolic::common::ByteView;
+use symbolicator_service::caching::CacheEntry;
+
+use crate::caches::breakpad::FetchBreakpadSymbols;
+use crate::interface::DumpBreakpadSymbols;
+
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Fetches a debug file and returns the Breakpad symbols generated from it.
+    ///
+    /// The generated symbols are cached like other derived caches.
+    pub async fn breakpad_symbols(
+        &self,
+        request: DumpBreakpadSymbols,
+    ) -> CacheEntry<ByteView<'static>> {
+        let DumpBreakpadSymbols {
+            scope,
+            sources,
+            identifier,
+        } = request;
+
+        self.breakpad
+            .fetch(FetchBreakpadSymbols {
+                identifier,
+                sources,
+                scope,
+            })
+            .await
+            .cache
+    }
+}
@@ -0,0 +1,670 @@
+//! Processing of Linux ELF core dumps.
+//!
+//! The kernel writes the registers of every thread into `NT_PRSTATUS` notes, the mapped files
+//! into an `NT_FILE` note, and the memory of the process into `PT_LOAD` segments. Core dumps are
+//! converted into minidumps containing the threads, their stack memory and the loaded modules,
+//! which then go through the same stackwalking and symbolication as any other minidump.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use symbolic::common::ByteView;
+use symbolicator_service::metric;
+use symbolicator_service::types::{Platform, Scope, ScrapingConfig};
+use symbolicator_sources::SourceConfig;
+use tempfile::TempPath;
+
+use crate::interface::{CompletedSymbolicationResponse, StackwalkOptions};
+
+use super::minidump_writer::{
+    current_timestamp, elf_cv_record, write_minidump, write_minidump_file, ContextArch,
+    ExceptionRecord, MinidumpContents, ModuleRecord, ThreadRecord, PLATFORM_LINUX,
+};
+use super::symbolicate::SymbolicationActor;
+
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_GNU_BUILD_ID: u32 = 3;
+const NT_SIGINFO: u32 = 0x5349_4749;
+const NT_FILE: u32 = 0x4649_4c45;
+
+/// Offset of `pr_cursig` in `struct elf_prstatus`.
+const PRSTATUS_CURSIG_OFFSET: usize = 12;
+/// Offset of `pr_pid` in `struct elf_prstatus`.
+const PRSTATUS_PID_OFFSET: usize = 32;
+/// Offset of `pr_reg` in `struct elf_prstatus`.
+const PRSTATUS_REGS_OFFSET: usize = 112;
+
+/// The most stack memory that is copied into the minidump for every thread.
+const MAX_STACK_SIZE: usize = 1024 * 1024;
+/// The area below the stack pointer which leaf functions may use without adjusting it.
+const RED_ZONE_SIZE: u64 = 128;
+
+impl SymbolicationActor {
+    /// Converts an ELF core dump into a minidump and processes it like any other minidump.
+    ///
+    /// The core dump is saved to the diagnostics cache if it cannot be converted.
+    pub async fn process_coredump(
+        &self,
+        platform: Option<Platform>,
+        scope: Scope,
+        coredump_file: TempPath,
+        sources: Arc<[SourceConfig]>,
+        scraping: ScrapingConfig,
+        options: StackwalkOptions,
+    ) -> Result<CompletedSymbolicationResponse> {
+        let len = coredump_file.metadata()?.len();
+        tracing::debug!("Processing core dump ({} bytes)", len);
+        metric!(time_raw("coredump.upload.size") = len);
+
+        let minidump = match convert_coredump_file(&coredump_file) {
+            Ok(minidump) => minidump,
+            Err(err) => {
+                self.maybe_persist_minidump(coredump_file);
+                return Err(err);
+            }
+        };
+
+        let minidump_file = write_minidump_file(&minidump, coredump_file.parent())?;
+        drop(coredump_file);
+
+        self.process_minidump(platform, scope, minidump_file, sources, scraping, options)
+            .await
+    }
+}
+
+fn convert_coredump_file(path: &TempPath) -> Result<Vec<u8>> {
+    let data = ByteView::open(path)?;
+    convert_coredump(&data, current_timestamp())
+}
+
+/// A thread, as described by an `NT_PRSTATUS` note.
+#[derive(Debug)]
+struct CoreThread {
+    tid: u32,
+    /// The signal which caused the core dump, or `0`.
+    signal: u32,
+    registers: Vec<u64>,
+}
+
+/// The signal information of the crashing thread, from the `NT_SIGINFO` note.
+#[derive(Debug)]
+struct SigInfo {
+    signo: u32,
+    code: i32,
+    addr: u64,
+}
+
+/// A mapping of a file into memory, from the `NT_FILE` note.
+#[derive(Debug)]
+struct MappedFile<'a> {
+    start: u64,
+    end: u64,
+    file_offset: u64,
+    name: &'a str,
+}
+
+/// A module which was loaded into the crashed process.
+#[derive(Debug)]
+struct CoreModule<'a> {
+    base: u64,
+    end: u64,
+    name: &'a str,
+    build_id: Option<&'a [u8]>,
+}
+
+/// A `PT_LOAD` segment holding process memory.
+#[derive(Debug)]
+struct Segment<'a> {
+    vaddr: u64,
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+struct Coredump<'a> {
+    arch: ContextArch,
+    segments: Vec<Segment<'a>>,
+    threads: Vec<CoreThread>,
+    siginfo: Option<SigInfo>,
+    files: Vec<MappedFile<'a>>,
+}
+
+impl<'a> Coredump<'a> {
+    /// Parses a little-endian 64-bit ELF core dump.
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        if !data.starts_with(b"\x7fELF") {
+            bail!("not an ELF file");
+        }
+        if data.get(4..6) != Some(&[2, 1][..]) {
+            bail!("only little-endian 64-bit core dumps are supported");
+        }
+        if read_u16(data, 0x10) != Some(ET_CORE) {
+            bail!("not an ELF core dump");
+        }
+        let arch = match read_u16(data, 0x12) {
+            Some(EM_X86_64) => ContextArch::Amd64,
+            Some(EM_AARCH64) => ContextArch::Arm64,
+            machine => bail!("unsupported core dump architecture {:?}", machine),
+        };
+
+        let mut segments = Vec::new();
+        let mut notes = Vec::new();
+        let program_headers = read_u64(data, 0x20)
+            .and_then(|phoff| program_headers(data, data, usize::try_from(phoff).ok()?))
+            .context("invalid program headers")?;
+        for header in program_headers {
+            // Truncated core dumps are cut off at the end of the file.
+            let contents = usize::try_from(header.offset)
+                .ok()
+                .and_then(|offset| data.get(offset..))
+                .map_or(&[][..], |rest| {
+                    &rest[..rest.len().min(header.filesz as usize)]
+                });
+            match header.ty {
+                PT_LOAD => segments.push(Segment {
+                    vaddr: header.vaddr,
+                    data: contents,
+                }),
+                PT_NOTE => notes.extend(parse_notes(contents)),
+                _ => {}
+            }
+        }
+
+        let mut threads = Vec::new();
+        let mut siginfo = None;
+        let mut files = Vec::new();
+        for note in notes.into_iter().filter(|note| note.name == b"CORE") {
+            match note.ty {
+                NT_PRSTATUS => threads.extend(parse_prstatus(note.desc, arch)),
+                NT_SIGINFO => siginfo = siginfo.or_else(|| parse_siginfo(note.desc)),
+                NT_FILE => files = parse_file_note(note.desc).unwrap_or_default(),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            arch,
+            segments,
+            threads,
+            siginfo,
+            files,
+        })
+    }
+
+    /// Reads `len` bytes of process memory at `addr`.
+    fn read_memory(&self, addr: u64, len: usize) -> Option<&'a [u8]> {
+        self.segments.iter().find_map(|segment| {
+            let offset = usize::try_from(addr.checked_sub(segment.vaddr)?).ok()?;
+            segment.data.get(offset..offset.checked_add(len)?)
+        })
+    }
+
+    /// Reads the process memory from `addr` up to the end of its segment, at most `max_len` bytes.
+    fn read_memory_from(&self, addr: u64, max_len: usize) -> Option<&'a [u8]> {
+        self.segments.iter().find_map(|segment| {
+            let offset = usize::try_from(addr.checked_sub(segment.vaddr)?).ok()?;
+            let rest = segment.data.get(offset..).filter(|rest| !rest.is_empty())?;
+            Some(&rest[..rest.len().min(max_len)])
+        })
+    }
+
+    /// Collects the modules from the file mappings.
+    ///
+    /// A module starts at the mapping of its file header. Files which are mapped without their
+    /// header, or whose header was dumped and is not an ELF header, are skipped.
+    fn modules(&self) -> Vec<CoreModule<'a>> {
+        let mut modules: Vec<CoreModule<'a>> = Vec::new();
+        let mut indices = HashMap::new();
+
+        for file in &self.files {
+            if file.file_offset == 0 {
+                let build_id = match self.read_memory(file.start, 4) {
+                    Some(b"\x7fELF") => self.build_id(file.start),
+                    Some(_) => continue,
+                    None => None,
+                };
+                indices.insert(file.name, modules.len());
+                modules.push(CoreModule {
+                    base: file.start,
+                    end: file.end,
+                    name: file.name,
+                    build_id,
+                });
+            } else if let Some(&index) = indices.get(file.name) {
+                let module = &mut modules[index];
+                if file.start >= module.base {
+                    module.end = module.end.max(file.end);
+                }
+            }
+        }
+
+        modules
+    }
+
+    /// Reads the GNU build id from the notes of the ELF file loaded at `base`.
+    fn build_id(&self, base: u64) -> Option<&'a [u8]> {
+        let header = self.read_memory(base, 64)?;
+        let phoff = read_u64(header, 0x20)?;
+        let phentsize = read_u16(header, 0x36)? as usize;
+        let phnum = read_u16(header, 0x38)? as usize;
+        let phdrs = self.read_memory(base.checked_add(phoff)?, phentsize * phnum)?;
+
+        let program_headers = program_headers(header, phdrs, 0)?;
+        // The load bias is the difference between the addresses in memory and in the file.
+        let first_load = program_headers.iter().find(|header| header.ty == PT_LOAD)?;
+        let bias = base.wrapping_sub(first_load.vaddr.wrapping_sub(first_load.offset));
+
+        program_headers
+            .iter()
+            .filter(|header| header.ty == PT_NOTE)
+            .filter_map(|header| {
+                self.read_memory(bias.wrapping_add(header.vaddr), header.filesz as usize)
+            })
+            .flat_map(parse_notes)
+            .find(|note| note.ty == NT_GNU_BUILD_ID && note.name == b"GNU")
+            .map(|note| note.desc)
+    }
+}
+
+/// An ELF program header.
+#[derive(Debug)]
+struct ProgramHeader {
+    ty: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+/// Reads the program headers described by the ELF `header` from `data` at `phoff`.
+fn program_headers(header: &[u8], data: &[u8], phoff: usize) -> Option<Vec<ProgramHeader>> {
+    let phentsize = read_u16(header, 0x36)? as usize;
+    let phnum = read_u16(header, 0x38)? as usize;
+    if phentsize < 56 {
+        return None;
+    }
+
+    (0..phnum)
+        .map(|index| {
+            let offset = phoff.checked_add(index.checked_mul(phentsize)?)?;
+            Some(ProgramHeader {
+                ty: read_u32(data, offset)?,
+                offset: read_u64(data, offset + 8)?,
+                vaddr: read_u64(data, offset + 16)?,
+                filesz: read_u64(data, offset + 32)?,
+            })
+        })
+        .collect()
+}
+
+/// An ELF note record.
+#[derive(Debug)]
+struct Note<'a> {
+    ty: u32,
+    name: &'a [u8],
+    desc: &'a [u8],
+}
+
+/// Parses the note records of a `PT_NOTE` segment, stopping at the first malformed record.
+fn parse_notes(data: &[u8]) -> Vec<Note<'_>> {
+    let align = |size: usize| size.checked_add(3).map(|size| size & !3);
+
+    let mut notes = Vec::new();
+    let mut offset = 0;
+    while let (Some(namesz), Some(descsz), Some(ty)) = (
+        read_u32(data, offset),
+        read_u32(data, offset + 4),
+        read_u32(data, offset + 8),
+    ) {
+        let (namesz, descsz) = (namesz as usize, descsz as usize);
+        let name_start = offset + 12;
+        let Some(desc_start) = align(namesz).and_then(|size| name_start.checked_add(size)) else {
+            break;
+        };
+        let (Some(name), Some(desc)) = (
+            data.get(name_start..name_start + namesz),
+            desc_start
+                .checked_add(descsz)
+                .and_then(|desc_end| data.get(desc_start..desc_end)),
+        ) else {
+            break;
+        };
+
+        notes.push(Note {
+            ty,
+            name: name.strip_suffix(b"\0").unwrap_or(name),
+            desc,
+        });
+
+        match align(descsz).and_then(|size| desc_start.checked_add(size)) {
+            Some(next) => offset = next,
+            None => break,
+        }
+    }
+    notes
+}
+
+fn parse_prstatus(desc: &[u8], arch: ContextArch) -> Option<CoreThread> {
+    let signal = read_u16(desc, PRSTATUS_CURSIG_OFFSET)? as u32;
+    let tid = read_u32(desc, PRSTATUS_PID_OFFSET)?;
+    let registers = (0..arch.register_names().len())
+        .map(|index| read_u64(desc, PRSTATUS_REGS_OFFSET + index * 8))
+        .collect::<Option<_>>()?;
+
+    Some(CoreThread {
+        tid,
+        signal,
+        registers,
+    })
+}
+
+fn parse_siginfo(desc: &[u8]) -> Option<SigInfo> {
+    Some(SigInfo {
+        signo: read_u32(desc, 0)?,
+        code: read_u32(desc, 8)? as i32,
+        addr: read_u64(desc, 16)?,
+    })
+}
+
+/// Parses the `NT_FILE` note, which lists the mapped ranges followed by the file names.
+fn parse_file_note(desc: &[u8]) -> Option<Vec<MappedFile<'_>>> {
+    let count = usize::try_from(read_u64(desc, 0)?).ok()?;
+    let page_size = read_u64(desc, 8)?;
+    let names_offset = count.checked_mul(24)?.checked_add(16)?;
+    let mut names = desc.get(names_offset..)?.split(|b| *b == 0);
+
+    (0..count)
+        .map(|index| {
+            let offset = 16 + index * 24;
+            Some(MappedFile {
+                start: read_u64(desc, offset)?,
+                end: read_u64(desc, offset + 8)?,
+                file_offset: read_u64(desc, offset + 16)?.checked_mul(page_size)?,
+                name: std::str::from_utf8(names.next()?).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Converts an ELF core dump into a minidump.
+///
+/// The first thread is the one which caused the core dump. If it received a signal, the
+/// minidump gets an exception stream for it.
+fn convert_coredump(data: &[u8], timestamp: u32) -> Result<Vec<u8>> {
+    let core = Coredump::parse(data)?;
+    let Some(crashed_thread) = core.threads.first() else {
+        bail!("core dump does not contain any threads");
+    };
+
+    let threads = core
+        .threads
+        .iter()
+        .map(|thread| {
+            let stack_start = core
+                .arch
+                .stack_pointer(&thread.registers)
+                .saturating_sub(RED_ZONE_SIZE);
+            ThreadRecord {
+                tid: thread.tid,
+                registers: thread.registers.clone(),
+                stack_start,
+                stack: core
+                    .read_memory_from(stack_start, MAX_STACK_SIZE)
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let modules = core
+        .modules()
+        .into_iter()
+        .map(|module| ModuleRecord {
+            base: module.base,
+            size: u32::try_from(module.end - module.base).unwrap_or(u32::MAX),
+            timestamp: 0,
+            name: module.name,
+            cv_record: module.build_id.map(elf_cv_record).unwrap_or_default(),
+        })
+        .collect();
+
+    let signal = crashed_thread.signal;
+    let exception = (signal != 0).then(|| {
+        let siginfo = core.siginfo.as_ref().filter(|info| info.signo == signal);
+        // Only signals raised by faults carry the faulting address.
+        let is_fault = matches!(signal, 4 | 5 | 7 | 8 | 11);
+        let address = match siginfo {
+            Some(info) if is_fault => info.addr,
+            _ => core.arch.instruction_pointer(&crashed_thread.registers),
+        };
+        ExceptionRecord {
+            thread_index: 0,
+            code: signal,
+            flags: siginfo.map_or(0, |info| info.code as u32),
+            address,
+        }
+    });
+
+    let contents = MinidumpContents {
+        arch: core.arch,
+        platform_id: PLATFORM_LINUX,
+        threads,
+        modules,
+        exception,
+    };
+    Ok(write_minidump(&contents, timestamp))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use minidump::system_info::{Cpu, Os};
+    use minidump::{
+        Minidump, MinidumpException, MinidumpModuleList, MinidumpSystemInfo, MinidumpThreadList,
+        Module,
+    };
+
+    use crate::symbolication::minidump_writer::{put_u16, put_u32, put_u64};
+
+    use super::*;
+
+    const BUILD_ID: &[u8] = &[
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32,
+        0x10, 0xaa, 0xbb, 0xcc, 0xdd,
+    ];
+
+    fn elf_header(e_type: u16, machine: u16, phnum: u16) -> Vec<u8> {
+        let mut header = vec![0; 64];
+        header[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        put_u16(&mut header, 0x10, e_type);
+        put_u16(&mut header, 0x12, machine);
+        put_u64(&mut header, 0x20, 64);
+        put_u16(&mut header, 0x36, 56);
+        put_u16(&mut header, 0x38, phnum);
+        header
+    }
+
+    fn program_header(ty: u32, offset: usize, vaddr: u64, filesz: usize) -> Vec<u8> {
+        let mut header = vec![0; 56];
+        put_u32(&mut header, 0, ty);
+        put_u64(&mut header, 8, offset as u64);
+        put_u64(&mut header, 16, vaddr);
+        put_u64(&mut header, 32, filesz as u64);
+        put_u64(&mut header, 40, filesz as u64);
+        header
+    }
+
+    fn note(ty: u32, name: &[u8], desc: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        note.extend_from_slice(&(name.len() as u32 + 1).to_le_bytes());
+        note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+        note.extend_from_slice(&ty.to_le_bytes());
+        note.extend_from_slice(name);
+        note.push(0);
+        note.resize((note.len() + 3) & !3, 0);
+        note.extend_from_slice(desc);
+        note.resize((note.len() + 3) & !3, 0);
+        note
+    }
+
+    fn prstatus(tid: u32, signal: u16, rip: u64, rsp: u64) -> Vec<u8> {
+        let mut desc = vec![0; 336];
+        put_u16(&mut desc, PRSTATUS_CURSIG_OFFSET, signal);
+        put_u32(&mut desc, PRSTATUS_PID_OFFSET, tid);
+        put_u64(&mut desc, PRSTATUS_REGS_OFFSET + 16 * 8, rip);
+        put_u64(&mut desc, PRSTATUS_REGS_OFFSET + 19 * 8, rsp);
+        note(NT_PRSTATUS, b"CORE", &desc)
+    }
+
+    /// An ELF header mapped into memory, with a build id note.
+    fn library_image() -> Vec<u8> {
+        let mut image = elf_header(3, EM_X86_64, 2);
+        image.extend(program_header(PT_LOAD, 0, 0, 0x1000));
+        let build_id = note(NT_GNU_BUILD_ID, b"GNU", BUILD_ID);
+        image.extend(program_header(PT_NOTE, 0x200, 0x200, build_id.len()));
+        image.resize(0x200, 0);
+        image.extend(build_id);
+        image.resize(0x1000, 0);
+        image
+    }
+
+    /// A core dump of a process with two threads, which crashed in the first one.
+    fn coredump() -> Vec<u8> {
+        let mut notes = Vec::new();
+        notes.extend(prstatus(42, 11, 0x10100, 0x7000_1000));
+        notes.extend(prstatus(43, 0, 0x10200, 0x7000_1800));
+
+        let mut siginfo = vec![0; 128];
+        put_u32(&mut siginfo, 0, 11);
+        put_u32(&mut siginfo, 8, 1);
+        put_u64(&mut siginfo, 16, 0xdead);
+        notes.extend(note(NT_SIGINFO, b"CORE", &siginfo));
+
+        let files = [
+            (0x10000, 0x11000, 0, "/usr/lib/libtest.so"),
+            (0x11000, 0x13000, 1, "/usr/lib/libtest.so"),
+            (0x20000, 0x21000, 0, "/usr/share/fonts/font.ttf"),
+            (0x30000, 0x31000, 0, "/usr/lib/libnotdumped.so"),
+        ];
+        let mut file_note = Vec::new();
+        file_note.extend_from_slice(&(files.len() as u64).to_le_bytes());
+        file_note.extend_from_slice(&0x1000u64.to_le_bytes());
+        for (start, end, page_offset, _) in files {
+            for value in [start, end, page_offset] {
+                file_note.extend_from_slice(&(value as u64).to_le_bytes());
+            }
+        }
+        for (_, _, _, name) in files {
+            file_note.extend_from_slice(name.as_bytes());
+            file_note.push(0);
+        }
+        notes.extend(note(NT_FILE, b"CORE", &file_note));
+
+        let segments = [
+            (0x10000, library_image()),
+            (0x20000, vec![0xff; 0x1000]),
+            (0x7000_0000, vec![0x11; 0x2000]),
+        ];
+
+        let phnum = segments.len() + 1;
+        let mut core = elf_header(ET_CORE, EM_X86_64, phnum as u16);
+        let mut offset = 64 + phnum * 56;
+        core.extend(program_header(PT_NOTE, offset, 0, notes.len()));
+        offset += notes.len();
+        for (vaddr, data) in &segments {
+            core.extend(program_header(PT_LOAD, offset, *vaddr, data.len()));
+            offset += data.len();
+        }
+        core.extend(notes);
+        for (_, data) in segments {
+            core.extend(data);
+        }
+        core
+    }
+
+    #[test]
+    fn test_parse_coredump() {
+        let data = coredump();
+        let core = Coredump::parse(&data).unwrap();
+
+        assert_eq!(core.arch, ContextArch::Amd64);
+        let threads: Vec<_> = core.threads.iter().map(|t| (t.tid, t.signal)).collect();
+        assert_eq!(threads, [(42, 11), (43, 0)]);
+
+        let modules: Vec<_> = core
+            .modules()
+            .into_iter()
+            .map(|module| (module.name, module.base, module.end, module.build_id))
+            .collect();
+        assert_eq!(
+            modules,
+            [
+                ("/usr/lib/libtest.so", 0x10000, 0x13000, Some(BUILD_ID)),
+                ("/usr/lib/libnotdumped.so", 0x30000, 0x31000, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_not_a_coredump() {
+        let data = elf_header(3, EM_X86_64, 0);
+        let err = Coredump::parse(&data).unwrap_err();
+        assert_eq!(err.to_string(), "not an ELF core dump");
+
+        let err = Coredump::parse(b"MDMP").unwrap_err();
+        assert_eq!(err.to_string(), "not an ELF file");
+    }
+
+    #[test]
+    fn test_convert_coredump() {
+        let minidump = convert_coredump(&coredump(), 0).unwrap();
+        let minidump = Minidump::read(minidump).unwrap();
+
+        let system_info: MinidumpSystemInfo = minidump.get_stream().unwrap();
+        assert_eq!(system_info.os, Os::Linux);
+        assert_eq!(system_info.cpu, Cpu::X86_64);
+
+        let threads: MinidumpThreadList = minidump.get_stream().unwrap();
+        let threads: Vec<_> = threads
+            .threads
+            .iter()
+            .map(|thread| (thread.raw.thread_id, thread.raw.stack.start_of_memory_range))
+            .collect();
+        assert_eq!(threads, [(42, 0x7000_1000 - 128), (43, 0x7000_1800 - 128)]);
+
+        let modules: MinidumpModuleList = minidump.get_stream().unwrap();
+        let module = modules.iter().next().unwrap();
+        assert_eq!(module.code_file(), "/usr/lib/libtest.so");
+        assert_eq!(module.base_address(), 0x10000);
+        assert_eq!(module.size(), 0x3000);
+        assert_eq!(
+            module.code_identifier().unwrap().as_str(),
+            "0123456789abcdeffedcba9876543210aabbccdd"
+        );
+        assert!(module.debug_identifier().is_some());
+
+        let exception: MinidumpException = minidump.get_stream().unwrap();
+        assert_eq!(exception.raw.thread_id, 42);
+        assert_eq!(exception.raw.exception_record.exception_code, 11);
+        assert_eq!(exception.raw.exception_record.exception_address, 0xdead);
+    }
+}
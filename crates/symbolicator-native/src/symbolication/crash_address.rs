@@ -0,0 +1,394 @@
+//! Classification of the addresses at which processes crash.
+//!
+//! The memory layout of the crashed process is read from the memory info list of Windows
+//! minidumps, or from the `/proc/self/maps` stream of Linux minidumps. Without either, only null
+//! pointers, modules and the captured thread stacks can be told apart.
+
+use minidump::{MinidumpThreadList, Module};
+use minidump_processor::ProcessState;
+use symbolicator_service::utils::hex::HexValue;
+
+use crate::interface::{CrashAddress, MemoryRegion};
+
+use super::process_minidump::Minidump;
+
+const MINIDUMP_MEMORY_INFO_LIST_STREAM: u32 = 16;
+const MINIDUMP_LINUX_MAPS_STREAM: u32 = 0x4767_0009;
+
+const MEMORY_INFO_SIZE: usize = 48;
+
+const MEM_RESERVE: u32 = 0x2000;
+const MEM_FREE: u32 = 0x10000;
+const MEM_MAPPED: u32 = 0x40000;
+const MEM_IMAGE: u32 = 0x100_0000;
+const PAGE_GUARD: u32 = 0x100;
+
+/// Accesses below this address are considered null pointer dereferences.
+pub(super) const NULL_PAGE_SIZE: u64 = 0x10000;
+/// How far below the stack pointer an access is considered to overflow the stack.
+const STACK_GUARD_SIZE: u64 = 0x10000;
+
+/// A region of the address space of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    start: u64,
+    end: u64,
+    kind: RegionKind,
+}
+
+impl Region {
+    fn contains(&self, address: u64) -> bool {
+        self.start <= address && address < self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegionKind {
+    /// Address space which is not allocated.
+    Free,
+    /// Address space which is allocated but not accessible.
+    Reserved,
+    /// Guard pages, which fault on the first access.
+    Guard,
+    /// The image of a module.
+    Image,
+    /// A memory-mapped file.
+    Mapped,
+    /// The stack of the main thread.
+    Stack,
+    /// The heap of the process.
+    Heap,
+    /// Any other memory allocated by the process.
+    Private,
+}
+
+/// Classifies the address at which the process of a minidump crashed.
+///
+/// Returns `None` if the process did not crash.
+pub(super) fn analyze_crash_address(
+    minidump: &Minidump,
+    process_state: &ProcessState,
+) -> Option<CrashAddress> {
+    let address = process_state.exception_info.as_ref()?.address.0;
+
+    let module = process_state
+        .modules
+        .module_at_address(address)
+        .map(|module| {
+            let offset = address - module.base_address();
+            (module.code_file().into_owned(), offset)
+        });
+
+    let stacks: Vec<_> = match minidump.get_stream::<MinidumpThreadList>() {
+        Ok(thread_list) => thread_list
+            .threads
+            .iter()
+            .map(|thread| {
+                let start = thread.raw.stack.start_of_memory_range;
+                let size = thread.raw.stack.memory.data_size;
+                (start, start.saturating_add(size.into()))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    let stack_pointer = process_state
+        .requesting_thread
+        .and_then(|index| process_state.threads.get(index))
+        .and_then(|thread| thread.frames.first())
+        .map(|frame| frame.context.get_stack_pointer());
+
+    let regions = match minidump.get_raw_stream(MINIDUMP_MEMORY_INFO_LIST_STREAM) {
+        Ok(data) => parse_memory_info_list(data),
+        Err(_) => match minidump.get_raw_stream(MINIDUMP_LINUX_MAPS_STREAM) {
+            Ok(data) => parse_linux_maps(data),
+            Err(_) => Vec::new(),
+        },
+    };
+
+    Some(classify_address(
+        address,
+        module,
+        &stacks,
+        stack_pointer,
+        &regions,
+    ))
+}
+
+/// Classifies an address given the layout of the process.
+///
+/// `module` is the code file of the module containing the address along with the offset into
+/// it. `stacks` are the ranges of the captured stack memory of all threads.
+fn classify_address(
+    address: u64,
+    module: Option<(String, u64)>,
+    stacks: &[(u64, u64)],
+    stack_pointer: Option<u64>,
+    regions: &[Region],
+) -> CrashAddress {
+    let mut crash_address = CrashAddress {
+        address: HexValue(address),
+        region: MemoryRegion::Unknown,
+        module: None,
+        offset: None,
+    };
+
+    // Stacks grow downwards, so overflowing accesses end up right below the stack pointer.
+    let below_stack_pointer =
+        stack_pointer.is_some_and(|sp| address < sp && sp - address <= STACK_GUARD_SIZE);
+
+    crash_address.region = if address < NULL_PAGE_SIZE {
+        MemoryRegion::NullPage
+    } else if let Some((module, offset)) = module {
+        crash_address.module = Some(module);
+        crash_address.offset = Some(HexValue(offset));
+        MemoryRegion::Module
+    } else if stacks
+        .iter()
+        .any(|&(start, end)| start <= address && address < end)
+    {
+        MemoryRegion::Stack
+    } else if !regions.is_empty() {
+        classify_region(address, stacks, regions, below_stack_pointer)
+    } else if below_stack_pointer {
+        MemoryRegion::StackGuard
+    } else {
+        MemoryRegion::Unknown
+    };
+
+    crash_address
+}
+
+/// Classifies an address by the memory region containing it.
+fn classify_region(
+    address: u64,
+    stacks: &[(u64, u64)],
+    regions: &[Region],
+    below_stack_pointer: bool,
+) -> MemoryRegion {
+    let contains_stack = |region: &Region| stacks.iter().any(|&(start, _)| region.contains(start));
+
+    let unmapped = if below_stack_pointer {
+        MemoryRegion::StackGuard
+    } else {
+        MemoryRegion::Unmapped
+    };
+    let Some(region) = regions.iter().find(|region| region.contains(address)) else {
+        return unmapped;
+    };
+
+    match region.kind {
+        RegionKind::Free => unmapped,
+        RegionKind::Guard => MemoryRegion::StackGuard,
+        RegionKind::Reserved => {
+            // Thread stacks are preceded by reserved memory or guard pages.
+            let is_guard = regions.iter().any(|above| {
+                above.start == region.end
+                    && (matches!(above.kind, RegionKind::Stack | RegionKind::Guard)
+                        || contains_stack(above))
+            });
+            if is_guard {
+                MemoryRegion::StackGuard
+            } else {
+                unmapped
+            }
+        }
+        RegionKind::Image => MemoryRegion::Module,
+        RegionKind::Mapped => MemoryRegion::Mapped,
+        RegionKind::Stack => MemoryRegion::Stack,
+        RegionKind::Private if contains_stack(region) => MemoryRegion::Stack,
+        RegionKind::Private | RegionKind::Heap => MemoryRegion::Heap,
+    }
+}
+
+/// Parses the `MINIDUMP_MEMORY_INFO` entries of a memory info list stream.
+fn parse_memory_info_list(data: &[u8]) -> Vec<Region> {
+    let (Some(header_size), Some(entry_size), Some(count)) =
+        (read_u32(data, 0), read_u32(data, 4), read_u64(data, 8))
+    else {
+        return Vec::new();
+    };
+    let entry_size = entry_size as usize;
+    if entry_size < MEMORY_INFO_SIZE {
+        return Vec::new();
+    }
+    let Some(entries) = data.get(header_size as usize..) else {
+        return Vec::new();
+    };
+
+    entries
+        .chunks_exact(entry_size)
+        .take(count as usize)
+        .filter_map(|entry| {
+            let start = read_u64(entry, 0)?;
+            let size = read_u64(entry, 24)?;
+            let state = read_u32(entry, 32)?;
+            let protection = read_u32(entry, 36)?;
+            let ty = read_u32(entry, 40)?;
+
+            let kind = if state == MEM_FREE {
+                RegionKind::Free
+            } else if state == MEM_RESERVE {
+                RegionKind::Reserved
+            } else if protection & PAGE_GUARD != 0 {
+                RegionKind::Guard
+            } else {
+                match ty {
+                    MEM_IMAGE => RegionKind::Image,
+                    MEM_MAPPED => RegionKind::Mapped,
+                    _ => RegionKind::Private,
+                }
+            };
+
+            Some(Region {
+                start,
+                end: start.saturating_add(size),
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Parses the lines of a `/proc/self/maps` file.
+///
+/// Modules are identified using the module list, so file mappings are not told apart here.
+fn parse_linux_maps(data: &[u8]) -> Vec<Region> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let (start, end) = parts.next()?.split_once('-')?;
+            let start = u64::from_str_radix(start, 16).ok()?;
+            let end = u64::from_str_radix(end, 16).ok()?;
+            let permissions = parts.next()?;
+            // Skips the offset, device and inode.
+            let path = parts.nth(3);
+
+            let kind = match path {
+                Some(path) if path.starts_with("[stack") => RegionKind::Stack,
+                Some("[heap]") => RegionKind::Heap,
+                _ if permissions.starts_with("---") => RegionKind::Reserved,
+                Some(path) if path.starts_with('/') => RegionKind::Mapped,
+                _ => RegionKind::Private,
+            };
+
+            Some(Region { start, end, kind })
+        })
+        .collect()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_PRIVATE: u32 = 0x20000;
+
+    fn memory_info_list(entries: &[(u64, u64, u32, u32, u32)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&(MEMORY_INFO_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for &(start, size, state, protection, ty) in entries {
+            data.extend_from_slice(&start.to_le_bytes());
+            data.extend_from_slice(&start.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&state.to_le_bytes());
+            data.extend_from_slice(&protection.to_le_bytes());
+            data.extend_from_slice(&ty.to_le_bytes());
+            data.extend_from_slice(&[0; 4]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_classify_address() {
+        let module = ("C:\\crash.exe".to_owned(), 0x1234);
+        let classify = |address| classify_address(address, None, &[], None, &[]).region;
+
+        assert_eq!(classify(0x45), MemoryRegion::NullPage);
+        assert_eq!(classify(0x7fff_0000), MemoryRegion::Unknown);
+
+        let crash_address = classify_address(0x40_1234, Some(module), &[], None, &[]);
+        assert_eq!(crash_address.region, MemoryRegion::Module);
+        assert_eq!(crash_address.module.as_deref(), Some("C:\\crash.exe"));
+        assert_eq!(crash_address.offset, Some(HexValue(0x1234)));
+
+        let stacks = [(0x7000_f000, 0x7001_0000)];
+        let sp = Some(0x7000_f000);
+        let classify = |address| classify_address(address, None, &stacks, sp, &[]).region;
+        assert_eq!(classify(0x7000_f800), MemoryRegion::Stack);
+        assert_eq!(classify(0x7000_eff8), MemoryRegion::StackGuard);
+        assert_eq!(classify(0x6000_0000), MemoryRegion::Unknown);
+    }
+
+    #[test]
+    fn test_classify_windows_regions() {
+        let data = memory_info_list(&[
+            (0x10000, 0x10000, MEM_COMMIT, 0x4, MEM_PRIVATE),
+            (0x100_0000, 0xc000, MEM_RESERVE, 0, MEM_PRIVATE),
+            (0x100_c000, 0x3000, MEM_COMMIT, 0x104, MEM_PRIVATE),
+            (0x100_f000, 0x1000, MEM_COMMIT, 0x4, MEM_PRIVATE),
+            (0x101_0000, 0x1000, MEM_COMMIT, 0x2, MEM_MAPPED),
+            (0x101_1000, 0x1_0000, MEM_FREE, 0x1, 0),
+            (0x200_0000, 0x1000, MEM_RESERVE, 0, MEM_PRIVATE),
+        ]);
+        let regions = parse_memory_info_list(&data);
+        assert_eq!(regions.len(), 7);
+        assert_eq!(
+            regions[2],
+            Region {
+                start: 0x100_c000,
+                end: 0x100_f000,
+                kind: RegionKind::Guard,
+            }
+        );
+
+        let stacks = [(0x100_f800, 0x101_0000)];
+        let classify = |address| classify_address(address, None, &stacks, None, &regions).region;
+        assert_eq!(classify(0x1_8000), MemoryRegion::Heap);
+        assert_eq!(classify(0x100_0000), MemoryRegion::StackGuard);
+        assert_eq!(classify(0x100_d000), MemoryRegion::StackGuard);
+        assert_eq!(classify(0x100_f100), MemoryRegion::Stack);
+        assert_eq!(classify(0x101_0010), MemoryRegion::Mapped);
+        assert_eq!(classify(0x101_2000), MemoryRegion::Unmapped);
+        assert_eq!(classify(0x200_0000), MemoryRegion::Unmapped);
+        assert_eq!(classify(0x300_0000), MemoryRegion::Unmapped);
+    }
+
+    #[test]
+    fn test_classify_linux_maps() {
+        let maps = b"\
+            55d0c5a00000-55d0c5a21000 rw-p 00000000 00:00 0          [heap]\n\
+            7f1c3f7ff000-7f1c3f800000 ---p 00000000 00:00 0 \n\
+            7f1c3f800000-7f1c40000000 rw-p 00000000 00:00 0 \n\
+            7f1c40000000-7f1c40001000 r--p 00000000 08:01 1316 /usr/share/locale/locale.alias\n\
+            7f1c41000000-7f1c41021000 rw-p 00000000 00:00 0 \n\
+            7ffd5a7f0000-7ffd5a811000 rw-p 00000000 00:00 0          [stack]\n";
+        let regions = parse_linux_maps(maps);
+        assert_eq!(regions.len(), 6);
+        assert_eq!(regions[0].kind, RegionKind::Heap);
+        assert_eq!(regions[5].kind, RegionKind::Stack);
+
+        let stacks = [(0x7f1c_3fffe000, 0x7f1c_40000000)];
+        let classify = |address| classify_address(address, None, &stacks, None, &regions).region;
+        assert_eq!(classify(0x55d0_c5a00100), MemoryRegion::Heap);
+        assert_eq!(classify(0x7f1c_3f7ff100), MemoryRegion::StackGuard);
+        assert_eq!(classify(0x7f1c_3f900000), MemoryRegion::Stack);
+        assert_eq!(classify(0x7f1c_40000010), MemoryRegion::Mapped);
+        assert_eq!(classify(0x7f1c_41000010), MemoryRegion::Heap);
+        assert_eq!(classify(0x7ffd_5a800000), MemoryRegion::Stack);
+        assert_eq!(classify(0x7f1c_50000000), MemoryRegion::Unmapped);
+    }
+}
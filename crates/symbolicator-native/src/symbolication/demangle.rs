@@ -1,7 +1,12 @@
-use symbolic::common::{Language, Name};
+use symbolic::common::{Language, Name, NameMangling};
 use symbolic::demangle::{Demangle, DemangleOptions};
 use symbolic::symcache::Function;
 
+use crate::interface::{
+    CompletedMetricKitResponse, CompletedSymbolicationResponse, DemanglingOptions,
+    DemanglingVerbosity, SymbolicatedFrame, SymbolicatedMetricKitFrame,
+};
+
 /// Options for demangling all symbols.
 pub const DEMANGLE_OPTIONS: DemangleOptions = DemangleOptions::complete().return_type(false);
 
@@ -25,6 +30,123 @@ pub fn demangle_symbol(cache: &DemangleCache, func: &Function) -> (String, Strin
     (key.0, entry.into_value())
 }
 
+impl DemanglingOptions {
+    /// Demangles the symbol with these options.
+    ///
+    /// Returns `None` if the symbol cannot be demangled.
+    pub fn demangle(&self, symbol: &str, language: Language) -> Option<String> {
+        let language = match language {
+            Language::Unknown => self.language.unwrap_or_default(),
+            language => language,
+        };
+        let options = match self.verbosity {
+            DemanglingVerbosity::Full => DemangleOptions::complete(),
+            DemanglingVerbosity::Standard => DEMANGLE_OPTIONS,
+            DemanglingVerbosity::Compact => DemangleOptions::name_only(),
+        };
+
+        let mut demangled = Name::new(symbol, NameMangling::Unknown, language).demangle(options)?;
+        if self.verbosity == DemanglingVerbosity::Compact {
+            demangled = strip_generic_args(&demangled);
+        }
+        if self.keep_hash {
+            if let Some(hash) = rust_legacy_hash(symbol) {
+                demangled = format!("{demangled}::{hash}");
+            }
+        }
+        Some(demangled)
+    }
+
+    /// Demangles the function of the frame again from its symbol.
+    fn apply(&self, frame: &mut SymbolicatedFrame) {
+        let raw = &mut frame.raw;
+        let Some(symbol) = raw.symbol.as_deref() else {
+            return;
+        };
+        if let Some(function) = self.demangle(symbol, raw.lang.unwrap_or_default()) {
+            raw.function = Some(function);
+        }
+    }
+}
+
+impl CompletedSymbolicationResponse {
+    /// Demangles the functions of all frames again, with the given options.
+    pub fn apply_demangling(&mut self, options: &DemanglingOptions) {
+        for thread in &mut self.stacktraces {
+            for frame in thread.frames.iter_mut().chain(&mut thread.async_frames) {
+                options.apply(frame);
+            }
+        }
+    }
+}
+
+impl CompletedMetricKitResponse {
+    /// Demangles the functions of all frames again, with the given options.
+    pub fn apply_demangling(&mut self, options: &DemanglingOptions) {
+        fn apply_to_tree(frames: &mut [SymbolicatedMetricKitFrame], options: &DemanglingOptions) {
+            for frame in frames {
+                for frame in &mut frame.frames {
+                    options.apply(frame);
+                }
+                apply_to_tree(&mut frame.sub_frames, options);
+            }
+        }
+
+        for diagnostic in &mut self.diagnostics {
+            for call_stack in &mut diagnostic.call_stacks {
+                apply_to_tree(&mut call_stack.root_frames, options);
+            }
+        }
+    }
+}
+
+/// Removes the template and generic arguments from a demangled name.
+///
+/// Angle brackets which do not follow a name, like in `<T as Trait>::method`, are kept. Names of
+/// operators with angle brackets are kept entirely, as their brackets are not balanced.
+fn strip_generic_args(name: &str) -> String {
+    if name.contains("operator<") || name.contains("operator>") {
+        return name.to_owned();
+    }
+
+    let mut stripped = String::with_capacity(name.len());
+    // Whether each of the currently open angle brackets is stripped.
+    let mut groups = Vec::new();
+    for c in name.chars() {
+        let is_stripping = groups.last() == Some(&true);
+        match c {
+            '<' => {
+                let strip = is_stripping
+                    || stripped.ends_with(|prev: char| prev.is_alphanumeric() || prev == '_');
+                if !strip {
+                    stripped.push(c);
+                }
+                groups.push(strip);
+            }
+            '>' => {
+                if groups.pop() != Some(true) {
+                    stripped.push(c);
+                }
+            }
+            _ if is_stripping => {}
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Returns the trailing hash of a Rust symbol in the legacy mangling scheme.
+///
+/// These symbols end with a path segment like `17h0123456789abcdefE`, from which the hash
+/// `h0123456789abcdef` is returned.
+fn rust_legacy_hash(symbol: &str) -> Option<&str> {
+    let symbol = symbol.strip_suffix('E')?;
+    let start = symbol.len().checked_sub(17)?;
+    let hash = symbol.get(start..)?;
+    let is_hash = hash.starts_with('h') && hash[1..].bytes().all(|b| b.is_ascii_hexdigit());
+    (is_hash && symbol[..start].ends_with("17")).then_some(hash)
+}
+
 #[allow(unused)] // we early return `symbol` here for now, but we might change that in the future
 fn report_demangling_failure(symbol: String, language: Language) -> String {
     return symbol;
@@ -52,3 +174,54 @@ fn report_demangling_failure(symbol: String, language: Language) -> String {
     }
     symbol
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_generic_args() {
+        assert_eq!(
+            strip_generic_args("std::vector<int, std::allocator<int> >::push_back"),
+            "std::vector::push_back"
+        );
+        assert_eq!(
+            strip_generic_args("<alloc::vec::Vec<T> as core::clone::Clone>::clone"),
+            "<alloc::vec::Vec as core::clone::Clone>::clone"
+        );
+        assert_eq!(
+            strip_generic_args("Foo::operator<<(int)"),
+            "Foo::operator<<(int)"
+        );
+    }
+
+    #[test]
+    fn test_rust_legacy_hash() {
+        let symbol = "_ZN3foo3bar17h0123456789abcdefE";
+        assert_eq!(rust_legacy_hash(symbol), Some("h0123456789abcdef"));
+        assert_eq!(rust_legacy_hash("_ZN3foo3barE"), None);
+        assert_eq!(rust_legacy_hash("_RNvCs1234_3foo3bar"), None);
+    }
+
+    #[test]
+    fn test_demangle_keep_hash() {
+        let symbol = "_ZN3foo3bar17h0123456789abcdefE";
+        let options = DemanglingOptions {
+            language: Some(Language::Rust),
+            ..DemanglingOptions::default()
+        };
+        assert_eq!(
+            options.demangle(symbol, Language::Unknown).as_deref(),
+            Some("foo::bar")
+        );
+
+        let options = DemanglingOptions {
+            keep_hash: true,
+            ..options
+        };
+        assert_eq!(
+            options.demangle(symbol, Language::Rust).as_deref(),
+            Some("foo::bar::h0123456789abcdef")
+        );
+    }
+}
@@ -21,6 +21,7 @@ pub fn symbolicate_dotnet_frame(
     let result = SymbolicatedFrame {
         status: FrameStatus::Symbolicated,
         original_index: Some(index),
+        inexact: false,
         raw: RawFrame {
             lang: Some(line_info.file_lang),
             filename: Some(filename.to_string()),
@@ -0,0 +1,275 @@
+//! Heuristic rating of the exploitability of crashes.
+//!
+//! The rating is derived from the crash reason, the memory region of the crash address, and the
+//! symbolicated frames of the crashing thread. It is meant to prioritize crashes for triage, and
+//! is neither a proof of exploitability nor of its absence.
+
+use crate::interface::{
+    CompletedSymbolicationResponse, Exploitability, ExploitabilityReason, FrameStatus, FrameTrust,
+    MemoryRegion, SymbolicatedFrame,
+};
+
+use super::crash_address::NULL_PAGE_SIZE;
+
+/// The kind of memory access which caused a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+    Execute,
+    Unknown,
+}
+
+/// The type of a crash, as given by its crash reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CrashType {
+    Access(Access),
+    Other(ExploitabilityReason),
+    Unknown,
+}
+
+/// Rates the exploitability of a crashed minidump.
+///
+/// Returns `None` if the process did not crash, or if nothing is known about the crash.
+pub(super) fn analyze_exploitability(
+    response: &CompletedSymbolicationResponse,
+) -> Option<Exploitability> {
+    if response.crashed != Some(true) {
+        return None;
+    }
+
+    let mut reasons = Vec::new();
+    let crash_type = match response.crash_reason.as_deref() {
+        Some(crash_reason) => parse_crash_type(crash_reason),
+        None => CrashType::Unknown,
+    };
+    let crash_address = response.crash_address.as_ref();
+    let frames = response
+        .stacktraces
+        .iter()
+        .find(|thread| thread.is_requesting == Some(true))
+        .map_or(&[][..], |thread| &thread.frames);
+    let instruction_addr = frames.first().map(|frame| frame.raw.instruction_addr.0);
+
+    match crash_type {
+        CrashType::Access(access) => {
+            let address = crash_address.map(|crash_address| crash_address.address.0);
+            let jumped_to_address = address.is_some() && address == instruction_addr;
+
+            let reason = match crash_address.map(|crash_address| crash_address.region) {
+                Some(MemoryRegion::NullPage) => ExploitabilityReason::NullDereference,
+                Some(MemoryRegion::StackGuard) => ExploitabilityReason::StackOverflow,
+                _ if access == Access::Execute || jumped_to_address => {
+                    ExploitabilityReason::InvalidExecute
+                }
+                _ => match access {
+                    Access::Read => ExploitabilityReason::InvalidRead,
+                    Access::Write => ExploitabilityReason::InvalidWrite,
+                    Access::Execute | Access::Unknown => ExploitabilityReason::BadAccess,
+                },
+            };
+            reasons.push(reason);
+        }
+        CrashType::Other(reason) => reasons.push(reason),
+        CrashType::Unknown => {}
+    }
+
+    // Frames in the null page are rated by the crash address instead.
+    let outside_modules = |frame: &SymbolicatedFrame| {
+        frame.status == FrameStatus::UnknownImage && frame.raw.instruction_addr.0 >= NULL_PAGE_SIZE
+    };
+
+    if frames.first().is_some_and(outside_modules)
+        && !reasons.contains(&ExploitabilityReason::InvalidExecute)
+    {
+        reasons.push(ExploitabilityReason::InstructionPointerOutsideModules);
+    }
+
+    // Scanned frames are only accepted if they point into a module, and are left out here.
+    let has_corrupted_return_address = frames.iter().skip(1).any(|frame| {
+        outside_modules(frame) && matches!(frame.raw.trust, FrameTrust::Cfi | FrameTrust::Fp)
+    });
+    if has_corrupted_return_address {
+        reasons.push(ExploitabilityReason::CorruptedReturnAddress);
+    }
+
+    let rating = reasons.iter().map(|reason| reason.rating()).max()?;
+    Some(Exploitability { rating, reasons })
+}
+
+/// Determines the type of a crash from its crash reason.
+///
+/// Crash reasons are formatted like `EXCEPTION_ACCESS_VIOLATION_WRITE / 0x45` or
+/// `EXC_BAD_ACCESS / KERN_INVALID_ADDRESS / 0x45`, depending on the platform.
+fn parse_crash_type(crash_reason: &str) -> CrashType {
+    let mut parts = crash_reason.split(" / ");
+    let Some(kind) = parts.next() else {
+        return CrashType::Unknown;
+    };
+    if kind == "SIGABRT" || parts.any(|part| part == "SIGABRT") {
+        return CrashType::Other(ExploitabilityReason::Abort);
+    }
+
+    match kind {
+        "EXCEPTION_ACCESS_VIOLATION_READ" => CrashType::Access(Access::Read),
+        "EXCEPTION_ACCESS_VIOLATION_WRITE" => CrashType::Access(Access::Write),
+        "EXCEPTION_ACCESS_VIOLATION_EXEC" => CrashType::Access(Access::Execute),
+        "EXCEPTION_ACCESS_VIOLATION"
+        | "EXCEPTION_IN_PAGE_ERROR"
+        | "SIGSEGV"
+        | "SIGBUS"
+        | "EXC_BAD_ACCESS" => CrashType::Access(Access::Unknown),
+        "EXCEPTION_STACK_OVERFLOW" => CrashType::Other(ExploitabilityReason::StackOverflow),
+        "EXCEPTION_ILLEGAL_INSTRUCTION"
+        | "EXCEPTION_PRIV_INSTRUCTION"
+        | "SIGILL"
+        | "EXC_BAD_INSTRUCTION" => CrashType::Other(ExploitabilityReason::IllegalInstruction),
+        "EXCEPTION_HEAP_CORRUPTION" => CrashType::Other(ExploitabilityReason::HeapCorruption),
+        "EXCEPTION_STACK_BUFFER_OVERRUN" => {
+            CrashType::Other(ExploitabilityReason::StackBufferOverrun)
+        }
+        "EXCEPTION_BREAKPOINT" | "SIGTRAP" | "EXC_BREAKPOINT" => {
+            CrashType::Other(ExploitabilityReason::Breakpoint)
+        }
+        "SIGFPE" | "EXC_ARITHMETIC" | "EXCEPTION_INT_DIVIDE_BY_ZERO" | "EXCEPTION_INT_OVERFLOW" => {
+            CrashType::Other(ExploitabilityReason::Arithmetic)
+        }
+        kind if kind.starts_with("EXCEPTION_FLT_") => {
+            CrashType::Other(ExploitabilityReason::Arithmetic)
+        }
+        _ => CrashType::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_service::utils::hex::HexValue;
+
+    use crate::interface::{CompleteStacktrace, CrashAddress, ExploitabilityRating, RawFrame};
+
+    use super::*;
+
+    fn frame(instruction_addr: u64, status: FrameStatus, trust: FrameTrust) -> SymbolicatedFrame {
+        SymbolicatedFrame {
+            status,
+            raw: RawFrame {
+                instruction_addr: HexValue(instruction_addr),
+                trust,
+                ..RawFrame::default()
+            },
+            ..SymbolicatedFrame::default()
+        }
+    }
+
+    fn crashed_response(
+        crash_reason: &str,
+        address: u64,
+        region: MemoryRegion,
+        frames: Vec<SymbolicatedFrame>,
+    ) -> CompletedSymbolicationResponse {
+        CompletedSymbolicationResponse {
+            crashed: Some(true),
+            crash_reason: Some(format!("{crash_reason} / {address:#x}")),
+            crash_address: Some(CrashAddress {
+                address: HexValue(address),
+                region,
+                module: None,
+                offset: None,
+            }),
+            stacktraces: vec![CompleteStacktrace {
+                is_requesting: Some(true),
+                frames,
+                ..CompleteStacktrace::default()
+            }],
+            ..CompletedSymbolicationResponse::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_crash_type() {
+        assert_eq!(
+            parse_crash_type("EXCEPTION_ACCESS_VIOLATION_WRITE / 0x45"),
+            CrashType::Access(Access::Write)
+        );
+        assert_eq!(
+            parse_crash_type("EXC_BAD_ACCESS / KERN_INVALID_ADDRESS / 0x45"),
+            CrashType::Access(Access::Unknown)
+        );
+        assert_eq!(
+            parse_crash_type("EXC_CRASH / SIGABRT / 0x0"),
+            CrashType::Other(ExploitabilityReason::Abort)
+        );
+        assert_eq!(
+            parse_crash_type("EXCEPTION_FLT_DIVIDE_BY_ZERO / 0x401000"),
+            CrashType::Other(ExploitabilityReason::Arithmetic)
+        );
+        assert_eq!(parse_crash_type(""), CrashType::Unknown);
+    }
+
+    #[test]
+    fn test_null_dereference() {
+        let frames = vec![frame(0x401000, FrameStatus::Missing, FrameTrust::Context)];
+        let response = crashed_response(
+            "EXCEPTION_ACCESS_VIOLATION_WRITE",
+            0x45,
+            MemoryRegion::NullPage,
+            frames,
+        );
+
+        let exploitability = analyze_exploitability(&response).unwrap();
+        assert_eq!(exploitability.rating, ExploitabilityRating::Low);
+        assert_eq!(
+            exploitability.reasons,
+            [ExploitabilityReason::NullDereference]
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let frames = vec![
+            frame(0x4141_4141, FrameStatus::UnknownImage, FrameTrust::Context),
+            frame(0x401000, FrameStatus::Symbolicated, FrameTrust::Scan),
+        ];
+        let response = crashed_response(
+            "EXCEPTION_ACCESS_VIOLATION_READ",
+            0x4141_4141,
+            MemoryRegion::Unmapped,
+            frames,
+        );
+
+        let exploitability = analyze_exploitability(&response).unwrap();
+        assert_eq!(exploitability.rating, ExploitabilityRating::High);
+        assert_eq!(
+            exploitability.reasons,
+            [ExploitabilityReason::InvalidExecute]
+        );
+    }
+
+    #[test]
+    fn test_corrupted_return_address() {
+        let frames = vec![
+            frame(0x401000, FrameStatus::Symbolicated, FrameTrust::Context),
+            frame(0x4141_4141, FrameStatus::UnknownImage, FrameTrust::Cfi),
+        ];
+        let response = crashed_response("SIGSEGV", 0x7f00_0000_0000, MemoryRegion::Heap, frames);
+
+        let exploitability = analyze_exploitability(&response).unwrap();
+        assert_eq!(exploitability.rating, ExploitabilityRating::High);
+        assert_eq!(
+            exploitability.reasons,
+            [
+                ExploitabilityReason::BadAccess,
+                ExploitabilityReason::CorruptedReturnAddress
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_crashed() {
+        let response = CompletedSymbolicationResponse {
+            crashed: Some(false),
+            ..CompletedSymbolicationResponse::default()
+        };
+        assert_eq!(analyze_exploitability(&response), None);
+    }
+}
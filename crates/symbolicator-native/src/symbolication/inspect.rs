@@ -0,0 +1,62 @@
+//! Inspection of the features of a debug file.
+
+use symbolic::common::Arch;
+use symbolicator_service::caching::CacheError;
+use symbolicator_service::objects::{FindObject, FindResult, ObjectPurpose};
+use symbolicator_sources::FileType;
+
+use crate::interface::{InspectObject, InspectedObject};
+
+use super::module_lookup::object_file_status_from_cache_entry;
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Fetches a debug file and reports its features.
+    ///
+    /// All file types are looked up, and the file that is best suited for symbolication is
+    /// inspected. This allows to check uploaded files without symbolicating a crash.
+    pub async fn inspect_object(&self, request: InspectObject) -> InspectedObject {
+        let InspectObject {
+            scope,
+            sources,
+            identifier,
+        } = request;
+
+        let FindResult {
+            meta, candidates, ..
+        } = self
+            .objects
+            .find(FindObject {
+                filetypes: FileType::all(),
+                purpose: ObjectPurpose::Debug,
+                scope,
+                identifier,
+                sources,
+            })
+            .await;
+
+        let (object, features) = match meta.map(|meta| meta.handle) {
+            Some(Ok(handle)) => {
+                let features = handle.features();
+                (self.objects.fetch(handle).await, features)
+            }
+            Some(Err(error)) => (Err(error), Default::default()),
+            None => (Err(CacheError::NotFound), Default::default()),
+        };
+
+        let mut inspected = InspectedObject {
+            status: object_file_status_from_cache_entry(&object),
+            features,
+            arch: Arch::Unknown,
+            file_format: None,
+            size: None,
+            candidates,
+        };
+        if let Ok(object) = object {
+            inspected.arch = object.object().arch();
+            inspected.file_format = Some(object.object().file_format().to_string());
+            inspected.size = Some(object.data().len() as u64);
+        }
+        inspected
+    }
+}
@@ -0,0 +1,385 @@
+//! Parsing of the JSON `.ips` crash reports written since macOS 12 and iOS 15.
+//!
+//! An `.ips` file consists of a single-line JSON header, followed by the JSON crash report.
+//! See <https://developer.apple.com/documentation/xcode/interpreting-the-json-format-of-a-crash-report>.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use symbolic::common::{Arch, CodeId, DebugId};
+use symbolicator_service::types::RawObjectInfo;
+use symbolicator_service::utils::hex::HexValue;
+use symbolicator_sources::ObjectType;
+
+use crate::interface::{CompleteObjectInfo, RawFrame, RawStacktrace, Registers, SystemInfo};
+
+use super::apple::{AppleCrashReportState, ParsedCrashReport};
+
+/// The `bug_type` of crash reports, as opposed to hangs or other diagnostics.
+const BUG_TYPE_CRASH: &str = "309";
+
+#[derive(Debug, Deserialize)]
+struct IpsHeader {
+    bug_type: Option<String>,
+    timestamp: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IpsReport {
+    capture_time: Option<String>,
+    os_version: Option<IpsOsVersion>,
+    model_code: Option<String>,
+    cpu_type: Option<String>,
+    exception: Option<IpsException>,
+    /// Application specific information, by image name.
+    #[serde(default)]
+    asi: BTreeMap<String, Vec<String>>,
+    faulting_thread: Option<usize>,
+    #[serde(default)]
+    threads: Vec<IpsThread>,
+    #[serde(default)]
+    used_images: Vec<IpsImage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpsOsVersion {
+    /// The name and version of the OS, such as `macOS 12.3.1`.
+    train: Option<String>,
+    build: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpsException {
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    signal: Option<String>,
+    subtype: Option<String>,
+    codes: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IpsThread {
+    id: Option<u64>,
+    name: Option<String>,
+    queue: Option<String>,
+    #[serde(default)]
+    triggered: bool,
+    /// The registers as `{"value": ...}` objects by name, and `x` for the general purpose
+    /// registers of ARM.
+    #[serde(default)]
+    thread_state: BTreeMap<String, Value>,
+    #[serde(default)]
+    frames: Vec<IpsFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IpsFrame {
+    image_offset: u64,
+    image_index: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpsImage {
+    base: u64,
+    #[serde(default)]
+    size: u64,
+    uuid: Option<String>,
+    path: Option<String>,
+    name: Option<String>,
+}
+
+/// Returns `true` if the crash report is in the JSON `.ips` format rather than the text format.
+pub(super) fn is_ips_crash_report(data: &[u8]) -> bool {
+    data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+}
+
+/// Parses a crash report in the JSON `.ips` format.
+///
+/// The header line is optional, in which case the report is not checked to be a crash report.
+pub(super) fn parse_ips_crash_report(data: &[u8]) -> Result<ParsedCrashReport> {
+    let mut documents = serde_json::Deserializer::from_slice(data).into_iter::<Value>();
+    let first = documents.next().context("empty crash report")??;
+    let (header, report) = match documents.next().transpose()? {
+        Some(report) => (Some(serde_json::from_value::<IpsHeader>(first)?), report),
+        None => (None, first),
+    };
+
+    if let Some(bug_type) = header.as_ref().and_then(|h| h.bug_type.as_deref()) {
+        if bug_type != BUG_TYPE_CRASH {
+            bail!("unsupported report type {bug_type}");
+        }
+    }
+
+    let IpsReport {
+        capture_time,
+        os_version,
+        model_code,
+        cpu_type,
+        exception,
+        asi,
+        faulting_thread,
+        threads,
+        used_images,
+    } = serde_json::from_value(report)?;
+
+    let modules = used_images.iter().filter_map(map_ips_image).collect();
+
+    let stacktraces = threads
+        .into_iter()
+        .enumerate()
+        .map(|(index, thread)| {
+            let frames = thread
+                .frames
+                .iter()
+                .map(|frame| {
+                    let image = used_images.get(frame.image_index);
+                    let image_addr = image.map_or(0, |image| image.base);
+                    RawFrame {
+                        instruction_addr: HexValue(image_addr.wrapping_add(frame.image_offset)),
+                        package: image.and_then(|image| image.name.clone()),
+                        ..RawFrame::default()
+                    }
+                })
+                .collect();
+
+            RawStacktrace {
+                thread_id: thread.id,
+                thread_name: thread.name.or(thread.queue),
+                is_requesting: Some(thread.triggered || faulting_thread == Some(index)),
+                registers: map_thread_state(&thread.thread_state),
+                frames,
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let mut system_info = SystemInfo {
+        device_model: model_code.unwrap_or_default(),
+        cpu_arch: cpu_type.as_deref().map(parse_cpu_type).unwrap_or_default(),
+        ..SystemInfo::default()
+    };
+    if let Some(os_version) = os_version {
+        let train = os_version.train.unwrap_or_default();
+        // The OS name may consist of multiple words, such as `iPhone OS 15.4`.
+        match train.rsplit_once(' ') {
+            Some((name, version)) => {
+                system_info.os_name = name.to_owned();
+                system_info.os_version = version.to_owned();
+            }
+            None => system_info.os_name = train,
+        }
+        system_info.os_build = os_version.build.unwrap_or_default();
+    }
+
+    let timestamp = capture_time
+        .or_else(|| header.and_then(|header| header.timestamp))
+        .and_then(|timestamp| parse_timestamp(&timestamp));
+
+    let (crash_reason, crash_details) = match exception {
+        Some(exception) => {
+            let crash_reason = match (exception.ty, exception.signal) {
+                (Some(ty), Some(signal)) => Some(format!("{ty} ({signal})")),
+                (ty, signal) => ty.or(signal),
+            };
+            let asi: Vec<_> = asi.into_values().flatten().collect();
+            let crash_details = Some(asi.join("\n"))
+                .filter(|asi| !asi.is_empty())
+                .or(exception.message)
+                .or(exception.subtype)
+                .or(exception.codes);
+            (crash_reason, crash_details)
+        }
+        None => (None, None),
+    };
+
+    let state = AppleCrashReportState {
+        timestamp,
+        system_info,
+        crash_reason,
+        crash_details,
+    };
+
+    Ok((modules, stacktraces, state))
+}
+
+fn parse_cpu_type(cpu_type: &str) -> Arch {
+    match cpu_type {
+        "X86-64" => Arch::Amd64,
+        "ARM-64" => Arch::Arm64,
+        "X86" => Arch::X86,
+        "ARM" => Arch::Arm,
+        _ => cpu_type.parse().unwrap_or_default(),
+    }
+}
+
+/// Parses timestamps like `2022-05-04 10:32:45.8857 +0200`.
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f %z")
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+fn map_thread_state(thread_state: &BTreeMap<String, Value>) -> Registers {
+    let register_value = |value: &Value| value.get("value")?.as_u64();
+
+    let mut registers = Registers::new();
+    for (name, value) in thread_state {
+        match value {
+            Value::Array(values) if name == "x" => {
+                for (index, value) in values.iter().enumerate() {
+                    if let Some(value) = register_value(value) {
+                        registers.insert(format!("x{index}"), HexValue(value));
+                    }
+                }
+            }
+            _ => {
+                if let Some(value) = register_value(value) {
+                    registers.insert(name.clone(), HexValue(value));
+                }
+            }
+        }
+    }
+    registers
+}
+
+/// Maps a used image, skipping placeholders without a UUID.
+fn map_ips_image(image: &IpsImage) -> Option<CompleteObjectInfo> {
+    let debug_id: DebugId = image.uuid.as_deref()?.parse().ok()?;
+    if debug_id.is_nil() {
+        return None;
+    }
+    let code_id = CodeId::from_binary(&debug_id.uuid().as_bytes()[..]);
+
+    let raw_info = RawObjectInfo {
+        ty: ObjectType::Macho,
+        code_id: Some(code_id.to_string()),
+        code_file: image.path.clone(),
+        debug_id: Some(debug_id.to_string()),
+        debug_file: image.path.clone(),
+        debug_checksum: None,
+        image_addr: HexValue(image.base),
+        image_size: match image.size {
+            0 => None,
+            size => Some(size),
+        },
+    };
+
+    Some(raw_info.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_test::read_fixture;
+
+    use super::*;
+
+    #[test]
+    fn test_is_ips_crash_report() {
+        assert!(is_ips_crash_report(&read_fixture("apple_crash_report.ips")));
+        assert!(!is_ips_crash_report(&read_fixture(
+            "apple_crash_report.txt"
+        )));
+    }
+
+    #[test]
+    fn test_parse_ips_crash_report() {
+        let data = read_fixture("apple_crash_report.ips");
+        let (modules, stacktraces, state) = parse_ips_crash_report(&data).unwrap();
+
+        // The placeholder image without a UUID is skipped.
+        assert_eq!(modules.len(), 3);
+        let module = &modules[0].raw;
+        assert_eq!(
+            module.debug_id.as_deref(),
+            Some("2d903291-397d-3d14-bfca-52c7fb8c5e00")
+        );
+        assert_eq!(
+            module.code_id.as_deref(),
+            Some("2d903291397d3d14bfca52c7fb8c5e00")
+        );
+        assert_eq!(module.image_addr, HexValue(0x10864e000));
+
+        assert_eq!(stacktraces.len(), 2);
+        let main_thread = &stacktraces[0];
+        assert_eq!(
+            main_thread.thread_name.as_deref(),
+            Some("com.apple.main-thread")
+        );
+        assert_eq!(main_thread.is_requesting, Some(false));
+        assert_eq!(
+            main_thread.frames[0].instruction_addr,
+            HexValue(0x7fff61bc6c2a)
+        );
+        assert_eq!(
+            main_thread.frames[0].package.as_deref(),
+            Some("libsystem_kernel.dylib")
+        );
+
+        let crashed_thread = &stacktraces[1];
+        assert_eq!(crashed_thread.thread_id, Some(1686650));
+        assert_eq!(crashed_thread.is_requesting, Some(true));
+        assert_eq!(crashed_thread.registers["rip"], HexValue(0x1090a0132));
+        assert!(!crashed_thread.registers.contains_key("flavor"));
+
+        assert_eq!(state.system_info.os_name, "macOS");
+        assert_eq!(state.system_info.os_version, "12.3.1");
+        assert_eq!(state.system_info.os_build, "21E258");
+        assert_eq!(state.system_info.device_model, "MacBookPro14,3");
+        assert_eq!(state.system_info.cpu_arch, Arch::Amd64);
+        assert_eq!(
+            state.timestamp.unwrap().to_rfc3339(),
+            "2022-05-04T10:32:45.885700+00:00"
+        );
+        assert_eq!(
+            state.crash_reason.as_deref(),
+            Some("EXC_BAD_ACCESS (SIGSEGV)")
+        );
+        assert_eq!(
+            state.crash_details.as_deref(),
+            Some("objc_msgSend() selector name: respondsToSelector:")
+        );
+    }
+
+    #[test]
+    fn test_parse_arm64_thread_state() {
+        let thread_state = serde_json::from_str(
+            r#"{
+                "x": [{"value": 1}, {"value": 2}],
+                "fp": {"value": 3},
+                "pc": {"value": 4, "matchesCrashFrame": 1},
+                "flavor": "ARM_THREAD_STATE64"
+            }"#,
+        )
+        .unwrap();
+        let registers = map_thread_state(&thread_state);
+
+        let registers: Vec<_> = registers
+            .into_iter()
+            .map(|(name, value)| (name, value.0))
+            .collect();
+        assert_eq!(
+            registers,
+            [
+                ("fp".to_owned(), 3),
+                ("pc".to_owned(), 4),
+                ("x0".to_owned(), 1),
+                ("x1".to_owned(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_report_type() {
+        let data = br#"{"bug_type":"288"}
+{"threads":[]}"#;
+        let err = parse_ips_crash_report(data).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported report type 288");
+    }
+}
@@ -0,0 +1,410 @@
+//! Symbolication of MetricKit diagnostic payloads.
+//!
+//! MetricKit reports the call stacks of crashes, hangs and other diagnostics as trees of
+//! frames, which identify their binary by its UUID and the offset into its text segment.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use symbolic::common::{CodeId, DebugId};
+use symbolicator_service::types::RawObjectInfo;
+use symbolicator_service::utils::hex::HexValue;
+use symbolicator_sources::ObjectType;
+
+use crate::interface::{
+    CompleteObjectInfo, CompletedMetricKitResponse, MetricKitDiagnostic, MetricKitDiagnosticType,
+    MetricKitFrame, MetricKitPayload, RawFrame, RawStacktrace, SymbolicateMetricKitPayload,
+    SymbolicateStacktraces, SymbolicatedFrame, SymbolicatedMetricKitCallStack,
+    SymbolicatedMetricKitDiagnostic, SymbolicatedMetricKitFrame,
+};
+use crate::metrics::StacktraceOrigin;
+
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Symbolicates the call stack trees of a MetricKit diagnostic payload.
+    ///
+    /// Every node of the trees is symbolicated as a stack frame, and the resulting frames are
+    /// added to the node in the response.
+    pub async fn symbolicate_metrickit(
+        &self,
+        request: SymbolicateMetricKitPayload,
+    ) -> Result<CompletedMetricKitResponse> {
+        let SymbolicateMetricKitPayload {
+            platform,
+            scope,
+            sources,
+            payload,
+            apply_source_context,
+            scraping,
+        } = request;
+
+        let mut diagnostics = map_payload(payload);
+
+        let mut modules = BTreeMap::new();
+        let mut stacktraces = Vec::new();
+        for diagnostic in &diagnostics {
+            for call_stack in &diagnostic.call_stacks {
+                let mut frames = Vec::new();
+                collect_frames(
+                    &call_stack.root_frames,
+                    true,
+                    diagnostic.call_stack_per_thread,
+                    &mut frames,
+                    &mut modules,
+                );
+                stacktraces.push(RawStacktrace {
+                    frames,
+                    ..Default::default()
+                });
+            }
+        }
+
+        let request = SymbolicateStacktraces {
+            platform,
+            modules: modules.into_values().collect(),
+            scope,
+            sources,
+            origin: StacktraceOrigin::MetricKit,
+            signal: None,
+            stacktraces,
+            apply_source_context,
+            scraping,
+        };
+        let response = self.symbolicate(request).await?;
+
+        // The stack traces are returned in the order in which the call stacks were collected.
+        let mut stacktraces = response.stacktraces.into_iter();
+        for diagnostic in &mut diagnostics {
+            for call_stack in &mut diagnostic.call_stacks {
+                let Some(stacktrace) = stacktraces.next() else {
+                    break;
+                };
+                let mut symbolicated = BTreeMap::<_, Vec<_>>::new();
+                for frame in stacktrace.frames {
+                    if let Some(index) = frame.original_index {
+                        symbolicated.entry(index).or_default().push(frame);
+                    }
+                }
+                fill_frames(&mut call_stack.root_frames, &mut symbolicated, &mut 0);
+            }
+        }
+
+        Ok(CompletedMetricKitResponse {
+            diagnostics,
+            modules: response.modules,
+        })
+    }
+}
+
+/// Maps the diagnostics of a payload, without symbolicating their frames yet.
+fn map_payload(payload: MetricKitPayload) -> Vec<SymbolicatedMetricKitDiagnostic> {
+    let MetricKitPayload {
+        crash_diagnostics,
+        hang_diagnostics,
+        cpu_exception_diagnostics,
+        disk_write_exception_diagnostics,
+        app_launch_diagnostics,
+    } = payload;
+
+    [
+        (MetricKitDiagnosticType::Crash, crash_diagnostics),
+        (MetricKitDiagnosticType::Hang, hang_diagnostics),
+        (
+            MetricKitDiagnosticType::CpuException,
+            cpu_exception_diagnostics,
+        ),
+        (
+            MetricKitDiagnosticType::DiskWriteException,
+            disk_write_exception_diagnostics,
+        ),
+        (MetricKitDiagnosticType::AppLaunch, app_launch_diagnostics),
+    ]
+    .into_iter()
+    .flat_map(|(ty, diagnostics)| {
+        diagnostics
+            .into_iter()
+            .map(move |diagnostic| map_diagnostic(ty, diagnostic))
+    })
+    .collect()
+}
+
+fn map_diagnostic(
+    ty: MetricKitDiagnosticType,
+    diagnostic: MetricKitDiagnostic,
+) -> SymbolicatedMetricKitDiagnostic {
+    let tree = diagnostic.call_stack_tree;
+    let call_stacks = tree
+        .call_stacks
+        .into_iter()
+        .map(|call_stack| SymbolicatedMetricKitCallStack {
+            thread_attributed: call_stack.thread_attributed,
+            root_frames: map_frames(call_stack.call_stack_root_frames),
+        })
+        .collect();
+
+    SymbolicatedMetricKitDiagnostic {
+        ty,
+        metadata: diagnostic.diagnostic_meta_data,
+        call_stack_per_thread: tree.call_stack_per_thread,
+        call_stacks,
+    }
+}
+
+fn map_frames(frames: Vec<MetricKitFrame>) -> Vec<SymbolicatedMetricKitFrame> {
+    frames
+        .into_iter()
+        .map(|frame| SymbolicatedMetricKitFrame {
+            binary_uuid: frame.binary_uuid,
+            binary_name: frame.binary_name,
+            address: HexValue(frame.address),
+            offset_into_binary_text_segment: HexValue(frame.offset_into_binary_text_segment),
+            sample_count: frame.sample_count,
+            frames: Vec::new(),
+            sub_frames: map_frames(frame.sub_frames),
+        })
+        .collect()
+}
+
+/// Flattens the nodes of a call stack tree into stack frames, in depth-first order.
+///
+/// Trees with one call stack per thread are rooted at the active frame of the thread and
+/// list its callers as sub frames, so all but the root frames are return addresses. Trees
+/// aggregated from samples are rooted at the outermost callers instead, so all frames that
+/// have sub frames are return addresses.
+fn collect_frames(
+    frames: &[SymbolicatedMetricKitFrame],
+    is_root: bool,
+    per_thread: bool,
+    raw_frames: &mut Vec<RawFrame>,
+    modules: &mut BTreeMap<DebugId, CompleteObjectInfo>,
+) {
+    for frame in frames {
+        if let Some(debug_id) = parse_binary_uuid(frame) {
+            if let Entry::Vacant(entry) = modules.entry(debug_id) {
+                if let Some(module) = map_module(frame, debug_id) {
+                    entry.insert(module);
+                }
+            }
+        }
+
+        let is_return_address = if per_thread {
+            !is_root
+        } else {
+            !frame.sub_frames.is_empty()
+        };
+        raw_frames.push(RawFrame {
+            instruction_addr: frame.address,
+            adjust_instruction_addr: Some(is_return_address),
+            package: frame.binary_name.clone(),
+            ..Default::default()
+        });
+
+        collect_frames(&frame.sub_frames, false, per_thread, raw_frames, modules);
+    }
+}
+
+/// Adds the symbolicated stack frames to the nodes, in the order of [`collect_frames`].
+fn fill_frames(
+    frames: &mut [SymbolicatedMetricKitFrame],
+    symbolicated: &mut BTreeMap<usize, Vec<SymbolicatedFrame>>,
+    index: &mut usize,
+) {
+    for frame in frames {
+        frame.frames = symbolicated.remove(index).unwrap_or_default();
+        *index += 1;
+        fill_frames(&mut frame.sub_frames, symbolicated, index);
+    }
+}
+
+fn parse_binary_uuid(frame: &SymbolicatedMetricKitFrame) -> Option<DebugId> {
+    let debug_id: DebugId = frame.binary_uuid.as_deref()?.parse().ok()?;
+    (!debug_id.is_nil()).then_some(debug_id)
+}
+
+/// Maps the binary of a frame, which is loaded at the frame's offset below its address.
+fn map_module(frame: &SymbolicatedMetricKitFrame, debug_id: DebugId) -> Option<CompleteObjectInfo> {
+    let image_addr = frame
+        .address
+        .0
+        .checked_sub(frame.offset_into_binary_text_segment.0)?;
+    let code_id = CodeId::from_binary(&debug_id.uuid().as_bytes()[..]);
+
+    let raw_info = RawObjectInfo {
+        ty: ObjectType::Macho,
+        code_id: Some(code_id.to_string()),
+        code_file: frame.binary_name.clone(),
+        debug_id: Some(debug_id.to_string()),
+        debug_file: frame.binary_name.clone(),
+        debug_checksum: None,
+        image_addr: HexValue(image_addr),
+        image_size: None,
+    };
+
+    Some(raw_info.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::FrameStatus;
+
+    use super::*;
+
+    const PAYLOAD: &str = r#"{
+        "timeStampBegin": "2024-01-15 10:00:00",
+        "timeStampEnd": "2024-01-16 10:00:00",
+        "crashDiagnostics": [{
+            "version": "1.0.0",
+            "callStackTree": {
+                "callStackPerThread": true,
+                "callStacks": [{
+                    "threadAttributed": true,
+                    "callStackRootFrames": [{
+                        "binaryUUID": "2B4E1F6F-1E2D-3B5A-9C8D-7E6F5A4B3C2D",
+                        "offsetIntoBinaryTextSegment": 4660,
+                        "sampleCount": 1,
+                        "binaryName": "App",
+                        "address": 4295037492,
+                        "subFrames": [{
+                            "binaryUUID": "9A8B7C6D-5E4F-3A2B-1C0D-E9F8A7B6C5D4",
+                            "offsetIntoBinaryTextSegment": 8192,
+                            "sampleCount": 1,
+                            "binaryName": "libdyld.dylib",
+                            "address": 6442459136
+                        }]
+                    }]
+                }]
+            },
+            "diagnosticMetaData": {
+                "osVersion": "iPhone OS 17.2 (21C62)",
+                "signal": 11
+            }
+        }],
+        "hangDiagnostics": [{
+            "callStackTree": {
+                "callStackPerThread": false,
+                "callStacks": [{
+                    "threadAttributed": true,
+                    "callStackRootFrames": [{
+                        "binaryUUID": "9A8B7C6D-5E4F-3A2B-1C0D-E9F8A7B6C5D4",
+                        "offsetIntoBinaryTextSegment": 8192,
+                        "sampleCount": 20,
+                        "binaryName": "libdyld.dylib",
+                        "address": 6442459136,
+                        "subFrames": [{
+                            "binaryName": "???",
+                            "address": 4096,
+                            "sampleCount": 20
+                        }]
+                    }]
+                }]
+            }
+        }]
+    }"#;
+
+    fn collect_call_stack(
+        diagnostic: &SymbolicatedMetricKitDiagnostic,
+        modules: &mut BTreeMap<DebugId, CompleteObjectInfo>,
+    ) -> Vec<RawFrame> {
+        let mut frames = Vec::new();
+        collect_frames(
+            &diagnostic.call_stacks[0].root_frames,
+            true,
+            diagnostic.call_stack_per_thread,
+            &mut frames,
+            modules,
+        );
+        frames
+    }
+
+    #[test]
+    fn test_map_payload() {
+        let payload: MetricKitPayload = serde_json::from_str(PAYLOAD).unwrap();
+        let diagnostics = map_payload(payload);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].ty, MetricKitDiagnosticType::Crash);
+        assert_eq!(diagnostics[0].metadata["signal"], 11);
+        assert_eq!(diagnostics[1].ty, MetricKitDiagnosticType::Hang);
+
+        let root = &diagnostics[0].call_stacks[0].root_frames[0];
+        assert_eq!(root.address, HexValue(0x100011234));
+        assert_eq!(root.offset_into_binary_text_segment, HexValue(0x1234));
+        assert_eq!(root.sub_frames.len(), 1);
+    }
+
+    #[test]
+    fn test_collect_frames() {
+        let payload: MetricKitPayload = serde_json::from_str(PAYLOAD).unwrap();
+        let diagnostics = map_payload(payload);
+        let mut modules = BTreeMap::new();
+
+        // The crashing frame is the root of a per-thread tree.
+        let frames = collect_call_stack(&diagnostics[0], &mut modules);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].instruction_addr, HexValue(0x100011234));
+        assert_eq!(frames[0].adjust_instruction_addr, Some(false));
+        assert_eq!(frames[0].package.as_deref(), Some("App"));
+        assert_eq!(frames[1].adjust_instruction_addr, Some(true));
+
+        // The sampled frames are the leaves of an aggregated tree.
+        let frames = collect_call_stack(&diagnostics[1], &mut modules);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].adjust_instruction_addr, Some(true));
+        assert_eq!(frames[1].adjust_instruction_addr, Some(false));
+
+        // Frames without a binary UUID do not add a module.
+        let modules: Vec<_> = modules.into_values().collect();
+        assert_eq!(modules.len(), 2);
+        let app = modules
+            .iter()
+            .find(|module| module.raw.code_file.as_deref() == Some("App"))
+            .unwrap();
+        assert_eq!(app.raw.ty, ObjectType::Macho);
+        assert_eq!(
+            app.raw.debug_id.as_deref(),
+            Some("2b4e1f6f-1e2d-3b5a-9c8d-7e6f5a4b3c2d")
+        );
+        assert_eq!(app.raw.image_addr, HexValue(0x100010000));
+    }
+
+    #[test]
+    fn test_fill_frames() {
+        let payload: MetricKitPayload = serde_json::from_str(PAYLOAD).unwrap();
+        let mut diagnostics = map_payload(payload);
+        let root_frames = &mut diagnostics[0].call_stacks[0].root_frames;
+
+        let symbolicated_frame = |index, function: &str| SymbolicatedFrame {
+            status: FrameStatus::Symbolicated,
+            original_index: Some(index),
+            inexact: false,
+            raw: RawFrame {
+                function: Some(function.to_owned()),
+                ..Default::default()
+            },
+        };
+        let mut symbolicated = BTreeMap::new();
+        symbolicated.insert(
+            0,
+            vec![
+                symbolicated_frame(0, "inlined"),
+                symbolicated_frame(0, "main"),
+            ],
+        );
+        symbolicated.insert(1, vec![symbolicated_frame(1, "start")]);
+
+        fill_frames(root_frames, &mut symbolicated, &mut 0);
+
+        let functions = |frame: &SymbolicatedMetricKitFrame| -> Vec<_> {
+            frame
+                .frames
+                .iter()
+                .map(|frame| frame.raw.function.clone().unwrap())
+                .collect()
+        };
+        assert_eq!(functions(&root_frames[0]), ["inlined", "main"]);
+        assert_eq!(functions(&root_frames[0].sub_frames[0]), ["start"]);
+        assert!(symbolicated.is_empty());
+    }
+}
@@ -0,0 +1,402 @@
+//! Writing of minidumps for crashes which are reported in other formats.
+//!
+//! Minidumps written here only contain the system info, the threads with their registers and
+//! stack memory, the loaded modules and optionally an exception. That is all the information
+//! stackwalking needs, so that other crash formats can be processed like uploaded minidumps.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use tempfile::TempPath;
+
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d;
+const MINIDUMP_VERSION: u32 = 0xa793;
+const MINIDUMP_HEADER_SIZE: usize = 32;
+const MINIDUMP_DIRECTORY_SIZE: usize = 12;
+
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const EXCEPTION_STREAM: u32 = 6;
+const SYSTEM_INFO_STREAM: u32 = 7;
+
+pub(super) const PLATFORM_WINDOWS: u32 = 2;
+pub(super) const PLATFORM_MACOS: u32 = 0x8101;
+pub(super) const PLATFORM_IOS: u32 = 0x8102;
+pub(super) const PLATFORM_LINUX: u32 = 0x8201;
+pub(super) const PLATFORM_ANDROID: u32 = 0x8203;
+
+/// The CodeView signature of ELF build ids, as written by Breakpad (`BpEL`).
+const CV_SIGNATURE_ELF: u32 = 0x4270_454c;
+/// The CodeView signature of PDB 7.0 records (`RSDS`).
+const CV_SIGNATURE_PDB70: u32 = 0x5344_5352;
+
+/// The CPU architectures for which thread contexts can be written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ContextArch {
+    Amd64,
+    Arm64,
+}
+
+impl ContextArch {
+    /// The names of the registers, in the order of the `user_regs_struct` of the architecture.
+    pub(super) fn register_names(self) -> &'static [&'static str] {
+        match self {
+            ContextArch::Amd64 => &[
+                "r15", "r14", "r13", "r12", "rbp", "rbx", "r11", "r10", "r9", "r8", "rax", "rcx",
+                "rdx", "rsi", "rdi", "orig_rax", "rip", "cs", "eflags", "rsp", "ss", "fs_base",
+                "gs_base", "ds", "es", "fs", "gs",
+            ],
+            ContextArch::Arm64 => &[
+                "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12",
+                "x13", "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24",
+                "x25", "x26", "x27", "x28", "x29", "x30", "sp", "pc", "pstate",
+            ],
+        }
+    }
+
+    pub(super) fn stack_pointer(self, registers: &[u64]) -> u64 {
+        match self {
+            ContextArch::Amd64 => registers[19],
+            ContextArch::Arm64 => registers[31],
+        }
+    }
+
+    pub(super) fn instruction_pointer(self, registers: &[u64]) -> u64 {
+        match self {
+            ContextArch::Amd64 => registers[16],
+            ContextArch::Arm64 => registers[32],
+        }
+    }
+
+    /// The `PROCESSOR_ARCHITECTURE` of the minidump system info.
+    fn processor_architecture(self) -> u16 {
+        match self {
+            ContextArch::Amd64 => 9,
+            ContextArch::Arm64 => 12,
+        }
+    }
+
+    /// Writes the registers as a minidump `CONTEXT_AMD64` or `CONTEXT_ARM64`.
+    fn context(self, registers: &[u64]) -> Vec<u8> {
+        match self {
+            ContextArch::Amd64 => {
+                let mut context = vec![0; 1232];
+                // CONTEXT_AMD64 | CONTEXT_CONTROL | CONTEXT_INTEGER | CONTEXT_SEGMENTS
+                put_u32(&mut context, 48, 0x0010_0007);
+                for (offset, index) in [(56, 17), (58, 23), (60, 24), (62, 25), (64, 26), (66, 20)]
+                {
+                    put_u16(&mut context, offset, registers[index] as u16);
+                }
+                put_u32(&mut context, 68, registers[18] as u32);
+                // rax, rcx, rdx, rbx, rsp, rbp, rsi, rdi, r8 .. r15, rip
+                let order = [10, 11, 12, 5, 19, 4, 13, 14, 9, 8, 7, 6, 3, 2, 1, 0, 16];
+                for (i, index) in order.into_iter().enumerate() {
+                    put_u64(&mut context, 120 + i * 8, registers[index]);
+                }
+                context
+            }
+            ContextArch::Arm64 => {
+                let mut context = vec![0; 912];
+                // CONTEXT_ARM64 | CONTEXT_CONTROL | CONTEXT_INTEGER
+                put_u32(&mut context, 0, 0x0040_0003);
+                put_u32(&mut context, 4, registers[33] as u32);
+                // x0 .. x30, sp, pc
+                for (i, value) in registers[..33].iter().enumerate() {
+                    put_u64(&mut context, 8 + i * 8, *value);
+                }
+                context
+            }
+        }
+    }
+}
+
+/// A thread with its registers, in the order of [`ContextArch::register_names`].
+#[derive(Debug)]
+pub(super) struct ThreadRecord<'a> {
+    pub tid: u32,
+    pub registers: Vec<u64>,
+    pub stack_start: u64,
+    pub stack: &'a [u8],
+}
+
+/// A loaded module.
+#[derive(Debug)]
+pub(super) struct ModuleRecord<'a> {
+    pub base: u64,
+    pub size: u32,
+    /// The timestamp of PE files, which is part of their code id.
+    pub timestamp: u32,
+    pub name: &'a str,
+    /// The CodeView record identifying the module, or empty.
+    pub cv_record: Vec<u8>,
+}
+
+/// The exception which caused a crash.
+#[derive(Debug)]
+pub(super) struct ExceptionRecord {
+    /// The index of the crashed thread.
+    pub thread_index: usize,
+    pub code: u32,
+    pub flags: u32,
+    pub address: u64,
+}
+
+/// The contents of a minidump.
+#[derive(Debug)]
+pub(super) struct MinidumpContents<'a> {
+    pub arch: ContextArch,
+    /// The `PlatformId` of the system info, such as [`PLATFORM_LINUX`].
+    pub platform_id: u32,
+    pub threads: Vec<ThreadRecord<'a>>,
+    pub modules: Vec<ModuleRecord<'a>>,
+    pub exception: Option<ExceptionRecord>,
+}
+
+/// Returns the CodeView record of an ELF module with the given build id.
+pub(super) fn elf_cv_record(build_id: &[u8]) -> Vec<u8> {
+    let mut record = CV_SIGNATURE_ELF.to_le_bytes().to_vec();
+    record.extend_from_slice(build_id);
+    record
+}
+
+/// Returns the PDB 7.0 CodeView record of a module.
+///
+/// The `guid` is given in the byte order of a UUID, and written with the little-endian fields
+/// of a GUID.
+pub(super) fn pdb70_cv_record(guid: &[u8; 16], age: u32, debug_file: &str) -> Vec<u8> {
+    let mut record = CV_SIGNATURE_PDB70.to_le_bytes().to_vec();
+    record.extend(guid_bytes(guid));
+    record.extend_from_slice(&age.to_le_bytes());
+    record.extend_from_slice(debug_file.as_bytes());
+    record.push(0);
+    record
+}
+
+/// Swaps the first three fields of a UUID, which are stored as little-endian in a GUID.
+pub(super) fn guid_bytes(uuid: &[u8; 16]) -> [u8; 16] {
+    let mut guid = *uuid;
+    guid[..4].reverse();
+    guid[4..6].reverse();
+    guid[6..8].reverse();
+    guid
+}
+
+/// Returns the current time as a minidump timestamp.
+pub(super) fn current_timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as u32)
+}
+
+/// Writes a minidump into a temporary file in `dir`, or the default temporary directory.
+pub(super) fn write_minidump_file(minidump: &[u8], dir: Option<&Path>) -> io::Result<TempPath> {
+    let mut minidump_file = tempfile::Builder::new();
+    minidump_file.prefix("minidump").suffix(".dmp");
+    let mut minidump_file = match dir {
+        Some(dir) => minidump_file.tempfile_in(dir),
+        None => minidump_file.tempfile(),
+    }?;
+    minidump_file.write_all(minidump)?;
+    Ok(minidump_file.into_temp_path())
+}
+
+/// Writes a minidump.
+///
+/// The exception, if any, refers to the context of its thread.
+pub(super) fn write_minidump(contents: &MinidumpContents<'_>, timestamp: u32) -> Vec<u8> {
+    let arch = contents.arch;
+    let stream_count = if contents.exception.is_some() { 5 } else { 4 };
+
+    let mut writer = MinidumpWriter::default();
+    writer.u32(MINIDUMP_SIGNATURE);
+    writer.u32(MINIDUMP_VERSION);
+    writer.u32(stream_count);
+    writer.u32(MINIDUMP_HEADER_SIZE as u32);
+    writer.u32(0); // checksum
+    writer.u32(timestamp);
+    writer.u64(0); // flags
+    writer.zeros(stream_count as usize * MINIDUMP_DIRECTORY_SIZE);
+    let mut directory = Vec::new();
+
+    // System info
+    let csd_version = writer.string("");
+    let start = writer.start();
+    writer.u16(arch.processor_architecture());
+    writer.u16(0); // processor level
+    writer.u16(0); // processor revision
+    writer.u8(0); // number of processors
+    writer.u8(0); // product type
+    writer.u32(0); // major version
+    writer.u32(0); // minor version
+    writer.u32(0); // build number
+    writer.u32(contents.platform_id);
+    writer.u32(csd_version);
+    writer.u16(0); // suite mask
+    writer.u16(0); // reserved
+    writer.zeros(24); // cpu information
+    directory.push(writer.stream(SYSTEM_INFO_STREAM, start));
+
+    // Thread contexts and stack memory
+    let mut threads = Vec::new();
+    for thread in &contents.threads {
+        let context = writer.bytes(&arch.context(&thread.registers));
+        let stack = writer.bytes(thread.stack);
+        threads.push((context, stack));
+    }
+
+    let start = writer.start();
+    writer.u32(threads.len() as u32);
+    for (thread, &(context, stack)) in contents.threads.iter().zip(&threads) {
+        writer.u32(thread.tid);
+        writer.u32(0); // suspend count
+        writer.u32(0); // priority class
+        writer.u32(0); // priority
+        writer.u64(0); // teb
+        writer.u64(thread.stack_start);
+        writer.location(stack);
+        writer.location(context);
+    }
+    directory.push(writer.stream(THREAD_LIST_STREAM, start));
+
+    let start = writer.start();
+    writer.u32(threads.len() as u32);
+    for (thread, &(_, stack)) in contents.threads.iter().zip(&threads) {
+        writer.u64(thread.stack_start);
+        writer.location(stack);
+    }
+    directory.push(writer.stream(MEMORY_LIST_STREAM, start));
+
+    // Modules
+    let mut module_records = Vec::new();
+    for module in &contents.modules {
+        let name = writer.string(module.name);
+        let cv_record = match module.cv_record.as_slice() {
+            [] => (0, 0),
+            cv_record => writer.bytes(cv_record),
+        };
+        module_records.push((name, cv_record));
+    }
+
+    let start = writer.start();
+    writer.u32(contents.modules.len() as u32);
+    for (module, &(name, cv_record)) in contents.modules.iter().zip(&module_records) {
+        writer.u64(module.base);
+        writer.u32(module.size);
+        writer.u32(0); // checksum
+        writer.u32(module.timestamp);
+        writer.u32(name);
+        writer.zeros(52); // version info
+        writer.location(cv_record);
+        writer.location((0, 0)); // misc record
+        writer.zeros(16); // reserved
+    }
+    directory.push(writer.stream(MODULE_LIST_STREAM, start));
+
+    // Exception
+    if let Some(exception) = &contents.exception {
+        let thread = &contents.threads[exception.thread_index];
+        let (context, _) = threads[exception.thread_index];
+
+        let start = writer.start();
+        writer.u32(thread.tid);
+        writer.u32(0); // alignment
+        writer.u32(exception.code);
+        writer.u32(exception.flags);
+        writer.u64(0); // nested exception record
+        writer.u64(exception.address);
+        writer.u32(0); // number of parameters
+        writer.u32(0); // alignment
+        writer.zeros(15 * 8); // parameters
+        writer.location(context);
+        directory.push(writer.stream(EXCEPTION_STREAM, start));
+    }
+
+    let mut offset = MINIDUMP_HEADER_SIZE;
+    for (ty, (size, rva)) in directory {
+        put_u32(&mut writer.buf, offset, ty);
+        put_u32(&mut writer.buf, offset + 4, size as u32);
+        put_u32(&mut writer.buf, offset + 8, rva as u32);
+        offset += MINIDUMP_DIRECTORY_SIZE;
+    }
+
+    writer.buf
+}
+
+/// A location in the minidump, as `(size, rva)`.
+type Location = (usize, usize);
+
+/// Appends little-endian minidump structures to a buffer.
+#[derive(Debug, Default)]
+struct MinidumpWriter {
+    buf: Vec<u8>,
+}
+
+impl MinidumpWriter {
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn zeros(&mut self, len: usize) {
+        self.buf.resize(self.buf.len() + len, 0);
+    }
+
+    fn location(&mut self, (size, rva): Location) {
+        self.u32(size as u32);
+        self.u32(rva as u32);
+    }
+
+    /// Aligns the buffer for the next structure and returns its offset.
+    fn start(&mut self) -> usize {
+        self.zeros(self.buf.len().wrapping_neg() % 8);
+        self.buf.len()
+    }
+
+    /// Returns the directory entry of a stream which was written since `start`.
+    fn stream(&self, ty: u32, start: usize) -> (u32, Location) {
+        (ty, (self.buf.len() - start, start))
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Location {
+        let start = self.start();
+        self.buf.extend_from_slice(bytes);
+        (bytes.len(), start)
+    }
+
+    /// Writes a `MINIDUMP_STRING` and returns its offset.
+    fn string(&mut self, string: &str) -> u32 {
+        let start = self.start();
+        let utf16: Vec<u16> = string.encode_utf16().collect();
+        self.u32((utf16.len() * 2) as u32);
+        for c in utf16 {
+            self.u16(c);
+        }
+        self.u16(0);
+        start as u32
+    }
+}
+
+pub(super) fn put_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn put_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
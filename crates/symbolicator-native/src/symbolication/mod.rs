@@ -1,9 +1,26 @@
 mod apple;
-mod demangle;
+mod breakpad;
+mod coredump;
+mod crash_address;
+pub(crate) mod demangle;
 mod dotnet;
+mod exploitability;
+mod inspect;
+mod ips;
+mod metrickit;
 mod minidump_stacktraces;
+mod minidump_writer;
 mod module_lookup;
 mod native;
+mod pointer_auth;
+mod prefetch;
 mod process_minidump;
+mod process_resources;
+mod purge;
+mod salvage;
 mod source_context;
+mod stack_memory;
+mod swift_async;
 pub mod symbolicate;
+mod variables;
+mod wer;
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 
 use futures::future;
@@ -11,17 +11,17 @@ use symbolicator_service::objects::{
     ObjectPurpose, ObjectsActor,
 };
 use symbolicator_service::source_context::get_context_lines;
-use symbolicator_service::types::{ObjectFileStatus, RawObjectInfo, Scope};
+use symbolicator_service::types::{ObjectFileStatus, RawObjectInfo, Scope, SourceRepository};
 use symbolicator_sources::{FileType, ObjectId, ObjectType, SourceConfig};
 
 use crate::caches::derived::DerivedCache;
 use crate::caches::ppdb_caches::{
     FetchPortablePdbCache, OwnedPortablePdbCache, PortablePdbCacheActor,
 };
-use crate::caches::symcaches::{FetchSymCache, OwnedSymCache, SymCacheActor};
+use crate::caches::symcaches::{FetchSymCache, OwnedSymCache, SymCacheActor, SymCacheShard};
 use crate::interface::{AddrMode, CompleteObjectInfo, CompleteStacktrace, RawFrame, RawStacktrace};
 
-fn object_id_from_object_info(object_info: &RawObjectInfo) -> ObjectId {
+pub(crate) fn object_id_from_object_info(object_info: &RawObjectInfo) -> ObjectId {
     ObjectId {
         debug_id: match object_info.debug_id.as_deref() {
             None | Some("") => None,
@@ -41,10 +41,19 @@ fn object_id_from_object_info(object_info: &RawObjectInfo) -> ObjectId {
 pub fn object_file_status_from_cache_entry<T>(cache_entry: &CacheEntry<T>) -> ObjectFileStatus {
     match cache_entry {
         Ok(_) => ObjectFileStatus::Found,
-        Err(CacheError::NotFound) => ObjectFileStatus::Missing,
-        Err(CacheError::PermissionDenied(_) | CacheError::DownloadError(_)) => {
-            ObjectFileStatus::FetchingFailed
-        }
+        Err(
+            CacheError::NotFound
+            | CacheError::NoMatchingArchiveMember(_)
+            | CacheError::NoMatchingArch(_),
+        ) => ObjectFileStatus::Missing,
+        Err(
+            CacheError::PermissionDenied(_)
+            | CacheError::Blocked(_)
+            | CacheError::DownloadError(_)
+            | CacheError::Unavailable(_)
+            | CacheError::SizeLimitExceeded(_)
+            | CacheError::ChecksumMismatch(_),
+        ) => ObjectFileStatus::FetchingFailed,
         Err(CacheError::Timeout(_)) => ObjectFileStatus::Timeout,
         Err(CacheError::Malformed(_)) => ObjectFileStatus::Malformed,
         Err(CacheError::Unsupported(_)) => ObjectFileStatus::Unsupported,
@@ -52,17 +61,77 @@ pub fn object_file_status_from_cache_entry<T>(cache_entry: &CacheEntry<T>) -> Ob
     }
 }
 
+/// How far below a return address the address of its call instruction may be.
+///
+/// Caller addresses are looked up in the SymCache shard covering them, which is not necessarily
+/// the shard covering the return address.
+const MAX_CALLER_ADDR_OFFSET: u64 = 16;
+
 #[derive(Debug, Clone)]
 pub enum CacheFileEntry {
     SymCache(OwnedSymCache),
+    /// The shards of a sharded SymCache, ordered by address.
+    ///
+    /// Only the shards covering addresses in the request are included.
+    SymCacheShards(Vec<(SymCacheShard, OwnedSymCache)>),
     PortablePdbCache(OwnedPortablePdbCache),
 }
 
+impl CacheFileEntry {
+    /// Returns the SymCache covering the given relative address.
+    pub fn symcache_for(&self, addr: u64) -> Option<&OwnedSymCache> {
+        match self {
+            CacheFileEntry::SymCache(symcache) => Some(symcache),
+            CacheFileEntry::SymCacheShards(shards) => shards
+                .iter()
+                .find(|(shard, _)| shard.contains(addr))
+                .map(|(_, symcache)| symcache),
+            CacheFileEntry::PortablePdbCache(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CacheFile {
     file: CacheEntry<CacheFileEntry>,
     candidates: AllObjectCandidates,
     features: ObjectFeatures,
+    loose_match: bool,
+}
+
+/// Combines the fetched shards of a SymCache into a single [`CacheFile`].
+///
+/// Shards which could not be fetched are left out, unless none of the shards could be fetched.
+fn combine_shards(shards: Vec<(SymCacheShard, DerivedCache<OwnedSymCache>)>) -> CacheFile {
+    let mut symcaches = Vec::new();
+    let mut error = None;
+    let mut candidates = AllObjectCandidates::default();
+    let mut features = ObjectFeatures::default();
+    let mut loose_match = false;
+
+    for (shard, derived) in shards {
+        candidates.merge(&derived.candidates);
+        features.merge(derived.features);
+        loose_match |= derived.loose_match;
+        match derived.cache {
+            Ok(symcache) => symcaches.push((shard, symcache)),
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+
+    let file = match error {
+        Some(error) if symcaches.is_empty() => Err(error),
+        _ => Ok(CacheFileEntry::SymCacheShards(symcaches)),
+    };
+
+    CacheFile {
+        file,
+        candidates,
+        features,
+        loose_match,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -178,13 +247,20 @@ impl ModuleLookup {
         ppdb_cache_actor: PortablePdbCacheActor,
         stacktraces: &[RawStacktrace],
     ) {
-        let mut referenced_objects = HashSet::new();
+        // The SymCache shards needed for each referenced module, if it is sharded.
+        let mut referenced_objects = HashMap::<usize, BTreeSet<SymCacheShard>>::new();
         for stacktrace in stacktraces {
-            for frame in &stacktrace.frames {
-                if let Some(CacheLookupResult { module_index, .. }) =
-                    self.lookup_cache(frame.instruction_addr.0, frame.addr_mode)
-                {
-                    referenced_objects.insert(module_index);
+            for frame in stacktrace.frames.iter().chain(&stacktrace.async_frames) {
+                if let Some(lookup) = self.lookup_cache(frame.instruction_addr.0, frame.addr_mode) {
+                    let shards = referenced_objects.entry(lookup.module_index).or_default();
+                    let image_size = lookup.object_info.raw.image_size;
+                    let addrs = lookup
+                        .relative_addr
+                        .into_iter()
+                        .flat_map(|addr| [addr, addr.saturating_sub(MAX_CALLER_ADDR_OFFSET)]);
+                    shards.extend(
+                        addrs.filter_map(|addr| symcache_actor.shard_for(image_size, addr)),
+                    );
                 }
             }
         }
@@ -194,11 +270,10 @@ impl ModuleLookup {
             .iter_mut()
             .enumerate()
             .filter_map(|(idx, entry)| {
-                let is_used = referenced_objects.contains(&entry.module_index);
-                if !is_used {
+                let Some(shards) = referenced_objects.remove(&entry.module_index) else {
                     entry.object_info.debug_status = ObjectFileStatus::Unused;
                     return None;
-                }
+                };
                 let symcache_actor = symcache_actor.clone();
                 let ppdb_cache_actor = ppdb_cache_actor.clone();
                 let identifier = object_id_from_object_info(&entry.object_info.raw);
@@ -219,34 +294,55 @@ impl ModuleLookup {
                                 cache,
                                 candidates,
                                 features,
+                                loose_match,
                             } = ppdb_cache_actor.fetch(request).await;
 
                             let cache_file = CacheFile {
                                 file: cache.map(CacheFileEntry::PortablePdbCache),
                                 candidates,
                                 features,
+                                loose_match,
                             };
 
                             (idx, cache_file)
                         }
+                        _ if !shards.is_empty() => {
+                            let requests = shards.into_iter().map(|shard| {
+                                let request = FetchSymCache {
+                                    object_type,
+                                    identifier: identifier.clone(),
+                                    sources: sources.clone(),
+                                    scope: scope.clone(),
+                                    shard: Some(shard),
+                                };
+                                let symcache_actor = &symcache_actor;
+                                async move { (shard, symcache_actor.fetch(request).await) }
+                            });
+
+                            let shards = future::join_all(requests).await;
+                            (idx, combine_shards(shards))
+                        }
                         _ => {
                             let request = FetchSymCache {
                                 object_type,
                                 identifier,
                                 sources,
                                 scope,
+                                shard: None,
                             };
 
                             let DerivedCache {
                                 cache,
                                 candidates,
                                 features,
+                                loose_match,
                             } = symcache_actor.fetch(request).await;
 
                             let cache_file = CacheFile {
                                 file: cache.map(CacheFileEntry::SymCache),
                                 candidates,
                                 features,
+                                loose_match,
                             };
 
                             (idx, cache_file)
@@ -262,6 +358,7 @@ impl ModuleLookup {
                 file,
                 candidates,
                 features,
+                loose_match,
             } = cache_file;
 
             if let Some(entry) = self.modules.get_mut(idx) {
@@ -269,9 +366,24 @@ impl ModuleLookup {
                 entry.object_info.features.merge(features);
                 entry.object_info.candidates.merge(&candidates);
                 entry.object_info.debug_status = object_file_status_from_cache_entry(&file);
-
-                if let Ok(CacheFileEntry::SymCache(ref symcache)) = file {
-                    entry.object_info.arch = symcache.get().arch();
+                // Without debug info, the SymCache was built from the symbol table only.
+                entry.object_info.symbols_only = matches!(
+                    file,
+                    Ok(CacheFileEntry::SymCache(_) | CacheFileEntry::SymCacheShards(_))
+                ) && !features.has_debug_info
+                    && features.has_symbols;
+                entry.object_info.loose_match = file.is_ok() && loose_match;
+
+                match file {
+                    Ok(CacheFileEntry::SymCache(ref symcache)) => {
+                        entry.object_info.arch = symcache.get().arch();
+                    }
+                    Ok(CacheFileEntry::SymCacheShards(ref shards)) => {
+                        if let Some((_, symcache)) = shards.first() {
+                            entry.object_info.arch = symcache.get().arch();
+                        }
+                    }
+                    _ => {}
                 }
 
                 entry.cache = file;
@@ -319,7 +431,9 @@ impl ModuleLookup {
                 };
 
                 let fut = async move {
-                    let FindResult { meta, candidates } = objects.find(find_request).await;
+                    let FindResult {
+                        meta, candidates, ..
+                    } = objects.find(find_request).await;
 
                     let source_object = match meta {
                         Some(meta) => match meta.handle {
@@ -432,6 +546,28 @@ impl ModuleLookup {
         }
     }
 
+    /// Maps the frame to a file in one of the `repositories`, if it has no source context yet.
+    ///
+    /// Return a quadruple of scope, URL, module index, and repository
+    /// in case the source code has to be fetched.
+    pub(crate) fn try_map_to_repository<'r>(
+        &self,
+        repositories: &'r [SourceRepository],
+        frame: &RawFrame,
+    ) -> Option<(Scope, url::Url, usize, &'r SourceRepository)> {
+        if frame.context_line.is_some() {
+            return None;
+        }
+        let abs_path = frame.abs_path.as_ref()?;
+        frame.lineno.filter(|lineno| *lineno > 0)?;
+
+        let entry = self.get_module_by_addr(frame.instruction_addr.0, frame.addr_mode)?;
+        repositories.iter().find_map(|repository| {
+            let url = repository.url_for_path(abs_path)?;
+            Some((self.scope.clone(), url, entry.module_index, repository))
+        })
+    }
+
     pub(crate) fn set_source_context(source: &str, frame: &mut RawFrame) -> Option<()> {
         let (pre_context, context_line, post_context) =
             get_context_lines(source, frame.lineno?.try_into().ok()?, 0, None)?;
@@ -48,6 +48,7 @@ pub fn symbolicate_native_frame(
         rv.push(SymbolicatedFrame {
             status: FrameStatus::Symbolicated,
             original_index: Some(index),
+            inexact: lookup_result.object_info.loose_match,
             raw: RawFrame {
                 platform: frame.platform.clone(),
                 package: lookup_result.object_info.raw.code_file.clone(),
@@ -75,6 +76,9 @@ pub fn symbolicate_native_frame(
                 },
                 in_app: None,
                 trust: frame.trust,
+                registers: frame.registers.clone(),
+                address_tag: frame.address_tag,
+                variables: frame.variables.clone(),
             },
         });
     }
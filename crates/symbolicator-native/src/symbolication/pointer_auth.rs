@@ -0,0 +1,194 @@
+//! Stripping of pointer authentication codes and memory tags from the addresses of frames.
+//!
+//! On `arm64e`, return addresses are signed with a pointer authentication code (PAC), which is
+//! stored in the otherwise unused upper bits of the address. Top byte ignore (TBI) additionally
+//! allows tags in the top byte, which devices with memory tagging (MTE) use for the memory tags
+//! of pointers. Both have to be removed before frames can be matched to modules.
+
+use symbolic::common::Arch;
+use symbolicator_service::config::PointerAuthConfig;
+use symbolicator_service::utils::hex::HexValue;
+
+use crate::interface::{AddrMode, CompleteObjectInfo, RawStacktrace};
+
+/// Strips pointer authentication codes and tags from the instruction addresses of all frames.
+///
+/// Nothing is stripped unless the architecture is enabled in the config.
+pub(super) fn strip_pointer_auth(
+    config: &PointerAuthConfig,
+    arch: Arch,
+    modules: &[CompleteObjectInfo],
+    stacktraces: &mut [RawStacktrace],
+) {
+    let Some(mask) = address_mask(config, arch, modules) else {
+        return;
+    };
+
+    for frame in stacktraces.iter_mut().flat_map(|thread| &mut thread.frames) {
+        frame.instruction_addr = HexValue(frame.instruction_addr.0 & mask);
+    }
+}
+
+/// The position of the top byte of addresses, which holds their tag.
+const TAG_SHIFT: u32 = 56;
+
+/// Removes the tags from instruction addresses which only fall into a module without them.
+///
+/// The removed top byte is kept in the [`address_tag`](crate::interface::RawFrame::address_tag)
+/// of the frame.
+pub(super) fn strip_address_tags(
+    modules: &[CompleteObjectInfo],
+    stacktraces: &mut [RawStacktrace],
+) {
+    for frame in stacktraces.iter_mut().flat_map(|thread| &mut thread.frames) {
+        let addr = frame.instruction_addr.0;
+        let untagged_addr = addr & ((1 << TAG_SHIFT) - 1);
+        if frame.addr_mode != AddrMode::Abs || untagged_addr == addr {
+            continue;
+        }
+
+        if !is_in_module(modules, addr) && is_in_module(modules, untagged_addr) {
+            frame.address_tag = Some((addr >> TAG_SHIFT) as u8);
+            frame.instruction_addr = HexValue(untagged_addr);
+        }
+    }
+}
+
+/// Returns whether the address falls into one of the modules.
+///
+/// Modules without an image size are skipped, since their extent is not known.
+fn is_in_module(modules: &[CompleteObjectInfo], addr: u64) -> bool {
+    modules.iter().any(|module| {
+        let raw = &module.raw;
+        raw.image_size
+            .is_some_and(|size| addr >= raw.image_addr.0 && addr - raw.image_addr.0 < size)
+    })
+}
+
+/// Returns the mask of the significant address bits of the given architecture.
+///
+/// Unless the config has a number of address bits for the architecture, the mask covers the
+/// highest end address of all modules.
+fn address_mask(
+    config: &PointerAuthConfig,
+    arch: Arch,
+    modules: &[CompleteObjectInfo],
+) -> Option<u64> {
+    if !config.is_enabled(arch.name()) {
+        return None;
+    }
+
+    let bits = match config.address_bits.get(arch.name()) {
+        Some(&bits) => bits,
+        None => {
+            let highest_addr = modules
+                .iter()
+                .map(|module| {
+                    let raw = &module.raw;
+                    raw.image_addr.0.saturating_add(raw.image_size.unwrap_or(0))
+                })
+                .max()?;
+            u64::BITS - highest_addr.leading_zeros()
+        }
+    };
+
+    (bits > 0 && bits < u64::BITS).then(|| (1 << bits) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolicator_service::types::RawObjectInfo;
+    use symbolicator_sources::ObjectType;
+
+    use crate::interface::RawFrame;
+
+    use super::*;
+
+    fn module(image_addr: u64, image_size: u64) -> CompleteObjectInfo {
+        RawObjectInfo {
+            ty: ObjectType::Macho,
+            code_id: None,
+            code_file: None,
+            debug_id: None,
+            debug_file: None,
+            debug_checksum: None,
+            image_addr: HexValue(image_addr),
+            image_size: Some(image_size),
+        }
+        .into()
+    }
+
+    fn stacktraces(addrs: &[u64]) -> Vec<RawStacktrace> {
+        let frames = addrs
+            .iter()
+            .map(|&addr| RawFrame {
+                instruction_addr: HexValue(addr),
+                ..RawFrame::default()
+            })
+            .collect();
+
+        vec![RawStacktrace {
+            frames,
+            ..RawStacktrace::default()
+        }]
+    }
+
+    fn instruction_addrs(stacktraces: &[RawStacktrace]) -> Vec<u64> {
+        stacktraces[0]
+            .frames
+            .iter()
+            .map(|frame| frame.instruction_addr.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_strip_from_modules() {
+        let config = PointerAuthConfig::default();
+        let modules = [module(0x1_0000_0000, 0x4000), module(0x1_8000_0000, 0x8000)];
+        let mut stacktraces = stacktraces(&[0x1_0000_1000, 0x3b0e_0001_8000_2000]);
+
+        strip_pointer_auth(&config, Arch::Arm64e, &modules, &mut stacktraces);
+        assert_eq!(
+            instruction_addrs(&stacktraces),
+            [0x1_0000_1000, 0x1_8000_2000]
+        );
+    }
+
+    #[test]
+    fn test_strip_configured_bits() {
+        let mut config = PointerAuthConfig::default();
+        config.address_bits.insert("arm64".to_owned(), 36);
+        let mut stacktraces = stacktraces(&[0xff00_000f_0000_1000]);
+
+        strip_pointer_auth(&config, Arch::Arm64, &[], &mut stacktraces);
+        assert_eq!(instruction_addrs(&stacktraces), [0xf_0000_1000]);
+    }
+
+    #[test]
+    fn test_strip_address_tags() {
+        let modules = [module(0x70_0000_0000, 0x10000)];
+        let mut stacktraces = stacktraces(&[0x0b00_0070_0000_1000, 0x0b00_0010_0000_1000]);
+
+        strip_address_tags(&modules, &mut stacktraces);
+        assert_eq!(
+            instruction_addrs(&stacktraces),
+            [0x70_0000_1000, 0x0b00_0010_0000_1000]
+        );
+        let tags: Vec<_> = stacktraces[0]
+            .frames
+            .iter()
+            .map(|frame| frame.address_tag)
+            .collect();
+        assert_eq!(tags, [Some(0x0b), None]);
+    }
+
+    #[test]
+    fn test_strip_disabled_arch() {
+        let config = PointerAuthConfig::default();
+        let modules = [module(0x1_0000_0000, 0x4000)];
+        let mut stacktraces = stacktraces(&[0x3b0e_0001_0000_1000]);
+
+        strip_pointer_auth(&config, Arch::Amd64, &modules, &mut stacktraces);
+        assert_eq!(instruction_addrs(&stacktraces), [0x3b0e_0001_0000_1000]);
+    }
+}
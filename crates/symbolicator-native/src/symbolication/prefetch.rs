@@ -0,0 +1,62 @@
+//! Warming of the caches needed to symbolicate a module.
+
+use futures::future;
+use symbolicator_sources::ObjectType;
+
+use crate::caches::cficaches::FetchCfiCache;
+use crate::caches::ppdb_caches::FetchPortablePdbCache;
+use crate::caches::symcaches::FetchSymCache;
+use crate::interface::{PrefetchModule, PrefetchedModule};
+
+use super::module_lookup::{object_file_status_from_cache_entry, object_id_from_object_info};
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Fetches the symbol and unwind caches of a module, computing them if they are not cached.
+    ///
+    /// This goes through the same caches as symbolication, so later requests referencing the
+    /// module find everything ready. Modules of .NET assemblies only have a Portable PDB cache.
+    pub async fn prefetch_module(&self, request: PrefetchModule) -> PrefetchedModule {
+        let PrefetchModule {
+            scope,
+            sources,
+            module,
+        } = request;
+        let identifier = object_id_from_object_info(&module);
+
+        if module.ty == ObjectType::PeDotnet {
+            let ppdb_cache = self
+                .ppdb_caches
+                .fetch(FetchPortablePdbCache {
+                    identifier,
+                    sources,
+                    scope,
+                })
+                .await;
+            return PrefetchedModule {
+                debug_status: object_file_status_from_cache_entry(&ppdb_cache.cache),
+                unwind_status: None,
+            };
+        }
+
+        let symcache = self.symcaches.fetch(FetchSymCache {
+            object_type: module.ty,
+            identifier: identifier.clone(),
+            sources: sources.clone(),
+            scope: scope.clone(),
+            shard: None,
+        });
+        let cficache = self.cficaches.fetch(FetchCfiCache {
+            object_type: module.ty,
+            identifier,
+            sources,
+            scope,
+        });
+        let (symcache, cficache) = future::join(symcache, cficache).await;
+
+        PrefetchedModule {
+            debug_status: object_file_status_from_cache_entry(&symcache.cache),
+            unwind_status: Some(object_file_status_from_cache_entry(&cficache.cache)),
+        }
+    }
+}
@@ -6,16 +6,22 @@ use std::time::Instant;
 use anyhow::Result;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use minidump::system_info::Os;
-use minidump::{MinidumpContext, MinidumpSystemInfo};
-use minidump::{MinidumpModule, Module};
-use minidump_processor::ProcessState;
+use minidump::format::MINIDUMP_THREAD;
+use minidump::system_info::{Cpu, Os};
+use minidump::{CodeView, MinidumpModule, Module};
+use minidump::{
+    MinidumpContext, MinidumpException, MinidumpMemoryList, MinidumpMiscInfo, MinidumpModuleList,
+    MinidumpSystemInfo, MinidumpThreadList, UnifiedMemory,
+};
+use minidump_processor::{ProcessError, ProcessState};
 use minidump_unwind::{
-    FileError, FileKind, FillSymbolError, FrameSymbolizer, FrameWalker, SymbolProvider,
+    walk_stack, CallStack, FileError, FileKind, FillSymbolError, FrameSymbolizer, FrameWalker,
+    StackFrame, SymbolProvider,
 };
 use sentry::{Hub, SentryFutureExt};
 use serde::{Deserialize, Serialize};
 use symbolic::common::{Arch, ByteView};
+use symbolicator_service::config::{UnwindingOptions, UnwindingStrategy};
 use symbolicator_service::metric;
 use symbolicator_service::types::{
     ObjectFileStatus, Platform, RawObjectInfo, Scope, ScrapingConfig,
@@ -27,21 +33,30 @@ use tokio::sync::Notify;
 
 use crate::caches::cficaches::{CfiCacheActor, CfiModuleInfo, FetchCfiCache, FetchedCfiCache};
 use crate::interface::{
-    CompleteObjectInfo, CompletedSymbolicationResponse, RawFrame, RawStacktrace, Registers,
-    SymbolicateStacktraces, SystemInfo,
+    CompleteObjectInfo, CompletedSymbolicationResponse, CrashAddress, FrameTrust, MinidumpWarning,
+    MinidumpWarningKind, ProcessResources, RawFrame, RawStacktrace, Registers, StackwalkOptions,
+    SymbolicateStacktraces, SystemInfo, ThreadState,
 };
 use crate::metrics::StacktraceOrigin;
 
+use super::crash_address::analyze_crash_address;
+use super::exploitability::analyze_exploitability;
 use super::minidump_stacktraces::parse_stacktraces_from_minidump;
 use super::module_lookup::object_file_status_from_cache_entry;
+use super::pointer_auth::{strip_address_tags, strip_pointer_auth};
+use super::process_resources::read_process_resources;
+use super::salvage::{salvage_threads, truncated_streams};
+use super::swift_async::SwiftAsyncUnwinder;
 use super::symbolicate::SymbolicationActor;
 
-type Minidump = minidump::Minidump<'static, ByteView<'static>>;
+pub(super) type Minidump = minidump::Minidump<'static, ByteView<'static>>;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct StackWalkMinidumpResult {
     modules: Vec<CompleteObjectInfo>,
     stacktraces: Vec<RawStacktrace>,
+    /// The registers of the top frames of the requesting thread, whose variables are recovered.
+    frame_contexts: Vec<Registers>,
     minidump_state: MinidumpState,
     duration: std::time::Duration,
 }
@@ -58,28 +73,16 @@ pub(super) struct MinidumpState {
     system_info: SystemInfo,
     crashed: bool,
     crash_reason: String,
+    crash_address: Option<CrashAddress>,
     assertion: String,
+    process_resources: Option<ProcessResources>,
+    warnings: Vec<MinidumpWarning>,
 }
 
 impl MinidumpState {
     fn from_process_state(process_state: &ProcessState) -> Self {
         let info = &process_state.system_info;
-
-        let cpu_arch = match info.cpu {
-            minidump::system_info::Cpu::X86 => Arch::X86,
-            minidump::system_info::Cpu::X86_64 => Arch::Amd64,
-            minidump::system_info::Cpu::Ppc => Arch::Ppc,
-            minidump::system_info::Cpu::Ppc64 => Arch::Ppc64,
-            minidump::system_info::Cpu::Arm => Arch::Arm,
-            minidump::system_info::Cpu::Arm64 => Arch::Arm64,
-            minidump::system_info::Cpu::Mips => Arch::Mips,
-            minidump::system_info::Cpu::Mips64 => Arch::Mips64,
-            arch => {
-                let msg = format!("Unknown minidump arch: {arch}");
-                sentry::capture_message(&msg, sentry::Level::Error);
-                Arch::Unknown
-            }
-        };
+        let cpu_arch = arch_from_cpu(info.cpu);
 
         MinidumpState {
             timestamp: process_state.time.into(),
@@ -96,7 +99,41 @@ impl MinidumpState {
                 .as_ref()
                 .map(|info| format!("{} / {:#x}", info.reason, info.address.0))
                 .unwrap_or_default(),
+            crash_address: None,
             assertion: process_state.assertion.clone().unwrap_or_default(),
+            process_resources: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Reads the meta-data of a minidump which could not be processed.
+    ///
+    /// Only the operating system and architecture are read from the system info, if it is
+    /// available.
+    fn from_minidump(minidump: &Minidump) -> Self {
+        let system_info = minidump.get_stream::<MinidumpSystemInfo>().ok();
+        let timestamp = i64::from(minidump.header.time_date_stamp);
+
+        MinidumpState {
+            timestamp: DateTime::from_timestamp(timestamp, 0).unwrap_or_default(),
+            system_info: SystemInfo {
+                os_name: system_info
+                    .as_ref()
+                    .map(|info| normalize_minidump_os_name(info.os).to_owned())
+                    .unwrap_or_default(),
+                os_version: String::default(),
+                os_build: String::default(),
+                cpu_arch: system_info
+                    .as_ref()
+                    .map_or(Arch::Unknown, |info| arch_from_cpu(info.cpu)),
+                device_model: String::default(),
+            },
+            crashed: minidump.get_stream::<MinidumpException>().is_ok(),
+            crash_reason: String::default(),
+            crash_address: None,
+            assertion: String::default(),
+            process_resources: None,
+            warnings: Vec::new(),
         }
     }
 
@@ -117,7 +154,28 @@ impl MinidumpState {
         response.system_info = Some(self.system_info);
         response.crashed = Some(self.crashed);
         response.crash_reason = Some(self.crash_reason);
+        response.crash_address = self.crash_address;
         response.assertion = Some(self.assertion);
+        response.process_resources = self.process_resources;
+        response.warnings = self.warnings;
+    }
+}
+
+fn arch_from_cpu(cpu: Cpu) -> Arch {
+    match cpu {
+        Cpu::X86 => Arch::X86,
+        Cpu::X86_64 => Arch::Amd64,
+        Cpu::Ppc => Arch::Ppc,
+        Cpu::Ppc64 => Arch::Ppc64,
+        Cpu::Arm => Arch::Arm,
+        Cpu::Arm64 => Arch::Arm64,
+        Cpu::Mips => Arch::Mips,
+        Cpu::Mips64 => Arch::Mips64,
+        arch => {
+            let msg = format!("Unknown minidump arch: {arch}");
+            sentry::capture_message(&msg, sentry::Level::Error);
+            Arch::Unknown
+        }
     }
 }
 
@@ -153,8 +211,10 @@ struct SymbolicatorSymbolProvider {
     scope: Scope,
     /// The sources from which to fetch CFI.
     sources: Arc<[SourceConfig]>,
-    /// The object type of the minidump to stackwalk.
-    object_type: ObjectType,
+    /// The object type of the minidump to stackwalk, derived from its operating system.
+    os_type: ObjectType,
+    /// The object types of the modules in the module list of the minidump.
+    object_types: HashMap<LookupKey, ObjectType>,
     /// The actor used for fetching CFI.
     cficache_actor: CfiCacheActor,
     /// An internal database of loaded CFI.
@@ -166,13 +226,23 @@ impl SymbolicatorSymbolProvider {
         scope: Scope,
         sources: Arc<[SourceConfig]>,
         cficache_actor: CfiCacheActor,
-        object_type: ObjectType,
+        os_type: ObjectType,
+        minidump: &Minidump,
     ) -> Self {
+        let object_types = match minidump.get_stream::<MinidumpModuleList>() {
+            Ok(modules) => modules
+                .iter()
+                .map(|module| (LookupKey::new(module), module_object_type(os_type, module)))
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+
         Self {
             scope,
             sources,
             cficache_actor,
-            object_type,
+            os_type,
+            object_types,
             cficaches: Default::default(),
         }
     }
@@ -208,6 +278,12 @@ impl SymbolicatorSymbolProvider {
 
         let code_file = non_empty_file_name(&module.code_file());
         let debug_file = module.debug_file().as_deref().and_then(non_empty_file_name);
+        let object_type = match self.object_types.get(&key) {
+            Some(object_type) => *object_type,
+            None => {
+                object_type_by_extension(code_file.as_deref(), debug_file.as_deref(), self.os_type)
+            }
+        };
 
         let identifier = ObjectId {
             code_id: module.code_identifier(),
@@ -215,13 +291,13 @@ impl SymbolicatorSymbolProvider {
             debug_id: module.debug_identifier(),
             debug_file,
             debug_checksum: None,
-            object_type: self.object_type,
+            object_type,
         };
 
         let cficache = self
             .cficache_actor
             .fetch(FetchCfiCache {
-                object_type: self.object_type,
+                object_type,
                 identifier,
                 sources,
                 scope,
@@ -286,7 +362,57 @@ impl SymbolProvider for SymbolicatorSymbolProvider {
     }
 }
 
-fn object_info_from_minidump_module(ty: ObjectType, module: &MinidumpModule) -> CompleteObjectInfo {
+/// Classifies a module by its object format, falling back to the object type of the minidump.
+///
+/// Processes running under Wine or Proton load PE modules into a Linux process. Classifying
+/// these by the operating system would skip the sources and path layouts for Windows, such as
+/// the Microsoft symbol server, which hold their debug files.
+///
+/// The format is primarily told by the CodeView record of the module. Mach-O modules carry PDB
+/// 7.0 records as well, though, so those only mark PE modules outside of Apple platforms. Modules
+/// without a record are classified by their file extensions.
+fn module_object_type(os_type: ObjectType, module: &MinidumpModule) -> ObjectType {
+    match module.codeview_info {
+        Some(CodeView::Elf(_)) => ObjectType::Elf,
+        Some(CodeView::Pdb70(_) | CodeView::Pdb20(_)) if os_type != ObjectType::Macho => {
+            ObjectType::Pe
+        }
+        _ => {
+            let code_file = non_empty_file_name(&module.code_file());
+            let debug_file = module.debug_file().as_deref().and_then(non_empty_file_name);
+            object_type_by_extension(code_file.as_deref(), debug_file.as_deref(), os_type)
+        }
+    }
+}
+
+/// Classifies a module by the extensions of its files, falling back to the object type of the
+/// minidump.
+fn object_type_by_extension(
+    code_file: Option<&str>,
+    debug_file: Option<&str>,
+    os_type: ObjectType,
+) -> ObjectType {
+    let has_extension = |file_name: Option<&str>, extensions: &[&str]| {
+        file_name.is_some_and(|file_name| {
+            let file_name = file_name.to_ascii_lowercase();
+            extensions.iter().any(|ext| file_name.ends_with(ext))
+        })
+    };
+
+    if has_extension(debug_file, &[".pdb"]) || has_extension(code_file, PE_EXTENSIONS) {
+        ObjectType::Pe
+    } else {
+        os_type
+    }
+}
+
+/// File extensions of PE executables and libraries.
+const PE_EXTENSIONS: &[&str] = &[".exe", ".dll", ".sys", ".ocx", ".drv", ".cpl", ".scr"];
+
+fn object_info_from_minidump_module(
+    os_type: ObjectType,
+    module: &MinidumpModule,
+) -> CompleteObjectInfo {
     // Some modules are not objects but rather fonts or JIT areas or other mmapped files
     // which we don't care about.  These may not have complete information so map these to
     // our schema by converting to None when needed.
@@ -296,6 +422,7 @@ fn object_info_from_minidump_module(ty: ObjectType, module: &MinidumpModule) ->
         .map(|code_id| code_id.to_string().to_lowercase());
     let code_file = non_empty_file_name(&module.code_file());
     let debug_file = module.debug_file().as_deref().and_then(non_empty_file_name);
+    let ty = module_object_type(os_type, module);
 
     CompleteObjectInfo::from(RawObjectInfo {
         ty,
@@ -315,62 +442,116 @@ fn object_info_from_minidump_module(ty: ObjectType, module: &MinidumpModule) ->
 async fn stackwalk(
     cficaches: CfiCacheActor,
     minidump: &Minidump,
+    data: &[u8],
     scope: Scope,
     sources: Arc<[SourceConfig]>,
+    unwinding: UnwindingOptions,
+    options: StackwalkOptions,
 ) -> Result<StackWalkMinidumpResult> {
     // Stackwalk the minidump.
     let duration = Instant::now();
-    let system_info = minidump
-        .get_stream::<MinidumpSystemInfo>()
-        .map_err(|_| minidump_processor::ProcessError::MissingSystemInfo)?;
-    let ty = match system_info.os {
+    let system_info = match minidump.get_stream::<MinidumpSystemInfo>() {
+        Ok(system_info) => system_info,
+        Err(_) if options.best_effort => {
+            let error = ProcessError::MissingSystemInfo;
+            let salvage = Salvage {
+                minidump,
+                data,
+                provider: None,
+                os_type: ObjectType::Unknown,
+                unwinding,
+                options,
+            };
+            return Ok(salvage.run(error, duration).await);
+        }
+        Err(_) => return Err(ProcessError::MissingSystemInfo.into()),
+    };
+    let os_type = match system_info.os {
         Os::Windows => ObjectType::Pe,
         Os::MacOs | Os::Ios => ObjectType::Macho,
         Os::Linux | Os::Solaris | Os::Android => ObjectType::Elf,
         _ => ObjectType::Unknown,
     };
-    let provider = SymbolicatorSymbolProvider::new(scope, sources, cficaches, ty);
-    let process_state = minidump_processor::process_minidump(minidump, &provider).await?;
+    let provider = SymbolicatorSymbolProvider::new(scope, sources, cficaches, os_type, minidump);
+    let process_state = match minidump_processor::process_minidump(minidump, &provider).await {
+        Ok(process_state) => process_state,
+        Err(error) if options.best_effort => {
+            let salvage = Salvage {
+                minidump,
+                data,
+                provider: Some(&provider),
+                os_type,
+                unwinding,
+                options,
+            };
+            return Ok(salvage.run(error, duration).await);
+        }
+        Err(error) => return Err(error.into()),
+    };
     let duration = duration.elapsed();
 
-    let minidump_state = MinidumpState::from_process_state(&process_state);
+    let mut minidump_state = MinidumpState::from_process_state(&process_state);
+    minidump_state.crash_address = analyze_crash_address(minidump, &process_state);
+    if options.process_resources {
+        minidump_state.process_resources =
+            Some(read_process_resources(minidump, &process_state.modules));
+    }
+
+    // The processed threads lack the platform-specific state of the raw threads.
+    let thread_list = minidump.get_stream::<MinidumpThreadList>().ok();
+    let memory_list = minidump.get_stream::<MinidumpMemoryList>().ok();
+    let async_unwinder = SwiftAsyncUnwinder::new(minidump, &process_state.modules);
 
     // Finally iterate through the threads and build the stacktraces to
     // return, marking modules as used when they are referenced by a frame.
     let requesting_thread_index: Option<usize> = process_state.requesting_thread;
     let threads = process_state.threads;
     let mut stacktraces = Vec::with_capacity(threads.len());
+    let mut frame_contexts = Vec::new();
     for (index, thread) in threads.into_iter().enumerate() {
         let registers = match thread.frames.first() {
             Some(frame) => map_symbolic_registers(&frame.context),
             None => Registers::new(),
         };
 
-        // We trim stack traces to 256 frames from the top. A similar limit is also in place in
-        // relay / store normalization, so any excess frames will be thrown away by Sentry anyway.
-        let frames = thread
-            .frames
-            .into_iter()
-            .take(256)
-            .map(|frame| {
-                let package = frame
-                    .module
-                    .and_then(|module| non_empty_file_name(&module.code_file()));
-                RawFrame {
-                    instruction_addr: HexValue(frame.resume_address),
-                    package,
-                    trust: frame.trust.into(),
-                    ..RawFrame::default()
-                }
-            })
-            .collect();
+        let thread_state = thread_list
+            .as_ref()
+            .and_then(|list| list.get_thread(thread.thread_id))
+            .and_then(|raw_thread| {
+                let last_error = match (system_info.os, &memory_list) {
+                    (Os::Windows, Some(memory_list)) => {
+                        read_last_error(raw_thread.raw.teb, system_info.cpu, memory_list)
+                    }
+                    _ => None,
+                };
+                thread_state(&raw_thread.raw, last_error)
+            });
+
+        let async_frames = async_unwinder
+            .as_ref()
+            .map(|unwinder| unwinder.async_frames(&thread.frames))
+            .unwrap_or_default();
+
+        // The caller of the last frame gives its canonical frame address.
+        if options.frame_variables > 0 && requesting_thread_index == Some(index) {
+            frame_contexts = thread
+                .frames
+                .iter()
+                .take(options.frame_variables + 1)
+                .map(|frame| map_symbolic_registers(&frame.context))
+                .collect();
+        }
+
+        let frames = raw_frames(thread.frames, unwinding, options);
 
         stacktraces.push(RawStacktrace {
             is_requesting: requesting_thread_index.map(|r| r == index),
             thread_name: thread.thread_name,
             thread_id: Some(thread.thread_id.into()),
+            thread_state,
             registers,
             frames,
+            async_frames,
         });
     }
 
@@ -389,7 +570,7 @@ async fn stackwalk(
             continue;
         }
 
-        let mut obj_info = object_info_from_minidump_module(ty, module);
+        let mut obj_info = object_info_from_minidump_module(os_type, module);
 
         let unwind_status = match cficaches.remove(&key) {
             Some(LazyCfiCache::Fetched(cfi_module)) => {
@@ -424,11 +605,243 @@ async fn stackwalk(
     Ok(StackWalkMinidumpResult {
         modules,
         stacktraces,
+        frame_contexts,
         minidump_state,
         duration,
     })
 }
 
+/// Converts the stackwalked frames of a thread into the frames of the response.
+fn raw_frames(
+    frames: Vec<StackFrame>,
+    unwinding: UnwindingOptions,
+    options: StackwalkOptions,
+) -> Vec<RawFrame> {
+    let mut frames: Vec<_> = frames
+        .into_iter()
+        .map(|frame| {
+            let package = frame
+                .module
+                .and_then(|module| non_empty_file_name(&module.code_file()));
+            RawFrame {
+                instruction_addr: HexValue(frame.resume_address),
+                package,
+                trust: frame.trust.into(),
+                registers: if options.frame_registers {
+                    map_symbolic_registers(&frame.context)
+                } else {
+                    Registers::new()
+                },
+                ..RawFrame::default()
+            }
+        })
+        .collect();
+    apply_unwinding_options(&mut frames, unwinding);
+
+    // We trim stack traces to 256 frames from the top. A similar limit is also in place in
+    // relay / store normalization, so any excess frames will be thrown away by Sentry anyway.
+    frames.truncate(256);
+    frames
+}
+
+/// Builds the result for a minidump which could not be stackwalked as a whole.
+struct Salvage<'a> {
+    minidump: &'a Minidump,
+    /// The contents of the minidump file.
+    data: &'a [u8],
+    /// The provider used to stackwalk the threads, if the system info could be read.
+    provider: Option<&'a SymbolicatorSymbolProvider>,
+    os_type: ObjectType,
+    unwinding: UnwindingOptions,
+    options: StackwalkOptions,
+}
+
+impl Salvage<'_> {
+    /// Reads the modules and threads which are still available.
+    ///
+    /// The modules are taken from the module list, so that the response still shows the modules
+    /// which were loaded into the process. The threads are read from the thread list as far as
+    /// it is contained in the file, and every thread with a CPU context is stackwalked on its
+    /// own.
+    async fn run(self, error: ProcessError, duration: Instant) -> StackWalkMinidumpResult {
+        let minidump = self.minidump;
+        let mut minidump_state = MinidumpState::from_minidump(minidump);
+        minidump_state.warnings.push(MinidumpWarning {
+            kind: MinidumpWarningKind::MissingThreads,
+            stream_type: None,
+            message: error.to_string(),
+        });
+
+        let module_list = match minidump.get_stream::<MinidumpModuleList>() {
+            Ok(module_list) => module_list,
+            Err(error) => {
+                minidump_state.warnings.push(MinidumpWarning {
+                    kind: MinidumpWarningKind::MissingModules,
+                    stream_type: None,
+                    message: error.to_string(),
+                });
+                MinidumpModuleList::new()
+            }
+        };
+        let modules = module_list
+            .by_addr()
+            .map(|module| object_info_from_minidump_module(self.os_type, module))
+            .collect();
+
+        let stacktraces = self.salvage_stacktraces(&module_list).await;
+
+        StackWalkMinidumpResult {
+            modules,
+            stacktraces,
+            frame_contexts: Vec::new(),
+            minidump_state,
+            duration: duration.elapsed(),
+        }
+    }
+
+    async fn salvage_stacktraces(&self, modules: &MinidumpModuleList) -> Vec<RawStacktrace> {
+        let minidump = self.minidump;
+        let system_info = minidump.get_stream::<MinidumpSystemInfo>().ok();
+        let misc_info = minidump.get_stream::<MinidumpMiscInfo>().ok();
+        let exception = minidump.get_stream::<MinidumpException>().ok();
+        let memory_list = minidump.get_memory();
+
+        let mut stacktraces = Vec::new();
+        for (index, thread) in salvage_threads(minidump, self.data).into_iter().enumerate() {
+            let thread_id = thread.raw.thread_id;
+            let is_requesting = exception
+                .as_ref()
+                .map(|exception| exception.thread_id == thread_id);
+
+            // Like during regular stackwalking, the context of the exception is preferred for the
+            // crashing thread.
+            let context = system_info.as_ref().and_then(|system_info| {
+                let exception_context = exception
+                    .as_ref()
+                    .filter(|exception| exception.thread_id == thread_id)
+                    .and_then(|exception| exception.context(system_info, misc_info.as_ref()));
+                match exception_context {
+                    Some(context) => Some(context.into_owned()),
+                    None => MinidumpContext::read(
+                        thread.context?,
+                        minidump.endian,
+                        system_info,
+                        misc_info.as_ref(),
+                    )
+                    .ok(),
+                }
+            });
+
+            let (registers, frames) = match context {
+                Some(context) => {
+                    let registers = map_symbolic_registers(&context);
+                    let mut stack = CallStack::with_context(context);
+                    if let (Some(provider), Some(system_info)) = (self.provider, &system_info) {
+                        let stack_memory = thread
+                            .stack
+                            .as_ref()
+                            .map(UnifiedMemory::Memory)
+                            .or_else(|| {
+                                let address = thread.raw.stack.start_of_memory_range;
+                                memory_list.as_ref()?.memory_at_address(address)
+                            });
+                        let system_info = unwind_system_info(system_info);
+                        walk_stack(
+                            index,
+                            (),
+                            &mut stack,
+                            stack_memory,
+                            modules,
+                            &system_info,
+                            provider,
+                        )
+                        .await;
+                    }
+                    let frames = raw_frames(stack.frames, self.unwinding, self.options);
+                    (registers, frames)
+                }
+                None => (Registers::new(), Vec::new()),
+            };
+
+            stacktraces.push(RawStacktrace {
+                is_requesting,
+                thread_name: None,
+                thread_id: Some(thread_id.into()),
+                thread_state: thread_state(&thread.raw, None),
+                registers,
+                frames,
+                async_frames: Vec::new(),
+            });
+        }
+        stacktraces
+    }
+}
+
+/// Returns the system info needed by the unwinder.
+fn unwind_system_info(system_info: &MinidumpSystemInfo) -> minidump_unwind::SystemInfo {
+    minidump_unwind::SystemInfo {
+        os: system_info.os,
+        os_version: None,
+        os_build: None,
+        cpu: system_info.cpu,
+        cpu_info: None,
+        cpu_microcode_version: None,
+        cpu_count: system_info.raw.number_of_processors as usize,
+    }
+}
+
+/// Returns the platform-specific state of a thread, if the minidump records any.
+fn thread_state(raw: &MINIDUMP_THREAD, last_error: Option<u32>) -> Option<ThreadState> {
+    let state = ThreadState {
+        suspend_count: raw.suspend_count,
+        priority_class: raw.priority_class,
+        priority: raw.priority,
+        teb: (raw.teb != 0).then_some(HexValue(raw.teb)),
+        last_error,
+    };
+    (state != ThreadState::default()).then_some(state)
+}
+
+/// Reads the last error value of a Windows thread from its thread environment block.
+fn read_last_error(teb: u64, cpu: Cpu, memory_list: &MinidumpMemoryList) -> Option<u32> {
+    // The offset of `LastErrorValue` within the `TEB` structure.
+    let offset = match cpu {
+        Cpu::X86 | Cpu::Arm => 0x34,
+        Cpu::X86_64 | Cpu::Arm64 => 0x68,
+        _ => return None,
+    };
+    if teb == 0 {
+        return None;
+    }
+    let address = teb.checked_add(offset)?;
+    memory_list
+        .memory_at_address(address)?
+        .get_memory_at_address(address)
+}
+
+/// Truncates the frames of a thread to the ones recovered with a method allowed by `options`.
+///
+/// The stack trace ends at the first frame which is not allowed, as all of its callers were
+/// unwound starting from that frame.
+fn apply_unwinding_options(frames: &mut Vec<RawFrame>, options: UnwindingOptions) {
+    let mut scanned_frames = 0;
+    let end = frames.iter().position(|frame| match frame.trust {
+        FrameTrust::Context | FrameTrust::Cfi | FrameTrust::PreWalked => false,
+        FrameTrust::Fp => options.strategy == UnwindingStrategy::CfiOnly,
+        FrameTrust::Scan | FrameTrust::CfiScan | FrameTrust::None => {
+            scanned_frames += 1;
+            options.strategy != UnwindingStrategy::Scan
+                || options
+                    .max_scanned_frames
+                    .is_some_and(|max| scanned_frames > max)
+        }
+    });
+
+    if let Some(end) = end {
+        frames.truncate(end);
+    }
+}
+
 fn maybe_backfill_debugid(info: &mut RawObjectInfo, cfi_module: &CfiModuleInfo) {
     if info.debug_id.is_none() {
         info.debug_id = Some(cfi_module.debug_id.to_string());
@@ -440,7 +853,7 @@ fn maybe_backfill_debugid(info: &mut RawObjectInfo, cfi_module: &CfiModuleInfo)
 
 impl SymbolicationActor {
     /// Saves the given `minidump_file` in the diagnostics cache if configured to do so.
-    fn maybe_persist_minidump(&self, minidump_file: TempPath) {
+    pub(super) fn maybe_persist_minidump(&self, minidump_file: TempPath) {
         if let Some(dir) = self.diagnostics_cache.cache_dir() {
             if let Some(file_name) = minidump_file.file_name() {
                 let path = dir.join(file_name);
@@ -461,6 +874,10 @@ impl SymbolicationActor {
         }
     }
 
+    /// Stackwalks and symbolicates a minidump.
+    ///
+    /// Threads are unwound with the given unwinding options, or with the ones configured for the
+    /// `scope` if there are none.
     pub async fn process_minidump(
         &self,
         platform: Option<Platform>,
@@ -468,13 +885,15 @@ impl SymbolicationActor {
         minidump_file: TempPath,
         sources: Arc<[SourceConfig]>,
         scraping: ScrapingConfig,
+        options: StackwalkOptions,
     ) -> Result<CompletedSymbolicationResponse> {
         let (request, state) = self
-            .stackwalk_minidump(platform, scope, minidump_file, sources, scraping)
+            .stackwalk_minidump(platform, scope, minidump_file, sources, scraping, options)
             .await?;
 
         let mut response = self.symbolicate(request).await?;
         state.merge_into(&mut response);
+        response.exploitability = analyze_exploitability(&response);
 
         Ok(response)
     }
@@ -487,15 +906,20 @@ impl SymbolicationActor {
         minidump_file: TempPath,
         sources: Arc<[SourceConfig]>,
         scraping: ScrapingConfig,
+        mut options: StackwalkOptions,
     ) -> Result<(SymbolicateStacktraces, MinidumpState)> {
+        options.frame_variables = options.frame_variables.min(self.max_frame_variables);
+
         let len = minidump_file.metadata()?.len();
         tracing::debug!("Processing minidump ({} bytes)", len);
         metric!(time_raw("minidump.upload.size") = len);
 
         let minidump_path = minidump_file.to_path_buf();
 
-        let minidump = match read_minidump(&minidump_path) {
-            Ok(md) => md,
+        // The minidump is shared with the recovery of frame variables, which runs on a
+        // blocking thread.
+        let (data, minidump) = match read_minidump(&minidump_path) {
+            Ok((data, md)) => (data, Arc::new(md)),
             Err(err) => {
                 self.maybe_persist_minidump(minidump_file);
                 return Err(err);
@@ -505,8 +929,13 @@ impl SymbolicationActor {
         let stackwalk_future = stackwalk(
             self.cficaches.clone(),
             &minidump,
+            &data,
             scope.clone(),
             sources.clone(),
+            options
+                .unwinding
+                .unwrap_or_else(|| self.unwinding.for_scope(scope.as_ref())),
+            options,
         );
 
         let result = match stackwalk_future.await {
@@ -520,12 +949,32 @@ impl SymbolicationActor {
         let StackWalkMinidumpResult {
             modules,
             mut stacktraces,
-            minidump_state,
+            frame_contexts,
+            mut minidump_state,
             duration,
         } = result;
 
         metric!(timer("minidump.stackwalk.duration") = duration);
 
+        if options.best_effort {
+            let warnings = truncated_streams(&minidump, &data);
+            minidump_state.warnings.extend(warnings);
+        }
+
+        if options.frame_variables > 0 {
+            self.recover_frame_variables(
+                minidump.clone(),
+                minidump_state.system_info.cpu_arch,
+                &scope,
+                &sources,
+                &modules,
+                &mut stacktraces,
+                frame_contexts,
+                options.frame_variables,
+            )
+            .await;
+        }
+
         match parse_stacktraces_from_minidump(&minidump) {
             Ok(Some(client_stacktraces)) => {
                 merge_clientside_with_processed_stacktraces(&mut stacktraces, client_stacktraces)
@@ -534,6 +983,14 @@ impl SymbolicationActor {
             _ => (),
         }
 
+        strip_address_tags(&modules, &mut stacktraces);
+        strip_pointer_auth(
+            &self.pointer_auth,
+            minidump_state.system_info.cpu_arch,
+            &modules,
+            &mut stacktraces,
+        );
+
         let request = SymbolicateStacktraces {
             platform,
             modules,
@@ -602,10 +1059,14 @@ fn normalize_minidump_os_name(os: Os) -> &'static str {
     }
 }
 
-fn read_minidump(path: &Path) -> Result<Minidump> {
+/// Reads the minidump at `path`.
+///
+/// The contents of the file are returned as well, to salvage the parts of truncated minidumps
+/// which the minidump parser rejects.
+fn read_minidump(path: &Path) -> Result<(ByteView<'static>, Minidump)> {
     let bv = ByteView::open(path)?;
-    let md = Minidump::read(bv)?;
-    Ok(md)
+    let md = Minidump::read(bv.clone())?;
+    Ok((bv, md))
 }
 
 /// Returns an owned version of `file_name`, or `None` if it is empty.
@@ -615,3 +1076,131 @@ fn non_empty_file_name(file_name: &str) -> Option<String> {
     }
     Some(file_name.to_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_object_type() {
+        use minidump::format::{CV_INFO_ELF, CV_INFO_PDB70, GUID};
+
+        let pdb70 = |pdb_file_name: &str| {
+            CodeView::Pdb70(CV_INFO_PDB70 {
+                cv_signature: 0x5344_5352,
+                signature: GUID {
+                    data1: 1,
+                    data2: 2,
+                    data3: 3,
+                    data4: [4; 8],
+                },
+                age: 1,
+                pdb_file_name: pdb_file_name.as_bytes().to_vec(),
+            })
+        };
+        let elf = CodeView::Elf(CV_INFO_ELF {
+            cv_signature: 0x4270_454c,
+            build_id: vec![0xab; 20],
+        });
+        let module = |name: &str, codeview_info: Option<CodeView>| {
+            let mut module = MinidumpModule::new(0x1000, 0x1000, name);
+            module.codeview_info = codeview_info;
+            module
+        };
+
+        // A PE module loaded by Wine into a Linux process, without a telling file name
+        let game = module("/home/user/game/bin/game", Some(pdb70("game")));
+        assert_eq!(module_object_type(ObjectType::Elf, &game), ObjectType::Pe);
+        // Mach-O modules carry PDB 7.0 records with their file name as well
+        let dylib = module(
+            "/usr/lib/libSystem.B.dylib",
+            Some(pdb70("libSystem.B.dylib")),
+        );
+        assert_eq!(
+            module_object_type(ObjectType::Macho, &dylib),
+            ObjectType::Macho
+        );
+
+        // An ELF module of a Windows process, for instance under WSL
+        let library = module("C:\\libs\\library.dll", Some(elf));
+        assert_eq!(
+            module_object_type(ObjectType::Pe, &library),
+            ObjectType::Elf
+        );
+
+        let library = module("C:\\libs\\library.dll", None);
+        assert_eq!(
+            module_object_type(ObjectType::Elf, &library),
+            ObjectType::Pe
+        );
+        let library = module("/usr/lib/library.so", None);
+        assert_eq!(
+            module_object_type(ObjectType::Elf, &library),
+            ObjectType::Elf
+        );
+    }
+
+    #[test]
+    fn test_object_type_by_extension() {
+        // A PE module loaded by Wine into a Linux process
+        let code_file = Some("Z:\\home\\user\\game\\bin\\Game.DLL");
+        let debug_file = Some("Game.pdb");
+        assert_eq!(
+            object_type_by_extension(code_file, debug_file, ObjectType::Elf),
+            ObjectType::Pe
+        );
+        assert_eq!(
+            object_type_by_extension(code_file, None, ObjectType::Elf),
+            ObjectType::Pe
+        );
+        assert_eq!(
+            object_type_by_extension(None, debug_file, ObjectType::Elf),
+            ObjectType::Pe
+        );
+
+        // Wine's own ELF libraries
+        let code_file = Some("/usr/lib/wine/x86_64-unix/ntdll.so");
+        assert_eq!(
+            object_type_by_extension(code_file, code_file, ObjectType::Elf),
+            ObjectType::Elf
+        );
+
+        assert_eq!(
+            object_type_by_extension(Some("libSystem.B.dylib"), None, ObjectType::Macho),
+            ObjectType::Macho
+        );
+        assert_eq!(
+            object_type_by_extension(None, None, ObjectType::Unknown),
+            ObjectType::Unknown
+        );
+    }
+
+    #[test]
+    fn test_apply_unwinding_options() {
+        use FrameTrust::{Cfi, CfiScan, Context, Fp, Scan};
+
+        let frames: Vec<_> = [Context, Cfi, Fp, Scan, Cfi, CfiScan, Scan]
+            .into_iter()
+            .map(|trust| RawFrame {
+                trust,
+                ..RawFrame::default()
+            })
+            .collect();
+        let unwind = |strategy, max_scanned_frames| {
+            let mut frames = frames.clone();
+            let options = UnwindingOptions {
+                strategy,
+                max_scanned_frames,
+            };
+            apply_unwinding_options(&mut frames, options);
+            frames.len()
+        };
+
+        assert_eq!(unwind(UnwindingStrategy::CfiOnly, None), 2);
+        assert_eq!(unwind(UnwindingStrategy::FramePointer, None), 3);
+        assert_eq!(unwind(UnwindingStrategy::FramePointer, Some(10)), 3);
+        assert_eq!(unwind(UnwindingStrategy::Scan, None), 7);
+        assert_eq!(unwind(UnwindingStrategy::Scan, Some(2)), 6);
+        assert_eq!(unwind(UnwindingStrategy::Scan, Some(0)), 3);
+    }
+}
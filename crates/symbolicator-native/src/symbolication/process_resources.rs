@@ -0,0 +1,100 @@
+//! Handles, module versions and drivers of processes in full Windows minidumps.
+//!
+//! Full minidumps record the handle table of the process, which shows the objects threads may be
+//! waiting for in a deadlock. The versions of modules and the loaded drivers help to tell whether
+//! security software interferes with a process.
+
+use minidump::{
+    MinidumpHandleDataStream, MinidumpHandleDescriptor, MinidumpModuleList, Module,
+    RawHandleDescriptor,
+};
+use symbolicator_service::utils::hex::HexValue;
+
+use crate::interface::{HandleInfo, ModuleVersion, ProcessResources};
+
+use super::process_minidump::Minidump;
+
+/// Reads the handles of the process from the minidump, along with its module versions and drivers.
+pub(super) fn read_process_resources(
+    minidump: &Minidump,
+    modules: &MinidumpModuleList,
+) -> ProcessResources {
+    let handles = match minidump.get_stream::<MinidumpHandleDataStream>() {
+        Ok(stream) => stream.handles.iter().map(handle_info).collect(),
+        Err(_) => Vec::new(),
+    };
+
+    let mut module_versions = Vec::new();
+    let mut drivers = Vec::new();
+    for module in modules.by_addr() {
+        let code_file = module.code_file();
+        let version = ModuleVersion {
+            code_file: code_file.clone().into_owned(),
+            image_addr: HexValue(module.base_address()),
+            version: module.version().map(|version| version.into_owned()),
+        };
+        if is_driver(&code_file) {
+            drivers.push(version.clone());
+        }
+        if version.version.is_some() {
+            module_versions.push(version);
+        }
+    }
+
+    ProcessResources {
+        handles,
+        module_versions,
+        drivers,
+    }
+}
+
+fn handle_info(descriptor: &MinidumpHandleDescriptor) -> HandleInfo {
+    let (handle, granted_access, handle_count, pointer_count) = match &descriptor.raw {
+        RawHandleDescriptor::HandleDescriptor(raw) => (
+            raw.handle,
+            raw.granted_access,
+            raw.handle_count,
+            raw.pointer_count,
+        ),
+        RawHandleDescriptor::HandleDescriptor2(raw) => (
+            raw.handle,
+            raw.granted_access,
+            raw.handle_count,
+            raw.pointer_count,
+        ),
+    };
+
+    HandleInfo {
+        handle: HexValue(handle),
+        type_name: descriptor.type_name.clone().filter(|name| !name.is_empty()),
+        object_name: descriptor
+            .object_name
+            .clone()
+            .filter(|name| !name.is_empty()),
+        granted_access: HexValue(granted_access.into()),
+        handle_count,
+        pointer_count,
+    }
+}
+
+/// Whether the code file of a module is a kernel driver.
+fn is_driver(code_file: &str) -> bool {
+    let file_name = code_file.rsplit(['\\', '/']).next().unwrap_or(code_file);
+    file_name
+        .rsplit_once('.')
+        .is_some_and(|(_, extension)| extension.eq_ignore_ascii_case("sys"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_driver() {
+        assert!(is_driver("C:\\Windows\\System32\\drivers\\WdFilter.sys"));
+        assert!(is_driver("\\SystemRoot\\system32\\DRIVERS\\klif.SYS"));
+        assert!(!is_driver("C:\\Windows\\System32\\ntdll.dll"));
+        assert!(!is_driver("C:\\sys\\crash.exe"));
+        assert!(!is_driver("sys"));
+    }
+}
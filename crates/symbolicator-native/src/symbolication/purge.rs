@@ -0,0 +1,50 @@
+//! Purging of all caches of a debug file.
+
+use symbolicator_service::objects::PurgeObject;
+
+use crate::interface::{PurgeCaches, PurgedCaches};
+
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Removes the cached object files of a debug file, and all caches derived from them.
+    ///
+    /// This removes errors as well, so the next request fetches the files from the sources
+    /// again. It is meant for debug files which have been replaced on a source.
+    pub async fn purge_caches(&self, request: PurgeCaches) -> PurgedCaches {
+        let PurgeCaches {
+            scope,
+            sources,
+            identifier,
+            shared_cache,
+        } = request;
+
+        let purged_object = self
+            .objects
+            .purge(PurgeObject {
+                scope,
+                identifier,
+                sources,
+                shared_cache,
+            })
+            .await;
+
+        let mut purged = PurgedCaches {
+            object_meta: purged_object.object_meta,
+            objects: purged_object.objects,
+            ..Default::default()
+        };
+        for cache_key in &purged_object.cache_keys {
+            purged.symcaches += self.symcaches.purge(cache_key, shared_cache).await;
+            purged.cficaches += self.cficaches.purge(cache_key, shared_cache).await;
+            purged.ppdb_caches += self.ppdb_caches.purge(cache_key, shared_cache).await;
+            purged.breakpad += self.breakpad.purge(cache_key, shared_cache).await;
+        }
+
+        tracing::info!(
+            "Purged the caches of {} object files",
+            purged_object.cache_keys.len()
+        );
+        purged
+    }
+}
@@ -0,0 +1,326 @@
+//! Detection of truncated minidumps, which are processed in best-effort mode.
+//!
+//! Minidumps which were cut off during the upload still start with their header and stream
+//! directory, but the streams towards the end of the file are incomplete, as is the memory the
+//! thread and memory lists point to. Such streams are reported as warnings, while the remaining
+//! streams are processed as usual. If the thread list itself is cut off, the threads it still
+//! contains are read from the file directly.
+
+use minidump::format::{
+    MINIDUMP_DIRECTORY, MINIDUMP_LOCATION_DESCRIPTOR, MINIDUMP_MEMORY_DESCRIPTOR,
+    MINIDUMP_STREAM_TYPE, MINIDUMP_THREAD,
+};
+use minidump::{Endian, MinidumpMemory};
+
+use crate::interface::{MinidumpWarning, MinidumpWarningKind};
+
+use super::process_minidump::Minidump;
+
+/// The size of a `MINIDUMP_THREAD` entry of the thread list.
+const THREAD_SIZE: usize = 48;
+
+/// The size of a `MINIDUMP_MEMORY_DESCRIPTOR` entry of the memory list.
+const MEMORY_DESCRIPTOR_SIZE: usize = 16;
+
+/// A thread which was read from the raw thread list.
+pub(super) struct SalvagedThread<'a> {
+    pub raw: MINIDUMP_THREAD,
+    /// The CPU context of the thread, if it is contained in the file.
+    pub context: Option<&'a [u8]>,
+    /// The stack memory of the thread, if it is contained in the file.
+    pub stack: Option<MinidumpMemory<'a>>,
+}
+
+/// Returns warnings for all streams of the minidump which extend beyond the end of the file, or
+/// whose threads or memory ranges do.
+pub(super) fn truncated_streams(minidump: &Minidump, data: &[u8]) -> Vec<MinidumpWarning> {
+    let file_size = data.len() as u64;
+    minidump
+        .all_streams()
+        .filter_map(|stream| {
+            truncated_stream(stream, file_size)
+                .or_else(|| truncated_contents(stream, data, minidump.endian))
+        })
+        .collect()
+}
+
+/// Reads the threads which are contained in the file from the thread list of the minidump.
+///
+/// In contrast to the `MinidumpThreadList`, this also reads thread lists which are cut off.
+pub(super) fn salvage_threads<'a>(minidump: &Minidump, data: &'a [u8]) -> Vec<SalvagedThread<'a>> {
+    let Some(stream) = minidump
+        .all_streams()
+        .find(|stream| stream.stream_type == MINIDUMP_STREAM_TYPE::ThreadListStream as u32)
+    else {
+        return Vec::new();
+    };
+
+    read_threads(stream, data, minidump.endian)
+        .into_iter()
+        .map(|raw| SalvagedThread {
+            context: location_slice(data, &raw.thread_context),
+            stack: MinidumpMemory::read(&raw.stack, data, minidump.endian).ok(),
+            raw,
+        })
+        .collect()
+}
+
+fn truncated_stream(stream: &MINIDUMP_DIRECTORY, file_size: u64) -> Option<MinidumpWarning> {
+    let location = &stream.location;
+    let start = u64::from(location.rva);
+    let end = start + u64::from(location.data_size);
+    if end <= file_size {
+        return None;
+    }
+
+    let available = file_size.saturating_sub(start);
+    Some(MinidumpWarning {
+        kind: MinidumpWarningKind::TruncatedStream,
+        stream_type: Some(stream.stream_type),
+        message: format!(
+            "stream is truncated to {available} of {} bytes",
+            location.data_size
+        ),
+    })
+}
+
+/// Checks whether the threads or memory ranges of a complete stream extend beyond the end of the
+/// file, which is where cut off minidumps usually lose their data.
+fn truncated_contents(
+    stream: &MINIDUMP_DIRECTORY,
+    data: &[u8],
+    endian: Endian,
+) -> Option<MinidumpWarning> {
+    let file_size = data.len() as u64;
+    let (truncated, total, message) = match stream.stream_type {
+        t if t == MINIDUMP_STREAM_TYPE::ThreadListStream as u32 => {
+            let threads = read_threads(stream, data, endian);
+            let truncated = threads
+                .iter()
+                .filter(|thread| {
+                    exceeds(&thread.thread_context, file_size)
+                        || exceeds(&thread.stack.memory, file_size)
+                })
+                .count();
+            (
+                truncated,
+                threads.len(),
+                "threads lack their context or stack",
+            )
+        }
+        t if t == MINIDUMP_STREAM_TYPE::MemoryListStream as u32 => {
+            let ranges = read_list(stream, data, MEMORY_DESCRIPTOR_SIZE, endian, |entry| {
+                read_memory_descriptor(entry, endian)
+            });
+            let truncated = ranges
+                .iter()
+                .filter(|range| exceeds(&range.memory, file_size))
+                .count();
+            (truncated, ranges.len(), "memory ranges are truncated")
+        }
+        _ => return None,
+    };
+    if truncated == 0 {
+        return None;
+    }
+
+    Some(MinidumpWarning {
+        kind: MinidumpWarningKind::TruncatedStream,
+        stream_type: Some(stream.stream_type),
+        message: format!("{truncated} of {total} {message}"),
+    })
+}
+
+/// Whether the data at `location` extends beyond the end of the file.
+fn exceeds(location: &MINIDUMP_LOCATION_DESCRIPTOR, file_size: u64) -> bool {
+    u64::from(location.rva) + u64::from(location.data_size) > file_size
+}
+
+fn location_slice<'a>(data: &'a [u8], location: &MINIDUMP_LOCATION_DESCRIPTOR) -> Option<&'a [u8]> {
+    let start = location.rva as usize;
+    data.get(start..start.checked_add(location.data_size as usize)?)
+}
+
+fn read_threads(stream: &MINIDUMP_DIRECTORY, data: &[u8], endian: Endian) -> Vec<MINIDUMP_THREAD> {
+    read_list(stream, data, THREAD_SIZE, endian, |entry| MINIDUMP_THREAD {
+        thread_id: read_u32(entry, 0, endian),
+        suspend_count: read_u32(entry, 4, endian),
+        priority_class: read_u32(entry, 8, endian),
+        priority: read_u32(entry, 12, endian),
+        teb: read_u64(entry, 16, endian),
+        stack: read_memory_descriptor(&entry[24..], endian),
+        thread_context: read_location(&entry[40..], endian),
+    })
+}
+
+/// Reads the complete entries of a list stream, which starts with the number of entries.
+///
+/// Entries which are cut off are skipped, along with all entries after them.
+fn read_list<T>(
+    stream: &MINIDUMP_DIRECTORY,
+    data: &[u8],
+    entry_size: usize,
+    endian: Endian,
+    read_entry: impl Fn(&[u8]) -> T,
+) -> Vec<T> {
+    let start = stream.location.rva as usize;
+    let end = start.saturating_add(stream.location.data_size as usize);
+    let Some(bytes) = data.get(start..end.min(data.len())) else {
+        return Vec::new();
+    };
+    if bytes.len() < 4 {
+        return Vec::new();
+    }
+
+    // Some writers align the entries to 8 bytes, which is only visible from the stream size.
+    let count = read_u32(bytes, 0, endian) as usize;
+    let offset = match count.checked_mul(entry_size) {
+        Some(size) if size.checked_add(8) == Some(stream.location.data_size as usize) => 8,
+        _ => 4,
+    };
+
+    bytes[offset.min(bytes.len())..]
+        .chunks_exact(entry_size)
+        .take(count)
+        .map(read_entry)
+        .collect()
+}
+
+fn read_memory_descriptor(bytes: &[u8], endian: Endian) -> MINIDUMP_MEMORY_DESCRIPTOR {
+    MINIDUMP_MEMORY_DESCRIPTOR {
+        start_of_memory_range: read_u64(bytes, 0, endian),
+        memory: read_location(&bytes[8..], endian),
+    }
+}
+
+fn read_location(bytes: &[u8], endian: Endian) -> MINIDUMP_LOCATION_DESCRIPTOR {
+    MINIDUMP_LOCATION_DESCRIPTOR {
+        data_size: read_u32(bytes, 0, endian),
+        rva: read_u32(bytes, 4, endian),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize, endian: Endian) -> u32 {
+    let bytes = bytes[offset..offset + 4].try_into().unwrap();
+    match endian {
+        Endian::Little => u32::from_le_bytes(bytes),
+        Endian::Big => u32::from_be_bytes(bytes),
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize, endian: Endian) -> u64 {
+    let bytes = bytes[offset..offset + 8].try_into().unwrap();
+    match endian {
+        Endian::Little => u64::from_le_bytes(bytes),
+        Endian::Big => u64::from_be_bytes(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(rva: u32, data_size: u32) -> MINIDUMP_LOCATION_DESCRIPTOR {
+        MINIDUMP_LOCATION_DESCRIPTOR { data_size, rva }
+    }
+
+    fn stream(stream_type: u32, rva: u32, data_size: u32) -> MINIDUMP_DIRECTORY {
+        MINIDUMP_DIRECTORY {
+            stream_type,
+            location: location(rva, data_size),
+        }
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        assert_eq!(truncated_stream(&stream(4, 0x100, 0x100), 0x200), None);
+
+        let warning = truncated_stream(&stream(3, 0x100, 0x200), 0x180).unwrap();
+        assert_eq!(warning.kind, MinidumpWarningKind::TruncatedStream);
+        assert_eq!(warning.stream_type, Some(3));
+        assert_eq!(warning.message, "stream is truncated to 128 of 512 bytes");
+
+        let warning = truncated_stream(&stream(5, 0x400, 0x10), 0x180).unwrap();
+        assert_eq!(warning.message, "stream is truncated to 0 of 16 bytes");
+    }
+
+    /// Builds a thread list at offset 0, with locations of the stack and context of every thread.
+    fn thread_list(threads: &[[MINIDUMP_LOCATION_DESCRIPTOR; 2]], padding: bool) -> Vec<u8> {
+        let mut data = (threads.len() as u32).to_le_bytes().to_vec();
+        if padding {
+            data.extend([0; 4]);
+        }
+        for (index, locations) in threads.iter().enumerate() {
+            data.extend((index as u32 + 1).to_le_bytes());
+            data.extend([0; 12]);
+            data.extend(0x1000u64.to_le_bytes());
+            data.extend(0x7000u64.to_le_bytes());
+            for location in locations {
+                data.extend(location.data_size.to_le_bytes());
+                data.extend(location.rva.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_threads() {
+        for padding in [false, true] {
+            let mut data = thread_list(
+                &[[location(0x100, 0x10), location(0x110, 0x20)]; 3],
+                padding,
+            );
+            let list = stream(3, 0, data.len() as u32);
+            data.resize(0x200, 0);
+
+            let threads = read_threads(&list, &data, Endian::Little);
+            assert_eq!(threads.len(), 3);
+            assert_eq!(threads[2].thread_id, 3);
+            assert_eq!(threads[2].teb, 0x1000);
+            assert_eq!(threads[2].stack.start_of_memory_range, 0x7000);
+            assert_eq!(threads[2].stack.memory.rva, 0x100);
+            assert_eq!(threads[2].thread_context.data_size, 0x20);
+
+            // Threads which are cut off are skipped.
+            let end = list.location.data_size as usize - 10;
+            let threads = read_threads(&list, &data[..end], Endian::Little);
+            assert_eq!(threads.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_truncated_contents() {
+        let mut data = thread_list(
+            &[
+                [location(0x100, 0x10), location(0x110, 0x20)],
+                [location(0x130, 0x10), location(0x140, 0x20)],
+            ],
+            false,
+        );
+        let list = stream(3, 0, data.len() as u32);
+        data.resize(0x150, 0);
+
+        let warning = truncated_contents(&list, &data, Endian::Little).unwrap();
+        assert_eq!(warning.stream_type, Some(3));
+        assert_eq!(
+            warning.message,
+            "1 of 2 threads lack their context or stack"
+        );
+
+        data.resize(0x160, 0);
+        assert_eq!(truncated_contents(&list, &data, Endian::Little), None);
+
+        let mut data = 2u32.to_le_bytes().to_vec();
+        for (rva, size) in [(0x30, 0x10), (0x40, 0x100)] {
+            data.extend(0x7000u64.to_le_bytes());
+            data.extend(u32::to_le_bytes(size));
+            data.extend(u32::to_le_bytes(rva));
+        }
+        let list = stream(5, 0, data.len() as u32);
+        data.resize(0x80, 0);
+
+        let warning = truncated_contents(&list, &data, Endian::Little).unwrap();
+        assert_eq!(warning.stream_type, Some(5));
+        assert_eq!(warning.message, "1 of 2 memory ranges are truncated");
+    }
+}
@@ -3,9 +3,9 @@ use std::collections::HashMap;
 use futures::future;
 use symbolicator_service::caching::{CacheEntry, CacheError};
 use symbolicator_service::objects::{
-    ObjectCandidate, ObjectDownloadInfo, ObjectFeatures, ObjectUseInfo,
+    CandidateRejection, ObjectCandidate, ObjectDownloadInfo, ObjectFeatures, ObjectUseInfo,
 };
-use symbolicator_service::types::{Scope, ScrapingConfig};
+use symbolicator_service::types::{Scope, ScrapingConfig, SourceRepository};
 use symbolicator_service::utils::http::is_valid_origin;
 use symbolicator_sources::{HttpRemoteFile, RemoteFileUri, SourceId};
 
@@ -14,6 +14,16 @@ use crate::interface::{CompleteStacktrace, RawFrame};
 use super::module_lookup::ModuleLookup;
 use super::symbolicate::SymbolicationActor;
 
+/// A source file which is fetched from a source link or a source repository.
+struct RemoteSource<'a> {
+    /// The index of the module the frames belong to.
+    module_idx: usize,
+    /// The repository of the file, or `None` for source links.
+    repository: Option<&'a SourceRepository>,
+    /// The frames which refer to the file.
+    frames: Vec<&'a mut RawFrame>,
+}
+
 impl SymbolicationActor {
     pub async fn apply_source_context(
         &self,
@@ -27,23 +37,33 @@ impl SymbolicationActor {
 
         // Map collected source contexts to the index of the module
         // and the list of frames they belong to and collect URLs
-        // for remote source links.
-        let mut remote_sources: HashMap<(Scope, url::Url), (usize, Vec<&mut RawFrame>)> =
-            HashMap::new();
+        // for remote source links and files in source repositories.
+        let mut remote_sources: HashMap<(Scope, url::Url), RemoteSource<'_>> = HashMap::new();
         {
             let debug_sessions = module_lookup.prepare_debug_sessions();
 
             for trace in stacktraces {
                 for frame in &mut trace.frames {
-                    let Some((scope, url, module_idx)) =
-                        module_lookup.try_set_source_context(&debug_sessions, &mut frame.raw)
-                    else {
-                        continue;
+                    let source_link = module_lookup
+                        .try_set_source_context(&debug_sessions, &mut frame.raw)
+                        .filter(|_| self.source_links.enabled);
+                    let (key, module_idx, repository) = match source_link {
+                        Some((scope, url, module_idx)) => ((scope, url), module_idx, None),
+                        None => {
+                            let Some((scope, url, module_idx, repository)) = module_lookup
+                                .try_map_to_repository(&scraping.source_repositories, &frame.raw)
+                            else {
+                                continue;
+                            };
+                            ((scope, url), module_idx, Some(repository))
+                        }
                     };
-                    let (_idx, frames) = remote_sources
-                        .entry((scope, url))
-                        .or_insert((module_idx, vec![]));
-                    frames.push(&mut frame.raw);
+                    let remote_source = remote_sources.entry(key).or_insert(RemoteSource {
+                        module_idx,
+                        repository,
+                        frames: vec![],
+                    });
+                    remote_source.frames.push(&mut frame.raw);
                 }
             }
         }
@@ -51,34 +71,70 @@ impl SymbolicationActor {
         // Download remote sources and update contexts.
         if !remote_sources.is_empty() {
             let cache = self.sourcefiles_cache.as_ref();
-            let futures = remote_sources.into_iter().map(
-                |((source_scope, url), (module_idx, frames))| async move {
-                    let mut remote_file =
-                        HttpRemoteFile::from_url(url.clone(), scraping.verify_ssl);
-
-                    if scraping.enabled && is_valid_origin(&url, &scraping.allowed_origins) {
-                        remote_file.headers.extend(
-                            scraping
-                                .headers
-                                .iter()
-                                .map(|(key, value)| (key.clone(), value.clone())),
-                        );
-                    }
-
-                    let uri = remote_file.uri();
-                    let res = cache
-                        .fetch_file(&source_scope, remote_file.into(), true)
-                        .await;
-
-                    if let Ok(source) = res.as_ref() {
-                        for frame in frames {
-                            ModuleLookup::set_source_context(source, frame);
+            let source_links = &self.source_links;
+            let futures =
+                remote_sources
+                    .into_iter()
+                    .map(|((source_scope, url), remote_source)| async move {
+                        let RemoteSource {
+                            module_idx,
+                            repository,
+                            frames,
+                        } = remote_source;
+                        let mut remote_file =
+                            HttpRemoteFile::from_url(url.clone(), scraping.verify_ssl);
+
+                        let source = match repository {
+                            Some(repository) => {
+                                remote_file.headers.extend(repository.headers.clone());
+                                SourceId::new("repository")
+                            }
+                            None => {
+                                let source = SourceId::new("sourcelink");
+                                if !is_valid_origin(&url, &source_links.allowed_origins) {
+                                    let error = CacheError::Blocked("Origin is not allowed".into());
+                                    let res = Err::<(), _>(error);
+                                    let candidate = Self::object_candidate_for_url(
+                                        source,
+                                        remote_file.uri(),
+                                        res,
+                                    );
+                                    return (module_idx, candidate);
+                                }
+
+                                for credentials in &source_links.credentials {
+                                    if is_valid_origin(&url, &credentials.origins) {
+                                        remote_file.headers.extend(credentials.headers.clone());
+                                    }
+                                }
+
+                                if scraping.enabled
+                                    && is_valid_origin(&url, &scraping.allowed_origins)
+                                {
+                                    remote_file.headers.extend(
+                                        scraping
+                                            .headers
+                                            .iter()
+                                            .map(|(key, value)| (key.clone(), value.clone())),
+                                    );
+                                }
+                                source
+                            }
+                        };
+
+                        let uri = remote_file.uri();
+                        let res = cache
+                            .fetch_file(&source_scope, remote_file.into(), true)
+                            .await;
+
+                        if let Ok(source) = res.as_ref() {
+                            for frame in frames {
+                                ModuleLookup::set_source_context(source, frame);
+                            }
                         }
-                    }
 
-                    (module_idx, Self::object_candidate_for_sourcelink(uri, res))
-                },
-            );
+                        (module_idx, Self::object_candidate_for_url(source, uri, res))
+                    });
 
             let candidates = future::join_all(futures).await;
 
@@ -91,14 +147,15 @@ impl SymbolicationActor {
     }
 
     // Creates an `ObjectCandidate` based on trying to download a
-    // source file from a link.
-    fn object_candidate_for_sourcelink<T>(
+    // source file from a link or repository.
+    fn object_candidate_for_url<T>(
+        source: SourceId,
         location: RemoteFileUri,
         res: CacheEntry<T>,
     ) -> ObjectCandidate {
-        let source = SourceId::new("sourcelink");
         let unwind = ObjectUseInfo::None;
         let debug = ObjectUseInfo::None;
+        let rejection = res.as_ref().err().and_then(CandidateRejection::from_error);
         let download = match res {
             Ok(_) => ObjectDownloadInfo::Ok {
                 features: ObjectFeatures {
@@ -110,6 +167,7 @@ impl SymbolicationActor {
             Err(error) => match error {
                 CacheError::NotFound => ObjectDownloadInfo::NotFound,
                 CacheError::PermissionDenied(details) => ObjectDownloadInfo::NoPerm { details },
+                CacheError::Blocked(details) => ObjectDownloadInfo::Blocked { details },
                 CacheError::Malformed(_) => ObjectDownloadInfo::Malformed,
                 err => ObjectDownloadInfo::Error {
                     details: err.to_string(),
@@ -121,6 +179,7 @@ impl SymbolicationActor {
             source,
             location,
             download,
+            rejection,
             unwind,
             debug,
         }
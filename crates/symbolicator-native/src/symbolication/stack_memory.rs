@@ -0,0 +1,381 @@
+//! Stackwalking of threads from their raw stack memory.
+//!
+//! Agents which cannot write minidumps may still report the registers and stack memory of the
+//! threads of a process, along with its loaded modules. These are written into a minidump,
+//! which then goes through the same stackwalking and symbolication as any other minidump.
+
+use anyhow::{bail, Result};
+use symbolic::common::{CpuFamily, DebugId};
+use symbolicator_service::types::RawObjectInfo;
+use symbolicator_sources::ObjectType;
+
+use crate::interface::{
+    CompletedSymbolicationResponse, ProcessOs, RawProcess, Registers, StackwalkStackMemory,
+};
+
+use super::minidump_writer::{
+    current_timestamp, elf_cv_record, guid_bytes, pdb70_cv_record, write_minidump,
+    write_minidump_file, ContextArch, ExceptionRecord, MinidumpContents, ModuleRecord,
+    ThreadRecord, PLATFORM_ANDROID, PLATFORM_IOS, PLATFORM_LINUX, PLATFORM_MACOS, PLATFORM_WINDOWS,
+};
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Stackwalks and symbolicates the threads of a process from their stack memory.
+    pub async fn process_stack_memory(
+        &self,
+        request: StackwalkStackMemory,
+    ) -> Result<CompletedSymbolicationResponse> {
+        let StackwalkStackMemory {
+            platform,
+            scope,
+            sources,
+            process,
+            scraping,
+            options,
+        } = request;
+
+        let minidump = convert_process(&process, current_timestamp())?;
+        drop(process);
+        let minidump_file = write_minidump_file(&minidump, None)?;
+
+        self.process_minidump(platform, scope, minidump_file, sources, scraping, options)
+            .await
+    }
+}
+
+/// Converts the threads and modules of a process into a minidump.
+fn convert_process(process: &RawProcess, timestamp: u32) -> Result<Vec<u8>> {
+    let arch = match process.arch.cpu_family() {
+        CpuFamily::Amd64 => ContextArch::Amd64,
+        CpuFamily::Arm64 => ContextArch::Arm64,
+        _ => bail!("unsupported architecture {}", process.arch),
+    };
+    if process.threads.is_empty() {
+        bail!("process does not contain any threads");
+    }
+
+    let threads: Vec<_> = process
+        .threads
+        .iter()
+        .map(|thread| {
+            let registers = map_registers(arch, &thread.registers);
+            let stack_start = match thread.stack_start {
+                Some(stack_start) => stack_start.0,
+                None => arch.stack_pointer(&registers),
+            };
+            ThreadRecord {
+                tid: thread.thread_id,
+                registers,
+                stack_start,
+                stack: &thread.stack_memory,
+            }
+        })
+        .collect();
+
+    let exception = match &process.exception {
+        Some(exception) => {
+            let Some(thread_index) = process
+                .threads
+                .iter()
+                .position(|thread| thread.thread_id == exception.thread_id)
+            else {
+                bail!("exception refers to unknown thread {}", exception.thread_id);
+            };
+            let address = match exception.address {
+                Some(address) => address.0,
+                None => arch.instruction_pointer(&threads[thread_index].registers),
+            };
+            Some(ExceptionRecord {
+                thread_index,
+                code: exception.code,
+                flags: 0,
+                address,
+            })
+        }
+        None => None,
+    };
+
+    let platform_id = match process.os {
+        ProcessOs::Windows => PLATFORM_WINDOWS,
+        ProcessOs::Macos => PLATFORM_MACOS,
+        ProcessOs::Ios => PLATFORM_IOS,
+        ProcessOs::Linux => PLATFORM_LINUX,
+        ProcessOs::Android => PLATFORM_ANDROID,
+    };
+
+    let contents = MinidumpContents {
+        arch,
+        platform_id,
+        threads,
+        modules: map_modules(&process.modules),
+        exception,
+    };
+    Ok(write_minidump(&contents, timestamp))
+}
+
+/// Orders the registers of a thread like [`ContextArch::register_names`].
+///
+/// Registers which are not given are zero. On ARM64, the frame pointer, link register and
+/// status register can also be given as `fp`, `lr` and `cpsr`.
+fn map_registers(arch: ContextArch, registers: &Registers) -> Vec<u64> {
+    arch.register_names()
+        .iter()
+        .map(|name| {
+            let alias = match *name {
+                "x29" => Some("fp"),
+                "x30" => Some("lr"),
+                "pstate" => Some("cpsr"),
+                _ => None,
+            };
+            registers
+                .get(*name)
+                .or_else(|| registers.get(alias?))
+                .map_or(0, |value| value.0)
+        })
+        .collect()
+}
+
+/// Maps the modules of a process to minidump modules, ordered by their address.
+///
+/// Modules without an image size extend up to the next module.
+fn map_modules(modules: &[RawObjectInfo]) -> Vec<ModuleRecord<'_>> {
+    let mut modules: Vec<_> = modules.iter().collect();
+    modules.sort_by_key(|module| module.image_addr);
+
+    let mut records = Vec::with_capacity(modules.len());
+    for (index, &module) in modules.iter().enumerate() {
+        let base = module.image_addr.0;
+        let size = match module.image_size {
+            Some(size) if size > 0 => size,
+            _ => modules
+                .get(index + 1)
+                .map_or(u32::MAX.into(), |next| next.image_addr.0 - base),
+        };
+        let (timestamp, cv_record) = identify_module(module);
+        let name = module.code_file.as_deref().or(module.debug_file.as_deref());
+
+        records.push(ModuleRecord {
+            base,
+            size: u32::try_from(size).unwrap_or(u32::MAX),
+            timestamp,
+            name: name.unwrap_or_default(),
+            cv_record,
+        });
+    }
+    records
+}
+
+/// Returns the PE timestamp and the CodeView record which identify a module.
+///
+/// These are written the way Breakpad writes them, so that the code and debug ids of the
+/// module are read back from the minidump unchanged.
+fn identify_module(module: &RawObjectInfo) -> (u32, Vec<u8>) {
+    let debug_id = module
+        .debug_id
+        .as_deref()
+        .and_then(|debug_id| debug_id.parse::<DebugId>().ok());
+    let debug_file = module.debug_file.as_deref().unwrap_or_default();
+
+    match module.ty {
+        ObjectType::Elf => {
+            let build_id = module.code_id.as_deref().and_then(parse_hex);
+            let cv_record = match (build_id, debug_id) {
+                (Some(build_id), _) => elf_cv_record(&build_id),
+                // The debug id is derived from the start of the build id.
+                (None, Some(debug_id)) => elf_cv_record(&guid_bytes(debug_id.uuid().as_bytes())),
+                (None, None) => Vec::new(),
+            };
+            (0, cv_record)
+        }
+        ObjectType::Pe => {
+            // The code id of PE files is their timestamp followed by their image size.
+            let timestamp = module
+                .code_id
+                .as_deref()
+                .and_then(|code_id| code_id.get(..8))
+                .and_then(|timestamp| u32::from_str_radix(timestamp, 16).ok())
+                .unwrap_or_default();
+            let cv_record = debug_id.map(|debug_id| {
+                pdb70_cv_record(debug_id.uuid().as_bytes(), debug_id.appendix(), debug_file)
+            });
+            (timestamp, cv_record.unwrap_or_default())
+        }
+        ObjectType::Macho => {
+            // The code id of Mach-O files is their UUID, which is also their debug id.
+            let debug_id = debug_id.or_else(|| module.code_id.as_deref()?.parse().ok());
+            let cv_record =
+                debug_id.map(|debug_id| pdb70_cv_record(debug_id.uuid().as_bytes(), 0, debug_file));
+            (0, cv_record.unwrap_or_default())
+        }
+        _ => (0, Vec::new()),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use minidump::system_info::{Cpu, Os};
+    use minidump::{
+        Minidump, MinidumpException, MinidumpModuleList, MinidumpSystemInfo, MinidumpThreadList,
+        Module,
+    };
+    use symbolic::common::Arch;
+    use symbolicator_service::utils::hex::HexValue;
+
+    use crate::interface::{RawException, RawThread};
+
+    use super::*;
+
+    fn module(ty: ObjectType, code_id: &str, debug_id: &str, code_file: &str) -> RawObjectInfo {
+        RawObjectInfo {
+            ty,
+            code_id: Some(code_id.to_owned()),
+            code_file: Some(code_file.to_owned()),
+            debug_id: Some(debug_id.to_owned()),
+            debug_file: Some(code_file.replace(".dll", ".pdb")),
+            debug_checksum: None,
+            image_addr: HexValue(0),
+            image_size: None,
+        }
+    }
+
+    fn process() -> RawProcess {
+        let thread = |thread_id, rip, rsp| RawThread {
+            thread_id,
+            registers: [("rip", rip), ("rsp", rsp)]
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), HexValue(value)))
+                .collect(),
+            stack_start: None,
+            stack_memory: vec![0x11; 0x100],
+        };
+
+        let mut elf = module(
+            ObjectType::Elf,
+            "0123456789abcdeffedcba9876543210aabbccdd",
+            "67452301-ab89-efcd-fedc-ba9876543210",
+            "/usr/lib/libtest.so",
+        );
+        elf.image_addr = HexValue(0x10000);
+        elf.image_size = Some(0x3000);
+        let mut pe = module(
+            ObjectType::Pe,
+            "5ab380779000",
+            "3249d99d-0c40-4931-8610-f4e4fb0b6936-1",
+            "C:\\crash.dll",
+        );
+        pe.image_addr = HexValue(0x20000);
+
+        RawProcess {
+            arch: Arch::Amd64,
+            os: ProcessOs::Linux,
+            threads: vec![
+                thread(42, 0x10100, 0x7000_1000),
+                thread(43, 0x20100, 0x7000_2000),
+            ],
+            modules: vec![pe, elf],
+            exception: Some(RawException {
+                thread_id: 43,
+                code: 11,
+                address: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_convert_process() {
+        let minidump = convert_process(&process(), 0).unwrap();
+        let minidump = Minidump::read(minidump).unwrap();
+
+        let system_info: MinidumpSystemInfo = minidump.get_stream().unwrap();
+        assert_eq!(system_info.os, Os::Linux);
+        assert_eq!(system_info.cpu, Cpu::X86_64);
+
+        let threads: MinidumpThreadList = minidump.get_stream().unwrap();
+        let threads: Vec<_> = threads
+            .threads
+            .iter()
+            .map(|thread| (thread.raw.thread_id, thread.raw.stack.start_of_memory_range))
+            .collect();
+        assert_eq!(threads, [(42, 0x7000_1000), (43, 0x7000_2000)]);
+
+        let modules: MinidumpModuleList = minidump.get_stream().unwrap();
+        let modules: Vec<_> = modules.iter().collect();
+        assert_eq!(modules.len(), 2);
+
+        let elf = modules[0];
+        assert_eq!(elf.code_file(), "/usr/lib/libtest.so");
+        assert_eq!(elf.base_address(), 0x10000);
+        assert_eq!(elf.size(), 0x3000);
+        assert_eq!(
+            elf.code_identifier().unwrap().as_str(),
+            "0123456789abcdeffedcba9876543210aabbccdd"
+        );
+        assert_eq!(
+            elf.debug_identifier().unwrap(),
+            "67452301-ab89-efcd-fedc-ba9876543210"
+                .parse::<DebugId>()
+                .unwrap()
+        );
+
+        // The PE module has no size and extends to the end of the address range.
+        let pe = modules[1];
+        assert_eq!(pe.base_address(), 0x20000);
+        assert_eq!(pe.size(), u64::from(u32::MAX));
+        assert_eq!(pe.raw.time_date_stamp, 0x5ab38077);
+        assert_eq!(
+            pe.debug_identifier().unwrap(),
+            "3249d99d-0c40-4931-8610-f4e4fb0b6936-1"
+                .parse::<DebugId>()
+                .unwrap()
+        );
+        assert_eq!(pe.debug_file().as_deref(), Some("C:\\crash.pdb"));
+
+        let exception: MinidumpException = minidump.get_stream().unwrap();
+        assert_eq!(exception.raw.thread_id, 43);
+        assert_eq!(exception.raw.exception_record.exception_code, 11);
+        assert_eq!(exception.raw.exception_record.exception_address, 0x20100);
+    }
+
+    #[test]
+    fn test_convert_invalid_process() {
+        let mut process = process();
+        process.exception.as_mut().unwrap().thread_id = 1;
+        let err = convert_process(&process, 0).unwrap_err();
+        assert_eq!(err.to_string(), "exception refers to unknown thread 1");
+
+        process.arch = Arch::X86;
+        let err = convert_process(&process, 0).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported architecture x86");
+
+        process.arch = Arch::Amd64;
+        process.threads.clear();
+        let err = convert_process(&process, 0).unwrap_err();
+        assert_eq!(err.to_string(), "process does not contain any threads");
+    }
+
+    #[test]
+    fn test_map_arm64_registers() {
+        let registers = [("x0", 1), ("fp", 2), ("x30", 3), ("sp", 4), ("pc", 5)]
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), HexValue(value)))
+            .collect();
+
+        let registers = map_registers(ContextArch::Arm64, &registers);
+        assert_eq!(registers.len(), 34);
+        assert_eq!(registers[0], 1);
+        assert_eq!(&registers[29..33], [2, 3, 4, 5]);
+        assert_eq!(ContextArch::Arm64.stack_pointer(&registers), 4);
+        assert_eq!(ContextArch::Arm64.instruction_pointer(&registers), 5);
+    }
+}
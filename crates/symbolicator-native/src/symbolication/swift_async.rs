@@ -0,0 +1,262 @@
+//! Reconstruction of the logical backtraces of Swift async functions.
+//!
+//! Swift async functions run on the stack of whichever thread executes their task, but their
+//! callers are not on that stack. Instead, every async function has an async context in the heap
+//! allocations of its task, which points to the context of its caller and to the resume function
+//! which continues the caller once the function returns.
+//!
+//! Async frames mark the frame pointer they save with [`ASYNC_FRAME_FLAG`], and store their async
+//! context right below it. Following the chain of contexts from there yields the logical callers,
+//! as long as the minidump contains the memory of the task allocations.
+
+use minidump::{MinidumpModuleList, Module, UnifiedMemoryList};
+use minidump_unwind::StackFrame;
+use symbolicator_service::utils::hex::HexValue;
+
+use crate::interface::RawFrame;
+
+use super::process_minidump::Minidump;
+
+/// The flag set in frame pointers saved by Swift async frames.
+const ASYNC_FRAME_FLAG: u64 = 1 << 60;
+
+/// The number of significant bits of user space addresses.
+///
+/// Resume functions are signed with a pointer authentication code on `arm64e`, which is stored
+/// in the bits above.
+const ADDRESS_BITS: u32 = 47;
+
+/// The maximum number of async frames per thread, matching the limit of physical frames.
+const MAX_ASYNC_FRAMES: usize = 256;
+
+/// Follows the async contexts of Swift async functions in the memory of a minidump.
+pub(super) struct SwiftAsyncUnwinder<'a> {
+    memory: UnifiedMemoryList<'a>,
+}
+
+impl<'a> SwiftAsyncUnwinder<'a> {
+    /// Creates an unwinder for the minidump.
+    ///
+    /// Returns `None` unless the Swift concurrency runtime is loaded into the process.
+    pub(super) fn new(minidump: &'a Minidump, modules: &MinidumpModuleList) -> Option<Self> {
+        let has_concurrency_runtime = modules
+            .iter()
+            .any(|module| module.code_file().contains("swift_Concurrency"));
+        if !has_concurrency_runtime {
+            return None;
+        }
+
+        let memory = minidump.get_memory()?;
+        Some(Self { memory })
+    }
+
+    /// Returns the logical callers of the innermost async function among the frames of a thread.
+    ///
+    /// The frames are the resume functions of the callers, starting with the direct caller.
+    pub(super) fn async_frames(&self, frames: &[StackFrame]) -> Vec<RawFrame> {
+        let mut async_frames = Vec::new();
+        let Some(mut context) = frames.iter().find_map(|frame| self.async_context(frame)) else {
+            return async_frames;
+        };
+
+        while context != 0 && async_frames.len() < MAX_ASYNC_FRAMES {
+            // The `Parent` and `ResumeParent` fields at the start of every `AsyncContext`.
+            let Some(parent) = self.read(context) else {
+                break;
+            };
+            let Some(resume_parent) = context.checked_add(8).and_then(|addr| self.read(addr))
+            else {
+                break;
+            };
+
+            let resume_addr = strip_address(resume_parent);
+            if resume_addr == 0 {
+                break;
+            }
+            async_frames.push(RawFrame {
+                instruction_addr: HexValue(resume_addr),
+                // Resume functions are entered rather than returned to.
+                adjust_instruction_addr: Some(false),
+                ..RawFrame::default()
+            });
+            context = strip_address(parent);
+        }
+
+        async_frames
+    }
+
+    /// Returns the async context of the frame if it belongs to an async function.
+    fn async_context(&self, frame: &StackFrame) -> Option<u64> {
+        let context = &frame.context;
+        let frame_pointer = context
+            .get_register("fp")
+            .or_else(|| context.get_register("rbp"))?;
+
+        let saved_frame_pointer = self.read(frame_pointer)?;
+        if saved_frame_pointer & ASYNC_FRAME_FLAG == 0 {
+            return None;
+        }
+        let context = self.read(frame_pointer.checked_sub(8)?)?;
+        Some(strip_address(context))
+    }
+
+    fn read(&self, addr: u64) -> Option<u64> {
+        self.memory
+            .memory_at_address(addr)?
+            .get_memory_at_address(addr)
+    }
+}
+
+/// Removes pointer authentication codes and flags from the upper bits of an address.
+fn strip_address(addr: u64) -> u64 {
+    addr & ((1 << ADDRESS_BITS) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use minidump::{MinidumpSystemInfo, MinidumpThreadList};
+    use minidump_unwind::FrameTrust;
+    use symbolic::common::ByteView;
+
+    use super::super::minidump_writer::{
+        write_minidump, ContextArch, MinidumpContents, ModuleRecord, ThreadRecord, PLATFORM_IOS,
+    };
+    use super::*;
+
+    /// The start of the memory of the thread, which holds its stack and the async contexts.
+    const MEMORY_START: u64 = 0x1_6000_0000;
+    /// The size of the memory of the thread.
+    const MEMORY_SIZE: usize = 0x1000;
+    /// The frame pointer of the innermost frame of the thread.
+    const FRAME_POINTER: u64 = MEMORY_START + 0x100;
+
+    /// Writes an `arm64` minidump with a single thread, whose memory contains the given words.
+    fn write_thread(words: &[(u64, u64)]) -> Minidump {
+        let mut memory = vec![0; MEMORY_SIZE];
+        for &(addr, value) in words {
+            let offset = (addr - MEMORY_START) as usize;
+            memory[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let mut registers = vec![0; ContextArch::Arm64.register_names().len()];
+        registers[29] = FRAME_POINTER;
+        registers[31] = MEMORY_START;
+        registers[32] = 0x1_8000_1000;
+
+        let contents = MinidumpContents {
+            arch: ContextArch::Arm64,
+            platform_id: PLATFORM_IOS,
+            threads: vec![ThreadRecord {
+                tid: 1,
+                registers,
+                stack_start: MEMORY_START,
+                stack: &memory,
+            }],
+            modules: vec![ModuleRecord {
+                base: 0x1_8000_0000,
+                size: 0x10000,
+                timestamp: 0,
+                name: "/usr/lib/swift/libswift_Concurrency.dylib",
+                cv_record: Vec::new(),
+            }],
+            exception: None,
+        };
+        Minidump::read(ByteView::from_vec(write_minidump(&contents, 0))).unwrap()
+    }
+
+    /// Returns the resume addresses of the async frames of the thread of the minidump.
+    fn async_frames(minidump: &Minidump) -> Vec<u64> {
+        let modules = minidump.get_stream::<MinidumpModuleList>().unwrap();
+        let unwinder = SwiftAsyncUnwinder::new(minidump, &modules).unwrap();
+
+        let system_info = minidump.get_stream::<MinidumpSystemInfo>().unwrap();
+        let threads = minidump.get_stream::<MinidumpThreadList>().unwrap();
+        let context = threads.threads[0].context(&system_info, None).unwrap();
+        let frame = StackFrame::from_context(context.into_owned(), FrameTrust::Context);
+
+        let frames = unwinder.async_frames(&[frame]);
+        assert!(frames
+            .iter()
+            .all(|frame| frame.adjust_instruction_addr == Some(false)));
+        frames
+            .iter()
+            .map(|frame| frame.instruction_addr.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_async_frames() {
+        let first = MEMORY_START + 0x200;
+        let second = MEMORY_START + 0x300;
+        let minidump = write_thread(&[
+            (FRAME_POINTER, (MEMORY_START + 0x180) | ASYNC_FRAME_FLAG),
+            (FRAME_POINTER - 8, first),
+            // The parent of the first context, and its signed resume function.
+            (first, second),
+            (first + 8, 0x3b0e_0001_8000_2000),
+            (second, 0),
+            (second + 8, 0x1_8000_3000),
+        ]);
+
+        assert_eq!(async_frames(&minidump), [0x1_8000_2000, 0x1_8000_3000]);
+    }
+
+    #[test]
+    fn test_async_frame_flag() {
+        let context = MEMORY_START + 0x200;
+        let minidump = write_thread(&[
+            (FRAME_POINTER, MEMORY_START + 0x180),
+            (FRAME_POINTER - 8, context),
+            (context + 8, 0x1_8000_2000),
+        ]);
+
+        assert_eq!(async_frames(&minidump), [0; 0]);
+    }
+
+    #[test]
+    fn test_max_async_frames() {
+        // A context which is its own parent.
+        let context = MEMORY_START + 0x200;
+        let minidump = write_thread(&[
+            (FRAME_POINTER, (MEMORY_START + 0x180) | ASYNC_FRAME_FLAG),
+            (FRAME_POINTER - 8, context),
+            (context, context),
+            (context + 8, 0x1_8000_2000),
+        ]);
+
+        assert_eq!(async_frames(&minidump), [0x1_8000_2000; MAX_ASYNC_FRAMES]);
+    }
+
+    #[test]
+    fn test_unmapped_context() {
+        // The chain ends once it leaves the memory of the minidump.
+        let context = MEMORY_START + 0x200;
+        let unmapped = MEMORY_START + MEMORY_SIZE as u64;
+        let minidump = write_thread(&[
+            (FRAME_POINTER, (MEMORY_START + 0x180) | ASYNC_FRAME_FLAG),
+            (FRAME_POINTER - 8, context),
+            (context, unmapped),
+            (context + 8, 0x1_8000_2000),
+        ]);
+        assert_eq!(async_frames(&minidump), [0x1_8000_2000]);
+
+        let minidump = write_thread(&[
+            (FRAME_POINTER, (MEMORY_START + 0x180) | ASYNC_FRAME_FLAG),
+            (FRAME_POINTER - 8, unmapped),
+        ]);
+        assert_eq!(async_frames(&minidump), [0; 0]);
+    }
+
+    #[test]
+    fn test_without_concurrency_runtime() {
+        let minidump = write_thread(&[]);
+        assert!(SwiftAsyncUnwinder::new(&minidump, &MinidumpModuleList::new()).is_none());
+    }
+
+    #[test]
+    fn test_strip_address() {
+        assert_eq!(strip_address(0x1000_7ff8_1234_5670), 0x7ff8_1234_5670);
+        assert_eq!(strip_address(0x3b0e_0001_0000_1000), 0x1_0000_1000);
+        assert_eq!(strip_address(0x1_0000_1000), 0x1_0000_1000);
+    }
+}
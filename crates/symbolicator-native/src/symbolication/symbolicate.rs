@@ -4,10 +4,13 @@ use symbolic::common::Name;
 use symbolic::demangle::Demangle;
 use symbolicator_service::caches::SourceFilesCache;
 use symbolicator_service::caching::{Cache, CacheError};
+use symbolicator_service::config::{PointerAuthConfig, SourceLinkConfig, UnwindingConfig};
+use symbolicator_service::download::DownloadService;
 use symbolicator_service::objects::ObjectsActor;
 use symbolicator_service::services::SharedServices;
 
 use crate::caches::bitcode::BitcodeService;
+use crate::caches::breakpad::BreakpadSymbolsActor;
 use crate::caches::cficaches::CfiCacheActor;
 use crate::caches::il2cpp::Il2cppService;
 use crate::caches::ppdb_caches::PortablePdbCacheActor;
@@ -23,6 +26,7 @@ use super::demangle::{DemangleCache, DEMANGLE_OPTIONS};
 use super::dotnet::symbolicate_dotnet_frame;
 use super::module_lookup::{CacheFileEntry, ModuleLookup};
 use super::native::{get_relative_caller_addr, symbolicate_native_frame};
+use super::pointer_auth::strip_address_tags;
 
 // we should really rename this here to the `SymbolicatorService`, as it does a lot more
 // than just symbolication ;-)
@@ -30,11 +34,17 @@ use super::native::{get_relative_caller_addr, symbolicate_native_frame};
 pub struct SymbolicationActor {
     demangle_cache: DemangleCache,
     pub(crate) objects: ObjectsActor,
-    symcaches: SymCacheActor,
+    pub(crate) symcaches: SymCacheActor,
     pub(crate) cficaches: CfiCacheActor,
-    ppdb_caches: PortablePdbCacheActor,
+    pub(crate) ppdb_caches: PortablePdbCacheActor,
+    pub(crate) breakpad: BreakpadSymbolsActor,
     pub(crate) diagnostics_cache: Cache,
+    pub(crate) download_svc: Arc<DownloadService>,
     pub(crate) sourcefiles_cache: Arc<SourceFilesCache>,
+    pub(crate) source_links: SourceLinkConfig,
+    pub(crate) unwinding: UnwindingConfig,
+    pub(crate) pointer_auth: PointerAuthConfig,
+    pub(crate) max_frame_variables: usize,
 }
 
 impl SymbolicationActor {
@@ -51,7 +61,11 @@ impl SymbolicationActor {
             download_svc.clone(),
         );
 
-        let il2cpp = Il2cppService::new(caches.il2cpp.clone(), shared_cache.clone(), download_svc);
+        let il2cpp = Il2cppService::new(
+            caches.il2cpp.clone(),
+            shared_cache.clone(),
+            download_svc.clone(),
+        );
 
         let symcaches = SymCacheActor::new(
             caches.symcaches.clone(),
@@ -59,6 +73,7 @@ impl SymbolicationActor {
             objects.clone(),
             bitcode,
             il2cpp,
+            services.config.symcache_sharding.clone(),
         );
 
         let cficaches = CfiCacheActor::new(
@@ -67,8 +82,14 @@ impl SymbolicationActor {
             objects.clone(),
         );
 
-        let ppdb_caches =
-            PortablePdbCacheActor::new(caches.ppdb_caches.clone(), shared_cache, objects.clone());
+        let ppdb_caches = PortablePdbCacheActor::new(
+            caches.ppdb_caches.clone(),
+            shared_cache.clone(),
+            objects.clone(),
+        );
+
+        let breakpad =
+            BreakpadSymbolsActor::new(caches.breakpad.clone(), shared_cache, objects.clone());
 
         let demangle_cache = DemangleCache::builder()
             .max_capacity(10 * 1024 * 1024) // 10 MiB, considering key and value:
@@ -81,8 +102,14 @@ impl SymbolicationActor {
             symcaches,
             cficaches,
             ppdb_caches,
+            breakpad,
             diagnostics_cache: caches.diagnostics.clone(),
+            download_svc,
             sourcefiles_cache,
+            source_links: services.config.source_links.clone(),
+            unwinding: services.config.unwinding.clone(),
+            pointer_auth: services.config.pointer_auth.clone(),
+            max_frame_variables: services.config.max_frame_variables,
         }
     }
 
@@ -93,7 +120,7 @@ impl SymbolicationActor {
     ) -> anyhow::Result<CompletedSymbolicationResponse> {
         let SymbolicateStacktraces {
             platform,
-            stacktraces,
+            mut stacktraces,
             sources,
             scope,
             signal,
@@ -104,6 +131,8 @@ impl SymbolicationActor {
             ..
         } = request;
 
+        strip_address_tags(&modules, &mut stacktraces);
+
         let mut module_lookup = ModuleLookup::new(scope.clone(), sources, modules);
         module_lookup
             .fetch_caches(
@@ -243,6 +272,7 @@ fn symbolicate_stacktrace(
                 symbolicated_frames.push(SymbolicatedFrame {
                     status,
                     original_index: Some(index),
+                    inexact: false,
                     raw: frame,
                 });
             }
@@ -268,12 +298,40 @@ fn symbolicate_stacktrace(
         metrics.bad_traces += 1;
     }
 
+    let async_frames = thread
+        .async_frames
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, mut frame)| {
+            let adjustment = AdjustInstructionAddr::for_frame(&frame, default_adjustment);
+            match symbolicate_frame(
+                demangle_cache,
+                caches,
+                &thread.registers,
+                signal,
+                &mut frame,
+                index,
+                adjustment,
+            ) {
+                Ok(frames) => frames,
+                Err(status) => vec![SymbolicatedFrame {
+                    status,
+                    original_index: Some(index),
+                    inexact: false,
+                    raw: frame,
+                }],
+            }
+        })
+        .collect();
+
     CompleteStacktrace {
         thread_id: thread.thread_id,
         thread_name: thread.thread_name,
         is_requesting: thread.is_requesting,
+        thread_state: thread.thread_state,
         registers: thread.registers,
         frames: symbolicated_frames,
+        async_frames,
     }
 }
 
@@ -314,6 +372,33 @@ fn symbolicate_frame(
                 index,
             )
         }
+        Ok(entry @ CacheFileEntry::SymCacheShards(_)) => {
+            // Caller addresses may be in a different shard than the instruction address, so they
+            // are looked up in the shard covering them.
+            let symcache = lookup_result
+                .relative_addr
+                .and_then(|addr| entry.symcache_for(addr))
+                .ok_or(FrameStatus::Missing)?;
+            let relative_addr = get_relative_caller_addr(
+                symcache.get(),
+                &lookup_result,
+                registers,
+                signal,
+                index,
+                adjustment,
+            )?;
+            let symcache = entry
+                .symcache_for(relative_addr)
+                .ok_or(FrameStatus::MissingSymbol)?;
+            symbolicate_native_frame(
+                demangle_cache,
+                symcache.get(),
+                lookup_result,
+                relative_addr,
+                frame,
+                index,
+            )
+        }
         Ok(CacheFileEntry::PortablePdbCache(ppdb_cache)) => {
             symbolicate_dotnet_frame(ppdb_cache.get(), frame, index)
         }
@@ -0,0 +1,274 @@
+//! Recovery of the arguments and local variables of the top frames of crashing threads.
+//!
+//! The variables are found in the DWARF debug information of the modules of the frames, and their
+//! locations are evaluated against the registers recovered during stackwalking and the memory
+//! captured in the minidump.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use minidump::UnifiedMemoryList;
+use symbolic::common::{Arch, CpuFamily};
+use symbolicator_service::objects::{
+    recover_variables, FindObject, ObjectHandle, ObjectPurpose, RecoveredVariable, VariableContext,
+};
+use symbolicator_service::types::Scope;
+use symbolicator_service::utils::hex::HexValue;
+use symbolicator_sources::{FileType, ObjectType, SourceConfig};
+
+use crate::interface::{CompleteObjectInfo, FrameVariable, RawStacktrace, Registers, VariableKind};
+
+use super::module_lookup::object_id_from_object_info;
+use super::process_minidump::Minidump;
+use super::symbolicate::SymbolicationActor;
+
+impl SymbolicationActor {
+    /// Recovers the variables of the top `frame_count` frames of the requesting thread.
+    ///
+    /// `contexts` contains the registers of the top frames of that thread. The registers of the
+    /// caller of a frame give its canonical frame address.
+    ///
+    /// Evaluating the debug information is CPU bound, so it runs on a blocking thread once the
+    /// debug files have been fetched.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn recover_frame_variables(
+        &self,
+        minidump: Arc<Minidump>,
+        arch: Arch,
+        scope: &Scope,
+        sources: &Arc<[SourceConfig]>,
+        modules: &[CompleteObjectInfo],
+        stacktraces: &mut [RawStacktrace],
+        contexts: Vec<Registers>,
+        frame_count: usize,
+    ) {
+        let Some(register_names) = dwarf_register_names(arch.cpu_family()) else {
+            return;
+        };
+        let Some(thread) = stacktraces
+            .iter_mut()
+            .find(|thread| thread.is_requesting == Some(true))
+        else {
+            return;
+        };
+
+        let frame_count = frame_count.min(contexts.len()).min(thread.frames.len());
+        let mut objects: HashMap<usize, Option<Arc<ObjectHandle>>> = HashMap::new();
+        for frame in &thread.frames[..frame_count] {
+            let Some(index) = module_index(modules, frame.instruction_addr.0) else {
+                continue;
+            };
+            if objects.contains_key(&index) {
+                continue;
+            }
+            let object = self
+                .fetch_debug_object(&modules[index], scope, sources)
+                .await;
+            objects.insert(index, object);
+        }
+
+        // The frames with a debug file, along with the address in it.
+        let mut lookups = Vec::new();
+        for (index, frame) in thread.frames[..frame_count].iter().enumerate() {
+            let addr = frame.instruction_addr.0;
+            let Some(module_index) = module_index(modules, addr) else {
+                continue;
+            };
+            let Some(Some(object)) = objects.get(&module_index) else {
+                continue;
+            };
+
+            // Return addresses point after the call, which might be in the next function already.
+            let offset = addr - modules[module_index].raw.image_addr.0;
+            let offset = if index == 0 {
+                offset
+            } else {
+                offset.saturating_sub(1)
+            };
+            let object_addr = object.object().load_address().wrapping_add(offset);
+            lookups.push((index, object.clone(), object_addr));
+        }
+
+        let recovered = tokio::task::spawn_blocking(move || {
+            let memory = minidump.get_memory();
+            let mut recovered = Vec::with_capacity(lookups.len());
+            for (index, object, object_addr) in lookups {
+                let context = FrameContext {
+                    registers: &contexts[index],
+                    caller_registers: contexts.get(index + 1),
+                    register_names,
+                    memory: memory.as_ref(),
+                };
+                match recover_variables(object.object(), object_addr, &context) {
+                    Ok(variables) => recovered.push((index, variables)),
+                    Err(error) => tracing::debug!(%error, "Failed to recover frame variables"),
+                }
+            }
+            recovered
+        })
+        .await;
+
+        match recovered {
+            Ok(recovered) => {
+                for (index, variables) in recovered {
+                    thread.frames[index].variables =
+                        variables.into_iter().map(frame_variable).collect();
+                }
+            }
+            Err(error) => tracing::error!(%error, "Failed to recover frame variables"),
+        }
+    }
+
+    /// Fetches the debug file of a module, returning `None` if it has no DWARF.
+    async fn fetch_debug_object(
+        &self,
+        module: &CompleteObjectInfo,
+        scope: &Scope,
+        sources: &Arc<[SourceConfig]>,
+    ) -> Option<Arc<ObjectHandle>> {
+        let ty = module.raw.ty;
+        if !matches!(ty, ObjectType::Elf | ObjectType::Macho) {
+            return None;
+        }
+
+        let result = self
+            .objects
+            .find(FindObject {
+                filetypes: FileType::from_object_type(ty),
+                purpose: ObjectPurpose::Debug,
+                identifier: object_id_from_object_info(&module.raw),
+                sources: sources.clone(),
+                scope: scope.clone(),
+            })
+            .await;
+        let handle = result.meta?.handle.ok()?;
+        self.objects.fetch(handle).await.ok()
+    }
+}
+
+/// The registers and memory of a frame in a minidump.
+struct FrameContext<'a> {
+    registers: &'a Registers,
+    caller_registers: Option<&'a Registers>,
+    register_names: &'static [&'static str],
+    memory: Option<&'a UnifiedMemoryList<'a>>,
+}
+
+impl FrameContext<'_> {
+    fn stack_pointer(&self) -> Option<&'static str> {
+        self.register_names
+            .iter()
+            .copied()
+            .find(|name| matches!(*name, "rsp" | "esp" | "sp"))
+    }
+}
+
+impl VariableContext for FrameContext<'_> {
+    fn register(&self, register: u16) -> Option<u64> {
+        let name = self.register_names.get(usize::from(register))?;
+        self.registers.get(*name).map(|value| value.0)
+    }
+
+    fn cfa(&self) -> Option<u64> {
+        // The stack pointer of the caller is the one before the call.
+        let name = self.stack_pointer()?;
+        self.caller_registers?.get(name).map(|value| value.0)
+    }
+
+    fn read_memory(&self, address: u64, size: u8) -> Option<u64> {
+        let memory = self.memory?.memory_at_address(address)?;
+        match size {
+            1 => memory.get_memory_at_address::<u8>(address).map(u64::from),
+            2 => memory.get_memory_at_address::<u16>(address).map(u64::from),
+            4 => memory.get_memory_at_address::<u32>(address).map(u64::from),
+            8 => memory.get_memory_at_address::<u64>(address),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the names of registers in minidumps, indexed by their DWARF register number.
+fn dwarf_register_names(cpu_family: CpuFamily) -> Option<&'static [&'static str]> {
+    let names: &'static [&'static str] = match cpu_family {
+        CpuFamily::Amd64 => &[
+            "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11",
+            "r12", "r13", "r14", "r15", "rip",
+        ],
+        CpuFamily::Intel32 => &[
+            "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "eip",
+        ],
+        CpuFamily::Arm64 => &[
+            "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+            "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25",
+            "x26", "x27", "x28", "fp", "lr", "sp",
+        ],
+        CpuFamily::Arm32 => &[
+            "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "fp", "ip", "sp",
+            "lr", "pc",
+        ],
+        _ => return None,
+    };
+    Some(names)
+}
+
+/// Returns the index of the module which contains the address.
+fn module_index(modules: &[CompleteObjectInfo], addr: u64) -> Option<usize> {
+    modules.iter().position(|module| {
+        let raw = &module.raw;
+        raw.image_size
+            .is_some_and(|size| addr >= raw.image_addr.0 && addr - raw.image_addr.0 < size)
+    })
+}
+
+fn frame_variable(variable: RecoveredVariable) -> FrameVariable {
+    FrameVariable {
+        name: variable.name,
+        kind: if variable.is_argument {
+            VariableKind::Argument
+        } else {
+            VariableKind::Local
+        },
+        type_name: variable.type_name,
+        value: variable.value.map(HexValue),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers(values: &[(&str, u64)]) -> Registers {
+        values
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), HexValue(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_context_registers() {
+        let frame_registers = registers(&[("rdi", 0x45), ("rsp", 0x7ff0)]);
+        let caller_registers = registers(&[("rsp", 0x8000)]);
+        let context = FrameContext {
+            registers: &frame_registers,
+            caller_registers: Some(&caller_registers),
+            register_names: dwarf_register_names(CpuFamily::Amd64).unwrap(),
+            memory: None,
+        };
+
+        assert_eq!(context.register(5), Some(0x45));
+        assert_eq!(context.register(7), Some(0x7ff0));
+        assert_eq!(context.register(0), None);
+        assert_eq!(context.register(100), None);
+        assert_eq!(context.cfa(), Some(0x8000));
+    }
+
+    #[test]
+    fn test_dwarf_register_names() {
+        let arm64 = dwarf_register_names(CpuFamily::Arm64).unwrap();
+        assert_eq!(arm64[29], "fp");
+        assert_eq!(arm64[31], "sp");
+        let x86 = dwarf_register_names(CpuFamily::Intel32).unwrap();
+        assert_eq!(x86[4], "esp");
+        assert_eq!(dwarf_register_names(CpuFamily::Ppc64), None);
+    }
+}
@@ -0,0 +1,183 @@
+//! Processing of Windows Error Reporting archives.
+//!
+//! WER collects crashes into CAB or zip archives, which contain a minidump next to a
+//! `Report.wer` file with metadata about the crash and the crashed application.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use symbolicator_service::download::{extract_archive_member, ArchiveMember};
+use symbolicator_service::metric;
+use symbolicator_service::types::{Platform, Scope, ScrapingConfig};
+use symbolicator_sources::SourceConfig;
+use tempfile::TempPath;
+
+use crate::interface::{CompletedSymbolicationResponse, StackwalkOptions};
+
+use super::symbolicate::SymbolicationActor;
+
+/// The name of the file holding the metadata of a report.
+const REPORT_FILE_NAME: &str = "Report.wer";
+
+impl SymbolicationActor {
+    /// Extracts the minidump out of a WER archive and processes it.
+    ///
+    /// The metadata of the `Report.wer` file, if the archive contains one, is added to the
+    /// response as `wer_metadata`.
+    pub async fn process_wer_archive(
+        &self,
+        platform: Option<Platform>,
+        scope: Scope,
+        archive_file: TempPath,
+        sources: Arc<[SourceConfig]>,
+        scraping: ScrapingConfig,
+        options: StackwalkOptions,
+    ) -> Result<CompletedSymbolicationResponse> {
+        let len = archive_file.metadata()?.len();
+        tracing::debug!("Processing WER archive ({} bytes)", len);
+        metric!(time_raw("wer.upload.size") = len);
+
+        let mut magic = [0; 4];
+        let is_archive = File::open(&archive_file)?.read_exact(&mut magic).is_ok()
+            && matches!(&magic, b"MSCF" | b"PK\x03\x04");
+        if !is_archive {
+            bail!("not a CAB or zip archive");
+        }
+
+        let minidump_file = extract_archive_member(
+            self.download_svc.clone(),
+            &archive_file,
+            ArchiveMember::Minidump,
+        )
+        .await
+        .context("failed to extract minidump")?;
+
+        let report = REPORT_FILE_NAME.to_owned();
+        let metadata = match extract_archive_member(
+            self.download_svc.clone(),
+            &archive_file,
+            ArchiveMember::Named(report),
+        )
+        .await
+        {
+            Ok(report_file) => Some(parse_wer_report(&std::fs::read(report_file.path())?)),
+            Err(err) => {
+                tracing::debug!(error = %err, "WER archive does not contain a report");
+                None
+            }
+        };
+        drop(archive_file);
+
+        let mut response = self
+            .process_minidump(
+                platform,
+                scope,
+                minidump_file.into_temp_path(),
+                sources,
+                scraping,
+                options,
+            )
+            .await?;
+        response.wer_metadata = metadata;
+
+        Ok(response)
+    }
+}
+
+/// Parses the `Key=Value` lines of a `Report.wer` file.
+///
+/// The event signature is listed as pairs of `Sig[n].Name` and `Sig[n].Value` entries, which
+/// are added by their name. Other indexed entries, such as the list of loaded modules, are
+/// skipped.
+fn parse_wer_report(data: &[u8]) -> BTreeMap<String, String> {
+    let text = decode_text(data);
+    let entries: Vec<_> = text
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let values: HashMap<_, _> = entries.iter().copied().collect();
+
+    let mut metadata = BTreeMap::new();
+    for &(key, value) in &entries {
+        match key.split_once('[') {
+            None => {
+                metadata.insert(key.to_owned(), value.to_owned());
+            }
+            Some((prefix @ ("Sig" | "DynamicSig"), index)) => {
+                let Some(index) = index.strip_suffix("].Name") else {
+                    continue;
+                };
+                if let Some(sig_value) = values.get(format!("{prefix}[{index}].Value").as_str()) {
+                    metadata.insert(value.to_owned(), (*sig_value).to_owned());
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    metadata
+}
+
+/// Decodes a text file, which WER writes as UTF-16 with a byte order mark.
+fn decode_text(data: &[u8]) -> String {
+    match data {
+        [0xff, 0xfe, rest @ ..] => {
+            let units: Vec<_> = rest
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(data)
+            .trim_start_matches('\u{feff}')
+            .to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wer_report() {
+        let report = "\u{feff}Version=1\r\n\
+            EventType=APPCRASH\r\n\
+            ReportIdentifier=6c4bca8a-371e-4bd7-9cd4-3f5317fc0be4\r\n\
+            Sig[0].Name=Application Name\r\n\
+            Sig[0].Value=crash.exe\r\n\
+            Sig[6].Name=Exception Code\r\n\
+            Sig[6].Value=c0000005\r\n\
+            DynamicSig[1].Name=OS Version\r\n\
+            DynamicSig[1].Value=10.0.19045.2.0.0.256.48\r\n\
+            LoadedModule[0]=C:\\crash.exe\r\n\
+            AppPath=C:\\crash.exe\r\n";
+        let mut data = vec![0xff, 0xfe];
+        for unit in report.trim_start_matches('\u{feff}').encode_utf16() {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+
+        let metadata = parse_wer_report(&data);
+        let metadata: Vec<_> = metadata
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            metadata,
+            [
+                ("AppPath", "C:\\crash.exe"),
+                ("Application Name", "crash.exe"),
+                ("EventType", "APPCRASH"),
+                ("Exception Code", "c0000005"),
+                ("OS Version", "10.0.19045.2.0.0.256.48"),
+                ("ReportIdentifier", "6c4bca8a-371e-4bd7-9cd4-3f5317fc0be4"),
+                ("Version", "1"),
+            ]
+        );
+
+        // The same report without UTF-16 encoding.
+        assert_eq!(parse_wer_report(report.as_bytes()).len(), metadata.len());
+    }
+}
@@ -204,6 +204,12 @@ async fn test_reserved_ip_addresses() {
         id: SourceId::new("getsentry"),
         url: url.clone(),
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files: files.clone(),
         accept_invalid_certs: false,
     })));
@@ -213,6 +219,12 @@ async fn test_reserved_ip_addresses() {
         id: SourceId::new("ip"),
         url: url.clone(),
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files: files.clone(),
         accept_invalid_certs: false,
     })));
@@ -222,6 +234,12 @@ async fn test_reserved_ip_addresses() {
         id: SourceId::new("localhost"),
         url,
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files,
         accept_invalid_certs: false,
     })));
@@ -294,6 +312,72 @@ async fn test_reserved_ip_addresses() {
     assert_eq!(candidates[5].download, error);
 }
 
+/// Tests that the `blocked_destinations` are never connected to, by IP address or host name.
+#[tokio::test]
+async fn test_blocked_destinations() {
+    let (symbolication, _cache_dir) = setup_service(|cfg| {
+        cfg.blocked_destinations = vec!["127.0.0.0/8".into(), "localhost".into()];
+    });
+    let hitcounter = Server::new();
+
+    let files = source_config(DirectoryLayoutType::Native, vec![FileType::MachCode]);
+
+    let mut url = hitcounter.url("not-found/");
+    url.set_host(Some("127.0.0.1")).unwrap();
+    let ip = SourceConfig::Http(Arc::new(HttpSourceConfig {
+        id: SourceId::new("ip"),
+        url: url.clone(),
+        headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
+        files: files.clone(),
+        accept_invalid_certs: false,
+    }));
+
+    url.set_host(Some("localhost")).unwrap();
+    let localhost = SourceConfig::Http(Arc::new(HttpSourceConfig {
+        id: SourceId::new("localhost"),
+        url,
+        headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
+        files,
+        accept_invalid_certs: false,
+    }));
+
+    let request = example_request(vec![ip, localhost]);
+    let mut response = symbolication.symbolicate(request).await.unwrap();
+    let candidates = response.modules.pop().unwrap().candidates.into_inner();
+
+    assert_eq!(hitcounter.accesses(), 0);
+
+    // NOTE: every second candidate is a "No object files listed on this source" one for the
+    // source bundle lookup
+    assert_eq!(candidates[1].source, SourceId::new("ip"));
+    assert_eq!(
+        candidates[1].download,
+        ObjectDownloadInfo::Blocked {
+            details: "connections to `127.0.0.1` are not allowed".into()
+        }
+    );
+
+    assert_eq!(candidates[3].source, SourceId::new("localhost"));
+    assert_eq!(
+        candidates[3].download,
+        ObjectDownloadInfo::Blocked {
+            details: "connections to `localhost` are not allowed".into()
+        }
+    );
+}
+
 /// Tests that symbolicator correctly follows redirects
 #[tokio::test]
 async fn test_redirects() {
@@ -340,6 +424,7 @@ async fn test_unreachable_bucket() {
                     id: SourceId::new(format!("broken-{ty}-{code}")),
                     url: hitcounter.url(&format!("respond_statuscode/{code}")),
                     token: "123abc".into(),
+                    organizations: Vec::new(),
                 }))
             };
 
@@ -3,6 +3,7 @@ use std::sync::Arc;
 
 use tempfile::NamedTempFile;
 
+use symbolicator_native::interface::StackwalkOptions;
 use symbolicator_service::types::Scope;
 
 use crate::{assert_snapshot, read_fixture, setup_service, symbol_server};
@@ -23,6 +24,7 @@ macro_rules! stackwalk_minidump {
                     minidump_file.into_temp_path(),
                     Arc::new([source]),
                     Default::default(),
+                    Default::default(),
                 )
                 .await;
 
@@ -45,3 +47,39 @@ async fn test_minidump_macos() {
 async fn test_minidump_linux() {
     stackwalk_minidump!("linux.dmp").await
 }
+
+/// The fixture is `windows.dmp` with its thread list moved to the end of the file, which is then
+/// cut off in the middle of the third thread.
+#[tokio::test]
+async fn test_minidump_truncated() {
+    let (symbolication, _cache_dir) = setup_service(|_| ());
+    let (_symsrv, source) = symbol_server();
+
+    let minidump = read_fixture("windows-truncated.dmp");
+    let mut minidump_file = NamedTempFile::new().unwrap();
+    minidump_file.write_all(&minidump).unwrap();
+    let options = StackwalkOptions {
+        best_effort: true,
+        ..Default::default()
+    };
+    let response = symbolication
+        .process_minidump(
+            None,
+            Scope::Global,
+            minidump_file.into_temp_path(),
+            Arc::new([source]),
+            Default::default(),
+            options,
+        )
+        .await
+        .unwrap();
+
+    let thread_ids: Vec<_> = response
+        .stacktraces
+        .iter()
+        .map(|thread| thread.thread_id)
+        .collect();
+    assert_eq!(thread_ids, [Some(1636), Some(3580)]);
+
+    assert_snapshot!(response);
+}
@@ -22,6 +22,12 @@ async fn test_nuget_source() {
             .parse()
             .unwrap(),
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files: source_config(DirectoryLayoutType::Symstore, vec![FileType::PortablePdb]),
         accept_invalid_certs: false,
     }));
@@ -57,6 +63,12 @@ async fn test_ubuntu_source() {
         id: SourceId::new("ubuntu"),
         url: "https://debuginfod.ubuntu.com/buildid/".parse().unwrap(),
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files: source_config(
             DirectoryLayoutType::Debuginfod,
             vec![FileType::ElfCode, FileType::ElfDebug],
@@ -124,7 +124,13 @@ async fn test_deny_list() {
     let (symbolication, _cache_dir) = setup_service(|config| {
         config.cache_dir = None;
         config.caches.downloaded.retry_misses_after = Some(Duration::ZERO);
+        config.caches.downloaded.retry_download_errors_after = Some(Duration::ZERO);
+        config.caches.downloaded.retry_timeouts_after = Some(Duration::ZERO);
+        config.caches.downloaded.retry_permission_denied_after = Some(Duration::ZERO);
         config.caches.derived.retry_misses_after = Some(Duration::ZERO);
+        config.caches.derived.retry_download_errors_after = Some(Duration::ZERO);
+        config.caches.derived.retry_timeouts_after = Some(Duration::ZERO);
+        config.caches.derived.retry_permission_denied_after = Some(Duration::ZERO);
         // FIXME: `object_meta` caches treat download errors as `malformed`
         config.caches.derived.retry_malformed_after = Some(Duration::ZERO);
         config.max_download_timeout = Duration::from_millis(200);
@@ -1,10 +1,16 @@
+use std::fs;
 use std::sync::Arc;
 
+use symbolicator_native::interface::FrameStatus;
+use symbolicator_service::objects::ObjectDownloadInfo;
 use symbolicator_service::types::Scope;
+use symbolicator_sources::{
+    CommonSourceConfig, DirectoryLayoutType, FilesystemSourceConfig, SourceConfig, SourceId,
+};
 
 use crate::{
     assert_snapshot, example_request, fixture, make_symbolication_request, setup_service,
-    symbol_server,
+    symbol_server, tempdir,
 };
 
 #[tokio::test]
@@ -273,3 +279,129 @@ async fn test_dotnet_only_source_links() {
 
     assert_snapshot!(response.unwrap());
 }
+
+#[tokio::test]
+async fn test_dotnet_source_links_not_allowed() {
+    let (symbolication, _cache_dir) = setup_service(|config| {
+        config.source_links.allowed_origins = vec!["dev.azure.com".into()];
+    });
+    let (_srv, source) = symbol_server();
+
+    let request = make_symbolication_request(
+        vec![source],
+        r#"[{
+          "type":"pe_dotnet",
+          "debug_file":"source-links.pdb",
+          "debug_id":"0c380a12-8221-4069-8565-bee6b3ac196e-a596286e"
+        }]"#,
+        r#"[{
+          "frames":[{
+            "instruction_addr": "0x2f",
+            "function_id": "0x5",
+            "addr_mode":"rel:0"
+          }]
+        }]"#,
+    );
+    let response = symbolication.symbolicate(request).await.unwrap();
+
+    // The source link is still reported, but the file is not fetched from GitHub.
+    let frame = &response.stacktraces[0].frames[0];
+    assert!(frame.raw.source_link.is_some());
+    assert_eq!(frame.raw.context_line, None);
+
+    let candidates = response.modules[0].candidates.clone().into_inner();
+    let candidate = candidates
+        .iter()
+        .find(|candidate| candidate.source.as_str() == "sourcelink")
+        .unwrap();
+    assert!(matches!(
+        candidate.download,
+        ObjectDownloadInfo::Blocked { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_breakpad_symbol_store() {
+    let (symbolication, _cache_dir) = setup_service(|_| ());
+    let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+        id: SourceId::new("breakpad"),
+        path: fixture("breakpad"),
+        files: CommonSourceConfig::with_layout(DirectoryLayoutType::Breakpad),
+    }));
+
+    let request = make_symbolication_request(
+        vec![source],
+        r#"[{
+          "type":"pe",
+          "code_id":"5AB380779000",
+          "code_file":"C:\\projects\\breakpad-tools\\windows\\Release\\crash.exe",
+          "debug_id":"3249d99d-0c40-4931-8610-f4e4fb0b6936-1",
+          "debug_file":"C:\\projects\\breakpad-tools\\windows\\Release\\crash.pdb",
+          "image_addr":"0x2a0000",
+          "image_size":36864
+        }]"#,
+        r#"[{
+          "frames":[{
+            "instruction_addr":"0x2a1012"
+          }]
+        }]"#,
+    );
+    let response = symbolication.symbolicate(request).await.unwrap();
+
+    let module = &response.modules[0];
+    assert!(module.features.has_debug_info);
+    assert!(module.features.has_unwind_info);
+    assert!(!module.symbols_only);
+
+    let frame = &response.stacktraces[0].frames[0];
+    assert_eq!(frame.status, FrameStatus::Symbolicated);
+    assert_eq!(frame.raw.function.as_deref(), Some("main"));
+    assert_eq!(frame.raw.lineno, Some(36));
+}
+
+#[tokio::test]
+async fn test_symbols_only() {
+    let (symbolication, _cache_dir) = setup_service(|_| ());
+
+    // The stripped object only has symbols in its MiniDebugInfo, and no debug file.
+    let symbol_dir = tempdir();
+    let object_dir = symbol_dir.path().join("7d");
+    fs::create_dir_all(&object_dir).unwrap();
+    fs::copy(
+        fixture("symbols/mini-debuginfo/mini"),
+        object_dir.join("cf0ee9592703014dee5153531d90b7b2cf605a"),
+    )
+    .unwrap();
+
+    let source = SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+        id: SourceId::new("local"),
+        path: symbol_dir.path().to_owned(),
+        files: CommonSourceConfig::with_layout(DirectoryLayoutType::Native),
+    }));
+
+    let request = make_symbolication_request(
+        vec![source],
+        r#"[{
+          "type":"elf",
+          "code_id":"7dcf0ee9592703014dee5153531d90b7b2cf605a",
+          "image_addr":"0x10000",
+          "image_size":16384
+        }]"#,
+        r#"[{
+          "frames":[{
+            "instruction_addr":"0x1112b"
+          }]
+        }]"#,
+    );
+    let response = symbolication.symbolicate(request).await.unwrap();
+
+    let module = &response.modules[0];
+    assert!(!module.features.has_debug_info);
+    assert!(module.features.has_symbols);
+    assert!(module.symbols_only);
+
+    let frame = &response.stacktraces[0].frames[0];
+    assert_eq!(frame.status, FrameStatus::Symbolicated);
+    assert_eq!(frame.raw.function.as_deref(), Some("add"));
+    assert_eq!(frame.raw.lineno, Some(0));
+}
@@ -8,7 +8,9 @@ use symbolicator_service::types::RawObjectInfo;
 use symbolicator_sources::SourceConfig;
 use symbolicator_test as test;
 
-pub use test::{assert_snapshot, fixture, read_fixture, source_config, symbol_server, Server};
+pub use test::{
+    assert_snapshot, fixture, read_fixture, source_config, symbol_server, tempdir, Server,
+};
 
 /// Setup tests and create a test service.
 ///
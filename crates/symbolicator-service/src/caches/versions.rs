@@ -10,11 +10,15 @@
 //! * prepend the `current` version to the `fallbacks`.
 //! * it is also possible to skip a version, in case a broken deploy needed to
 //!   be reverted which left behind broken cache files.
+//! * if existing files can be upgraded to the new format, add a
+//!   [`CacheMigration`](crate::caching::CacheMigration) from the previous version to the
+//!   `migrations`. Files of the previous version are then upgraded when they are read, or offline
+//!   with `symbolicator cache migrate`, instead of being recomputed.
 //!
 //! Some of the versioned caches are also tied to format versions defined in [`symbolic`].
 //! For those cases, there are static assertions that are a reminder to also bump the cache version.
 
-use crate::caching::CacheVersions;
+use crate::caching::{CacheName, CacheVersions};
 
 /// CFI cache, with the following versions:
 ///
@@ -31,11 +35,19 @@ use crate::caching::CacheVersions;
 pub const CFICACHE_VERSIONS: CacheVersions = CacheVersions {
     current: 4,
     fallbacks: &[],
+    migrations: &[],
 };
 static_assert!(symbolic::cfi::CFICACHE_LATEST_VERSION == 2);
 
 /// SymCache, with the following versions:
 ///
+/// - `10`: Includes the functions and lines of the Go runtime line table (`.gopclntab`) in
+///   objects without DWARF.
+///
+/// - `9`: Includes the symbols of MiniDebugInfo (`.gnu_debugdata`) in stripped ELF objects.
+///
+/// - `8`: Files may be compressed with zstd, which older versions can not read.
+///
 /// - `7`: Fixes inlinee lookup. (<https://github.com/getsentry/symbolic/pull/883>)
 ///
 /// - `6`: Recomputation to use new `CacheKey` format.
@@ -60,29 +72,44 @@ static_assert!(symbolic::cfi::CFICACHE_LATEST_VERSION == 2);
 ///
 /// - `0`: Initial version.
 pub const SYMCACHE_VERSIONS: CacheVersions = CacheVersions {
-    current: 7,
-    fallbacks: &[6],
+    current: 10,
+    fallbacks: &[9, 8, 7, 6],
+    migrations: &[],
 };
 static_assert!(symbolic::symcache::SYMCACHE_VERSION == 8);
 
 /// Data / Objects cache, with the following versions:
 ///
+/// - `2`: Files may be compressed with zstd, which older versions can not read.
+///
 /// - `1`: Recomputation to use new `CacheKey` format.
 ///
 /// - `0`: Initial version.
 pub const OBJECTS_CACHE_VERSIONS: CacheVersions = CacheVersions {
-    current: 1,
-    fallbacks: &[],
+    current: 2,
+    fallbacks: &[1],
+    migrations: &[],
 };
 
 /// Objects Meta cache, with the following versions:
 ///
+/// - `6`: Records the debug ids of objects.
+///
+/// - `5`: Detects the line tables of the Go runtime as debug info.
+///
+/// - `4`: Records the debug links of objects without debug info.
+///
+/// - `3`: Detects MiniDebugInfo as symbols.
+///
+/// - `2`: Detects skeleton units of split DWARF, and DWARF packages as debug info.
+///
 /// - `1`: Recomputation to use new `CacheKey` format.
 ///
 /// - `0`: Initial version.
 pub const META_CACHE_VERSIONS: CacheVersions = CacheVersions {
-    current: 1,
-    fallbacks: &[],
+    current: 6,
+    fallbacks: &[5, 4, 3, 2, 1],
+    migrations: &[],
 };
 
 /// Portable PDB cache, with the following versions:
@@ -95,6 +122,16 @@ pub const META_CACHE_VERSIONS: CacheVersions = CacheVersions {
 pub const PPDB_CACHE_VERSIONS: CacheVersions = CacheVersions {
     current: 3,
     fallbacks: &[2],
+    migrations: &[],
+};
+
+/// Breakpad symbol files, with the following versions:
+///
+/// - `1`: Initial version.
+pub const BREAKPAD_CACHE_VERSIONS: CacheVersions = CacheVersions {
+    current: 1,
+    fallbacks: &[],
+    migrations: &[],
 };
 
 /// SourceMapCache, with the following versions:
@@ -103,26 +140,33 @@ pub const PPDB_CACHE_VERSIONS: CacheVersions = CacheVersions {
 pub const SOURCEMAP_CACHE_VERSIONS: CacheVersions = CacheVersions {
     current: 1,
     fallbacks: &[],
+    migrations: &[],
 };
 
 /// Il2cpp cache, with the following versions:
 ///
+/// - `2`: Converts the per-method line mappings of newer Unity versions.
+///
 /// - `1`: Recomputation to use new `CacheKey` format.
 ///
 /// - `0`: Initial version.
 pub const IL2CPP_CACHE_VERSIONS: CacheVersions = CacheVersions {
-    current: 1,
+    current: 2,
     fallbacks: &[],
+    migrations: &[],
 };
 
 /// Bitcode / Auxdif (plist / bcsymbolmap) cache, with the following versions:
 ///
+/// - `2`: Reads the member named after the UUID out of zip archives.
+///
 /// - `1`: Recomputation to use new `CacheKey` format.
 ///
 /// - `0`: Initial version.
 pub const BITCODE_CACHE_VERSIONS: CacheVersions = CacheVersions {
-    current: 1,
-    fallbacks: &[],
+    current: 2,
+    fallbacks: &[1],
+    migrations: &[],
 };
 
 /// Source Files Cache, with the following versions:
@@ -131,6 +175,7 @@ pub const BITCODE_CACHE_VERSIONS: CacheVersions = CacheVersions {
 pub const SOURCEFILES_CACHE_VERSIONS: CacheVersions = CacheVersions {
     current: 1,
     fallbacks: &[],
+    migrations: &[],
 };
 
 /// Bundle Index Cache, with the following versions:
@@ -139,6 +184,7 @@ pub const SOURCEFILES_CACHE_VERSIONS: CacheVersions = CacheVersions {
 pub const BUNDLE_INDEX_CACHE_VERSIONS: CacheVersions = CacheVersions {
     current: 1,
     fallbacks: &[],
+    migrations: &[],
 };
 
 /// Proguard Cache, with the following versions:
@@ -148,4 +194,24 @@ pub const BUNDLE_INDEX_CACHE_VERSIONS: CacheVersions = CacheVersions {
 pub const PROGUARD_CACHE_VERSIONS: CacheVersions = CacheVersions {
     current: 2,
     fallbacks: &[],
+    migrations: &[],
 };
+
+/// Returns the versions of the cache with the given name, or `None` for unversioned caches.
+pub fn cache_versions(cache: CacheName) -> Option<CacheVersions> {
+    let versions = match cache {
+        CacheName::Objects => OBJECTS_CACHE_VERSIONS,
+        CacheName::ObjectMeta => META_CACHE_VERSIONS,
+        CacheName::Auxdifs => BITCODE_CACHE_VERSIONS,
+        CacheName::Il2cpp => IL2CPP_CACHE_VERSIONS,
+        CacheName::Symcaches => SYMCACHE_VERSIONS,
+        CacheName::Cficaches => CFICACHE_VERSIONS,
+        CacheName::PpdbCaches => PPDB_CACHE_VERSIONS,
+        CacheName::Breakpad => BREAKPAD_CACHE_VERSIONS,
+        CacheName::SourceMapCaches => SOURCEMAP_CACHE_VERSIONS,
+        CacheName::SourceFiles => SOURCEFILES_CACHE_VERSIONS,
+        CacheName::Proguard => PROGUARD_CACHE_VERSIONS,
+        CacheName::Diagnostics => return None,
+    };
+    Some(versions)
+}
@@ -0,0 +1,223 @@
+//! Portable bundles of cache files.
+//!
+//! Bundles pre-seed the caches of symbolicators which can not populate them on their own, like
+//! air-gapped deployments. A bundle is a tar archive starting with a `manifest.json`, followed by
+//! the cache files under their path within the cache directory, like `symcaches/v8/aa/bbccdd/...`.
+//!
+//! Only good entries are exported, as errors depend on the environment that produced them. Files
+//! are imported only if they belong to a cache version the importing symbolicator can read.
+
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use symbolic::common::{ByteView, DebugId};
+
+use crate::caches::versions::cache_versions;
+
+use super::fs::catch_not_found;
+use super::inspect::decode_header;
+use super::memory::persist_tempfile;
+use super::{Cache, Caches};
+
+/// The name of the manifest, which is the first entry of every bundle.
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// The version of the bundle format.
+const BUNDLE_FORMAT: u32 = 1;
+
+/// The manifest of a bundle.
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    /// The version of the bundle format.
+    format: u32,
+    /// The version of the symbolicator which exported the bundle.
+    symbolicator_version: String,
+}
+
+/// Selects the files which are exported into a bundle.
+#[derive(Debug, Clone, Default)]
+pub struct BundleFilter {
+    /// Only export files with one of these debug ids.
+    ///
+    /// Debug ids are read from the headers of the files, so this only selects objects and
+    /// symcaches.
+    pub debug_ids: Option<HashSet<DebugId>>,
+    /// Only export files which have been used within this duration.
+    pub max_age: Option<Duration>,
+}
+
+/// The outcome of [`import_bundle`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BundleImport {
+    /// The number of files written into the caches.
+    pub imported: usize,
+    /// The number of files which already existed in the caches.
+    pub existing: usize,
+    /// The number of files skipped for unknown caches or cache versions.
+    pub unsupported: usize,
+}
+
+/// Writes the files of the given caches which match `filter` into a bundle.
+///
+/// Returns the number of exported files.
+pub fn export_bundle<W: Write>(
+    caches: &[&Cache],
+    filter: &BundleFilter,
+    writer: W,
+) -> Result<usize> {
+    let mut builder = tar::Builder::new(writer);
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        symbolicator_version: env!("CARGO_PKG_VERSION").to_owned(),
+    };
+    append(
+        &mut builder,
+        Path::new(MANIFEST_NAME),
+        &serde_json::to_vec(&manifest)?,
+    )?;
+
+    let mut exported = 0;
+    for cache in caches {
+        let Some(versions) = cache_versions(cache.name()) else {
+            continue;
+        };
+        let cache_dir = cache.cache_dir_or_err()?;
+
+        for file in cache.list_files()? {
+            let is_known_version =
+                file.version == versions.current || versions.fallbacks.contains(&file.version);
+            if file.error.is_some() || !is_known_version {
+                continue;
+            }
+            if filter.max_age.is_some_and(|max_age| file.age > max_age) {
+                continue;
+            }
+
+            // Files may be removed by a cleanup at any time.
+            let path = cache_dir.join(&file.path);
+            let open = || ByteView::open(&path).and_then(|data| cache.resolve_content(data));
+            let Some(data) = catch_not_found(open)? else {
+                continue;
+            };
+            if let Some(debug_ids) = &filter.debug_ids {
+                if !has_debug_id(cache, &data, debug_ids)? {
+                    continue;
+                }
+            }
+
+            let path = Path::new(cache.name().as_ref()).join(&file.path);
+            append(&mut builder, &path, &data)?;
+            exported += 1;
+        }
+    }
+
+    builder.into_inner()?.flush()?;
+    Ok(exported)
+}
+
+/// Writes the files of a bundle into the caches.
+///
+/// Existing files are kept, and files of unknown caches or cache versions are skipped.
+pub fn import_bundle<R: Read>(caches: &Caches, reader: R) -> Result<BundleImport> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = archive.entries()?;
+
+    let mut manifest = entries.next().context("the bundle is empty")??;
+    if manifest.path()? != Path::new(MANIFEST_NAME) {
+        bail!("the bundle does not start with a manifest");
+    }
+    let manifest: BundleManifest =
+        serde_json::from_reader(&mut manifest).context("failed to parse bundle manifest")?;
+    if manifest.format != BUNDLE_FORMAT {
+        bail!("unsupported bundle format {}", manifest.format);
+    }
+
+    let mut import = BundleImport::default();
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            bail!("invalid path `{}` in bundle", path.display());
+        }
+
+        let mut components = path.components();
+        let cache = components
+            .next()
+            .and_then(|name| caches.get(name.as_os_str().to_str()?));
+        // Paths within the cache directory start with the version.
+        let relative = components.as_path();
+        let version = components
+            .next()
+            .and_then(|dir| dir.as_os_str().to_str()?.strip_prefix('v')?.parse().ok());
+        let (Some(cache), Some(version)) = (cache, version) else {
+            import.unsupported += 1;
+            continue;
+        };
+        let is_supported = cache_versions(cache.name()).is_some_and(|versions| {
+            versions.current == version || versions.fallbacks.contains(&version)
+        });
+        if !is_supported {
+            import.unsupported += 1;
+            continue;
+        }
+
+        let target = cache.cache_dir_or_err()?.join(relative);
+        if target.exists() {
+            import.existing += 1;
+            continue;
+        }
+
+        let mut temp_file = cache.tempfile()?;
+        let size = io::copy(&mut entry, &mut temp_file)?;
+        persist_tempfile(temp_file, &target)
+            .with_context(|| format!("failed to write `{}`", target.display()))?;
+        cache.record_write(&target, size);
+        import.imported += 1;
+    }
+
+    tracing::info!(
+        "Imported {} files from a bundle of symbolicator {}",
+        import.imported,
+        manifest.symbolicator_version
+    );
+    Ok(import)
+}
+
+/// Whether the header of a cache file has one of the given debug ids.
+fn has_debug_id(
+    cache: &Cache,
+    data: &ByteView<'static>,
+    debug_ids: &HashSet<DebugId>,
+) -> Result<bool> {
+    let data = cache.decompress(data.clone())?;
+    // Files which can not be decoded are never selected.
+    let Ok(header) = decode_header(cache.name(), &data) else {
+        return Ok(false);
+    };
+    let debug_id = header
+        .iter()
+        .find(|(field, _)| *field == "debug_id")
+        .and_then(|(_, value)| DebugId::from_str(value).ok());
+    Ok(debug_id.is_some_and(|debug_id| debug_ids.contains(&debug_id)))
+}
+
+/// Appends a file with the given contents to the bundle.
+fn append<W: Write>(builder: &mut tar::Builder<W>, path: &Path, data: &[u8]) -> io::Result<()> {
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    builder.append_data(&mut header, path, data)
+}
@@ -11,7 +11,7 @@ use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 /// An error that happens when fetching an object from a remote location.
 ///
 /// This error enum is intended for persisting in caches, except for the
-/// [`InternalError`](Self::InternalError) variant.
+/// [`Unavailable`](Self::Unavailable) and [`InternalError`](Self::InternalError) variants.
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum CacheError {
     /// The object was not found at the remote source.
@@ -23,6 +23,12 @@ pub enum CacheError {
     /// The attached string contains the remote source's response.
     #[error("permission denied: {0}")]
     PermissionDenied(String),
+    /// The object was not fetched because the remote source is one of the
+    /// `blocked_destinations`.
+    ///
+    /// The attached string contains the blocked host.
+    #[error("blocked: {0}")]
+    Blocked(String),
     /// The object could not be fetched from the remote source due to a timeout.
     #[error("download timed out after {0:?}")]
     Timeout(Duration),
@@ -32,6 +38,13 @@ pub enum CacheError {
     /// The attached string contains the remote source's response.
     #[error("download failed: {0}")]
     DownloadError(String),
+    /// The object was not fetched because the remote source is temporarily unavailable, like
+    /// when its circuit breaker is open.
+    ///
+    /// This variant is not persisted to caches, as the source may be available again any
+    /// moment. The attached string says why the source is unavailable.
+    #[error("source unavailable: {0}")]
+    Unavailable(String),
     /// The object was fetched successfully, but is invalid in some way.
     ///
     /// For example, this could result from an unsupported object file or an error
@@ -46,6 +59,30 @@ pub enum CacheError {
     /// [here](https://github.com/getsentry/symbolic/issues/871).
     #[error("unsupported: {0}")]
     Unsupported(String),
+    /// The object was fetched successfully, but decompressing it exceeded the configured
+    /// maximum size.
+    ///
+    /// The attached number is the size limit in bytes.
+    #[error("decompressed size exceeds limit of {0} bytes")]
+    SizeLimitExceeded(u64),
+    /// The object was fetched successfully, but it is an archive which does not contain a
+    /// file matching the requested identifiers.
+    ///
+    /// The attached string describes the files that were found in the archive.
+    #[error("no matching file in archive: {0}")]
+    NoMatchingArchiveMember(String),
+    /// The object was fetched successfully, but it is a multi-architecture file which does
+    /// not contain an object matching the requested identifiers.
+    ///
+    /// The attached string lists the architectures of the objects in the file.
+    #[error("no object for the requested architecture, found: {0}")]
+    NoMatchingArch(String),
+    /// The object was downloaded, but its checksum does not match the checksum announced
+    /// by the remote source.
+    ///
+    /// The attached string contains the expected and the actual checksum.
+    #[error("checksum mismatch: {0}")]
+    ChecksumMismatch(String),
     /// An unexpected error in symbolicator itself.
     ///
     /// This variant is not intended to be persisted to or read from caches.
@@ -70,20 +107,30 @@ impl From<serde_json::Error> for CacheError {
 impl CacheError {
     pub(super) const MALFORMED_MARKER: &'static [u8] = b"malformed";
     pub(super) const PERMISSION_DENIED_MARKER: &'static [u8] = b"permissiondenied";
+    pub(super) const BLOCKED_MARKER: &'static [u8] = b"blocked";
     pub(super) const TIMEOUT_MARKER: &'static [u8] = b"timeout";
     pub(super) const DOWNLOAD_ERROR_MARKER: &'static [u8] = b"downloaderror";
     pub(super) const UNSUPPORTED_MARKER: &'static [u8] = b"unsupported";
+    pub(super) const SIZE_LIMIT_EXCEEDED_MARKER: &'static [u8] = b"sizelimitexceeded";
+    pub(super) const NO_MATCHING_ARCHIVE_MEMBER_MARKER: &'static [u8] = b"nomatchingarchivemember";
+    pub(super) const CHECKSUM_MISMATCH_MARKER: &'static [u8] = b"checksummismatch";
+    pub(super) const NO_MATCHING_ARCH_MARKER: &'static [u8] = b"nomatchingarch";
 
     /// Writes error markers and details to a file.
     ///
-    /// * If `self` is [`InternalError`](Self::InternalError), it does nothing.
+    /// * If `self` is [`Unavailable`](Self::Unavailable) or
+    ///   [`InternalError`](Self::InternalError), it does nothing.
     /// * If `self` is [`NotFound`](Self::NotFound), it empties the file.
     /// * In all other cases, it writes the corresponding marker, followed by the error
     ///   details, and truncates the file.
     pub async fn write(&self, file: &mut File) -> Result<(), io::Error> {
-        if let Self::InternalError = self {
-            tracing::error!("A `CacheError::InternalError` should never be written out");
-            return Ok(());
+        match self {
+            Self::Unavailable(_) => return Ok(()),
+            Self::InternalError => {
+                tracing::error!("A `CacheError::InternalError` should never be written out");
+                return Ok(());
+            }
+            _ => {}
         }
         file.rewind().await?;
 
@@ -99,6 +146,10 @@ impl CacheError {
                 file.write_all(Self::PERMISSION_DENIED_MARKER).await?;
                 file.write_all(details.as_bytes()).await?;
             }
+            CacheError::Blocked(details) => {
+                file.write_all(Self::BLOCKED_MARKER).await?;
+                file.write_all(details.as_bytes()).await?;
+            }
             CacheError::Timeout(duration) => {
                 file.write_all(Self::TIMEOUT_MARKER).await?;
                 file.write_all(format_duration(*duration).to_string().as_bytes())
@@ -112,7 +163,24 @@ impl CacheError {
                 file.write_all(Self::UNSUPPORTED_MARKER).await?;
                 file.write_all(details.as_bytes()).await?;
             }
-            CacheError::InternalError => {
+            CacheError::SizeLimitExceeded(limit) => {
+                file.write_all(Self::SIZE_LIMIT_EXCEEDED_MARKER).await?;
+                file.write_all(limit.to_string().as_bytes()).await?;
+            }
+            CacheError::NoMatchingArchiveMember(details) => {
+                file.write_all(Self::NO_MATCHING_ARCHIVE_MEMBER_MARKER)
+                    .await?;
+                file.write_all(details.as_bytes()).await?;
+            }
+            CacheError::ChecksumMismatch(details) => {
+                file.write_all(Self::CHECKSUM_MISMATCH_MARKER).await?;
+                file.write_all(details.as_bytes()).await?;
+            }
+            CacheError::NoMatchingArch(details) => {
+                file.write_all(Self::NO_MATCHING_ARCH_MARKER).await?;
+                file.write_all(details.as_bytes()).await?;
+            }
+            CacheError::Unavailable(_) | CacheError::InternalError => {
                 unreachable!("this was already handled above");
             }
         }
@@ -132,6 +200,9 @@ impl CacheError {
         if let Some(raw_message) = bytes.strip_prefix(Self::PERMISSION_DENIED_MARKER) {
             let err_msg = utf8_message(raw_message);
             Some(Self::PermissionDenied(err_msg.into_owned()))
+        } else if let Some(raw_message) = bytes.strip_prefix(Self::BLOCKED_MARKER) {
+            let err_msg = utf8_message(raw_message);
+            Some(Self::Blocked(err_msg.into_owned()))
         } else if let Some(raw_duration) = bytes.strip_prefix(Self::TIMEOUT_MARKER) {
             let raw_duration = utf8_message(raw_duration);
             match parse_duration(&raw_duration) {
@@ -150,6 +221,27 @@ impl CacheError {
         } else if let Some(raw_message) = bytes.strip_prefix(Self::UNSUPPORTED_MARKER) {
             let err_msg = utf8_message(raw_message);
             Some(Self::Unsupported(err_msg.into_owned()))
+        } else if let Some(raw_limit) = bytes.strip_prefix(Self::SIZE_LIMIT_EXCEEDED_MARKER) {
+            let raw_limit = utf8_message(raw_limit);
+            match raw_limit.parse() {
+                Ok(limit) => Some(Self::SizeLimitExceeded(limit)),
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to read size limit");
+                    Some(Self::InternalError)
+                }
+            }
+        } else if let Some(raw_message) =
+            bytes.strip_prefix(Self::NO_MATCHING_ARCHIVE_MEMBER_MARKER)
+        {
+            let err_msg = utf8_message(raw_message);
+            Some(Self::NoMatchingArchiveMember(err_msg.into_owned()))
+        } else if let Some(raw_message) = bytes.strip_prefix(Self::CHECKSUM_MISMATCH_MARKER) {
+            let err_msg = utf8_message(raw_message);
+            Some(Self::ChecksumMismatch(err_msg.into_owned()))
+        } else if let Some(raw_message) = bytes.strip_prefix(Self::NO_MATCHING_ARCH_MARKER) {
+            // This marker is a prefix of `NO_MATCHING_ARCHIVE_MEMBER_MARKER`, which is checked first.
+            let err_msg = utf8_message(raw_message);
+            Some(Self::NoMatchingArch(err_msg.into_owned()))
         } else if bytes.is_empty() {
             Some(Self::NotFound)
         } else {
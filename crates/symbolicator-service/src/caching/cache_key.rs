@@ -1,4 +1,5 @@
 use std::fmt::{self, Write};
+use std::path::{Component, Path};
 use std::sync::Arc;
 
 use sha2::{Digest, Sha256};
@@ -6,10 +7,21 @@ use symbolicator_sources::RemoteFile;
 
 use crate::types::Scope;
 
+/// The partition of the global scope in partitioned caches.
+pub(super) const GLOBAL_PARTITION: &str = "global";
+
+/// The directory of the partitions of all other scopes in partitioned caches.
+const SCOPES_DIR: &str = "scopes";
+
 #[derive(Debug, Clone, Eq)]
 pub struct CacheKey {
     metadata: Arc<str>,
     hash: [u8; 32],
+    /// The metric key of the source of the first file contributing to this key, used to tag
+    /// metrics, see [`RemoteFile::source_metric_key`].
+    source: Option<Arc<str>>,
+    /// The partition of the scope of this key, like `global` or `scopes/123`.
+    partition: Arc<str>,
 }
 
 impl fmt::Display for CacheKey {
@@ -43,12 +55,33 @@ impl CacheKey {
         &self.metadata
     }
 
+    /// Returns the metric key of the source this key traces back to, or `"unknown"`.
+    ///
+    /// This is the source of the first file written into the key. Like
+    /// [`RemoteFile::source_metric_key`], custom sources are only named by their type.
+    pub fn source_metric_key(&self) -> &str {
+        self.source.as_deref().unwrap_or("unknown")
+    }
+
     /// Returns the relative path for this cache key.
     ///
     /// The relative path is a sha-256 hash hex-formatted like so:
     /// `v$version/aa/bbccdd/eeff...`
     pub fn cache_path(&self, version: u32) -> String {
-        let mut path = format!("v{version}/{:02x}/", self.hash[0]);
+        self.hashed_path(format!("v{version}/"))
+    }
+
+    /// Returns the relative path for this cache key in a cache partitioned by scope.
+    ///
+    /// The scope is part of the path like so: `v$version/global/aa/bbccdd/eeff...`, or
+    /// `v$version/scopes/$scope/aa/bbccdd/eeff...`.
+    pub fn partitioned_cache_path(&self, version: u32) -> String {
+        self.hashed_path(format!("v{version}/{}/", self.partition))
+    }
+
+    fn hashed_path(&self, mut path: String) -> String {
+        path.write_fmt(format_args!("{:02x}/", self.hash[0]))
+            .unwrap();
         for b in &self.hash[1..4] {
             path.write_fmt(format_args!("{b:02x}")).unwrap();
         }
@@ -63,14 +96,27 @@ impl CacheKey {
     /// contributing sources.
     pub fn scoped_builder(scope: &Scope) -> CacheKeyBuilder {
         let metadata = format!("scope: {scope}\n\n");
-        CacheKeyBuilder { metadata }
+        let partition = match scope {
+            Scope::Global => GLOBAL_PARTITION.into(),
+            Scope::Scoped(scope) => format!("{SCOPES_DIR}/{}", sanitize_scope(scope)).into(),
+        };
+        CacheKeyBuilder {
+            metadata,
+            source: None,
+            partition,
+        }
     }
 
     #[cfg(test)]
     pub fn for_testing(key: impl Into<String>) -> Self {
         let metadata = key.into();
 
-        CacheKeyBuilder { metadata }.build()
+        CacheKeyBuilder {
+            metadata,
+            source: None,
+            partition: GLOBAL_PARTITION.into(),
+        }
+        .build()
     }
 }
 
@@ -82,11 +128,16 @@ impl CacheKey {
 /// the cache files to help debugging.
 pub struct CacheKeyBuilder {
     metadata: String,
+    source: Option<Arc<str>>,
+    partition: Arc<str>,
 }
 
 impl CacheKeyBuilder {
     /// Writes metadata about the [`RemoteFile`] into the [`CacheKey`].
     pub fn write_file_meta(&mut self, file: &RemoteFile) -> Result<(), fmt::Error> {
+        if self.source.is_none() {
+            self.source = Some(file.source_metric_key().into());
+        }
         self.metadata.write_fmt(format_args!(
             "source: {}\nlocation: {}\n",
             file.source_id(),
@@ -101,10 +152,47 @@ impl CacheKeyBuilder {
         CacheKey {
             metadata: self.metadata.into(),
             hash: hash.into(),
+            source: self.source,
+            partition: self.partition,
         }
     }
 }
 
+/// Turns a scope into a name which is safe to use as a directory, like `123`.
+///
+/// Characters other than ASCII alphanumerics, `-` and `_` are replaced, which may map different
+/// scopes to the same partition. As the scope is part of the hashed metadata, their files are
+/// still kept apart.
+pub(super) fn sanitize_scope(scope: &str) -> String {
+    let scope: String = scope
+        .chars()
+        .take(64)
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    if scope.is_empty() {
+        "_".into()
+    } else {
+        scope
+    }
+}
+
+/// Returns the scope of a file in a partitioned cache, given its path relative to the cache
+/// directory, like `global` or `123`.
+pub(super) fn partition_of(path: &Path) -> Option<&str> {
+    let mut components = path.components().skip(1).map(|component| match component {
+        Component::Normal(name) => name.to_str(),
+        _ => None,
+    });
+    match components.next()?? {
+        GLOBAL_PARTITION => Some(GLOBAL_PARTITION),
+        SCOPES_DIR => components.next()?,
+        _ => None,
+    }
+}
+
 impl fmt::Write for CacheKeyBuilder {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         self.metadata.write_str(s)
@@ -142,6 +230,11 @@ mod tests {
             key.metadata(),
             "scope: global\n\nsource: foo\nlocation: file://bar.baz\n"
         );
+        assert_eq!(key.source_metric_key(), "filesystem");
+        assert_eq!(
+            &key.partitioned_cache_path(0),
+            "v0/global/f5/e08b92/a55c1357413b5e36547a8b534a014c3a00299e7622e4c4b022a96541"
+        );
 
         let built_key = CacheKey::from_scoped_file(&scope, &file);
 
@@ -165,4 +258,19 @@ mod tests {
             "scope: global\n\nsource: foo\nlocation: file://bar.baz\n\nsecond_source:\nsource: foo\nlocation: file://bar.quux\n"
         );
     }
+
+    #[test]
+    fn test_partitions() {
+        let scope = Scope::Scoped("org/123".into());
+        let key = CacheKey::scoped_builder(&scope).build();
+        let path = key.partitioned_cache_path(2);
+        assert!(path.starts_with("v2/scopes/org_123/"));
+        assert_eq!(partition_of(Path::new(&path)), Some("org_123"));
+
+        let key = CacheKey::scoped_builder(&Scope::Global).build();
+        let path = key.partitioned_cache_path(2);
+        assert_eq!(partition_of(Path::new(&path)), Some("global"));
+
+        assert_eq!(partition_of(Path::new(&key.cache_path(2))), None);
+    }
 }
@@ -1,15 +1,25 @@
-use std::fs::{read_dir, remove_dir, remove_file};
-use std::io;
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{
+    read, read_dir, read_to_string, remove_dir, remove_file, File, Metadata, OpenOptions,
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+use symbolic::common::ByteView;
 
 use crate::config::Config;
-use crate::metric;
 
-use super::fs::catch_not_found;
+use super::cache_error::cache_entry_from_bytes;
+use super::content::{
+    hash_of_content_path, parse_pointer, CONTENT_DIR, CONTENT_GRACE_PERIOD, POINTER_LEN,
+};
+use super::fs::{catch_not_found, expiration_strategy, ExpirationStrategy};
 use super::{Cache, Caches};
 
 /// Entry function for the cleanup command.
@@ -49,6 +59,7 @@ impl Caches {
             symcaches,
             cficaches,
             ppdb_caches,
+            breakpad,
             sourcemap_caches,
             sourcefiles,
             diagnostics,
@@ -70,6 +81,7 @@ impl Caches {
             symcaches,
             cficaches,
             ppdb_caches,
+            breakpad,
             sourcemap_caches,
             sourcefiles,
             diagnostics,
@@ -99,6 +111,9 @@ impl Caches {
     }
 }
 
+/// The suffix of the progress marker of a cache, which is stored next to the cache directory.
+const PROGRESS_SUFFIX: &str = "cleanup";
+
 #[derive(Default)]
 struct CleanupStats {
     removed_dirs: usize,
@@ -108,11 +123,125 @@ struct CleanupStats {
     retained_dirs: usize,
     retained_files: usize,
     retained_bytes: u64,
+
+    /// The removed bytes by the kind of file, like `negative`.
+    reclaimed: BTreeMap<&'static str, u64>,
+}
+
+impl CleanupStats {
+    fn record_removal(&mut self, kind: &'static str, size: u64) {
+        self.removed_files += 1;
+        self.removed_bytes += size;
+        *self.reclaimed.entry(kind).or_default() += size;
+    }
+
+    fn merge(&mut self, other: CleanupStats) {
+        self.removed_dirs += other.removed_dirs;
+        self.removed_files += other.removed_files;
+        self.removed_bytes += other.removed_bytes;
+        self.retained_dirs += other.retained_dirs;
+        self.retained_files += other.retained_files;
+        self.retained_bytes += other.retained_bytes;
+        for (kind, bytes) in other.reclaimed {
+            *self.reclaimed.entry(kind).or_default() += bytes;
+        }
+    }
+}
+
+/// Limits the rate at which files are checked, shared by all threads cleaning up a cache.
+struct Throttle {
+    interval: Option<Duration>,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(max_per_second: Option<u32>) -> Self {
+        Self {
+            interval: max_per_second
+                .filter(|max| *max > 0)
+                .map(|max| Duration::from_secs(1) / max),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Blocks until the next file may be checked.
+    fn wait(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let due = {
+            let mut next = self.next.lock().unwrap();
+            let due = (*next).max(Instant::now());
+            *next = due + interval;
+            due
+        };
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// The shards of a cache which an interrupted cleanup has already finished.
+///
+/// Finished shards are appended to a marker file, so that the next cleanup skips them. The
+/// marker is removed once a cleanup finishes all shards.
+struct CleanupProgress {
+    path: PathBuf,
+    finished: HashSet<PathBuf>,
+    file: Mutex<Option<File>>,
+}
+
+impl CleanupProgress {
+    /// Reads the progress marker at `path`, and opens it to record finished shards.
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let finished = match catch_not_found(|| read_to_string(&path))? {
+            Some(contents) => contents.lines().map(PathBuf::from).collect(),
+            None => HashSet::new(),
+        };
+        if !finished.is_empty() {
+            tracing::info!(
+                "Resuming cleanup, skipping {} finished directories",
+                finished.len()
+            );
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            finished,
+            file: Mutex::new(Some(file)),
+        })
+    }
+
+    fn is_finished(&self, shard: &Path) -> bool {
+        self.finished.contains(shard)
+    }
+
+    fn finish(&self, shard: &Path) {
+        let mut file = self.file.lock().unwrap();
+        let Some(marker) = file.as_mut() else {
+            return;
+        };
+        if let Err(err) = writeln!(marker, "{}", shard.display()) {
+            let stderr: &dyn std::error::Error = &err;
+            tracing::error!(stderr, "Failed to record cleanup progress");
+            // The next cleanup repeats the shards which are not recorded.
+            *file = None;
+        }
+    }
+
+    /// Removes the marker after all shards have been finished.
+    fn complete(self) -> io::Result<()> {
+        drop(self.file);
+        catch_not_found(|| remove_file(&self.path))?;
+        Ok(())
+    }
 }
 
 impl Cache {
     /// Cleans up this cache based on configured cache retention.
     ///
+    /// The directories within the top-level directories of the cache, like `v8/aa`, are cleaned
+    /// up in parallel shards. A marker next to the cache directory records finished shards, so
+    /// that an interrupted cleanup resumes where it stopped.
+    ///
     /// If `dry_run` is `true`, no files will actually be deleted.
     pub fn cleanup(&self, dry_run: bool) -> Result<()> {
         tracing::info!("Cleaning up `{}` cache", self.name);
@@ -120,8 +249,80 @@ impl Cache {
             anyhow!("no caching configured! Did you provide a path to your config file?")
         })?;
 
+        let throttle = Throttle::new(self.cleanup.max_files_per_second);
         let mut stats = CleanupStats::default();
-        self.cleanup_directory_recursive(cache_dir, &mut stats, dry_run)?;
+
+        // Files at the top of the cache are cleaned up right away, and directories within the
+        // top-level directories are the shards.
+        let mut parents = Vec::new();
+        let mut shards = Vec::new();
+        for path in list_dir(cache_dir)? {
+            if self.is_content_dir(&path) {
+                continue;
+            } else if path.is_dir() {
+                for child in list_dir(&path)? {
+                    if child.is_dir() {
+                        shards.push(child);
+                    } else {
+                        self.cleanup_file(&child, &mut stats, dry_run, &throttle);
+                    }
+                }
+                parents.push(path);
+            } else {
+                self.cleanup_file(&path, &mut stats, dry_run, &throttle);
+            }
+        }
+
+        // Dry runs neither resume nor record progress.
+        let progress = if dry_run || !cache_dir.is_dir() {
+            None
+        } else {
+            let path = cache_dir.with_extension(PROGRESS_SUFFIX);
+            Some(CleanupProgress::open(path)?)
+        };
+        let relative = |shard: &Path| shard.strip_prefix(cache_dir).unwrap_or(shard).to_owned();
+        if let Some(progress) = &progress {
+            shards.retain(|shard| !progress.is_finished(&relative(shard)));
+        }
+
+        let next_shard = AtomicUsize::new(0);
+        let threads = self.cleanup.threads.clamp(1, shards.len().max(1));
+        let (next_shard, shards, progress_ref, throttle_ref, relative_ref) =
+            (&next_shard, &shards, &progress, &throttle, &relative);
+        let results: Vec<Result<CleanupStats>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(move || {
+                        let mut stats = CleanupStats::default();
+                        loop {
+                            let index = next_shard.fetch_add(1, Ordering::Relaxed);
+                            let Some(shard) = shards.get(index) else {
+                                return Ok(stats);
+                            };
+                            self.cleanup_shard(shard, &mut stats, dry_run, throttle_ref)?;
+                            if let Some(progress) = progress_ref {
+                                progress.finish(&relative_ref(shard));
+                            }
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("cleanup thread panicked"))
+                .collect()
+        });
+        for result in results {
+            stats.merge(result?);
+        }
+
+        for parent in parents {
+            self.remove_dir_if_empty(&parent, &mut stats, dry_run);
+        }
+        self.cleanup_content(cache_dir, &mut stats, dry_run, &throttle)?;
+        if let Some(progress) = progress {
+            progress.complete()?;
+        }
 
         tracing::info!("Cleaning up `{}` complete", self.name);
         tracing::info!(
@@ -141,10 +342,63 @@ impl Cache {
         metric!(gauge("caches.size.bytes") = stats.retained_bytes, "cache" => self.name.as_ref());
         metric!(counter("caches.size.files_removed") += stats.removed_files as i64, "cache" => self.name.as_ref());
         metric!(counter("caches.size.bytes_removed") += stats.removed_bytes as i64, "cache" => self.name.as_ref());
+        for (kind, bytes) in stats.reclaimed {
+            tracing::info!("Reclaimed {bytes} bytes of {kind} files");
+            metric!(
+                counter("caches.size.bytes_reclaimed") += bytes as i64,
+                "cache" => self.name.as_ref(),
+                "kind" => kind,
+            );
+        }
 
         Ok(())
     }
 
+    /// Cleans up a shard of the cache, and removes its directory if it is left empty.
+    fn cleanup_shard(
+        &self,
+        shard: &Path,
+        stats: &mut CleanupStats,
+        dry_run: bool,
+        throttle: &Throttle,
+    ) -> Result<()> {
+        if self.cleanup_directory_recursive(shard, stats, dry_run, throttle)? {
+            self.remove_dir(shard, stats, dry_run);
+        } else {
+            stats.retained_dirs += 1;
+        }
+        Ok(())
+    }
+
+    /// Removes a top-level directory of the cache once all of its shards have been removed.
+    fn remove_dir_if_empty(&self, directory: &Path, stats: &mut CleanupStats, dry_run: bool) {
+        // In a dry run, nothing has been removed, so the directory is only kept if it is empty.
+        let is_empty =
+            !dry_run && read_dir(directory).is_ok_and(|mut entries| entries.next().is_none());
+        if is_empty {
+            self.remove_dir(directory, stats, dry_run);
+        } else {
+            stats.retained_dirs += 1;
+        }
+    }
+
+    /// Removes an empty directory, returning `true` if it was removed.
+    fn remove_dir(&self, directory: &Path, stats: &mut CleanupStats, dry_run: bool) -> bool {
+        tracing::debug!("Removing directory `{}`", directory.display());
+        if !dry_run {
+            if let Err(e) = remove_dir(directory) {
+                sentry::with_scope(
+                    |scope| scope.set_extra("path", directory.display().to_string().into()),
+                    || tracing::error!("Failed to clean cache directory: {:?}", e),
+                );
+                stats.retained_dirs += 1;
+                return false;
+            }
+        }
+        stats.removed_dirs += 1;
+        true
+    }
+
     /// Cleans up the directory recursively, returning `true` if the directory is left empty after cleanup.
     ///
     /// If `dry_run` is `true`, no files will actually be deleted.
@@ -153,6 +407,7 @@ impl Cache {
         directory: &Path,
         stats: &mut CleanupStats,
         dry_run: bool,
+        throttle: &Throttle,
     ) -> Result<bool> {
         let entries = match catch_not_found(|| read_dir(directory))? {
             Some(x) => x,
@@ -167,41 +422,98 @@ impl Cache {
         for entry in entries {
             let path = entry?.path();
             if path.is_dir() {
-                let mut dir_is_empty = self.cleanup_directory_recursive(&path, stats, dry_run)?;
+                let dir_is_empty =
+                    self.cleanup_directory_recursive(&path, stats, dry_run, throttle)?;
                 if dir_is_empty {
-                    tracing::debug!("Removing directory `{}`", directory.display());
-                    if !dry_run {
-                        if let Err(e) = remove_dir(&path) {
-                            sentry::with_scope(
-                                |scope| scope.set_extra("path", path.display().to_string().into()),
-                                || tracing::error!("Failed to clean cache directory: {:?}", e),
-                            );
-                            dir_is_empty = false;
-                        }
-                    }
-                }
-                if dir_is_empty {
-                    stats.removed_dirs += 1;
+                    is_empty &= self.remove_dir(&path, stats, dry_run);
                 } else {
                     stats.retained_dirs += 1;
+                    is_empty = false;
                 }
-                is_empty &= dir_is_empty;
             } else {
-                match self.try_cleanup_path(&path, stats, dry_run) {
-                    Err(e) => {
-                        sentry::with_scope(
-                            |scope| scope.set_extra("path", path.display().to_string().into()),
-                            || tracing::error!("Failed to clean cache file: {:?}", e),
-                        );
-                    }
-                    Ok(file_removed) => is_empty &= file_removed,
-                }
+                is_empty &= self.cleanup_file(&path, stats, dry_run, throttle);
             }
         }
 
         Ok(is_empty)
     }
 
+    /// Cleans up the file at `path`, returning `true` if it was removed.
+    fn cleanup_file(
+        &self,
+        path: &Path,
+        stats: &mut CleanupStats,
+        dry_run: bool,
+        throttle: &Throttle,
+    ) -> bool {
+        throttle.wait();
+        match self.try_cleanup_path(path, stats, dry_run) {
+            Err(e) => {
+                sentry::with_scope(
+                    |scope| scope.set_extra("path", path.display().to_string().into()),
+                    || tracing::error!("Failed to clean cache file: {:?}", e),
+                );
+                false
+            }
+            Ok(file_removed) => file_removed,
+        }
+    }
+
+    fn is_content_dir(&self, path: &Path) -> bool {
+        self.cache_dir
+            .as_deref()
+            .is_some_and(|cache_dir| path == cache_dir.join(CONTENT_DIR))
+    }
+
+    /// Removes the contents which are no longer pointed to by any cache file.
+    ///
+    /// This needs to run after the cache files have been cleaned up.
+    fn cleanup_content(
+        &self,
+        cache_dir: &Path,
+        stats: &mut CleanupStats,
+        dry_run: bool,
+        throttle: &Throttle,
+    ) -> Result<()> {
+        let content_dir = cache_dir.join(CONTENT_DIR);
+        if !content_dir.is_dir() {
+            return Ok(());
+        }
+
+        let mut referenced = HashSet::new();
+        walk_files(cache_dir, &mut |path, metadata| {
+            if metadata.len() == POINTER_LEN && !path.starts_with(&content_dir) {
+                if let Some(data) = catch_not_found(|| read(path))? {
+                    if let Some(hash) = parse_pointer(&data) {
+                        referenced.insert(hash.to_owned());
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        walk_files(&content_dir, &mut |path, metadata| {
+            throttle.wait();
+            let is_referenced = hash_of_content_path(&content_dir, path)
+                .is_none_or(|hash| referenced.contains(&hash));
+            let age = metadata.modified()?.elapsed().unwrap_or_default();
+            if is_referenced || age < CONTENT_GRACE_PERIOD {
+                stats.retained_bytes += metadata.len();
+                stats.retained_files += 1;
+                return Ok(());
+            }
+
+            tracing::debug!("Removing unreferenced content `{}`", path.display());
+            if !dry_run {
+                self.remove_cachefile(path)?;
+            }
+            stats.record_removal("content", metadata.len());
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
     /// Tries to clean up the file at `path`, returning `true` if it was removed.
     ///
     /// If `dry_run` is `true`, the file will not actually be deleted.
@@ -220,12 +532,12 @@ impl Cache {
 
         if catch_not_found(|| self.check_expiry(path))?.is_none() {
             tracing::debug!("Removing file `{}`", path.display());
+            let kind = file_kind(path);
             if !dry_run {
                 catch_not_found(|| remove_file(path))?;
             }
 
-            stats.removed_bytes += size;
-            stats.removed_files += 1;
+            stats.record_removal(kind, size);
 
             return Ok(true);
         }
@@ -235,3 +547,49 @@ impl Cache {
         Ok(false)
     }
 }
+
+/// Lists the entries of `directory`, which may not exist.
+fn list_dir(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let Some(entries) = catch_not_found(|| read_dir(directory))? else {
+        return Ok(Vec::new());
+    };
+    entries.map(|entry| Ok(entry?.path())).collect()
+}
+
+/// Classifies a cache file for the reclaimed bytes reported by a cleanup.
+fn file_kind(path: &Path) -> &'static str {
+    let Ok(data) = ByteView::open(path) else {
+        return "unknown";
+    };
+    match expiration_strategy(&cache_entry_from_bytes(data)) {
+        ExpirationStrategy::None => "positive",
+        ExpirationStrategy::Negative => "negative",
+        ExpirationStrategy::Malformed => "malformed",
+    }
+}
+
+/// Calls `f` with every file in `directory` and its subdirectories.
+fn walk_files(
+    directory: &Path,
+    f: &mut dyn FnMut(&Path, &Metadata) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut directories = vec![directory.to_owned()];
+    while let Some(directory) = directories.pop() {
+        let Some(entries) = catch_not_found(|| read_dir(&directory))? else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            // Files may be removed by another cleanup at any time.
+            let Some(metadata) = catch_not_found(|| path.symlink_metadata())? else {
+                continue;
+            };
+            if metadata.is_dir() {
+                directories.push(path);
+            } else if metadata.is_file() {
+                f(&path, &metadata)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Transparent compression of cache files.
+//!
+//! Caches listed in [`CacheConfigs::compressed`](crate::config::CacheConfigs::compressed) write
+//! their positive entries compressed with zstd. Files of these caches are decompressed into memory
+//! when they are opened, so readers always see the original contents. Files of other caches are
+//! never decompressed, even if they happen to start with the zstd magic bytes. Error entries and
+//! the contents uploaded to the shared cache are never compressed.
+
+use std::fs::File;
+use std::io::{self, Read};
+
+use symbolic::common::ByteView;
+
+/// The magic bytes at the start of every zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The zstd compression level, which favors speed over size.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Whether the contents of a cache file are compressed.
+pub(super) fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// Writes the compressed `data` to `file`.
+pub(super) fn compress(data: &[u8], file: &mut File) -> io::Result<()> {
+    zstd::stream::copy_encode(data, file, COMPRESSION_LEVEL)
+}
+
+/// Decompresses the contents of a cache file, if they are compressed.
+///
+/// Contents decompressing to more than `max_size` bytes are rejected, like downloads exceeding the
+/// [`max_decompressed_size`](crate::config::DecompressionConfig::max_decompressed_size).
+pub(super) fn decompress(
+    data: ByteView<'static>,
+    max_size: Option<u64>,
+) -> io::Result<ByteView<'static>> {
+    if !is_compressed(&data) {
+        return Ok(data);
+    }
+    let mut decoder = zstd::stream::Decoder::new(&*data)?;
+    let mut decompressed = Vec::new();
+    let Some(max_size) = max_size else {
+        decoder.read_to_end(&mut decompressed)?;
+        return Ok(ByteView::from_vec(decompressed));
+    };
+
+    decoder
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > max_size {
+        let message = format!("cache file decompresses to more than {max_size} bytes");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    Ok(ByteView::from_vec(decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = b"SYMC some symcache contents".repeat(100);
+        let mut temp_file = NamedTempFile::new().unwrap();
+        compress(&data, temp_file.as_file_mut()).unwrap();
+
+        let compressed = ByteView::open(temp_file.path()).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < data.len());
+
+        let decompressed = decompress(compressed.clone(), None).unwrap();
+        assert_eq!(&*decompressed, &data[..]);
+
+        let limit = data.len() as u64;
+        let decompressed = decompress(compressed.clone(), Some(limit)).unwrap();
+        assert_eq!(&*decompressed, &data[..]);
+
+        let error = decompress(compressed, Some(limit - 1)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Other contents are passed through as is.
+        let raw = decompress(ByteView::from_slice(b"malformed"), None).unwrap();
+        assert_eq!(&*raw, b"malformed");
+    }
+}
@@ -1,7 +1,10 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 /// All known cache names.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CacheName {
     Objects,
     ObjectMeta,
@@ -10,7 +13,10 @@ pub enum CacheName {
     Symcaches,
     Cficaches,
     PpdbCaches,
+    Breakpad,
+    #[serde(rename = "sourcemap_caches")]
     SourceMapCaches,
+    #[serde(rename = "sourcefiles")]
     SourceFiles,
     Diagnostics,
     Proguard,
@@ -26,6 +32,7 @@ impl AsRef<str> for CacheName {
             Self::Symcaches => "symcaches",
             Self::Cficaches => "cficaches",
             Self::PpdbCaches => "ppdb_caches",
+            Self::Breakpad => "breakpad",
             Self::SourceMapCaches => "sourcemap_caches",
             Self::SourceFiles => "sourcefiles",
             Self::Diagnostics => "diagnostics",
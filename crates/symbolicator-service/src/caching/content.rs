@@ -0,0 +1,191 @@
+//! Content-addressed storage of cache files.
+//!
+//! Different cache keys frequently resolve to identical files, like the same PDB found on two
+//! sources. Caches listed in [`CacheConfigs::deduplicated`](crate::config::CacheConfigs::deduplicated)
+//! store the contents of their positive entries under their SHA-256 hash in the `content`
+//! directory, and the file of every cache key is only a small pointer to the contents. This way,
+//! identical files are stored once.
+//!
+//! Pointers are resolved when cache files are opened, and pointers to contents which have been
+//! removed are cache misses. Contents which are no longer pointed to are removed by the cleanup.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use filetime::FileTime;
+use sha2::{Digest, Sha256};
+use symbolic::common::ByteView;
+use tempfile::NamedTempFile;
+
+use super::cache_error::cache_entry_from_bytes;
+use super::fs::catch_not_found;
+use super::memory::persist_tempfile;
+use super::Cache;
+
+/// The directory of the contents, within the cache directory.
+pub(super) const CONTENT_DIR: &str = "content";
+
+/// The start of every pointer file, followed by the hex-formatted hash of the contents.
+const POINTER_MARKER: &[u8] = b"contentaddressed";
+
+/// The size of every pointer file.
+pub(super) const POINTER_LEN: u64 = POINTER_MARKER.len() as u64 + 64;
+
+/// Contents younger than this are never removed by the cleanup.
+///
+/// The pointer to new contents is written after the contents themselves, so the cleanup could
+/// otherwise remove contents whose pointer is just being written.
+pub(super) const CONTENT_GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Returns the hex-formatted SHA-256 hash of the contents.
+fn content_hash(contents: &[u8]) -> String {
+    let mut hash = String::with_capacity(64);
+    for b in Sha256::digest(contents) {
+        write!(hash, "{b:02x}").unwrap();
+    }
+    hash
+}
+
+/// Returns the path of the contents with the given hash.
+fn content_path(cache_dir: &Path, hash: &str) -> PathBuf {
+    cache_dir
+        .join(CONTENT_DIR)
+        .join(&hash[..2])
+        .join(&hash[2..])
+}
+
+/// Returns the hash of the contents a pointer file points to, if `data` is a pointer file.
+pub(super) fn parse_pointer(data: &[u8]) -> Option<&str> {
+    let hash = data.strip_prefix(POINTER_MARKER)?;
+    if hash.len() != 64 || !hash.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    std::str::from_utf8(hash).ok()
+}
+
+/// Returns the hash of the contents at `path` within the content directory.
+pub(super) fn hash_of_content_path(content_dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(content_dir).ok()?;
+    let hash: String = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    (hash.len() == 64).then_some(hash)
+}
+
+impl Cache {
+    /// Whether positive entries are stored in the content store.
+    pub fn is_deduplicated(&self) -> bool {
+        self.deduplicate
+    }
+
+    /// Returns the contents a cache file points to, or the data itself if it is no pointer.
+    ///
+    /// Contents which have been removed are reported as [`io::ErrorKind::NotFound`].
+    pub(super) fn resolve_content(&self, data: ByteView<'static>) -> io::Result<ByteView<'static>> {
+        let (Some(cache_dir), Some(hash)) = (self.cache_dir.as_deref(), parse_pointer(&data))
+        else {
+            return Ok(data);
+        };
+        ByteView::open(content_path(cache_dir, hash))
+    }
+
+    /// Moves `temp_file` into the content store, and returns a temporary file pointing to it.
+    ///
+    /// The `contents` are the uncompressed contents of `temp_file`, which determine its hash.
+    pub(super) fn store_content(
+        &self,
+        temp_file: NamedTempFile,
+        contents: &[u8],
+    ) -> io::Result<NamedTempFile> {
+        let cache_dir = self
+            .cache_dir
+            .as_deref()
+            .ok_or_else(|| io::Error::other("caching is disabled"))?;
+        let hash = content_hash(contents);
+        let path = content_path(cache_dir, &hash);
+
+        // Touching the existing contents protects them from a concurrent cleanup.
+        match catch_not_found(|| filetime::set_file_mtime(&path, FileTime::now()))? {
+            Some(()) => {
+                metric!(counter("caches.content.deduplicated") += 1, "cache" => self.name.as_ref());
+            }
+            None => {
+                let file = persist_tempfile(temp_file, &path)?;
+                if let Ok(metadata) = file.metadata() {
+                    self.record_write(&path, metadata.len());
+                }
+            }
+        }
+
+        let mut pointer = self.tempfile()?;
+        pointer.write_all(POINTER_MARKER)?;
+        pointer.write_all(hash.as_bytes())?;
+        Ok(pointer)
+    }
+
+    /// Moves the positive entries of this cache which are no pointers yet into the content store.
+    ///
+    /// This migrates caches which have been written before they were deduplicated. The pointers
+    /// keep the modification time of the original files, and thus their expiration. Returns the
+    /// number of migrated files.
+    pub fn deduplicate(&self) -> Result<usize> {
+        let cache_dir = self.cache_dir.as_deref().ok_or_else(|| {
+            anyhow!("no caching configured! Did you provide a path to your config file?")
+        })?;
+        let content_dir = cache_dir.join(CONTENT_DIR);
+
+        let mut migrated = 0;
+        let mut directories = vec![cache_dir.to_owned()];
+        while let Some(directory) = directories.pop() {
+            let Some(entries) = catch_not_found(|| fs::read_dir(&directory))? else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path == content_dir {
+                    continue;
+                } else if path.is_dir() {
+                    directories.push(path);
+                } else if path.extension().is_none() {
+                    // Files may be removed by a cleanup at any time.
+                    if catch_not_found(|| self.migrate_file(cache_dir, &path))?.unwrap_or(false) {
+                        migrated += 1;
+                    }
+                }
+            }
+        }
+
+        tracing::info!("Deduplicated {migrated} files of `{}` cache", self.name);
+        Ok(migrated)
+    }
+
+    /// Moves the file at `path` into the content store, returning `false` if it is no entry.
+    fn migrate_file(&self, cache_dir: &Path, path: &Path) -> io::Result<bool> {
+        let metadata = path.metadata()?;
+        let data = ByteView::open(path)?;
+        if cache_entry_from_bytes(data.clone()).is_err() || parse_pointer(&data).is_some() {
+            return Ok(false);
+        }
+
+        let hash = content_hash(&self.decompress(data)?);
+        let content = content_path(cache_dir, &hash);
+        if content.exists() {
+            fs::remove_file(path)?;
+        } else {
+            fs::create_dir_all(content.parent().unwrap())?;
+            fs::rename(path, &content)?;
+        }
+
+        let mut pointer = self.tempfile()?;
+        pointer.write_all(POINTER_MARKER)?;
+        pointer.write_all(hash.as_bytes())?;
+        persist_tempfile(pointer, path)?;
+        filetime::set_file_mtime(path, FileTime::from_last_modification_time(&metadata))?;
+        Ok(true)
+    }
+}
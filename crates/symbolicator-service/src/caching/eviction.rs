@@ -0,0 +1,529 @@
+//! Size-based eviction of file system caches.
+//!
+//! Caches with a `max_size` keep an index of their files in memory, ordered by last use. The
+//! index is built by scanning the cache directory once at startup, and from then on it is kept
+//! up to date as files are written and used. Whenever the files exceed the budget, the least
+//! recently used ones are removed.
+//!
+//! Caches partitioned by scope can additionally have [`ScopeQuotas`]. The index then also keeps
+//! track of the files of every scope, and evicts the least recently used files of a scope once
+//! they exceed its quota. This way, a single scope can not evict the files of all others.
+//!
+//! This complements the time-based [`cleanup`](super::cleanup), which still removes expired
+//! files. Files removed by a cleanup are eventually dropped from the index as well, when they
+//! are up for eviction.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::cache_key::{partition_of, GLOBAL_PARTITION};
+use super::CacheName;
+
+/// The files of a cache, ordered by last use.
+#[derive(Debug, Default)]
+struct LruIndex {
+    /// The total size of all files.
+    total_size: u64,
+    /// The size and last use of every file.
+    files: HashMap<Arc<Path>, (u64, SystemTime)>,
+    /// The files ordered by last use.
+    by_last_use: BTreeSet<(SystemTime, Arc<Path>)>,
+}
+
+impl LruIndex {
+    /// Inserts a file, replacing a previous entry for it.
+    fn insert(&mut self, path: Arc<Path>, size: u64, last_use: SystemTime) {
+        if let Some((old_size, old_use)) = self.files.insert(path.clone(), (size, last_use)) {
+            self.total_size -= old_size;
+            self.by_last_use.remove(&(old_use, path.clone()));
+        }
+        self.total_size += size;
+        self.by_last_use.insert((last_use, path));
+    }
+
+    /// Updates the last use of a file, returning `false` if the file is not known.
+    fn touch(&mut self, path: &Path, last_use: SystemTime) -> bool {
+        let Some((path, &(size, _))) = self.files.get_key_value(path) else {
+            return false;
+        };
+        let path = Arc::clone(path);
+        self.insert(path, size, last_use);
+        true
+    }
+
+    /// Removes a file, returning whether it was known.
+    fn remove(&mut self, path: &Path) -> bool {
+        let Some((path, (size, last_use))) = self.files.remove_entry(path) else {
+            return false;
+        };
+        self.total_size -= size;
+        self.by_last_use.remove(&(last_use, path));
+        true
+    }
+
+    /// Removes the least recently used file.
+    fn pop_oldest(&mut self) -> Option<(Arc<Path>, u64)> {
+        let (_, path) = self.by_last_use.pop_first()?;
+        let (size, _) = self.files.remove(&path)?;
+        self.total_size -= size;
+        Some((path, size))
+    }
+}
+
+/// Byte quotas for the scopes of a cache partitioned by scope.
+#[derive(Debug, Clone, Default)]
+pub struct ScopeQuotas {
+    /// The quota of every scope other than `global` without a quota of its own.
+    pub default: Option<u64>,
+    /// The quotas of individual scopes, by the name of their partition.
+    pub scopes: HashMap<String, u64>,
+}
+
+impl ScopeQuotas {
+    /// Returns the quota of the scope with the given partition name.
+    fn quota(&self, scope: &str) -> Option<u64> {
+        match self.scopes.get(scope) {
+            Some(quota) => Some(*quota),
+            None if scope == GLOBAL_PARTITION => None,
+            None => self.default,
+        }
+    }
+}
+
+/// The files of a partitioned cache, by scope.
+#[derive(Debug)]
+struct ScopeIndexes {
+    cache_dir: PathBuf,
+    quotas: ScopeQuotas,
+    scopes: HashMap<Arc<str>, LruIndex>,
+}
+
+impl ScopeIndexes {
+    /// Returns the scope of the file at `path`, if it belongs to one.
+    fn scope_of<'a>(&self, path: &'a Path) -> Option<&'a str> {
+        partition_of(path.strip_prefix(&self.cache_dir).ok()?)
+    }
+}
+
+/// The files of a cache, in total and by scope if the cache has scope quotas.
+#[derive(Debug, Default)]
+struct EvictionIndex {
+    all: LruIndex,
+    scopes: Option<ScopeIndexes>,
+}
+
+impl EvictionIndex {
+    /// Returns the index of the scope of the file at `path`, if it is known.
+    fn scope_index(&mut self, path: &Path) -> Option<&mut LruIndex> {
+        let scopes = self.scopes.as_mut()?;
+        let scope = scopes.scope_of(path)?;
+        scopes.scopes.get_mut(scope)
+    }
+
+    /// Inserts a file, replacing a previous entry for it.
+    fn insert(&mut self, path: Arc<Path>, size: u64, last_use: SystemTime) {
+        self.all.insert(path.clone(), size, last_use);
+        if let Some(scopes) = &mut self.scopes {
+            if let Some(scope) = scopes.scope_of(&path) {
+                let index = scopes.scopes.entry(scope.into()).or_default();
+                index.insert(path, size, last_use);
+            }
+        }
+    }
+
+    /// Updates the last use of a file, returning `false` if the file is not known.
+    fn touch(&mut self, path: &Path, last_use: SystemTime) -> bool {
+        if !self.all.touch(path, last_use) {
+            return false;
+        }
+        if let Some(index) = self.scope_index(path) {
+            index.touch(path, last_use);
+        }
+        true
+    }
+
+    /// Removes a file, returning whether it was known.
+    fn remove(&mut self, path: &Path) -> bool {
+        if let Some(index) = self.scope_index(path) {
+            index.remove(path);
+        }
+        self.all.remove(path)
+    }
+
+    /// Returns the scope of the file at `path` and the total size of its files.
+    fn scope_size<'a>(&self, path: &'a Path) -> Option<(&'a str, u64)> {
+        let scopes = self.scopes.as_ref()?;
+        let scope = scopes.scope_of(path)?;
+        Some((scope, scopes.scopes.get(scope)?.total_size))
+    }
+
+    /// Takes the least recently used files of scopes which exceed their quota.
+    ///
+    /// With a `path`, only the scope of that file is checked.
+    fn evict_scopes(&mut self, path: Option<&Path>) -> Vec<(Arc<Path>, u64)> {
+        let mut evicted = Vec::new();
+        let Some(scopes) = &mut self.scopes else {
+            return evicted;
+        };
+
+        let mut evict = |scope: &str, index: &mut LruIndex| {
+            let Some(quota) = scopes.quotas.quota(scope) else {
+                return;
+            };
+            while index.total_size > quota {
+                let Some(file) = index.pop_oldest() else {
+                    break;
+                };
+                evicted.push(file);
+            }
+        };
+        match path {
+            Some(path) => {
+                if let Some(scope) = scopes.scope_of(path) {
+                    if let Some(index) = scopes.scopes.get_mut(scope) {
+                        evict(scope, index);
+                    }
+                }
+            }
+            None => {
+                for (scope, index) in &mut scopes.scopes {
+                    evict(scope, index);
+                }
+            }
+        }
+
+        for (path, _) in &evicted {
+            self.all.remove(path);
+        }
+        evicted
+    }
+
+    /// Takes the least recently used files until all files are within `max_size`.
+    fn evict_oldest(&mut self, max_size: u64) -> Vec<(Arc<Path>, u64)> {
+        let mut evicted = Vec::new();
+        while self.all.total_size > max_size {
+            let Some((path, size)) = self.all.pop_oldest() else {
+                break;
+            };
+            if let Some(index) = self.scope_index(&path) {
+                index.remove(&path);
+            }
+            evicted.push((path, size));
+        }
+        evicted
+    }
+}
+
+/// Evicts the least recently used files of a cache once they exceed a total size.
+pub struct LruEviction {
+    name: CacheName,
+    max_size: u64,
+    index: Mutex<EvictionIndex>,
+}
+
+impl LruEviction {
+    /// Creates the eviction for a cache with the given budget in bytes, without any files.
+    pub fn new(name: CacheName, max_size: u64) -> Self {
+        Self {
+            name,
+            max_size,
+            index: Default::default(),
+        }
+    }
+
+    /// Additionally evicts the files of scopes which exceed their quota.
+    ///
+    /// The `cache_dir` is the directory of the cache, which is partitioned by scope.
+    pub fn with_scope_quotas(mut self, cache_dir: &Path, quotas: ScopeQuotas) -> Self {
+        self.index.get_mut().unwrap().scopes = Some(ScopeIndexes {
+            cache_dir: cache_dir.to_owned(),
+            quotas,
+            scopes: Default::default(),
+        });
+        self
+    }
+
+    /// Creates the eviction, and adds the existing files of `cache_dir` in the background.
+    pub fn spawn(
+        name: CacheName,
+        cache_dir: &Path,
+        max_size: u64,
+        quotas: Option<ScopeQuotas>,
+    ) -> Arc<Self> {
+        let mut eviction = Self::new(name, max_size);
+        if let Some(quotas) = quotas {
+            eviction = eviction.with_scope_quotas(cache_dir, quotas);
+        }
+        let eviction = Arc::new(eviction);
+
+        let scanned = Arc::clone(&eviction);
+        let cache_dir = cache_dir.to_owned();
+        let thread = std::thread::Builder::new()
+            .name(format!("sym-lru-{name}"))
+            .spawn(move || {
+                if let Err(err) = scanned.scan(&cache_dir) {
+                    let error: &dyn std::error::Error = &err;
+                    tracing::error!(error, "Failed to scan the `{}` cache", scanned.name);
+                }
+            });
+        if let Err(err) = thread {
+            let error: &dyn std::error::Error = &err;
+            tracing::error!(error, "Failed to start scanning the `{name}` cache");
+        }
+
+        eviction
+    }
+
+    /// Records a file which has been written to the cache.
+    ///
+    /// This may evict other files, and even the written file itself if it exceeds the budget
+    /// on its own.
+    pub fn record_write(&self, path: &Path, size: u64) {
+        self.record(path, size, SystemTime::now());
+    }
+
+    /// Records a use of a file in the cache.
+    pub fn record_use(&self, path: &Path) {
+        let now = SystemTime::now();
+        if self.index.lock().unwrap().touch(path, now) {
+            return;
+        }
+        // The file has not been scanned yet.
+        if let Ok(metadata) = fs::metadata(path) {
+            self.record(path, metadata.len(), now);
+        }
+    }
+
+    /// Records a file which has been removed from the cache by other means.
+    pub fn record_removal(&self, path: &Path) {
+        self.index.lock().unwrap().remove(path);
+    }
+
+    /// The total size of all files in the cache.
+    pub fn total_size(&self) -> u64 {
+        self.index.lock().unwrap().all.total_size
+    }
+
+    fn record(&self, path: &Path, size: u64, last_use: SystemTime) {
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            index.insert(path.into(), size, last_use);
+            let evicted = self.evict(&mut index, Some(path));
+            if let Some((scope, size)) = index.scope_size(path) {
+                metric!(
+                    gauge("caches.scope.size") = size,
+                    "cache" => self.name.as_ref(),
+                    "scope" => scope,
+                );
+            }
+            evicted
+        };
+        self.remove(evicted);
+    }
+
+    /// Takes files from the index until it is within the budget.
+    ///
+    /// With a `path`, only the quota of the scope of that file is checked, and the quotas of all
+    /// scopes otherwise.
+    fn evict(&self, index: &mut EvictionIndex, path: Option<&Path>) -> Vec<(Arc<Path>, u64)> {
+        let mut evicted = index.evict_scopes(path);
+        if !evicted.is_empty() {
+            metric!(
+                counter("caches.scope.files_evicted") += evicted.len() as i64,
+                "cache" => self.name.as_ref(),
+            );
+        }
+        evicted.extend(index.evict_oldest(self.max_size));
+        evicted
+    }
+
+    /// Removes evicted files from the file system.
+    fn remove(&self, evicted: Vec<(Arc<Path>, u64)>) {
+        if evicted.is_empty() {
+            return;
+        }
+
+        let mut removed_files = 0i64;
+        let mut removed_bytes = 0i64;
+        for (path, size) in evicted {
+            tracing::debug!("Evicting file `{}`", path.display());
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    removed_files += 1;
+                    removed_bytes += size as i64;
+                }
+                // The file has been cleaned up in the meantime.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    let error: &dyn std::error::Error = &err;
+                    tracing::error!(error, "Failed to evict cache file `{}`", path.display());
+                }
+            }
+        }
+
+        let name = self.name.as_ref();
+        metric!(counter("caches.size.files_evicted") += removed_files, "cache" => name);
+        metric!(counter("caches.size.bytes_evicted") += removed_bytes, "cache" => name);
+    }
+
+    /// Adds all files of `cache_dir` which are not known yet, using their `mtime` as last use.
+    fn scan(&self, cache_dir: &Path) -> io::Result<()> {
+        let mut directories = vec![cache_dir.to_owned()];
+        while let Some(directory) = directories.pop() {
+            let entries = match fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            for entry in entries {
+                let path = entry?.path();
+                // Files may be removed at any time.
+                let Ok(metadata) = fs::symlink_metadata(&path) else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    directories.push(path);
+                } else if metadata.is_file() {
+                    let last_use = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    self.add_scanned(path, metadata.len(), last_use);
+                }
+            }
+        }
+
+        let evicted = self.evict(&mut self.index.lock().unwrap(), None);
+        self.remove(evicted);
+        tracing::info!(
+            "Scanned `{}` cache, totaling {} bytes",
+            self.name,
+            self.total_size()
+        );
+        Ok(())
+    }
+
+    fn add_scanned(&self, path: PathBuf, size: u64, last_use: SystemTime) {
+        let mut index = self.index.lock().unwrap();
+        // Files written or used since the scan started are already up to date.
+        if !index.all.files.contains_key(path.as_path()) {
+            index.insert(path.into(), size, last_use);
+        }
+    }
+}
+
+impl fmt::Debug for LruEviction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruEviction")
+            .field("name", &self.name)
+            .field("max_size", &self.max_size)
+            .field("total_size", &self.total_size())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn create(dir: &Path, name: &str, size: usize) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, vec![b'x'; size]).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let eviction = LruEviction::new(CacheName::Objects, 100);
+        let start = SystemTime::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let a = create(dir.path(), "a", 40);
+        let b = create(dir.path(), "b", 40);
+        eviction.record(&a, 40, at(0));
+        eviction.record(&b, 40, at(1));
+        assert_eq!(eviction.total_size(), 80);
+
+        // `a` has been used more recently than `b`.
+        assert!(eviction.index.lock().unwrap().touch(&a, at(2)));
+
+        let c = create(dir.path(), "c", 40);
+        eviction.record(&c, 40, at(3));
+        assert_eq!(eviction.total_size(), 80);
+        assert!(a.exists());
+        assert!(!b.exists());
+        assert!(c.exists());
+    }
+
+    #[test]
+    fn test_scope_quotas() {
+        let dir = tempfile::tempdir().unwrap();
+        for scope in ["v1/global", "v1/scopes/a", "v1/scopes/b"] {
+            fs::create_dir_all(dir.path().join(scope)).unwrap();
+        }
+        let quotas = ScopeQuotas {
+            default: Some(50),
+            scopes: [("b".to_owned(), 100)].into(),
+        };
+        let eviction =
+            LruEviction::new(CacheName::Objects, 1000).with_scope_quotas(dir.path(), quotas);
+        let start = SystemTime::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Scopes without a quota of their own use the default quota.
+        let a1 = create(dir.path(), "v1/scopes/a/1", 40);
+        let a2 = create(dir.path(), "v1/scopes/a/2", 40);
+        eviction.record(&a1, 40, at(0));
+        eviction.record(&a2, 40, at(1));
+        assert!(!a1.exists());
+        assert!(a2.exists());
+
+        let b1 = create(dir.path(), "v1/scopes/b/1", 40);
+        let b2 = create(dir.path(), "v1/scopes/b/2", 40);
+        eviction.record(&b1, 40, at(2));
+        eviction.record(&b2, 40, at(3));
+        assert!(b1.exists());
+        assert!(b2.exists());
+
+        // The global scope has no quota by default.
+        let g1 = create(dir.path(), "v1/global/1", 40);
+        let g2 = create(dir.path(), "v1/global/2", 40);
+        eviction.record(&g1, 40, at(4));
+        eviction.record(&g2, 40, at(5));
+        assert!(g1.exists());
+        assert!(g2.exists());
+
+        assert_eq!(eviction.total_size(), 200);
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        create(dir.path(), "a", 30);
+        create(dir.path(), "nested/b", 30);
+
+        let eviction = LruEviction::new(CacheName::Objects, 100);
+        eviction.scan(dir.path()).unwrap();
+        assert_eq!(eviction.total_size(), 60);
+
+        // Files are only added once.
+        eviction.scan(dir.path()).unwrap();
+        assert_eq!(eviction.total_size(), 60);
+
+        // Unknown files are picked up when they are used.
+        let c = create(dir.path(), "c", 30);
+        eviction.record_use(&c);
+        assert_eq!(eviction.total_size(), 90);
+
+        // Files larger than the budget are evicted right away.
+        let d = create(dir.path(), "d", 200);
+        eviction.record_write(&d, 200);
+        assert!(!d.exists());
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicIsize;
@@ -8,10 +10,14 @@ use filetime::FileTime;
 use symbolic::common::ByteView;
 use tempfile::NamedTempFile;
 
-use crate::config::{CacheConfig, Config};
+use crate::config::{CacheConfig, CleanupConfig, Config};
 
 use super::cache_error::cache_entry_from_bytes;
-use super::{CacheEntry, CacheError, CacheName};
+use super::cache_key::sanitize_scope;
+use super::compression;
+use super::eviction::{LruEviction, ScopeQuotas};
+use super::open_files::OpenFiles;
+use super::{CacheEntry, CacheError, CacheKey, CacheName};
 
 /// The interval in which positive caches should be touched.
 ///
@@ -53,8 +59,41 @@ pub struct Cache {
     /// The maximum number of lazy refreshes of this cache.
     max_lazy_refreshes: Arc<AtomicIsize>,
 
+    /// Duration before their expiration in which negative entries are revalidated.
+    revalidation_window: Option<Duration>,
+
+    /// The maximum number of concurrent revalidations of this cache.
+    max_revalidations: Arc<AtomicIsize>,
+
+    /// Fraction by which the expiration of negative and malformed entries is extended at random.
+    expiry_jitter: f64,
+
+    /// Duration after their expiration in which negative entries are served while refreshed.
+    refresh_ahead: Option<Duration>,
+
     /// The capacity (in bytes) of the in-memory cache.
     pub(super) in_memory_capacity: u64,
+
+    /// The budget of files held open by the in-memory cache, shared by all [`Caches`](super::Caches).
+    pub(super) open_files: Arc<OpenFiles>,
+
+    /// Evicts the least recently used files once the cache exceeds its `max_size`.
+    eviction: Option<Arc<LruEviction>>,
+
+    /// Whether positive entries are written compressed.
+    compress: bool,
+
+    /// The maximum size compressed entries may decompress to.
+    max_decompressed_size: Option<u64>,
+
+    /// Whether positive entries are stored in the content store.
+    pub(super) deduplicate: bool,
+
+    /// Whether files are partitioned by the scope of their cache key.
+    partitioned: bool,
+
+    /// Options for the cleanup of this cache.
+    pub(super) cleanup: CleanupConfig,
 }
 
 impl Cache {
@@ -72,6 +111,28 @@ impl Cache {
             std::fs::create_dir_all(dir)?;
         }
 
+        let partitions = &config.caches.partitions;
+        let partitioned = partitions.caches.contains(&name);
+        let has_quotas =
+            partitions.max_scope_size.is_some() || !partitions.max_scope_sizes.is_empty();
+        let quotas = (partitioned && has_quotas).then(|| ScopeQuotas {
+            default: partitions.max_scope_size,
+            scopes: partitions
+                .max_scope_sizes
+                .iter()
+                .map(|(scope, max_size)| (sanitize_scope(scope), *max_size))
+                .collect(),
+        });
+
+        let max_size = cache_config.max_size();
+        let eviction = match &cache_dir {
+            Some(dir) if max_size.is_some() || quotas.is_some() => {
+                let max_size = max_size.unwrap_or(u64::MAX);
+                Some(LruEviction::spawn(name, dir, max_size, quotas))
+            }
+            _ => None,
+        };
+
         Ok(Cache {
             name,
             cache_dir,
@@ -79,7 +140,20 @@ impl Cache {
             start_time: SystemTime::now(),
             cache_config,
             max_lazy_refreshes,
+            revalidation_window: config.caches.revalidation.window,
+            max_revalidations: Arc::new(AtomicIsize::new(
+                config.caches.revalidation.max_concurrent.max(1),
+            )),
+            expiry_jitter: config.caches.expiry.jitter.clamp(0.0, 1.0),
+            refresh_ahead: config.caches.expiry.refresh_ahead,
             in_memory_capacity,
+            open_files: Arc::new(OpenFiles::new(config.caches.max_open_files)),
+            eviction,
+            compress: config.caches.compressed.contains(&name),
+            max_decompressed_size: config.decompression.max_decompressed_size,
+            deduplicate: config.caches.deduplicated.contains(&name),
+            partitioned,
+            cleanup: config.caches.cleanup,
         })
     }
 
@@ -95,6 +169,46 @@ impl Cache {
         self.max_lazy_refreshes.clone()
     }
 
+    /// Duration before their expiration in which negative entries are revalidated, if enabled.
+    pub fn revalidation_window(&self) -> Option<Duration> {
+        self.revalidation_window
+    }
+
+    pub fn max_revalidations(&self) -> Arc<AtomicIsize> {
+        self.max_revalidations.clone()
+    }
+
+    /// Whether positive entries are written compressed.
+    pub fn compress(&self) -> bool {
+        self.compress
+    }
+
+    /// Decompresses the contents of a file of this cache, if the cache is compressed.
+    pub(super) fn decompress(&self, data: ByteView<'static>) -> io::Result<ByteView<'static>> {
+        if !self.compress {
+            return Ok(data);
+        }
+        compression::decompress(data, self.max_decompressed_size)
+    }
+
+    /// Whether files are partitioned by the scope of their cache key.
+    pub fn is_partitioned(&self) -> bool {
+        self.partitioned
+    }
+
+    /// Returns the path of the file of `key` in the given `version`, relative to the cache
+    /// directory.
+    ///
+    /// This is the [`CacheKey::cache_path`], which also identifies the item in the shared cache,
+    /// unless this cache is partitioned by scope.
+    pub fn item_path(&self, key: &CacheKey, version: u32) -> String {
+        if self.partitioned {
+            key.partitioned_cache_path(version)
+        } else {
+            key.cache_path(version)
+        }
+    }
+
     /// Validate cache expiration of path.
     ///
     /// If cache should not be used, `Err(io::ErrorKind::NotFound)` is returned.
@@ -139,14 +253,17 @@ impl Cache {
                 ExpirationTime::TouchIn(touch_in)
             }
             ExpirationStrategy::Negative => {
-                let retry_misses_after = self
-                    .cache_config
-                    .retry_misses_after()
-                    .unwrap_or(Duration::MAX);
+                let retry_after = retry_negative_after(&self.cache_config, &cache_entry);
+                let retry_after = jitter(retry_after, self.expiry_jitter, path_seed(path));
 
-                let expires_in = retry_misses_after.saturating_sub(mtime_elapsed);
+                let expires_in = retry_after.saturating_sub(mtime_elapsed);
 
                 if expires_in == Duration::ZERO {
+                    // Recently expired entries are still served while they are refreshed.
+                    let stale_for = mtime_elapsed.saturating_sub(retry_after);
+                    if self.refresh_ahead.is_some_and(|window| stale_for < window) {
+                        return Ok((cache_entry, ExpirationTime::Stale));
+                    }
                     return Err(io::ErrorKind::NotFound.into());
                 }
 
@@ -157,6 +274,8 @@ impl Cache {
                     .cache_config
                     .retry_malformed_after()
                     .unwrap_or(Duration::MAX);
+                let retry_malformed_after =
+                    jitter(retry_malformed_after, self.expiry_jitter, path_seed(path));
 
                 let expires_in = retry_malformed_after.saturating_sub(mtime_elapsed);
 
@@ -176,7 +295,8 @@ impl Cache {
 
     /// Validates `cachefile` against expiration config and open a [`ByteView`] on it.
     ///
-    /// Takes care of bumping `mtime`.
+    /// Takes care of bumping `mtime`, and of recording the use for the size-based eviction.
+    /// Pointers to the content store are resolved, and compressed files are decompressed.
     ///
     /// If an open [`ByteView`] is returned it also returns whether the mtime has been
     /// bumped.
@@ -196,11 +316,37 @@ impl Cache {
                 // well, we just touched the file ;-)
                 expiration = ExpirationTime::TouchIn(TOUCH_EVERY);
             }
+            if let Some(eviction) = &self.eviction {
+                eviction.record_use(path);
+            }
+
+            let cache_entry = match cache_entry {
+                Ok(data) => Ok(self.decompress(self.resolve_content(data)?)?),
+                Err(err) => Err(err),
+            };
 
             Ok((cache_entry, expiration))
         })
     }
 
+    /// Records a file of `size` bytes which has been written to the cache.
+    ///
+    /// If the cache has a `max_size`, this evicts the least recently used files which exceed it.
+    pub(super) fn record_write(&self, path: &Path, size: u64) {
+        if let Some(eviction) = &self.eviction {
+            eviction.record_write(path, size);
+        }
+    }
+
+    /// Removes a file from the cache, returning whether it existed.
+    pub(super) fn remove_cachefile(&self, path: &Path) -> io::Result<bool> {
+        let removed = catch_not_found(|| std::fs::remove_file(path))?.is_some();
+        if let Some(eviction) = &self.eviction {
+            eviction.record_removal(path);
+        }
+        Ok(removed)
+    }
+
     /// Create a new temporary file to use in the cache.
     pub fn tempfile(&self) -> io::Result<NamedTempFile> {
         match self.tmp_dir {
@@ -267,6 +413,10 @@ pub enum ExpirationTime {
     /// The [`Duration`] after which a positive cache entry needs to be touched to keep it
     /// alive for a longer time.
     TouchIn(Duration),
+
+    /// A [`Negative`](ExpirationStrategy::Negative) cache entry which has expired, but is still
+    /// served within the `refresh_ahead` window while it is refreshed in the background.
+    Stale,
 }
 
 impl ExpirationTime {
@@ -280,14 +430,18 @@ impl ExpirationTime {
                 Self::TouchIn(Duration::from_secs(3600))
             }
             ExpirationStrategy::Negative => {
-                let retry_misses_after = config.retry_misses_after().unwrap_or(Duration::MAX);
+                let retry_after = retry_negative_after(config, entry);
 
-                Self::RefreshIn(retry_misses_after)
+                Self::RefreshIn(jitter(retry_after, cache.expiry_jitter, rand::random()))
             }
             ExpirationStrategy::Malformed => {
                 let retry_malformed_after = config.retry_malformed_after().unwrap_or(Duration::MAX);
 
-                Self::RefreshIn(retry_malformed_after)
+                Self::RefreshIn(jitter(
+                    retry_malformed_after,
+                    cache.expiry_jitter,
+                    rand::random(),
+                ))
             }
         }
     }
@@ -297,13 +451,19 @@ impl ExpirationTime {
         matches!(self, ExpirationTime::TouchIn(TOUCH_EVERY))
     }
 
+    /// Says whether the cache has expired, and needs to be refreshed in the background.
+    pub fn is_stale(&self) -> bool {
+        matches!(self, ExpirationTime::Stale)
+    }
+
     /// Gives the [`Instant`] at which the item expires.
     pub fn as_instant(&self) -> Instant {
         let duration = match self {
-            ExpirationTime::RefreshIn(d) => d,
-            ExpirationTime::TouchIn(d) => d,
+            ExpirationTime::RefreshIn(d) => *d,
+            ExpirationTime::TouchIn(d) => *d,
+            ExpirationTime::Stale => Duration::ZERO,
         };
-        Instant::now() + *duration
+        Instant::now() + duration
     }
 }
 
@@ -318,6 +478,43 @@ pub(super) fn expiration_strategy<T>(status: &CacheEntry<T>) -> ExpirationStrate
     }
 }
 
+/// Returns the duration after which a [`Negative`](ExpirationStrategy::Negative) cache entry
+/// expires, depending on the kind of error.
+fn retry_negative_after<T>(config: &CacheConfig, entry: &CacheEntry<T>) -> Duration {
+    let retry_after = match entry {
+        Err(CacheError::DownloadError(_)) => config.retry_download_errors_after(),
+        // The source may be available again any moment.
+        Err(CacheError::Unavailable(_)) => Some(Duration::ZERO),
+        Err(CacheError::Timeout(_)) => config.retry_timeouts_after(),
+        Err(CacheError::PermissionDenied(_)) => config.retry_permission_denied_after(),
+        _ => config.retry_misses_after(),
+    };
+    retry_after.unwrap_or(Duration::MAX)
+}
+
+/// Extends `duration` by up to the `fraction` of it, depending on `seed`.
+///
+/// Entries created at the same time would otherwise all expire at once, and cause a burst of
+/// recomputations.
+fn jitter(duration: Duration, fraction: f64, seed: u64) -> Duration {
+    if fraction <= 0.0 || duration == Duration::MAX {
+        return duration;
+    }
+    let factor = (seed as f64 / u64::MAX as f64) * fraction;
+    duration
+        .checked_add(duration.mul_f64(factor))
+        .unwrap_or(Duration::MAX)
+}
+
+/// Derives the seed of the [`jitter`] of a cache file from its path.
+///
+/// The seed is stable, so every check of the file computes the same expiration.
+fn path_seed(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    hasher.finish()
+}
+
 pub(super) fn catch_not_found<F, R>(f: F) -> io::Result<Option<R>>
 where
     F: FnOnce() -> io::Result<R>,
@@ -0,0 +1,229 @@
+//! Inspection of the files in the file system caches.
+//!
+//! This backs the `symbolicator cache` command, which lists the files of the caches together
+//! with their age, size and the errors they store, and decodes the headers of cache files.
+
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use symbolic::cfi::CfiCache;
+use symbolic::common::ByteView;
+use symbolic::debuginfo::Object;
+use symbolic::symcache::SymCache;
+
+use super::cache_error::cache_entry_from_bytes;
+use super::compression;
+use super::content::CONTENT_DIR;
+use super::fs::catch_not_found;
+use super::{Cache, CacheError, CacheName, Caches};
+
+/// A file in a cache, as listed by [`Cache::list_files`].
+#[derive(Debug, Clone)]
+pub struct CacheFileInfo {
+    /// The path of the file, relative to the cache directory.
+    pub path: PathBuf,
+    /// The cache version this file belongs to, `0` for files outside of a version directory.
+    pub version: u32,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// How long ago the file was last modified.
+    ///
+    /// This is the time of the last use for good entries, and the time of creation for errors.
+    pub age: Duration,
+    /// The error stored in the file, if it is an error entry.
+    pub error: Option<CacheError>,
+    /// Whether the file is compressed.
+    pub compressed: bool,
+}
+
+/// The decoded contents of a cache file, as returned by [`Cache::inspect_file`].
+#[derive(Debug, Clone)]
+pub struct CacheFileDetails {
+    /// The file itself.
+    pub info: CacheFileInfo,
+    /// Fields decoded from the header of the file, depending on the kind of cache.
+    ///
+    /// This is empty for error entries and for caches without a known file format.
+    pub header: Vec<(&'static str, String)>,
+}
+
+impl Caches {
+    /// Returns all caches.
+    pub fn all(&self) -> [&Cache; 12] {
+        // Destructure so we do not accidentally forget one of our members.
+        let Self {
+            objects,
+            object_meta,
+            auxdifs,
+            il2cpp,
+            symcaches,
+            cficaches,
+            ppdb_caches,
+            breakpad,
+            sourcemap_caches,
+            sourcefiles,
+            diagnostics,
+            proguard,
+        } = self;
+
+        [
+            objects,
+            object_meta,
+            auxdifs,
+            il2cpp,
+            symcaches,
+            cficaches,
+            ppdb_caches,
+            breakpad,
+            sourcemap_caches,
+            sourcefiles,
+            diagnostics,
+            proguard,
+        ]
+    }
+
+    /// Returns the cache with the given name, like `symcaches`.
+    pub fn get(&self, name: &str) -> Option<&Cache> {
+        self.all()
+            .into_iter()
+            .find(|cache| cache.name().as_ref() == name)
+    }
+}
+
+impl Cache {
+    /// Lists all files of this cache, ordered by path.
+    ///
+    /// Metadata files written alongside cache files in debug builds are skipped, as well as the
+    /// content store of deduplicated caches.
+    pub fn list_files(&self) -> Result<Vec<CacheFileInfo>> {
+        let cache_dir = self.cache_dir_or_err()?;
+        let content_dir = cache_dir.join(CONTENT_DIR);
+
+        let mut files = Vec::new();
+        let mut directories = vec![cache_dir.to_owned()];
+        while let Some(directory) = directories.pop() {
+            let Some(entries) = catch_not_found(|| read_dir(&directory))? else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path == content_dir {
+                    continue;
+                } else if path.is_dir() {
+                    directories.push(path);
+                } else if path.extension().is_none() {
+                    // Files may be removed by a cleanup at any time.
+                    if let Some(info) = catch_not_found(|| read_file_info(cache_dir, &path))? {
+                        files.push(info.0);
+                    }
+                }
+            }
+        }
+
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Reads the file at `path` and decodes its header.
+    ///
+    /// The path is either absolute, or relative to the cache directory as returned by
+    /// [`list_files`](Self::list_files).
+    pub fn inspect_file(&self, path: &Path) -> Result<CacheFileDetails> {
+        let cache_dir = self.cache_dir_or_err()?;
+        let path = cache_dir.join(path);
+        let (info, data) = read_file_info(cache_dir, &path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+
+        let header = match info.error {
+            Some(_) => Vec::new(),
+            None => self
+                .resolve_content(data)
+                .and_then(|data| self.decompress(data))
+                .map_err(anyhow::Error::from)
+                .and_then(|data| decode_header(self.name, &data))
+                .with_context(|| format!("failed to decode `{}`", path.display()))?,
+        };
+
+        Ok(CacheFileDetails { info, header })
+    }
+
+    pub(super) fn cache_dir_or_err(&self) -> Result<&Path> {
+        self.cache_dir.as_deref().ok_or_else(|| {
+            anyhow!("no caching configured! Did you provide a path to your config file?")
+        })
+    }
+}
+
+/// Reads the metadata and contents of the cache file at `path`.
+fn read_file_info(
+    cache_dir: &Path,
+    path: &Path,
+) -> std::io::Result<(CacheFileInfo, ByteView<'static>)> {
+    let metadata = path.metadata()?;
+    let age = metadata.modified()?.elapsed().unwrap_or_default();
+    let data = ByteView::open(path)?;
+    let error = cache_entry_from_bytes(data.clone()).err();
+
+    let relative = path.strip_prefix(cache_dir).unwrap_or(path).to_owned();
+    let version = relative
+        .components()
+        .next()
+        .and_then(|dir| dir.as_os_str().to_str()?.strip_prefix('v')?.parse().ok())
+        .unwrap_or(0);
+
+    let info = CacheFileInfo {
+        path: relative,
+        version,
+        size: metadata.len(),
+        age,
+        error,
+        compressed: compression::is_compressed(&data),
+    };
+    Ok((info, data))
+}
+
+/// Decodes the header of a good cache file, for the caches with a known file format.
+pub(super) fn decode_header(
+    cache: CacheName,
+    data: &ByteView<'static>,
+) -> Result<Vec<(&'static str, String)>> {
+    let header = match cache {
+        CacheName::Objects => {
+            let object = Object::parse(data)?;
+            let mut header = vec![
+                ("format", object.file_format().to_string()),
+                ("kind", object.kind().to_string()),
+                ("arch", object.arch().to_string()),
+                ("debug_id", object.debug_id().to_string()),
+            ];
+            if let Some(code_id) = object.code_id() {
+                header.push(("code_id", code_id.to_string()));
+            }
+            header
+        }
+        CacheName::Symcaches => {
+            let symcache = SymCache::parse(data)?;
+            vec![
+                ("version", symcache.version().to_string()),
+                ("arch", symcache.arch().to_string()),
+                ("debug_id", symcache.debug_id().to_string()),
+            ]
+        }
+        CacheName::Cficaches => {
+            let cficache = CfiCache::from_bytes(data.clone())?;
+            let records = cficache
+                .as_slice()
+                .split(|b| *b == b'\n')
+                .filter(|line| !line.is_empty())
+                .count();
+            vec![
+                ("version", cficache.version().to_string()),
+                ("records", records.to_string()),
+            ]
+        }
+        _ => Vec::new(),
+    };
+    Ok(header)
+}
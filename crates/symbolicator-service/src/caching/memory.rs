@@ -9,12 +9,30 @@ use sentry::{Hub, SentryFutureExt};
 use symbolic::common::ByteView;
 use tempfile::NamedTempFile;
 
-use super::shared_cache::{CacheStoreReason, SharedCacheRef};
+use super::compression;
+use super::migration::CacheMigration;
+use super::open_files::OpenFileGuard;
+use super::shared_cache::{CacheStoreReason, LeaseStatus, SharedCacheRef, SharedCacheStorePolicy};
 use crate::utils::futures::CallOnDrop;
 
-use super::{Cache, CacheEntry, CacheError, CacheKey, ExpirationTime, SharedCacheService};
+use super::{
+    Cache, CacheEntry, CacheError, CacheKey, CacheName, ExpirationTime, SharedCacheService,
+};
+
+/// How often a symbolicator waiting on the lease of another one checks the shared cache.
+const LEASE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Why an item is refreshed in the background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Refresh {
+    /// The item was found in a fallback version, and is recomputed for the current version.
+    Lazy,
+    /// The item is a negative entry about to expire, or which has expired within the
+    /// `refresh_ahead` window, and is recomputed while it is still served.
+    Revalidation,
+}
 
-type InMemoryItem<T> = (Instant, CacheEntry<T>);
+type InMemoryItem<T> = (Instant, CacheEntry<T>, Option<Arc<OpenFileGuard>>);
 type InMemoryCache<T> = moka::future::Cache<CacheKey, InMemoryItem<T>>;
 
 /// Manages a filesystem cache of any kind of data that can be de/serialized from/to bytes.
@@ -133,6 +151,9 @@ impl<T: CacheItemRequest> Cacher<T> {
 /// The `fallback` versions are tried next, in first to last order. They are used only for cache
 /// lookups, but never for writing.
 ///
+/// Files of fallback versions for which `migrations` exist are upgraded to the `current` version
+/// instead of being recomputed, see the `migration` module.
+///
 /// The version `0` is special in the sense that it is not used as part of the resulting cache
 /// file path, and generates the same paths as "legacy" unversioned cache files.
 #[derive(Clone, Debug)]
@@ -142,6 +163,8 @@ pub struct CacheVersions {
     /// A list of fallback cache versions that are being tried on lookup,
     /// in descending order of priority.
     pub fallbacks: &'static [u32],
+    /// Upgrades of files from fallback versions, which are applied when the files are read.
+    pub migrations: &'static [CacheMigration],
 }
 
 pub trait CacheItemRequest: 'static + Send + Sync + Clone {
@@ -189,11 +212,12 @@ impl<T: CacheItemRequest> Cacher<T> {
         let mut temp_file = self.config.tempfile()?;
 
         let shared_cache = self.shared_cache(&request);
-        let shared_cache_hit = if let Some(shared_cache) = shared_cache {
-            let temp_fd = tokio::fs::File::from_std(temp_file.reopen()?);
-            shared_cache.fetch(name, &cache_path, temp_fd).await
-        } else {
-            false
+        let (shared_cache_hit, holds_lease) = match shared_cache {
+            Some(shared_cache) => {
+                self.fetch_shared(shared_cache, &temp_file, &cache_path)
+                    .await?
+            }
+            None => (false, false),
         };
 
         let mut entry = if shared_cache_hit {
@@ -205,6 +229,9 @@ impl<T: CacheItemRequest> Cacher<T> {
 
         if entry.is_err() {
             metric!(counter("caches.computation") += 1, "cache" => name.as_ref());
+            if is_refresh {
+                record_lookup(name, key, "recomputation");
+            }
             match request.compute(&mut temp_file).await {
                 Ok(()) => {
                     // Now we have written the data to the tempfile we can mmap it, persisting it later
@@ -221,9 +248,11 @@ impl<T: CacheItemRequest> Cacher<T> {
             }
         }
 
-        if let Some(cache_dir) = self.config.cache_dir() {
+        // Errors of unavailable sources are not persisted, the source is tried again next time.
+        let persist = !matches!(entry, Err(CacheError::Unavailable(_)));
+        if let Some(cache_dir) = self.config.cache_dir().filter(|_| persist) {
             // Cache is enabled, write it!
-            let cache_path = cache_dir.join(&cache_path);
+            let cache_path = cache_dir.join(self.config.item_path(key, T::VERSIONS.current));
 
             sentry::configure_scope(|scope| {
                 scope.set_extra(
@@ -254,7 +283,22 @@ impl<T: CacheItemRequest> Cacher<T> {
 
             tracing::trace!("Creating {name} at path {:?}", cache_path.display());
 
-            persist_tempfile(temp_file, &cache_path)?;
+            // The entry keeps using the uncompressed temporary file, which remains mapped.
+            if let Ok(byte_view) = &entry {
+                if self.config.compress() {
+                    let mut compressed = self.config.tempfile()?;
+                    compression::compress(byte_view, compressed.as_file_mut())?;
+                    temp_file = compressed;
+                }
+                if self.config.is_deduplicated() {
+                    temp_file = self.config.store_content(temp_file, byte_view)?;
+                }
+            }
+
+            let file = persist_tempfile(temp_file, &cache_path)?;
+            if let Ok(metadata) = file.metadata() {
+                self.config.record_write(&cache_path, metadata.len());
+            }
 
             #[cfg(debug_assertions)]
             {
@@ -270,9 +314,25 @@ impl<T: CacheItemRequest> Cacher<T> {
         // TODO: Not handling negative caches probably has a huge perf impact.  Need to
         // figure out negative caches.  Maybe put them in redis with a TTL?
         if !shared_cache_hit {
-            if let Ok(byteview) = &entry {
-                if let Some(shared_cache) = shared_cache {
-                    shared_cache.store(name, &cache_path, byteview.clone(), CacheStoreReason::New);
+            if let Some(shared_cache) = shared_cache {
+                let mut stored = entry.as_ref().ok().map(|byteview| {
+                    shared_cache.store(name, &cache_path, byteview.clone(), CacheStoreReason::New)
+                });
+                if shared_cache.store_policy(name) == SharedCacheStorePolicy::WriteThrough {
+                    if let Some(stored) = stored.take() {
+                        stored.await.ok();
+                    }
+                }
+                if holds_lease {
+                    let shared_cache = shared_cache.clone();
+                    let future = async move {
+                        // Waiting symbolicators fetch the item once they notice the released lease.
+                        if let Some(stored) = stored {
+                            stored.await.ok();
+                        }
+                        shared_cache.release_lease(name, &cache_path).await;
+                    };
+                    tokio::spawn(future.bind_hub(Hub::new_from_top(Hub::current())));
                 }
             }
         }
@@ -280,6 +340,60 @@ impl<T: CacheItemRequest> Cacher<T> {
         entry.and_then(|byteview| request.load(byteview))
     }
 
+    /// Fetches the item at `cache_path` from the shared cache into `temp_file`.
+    ///
+    /// On a miss, this takes the lease on computing the item. If another symbolicator holds the
+    /// lease, this waits for the item to appear in the shared cache. Waiting ends once the lease
+    /// times out, or once the lease is released without the item, for example because computing
+    /// it failed. In both cases, the item is computed here as well.
+    ///
+    /// Returns whether the item was found, and whether the lease has been taken.
+    async fn fetch_shared(
+        &self,
+        shared_cache: &SharedCacheService,
+        temp_file: &NamedTempFile,
+        cache_path: &str,
+    ) -> std::io::Result<(bool, bool)> {
+        let name = self.config.name();
+        let temp_fd = tokio::fs::File::from_std(temp_file.reopen()?);
+        if shared_cache.fetch(name, cache_path, temp_fd).await {
+            return Ok((true, false));
+        }
+
+        match shared_cache.acquire_lease(name, cache_path).await {
+            LeaseStatus::Acquired => return Ok((false, true)),
+            LeaseStatus::Unavailable => return Ok((false, false)),
+            LeaseStatus::Held => {}
+        }
+
+        let started = Instant::now();
+        let timeout = shared_cache.lease_timeout().unwrap_or_default();
+        let (outcome, result) = loop {
+            if started.elapsed() >= timeout {
+                break ("timeout", (false, false));
+            }
+            tokio::time::sleep(LEASE_POLL_INTERVAL).await;
+
+            // Discard anything a failed fetch may have written.
+            temp_file.as_file().set_len(0)?;
+            let temp_fd = tokio::fs::File::from_std(temp_file.reopen()?);
+            if shared_cache.fetch(name, cache_path, temp_fd).await {
+                break ("hit", (true, false));
+            }
+            match shared_cache.acquire_lease(name, cache_path).await {
+                LeaseStatus::Acquired => break ("acquired", (false, true)),
+                LeaseStatus::Unavailable => break ("unavailable", (false, false)),
+                LeaseStatus::Held => {}
+            }
+        };
+        metric!(
+            timer("caches.lease.wait") = started.elapsed(),
+            "cache" => name.as_ref(),
+            "outcome" => outcome,
+        );
+        Ok(result)
+    }
+
     /// Computes an item by loading from or populating the cache.
     ///
     /// The actual computation is deduplicated between concurrent requests. Finally, the result is
@@ -296,7 +410,14 @@ impl<T: CacheItemRequest> Cacher<T> {
         let name = self.config.name();
         metric!(counter("caches.access") += 1, "cache" => name.as_ref());
 
-        let init = Box::pin(async {
+        // The request is moved into the computation below, so keep a copy in case the entry needs
+        // to be revalidated.
+        let revalidation = self
+            .config
+            .revalidation_window()
+            .map(|window| (window, request.clone()));
+
+        let lookup = Box::pin(async {
             // cache_path is None when caching is disabled.
             if let Some(cache_dir) = self.config.cache_dir() {
                 let shared_cache = self.shared_cache(&request);
@@ -306,11 +427,12 @@ impl<T: CacheItemRequest> Cacher<T> {
                 for version in versions {
                     let is_current_version = version == T::VERSIONS.current;
                     // try the new cache key first, then fall back to the old cache key
-                    let item = match lookup_local_cache(
+                    let (expiration, item, stale) = match lookup_local_cache(
                         &self.config,
                         shared_cache,
                         cache_dir,
-                        &cache_key.cache_path(version),
+                        &cache_key,
+                        version,
                         is_current_version,
                     ) {
                         Err(CacheError::NotFound) => continue,
@@ -319,10 +441,33 @@ impl<T: CacheItemRequest> Cacher<T> {
                             let expiration = ExpirationTime::for_fresh_status(&self.config, &item);
                             return (expiration.as_instant(), item);
                         }
-                        Ok(item) => (item.0, item.1.and_then(|byteview| request.load(byteview))),
+                        Ok(item) => {
+                            let outcome = if item.1.is_ok() {
+                                "hit"
+                            } else {
+                                "negative_hit"
+                            };
+                            record_lookup(name, &cache_key, outcome);
+                            if !is_current_version {
+                                if let Some(upgraded) = self.upgrade(&cache_key, version, &item.1) {
+                                    let item: CacheEntry<_> = Ok(upgraded);
+                                    let expiration =
+                                        ExpirationTime::for_fresh_status(&self.config, &item);
+                                    let item = item.and_then(|byteview| request.load(byteview));
+                                    return (expiration.as_instant(), item);
+                                }
+                            }
+                            let (expiration, item) = item;
+                            let stale = expiration.is_stale();
+                            let item = item.and_then(|byteview| request.load(byteview));
+                            (expiration.as_instant(), item, stale)
+                        }
                     };
 
-                    if !is_current_version {
+                    if stale {
+                        // The expired entry is served until its refresh replaces it.
+                        self.spawn_refresh(cache_key.clone(), request, Refresh::Revalidation);
+                    } else if !is_current_version {
                         // we have found an outdated cache that we will use right away,
                         // and we will kick off a recomputation for the `current` cache version
                         // in a deduplicated background task, which we will not await
@@ -331,16 +476,17 @@ impl<T: CacheItemRequest> Cacher<T> {
                             "version" => &version.to_string(),
                             "cache" => name.as_ref(),
                         );
-                        self.spawn_refresh(cache_key.clone(), request);
+                        self.spawn_refresh(cache_key.clone(), request, Refresh::Lazy);
                     }
 
-                    return item;
+                    return (expiration, item);
                 }
             }
 
             // A file was not found. If this spikes, it's possible that the filesystem cache
             // just got pruned.
             metric!(counter("caches.file.miss") += 1, "cache" => name.as_ref());
+            record_lookup(name, &cache_key, "miss");
 
             let item = self
                 .compute(request, &cache_key, false)
@@ -356,6 +502,11 @@ impl<T: CacheItemRequest> Cacher<T> {
 
             (expiration.as_instant(), item)
         });
+        let init = Box::pin(async {
+            let (expiration, item) = lookup.await;
+            let open_file = self.open_file(&cache_key, &item);
+            (expiration, item, open_file)
+        });
 
         let entry = self
             .cache
@@ -363,13 +514,147 @@ impl<T: CacheItemRequest> Cacher<T> {
             .or_insert_with(init)
             .await;
 
-        if !entry.is_fresh() {
+        if entry.is_fresh() {
+            // Unavailable sources are retried by the next request, the requests waiting for this
+            // computation share the error though.
+            if matches!(entry.value().1, Err(CacheError::Unavailable(_))) {
+                self.cache.invalidate(&cache_key).await;
+            }
+            metric!(counter("caches.memory.miss") += 1, "cache" => name.as_ref());
+            metric!(gauge("caches.memory.entries") = self.cache.entry_count(), "cache" => name.as_ref());
+            metric!(gauge("caches.memory.size") = self.cache.weighted_size(), "cache" => name.as_ref());
+        } else {
             metric!(counter("caches.memory.hit") += 1, "cache" => name.as_ref());
+            if let Some(open_file) = &entry.value().2 {
+                open_file.touch();
+            }
+            let outcome = if entry.value().1.is_ok() {
+                "hit"
+            } else {
+                "negative_hit"
+            };
+            record_lookup(name, &cache_key, outcome);
         }
-        entry.into_value().1
+
+        let (expiration, item, _) = entry.into_value();
+        if let Some((window, request)) = revalidation {
+            // Errors which are used shortly before they expire are hot, so recompute them now
+            // instead of letting the next request wait for the recomputation.
+            let expires_in = expiration.saturating_duration_since(Instant::now());
+            if item.is_err() && expires_in <= window {
+                self.spawn_refresh(cache_key, request, Refresh::Revalidation);
+            }
+        }
+        item
+    }
+
+    /// Removes an item from all layers of the cache.
+    ///
+    /// This drops the in-memory item and removes the files of all cache versions, regardless of
+    /// whether they contain an item or an error. With `shared_cache`, the item is deleted from
+    /// the shared cache as well.
+    ///
+    /// Returns the number of removed files and shared cache entries.
+    pub async fn purge(&self, cache_key: &CacheKey, shared_cache: bool) -> usize {
+        let name = self.config.name();
+        self.cache.invalidate(cache_key).await;
+
+        let shared_cache = shared_cache.then(|| self.shared_cache.get()).flatten();
+        let versions =
+            std::iter::once(T::VERSIONS.current).chain(T::VERSIONS.fallbacks.iter().copied());
+
+        let mut removed = 0;
+        for version in versions {
+            let cache_path = cache_key.cache_path(version);
+            if let Some(cache_dir) = self.config.cache_dir() {
+                let item_path = cache_dir.join(self.config.item_path(cache_key, version));
+                match self.config.remove_cachefile(&item_path) {
+                    Ok(true) => removed += 1,
+                    Ok(false) => {}
+                    Err(err) => {
+                        let error: &dyn std::error::Error = &err;
+                        tracing::error!(
+                            error,
+                            "Failed to purge {name} at path {}",
+                            item_path.display()
+                        );
+                    }
+                }
+
+                #[cfg(debug_assertions)]
+                {
+                    let _ = std::fs::remove_file(item_path.with_extension("txt"));
+                }
+            }
+            if let Some(shared_cache) = shared_cache {
+                if shared_cache.delete(name, &cache_path).await {
+                    removed += 1;
+                }
+            }
+        }
+
+        metric!(counter("caches.purged") += removed as i64, "cache" => name.as_ref());
+        removed
     }
 
-    fn spawn_refresh(&self, cache_key: CacheKey, request: T) {
+    /// Upgrades a good `entry` of a fallback `version` to the current version, if there are
+    /// migrations for it.
+    ///
+    /// Returns the upgraded contents, or `None` if the entry could not be upgraded.
+    fn upgrade(
+        &self,
+        cache_key: &CacheKey,
+        version: u32,
+        entry: &CacheEntry<ByteView<'static>>,
+    ) -> Option<ByteView<'static>> {
+        let data = entry.as_ref().ok()?;
+        let cache_dir = self.config.cache_dir()?;
+        let path = cache_dir.join(self.config.item_path(cache_key, T::VERSIONS.current));
+
+        match self
+            .config
+            .write_upgraded(&T::VERSIONS, version, data, &path)
+        {
+            Ok(upgraded) => upgraded,
+            Err(err) => {
+                let error: &dyn std::error::Error = &err;
+                tracing::error!(
+                    error,
+                    "Failed to upgrade {} from version {version}",
+                    self.config.name()
+                );
+                None
+            }
+        }
+    }
+
+    /// Records the file kept open by an item which is inserted into the in-memory cache.
+    ///
+    /// Items are loaded from files only if the file system cache is enabled, and errors do not
+    /// keep their file open. Once the [`OpenFiles`](super::OpenFiles) budget is exhausted, the
+    /// item is dropped from the in-memory cache to close its file.
+    fn open_file(
+        &self,
+        cache_key: &CacheKey,
+        item: &CacheEntry<T::Item>,
+    ) -> Option<Arc<OpenFileGuard>> {
+        if item.is_err() || self.config.cache_dir().is_none() {
+            return None;
+        }
+
+        let cache = self.cache.clone();
+        let cache_key = cache_key.clone();
+        let close = Box::new(move || {
+            tokio::spawn(async move { cache.invalidate(&cache_key).await });
+        });
+        Some(Arc::new(self.config.open_files.open(close)))
+    }
+
+    /// Recomputes an item in a deduplicated background task, and replaces the in-memory item.
+    ///
+    /// Lazy refreshes and revalidations are limited by separate budgets. If the budget of the
+    /// `refresh` is exhausted, nothing is spawned.
+    fn spawn_refresh(&self, cache_key: CacheKey, request: T, refresh: Refresh) {
         let name = self.config.name();
 
         let mut refreshes = self.refreshes.lock().unwrap();
@@ -378,19 +663,29 @@ impl<T: CacheItemRequest> Cacher<T> {
         }
 
         // We count down towards zero, and if we reach or surpass it, we will stop here.
-        let max_lazy_refreshes = self.config.max_lazy_refreshes();
-        if max_lazy_refreshes.fetch_sub(1, Ordering::Relaxed) <= 0 {
-            max_lazy_refreshes.fetch_add(1, Ordering::Relaxed);
-
-            metric!(counter("caches.lazy_limit_hit") += 1, "cache" => name.as_ref());
+        let budget = match refresh {
+            Refresh::Lazy => self.config.max_lazy_refreshes(),
+            Refresh::Revalidation => self.config.max_revalidations(),
+        };
+        if budget.fetch_sub(1, Ordering::Relaxed) <= 0 {
+            budget.fetch_add(1, Ordering::Relaxed);
+
+            let limit_hit = match refresh {
+                Refresh::Lazy => "caches.lazy_limit_hit",
+                Refresh::Revalidation => "caches.revalidation.limit_hit",
+            };
+            metric!(counter(limit_hit) += 1, "cache" => name.as_ref());
             return;
         }
+        if refresh == Refresh::Revalidation {
+            metric!(counter("caches.revalidation") += 1, "cache" => name.as_ref());
+        }
 
         let done_token = {
             let key = cache_key.clone();
             let refreshes = Arc::clone(&self.refreshes);
             CallOnDrop::new(move || {
-                max_lazy_refreshes.fetch_add(1, Ordering::Relaxed);
+                budget.fetch_add(1, Ordering::Relaxed);
                 refreshes.lock().unwrap().remove(&key);
             })
         };
@@ -421,7 +716,8 @@ impl<T: CacheItemRequest> Cacher<T> {
 
             // we just created a fresh cache, so use the initial expiration times
             let expiration = ExpirationTime::for_fresh_status(&this.config, &item);
-            let value = (expiration.as_instant(), item);
+            let open_file = this.open_file(&cache_key, &item);
+            let value = (expiration.as_instant(), item, open_file);
 
             // refresh the memory cache with the newly refreshed result
             this.cache.insert(cache_key, value).await;
@@ -440,12 +736,14 @@ fn lookup_local_cache(
     config: &Cache,
     shared_cache: Option<&SharedCacheService>,
     cache_dir: &Path,
-    cache_key: &str,
+    key: &CacheKey,
+    version: u32,
     is_current_version: bool,
-) -> CacheEntry<(Instant, CacheEntry<ByteView<'static>>)> {
+) -> CacheEntry<(ExpirationTime, CacheEntry<ByteView<'static>>)> {
     let name = config.name();
+    let cache_key = key.cache_path(version);
 
-    let item_path = cache_dir.join(cache_key);
+    let item_path = cache_dir.join(config.item_path(key, version));
     tracing::trace!("Trying {} cache at path {}", name, item_path.display());
     let _scope = Hub::current().push_scope();
     sentry::configure_scope(|scope| {
@@ -467,7 +765,12 @@ fn lookup_local_cache(
     if is_current_version && needs_reupload {
         if let Ok(byteview) = &entry {
             if let Some(shared_cache) = shared_cache {
-                shared_cache.store(name, cache_key, byteview.clone(), CacheStoreReason::Refresh);
+                shared_cache.store(
+                    name,
+                    &cache_key,
+                    byteview.clone(),
+                    CacheStoreReason::Refresh,
+                );
             }
         }
     }
@@ -485,10 +788,23 @@ fn lookup_local_cache(
 
     tracing::trace!("Loading {} at path {}", name, item_path.display());
 
-    Ok((expiration.as_instant(), entry))
+    Ok((expiration, entry))
+}
+
+/// Records the outcome of looking up the item at `key` in the in-memory or file-system layers.
+///
+/// Outcomes are `hit`, `negative_hit` for cached errors, `miss`, and `recomputation` for the
+/// computations of lazy refreshes.
+fn record_lookup(name: CacheName, key: &CacheKey, outcome: &str) {
+    metric!(
+        counter("caches.lookup") += 1,
+        "cache" => name.as_ref(),
+        "source" => key.source_metric_key(),
+        "outcome" => outcome,
+    );
 }
 
-fn persist_tempfile(
+pub(super) fn persist_tempfile(
     mut temp_file: NamedTempFile,
     cache_path: &Path,
 ) -> std::io::Result<std::fs::File> {
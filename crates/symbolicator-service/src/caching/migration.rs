@@ -0,0 +1,146 @@
+//! Upgrades of cache files to newer cache versions.
+//!
+//! Bumping the version of a cache throws away all of its files, which are then recomputed. For
+//! format changes which can be applied to existing files, the [`CacheVersions`] list
+//! [`CacheMigration`]s instead. Files found in a fallback version are then upgraded when they are
+//! read, instead of being recomputed in the background. The `symbolicator cache migrate` command
+//! upgrades all files of a cache ahead of time.
+//!
+//! Migrations upgrade files from one version to the next, like `7` to `8`. Files of older versions
+//! are upgraded by chaining migrations, and are recomputed as usual if any migration is missing.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+
+use anyhow::Result;
+use filetime::FileTime;
+use symbolic::common::ByteView;
+
+use super::compression;
+use super::fs::catch_not_found;
+use super::memory::persist_tempfile;
+use super::{Cache, CacheVersions};
+
+/// An upgrade of the files of a cache from one version to another.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheMigration {
+    /// The version of the upgraded files.
+    pub from: u32,
+    /// The version the files are upgraded to.
+    pub to: u32,
+    /// Writes the upgraded contents of an uncompressed file.
+    pub upgrade: fn(&[u8], &mut dyn Write) -> io::Result<()>,
+}
+
+/// Returns the migrations which upgrade files of version `from` to the current version.
+///
+/// Returns `None` if any migration is missing, or if `from` is the current version.
+fn migration_path(versions: &CacheVersions, from: u32) -> Option<Vec<&'static CacheMigration>> {
+    let mut migrations = Vec::new();
+    let mut version = from;
+    while version != versions.current {
+        // Guards against cycles in misconfigured migrations.
+        if migrations.len() >= versions.migrations.len() {
+            return None;
+        }
+        let migration = versions.migrations.iter().find(|m| m.from == version)?;
+        migrations.push(migration);
+        version = migration.to;
+    }
+    (!migrations.is_empty()).then_some(migrations)
+}
+
+impl Cache {
+    /// Upgrades the uncompressed `data` of a file of version `from`, and writes it to `path`.
+    ///
+    /// The upgraded file is written like a freshly computed one, so it is compressed and
+    /// deduplicated if this cache is configured to. Returns the uncompressed upgraded contents, or
+    /// `None` if there are no migrations from version `from`.
+    pub(super) fn write_upgraded(
+        &self,
+        versions: &CacheVersions,
+        from: u32,
+        data: &[u8],
+        path: &Path,
+    ) -> io::Result<Option<ByteView<'static>>> {
+        let Some(migrations) = migration_path(versions, from) else {
+            return Ok(None);
+        };
+
+        let mut data = Cow::Borrowed(data);
+        for migration in migrations {
+            let mut upgraded = Vec::new();
+            (migration.upgrade)(&data, &mut upgraded)?;
+            data = Cow::Owned(upgraded);
+        }
+
+        let mut temp_file = self.tempfile()?;
+        temp_file.write_all(&data)?;
+        let upgraded = ByteView::map_file_ref(temp_file.as_file())?;
+
+        if self.compress() {
+            let mut compressed = self.tempfile()?;
+            compression::compress(&upgraded, compressed.as_file_mut())?;
+            temp_file = compressed;
+        }
+        if self.is_deduplicated() {
+            temp_file = self.store_content(temp_file, &upgraded)?;
+        }
+        let file = persist_tempfile(temp_file, path)?;
+        if let Ok(metadata) = file.metadata() {
+            self.record_write(path, metadata.len());
+        }
+
+        metric!(
+            counter("caches.migration") += 1,
+            "cache" => self.name.as_ref(),
+            "from" => &from.to_string(),
+        );
+        Ok(Some(upgraded))
+    }
+
+    /// Upgrades the good files of fallback versions of this cache to the current version.
+    ///
+    /// Files which already exist in the current version are skipped, as well as files without
+    /// migrations to the current version. The upgraded files keep the modification time of the
+    /// original files, and thus their expiration. Returns the number of upgraded files.
+    pub fn migrate(&self, versions: &CacheVersions) -> Result<usize> {
+        let cache_dir = self.cache_dir_or_err()?;
+
+        let mut migrated = 0;
+        for file in self.list_files()? {
+            if file.error.is_some() || migration_path(versions, file.version).is_none() {
+                continue;
+            }
+
+            // The path within the version directory stays the same.
+            let mut components = file.path.components();
+            if !matches!(components.next(), Some(Component::Normal(_))) {
+                continue;
+            }
+            let target = cache_dir
+                .join(format!("v{}", versions.current))
+                .join(components.as_path());
+            if target.exists() {
+                continue;
+            }
+
+            // Files may be removed by a cleanup at any time.
+            let path = cache_dir.join(&file.path);
+            let upgrade = || {
+                let metadata = path.metadata()?;
+                let data = ByteView::open(&path)?;
+                let data = self.decompress(self.resolve_content(data)?)?;
+                self.write_upgraded(versions, file.version, &data, &target)?;
+                filetime::set_file_mtime(&target, FileTime::from_last_modification_time(&metadata))
+            };
+            if catch_not_found(upgrade)?.is_some() {
+                migrated += 1;
+            }
+        }
+
+        tracing::info!("Migrated {migrated} files of `{}` cache", self.name);
+        Ok(migrated)
+    }
+}
@@ -34,9 +34,13 @@
 //!
 //! - `caches.access`: All accesses.
 //! - `caches.memory.hit`: Accesses served by the in-memory layer.
+//! - `caches.memory.miss`: Accesses not served by the in-memory layer.
 //! - `caches.file.hit`: Accesses served by the file-system layer.
 //! - `services.shared_cache.fetch(hit:true)`: Accesses served by the shared-cache layer.
 //! - `caches.computation`: Actual computations being run, and not served by any of the caching layers.
+//! - `caches.lookup`: Lookups in the in-memory and file-system layers, additionally tagged with the
+//!   `source` the item traces back to, which is the type of custom sources, and their `outcome`:
+//!   `hit`, `negative_hit` for cached errors, `miss`, and `recomputation` for lazy refreshes.
 //!
 //! NOTE: The sum of shared-cache hits and computations can exceed the number of cache misses of
 //! previous layers in case of lazy cache recomputation.
@@ -45,6 +49,18 @@
 //! - `caches.file.size`: A histogram for the size (in bytes) of the successfully loaded / written cache files.
 //! - `caches.file.write`: The number of caches being written to disk.
 //!   This should match `caches.computation` if the file-system layer is enabled.
+//! - `caches.memory.entries` and `caches.memory.size`: Gauges for the number of items and their
+//!   total weight in the in-memory layer, reported on misses.
+//! - `caches.lease.wait`: The time spent waiting on the computation of another Symbolicator,
+//!   tagged with the `outcome` of the wait.
+//! - `caches.scope.size` and `caches.scope.files_evicted`: The total size of the files of a scope
+//!   in caches with scope quotas, tagged with the `scope`, and the files evicted for exceeding it.
+//! - `caches.revalidation` and `caches.revalidation.limit_hit`: Revalidations of negative entries
+//!   which have been spawned, or skipped because too many are running already.
+//! - `caches.files.open` and `caches.files.closed`: A gauge for the number of files held open by
+//!   the in-memory caches, and the files closed for exceeding `max_open_files`.
+//! - `caches.size.bytes_reclaimed`: The bytes removed by `symbolicator cleanup`, tagged with the
+//!   `kind` of the removed files: `positive`, `negative`, `malformed`, or `content`.
 //! - TODO: list all the other metrics that are missing here :-)
 //!
 //! ### Configuration
@@ -61,6 +77,11 @@
 //! re-downloads and re-computation. The limit applies to the whole category at once.
 //! See the section on [`CacheVersions`] for more details.
 //!
+//! With a `revalidation.window`, negative entries which are used within that window of their
+//! expiration are recomputed in the background, limited to `revalidation.max_concurrent`
+//! revalidations of each cache. That way, the request which would otherwise find the entry
+//! expired does not have to wait for its recomputation.
+//!
 //! The `retry_X_after` options specify a time-to-live after which the cache expires and will be
 //! re-computed. The `max_unused_for` option is rather a time-to-idle value, after which the item
 //! will be evicted. File-system `mtime` is used to check for these. Cache items that are in use
@@ -71,6 +92,16 @@
 //! The "derived" category will keep entries alive for up to 7 days, and will also retry "missing"
 //! entries every hour, and "malformed" entries every 24 hours.
 //!
+//! Additionally, every category can be given a `max_size` in bytes, which applies to each of its
+//! caches separately. Such caches keep track of the size and last use of their files, and evict
+//! the least recently used ones once the total size exceeds the limit. See the `eviction` module
+//! for details.
+//!
+//! Caches listed in `partitions.caches` store their files partitioned by the scope of their cache
+//! key. With `partitions.max_scope_size`, or quotas for individual scopes, the least recently used
+//! files of a scope are evicted once they exceed its quota, so a single scope can not evict the
+//! files of all others.
+//!
 //! A "successful" entry is considered immutable and it will be reused indefinitely as long as it
 //! is being actively used.
 //!
@@ -123,6 +154,10 @@
 //! This procedure ensures that when we update a cache's format to a new version, we don't immediately throw away
 //! all old cache entries if they're still usable, but rather migrate to the new version over time.
 //!
+//! If the format change can be applied to existing files, [`CacheVersions::migrations`] can upgrade
+//! fallback entries to the current version when they are read, instead of recomputing them. See the
+//! `migration` module for details.
+//!
 //! The number of simultaneous redownloads/recomputations of outdated cache items can be configured via the options
 //! `max_lazy_redownloads` (default: 50) for "downloaded" caches and `max_lazy_recomputations` (default: 20) for
 //! "derived" caches, respectively.
@@ -156,23 +191,37 @@ use std::sync::Arc;
 
 use crate::config::Config;
 
+mod bundle;
 mod cache_error;
 mod cache_key;
 mod cleanup;
+mod compression;
 mod config;
+mod content;
+mod eviction;
 mod fs;
+mod inspect;
 mod memory;
+mod migration;
+mod open_files;
 mod shared_cache;
 #[cfg(test)]
 mod tests;
 
+pub use bundle::{export_bundle, import_bundle, BundleFilter, BundleImport};
 pub use cache_error::{CacheEntry, CacheError};
 pub use cache_key::{CacheKey, CacheKeyBuilder};
 pub use cleanup::cleanup;
 pub use config::CacheName;
 pub use fs::{Cache, ExpirationStrategy, ExpirationTime};
+pub use inspect::{CacheFileDetails, CacheFileInfo};
 pub use memory::{CacheItemRequest, CacheVersions, Cacher};
-pub use shared_cache::{CacheStoreReason, SharedCacheConfig, SharedCacheRef, SharedCacheService};
+pub use migration::CacheMigration;
+pub use open_files::OpenFiles;
+pub use shared_cache::{
+    CacheStoreReason, LeaseStatus, SharedCacheConfig, SharedCacheRef, SharedCacheService,
+    SharedCacheStorePolicy,
+};
 
 pub struct Caches {
     /// Caches for object files.
@@ -189,6 +238,8 @@ pub struct Caches {
     pub cficaches: Cache,
     /// PortablePDB files.
     pub ppdb_caches: Cache,
+    /// Breakpad symbol files generated from object files.
+    pub breakpad: Cache,
     /// `SourceMapCache` files.
     pub sourcemap_caches: Cache,
     /// Source files.
@@ -219,7 +270,7 @@ impl Caches {
         let default_cap = 100 * 1024;
         let in_memory = &config.caches.in_memory;
 
-        Ok(Self {
+        let mut caches = Self {
             objects: Cache::from_config(
                 CacheName::Objects,
                 config,
@@ -253,7 +304,7 @@ impl Caches {
                 config,
                 config.caches.derived.into(),
                 max_lazy_recomputations.clone(),
-                default_cap,
+                in_memory.symcaches_capacity,
             )?,
             cficaches: Cache::from_config(
                 CacheName::Cficaches,
@@ -269,6 +320,13 @@ impl Caches {
                 max_lazy_recomputations.clone(),
                 default_cap,
             )?,
+            breakpad: Cache::from_config(
+                CacheName::Breakpad,
+                config,
+                config.caches.derived.into(),
+                max_lazy_recomputations.clone(),
+                default_cap,
+            )?,
             sourcemap_caches: Cache::from_config(
                 CacheName::SourceMapCaches,
                 config,
@@ -297,6 +355,47 @@ impl Caches {
                 max_lazy_redownloads,
                 default_cap,
             )?,
-        })
+        };
+
+        // Open files are limited for the whole process, so all caches share one budget.
+        let open_files = Arc::new(OpenFiles::new(config.caches.max_open_files));
+        for cache in caches.all_mut() {
+            cache.open_files = Arc::clone(&open_files);
+        }
+
+        Ok(caches)
+    }
+
+    fn all_mut(&mut self) -> [&mut Cache; 12] {
+        // Destructure so we do not accidentally forget one of our members.
+        let Self {
+            objects,
+            object_meta,
+            auxdifs,
+            il2cpp,
+            symcaches,
+            cficaches,
+            ppdb_caches,
+            breakpad,
+            sourcemap_caches,
+            sourcefiles,
+            diagnostics,
+            proguard,
+        } = self;
+
+        [
+            objects,
+            object_meta,
+            auxdifs,
+            il2cpp,
+            symcaches,
+            cficaches,
+            ppdb_caches,
+            breakpad,
+            sourcemap_caches,
+            sourcefiles,
+            diagnostics,
+            proguard,
+        ]
     }
 }
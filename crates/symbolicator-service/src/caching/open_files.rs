@@ -0,0 +1,208 @@
+//! A budget for the cache files held open by the in-memory caches.
+//!
+//! Items loaded from cache files keep them mapped into memory, and every mapping uses up a file
+//! handle for as long as the in-memory cache holds the item. With thousands of cached items, this
+//! can exceed the limit of open files of the process. All caches thus share a budget of open
+//! files, and once it is used up, the least recently used items are dropped from their in-memory
+//! cache, which closes their files. They are loaded from the file system again when needed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Closes an open file by dropping the item holding it from its in-memory cache.
+pub(super) type CloseFile = Box<dyn FnOnce() + Send>;
+
+/// The budget of open cache files shared by all caches.
+pub struct OpenFiles {
+    /// The maximum number of open files, or `None` if unlimited.
+    limit: Option<usize>,
+    state: Mutex<OpenFilesState>,
+}
+
+#[derive(Default)]
+struct OpenFilesState {
+    /// Increases with every use of a file, to order files by their last use.
+    tick: u64,
+    /// The id of the next opened file.
+    next_id: u64,
+    /// The open files by their id.
+    files: HashMap<u64, OpenFile>,
+    /// The ids of the open files by their last use.
+    lru: BTreeMap<u64, u64>,
+}
+
+struct OpenFile {
+    last_used: u64,
+    close: CloseFile,
+}
+
+impl std::fmt::Debug for OpenFiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenFiles")
+            .field("limit", &self.limit)
+            .field("open", &self.len())
+            .finish()
+    }
+}
+
+impl OpenFiles {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            state: Default::default(),
+        }
+    }
+
+    /// The number of currently open files.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().files.len()
+    }
+
+    /// Whether no files are open.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records a newly opened file, which is closed by calling `close`.
+    ///
+    /// If this exceeds the budget, the least recently used files are closed. The returned guard
+    /// releases the file from the budget once it is dropped.
+    pub(super) fn open(self: &Arc<Self>, close: CloseFile) -> OpenFileGuard {
+        let (id, closed) = {
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+            let id = state.next_id;
+            state.next_id += 1;
+            state.tick += 1;
+            state.lru.insert(state.tick, id);
+            state.files.insert(
+                id,
+                OpenFile {
+                    last_used: state.tick,
+                    close,
+                },
+            );
+
+            let mut closed = Vec::new();
+            let limit = self.limit.unwrap_or(usize::MAX);
+            while state.files.len() > limit {
+                let Some((_, lru_id)) = state.lru.pop_first() else {
+                    break;
+                };
+                if let Some(file) = state.files.remove(&lru_id) {
+                    closed.push(file.close);
+                }
+            }
+
+            metric!(gauge("caches.files.open") = state.files.len() as u64);
+            (id, closed)
+        };
+
+        // The files are closed outside of the lock, as dropping them releases them again.
+        if !closed.is_empty() {
+            metric!(counter("caches.files.closed") += closed.len() as i64);
+        }
+        for close in closed {
+            close();
+        }
+
+        OpenFileGuard {
+            open_files: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// Marks the file with the given id as used.
+    fn touch(&self, id: u64) {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        state.tick += 1;
+        if let Some(file) = state.files.get_mut(&id) {
+            state.lru.remove(&file.last_used);
+            file.last_used = state.tick;
+            state.lru.insert(state.tick, id);
+        }
+    }
+
+    /// Removes a closed file from the budget.
+    fn release(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(file) = state.files.remove(&id) {
+            state.lru.remove(&file.last_used);
+            metric!(gauge("caches.files.open") = state.files.len() as u64);
+        }
+    }
+}
+
+/// An open cache file, which is released from the [`OpenFiles`] budget when dropped.
+pub(super) struct OpenFileGuard {
+    open_files: Arc<OpenFiles>,
+    id: u64,
+}
+
+impl OpenFileGuard {
+    /// Marks the file as used, so it is closed after files which have not been used since.
+    pub(super) fn touch(&self) {
+        self.open_files.touch(self.id);
+    }
+}
+
+impl Drop for OpenFileGuard {
+    fn drop(&mut self) {
+        self.open_files.release(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_open_files_budget() {
+        let open_files = Arc::new(OpenFiles::new(Some(2)));
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let close = |name: &'static str| -> CloseFile {
+            let closed = Arc::clone(&closed);
+            Box::new(move || closed.lock().unwrap().push(name))
+        };
+
+        let first = open_files.open(close("first"));
+        let second = open_files.open(close("second"));
+        assert_eq!(open_files.len(), 2);
+
+        // The first file was used more recently, so the second one is closed.
+        first.touch();
+        let third = open_files.open(close("third"));
+        assert_eq!(*closed.lock().unwrap(), ["second"]);
+        assert_eq!(open_files.len(), 2);
+
+        // Closed files are no longer counted when their guard is dropped.
+        drop(second);
+        assert_eq!(open_files.len(), 2);
+        drop(first);
+        assert_eq!(open_files.len(), 1);
+        drop(third);
+        assert!(open_files.is_empty());
+    }
+
+    #[test]
+    fn test_open_files_unlimited() {
+        let open_files = Arc::new(OpenFiles::new(None));
+        let closed = Arc::new(AtomicUsize::new(0));
+        let guards: Vec<_> = (0..100)
+            .map(|_| {
+                let closed = Arc::clone(&closed);
+                open_files.open(Box::new(move || {
+                    closed.fetch_add(1, Ordering::Relaxed);
+                }))
+            })
+            .collect();
+
+        assert_eq!(open_files.len(), 100);
+        assert_eq!(closed.load(Ordering::Relaxed), 0);
+        drop(guards);
+        assert!(open_files.is_empty());
+    }
+}
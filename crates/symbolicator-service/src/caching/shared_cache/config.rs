@@ -1,6 +1,11 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::caching::CacheName;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilesystemSharedCacheConfig {
@@ -22,12 +27,91 @@ pub struct GcsSharedCacheConfig {
     pub service_account_path: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisSharedCacheConfig {
+    /// URLs of the Redis servers, like `redis://127.0.0.1:6379`.
+    ///
+    /// With more than one URL, or if [`cluster`](Self::cluster) is set, the servers are used as
+    /// the initial nodes of a Redis Cluster.
+    pub urls: Vec<String>,
+
+    /// Whether the servers form a Redis Cluster.
+    #[serde(default)]
+    pub cluster: bool,
+
+    /// The maximum size in bytes of items stored in Redis.
+    ///
+    /// Larger items, which are mostly objects, are never stored. This keeps the shared cache to
+    /// small derived caches like symcaches and cficaches.
+    #[serde(default = "default_redis_max_item_size")]
+    pub max_item_size: u64,
+
+    /// The duration after which items expire from Redis.
+    #[serde(default = "default_redis_ttl", with = "humantime_serde")]
+    pub ttl: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AzureSharedCacheConfig {
+    /// Name of the storage account.
+    pub account: String,
+
+    /// Name of the container within the storage account.
+    pub container: String,
+
+    /// The blob service endpoint of the storage account.
+    ///
+    /// Defaults to `https://<account>.blob.core.windows.net/`.
+    #[serde(default)]
+    pub endpoint: Option<Url>,
+
+    /// Optional client ID of a user-assigned managed identity.
+    ///
+    /// If this is not provided the system-assigned managed identity is used. The identity needs
+    /// the "Storage Blob Data Contributor" role on the container.
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+impl AzureSharedCacheConfig {
+    /// Returns the URL of the blob with the given key.
+    pub fn blob_url(&self, key: &str) -> anyhow::Result<Url> {
+        let mut url = match self.endpoint {
+            Some(ref endpoint) => endpoint.clone(),
+            None => Url::parse(&format!("https://{}.blob.core.windows.net/", self.account))?,
+        };
+        url.path_segments_mut()
+            .map_err(|_| anyhow::Error::msg("URL cannot-be-a-base"))?
+            .pop_if_empty()
+            .push(&self.container)
+            .extend(key.split('/'));
+        Ok(url)
+    }
+}
+
 /// The backend to use for the shared cache.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SharedCacheBackendConfig {
     Gcs(GcsSharedCacheConfig),
+    Azure(AzureSharedCacheConfig),
     Filesystem(FilesystemSharedCacheConfig),
+    Redis(RedisSharedCacheConfig),
+}
+
+/// How the items of a cache are stored in the shared cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SharedCacheStorePolicy {
+    /// Items are uploaded in the background, through the bounded upload queue.
+    ///
+    /// Uploads are dropped if the queue is full.
+    #[default]
+    WriteBack,
+    /// Items are uploaded right away, and requests computing them wait for the upload.
+    WriteThrough,
+    /// Items are never stored in the shared cache, though they are still fetched from it.
+    Disabled,
 }
 
 /// A remote cache that can be shared between symbolicator instances.
@@ -54,6 +138,21 @@ pub struct SharedCacheConfig {
     #[serde(default = "default_max_upload_queue_size")]
     pub max_upload_queue_size: usize,
 
+    /// The duration of leases on computing cache items, disabled by default.
+    ///
+    /// With leases, a symbolicator missing an item like a symcache in the shared cache takes a
+    /// lease on computing it. Other symbolicators missing the same item wait for it to appear in
+    /// the shared cache instead of computing it as well. Leases expire after this duration, and
+    /// symbolicators stop waiting after it, so a crashed lease holder does not block anyone.
+    #[serde(default, with = "humantime_serde")]
+    pub lease_timeout: Option<Duration>,
+
+    /// How the items of each cache are stored, like `objects: disabled`.
+    ///
+    /// Caches which are not listed use [`SharedCacheStorePolicy::WriteBack`].
+    #[serde(default)]
+    pub store_policies: HashMap<CacheName, SharedCacheStorePolicy>,
+
     /// The backend to use for the shared cache.
     #[serde(flatten)]
     pub backend: SharedCacheBackendConfig,
@@ -66,3 +165,11 @@ fn default_max_upload_queue_size() -> usize {
 fn default_max_concurrent_uploads() -> usize {
     20
 }
+
+fn default_redis_max_item_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_redis_ttl() -> Duration {
+    Duration::from_secs(3600 * 24 * 7)
+}
@@ -5,10 +5,10 @@
 //! lives closer to Symbolicator. Expensive computations related to the computation of derived
 //! caches may also be saved via this shared cache.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Context as _, Error, Result};
 use futures::{Future, TryStreamExt};
@@ -16,6 +16,7 @@ use gcp_auth::{CustomServiceAccount, Token, TokenProvider};
 use reqwest::{Body, Client, StatusCode};
 use sentry::protocol::Context;
 use sentry::{Hub, SentryFutureExt};
+use serde::Deserialize;
 use symbolic::common::ByteView;
 use tempfile::NamedTempFile;
 use tokio::fs::{self, File};
@@ -32,13 +33,25 @@ use super::CacheName;
 
 pub mod config;
 
-pub use config::SharedCacheConfig;
-use config::{FilesystemSharedCacheConfig, GcsSharedCacheConfig, SharedCacheBackendConfig};
+use config::{
+    AzureSharedCacheConfig, FilesystemSharedCacheConfig, GcsSharedCacheConfig,
+    RedisSharedCacheConfig, SharedCacheBackendConfig,
+};
+pub use config::{SharedCacheConfig, SharedCacheStorePolicy};
 
 // TODO: get timeouts from global config?
 const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
 const STORE_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// The version of the Blob service REST API, which is required for OAuth authentication.
+const AZURE_API_VERSION: &str = "2021-08-06";
+
+/// The endpoint of the Azure Instance Metadata Service handing out managed identity tokens.
+const AZURE_IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// Azure tokens are refreshed this long before they expire.
+const AZURE_TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(5 * 60);
+
 /// Errors using the cache backend.
 ///
 /// This exists since some special cache errors should not be logged since they are
@@ -255,6 +268,31 @@ impl GcsState {
         ret
     }
 
+    /// Deletes an object from the bucket, returning whether it existed.
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let token = self.get_token().await?;
+        let url =
+            gcs::object_url(&self.config.bucket, key).context("failed to build object url")?;
+        let request = self.client.delete(url).bearer_auth(token.as_str()).send();
+        let request = tokio::time::timeout(CONNECT_TIMEOUT, request);
+
+        match request.await {
+            Ok(Ok(response)) => match response.status() {
+                status if status.is_success() => Ok(true),
+                StatusCode::NOT_FOUND => Ok(false),
+                StatusCode::FORBIDDEN => Err(anyhow!(
+                    "Insufficient permissions for bucket {}",
+                    self.config.bucket
+                )
+                .into()),
+                StatusCode::UNAUTHORIZED => Err(anyhow!("Invalid credentials").into()),
+                status => Err(anyhow!("Error response from GCS: {}", status).into()),
+            },
+            Ok(Err(err)) => Err(err).context("Error connecting to GCS")?,
+            Err(_) => Err(CacheError::ConnectTimeout),
+        }
+    }
+
     /// Stores a file on GCS.
     ///
     /// Because we use a very dumb API to upload files we always upload the data over the
@@ -346,6 +384,339 @@ impl GcsState {
     }
 }
 
+/// An OAuth token of an Azure managed identity.
+#[derive(Debug, Clone)]
+struct AzureToken {
+    bearer_token: Arc<str>,
+    expires_at: Instant,
+}
+
+/// The token response of the managed identity endpoints.
+#[derive(Deserialize)]
+struct AzureTokenResponse {
+    access_token: String,
+    /// The Instance Metadata Service returns this as a string.
+    #[serde(deserialize_with = "deserialize_expires_in")]
+    expires_in: u64,
+}
+
+fn deserialize_expires_in<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ExpiresIn {
+        Number(u64),
+        String(String),
+    }
+
+    match ExpiresIn::deserialize(deserializer)? {
+        ExpiresIn::Number(secs) => Ok(secs),
+        ExpiresIn::String(secs) => secs.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Debug)]
+struct AzureState {
+    config: AzureSharedCacheConfig,
+    client: Client,
+    token: tokio::sync::Mutex<Option<AzureToken>>,
+}
+
+impl AzureState {
+    pub fn new(config: AzureSharedCacheConfig) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+            token: Default::default(),
+        }
+    }
+
+    /// Returns the bearer token of the managed identity, refreshing it if needed.
+    async fn get_token(&self) -> Result<Arc<str>> {
+        let mut token = self.token.lock().await;
+        match token.as_ref() {
+            Some(token) if token.expires_at > Instant::now() => Ok(token.bearer_token.clone()),
+            _ => {
+                let new_token = self.request_token().await?;
+                let bearer_token = new_token.bearer_token.clone();
+                *token = Some(new_token);
+                Ok(bearer_token)
+            }
+        }
+    }
+
+    /// Requests a new token of the managed identity.
+    ///
+    /// App Service and Container Apps expose the token endpoint through the `IDENTITY_ENDPOINT`
+    /// and `IDENTITY_HEADER` variables, virtual machines and AKS use the Instance Metadata Service.
+    async fn request_token(&self) -> Result<AzureToken> {
+        let identity_endpoint = std::env::var("IDENTITY_ENDPOINT");
+        let identity_header = std::env::var("IDENTITY_HEADER");
+        let mut request = match (identity_endpoint, identity_header) {
+            (Ok(endpoint), Ok(header)) => self
+                .client
+                .get(endpoint)
+                .query(&[("api-version", "2019-08-01")])
+                .header("x-identity-header", header),
+            _ => self
+                .client
+                .get(AZURE_IMDS_TOKEN_URL)
+                .query(&[("api-version", "2018-02-01")])
+                .header("metadata", "true"),
+        };
+        request = request.query(&[("resource", "https://storage.azure.com/")]);
+        if let Some(ref client_id) = self.config.client_id {
+            request = request.query(&[("client_id", client_id)]);
+        }
+
+        let requested_at = Instant::now();
+        let response = tokio::time::timeout(Duration::from_secs(5), request.send())
+            .await
+            .map_err(|_| Error::msg("Timeout requesting Azure managed identity token"))?
+            .context("Failed to request Azure managed identity token")?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Error response from Azure managed identity endpoint: {}",
+                status
+            ));
+        }
+        let token: AzureTokenResponse = response
+            .json()
+            .await
+            .context("Invalid Azure managed identity token")?;
+
+        let lifetime =
+            Duration::from_secs(token.expires_in).saturating_sub(AZURE_TOKEN_EXPIRY_MARGIN);
+        Ok(AzureToken {
+            bearer_token: format!("Bearer {}", token.access_token).into(),
+            expires_at: requested_at + lifetime,
+        })
+    }
+
+    /// Fetches item from shared cache if available and copies them to the writer.
+    ///
+    /// # Returns
+    ///
+    /// If successful the number of bytes written to the writer are returned.
+    async fn fetch<W>(&self, key: &str, writer: &mut W) -> Result<Option<u64>, CacheError>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        sentry::configure_scope(|scope| {
+            let mut map = BTreeMap::new();
+            map.insert(
+                "container".to_string(),
+                self.config.container.clone().into(),
+            );
+            map.insert("key".to_string(), key.into());
+            scope.set_context("Azure Shared Cache", Context::Other(map));
+        });
+        let token = self.get_token().await?;
+        let url = self
+            .config
+            .blob_url(key)
+            .context("URL construction failed")?;
+        let request = self
+            .client
+            .get(url)
+            .header("authorization", token.as_ref())
+            .header("x-ms-version", AZURE_API_VERSION)
+            .send();
+        let request = tokio::time::timeout(CONNECT_TIMEOUT, request);
+        let request =
+            measure_download_time("services.shared_cache.fetch.connect", "azure", request);
+
+        let response = request
+            .await
+            .map_err(|_| CacheError::ConnectTimeout)?
+            .map_err(|err| {
+                tracing::trace!("Error in shared_cache Azure response for {}", key);
+                Error::new(err).context("Bad Azure response for shared_cache")
+            })?;
+
+        let status = response.status();
+        match status {
+            _ if status.is_success() => {
+                tracing::trace!("Success hitting shared_cache Azure {}", key);
+                let stream = response
+                    .bytes_stream()
+                    .map_err(std::io::Error::other);
+                let mut stream = StreamReader::new(stream);
+
+                let future = tokio::time::timeout(STORE_TIMEOUT, io::copy(&mut stream, writer));
+                let res = future
+                    .await
+                    .map_err(|_| CacheError::Timeout)?
+                    .context("IO Error streaming HTTP bytes to writer")
+                    .map_err(CacheError::Other);
+
+                Some(res).transpose()
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::FORBIDDEN => Err(anyhow!(
+                "Insufficient permissions for container {}",
+                self.config.container
+            )
+            .into()),
+            StatusCode::UNAUTHORIZED => Err(anyhow!("Invalid credentials").into()),
+            _ => Err(anyhow!("Error response from Azure: {}", status).into()),
+        }
+    }
+
+    async fn exists(&self, cache: CacheName, key: &str) -> Result<bool, CacheError> {
+        let token = self.get_token().await?;
+        let url = self
+            .config
+            .blob_url(key)
+            .context("failed to build blob url")?;
+        let request = self
+            .client
+            .head(url)
+            .header("authorization", token.as_ref())
+            .header("x-ms-version", AZURE_API_VERSION)
+            .send();
+        let request = tokio::time::timeout(CONNECT_TIMEOUT, request);
+
+        let ret = match request.await {
+            Ok(Ok(response)) => match response.status() {
+                StatusCode::OK => Ok(true),
+                StatusCode::NOT_FOUND => Ok(false),
+                status => Err(anyhow!("Unexpected status code from Azure: {}", status).into()),
+            },
+            Ok(Err(err)) => Err(err).context("Error connecting to Azure")?,
+            Err(_) => Err(CacheError::ConnectTimeout),
+        };
+        let status = match ret {
+            Ok(_) => "ok",
+            Err(CacheError::ConnectTimeout) => "connect-timeout",
+            Err(_) => "error",
+        };
+        metric!(
+            counter("services.shared_cache.exists") += 1,
+            "cache" => cache.as_ref(),
+            "status" => status
+        );
+        ret
+    }
+
+    /// Deletes a blob from the container, returning whether it existed.
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let token = self.get_token().await?;
+        let url = self
+            .config
+            .blob_url(key)
+            .context("failed to build blob url")?;
+        let request = self
+            .client
+            .delete(url)
+            .header("authorization", token.as_ref())
+            .header("x-ms-version", AZURE_API_VERSION)
+            .send();
+        let request = tokio::time::timeout(CONNECT_TIMEOUT, request);
+
+        match request.await {
+            Ok(Ok(response)) => match response.status() {
+                status if status.is_success() => Ok(true),
+                StatusCode::NOT_FOUND => Ok(false),
+                StatusCode::FORBIDDEN => Err(anyhow!(
+                    "Insufficient permissions for container {}",
+                    self.config.container
+                )
+                .into()),
+                StatusCode::UNAUTHORIZED => Err(anyhow!("Invalid credentials").into()),
+                status => Err(anyhow!("Error response from Azure: {}", status).into()),
+            },
+            Ok(Err(err)) => Err(err).context("Error connecting to Azure")?,
+            Err(_) => Err(CacheError::ConnectTimeout),
+        }
+    }
+
+    /// Stores a blob in the container, unless it already exists.
+    ///
+    /// Like for GCS, a [`CacheStoreReason::Refresh`] first checks whether the blob exists to avoid
+    /// uploading the data for no reason.
+    async fn store(
+        &self,
+        cache: CacheName,
+        key: &str,
+        content: ByteView<'static>,
+        reason: CacheStoreReason,
+    ) -> Result<SharedCacheStoreResult, CacheError> {
+        sentry::configure_scope(|scope| {
+            let mut map = BTreeMap::new();
+            map.insert(
+                "container".to_string(),
+                self.config.container.clone().into(),
+            );
+            map.insert("key".to_string(), key.into());
+            scope.set_context("Azure Shared Cache", Context::Other(map));
+        });
+        if reason == CacheStoreReason::Refresh {
+            match self.exists(cache, key).await {
+                Ok(true) => return Ok(SharedCacheStoreResult::Skipped),
+                Ok(false) | Err(CacheError::ConnectTimeout) => (),
+                Err(err) => {
+                    let stderr: &dyn std::error::Error = &err;
+                    tracing::warn!(stderr, "Failed fetching Azure blob metadata");
+                }
+            }
+        }
+
+        let total_bytes = content.len() as u64;
+        let token = self.get_token().await?;
+        let url = self
+            .config
+            .blob_url(key)
+            .context("failed to build blob url")?;
+
+        let stream = ReaderStream::new(std::io::Cursor::new(content));
+        let body = Body::wrap_stream(stream);
+        let request = self
+            .client
+            .put(url)
+            .header("authorization", token.as_ref())
+            .header("x-ms-version", AZURE_API_VERSION)
+            .header("x-ms-blob-type", "BlockBlob")
+            .header(reqwest::header::CONTENT_LENGTH, total_bytes)
+            // Upload only if it's not already there
+            .header(reqwest::header::IF_NONE_MATCH, "*")
+            .body(body)
+            .send();
+        let request = tokio::time::timeout(STORE_TIMEOUT, request);
+        let request = measure_download_time("services.shared_cache.store.upload", "azure", request);
+
+        match request.await {
+            Ok(Ok(response)) => {
+                let status = response.status();
+                match status {
+                    successful if successful.is_success() => {
+                        tracing::trace!("Success hitting shared_cache Azure {}", key);
+                        Ok(SharedCacheStoreResult::Written(total_bytes))
+                    }
+                    StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => {
+                        Ok(SharedCacheStoreResult::Skipped)
+                    }
+                    StatusCode::FORBIDDEN => Err(anyhow!(
+                        "Insufficient permissions for container {}",
+                        self.config.container
+                    )
+                    .into()),
+                    StatusCode::UNAUTHORIZED => Err(anyhow!("Invalid credentials").into()),
+                    _ => Err(anyhow!("Error response from Azure: {}", status).into()),
+                }
+            }
+            Ok(Err(err)) => {
+                tracing::trace!("Error in shared_cache Azure response for {}", key);
+                Err(err).context("Bad Azure response for shared_cache")?
+            }
+            Err(_) => Err(CacheError::ConnectTimeout),
+        }
+    }
+}
+
 impl FilesystemSharedCacheConfig {
     /// Fetches item from shared cache if available and copies them to the writer.
     ///
@@ -371,6 +742,16 @@ impl FilesystemSharedCacheConfig {
         }
     }
 
+    /// Deletes a file from the shared cache, returning whether it existed.
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let abspath = self.path.join(key);
+        match fs::remove_file(abspath).await {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err).context("Failed to delete file in shared cache")?,
+        }
+    }
+
     async fn store(
         &self,
         // FIXME(swatinem): using a `&str` here leads to a
@@ -403,10 +784,147 @@ impl FilesystemSharedCacheConfig {
             .await
             .context("Failed to copy data into file")?;
 
-        temp_file
-            .persist(abspath)
-            .context("Failed to save file in shared cache")?;
-        Ok(SharedCacheStoreResult::Written(bytes))
+        match temp_file.persist_noclobber(abspath) {
+            Ok(_) => Ok(SharedCacheStoreResult::Written(bytes)),
+            // Another symbolicator stored the file in the meantime.
+            Err(err) if err.error.kind() == io::ErrorKind::AlreadyExists => {
+                Ok(SharedCacheStoreResult::Skipped)
+            }
+            Err(err) => Err(err).context("Failed to save file in shared cache")?,
+        }
+    }
+}
+
+/// A connection to a single Redis server, or to a Redis Cluster.
+///
+/// Both kinds of connections are cheap to clone, and reconnect on their own.
+#[derive(Clone)]
+enum RedisConnection {
+    Single(redis::aio::ConnectionManager),
+    Cluster(redis::cluster_async::ClusterConnection),
+}
+
+struct RedisState {
+    config: RedisSharedCacheConfig,
+    connection: RedisConnection,
+}
+
+impl fmt::Debug for RedisState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisState")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisState {
+    pub async fn try_new(config: RedisSharedCacheConfig) -> Result<Self> {
+        let connection = if config.cluster || config.urls.len() > 1 {
+            let client = redis::cluster::ClusterClient::new(config.urls.clone())
+                .context("Invalid Redis Cluster configuration")?;
+            let connection = client
+                .get_async_connection()
+                .await
+                .context("Failed to connect to Redis Cluster")?;
+            RedisConnection::Cluster(connection)
+        } else {
+            let url = config
+                .urls
+                .first()
+                .ok_or_else(|| Error::msg("No Redis URL configured"))?;
+            let client = redis::Client::open(url.as_str()).context("Invalid Redis URL")?;
+            let connection = redis::aio::ConnectionManager::new(client)
+                .await
+                .context("Failed to connect to Redis")?;
+            RedisConnection::Single(connection)
+        };
+        Ok(Self { config, connection })
+    }
+
+    /// Runs a command, with timeout and error handling.
+    async fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T, CacheError> {
+        let future = async {
+            match self.connection.clone() {
+                RedisConnection::Single(mut connection) => cmd.query_async(&mut connection).await,
+                RedisConnection::Cluster(mut connection) => cmd.query_async(&mut connection).await,
+            }
+        };
+        match tokio::time::timeout(STORE_TIMEOUT, future).await {
+            Ok(result) => Ok(result.context("Error response from Redis")?),
+            Err(_) => Err(CacheError::Timeout),
+        }
+    }
+
+    /// Fetches item from shared cache if available and copies them to the writer.
+    ///
+    /// # Returns
+    ///
+    /// If successful the number of bytes written to the writer are returned.
+    async fn fetch<W>(&self, key: &str, writer: &mut W) -> Result<Option<u64>, CacheError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut cmd = redis::cmd("GET");
+        cmd.arg(key);
+        let request = self.query::<Option<Vec<u8>>>(&cmd);
+        let request =
+            measure_download_time("services.shared_cache.fetch.connect", "redis", request);
+
+        let Some(content) = request.await? else {
+            return Ok(None);
+        };
+        let mut reader: &[u8] = &content;
+        match io::copy(&mut reader, writer).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) => Err(err).context("IO Error writing Redis bytes to writer")?,
+        }
+    }
+
+    /// Deletes an item from Redis, returning whether it existed.
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(key);
+        let deleted: u64 = self.query(&cmd).await?;
+        Ok(deleted > 0)
+    }
+
+    /// Stores a file in Redis, unless it exceeds the `max_item_size`.
+    ///
+    /// Items are only written if they do not exist yet, and expire after the configured `ttl`.
+    async fn store(
+        &self,
+        key: &str,
+        content: ByteView<'static>,
+    ) -> Result<SharedCacheStoreResult, CacheError> {
+        let total_bytes = content.len() as u64;
+        if total_bytes > self.config.max_item_size {
+            return Ok(SharedCacheStoreResult::TooLarge);
+        }
+
+        let ttl_ms: u64 = self.config.ttl.as_millis().try_into().unwrap_or(u64::MAX);
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(key)
+            .arg(content.as_slice())
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl_ms.max(1));
+        let request = self.query::<Option<String>>(&cmd);
+        let request = measure_download_time("services.shared_cache.store.upload", "redis", request);
+
+        match request.await? {
+            Some(_) => Ok(SharedCacheStoreResult::Written(total_bytes)),
+            // `NX` does not set existing keys.
+            None => Ok(SharedCacheStoreResult::Skipped),
+        }
+    }
+
+    /// Takes a lease which Redis expires after `ttl`, returning whether it was free.
+    async fn acquire_lease(&self, key: &str, ttl: Duration) -> Result<bool, CacheError> {
+        let ttl_ms: u64 = ttl.as_millis().try_into().unwrap_or(u64::MAX);
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(key).arg("1").arg("NX").arg("PX").arg(ttl_ms.max(1));
+        let acquired: Option<String> = self.query(&cmd).await?;
+        Ok(acquired.is_some())
     }
 }
 
@@ -417,6 +935,8 @@ enum SharedCacheStoreResult {
     Written(u64),
     /// Skipped writing the item as it was already on the cache.
     Skipped,
+    /// Skipped writing the item as it is too large for the cache.
+    TooLarge,
 }
 
 impl AsRef<str> for SharedCacheStoreResult {
@@ -424,6 +944,7 @@ impl AsRef<str> for SharedCacheStoreResult {
         match self {
             SharedCacheStoreResult::Written(_) => "written",
             SharedCacheStoreResult::Skipped => "skipped",
+            SharedCacheStoreResult::TooLarge => "too_large",
         }
     }
 }
@@ -438,7 +959,9 @@ impl fmt::Display for SharedCacheStoreResult {
 #[allow(clippy::large_enum_variant)]
 enum SharedCacheBackend {
     Gcs(Arc<GcsState>),
+    Azure(Arc<AzureState>),
     Fs(FilesystemSharedCacheConfig),
+    Redis(Arc<RedisState>),
 }
 
 impl SharedCacheBackend {
@@ -459,17 +982,136 @@ impl SharedCacheBackend {
                     }
                 }
             }
+            SharedCacheBackendConfig::Azure(cfg) => {
+                Some(SharedCacheBackend::Azure(Arc::new(AzureState::new(cfg))))
+            }
             // TODO: We could check if we can write in the configured directory here, but
             // this is only test backend so not very important.
             SharedCacheBackendConfig::Filesystem(cfg) => Some(SharedCacheBackend::Fs(cfg)),
+            SharedCacheBackendConfig::Redis(cfg) => {
+                match RedisState::try_new(cfg)
+                    .await
+                    .context("Failed to initialise Redis backend for shared cache")
+                {
+                    Ok(state) => Some(SharedCacheBackend::Redis(Arc::new(state))),
+                    Err(err) => {
+                        sentry::capture_error(&*err);
+                        None
+                    }
+                }
+            }
         }
     }
 
     fn name(&self) -> &'static str {
         match self {
             Self::Gcs(_) => "GCS",
+            Self::Azure(_) => "Azure",
             Self::Fs(_) => "filesystem",
+            Self::Redis(_) => "Redis",
+        }
+    }
+
+    /// Fetches the item at `key` into memory.
+    ///
+    /// This is only meant for tiny items like leases.
+    async fn fetch_to_vec(&self, key: &str) -> Result<Option<Vec<u8>>, CacheError> {
+        let mut content = Vec::new();
+        let fetched = match self {
+            Self::Gcs(state) => state.fetch(key, &mut content).await?,
+            Self::Azure(state) => state.fetch(key, &mut content).await?,
+            Self::Fs(cfg) => cfg.fetch(key, &mut content).await?,
+            Self::Redis(state) => state.fetch(key, &mut content).await?,
+        };
+        Ok(fetched.map(|_| content))
+    }
+
+    /// Stores an item at `key`, unless an item already exists there.
+    async fn create(
+        &self,
+        cache: CacheName,
+        key: &str,
+        content: ByteView<'static>,
+    ) -> Result<SharedCacheStoreResult, CacheError> {
+        match self {
+            Self::Gcs(state) => {
+                state
+                    .store(cache, key, content, CacheStoreReason::New)
+                    .await
+            }
+            Self::Azure(state) => {
+                state
+                    .store(cache, key, content, CacheStoreReason::New)
+                    .await
+            }
+            Self::Fs(cfg) => cfg.store(key.to_owned(), content).await,
+            Self::Redis(state) => state.store(key, content).await,
+        }
+    }
+
+    /// Deletes the item at `key`, returning whether it existed.
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        match self {
+            Self::Gcs(state) => state.delete(key).await,
+            Self::Azure(state) => state.delete(key).await,
+            Self::Fs(cfg) => cfg.delete(key).await,
+            Self::Redis(state) => state.delete(key).await,
+        }
+    }
+
+    /// Takes the lease at `key` for `ttl`, returning `false` if someone else holds it.
+    ///
+    /// Redis expires leases on its own. On the other backends, a lease is an item holding its
+    /// expiration time, and expired leases are replaced when they are taken the next time.
+    async fn acquire_lease(
+        &self,
+        cache: CacheName,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<bool, CacheError> {
+        if let Self::Redis(state) = self {
+            return state.acquire_lease(key, ttl).await;
+        }
+
+        let expires_at = (SystemTime::now() + ttl)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            .to_string();
+        // A second attempt is made after the existing lease turned out to be gone or expired.
+        for _ in 0..2 {
+            let content = ByteView::from_vec(expires_at.clone().into_bytes());
+            if let SharedCacheStoreResult::Written(_) = self.create(cache, key, content).await? {
+                return Ok(true);
+            }
+            match self.fetch_to_vec(key).await? {
+                Some(lease) if !is_lease_expired(&lease) => return Ok(false),
+                Some(_) => {
+                    self.delete(key).await?;
+                }
+                // The lease has been released in the meantime.
+                None => {}
+            }
         }
+        Ok(false)
+    }
+}
+
+/// Returns the key of the lease on computing the item at `key`.
+fn lease_key(cache: CacheName, key: &str) -> String {
+    format!("leases/{}/{key}", cache.as_ref())
+}
+
+/// Whether the stored expiration time of a lease has passed.
+///
+/// Leases which do not hold a valid expiration time are considered expired.
+fn is_lease_expired(lease: &[u8]) -> bool {
+    let expires_at = std::str::from_utf8(lease)
+        .ok()
+        .and_then(|millis| millis.parse().ok());
+    match expires_at {
+        Some(millis) => UNIX_EPOCH + Duration::from_millis(millis) <= SystemTime::now(),
+        None => true,
     }
 }
 
@@ -508,6 +1150,17 @@ impl AsRef<str> for CacheStoreReason {
     }
 }
 
+/// The outcome of trying to take the lease on computing an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseStatus {
+    /// The lease has been taken, and should be released once the item has been stored.
+    Acquired,
+    /// Another symbolicator holds the lease, and is computing the item.
+    Held,
+    /// Leases are disabled, or taking the lease failed.
+    Unavailable,
+}
+
 pub type SharedCacheRef = Arc<OnceCell<SharedCacheService>>;
 
 /// A shared cache service.
@@ -518,6 +1171,8 @@ pub struct SharedCacheService {
     backend: Arc<SharedCacheBackend>,
     upload_queue_tx: mpsc::Sender<UploadMessage>,
     runtime: tokio::runtime::Handle,
+    lease_timeout: Option<Duration>,
+    store_policies: Arc<HashMap<CacheName, SharedCacheStorePolicy>>,
 }
 
 impl SharedCacheService {
@@ -548,6 +1203,8 @@ impl SharedCacheService {
                 backend,
                 upload_queue_tx: tx,
                 runtime,
+                lease_timeout: config.lease_timeout,
+                store_policies: Arc::new(config.store_policies),
             });
         }
     }
@@ -563,6 +1220,8 @@ impl SharedCacheService {
         loop {
             tokio::select! {
                 Some(message) = work_rx.recv(), if uploads_counter > 0 => {
+                    let queue_depth = work_rx.len() as u64;
+                    metric!(gauge("services.shared_cache.uploads_queue_depth") = queue_depth);
                     uploads_counter -= 1;
                     tokio::spawn(
                         Self::single_uploader(done_tx.clone(), backend.clone(), message)
@@ -580,14 +1239,28 @@ impl SharedCacheService {
         tracing::info!("Shared cache upload worker terminated");
     }
 
-    /// Does a single upload to the shared cache backend.
-    ///
-    /// Handles metrics and error reporting.
+    /// Does a single upload of the upload worker.
     async fn single_uploader(
         done_tx: mpsc::Sender<()>,
         backend: Arc<SharedCacheBackend>,
         message: UploadMessage,
     ) {
+        Self::upload(backend, message).await;
+
+        // Tell the work coordinator we're done.
+        done_tx.send(()).await.unwrap_or_else(|err| {
+            let stderr: &dyn std::error::Error = &err;
+            tracing::error!(
+                stderr,
+                "Shared cache single_uploader failed to send done message",
+            );
+        });
+    }
+
+    /// Does a single upload to the shared cache backend.
+    ///
+    /// Handles metrics and error reporting.
+    async fn upload(backend: Arc<SharedCacheBackend>, message: UploadMessage) {
         let UploadMessage {
             cache,
             key,
@@ -606,7 +1279,9 @@ impl SharedCacheService {
 
         let res = match *backend {
             SharedCacheBackend::Gcs(ref state) => state.store(cache, &key, content, reason).await,
+            SharedCacheBackend::Azure(ref state) => state.store(cache, &key, content, reason).await,
             SharedCacheBackend::Fs(ref cfg) => cfg.store(key, content).await,
+            SharedCacheBackend::Redis(ref state) => state.store(&key, content).await,
         };
         match res {
             Ok(op) => {
@@ -649,15 +1324,6 @@ impl SharedCacheService {
             }
         }
 
-        // Tell the work coordinator we're done.
-        done_tx.send(()).await.unwrap_or_else(|err| {
-            let stderr: &dyn std::error::Error = &err;
-            tracing::error!(
-                stderr,
-                "Shared cache single_uploader failed to send done message",
-            );
-        });
-
         // Tell the original work submitter we're done, if they dropped this we don't care.
         complete_tx.send(()).ok();
     }
@@ -700,7 +1366,25 @@ impl SharedCacheService {
                     .map(|res| res.unwrap_or(Err(CacheError::ConnectTimeout)))
                     .unwrap_or(Err(CacheError::Timeout))
             }
+            SharedCacheBackend::Azure(state) => {
+                let state = Arc::clone(state);
+                let future = async move { state.fetch(&key, &mut file).await }
+                    .bind_hub(sentry::Hub::current());
+                let future = CancelOnDrop::new(self.runtime.spawn(future));
+                let future = tokio::time::timeout(STORE_TIMEOUT, future);
+                future
+                    .await
+                    .map(|res| res.unwrap_or(Err(CacheError::ConnectTimeout)))
+                    .unwrap_or(Err(CacheError::Timeout))
+            }
             SharedCacheBackend::Fs(cfg) => cfg.fetch(&key, &mut file).await,
+            SharedCacheBackend::Redis(state) => {
+                let state = Arc::clone(state);
+                let future = async move { state.fetch(&key, &mut file).await }
+                    .bind_hub(sentry::Hub::current());
+                let future = CancelOnDrop::new(self.runtime.spawn(future));
+                future.await.unwrap_or(Err(CacheError::ConnectTimeout))
+            }
         };
         match res {
             Ok(Some(bytes)) => {
@@ -747,6 +1431,39 @@ impl SharedCacheService {
         }
     }
 
+    /// Deletes a file from the shared cache.
+    ///
+    /// Returns `true` if the file existed and has been deleted. Errors are reported and
+    /// otherwise hidden, like for [`fetch`](Self::fetch).
+    pub async fn delete(&self, cache: CacheName, key: &str) -> bool {
+        let backend_name = self.backend_name();
+        let key = format!("{}/{key}", cache.as_ref());
+        let res = self.backend.delete(&key).await;
+        let status = match &res {
+            Ok(true) => "deleted",
+            Ok(false) => "not_found",
+            Err(CacheError::ConnectTimeout) => "connect-timeout",
+            Err(CacheError::Timeout) => "timeout",
+            Err(CacheError::Other(_)) => "error",
+        };
+        metric!(
+            counter("services.shared_cache.delete") += 1,
+            "cache" => cache.as_ref(),
+            "status" => status,
+        );
+        match res {
+            Ok(deleted) => deleted,
+            Err(err) => {
+                let stderr: &dyn std::error::Error = &err;
+                tracing::error!(
+                    stderr,
+                    "Error deleting {key} from {backend_name} shared cache"
+                );
+                false
+            }
+        }
+    }
+
     /// Place a file on the shared cache, if it does not yet exist there.
     ///
     /// Errors are transparently hidden, this service handles any errors itself.
@@ -765,6 +1482,9 @@ impl SharedCacheService {
     /// If [`CacheStoreReason::Refresh`] is used the implementation will trade off an extra
     /// request to check if the file already exists before uploading.  This is racy but a
     /// good tradeoff for refreshed stores.
+    ///
+    /// Depending on the [`store_policy`](Self::store_policy) of the cache, the file is queued
+    /// for upload, uploaded right away bypassing the queue, or not stored at all.
     pub fn store(
         &self,
         cache: CacheName,
@@ -782,24 +1502,107 @@ impl SharedCacheService {
             let _ = done_tx.send(());
             return done_rx;
         }
+
+        let message = UploadMessage {
+            cache,
+            key: format!("{}/{key}", cache.as_ref()),
+            content,
+            done_tx,
+            reason,
+        };
+        match self.store_policy(cache) {
+            SharedCacheStorePolicy::WriteBack => {}
+            SharedCacheStorePolicy::WriteThrough => {
+                let future = Self::upload(Arc::clone(&self.backend), message);
+                self.runtime
+                    .spawn(future.bind_hub(Hub::new_from_top(Hub::current())));
+                return done_rx;
+            }
+            SharedCacheStorePolicy::Disabled => {
+                let _ = message.done_tx.send(());
+                return done_rx;
+            }
+        }
+
+        let capacity = self.upload_queue_tx.capacity();
+        metric!(gauge("services.shared_cache.uploads_queue_capacity") = capacity as u64);
         metric!(
-            gauge("services.shared_cache.uploads_queue_capacity") =
-                self.upload_queue_tx.capacity() as u64
+            gauge("services.shared_cache.uploads_queue_depth") =
+                (self.upload_queue_tx.max_capacity() - capacity) as u64
         );
-        self.upload_queue_tx
-            .try_send(UploadMessage {
-                cache,
-                key: format!("{}/{key}", cache.as_ref()),
-                content,
-                done_tx,
-                reason,
-            })
-            .unwrap_or_else(|_| {
-                metric!(counter("services.shared_cache.store.dropped") += 1);
-                tracing::error!("Shared cache upload queue full");
-            });
+        self.upload_queue_tx.try_send(message).unwrap_or_else(|_| {
+            metric!(counter("services.shared_cache.store.dropped") += 1);
+            tracing::error!("Shared cache upload queue full");
+        });
         done_rx
     }
+
+    /// Returns how the items of the given cache are stored.
+    pub fn store_policy(&self, cache: CacheName) -> SharedCacheStorePolicy {
+        self.store_policies.get(&cache).copied().unwrap_or_default()
+    }
+
+    /// Returns the duration of leases on computing items, if leases are enabled.
+    pub fn lease_timeout(&self) -> Option<Duration> {
+        self.lease_timeout
+    }
+
+    /// Tries to take the lease on computing the item at `key`.
+    ///
+    /// The lease expires after the [`lease_timeout`](Self::lease_timeout), unless it is released
+    /// with [`release_lease`](Self::release_lease) before. Errors are reported and otherwise
+    /// treated like disabled leases, so that they never block computations.
+    pub async fn acquire_lease(&self, cache: CacheName, key: &str) -> LeaseStatus {
+        let Some(ttl) = self.lease_timeout else {
+            return LeaseStatus::Unavailable;
+        };
+        let key = lease_key(cache, key);
+        let res = tokio::time::timeout(STORE_TIMEOUT, self.backend.acquire_lease(cache, &key, ttl))
+            .await
+            .unwrap_or(Err(CacheError::Timeout));
+
+        let status = match &res {
+            Ok(true) => "acquired",
+            Ok(false) => "held",
+            Err(CacheError::ConnectTimeout) => "connect-timeout",
+            Err(CacheError::Timeout) => "timeout",
+            Err(CacheError::Other(_)) => "error",
+        };
+        metric!(
+            counter("services.shared_cache.lease") += 1,
+            "cache" => cache.as_ref(),
+            "status" => status,
+        );
+        match res {
+            Ok(true) => LeaseStatus::Acquired,
+            Ok(false) => LeaseStatus::Held,
+            Err(err) => {
+                let stderr: &dyn std::error::Error = &err;
+                tracing::error!(
+                    stderr,
+                    "Error taking lease {key} on {} shared cache",
+                    self.backend_name()
+                );
+                LeaseStatus::Unavailable
+            }
+        }
+    }
+
+    /// Releases the lease on computing the item at `key`.
+    ///
+    /// Symbolicators waiting for the item take the lease themselves once it is released, so this
+    /// should only be called after the item has been stored.
+    pub async fn release_lease(&self, cache: CacheName, key: &str) {
+        let key = lease_key(cache, key);
+        if let Err(err) = self.backend.delete(&key).await {
+            let stderr: &dyn std::error::Error = &err;
+            tracing::error!(
+                stderr,
+                "Error releasing lease {key} on {} shared cache",
+                self.backend_name()
+            );
+        }
+    }
 }
 
 #[cfg(test)]
@@ -848,6 +1651,8 @@ mod tests {
         let cfg = SharedCacheConfig {
             max_concurrent_uploads: 10,
             max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: Default::default(),
             backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
                 path: dir.path().to_path_buf(),
             }),
@@ -876,6 +1681,8 @@ mod tests {
         let cfg = SharedCacheConfig {
             max_concurrent_uploads: 10,
             max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: Default::default(),
             backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
                 path: dir.path().to_path_buf(),
             }),
@@ -903,6 +1710,8 @@ mod tests {
         let cfg = SharedCacheConfig {
             max_concurrent_uploads: 10,
             max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: Default::default(),
             backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
                 path: dir.path().to_path_buf(),
             }),
@@ -927,6 +1736,118 @@ mod tests {
         assert_eq!(data, b"cache data");
     }
 
+    #[tokio::test]
+    async fn test_filesystem_store_policies() {
+        symbolicator_test::setup();
+        let dir = symbolicator_test::tempdir();
+
+        let cfg = SharedCacheConfig {
+            max_concurrent_uploads: 10,
+            max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: HashMap::from([
+                (CacheName::Objects, SharedCacheStorePolicy::Disabled),
+                (CacheName::Symcaches, SharedCacheStorePolicy::WriteThrough),
+            ]),
+            backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
+                path: dir.path().to_path_buf(),
+            }),
+        };
+        let svc = SharedCacheService::new(Some(cfg), tokio::runtime::Handle::current());
+        let svc = wait_init(&svc).await;
+
+        for cache in [CacheName::Objects, CacheName::Symcaches] {
+            let recv = svc.store(
+                cache,
+                "global/some_item",
+                ByteView::from_slice(b"cache data"),
+                CacheStoreReason::New,
+            );
+            recv.await.unwrap();
+        }
+
+        assert!(!dir.path().join("objects/global/some_item").exists());
+        let data = fs::read(dir.path().join("symcaches/global/some_item"))
+            .await
+            .unwrap();
+        assert_eq!(data, b"cache data");
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_delete() {
+        symbolicator_test::setup();
+        let dir = symbolicator_test::tempdir();
+
+        let cache_path = dir.path().join("objects/global/some_item");
+        fs::create_dir_all(cache_path.parent().unwrap())
+            .await
+            .unwrap();
+        fs::write(&cache_path, b"cache data").await.unwrap();
+
+        let cfg = SharedCacheConfig {
+            max_concurrent_uploads: 10,
+            max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: Default::default(),
+            backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
+                path: dir.path().to_path_buf(),
+            }),
+        };
+        let svc = SharedCacheService::new(Some(cfg), tokio::runtime::Handle::current());
+        let svc = wait_init(&svc).await;
+
+        assert!(svc.delete(CacheName::Objects, "global/some_item").await);
+        assert!(!cache_path.exists());
+        assert!(!svc.delete(CacheName::Objects, "global/some_item").await);
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_lease() {
+        symbolicator_test::setup();
+        let dir = symbolicator_test::tempdir();
+
+        let cfg = SharedCacheConfig {
+            max_concurrent_uploads: 10,
+            max_upload_queue_size: 10,
+            lease_timeout: Some(Duration::from_secs(60)),
+            store_policies: Default::default(),
+            backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
+                path: dir.path().to_path_buf(),
+            }),
+        };
+        let svc = SharedCacheService::new(Some(cfg), tokio::runtime::Handle::current());
+        let svc = wait_init(&svc).await;
+
+        let key = "global/some_item";
+        let lease_path = dir.path().join("leases/symcaches/global/some_item");
+
+        assert_eq!(
+            svc.acquire_lease(CacheName::Symcaches, key).await,
+            LeaseStatus::Acquired
+        );
+        assert!(lease_path.exists());
+        assert_eq!(
+            svc.acquire_lease(CacheName::Symcaches, key).await,
+            LeaseStatus::Held
+        );
+
+        svc.release_lease(CacheName::Symcaches, key).await;
+        assert!(!lease_path.exists());
+        assert_eq!(
+            svc.acquire_lease(CacheName::Symcaches, key).await,
+            LeaseStatus::Acquired
+        );
+
+        // Leases of crashed symbolicators are taken over once they expire.
+        fs::write(&lease_path, b"1000").await.unwrap();
+        assert_eq!(
+            svc.acquire_lease(CacheName::Symcaches, key).await,
+            LeaseStatus::Acquired
+        );
+        let lease = fs::read(&lease_path).await.unwrap();
+        assert!(!is_lease_expired(&lease));
+    }
+
     #[tokio::test]
     async fn test_gcs_fetch_not_found() {
         symbolicator_test::setup();
@@ -937,6 +1858,8 @@ mod tests {
         let cfg = SharedCacheConfig {
             max_concurrent_uploads: 10,
             max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: Default::default(),
             backend: SharedCacheBackendConfig::Gcs(GcsSharedCacheConfig::from(credentials)),
         };
         let svc = SharedCacheService::new(Some(cfg), tokio::runtime::Handle::current());
@@ -983,6 +1906,8 @@ mod tests {
         let cfg = SharedCacheConfig {
             max_concurrent_uploads: 10,
             max_upload_queue_size: 10,
+            lease_timeout: None,
+            store_policies: Default::default(),
             backend: SharedCacheBackendConfig::Gcs(GcsSharedCacheConfig::from(credentials)),
         };
         let svc = SharedCacheService::new(Some(cfg), tokio::runtime::Handle::current());
@@ -1067,4 +1992,62 @@ mod tests {
 
         assert!(state.exists(CacheName::Objects, &key).await.unwrap());
     }
+
+    #[test]
+    fn test_azure_token_response() {
+        // The Instance Metadata Service returns `expires_in` as a string.
+        let json = r#"{"access_token": "secret", "expires_in": "3599", "token_type": "Bearer"}"#;
+        let token: AzureTokenResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(token.access_token, "secret");
+        assert_eq!(token.expires_in, 3599);
+
+        let json = r#"{"access_token": "secret", "expires_in": 3599}"#;
+        let token: AzureTokenResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(token.expires_in, 3599);
+    }
+
+    #[tokio::test]
+    async fn test_redis_state_store_fetch() {
+        symbolicator_test::setup();
+        let Ok(url) = std::env::var("SYMBOLICATOR_TEST_REDIS_URL") else {
+            println!("Skipping due to missing SYMBOLICATOR_TEST_REDIS_URL");
+            return;
+        };
+
+        let state = RedisState::try_new(RedisSharedCacheConfig {
+            urls: vec![url],
+            cluster: false,
+            max_item_size: 16,
+            ttl: Duration::from_secs(60),
+        })
+        .await
+        .unwrap();
+
+        let key = format!("{}/some_item", Uuid::new_v4());
+
+        let mut writer = Vec::new();
+        assert!(state.fetch(&key, &mut writer).await.unwrap().is_none());
+
+        let ret = state
+            .store(&key, ByteView::from_slice(b"cache data"))
+            .await
+            .unwrap();
+        assert!(matches!(ret, SharedCacheStoreResult::Written(10)));
+
+        let ret = state
+            .store(&key, ByteView::from_slice(b"cache data"))
+            .await
+            .unwrap();
+        assert!(matches!(ret, SharedCacheStoreResult::Skipped));
+
+        let ret = state.fetch(&key, &mut writer).await.unwrap();
+        assert_eq!(ret, Some(10));
+        assert_eq!(writer, b"cache data");
+
+        let ret = state
+            .store(&key, ByteView::from_slice(b"too large for the cache"))
+            .await
+            .unwrap();
+        assert!(matches!(ret, SharedCacheStoreResult::TooLarge));
+    }
 }
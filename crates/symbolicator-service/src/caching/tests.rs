@@ -13,12 +13,14 @@ use tempfile::NamedTempFile;
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
 use crate::config::{
-    CacheConfig, CacheConfigs, DerivedCacheConfig, DiagnosticsCacheConfig, DownloadedCacheConfig,
+    CacheConfig, CacheConfigs, CachePartitionConfig, DerivedCacheConfig, DiagnosticsCacheConfig,
+    DownloadedCacheConfig, ExpiryConfig, RevalidationConfig,
 };
 use crate::test;
+use crate::types::Scope;
 
 use super::cache_error::cache_entry_from_bytes;
-use super::shared_cache::config::SharedCacheBackendConfig;
+use super::shared_cache::config::{FilesystemSharedCacheConfig, SharedCacheBackendConfig};
 use super::*;
 
 fn tempdir() -> io::Result<tempfile::TempDir> {
@@ -160,6 +162,47 @@ fn test_retry_misses_after() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_retry_errors_by_kind() -> Result<()> {
+    let tempdir = tempdir()?;
+    let config = Config {
+        cache_dir: Some(tempdir.path().to_path_buf()),
+        ..Default::default()
+    };
+    fs::create_dir_all(tempdir.path().join("objects"))?;
+
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::Downloaded(DownloadedCacheConfig {
+            retry_misses_after: Some(Duration::from_secs(3600)),
+            retry_download_errors_after: Some(Duration::from_millis(50)),
+            ..Default::default()
+        }),
+        Default::default(),
+        1024,
+    )?;
+
+    File::create(tempdir.path().join("objects/notfound"))?.write_all(b"")?;
+    File::create(tempdir.path().join("objects/permissiondenied"))?
+        .write_all(b"permissiondeniedforbidden")?;
+    File::create(tempdir.path().join("objects/downloaderror"))?
+        .write_all(b"downloaderror500 internal server error")?;
+    sleep(Duration::from_millis(100));
+
+    cache.cleanup(false)?;
+
+    let mut basenames: Vec<_> = fs::read_dir(tempdir.path().join("objects"))?
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect();
+
+    basenames.sort();
+
+    assert_eq!(basenames, vec!["notfound", "permissiondenied"]);
+
+    Ok(())
+}
+
 #[test]
 fn test_cleanup_malformed() -> Result<()> {
     let tempdir = tempdir()?;
@@ -228,6 +271,7 @@ fn test_cleanup_cache_download() -> Result<()> {
         &config,
         CacheConfig::Downloaded(DownloadedCacheConfig {
             retry_misses_after: Some(Duration::from_millis(20)),
+            retry_download_errors_after: Some(Duration::from_millis(20)),
             ..Default::default()
         }),
         Default::default(),
@@ -392,6 +436,49 @@ fn test_open_cachefile() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_max_size() -> Result<()> {
+    let tempdir = tempdir()?;
+    let config = Config {
+        cache_dir: Some(tempdir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::Downloaded(DownloadedCacheConfig {
+            max_size: Some(10),
+            ..Default::default()
+        }),
+        Default::default(),
+        1024,
+    )?;
+
+    let first = tempdir.path().join("objects/first");
+    File::create(&first)?.write_all(b"hello")?;
+    cache.record_write(&first, 5);
+    sleep(Duration::from_millis(10));
+
+    let second = tempdir.path().join("objects/second");
+    File::create(&second)?.write_all(b"world")?;
+    cache.record_write(&second, 5);
+    sleep(Duration::from_millis(10));
+
+    // Using the first file makes the second one the least recently used.
+    assert!(cache.open_cachefile(&first)?.is_some());
+    sleep(Duration::from_millis(10));
+
+    let third = tempdir.path().join("objects/third");
+    File::create(&third)?.write_all(b"!")?;
+    cache.record_write(&third, 1);
+
+    assert!(first.exists());
+    assert!(!second.exists());
+    assert!(third.exists());
+
+    Ok(())
+}
+
 #[test]
 fn test_cleanup() {
     let tempdir = tempdir().unwrap();
@@ -429,6 +516,7 @@ fn test_cleanup() {
             },
             diagnostics: DiagnosticsCacheConfig {
                 retention: Some(Duration::from_secs(60)),
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -454,6 +542,145 @@ fn test_cleanup() {
     assert!(!diagnostics_entry.is_file());
 }
 
+#[test]
+fn test_cleanup_resume() -> Result<()> {
+    let tempdir = tempdir()?;
+    let mtime = FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3600));
+    for shard in ["v1/aa", "v1/bb"] {
+        let dir = tempdir.path().join("objects").join(shard);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("entry"), "contents")?;
+        filetime::set_file_mtime(dir.join("entry"), mtime)?;
+    }
+
+    // An interrupted cleanup already finished `v1/aa`.
+    let marker = tempdir.path().join("objects.cleanup");
+    fs::write(&marker, "v1/aa\n")?;
+
+    let config = Config {
+        cache_dir: Some(tempdir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::Downloaded(DownloadedCacheConfig {
+            max_unused_for: Some(Duration::from_secs(60)),
+            ..Default::default()
+        }),
+        Default::default(),
+        1024,
+    )?;
+    cache.cleanup(false)?;
+
+    assert!(tempdir.path().join("objects/v1/aa/entry").is_file());
+    assert!(!tempdir.path().join("objects/v1/bb").exists());
+    assert!(!marker.exists());
+
+    // The next cleanup starts over.
+    cache.cleanup(false)?;
+    assert!(!tempdir.path().join("objects/v1").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_inspect() -> Result<()> {
+    let tempdir = tempdir()?;
+    let caches = Caches::from_config(&Config {
+        cache_dir: Some(tempdir.path().to_path_buf()),
+        ..Default::default()
+    })?;
+    assert!(caches.get("unknown").is_none());
+    let cache = caches.get("auxdifs").unwrap();
+
+    let dir = tempdir.path().join("auxdifs/v1/ab");
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("good"), "contents")?;
+    fs::write(dir.join("good.txt"), "metadata")?;
+    fs::write(dir.join("malformed"), "malformedbroken file")?;
+
+    let files = cache.list_files()?;
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].path, Path::new("v1/ab/good"));
+    assert_eq!(files[0].version, 1);
+    assert_eq!(files[0].size, 8);
+    assert_eq!(files[0].error, None);
+    assert_eq!(
+        files[1].error,
+        Some(CacheError::Malformed("broken file".into()))
+    );
+
+    let details = cache.inspect_file(Path::new("v1/ab/malformed"))?;
+    assert_eq!(details.info.path, Path::new("v1/ab/malformed"));
+    assert!(details.header.is_empty());
+
+    assert!(cache.inspect_file(Path::new("v1/ab/missing")).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_bundle_roundtrip() -> Result<()> {
+    let source_dir = tempdir()?;
+    let source = Caches::from_config(&Config {
+        cache_dir: Some(source_dir.path().to_path_buf()),
+        ..Default::default()
+    })?;
+
+    let dir = source_dir.path().join("auxdifs/v1/ab");
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("good"), "contents")?;
+    fs::write(dir.join("old"), "old contents")?;
+    fs::write(dir.join("malformed"), "malformedbroken file")?;
+    let mtime = FileTime::from_system_time(SystemTime::now() - Duration::from_secs(7200));
+    filetime::set_file_mtime(dir.join("old"), mtime)?;
+
+    let filter = BundleFilter {
+        max_age: Some(Duration::from_secs(3600)),
+        ..Default::default()
+    };
+    let mut bundle = Vec::new();
+    let exported = export_bundle(&source.all(), &filter, &mut bundle)?;
+    assert_eq!(exported, 1);
+
+    // Files of cache versions the importing symbolicator does not know are skipped.
+    let mut builder = tar::Builder::new(Vec::new());
+    let files: [(&str, &[u8]); 2] = [
+        (
+            "manifest.json",
+            br#"{"format": 1, "symbolicator_version": "0.0.0"}"#,
+        ),
+        ("auxdifs/v99/ab/future", b"contents"),
+    ];
+    for (path, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, path, data)?;
+    }
+    let unsupported = builder.into_inner()?;
+
+    let target_dir = tempdir()?;
+    let target = Caches::from_config(&Config {
+        cache_dir: Some(target_dir.path().to_path_buf()),
+        ..Default::default()
+    })?;
+
+    let import = import_bundle(&target, &bundle[..])?;
+    assert_eq!(import.imported, 1);
+    let imported = target_dir.path().join("auxdifs/v1/ab/good");
+    assert_eq!(fs::read_to_string(imported)?, "contents");
+
+    let import = import_bundle(&target, &bundle[..])?;
+    assert_eq!(import.existing, 1);
+
+    let import = import_bundle(&target, &unsupported[..])?;
+    assert_eq!(import.unsupported, 1);
+    assert!(!target_dir.path().join("auxdifs/v99").exists());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cache_error_write_negative() -> Result<()> {
     let dir = tempdir()?;
@@ -642,10 +869,10 @@ fn test_shared_cache_config_filesystem_common_defaults() {
     assert_eq!(cfg.max_upload_queue_size, 400);
     assert_eq!(cfg.max_concurrent_uploads, 20);
     match cfg.backend {
-        SharedCacheBackendConfig::Gcs(_) => panic!("wrong backend"),
         SharedCacheBackendConfig::Filesystem(cfg) => {
             assert_eq!(cfg.path, Path::new("/path/to/somewhere"))
         }
+        _ => panic!("wrong backend"),
     }
 }
 
@@ -667,6 +894,28 @@ fn test_shared_cache_config_common_settings() {
     ));
 }
 
+#[test]
+fn test_shared_cache_config_store_policies() {
+    let yaml = r#"
+            store_policies:
+              objects: disabled
+              symcaches: write_through
+            filesystem:
+              path: "/path/to/somewhere"
+        "#;
+    let cfg: SharedCacheConfig = serde_yaml::from_reader(yaml.as_bytes()).unwrap();
+
+    assert_eq!(
+        cfg.store_policies[&CacheName::Objects],
+        SharedCacheStorePolicy::Disabled
+    );
+    assert_eq!(
+        cfg.store_policies[&CacheName::Symcaches],
+        SharedCacheStorePolicy::WriteThrough
+    );
+    assert!(!cfg.store_policies.contains_key(&CacheName::Cficaches));
+}
+
 #[test]
 fn test_shared_cache_config_gcs() {
     let yaml = r#"
@@ -680,7 +929,48 @@ fn test_shared_cache_config_gcs() {
             assert_eq!(gcs.bucket, "some-bucket");
             assert!(gcs.service_account_path.is_none());
         }
-        SharedCacheBackendConfig::Filesystem(_) => panic!("wrong backend"),
+        _ => panic!("wrong backend"),
+    }
+}
+
+#[test]
+fn test_shared_cache_config_azure() {
+    let yaml = r#"
+            azure:
+              account: "symbolicator"
+              container: "cache"
+        "#;
+    let cfg: SharedCacheConfig = serde_yaml::from_reader(yaml.as_bytes()).unwrap();
+
+    match cfg.backend {
+        SharedCacheBackendConfig::Azure(azure) => {
+            assert!(azure.client_id.is_none());
+            assert_eq!(
+                azure.blob_url("objects/global/some_item").unwrap().as_str(),
+                "https://symbolicator.blob.core.windows.net/cache/objects/global/some_item"
+            );
+        }
+        _ => panic!("wrong backend"),
+    }
+}
+
+#[test]
+fn test_shared_cache_config_redis() {
+    let yaml = r#"
+            redis:
+              urls: ["redis://127.0.0.1:6379"]
+              max_item_size: 1024
+        "#;
+    let cfg: SharedCacheConfig = serde_yaml::from_reader(yaml.as_bytes()).unwrap();
+
+    match cfg.backend {
+        SharedCacheBackendConfig::Redis(redis) => {
+            assert_eq!(redis.urls, ["redis://127.0.0.1:6379"]);
+            assert!(!redis.cluster);
+            assert_eq!(redis.max_item_size, 1024);
+            assert_eq!(redis.ttl, Duration::from_secs(3600 * 24 * 7));
+        }
+        _ => panic!("wrong backend"),
     }
 }
 
@@ -729,6 +1019,47 @@ fn test_cache_entry() {
         ))
     );
 
+    let blocked = b"blockedconnections to `169.254.169.254` are not allowed";
+
+    assert_eq!(
+        read_cache_entry(blocked),
+        Err(CacheError::Blocked(
+            "connections to `169.254.169.254` are not allowed".into()
+        ))
+    );
+
+    let size_limit_exceeded = b"sizelimitexceeded1048576";
+
+    assert_eq!(
+        read_cache_entry(size_limit_exceeded),
+        Err(CacheError::SizeLimitExceeded(1024 * 1024))
+    );
+
+    let no_matching_archive_member = b"nomatchingarchivememberchecked a.pdb, b.pdb";
+
+    assert_eq!(
+        read_cache_entry(no_matching_archive_member),
+        Err(CacheError::NoMatchingArchiveMember(
+            "checked a.pdb, b.pdb".into()
+        ))
+    );
+
+    let checksum_mismatch = b"checksummismatchexpected crc32c:c99465aa, got crc32c:00000000";
+
+    assert_eq!(
+        read_cache_entry(checksum_mismatch),
+        Err(CacheError::ChecksumMismatch(
+            "expected crc32c:c99465aa, got crc32c:00000000".into()
+        ))
+    );
+
+    let no_matching_arch = b"nomatchingarcharm64, x86_64";
+
+    assert_eq!(
+        read_cache_entry(no_matching_arch),
+        Err(CacheError::NoMatchingArch("arm64, x86_64".into()))
+    );
+
     let all_good = b"Not any of the error cases";
 
     assert_eq!(
@@ -756,6 +1087,7 @@ impl CacheItemRequest for TestCacheItem {
     const VERSIONS: CacheVersions = CacheVersions {
         current: 1,
         fallbacks: &[0],
+        migrations: &[],
     };
 
     fn compute<'a>(&'a self, temp_file: &'a mut NamedTempFile) -> BoxFuture<'a, CacheEntry> {
@@ -820,6 +1152,67 @@ async fn test_cache_fallback() {
     assert_eq!(request.computations.load(Ordering::SeqCst), 1);
 }
 
+/// A request which fails because its source is unavailable.
+#[derive(Clone, Default)]
+struct UnavailableCacheItem {
+    computations: Arc<AtomicUsize>,
+}
+
+impl CacheItemRequest for UnavailableCacheItem {
+    type Item = String;
+
+    const VERSIONS: CacheVersions = CacheVersions {
+        current: 1,
+        fallbacks: &[],
+        migrations: &[],
+    };
+
+    fn compute<'a>(&'a self, _temp_file: &'a mut NamedTempFile) -> BoxFuture<'a, CacheEntry> {
+        self.computations.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async { Err(CacheError::Unavailable("circuit breaker is open".into())) })
+    }
+
+    fn load(&self, data: ByteView<'static>) -> CacheEntry<Self::Item> {
+        Ok(std::str::from_utf8(data.as_slice()).unwrap().to_owned())
+    }
+}
+
+/// Makes sure that errors of unavailable sources are neither written to disk nor kept in memory.
+#[tokio::test]
+async fn test_unavailable_not_cached() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let request = UnavailableCacheItem::default();
+    let key = CacheKey::for_testing("global/some_cache_key");
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().downloaded),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+    let cacher = Cacher::new(cache, Default::default());
+
+    for _ in 0..2 {
+        let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+        assert_eq!(
+            result,
+            Err(CacheError::Unavailable("circuit breaker is open".into()))
+        );
+    }
+
+    assert_eq!(request.computations.load(Ordering::SeqCst), 2);
+    let cache_file = cache_dir.path().join("objects").join(key.cache_path(1));
+    assert!(!cache_file.exists());
+}
+
 /// Makes sure that a `NotFound` result does not fall back to older cache versions.
 #[tokio::test]
 async fn test_cache_fallback_notfound() {
@@ -861,13 +1254,55 @@ async fn test_cache_fallback_notfound() {
     assert_eq!(request.computations.load(Ordering::SeqCst), 0);
 }
 
-/// This test asserts that the bounded maximum number of recomputations is not exceeded.
+/// A [`TestCacheItem`] with a migration from version `1` to `2`, which uppercases the contents.
+#[derive(Clone, Default)]
+struct UpgradedCacheItem(TestCacheItem);
+
+fn uppercase(data: &[u8], writer: &mut dyn Write) -> io::Result<()> {
+    writer.write_all(&data.to_ascii_uppercase())
+}
+
+impl CacheItemRequest for UpgradedCacheItem {
+    type Item = String;
+
+    const VERSIONS: CacheVersions = CacheVersions {
+        current: 2,
+        fallbacks: &[1, 0],
+        migrations: &[CacheMigration {
+            from: 1,
+            to: 2,
+            upgrade: uppercase,
+        }],
+    };
+
+    fn compute<'a>(&'a self, temp_file: &'a mut NamedTempFile) -> BoxFuture<'a, CacheEntry> {
+        self.0.compute(temp_file)
+    }
+
+    fn load(&self, data: ByteView<'static>) -> CacheEntry<Self::Item> {
+        self.0.load(data)
+    }
+}
+
+/// Makes sure that files of fallback versions are upgraded when read, if there are migrations.
 #[tokio::test]
-async fn test_lazy_computation_limit() {
+async fn test_migration() {
     test::setup();
+    let cache_dir = test::tempdir();
+
+    let request = UpgradedCacheItem::default();
+    let key = CacheKey::for_testing("global/1");
+    let unmigrated_key = CacheKey::for_testing("global/2");
+
+    let objects_dir = cache_dir.path().join("objects");
+    for (key, version) in [(&key, 1), (&unmigrated_key, 0)] {
+        let cache_file = objects_dir.join(key.cache_path(version));
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(cache_file, "some old cached contents").unwrap();
+    }
 
     let config = Config {
-        cache_dir: Some(test::tempdir().path().to_path_buf()),
+        cache_dir: Some(cache_dir.path().to_path_buf()),
         ..Default::default()
     };
     let cache = Cache::from_config(
@@ -878,29 +1313,451 @@ async fn test_lazy_computation_limit() {
         1024,
     )
     .unwrap();
-    let cache_dir = cache.cache_dir.clone().unwrap();
     let cacher = Cacher::new(cache, Default::default());
 
-    let keys = &["global/1", "global/2", "global/3"];
-    let request = TestCacheItem::new();
-
-    for key in keys {
-        let request = request.clone();
-        let key = CacheKey::for_testing(*key);
-
-        let cache_file = cache_dir.join(key.cache_path(0));
-        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
-        fs::write(cache_file, "some old cached contents").unwrap();
+    let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+    assert_eq!(result.unwrap().as_str(), "SOME OLD CACHED CONTENTS");
+    let upgraded = fs::read_to_string(objects_dir.join(key.cache_path(2))).unwrap();
+    assert_eq!(upgraded, "SOME OLD CACHED CONTENTS");
 
-        let result = cacher.compute_memoized(request.clone(), key).await;
-        assert_eq!(result.unwrap().as_str(), "some old cached contents");
-    }
+    // there is no migration from version `0`, so this is recomputed in the background
+    let result = cacher
+        .compute_memoized(request.clone(), unmigrated_key)
+        .await;
+    assert_eq!(result.unwrap().as_str(), "some old cached contents");
 
     tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(request.0.computations.load(Ordering::SeqCst), 1);
+}
 
-    // we want the actual computation to be done only one time, as that is the
-    // maximum number of lazy computations.
-    assert_eq!(request.computations.load(Ordering::SeqCst), 1);
+#[test]
+fn test_migrate() -> Result<()> {
+    let cache_dir = tempdir()?;
+    let objects_dir = cache_dir.path().join("objects");
+
+    let key = CacheKey::for_testing("global/1");
+    let old_file = objects_dir.join(key.cache_path(1));
+    fs::create_dir_all(old_file.parent().unwrap())?;
+    fs::write(&old_file, "some old cached contents")?;
+    let mtime = FileTime::from_unix_time(FileTime::now().unix_seconds() - 3600, 0);
+    filetime::set_file_mtime(&old_file, mtime)?;
+
+    // errors are not migrated
+    let error_key = CacheKey::for_testing("global/2");
+    let error_file = objects_dir.join(error_key.cache_path(1));
+    fs::create_dir_all(error_file.parent().unwrap())?;
+    fs::write(&error_file, "")?;
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )?;
+
+    assert_eq!(cache.migrate(&UpgradedCacheItem::VERSIONS)?, 1);
+    let new_file = objects_dir.join(key.cache_path(2));
+    assert_eq!(fs::read_to_string(&new_file)?, "SOME OLD CACHED CONTENTS");
+    assert_eq!(
+        FileTime::from_last_modification_time(&new_file.metadata()?),
+        mtime
+    );
+    assert!(!objects_dir.join(error_key.cache_path(2)).exists());
+
+    // files which exist in the current version are kept
+    assert_eq!(cache.migrate(&UpgradedCacheItem::VERSIONS)?, 0);
+
+    Ok(())
+}
+
+/// Makes sure that partitioned caches write files into the partition of their scope.
+#[tokio::test]
+async fn test_partitioned() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        caches: CacheConfigs {
+            partitions: CachePartitionConfig {
+                caches: vec![CacheName::Objects],
+                max_scope_size: Some(1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+    assert!(cache.is_partitioned());
+    let cacher = Cacher::new(cache, Default::default());
+
+    let request = TestCacheItem::new();
+    let key = CacheKey::scoped_builder(&Scope::Scoped("123".into())).build();
+    let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+
+    let objects_dir = cache_dir.path().join("objects");
+    let path = objects_dir.join(key.partitioned_cache_path(1));
+    assert!(path.starts_with(objects_dir.join("v1/scopes/123")));
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "some new cached contents"
+    );
+    assert!(!objects_dir.join(key.cache_path(1)).exists());
+}
+
+/// Makes sure that purging removes all cache versions, and the in-memory item.
+#[tokio::test]
+async fn test_purge() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let request = TestCacheItem::new();
+    let key = CacheKey::for_testing("global/some_cache_key");
+
+    let objects_dir = cache_dir.path().join("objects");
+    let old_file = objects_dir.join(key.cache_path(0));
+    fs::create_dir_all(old_file.parent().unwrap()).unwrap();
+    fs::write(&old_file, "").unwrap();
+    let current_file = objects_dir.join(key.cache_path(1));
+    fs::create_dir_all(current_file.parent().unwrap()).unwrap();
+    fs::write(&current_file, "").unwrap();
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+    let cacher = Cacher::new(cache, Default::default());
+
+    let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+    assert_eq!(result, Err(CacheError::NotFound));
+
+    assert_eq!(cacher.purge(&key, false).await, 2);
+    assert!(!old_file.exists());
+    assert!(!current_file.exists());
+
+    let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+    assert_eq!(request.computations.load(Ordering::SeqCst), 1);
+}
+
+/// Makes sure that compressed caches write compressed files, and read them transparently.
+#[tokio::test]
+async fn test_compressed() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let request = TestCacheItem::new();
+    let key = CacheKey::for_testing("global/some_cache_key");
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        caches: CacheConfigs {
+            compressed: vec![CacheName::Objects],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cache = || {
+        Cache::from_config(
+            CacheName::Objects,
+            &config,
+            CacheConfig::from(CacheConfigs::default().derived),
+            Arc::new(AtomicIsize::new(1)),
+            1024,
+        )
+        .unwrap()
+    };
+
+    let cacher = Cacher::new(cache(), Default::default());
+    let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+
+    let cache_file = cache_dir.path().join("objects").join(key.cache_path(1));
+    assert!(compression::is_compressed(&fs::read(cache_file).unwrap()));
+
+    // A new cacher does not have the item in memory, and reads the compressed file.
+    let cacher = Cacher::new(cache(), Default::default());
+    let result = cacher.compute_memoized(request.clone(), key).await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+    assert_eq!(request.computations.load(Ordering::SeqCst), 1);
+}
+
+/// Makes sure that files of other caches are never decompressed, even if they look compressed.
+#[test]
+fn test_uncompressed_magic() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    compression::compress(b"some contents", temp_file.as_file_mut()).unwrap();
+    let contents = fs::read(temp_file.path()).unwrap();
+
+    let cache_file = cache_dir.path().join("objects/some_file");
+    fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+    fs::write(&cache_file, &contents).unwrap();
+
+    let (entry, _) = cache.open_cachefile(&cache_file).unwrap().unwrap();
+    assert_eq!(&*entry.unwrap(), &contents[..]);
+}
+
+/// Returns the files in the content store of the `objects` cache.
+fn content_files(cache_dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut files = vec![];
+    for dir in fs::read_dir(cache_dir.join("objects/content")).unwrap() {
+        for file in fs::read_dir(dir.unwrap().path()).unwrap() {
+            files.push(file.unwrap().path());
+        }
+    }
+    files
+}
+
+/// Makes sure that identical files of deduplicated caches are stored once, and cleaned up once
+/// they are not pointed to anymore.
+#[tokio::test]
+async fn test_deduplicated() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let request = TestCacheItem::new();
+    let first_key = CacheKey::for_testing("global/first_cache_key");
+    let second_key = CacheKey::for_testing("global/second_cache_key");
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        caches: CacheConfigs {
+            deduplicated: vec![CacheName::Objects],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cache = || {
+        Cache::from_config(
+            CacheName::Objects,
+            &config,
+            CacheConfig::from(CacheConfigs::default().derived),
+            Arc::new(AtomicIsize::new(1)),
+            1024,
+        )
+        .unwrap()
+    };
+
+    let cacher = Cacher::new(cache(), Default::default());
+    for key in [&first_key, &second_key] {
+        let result = cacher.compute_memoized(request.clone(), key.clone()).await;
+        assert_eq!(result.unwrap().as_str(), "some new cached contents");
+    }
+    let contents = content_files(cache_dir.path());
+    assert_eq!(contents.len(), 1);
+
+    // A new cacher does not have the items in memory, and resolves the pointers.
+    let cacher = Cacher::new(cache(), Default::default());
+    let result = cacher
+        .compute_memoized(request.clone(), first_key.clone())
+        .await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+    assert_eq!(request.computations.load(Ordering::SeqCst), 2);
+
+    // The contents are only removed once no file points to them anymore.
+    let objects_dir = cache_dir.path().join("objects");
+    let mtime = FileTime::from_system_time(SystemTime::now() - Duration::from_secs(7200));
+    filetime::set_file_mtime(&contents[0], mtime).unwrap();
+
+    fs::remove_file(objects_dir.join(second_key.cache_path(1))).unwrap();
+    cache().cleanup(false).unwrap();
+    assert!(contents[0].exists());
+
+    fs::remove_file(objects_dir.join(first_key.cache_path(1))).unwrap();
+    cache().cleanup(false).unwrap();
+    assert!(!contents[0].exists());
+}
+
+/// Makes sure that existing files are migrated into the content store.
+#[tokio::test]
+async fn test_deduplicate_existing() {
+    test::setup();
+    let cache_dir = test::tempdir();
+
+    let request = TestCacheItem::new();
+    let key = CacheKey::for_testing("global/some_cache_key");
+
+    let cache_file = cache_dir.path().join("objects").join(key.cache_path(1));
+    fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+    fs::write(&cache_file, "some old cached contents").unwrap();
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+
+    assert_eq!(cache.deduplicate().unwrap(), 1);
+    assert_eq!(content_files(cache_dir.path()).len(), 1);
+    assert_eq!(
+        fs::metadata(&cache_file).unwrap().len(),
+        content::POINTER_LEN
+    );
+
+    // Files are only migrated once.
+    assert_eq!(cache.deduplicate().unwrap(), 0);
+
+    let cacher = Cacher::new(cache, Default::default());
+    let result = cacher.compute_memoized(request.clone(), key).await;
+    assert_eq!(result.unwrap().as_str(), "some old cached contents");
+    assert_eq!(request.computations.load(Ordering::SeqCst), 0);
+}
+
+/// Makes sure that items leased by another symbolicator are fetched from the shared cache once
+/// they are stored there, instead of being computed.
+#[tokio::test]
+async fn test_shared_cache_lease() {
+    test::setup();
+    let cache_dir = test::tempdir();
+    let shared_dir = test::tempdir();
+
+    let request = TestCacheItem::new();
+    let key = CacheKey::for_testing("global/some_cache_key");
+    let cache_path = key.cache_path(1);
+
+    let shared_config = SharedCacheConfig {
+        max_concurrent_uploads: 10,
+        max_upload_queue_size: 10,
+        lease_timeout: Some(Duration::from_secs(30)),
+        store_policies: Default::default(),
+        backend: SharedCacheBackendConfig::Filesystem(FilesystemSharedCacheConfig {
+            path: shared_dir.path().to_path_buf(),
+        }),
+    };
+    let shared_cache =
+        SharedCacheService::new(Some(shared_config), tokio::runtime::Handle::current());
+    let shared = loop {
+        if let Some(shared) = shared_cache.get() {
+            break shared.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+
+    // Another symbolicator takes the lease, and stores the item a bit later.
+    assert_eq!(
+        shared.acquire_lease(CacheName::Objects, &cache_path).await,
+        LeaseStatus::Acquired
+    );
+    let other = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let contents = ByteView::from_slice(b"some shared cached contents");
+        shared
+            .store(
+                CacheName::Objects,
+                &cache_path,
+                contents,
+                CacheStoreReason::New,
+            )
+            .await
+            .unwrap();
+        shared.release_lease(CacheName::Objects, &cache_path).await;
+    });
+
+    let config = Config {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+    let cacher = Cacher::new(cache, shared_cache);
+
+    let result = cacher.compute_memoized(request.clone(), key).await;
+    assert_eq!(result.unwrap().as_str(), "some shared cached contents");
+    assert_eq!(request.computations.load(Ordering::SeqCst), 0);
+    other.await.unwrap();
+}
+
+/// This test asserts that the bounded maximum number of recomputations is not exceeded.
+#[tokio::test]
+async fn test_lazy_computation_limit() {
+    test::setup();
+
+    let config = Config {
+        cache_dir: Some(test::tempdir().path().to_path_buf()),
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+    let cache_dir = cache.cache_dir.clone().unwrap();
+    let cacher = Cacher::new(cache, Default::default());
+
+    let keys = &["global/1", "global/2", "global/3"];
+    let request = TestCacheItem::new();
+
+    for key in keys {
+        let request = request.clone();
+        let key = CacheKey::for_testing(*key);
+
+        let cache_file = cache_dir.join(key.cache_path(0));
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(cache_file, "some old cached contents").unwrap();
+
+        let result = cacher.compute_memoized(request.clone(), key).await;
+        assert_eq!(result.unwrap().as_str(), "some old cached contents");
+    }
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // we want the actual computation to be done only one time, as that is the
+    // maximum number of lazy computations.
+    assert_eq!(request.computations.load(Ordering::SeqCst), 1);
 
     // double check that we actually get outdated contents for two of the requests.
     let mut num_outdated = 0;
@@ -917,3 +1774,116 @@ async fn test_lazy_computation_limit() {
 
     assert_eq!(num_outdated, 2);
 }
+
+/// Makes sure that negative entries are revalidated ahead of their expiration, within the budget.
+#[tokio::test]
+async fn test_revalidation() {
+    test::setup();
+
+    // The window exceeds `retry_misses_after`, so all negative entries are revalidated.
+    let config = Config {
+        cache_dir: Some(test::tempdir().path().to_path_buf()),
+        caches: CacheConfigs {
+            revalidation: RevalidationConfig {
+                window: Some(Duration::from_secs(2 * 3600)),
+                max_concurrent: 1,
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::from(CacheConfigs::default().derived),
+        Arc::new(AtomicIsize::new(1)),
+        1024,
+    )
+    .unwrap();
+    let cache_dir = cache.cache_dir.clone().unwrap();
+    let cacher = Cacher::new(cache, Default::default());
+
+    let keys = &["global/1", "global/2", "global/3"];
+    let request = TestCacheItem::new();
+
+    for key in keys {
+        let key = CacheKey::for_testing(*key);
+
+        let cache_file = cache_dir.join(key.cache_path(1));
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(cache_file, "").unwrap();
+
+        let result = cacher.compute_memoized(request.clone(), key).await;
+        assert_eq!(result, Err(CacheError::NotFound));
+    }
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // only one revalidation fits into the budget
+    assert_eq!(request.computations.load(Ordering::SeqCst), 1);
+
+    let result = cacher
+        .compute_memoized(request.clone(), CacheKey::for_testing("global/1"))
+        .await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+}
+
+#[tokio::test]
+async fn test_refresh_ahead() {
+    test::setup();
+
+    let config = Config {
+        cache_dir: Some(test::tempdir().path().to_path_buf()),
+        caches: CacheConfigs {
+            expiry: ExpiryConfig {
+                refresh_ahead: Some(Duration::from_secs(3600)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cache = Cache::from_config(
+        CacheName::Objects,
+        &config,
+        CacheConfig::Derived(DerivedCacheConfig {
+            retry_misses_after: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        }),
+        Default::default(),
+        1024,
+    )
+    .unwrap();
+    let cache_dir = cache.cache_dir.clone().unwrap();
+    let cacher = Cacher::new(cache, Default::default());
+    let request = TestCacheItem::new();
+
+    // The first entry expired within the `refresh_ahead` window, the second one before it.
+    let write_expired = |key: &CacheKey, age: Duration| {
+        let cache_file = cache_dir.join(key.cache_path(1));
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(&cache_file, "").unwrap();
+        let mtime = FileTime::from_system_time(SystemTime::now() - age);
+        filetime::set_file_mtime(&cache_file, mtime).unwrap();
+    };
+    let stale = CacheKey::for_testing("global/stale");
+    write_expired(&stale, Duration::from_secs(5400));
+    let expired = CacheKey::for_testing("global/expired");
+    write_expired(&expired, Duration::from_secs(3 * 3600));
+
+    // The stale entry is served while it is refreshed in the background.
+    let result = cacher
+        .compute_memoized(request.clone(), stale.clone())
+        .await;
+    assert_eq!(result, Err(CacheError::NotFound));
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(request.computations.load(Ordering::SeqCst), 1);
+    let result = cacher.compute_memoized(request.clone(), stale).await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+
+    // Entries which expired before the window are recomputed right away.
+    let result = cacher.compute_memoized(request.clone(), expired).await;
+    assert_eq!(result.unwrap().as_str(), "some new cached contents");
+    assert_eq!(request.computations.load(Ordering::SeqCst), 2);
+}
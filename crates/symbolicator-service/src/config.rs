@@ -1,19 +1,21 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fmt;
 use std::fs;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
 use sentry::types::Dsn;
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use tracing::level_filters::LevelFilter;
+use url::Url;
 
 use symbolicator_sources::SourceConfig;
 
-use crate::caching::SharedCacheConfig;
+use crate::caching::{CacheName, SharedCacheConfig};
 
 /// Controls the log format
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize)]
@@ -118,6 +120,496 @@ impl Default for Metrics {
     }
 }
 
+/// Controls how downloaded files are decompressed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DecompressionConfig {
+    /// The maximum size in bytes a downloaded file may decompress to.
+    ///
+    /// This protects the cache volume against decompression bombs. Files exceeding this limit
+    /// are rejected. A value of `None` disables the limit.
+    pub max_decompressed_size: Option<u64>,
+
+    /// Whether to fall back to an external tool for CAB files which cannot be extracted natively.
+    ///
+    /// CAB files are extracted in-process. Some rarely used CAB features, such as Quantum
+    /// compression, are not supported natively. If this is enabled, such files are handed to
+    /// the [`cab_tool`](ExternalToolsConfig::cab_tool).
+    ///
+    /// Defaults to `false`.
+    pub cab_external_fallback: bool,
+
+    /// Controls how external decompression tools are run.
+    pub external_tools: ExternalToolsConfig,
+
+    /// Whether gzip, zlib and zstd compressed files are decompressed while being downloaded.
+    ///
+    /// This avoids writing the compressed file to disk only to read it back and decompress it
+    /// in a second pass. Other formats are always decompressed after the download finished.
+    ///
+    /// Defaults to `true`.
+    pub streaming: bool,
+
+    /// The number of dedicated threads decompressing downloaded files.
+    ///
+    /// Files are decompressed one at a time per thread, further files are queued.
+    ///
+    /// Defaults to the number of available CPU cores.
+    pub workers: usize,
+}
+
+impl Default for DecompressionConfig {
+    fn default() -> Self {
+        Self {
+            // The largest PDB files we have observed in the wild are a couple of GB in size.
+            max_decompressed_size: Some(16 * 1024 * 1024 * 1024),
+            cab_external_fallback: false,
+            external_tools: ExternalToolsConfig::default(),
+            streaming: true,
+            workers: std::thread::available_parallelism().map_or(4, |n| n.get()),
+        }
+    }
+}
+
+/// Controls how very large files are downloaded from HTTP sources.
+///
+/// Chunked downloads fetch multiple ranges of a file concurrently, which helps when a single
+/// connection can not saturate the available bandwidth. The ranges are written to the file in
+/// order, so up to `chunk_size * concurrency` bytes are held in memory per download.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChunkedDownloadConfig {
+    /// Whether large files are downloaded in chunks.
+    ///
+    /// This requires the server to support `Range` requests, and to announce an `ETag` or
+    /// `Last-Modified` date for the file. Other files are downloaded over a single connection.
+    ///
+    /// Defaults to `false`.
+    pub enabled: bool,
+
+    /// The size in bytes from which on files are downloaded in chunks.
+    ///
+    /// Defaults to 1 GiB.
+    pub min_size: u64,
+
+    /// The size in bytes of a single chunk.
+    ///
+    /// Defaults to 16 MiB.
+    pub chunk_size: u64,
+
+    /// The number of chunks downloaded concurrently.
+    ///
+    /// Defaults to `4`.
+    pub concurrency: usize,
+}
+
+impl Default for ChunkedDownloadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_size: 1024 * 1024 * 1024,
+            chunk_size: 16 * 1024 * 1024,
+            concurrency: 4,
+        }
+    }
+}
+
+/// Controls how SymCaches of huge modules are split by address range.
+///
+/// The SymCache of a sharded module is computed and loaded in shards of a fixed size, of which
+/// only the ones covering the addresses in a request are used.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SymCacheShardingConfig {
+    /// Whether SymCaches of huge modules are sharded.
+    ///
+    /// Defaults to `false`.
+    pub enabled: bool,
+
+    /// The image size in bytes from which on modules are sharded.
+    ///
+    /// Modules without an image size are never sharded. Defaults to 256 MiB.
+    pub min_image_size: u64,
+
+    /// The size in bytes of the address range covered by a single shard.
+    ///
+    /// Defaults to 16 MiB.
+    pub shard_size: u64,
+}
+
+impl Default for SymCacheShardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_image_size: 256 * 1024 * 1024,
+            shard_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// How far stackwalking falls back when a module has no call frame information (CFI).
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnwindingStrategy {
+    /// Only frames recovered from the CPU context or from CFI are kept.
+    ///
+    /// The stack trace ends at the first frame which would need another method.
+    CfiOnly,
+    /// Frames recovered from frame pointers are kept in addition to CFI frames.
+    FramePointer,
+    /// Frames found by scanning the stack for return addresses are kept as well.
+    #[default]
+    Scan,
+}
+
+/// Controls which unwinding methods produce the frames of a stackwalked thread.
+///
+/// The method with which a frame was recovered is reported in its `trust` field.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct UnwindingOptions {
+    /// The fallback used for frames without CFI.
+    ///
+    /// Defaults to `scan`.
+    pub strategy: UnwindingStrategy,
+
+    /// The maximum number of frames found by stack scanning in a single thread.
+    ///
+    /// The stack trace ends once this many scanned frames have been kept. Only applies to
+    /// the `scan` strategy, and defaults to `None`, which does not limit scanned frames.
+    pub max_scanned_frames: Option<usize>,
+}
+
+/// Controls how the threads of minidumps and other crash reports are unwound.
+///
+/// Individual requests can override these options with their own.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct UnwindingConfig {
+    /// The unwinding options used for all scopes without their own.
+    #[serde(flatten)]
+    pub defaults: UnwindingOptions,
+
+    /// The unwinding options of individual scopes, like `global`.
+    pub scopes: HashMap<String, UnwindingOptions>,
+}
+
+impl UnwindingConfig {
+    /// Returns the unwinding options for the given scope.
+    pub fn for_scope(&self, scope: &str) -> UnwindingOptions {
+        self.scopes.get(scope).copied().unwrap_or(self.defaults)
+    }
+}
+
+/// Controls how pointer authentication codes are stripped from the addresses of frames.
+///
+/// On `arm64e`, return addresses carry a pointer authentication code (PAC) in their upper bits,
+/// and top byte ignore (TBI) allows tags in the top byte of pointers. Neither is part of the
+/// address itself, so both are stripped before frames are matched to modules.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PointerAuthConfig {
+    /// The architectures whose addresses are stripped, like `arm64e`.
+    ///
+    /// Defaults to `arm64` and `arm64e`, since crash reports of `arm64e` processes often only
+    /// declare `arm64`.
+    pub architectures: Vec<String>,
+
+    /// The number of significant address bits of individual architectures.
+    ///
+    /// Architectures without an entry keep as many bits as are needed to address all modules of
+    /// the crash report.
+    pub address_bits: HashMap<String, u32>,
+}
+
+impl PointerAuthConfig {
+    /// Returns whether addresses of the given architecture are stripped.
+    pub fn is_enabled(&self, arch: &str) -> bool {
+        self.architectures.iter().any(|enabled| enabled == arch)
+    }
+}
+
+impl Default for PointerAuthConfig {
+    fn default() -> Self {
+        Self {
+            architectures: vec!["arm64".to_owned(), "arm64e".to_owned()],
+            address_bits: HashMap::new(),
+        }
+    }
+}
+
+/// Where the [audit log](Config::audit_log) of downloads is written to.
+///
+/// Every record is a JSON object on its own line.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AuditLogConfig {
+    /// Appends the records to a file.
+    File {
+        /// The path of the file, which is created if it does not exist.
+        path: PathBuf,
+    },
+    /// Sends batches of records to an HTTP endpoint.
+    Http {
+        /// The URL the records are `POST`ed to.
+        url: Url,
+    },
+}
+
+/// Controls the circuit breakers which stop downloads from failing sources.
+///
+/// Every source has its own circuit breaker, which opens once too many downloads from the
+/// source fail. While it is open, no downloads from the source are attempted. After
+/// `open_duration`, it lets a few probe downloads through, and closes again if all of them
+/// succeed. Downloads count as failed if they time out or fail with a download error, files
+/// which are not found or not accessible do not count.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Whether circuit breakers are enabled.
+    ///
+    /// Defaults to `false`.
+    pub enabled: bool,
+
+    /// The time window in which the failure rate of a source is measured.
+    ///
+    /// Defaults to `60s`.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+
+    /// The minimum number of downloads in the time window before the circuit breaker opens.
+    ///
+    /// Defaults to `20`.
+    pub min_downloads: usize,
+
+    /// The ratio of failed downloads in the time window at which the circuit breaker opens.
+    ///
+    /// Defaults to `0.5`.
+    pub failure_rate: f64,
+
+    /// How long the circuit breaker stays open before probing the source again.
+    ///
+    /// Defaults to `30s`.
+    #[serde(with = "humantime_serde")]
+    pub open_duration: Duration,
+
+    /// The number of probe downloads which have to succeed to close the circuit breaker.
+    ///
+    /// Defaults to `3`.
+    pub half_open_probes: usize,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: Duration::from_secs(60),
+            min_downloads: 20,
+            failure_rate: 0.5,
+            open_duration: Duration::from_secs(30),
+            half_open_probes: 3,
+        }
+    }
+}
+
+/// Controls how source files referenced by SourceLink are fetched.
+///
+/// Portable PDBs map the paths of their source files to URLs, usually on GitHub or Azure
+/// DevOps. These files are fetched for source context if the sources are not embedded in a
+/// debug file or source bundle.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SourceLinkConfig {
+    /// Whether source files referenced by SourceLink are fetched.
+    ///
+    /// Defaults to `true`.
+    pub enabled: bool,
+
+    /// The origins that source files may be fetched from.
+    ///
+    /// These are patterns like in [`ScrapingConfig::allowed_origins`](crate::types::ScrapingConfig).
+    /// Defaults to `["*"]`, which allows all origins.
+    pub allowed_origins: Vec<String>,
+
+    /// Credentials sent along with requests for source files.
+    pub credentials: Vec<SourceLinkCredentials>,
+}
+
+impl Default for SourceLinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowed_origins: vec!["*".to_owned()],
+            credentials: Vec::new(),
+        }
+    }
+}
+
+/// Headers which authenticate the requests for source files from some origins.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SourceLinkCredentials {
+    /// The origins the headers are sent to, as patterns like in
+    /// [`SourceLinkConfig::allowed_origins`].
+    pub origins: Vec<String>,
+
+    /// The headers, like `Authorization`.
+    pub headers: BTreeMap<String, String>,
+}
+
+/// The IP family used for connections, see [`DnsConfig::ip_family`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    /// Addresses are tried in the order they are resolved in.
+    #[default]
+    Any,
+    /// IPv4 addresses are tried first, IPv6 addresses only if connecting via IPv4 is slow or
+    /// fails.
+    PreferIpv4,
+    /// IPv6 addresses are tried first, IPv4 addresses only if connecting via IPv6 is slow or
+    /// fails.
+    PreferIpv6,
+    /// Only IPv4 addresses are used.
+    Ipv4Only,
+    /// Only IPv6 addresses are used.
+    Ipv6Only,
+}
+
+impl IpFamily {
+    /// Whether addresses of this family may be connected to.
+    pub fn allows(self, ip: IpAddr) -> bool {
+        match self {
+            IpFamily::Ipv4Only => ip.is_ipv4(),
+            IpFamily::Ipv6Only => ip.is_ipv6(),
+            _ => true,
+        }
+    }
+}
+
+/// Controls how the hosts of sources are resolved.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// The IP family used for connections to hosts with both IPv4 and IPv6 addresses.
+    ///
+    /// Connections are attempted for both families concurrently ("Happy Eyeballs"), starting
+    /// with the family of the first address. Defaults to `any`.
+    pub ip_family: IpFamily,
+
+    /// Static addresses of hosts, which are used instead of resolving them.
+    ///
+    /// Defaults to no overrides.
+    pub overrides: BTreeMap<String, Vec<IpAddr>>,
+}
+
+/// Controls whether sources with a lower priority are queried once a file has been found.
+///
+/// Sources are grouped by their priority, and the groups are queried one after the other,
+/// starting with the highest priority. Sources within a group are always queried concurrently.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceLookupPolicy {
+    /// All sources are queried concurrently, and the best file is used.
+    #[default]
+    All,
+    /// Sources with a lower priority are only queried until a file of the best possible
+    /// quality has been found, eg. one with unwind information for stack walking.
+    BestMatch,
+    /// Sources with a lower priority are only queried until any usable file has been found.
+    FirstMatch,
+}
+
+/// Controls how external decompression tools are run.
+///
+/// Tools which exceed any of the limits are killed, and their partial output is removed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ExternalToolsConfig {
+    /// The tool used to extract CAB files which cannot be extracted natively.
+    ///
+    /// This is looked up on the `PATH` unless it is an absolute path.
+    ///
+    /// Defaults to `cabextract`, or `expand` on Windows.
+    pub cab_tool: PathBuf,
+
+    /// The maximum wall-clock time an external tool may run for.
+    ///
+    /// Defaults to `60s`.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+
+    /// The maximum size in bytes of the output of an external tool.
+    ///
+    /// This applies in addition to the
+    /// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size). A value of `None`
+    /// disables this limit.
+    ///
+    /// Defaults to `None`.
+    pub max_output_size: Option<u64>,
+
+    /// Restricts the resources available to external tools.
+    pub sandbox: SandboxConfig,
+}
+
+impl Default for ExternalToolsConfig {
+    fn default() -> Self {
+        let cab_tool = if cfg!(target_os = "windows") {
+            "expand"
+        } else {
+            "cabextract"
+        };
+
+        Self {
+            cab_tool: cab_tool.into(),
+            timeout: Duration::from_secs(60),
+            max_output_size: None,
+            sandbox: SandboxConfig::default(),
+        }
+    }
+}
+
+/// Restricts the resources available to external decompression tools.
+///
+/// On Unix, the limits are enforced using resource limits, and on Linux, a seccomp filter
+/// additionally denies the tools access to the network and to other processes. On Windows,
+/// the tools are run in a job object, which does not support the
+/// [`max_file_size`](Self::max_file_size).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /// Whether external tools are run in the sandbox.
+    ///
+    /// Defaults to `false`.
+    pub enabled: bool,
+
+    /// The maximum memory in bytes an external tool may use.
+    ///
+    /// Defaults to 1 GiB.
+    pub max_memory: Option<u64>,
+
+    /// The maximum CPU time an external tool may use.
+    ///
+    /// Defaults to `60s`.
+    #[serde(with = "humantime_serde")]
+    pub max_cpu_time: Option<Duration>,
+
+    /// The maximum size in bytes of any file an external tool writes.
+    ///
+    /// Defaults to `None`.
+    pub max_file_size: Option<u64>,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_memory: Some(1024 * 1024 * 1024),
+            max_cpu_time: Some(Duration::from_secs(60)),
+            max_file_size: None,
+        }
+    }
+}
+
 /// Fine-tuning downloaded cache expiry.
 ///
 /// These differ from [`DerivedCacheConfig`] in the [`Default`] implementation.
@@ -129,15 +621,38 @@ pub struct DownloadedCacheConfig {
     pub max_unused_for: Option<Duration>,
 
     /// Maximum duration since creation of negative cache item (item age).
+    ///
+    /// This applies to files which were not found, and to all errors without a more specific
+    /// setting below.
     #[serde(with = "humantime_serde")]
     pub retry_misses_after: Option<Duration>,
 
+    /// Maximum duration since creation of cache item for a failed download (item age).
+    ///
+    /// Download errors like connection losses or 5xx responses are usually transient.
+    #[serde(with = "humantime_serde")]
+    pub retry_download_errors_after: Option<Duration>,
+
+    /// Maximum duration since creation of cache item for a timed out download (item age).
+    #[serde(with = "humantime_serde")]
+    pub retry_timeouts_after: Option<Duration>,
+
+    /// Maximum duration since creation of cache item for a download without permission (item age).
+    #[serde(with = "humantime_serde")]
+    pub retry_permission_denied_after: Option<Duration>,
+
     /// Maximum duration since creation of malformed cache item (item age).
     #[serde(with = "humantime_serde")]
     pub retry_malformed_after: Option<Duration>,
 
     /// Maximum number of lazy re-downloads
     pub max_lazy_redownloads: isize,
+
+    /// Maximum total size in bytes of the files of a cache.
+    ///
+    /// Once exceeded, the least recently used files are evicted. This applies to every cache of
+    /// the category separately.
+    pub max_size: Option<u64>,
 }
 
 impl Default for DownloadedCacheConfig {
@@ -145,8 +660,12 @@ impl Default for DownloadedCacheConfig {
         Self {
             max_unused_for: Some(Duration::from_secs(3600 * 24)),
             retry_misses_after: Some(Duration::from_secs(3600)),
+            retry_download_errors_after: Some(Duration::from_secs(300)),
+            retry_timeouts_after: Some(Duration::from_secs(300)),
+            retry_permission_denied_after: Some(Duration::from_secs(3600)),
             retry_malformed_after: Some(Duration::from_secs(3600 * 24)),
             max_lazy_redownloads: 50,
+            max_size: None,
         }
     }
 }
@@ -162,15 +681,38 @@ pub struct DerivedCacheConfig {
     pub max_unused_for: Option<Duration>,
 
     /// Maximum duration since creation of negative cache item (item age).
+    ///
+    /// This applies to files which were not found, and to all errors without a more specific
+    /// setting below.
     #[serde(with = "humantime_serde")]
     pub retry_misses_after: Option<Duration>,
 
+    /// Maximum duration since creation of cache item for a failed download (item age).
+    ///
+    /// Download errors like connection losses or 5xx responses are usually transient.
+    #[serde(with = "humantime_serde")]
+    pub retry_download_errors_after: Option<Duration>,
+
+    /// Maximum duration since creation of cache item for a timed out download (item age).
+    #[serde(with = "humantime_serde")]
+    pub retry_timeouts_after: Option<Duration>,
+
+    /// Maximum duration since creation of cache item for a download without permission (item age).
+    #[serde(with = "humantime_serde")]
+    pub retry_permission_denied_after: Option<Duration>,
+
     /// Maximum duration since creation of malformed cache item (item age).
     #[serde(with = "humantime_serde")]
     pub retry_malformed_after: Option<Duration>,
 
     /// Maximum number of lazy re-computations
     pub max_lazy_recomputations: isize,
+
+    /// Maximum total size in bytes of the files of a cache.
+    ///
+    /// Once exceeded, the least recently used files are evicted. This applies to every cache of
+    /// the category separately.
+    pub max_size: Option<u64>,
 }
 
 impl Default for DerivedCacheConfig {
@@ -178,8 +720,12 @@ impl Default for DerivedCacheConfig {
         Self {
             max_unused_for: Some(Duration::from_secs(3600 * 24 * 7)),
             retry_misses_after: Some(Duration::from_secs(3600)),
+            retry_download_errors_after: Some(Duration::from_secs(300)),
+            retry_timeouts_after: Some(Duration::from_secs(300)),
+            retry_permission_denied_after: Some(Duration::from_secs(3600)),
             retry_malformed_after: Some(Duration::from_secs(3600 * 24)),
             max_lazy_recomputations: 20,
+            max_size: None,
         }
     }
 }
@@ -191,16 +737,108 @@ pub struct DiagnosticsCacheConfig {
     /// Time to keep diagnostics files cached.
     #[serde(with = "humantime_serde")]
     pub retention: Option<Duration>,
+
+    /// Maximum total size in bytes of the diagnostics files.
+    ///
+    /// Once exceeded, the least recently used files are evicted.
+    pub max_size: Option<u64>,
 }
 
 impl Default for DiagnosticsCacheConfig {
     fn default() -> Self {
         Self {
             retention: Some(Duration::from_secs(3600 * 24)),
+            max_size: None,
         }
     }
 }
 
+/// Fine-tuning the expiration of negative cache items.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ExpiryConfig {
+    /// Fraction by which the expiration of negative and malformed cache items is extended at
+    /// random, between `0` and `1`.
+    ///
+    /// With a jitter of `0.1`, items which are retried after one hour are retried after 60 to 66
+    /// minutes instead. This spreads out the retries of items which were created together.
+    /// Defaults to `0`.
+    pub jitter: f64,
+
+    /// Duration after their expiration in which negative cache items are still served, while
+    /// they are refreshed in the background.
+    ///
+    /// Disabled by default, in which case requests wait for the recomputation of expired items.
+    #[serde(with = "humantime_serde")]
+    pub refresh_ahead: Option<Duration>,
+}
+
+/// Fine-tuning the background revalidation of negative cache items.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct RevalidationConfig {
+    /// Duration before their expiration in which negative cache items are revalidated.
+    ///
+    /// Negative items which are used within this duration of their expiration are recomputed in
+    /// the background, so that no request has to wait for them once they expire. Disabled by
+    /// default.
+    #[serde(with = "humantime_serde")]
+    pub window: Option<Duration>,
+
+    /// Maximum number of concurrent revalidations of every cache.
+    pub max_concurrent: isize,
+}
+
+impl Default for RevalidationConfig {
+    fn default() -> Self {
+        Self {
+            window: None,
+            max_concurrent: 10,
+        }
+    }
+}
+
+/// Fine-tuning the cleanup of caches.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct CleanupConfig {
+    /// Number of threads cleaning up the directories of a cache in parallel.
+    pub threads: usize,
+
+    /// Maximum number of files checked per second while cleaning up a cache.
+    ///
+    /// This limits the I/O load of the cleanup on the cache volume. Defaults to no limit.
+    pub max_files_per_second: Option<u32>,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            threads: 4,
+            max_files_per_second: None,
+        }
+    }
+}
+
+/// Partitioning of caches by scope, with byte quotas for every scope.
+#[derive(Debug, Clone, Default, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct CachePartitionConfig {
+    /// The caches which store their files partitioned by scope, like `symcaches`.
+    ///
+    /// Files which have been written before a cache was partitioned are no longer found.
+    pub caches: Vec<CacheName>,
+
+    /// Maximum total size in bytes of the files of every scope within a partitioned cache.
+    ///
+    /// Once exceeded, the least recently used files of the scope are evicted. This does not
+    /// apply to the `global` scope, which is only limited if listed in `max_scope_sizes`.
+    pub max_scope_size: Option<u64>,
+
+    /// Maximum total sizes in bytes of the files of individual scopes, like `global`.
+    pub max_scope_sizes: HashMap<String, u64>,
+}
+
 /// Struct to treat all cache configs identical in cache code.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CacheConfig {
@@ -226,6 +864,30 @@ impl CacheConfig {
         }
     }
 
+    pub fn retry_download_errors_after(&self) -> Option<Duration> {
+        match self {
+            Self::Downloaded(cfg) => cfg.retry_download_errors_after,
+            Self::Derived(cfg) => cfg.retry_download_errors_after,
+            Self::Diagnostics(_cfg) => None,
+        }
+    }
+
+    pub fn retry_timeouts_after(&self) -> Option<Duration> {
+        match self {
+            Self::Downloaded(cfg) => cfg.retry_timeouts_after,
+            Self::Derived(cfg) => cfg.retry_timeouts_after,
+            Self::Diagnostics(_cfg) => None,
+        }
+    }
+
+    pub fn retry_permission_denied_after(&self) -> Option<Duration> {
+        match self {
+            Self::Downloaded(cfg) => cfg.retry_permission_denied_after,
+            Self::Derived(cfg) => cfg.retry_permission_denied_after,
+            Self::Diagnostics(_cfg) => None,
+        }
+    }
+
     pub fn retry_malformed_after(&self) -> Option<Duration> {
         match self {
             Self::Downloaded(cfg) => cfg.retry_malformed_after,
@@ -233,6 +895,14 @@ impl CacheConfig {
             Self::Diagnostics(_cfg) => None,
         }
     }
+
+    pub fn max_size(&self) -> Option<u64> {
+        match self {
+            Self::Downloaded(cfg) => cfg.max_size,
+            Self::Derived(cfg) => cfg.max_size,
+            Self::Diagnostics(cfg) => cfg.max_size,
+        }
+    }
 }
 
 impl From<DownloadedCacheConfig> for CacheConfig {
@@ -286,6 +956,66 @@ pub struct InMemoryCacheConfig {
     /// Defaults to `100`.
     pub gcs_token_capacity: u64,
 
+    /// Capacity for the Azure OAuth token cache.
+    ///
+    /// This number defines the size of the internal cache for Azure OAuth tokens of service
+    /// principals and should be higher than expected concurrency across Azure containers. If this
+    /// number is too low, the downloader will re-authenticate between every request.
+    ///
+    /// The cache is keyed by Azure source keys.
+    ///
+    /// This can be monitored with the `source.azure.token.access` and
+    /// `source.azure.token.computation` counter metrics.
+    ///
+    /// Defaults to `100`.
+    pub azure_token_capacity: u64,
+
+    /// Capacity for the OAuth2 token cache of HTTP sources.
+    ///
+    /// This number defines the size of the internal cache for tokens obtained with the OAuth2
+    /// client credentials flow and should be higher than the number of distinct credentials
+    /// configured across HTTP sources. If this number is too low, the downloader will
+    /// re-authenticate between every request.
+    ///
+    /// The cache is keyed by the OAuth2 client credentials.
+    ///
+    /// This can be monitored with the `source.http.oauth2.token.access` and
+    /// `source.http.oauth2.token.computation` counter metrics.
+    ///
+    /// Defaults to `100`.
+    pub http_oauth2_token_capacity: u64,
+
+    /// Capacity for the cache of HTTP clients with source-specific settings.
+    ///
+    /// HTTP sources with custom TLS settings, such as client certificates, or with their own proxy
+    /// need a dedicated client. This number should be higher than the number of distinct settings
+    /// configured across HTTP sources. If this number is too low, connections can not be reused.
+    ///
+    /// This can be monitored with the `source.http.client.access` and
+    /// `source.http.client.computation` counter metrics.
+    ///
+    /// Defaults to `100`.
+    pub http_client_capacity: u64,
+
+    /// Capacity for the debuginfod negative cache.
+    ///
+    /// This cache remembers which files were not found on which debuginfod server, so that the
+    /// servers of a debuginfod source are not asked for the same missing build-id over and over
+    /// again.
+    ///
+    /// The cache is keyed by the full request URL.
+    ///
+    /// Defaults to `100_000`.
+    pub debuginfod_negative_capacity: u64,
+
+    /// The TTL for debuginfod negative cache entries.
+    ///
+    /// After this time, servers are asked again for files they did not have before.
+    ///
+    /// Defaults to `1h`.
+    #[serde(with = "humantime_serde")]
+    pub debuginfod_negative_ttl: Duration,
+
     /// Capacity for the S3 Client Cache.
     ///
     /// This number defines the size of the internal cache for S3 clients and should be higher than
@@ -307,6 +1037,17 @@ pub struct InMemoryCacheConfig {
     /// Defaults to `100 MiB (= 104_857_600)`.
     pub object_meta_capacity: u64,
 
+    /// Capacity (in bytes) for the in-memory `symcaches` Cache.
+    ///
+    /// This keeps parsed SymCaches of frequently used modules alive across requests, so they
+    /// do not need to be opened again. The size of an item is the size of its file, which is
+    /// memory-mapped and thus counts towards *virtual memory*.
+    ///
+    /// The in-memory size limit is a best-effort approximation, and not an exact limit.
+    ///
+    /// Defaults to `1 GiB (= 1_073_741_824)`.
+    pub symcaches_capacity: u64,
+
     /// Capacity (in bytes) for the in-memory `cficaches` Cache.
     ///
     /// The in-memory size limit is a best-effort approximation, and not an exact limit.
@@ -329,8 +1070,14 @@ impl Default for InMemoryCacheConfig {
             sentry_index_capacity: 100_000.try_into().unwrap(),
             sentry_index_ttl: Duration::from_secs(3600),
             gcs_token_capacity: 100.try_into().unwrap(),
+            azure_token_capacity: 100,
+            http_oauth2_token_capacity: 100,
+            http_client_capacity: 100,
+            debuginfod_negative_capacity: 100_000,
+            debuginfod_negative_ttl: Duration::from_secs(3600),
             s3_client_capacity: 100,
             object_meta_capacity: 100 * meg,
+            symcaches_capacity: 1024 * meg,
             cficaches_capacity: 400 * meg,
             // NOTE: JS symbolication is very sensitive to this cache size.
             // We noticed a significant reduction in CPU usage with a cache size of ~2G, which
@@ -354,6 +1101,35 @@ pub struct CacheConfigs {
 
     /// Configuration of various in-memory caches.
     pub in_memory: InMemoryCacheConfig,
+
+    /// Configure the background revalidation of negative cache items.
+    pub revalidation: RevalidationConfig,
+
+    /// Configure the expiration of negative cache items.
+    pub expiry: ExpiryConfig,
+
+    /// Configure the partitioning of caches by scope.
+    pub partitions: CachePartitionConfig,
+
+    /// Configure the cleanup of caches.
+    pub cleanup: CleanupConfig,
+
+    /// Maximum number of cache files held open by the in-memory caches at once.
+    ///
+    /// Once exceeded, the least recently used items of the in-memory caches are dropped, which
+    /// closes their files. Defaults to no limit.
+    pub max_open_files: Option<usize>,
+
+    /// The caches which store their files compressed with zstd, like `symcaches`.
+    ///
+    /// Compressed files are decompressed into memory when they are used, up to the
+    /// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size).
+    pub compressed: Vec<CacheName>,
+
+    /// The caches which store identical files only once, like `objects`.
+    ///
+    /// Existing files can be migrated with the `symbolicator cache dedup` command.
+    pub deduplicated: Vec<CacheName>,
 }
 
 /// See docs/index.md for more information on config values.
@@ -391,9 +1167,84 @@ pub struct Config {
     /// Default list of sources and the sources used for proxy mode.
     pub sources: Arc<[SourceConfig]>,
 
+    /// How often the configuration file is checked for changes to the `sources`.
+    ///
+    /// Sources are also reloaded when the process receives `SIGHUP`. Defaults to `None`, which
+    /// only reloads on `SIGHUP`.
+    #[serde(with = "humantime_serde")]
+    pub sources_reload_interval: Option<Duration>,
+
+    /// The interval in which S3 and GCS sources with a `canary` file are probed.
+    ///
+    /// Each probe reports the `source.healthy` metric. Defaults to `None`, in which case sources
+    /// are only probed through the `/sources/health` endpoint.
+    #[serde(with = "humantime_serde")]
+    pub source_health_interval: Option<Duration>,
+
+    /// The token authenticating requests to the admin endpoints, like purging caches.
+    ///
+    /// Requests need to send it as `Authorization: Bearer <token>`. Defaults to `None`, which
+    /// disables the admin endpoints.
+    pub admin_token: Option<String>,
+
     /// Allow reserved IP addresses for requests to sources.
     pub connect_to_reserved_ips: bool,
 
+    /// Hosts and IP networks which are never connected to, even with `connect_to_reserved_ips`.
+    ///
+    /// This applies to HTTP sources and scraped files, including the hosts they redirect to.
+    /// Entries are IP addresses like `169.254.169.254`, networks like `10.0.0.0/8`, or host
+    /// names. Host names starting with `*.` also block all subdomains.
+    pub blocked_destinations: Vec<String>,
+
+    /// Fine-tune decompression of downloaded files.
+    pub decompression: DecompressionConfig,
+
+    /// Fine-tune chunked downloads of very large files.
+    pub chunked_downloads: ChunkedDownloadConfig,
+
+    /// Fine-tune how SymCaches of huge modules are sharded by address range.
+    pub symcache_sharding: SymCacheShardingConfig,
+
+    /// Fine-tune how threads are unwound when stackwalking crash reports.
+    pub unwinding: UnwindingConfig,
+
+    /// Fine-tune how pointer authentication codes are stripped from the addresses of frames.
+    pub pointer_auth: PointerAuthConfig,
+
+    /// Stop downloading from sources which fail too often.
+    pub circuit_breaker: CircuitBreakerConfig,
+
+    /// Fine-tune how source files referenced by SourceLink are fetched.
+    pub source_links: SourceLinkConfig,
+
+    /// Keep a record of every download in an audit log, separate from the logs.
+    ///
+    /// Defaults to `None`, which disables the audit log.
+    pub audit_log: Option<AuditLogConfig>,
+
+    /// Whether sources with a lower priority are queried once a file has been found.
+    pub source_lookup_policy: SourceLookupPolicy,
+
+    /// Verify downloads against the checksums announced by their source.
+    ///
+    /// This is currently supported for S3 and GCS sources. Downloads with a mismatching
+    /// checksum are rejected.
+    pub verify_checksums: bool,
+
+    /// How often an interrupted download is resumed where it left off.
+    ///
+    /// This is currently supported for HTTP, S3 and GCS sources, provided the server supports
+    /// `Range` requests and announces an `ETag` or `Last-Modified` date for the file. Every
+    /// resume counts towards this budget, regardless of whether it succeeds. Downloads which
+    /// can not be resumed are retried from the start.
+    pub max_download_resumes: u32,
+
+    /// The maximum number of top frames whose variables are recovered for a request.
+    ///
+    /// Requests asking for more `frame_variables` are clamped to this number.
+    pub max_frame_variables: usize,
+
     /// The maximum timeout for downloads.
     ///
     /// This is the upper limit the download service will take for downloading from a single
@@ -416,6 +1267,9 @@ pub struct Config {
     #[serde(with = "humantime_serde")]
     pub head_timeout: Duration,
 
+    /// Fine-tune how the hosts of sources are resolved.
+    pub dns: DnsConfig,
+
     /// Whether to enable the host deny list.
     ///
     /// The host deny list temporarily blocks symbol sources when
@@ -545,12 +1399,29 @@ impl Default for Config {
             caches: CacheConfigs::default(),
             symstore_proxy: true,
             sources: Arc::from(vec![]),
+            sources_reload_interval: None,
+            source_health_interval: None,
+            admin_token: None,
             connect_to_reserved_ips: false,
+            blocked_destinations: Vec::new(),
+            decompression: DecompressionConfig::default(),
+            chunked_downloads: ChunkedDownloadConfig::default(),
+            symcache_sharding: SymCacheShardingConfig::default(),
+            unwinding: UnwindingConfig::default(),
+            pointer_auth: PointerAuthConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            source_links: SourceLinkConfig::default(),
+            audit_log: None,
+            source_lookup_policy: SourceLookupPolicy::default(),
+            verify_checksums: true,
+            max_download_resumes: 3,
+            max_frame_variables: 5,
             // We want to have a hard download timeout of 5 minutes.
             // This means a download connection needs to sustain ~6,7MB/s to download a 2GB file.
             max_download_timeout: Duration::from_secs(5 * 60),
             connect_timeout: Duration::from_secs(1),
             head_timeout: Duration::from_secs(5),
+            dns: Default::default(),
             // Allow a 4MB/s connection to download 1GB without timing out.
             streaming_timeout: Duration::from_secs(250),
             deny_list_enabled: true,
@@ -685,6 +1556,10 @@ mod tests {
             cfg.caches.downloaded.retry_malformed_after,
             Some(Duration::from_secs(3600 * 24))
         );
+        assert_eq!(
+            cfg.caches.downloaded.retry_download_errors_after,
+            Some(Duration::from_secs(300))
+        );
         assert_eq!(cfg.caches.derived, DerivedCacheConfig::default());
         assert_eq!(cfg.caches.diagnostics, DiagnosticsCacheConfig::default());
     }
@@ -736,6 +1611,159 @@ mod tests {
         assert_eq!(cfg.streaming_timeout, Duration::from_secs(0));
     }
 
+    #[test]
+    fn test_decompression_config() {
+        let cfg = Config::get(None).unwrap();
+        assert!(!cfg.decompression.cab_external_fallback);
+        assert!(cfg.decompression.streaming);
+        assert_eq!(
+            cfg.decompression.max_decompressed_size,
+            Some(16 * 1024 * 1024 * 1024)
+        );
+
+        let yaml = r#"
+            decompression:
+              cab_external_fallback: true
+              max_decompressed_size: null
+              streaming: false
+              workers: 2
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert!(cfg.decompression.cab_external_fallback);
+        assert!(!cfg.decompression.streaming);
+        assert_eq!(cfg.decompression.workers, 2);
+        assert_eq!(cfg.decompression.max_decompressed_size, None);
+    }
+
+    #[test]
+    fn test_source_link_config() {
+        let cfg = Config::get(None).unwrap();
+        assert!(cfg.source_links.enabled);
+        assert_eq!(cfg.source_links.allowed_origins, ["*"]);
+        assert!(cfg.source_links.credentials.is_empty());
+
+        let yaml = r#"
+            source_links:
+              allowed_origins: [raw.githubusercontent.com, dev.azure.com]
+              credentials:
+                - origins: [dev.azure.com]
+                  headers:
+                    Authorization: Basic dG9rZW4=
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert!(cfg.source_links.enabled);
+        assert_eq!(
+            cfg.source_links.allowed_origins,
+            ["raw.githubusercontent.com", "dev.azure.com"]
+        );
+        let credentials = &cfg.source_links.credentials[0];
+        assert_eq!(credentials.origins, ["dev.azure.com"]);
+        assert_eq!(credentials.headers["Authorization"], "Basic dG9rZW4=");
+    }
+
+    #[test]
+    fn test_external_tools_config() {
+        let cfg = Config::get(None).unwrap();
+        let tools = &cfg.decompression.external_tools;
+        assert_eq!(tools.timeout, Duration::from_secs(60));
+        assert_eq!(tools.max_output_size, None);
+
+        let yaml = r#"
+            decompression:
+              external_tools:
+                cab_tool: /opt/bin/cabextract
+                timeout: 5s
+                max_output_size: 1024
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        let tools = &cfg.decompression.external_tools;
+        assert_eq!(tools.cab_tool, Path::new("/opt/bin/cabextract"));
+        assert_eq!(tools.timeout, Duration::from_secs(5));
+        assert_eq!(tools.max_output_size, Some(1024));
+        assert!(!tools.sandbox.enabled);
+    }
+
+    #[test]
+    fn test_sandbox_config() {
+        let yaml = r#"
+            decompression:
+              external_tools:
+                sandbox:
+                  enabled: true
+                  max_memory: null
+                  max_cpu_time: 10s
+                  max_file_size: 1024
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        let sandbox = &cfg.decompression.external_tools.sandbox;
+        assert!(sandbox.enabled);
+        assert_eq!(sandbox.max_memory, None);
+        assert_eq!(sandbox.max_cpu_time, Some(Duration::from_secs(10)));
+        assert_eq!(sandbox.max_file_size, Some(1024));
+    }
+
+    #[test]
+    fn test_dns_config() {
+        let cfg = Config::get(None).unwrap();
+        assert_eq!(cfg.dns, DnsConfig::default());
+
+        let yaml = r#"
+            dns:
+              ip_family: prefer_ipv4
+              overrides:
+                symbols.example.com:
+                  - 192.0.2.1
+                  - 2001:db8::1
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert_eq!(cfg.dns.ip_family, IpFamily::PreferIpv4);
+        let addrs: Vec<IpAddr> = vec!["192.0.2.1".parse().unwrap(), "2001:db8::1".parse().unwrap()];
+        assert_eq!(cfg.dns.overrides["symbols.example.com"], addrs);
+    }
+
+    #[test]
+    fn test_unwinding_config() {
+        let cfg = Config::get(None).unwrap();
+        assert_eq!(
+            cfg.unwinding.for_scope("global"),
+            UnwindingOptions::default()
+        );
+
+        let yaml = r#"
+            unwinding:
+              strategy: frame_pointer
+              scopes:
+                "42":
+                  strategy: scan
+                  max_scanned_frames: 10
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        let options = cfg.unwinding.for_scope("global");
+        assert_eq!(options.strategy, UnwindingStrategy::FramePointer);
+        assert_eq!(options.max_scanned_frames, None);
+        let options = cfg.unwinding.for_scope("42");
+        assert_eq!(options.strategy, UnwindingStrategy::Scan);
+        assert_eq!(options.max_scanned_frames, Some(10));
+    }
+
+    #[test]
+    fn test_pointer_auth_config() {
+        let cfg = Config::get(None).unwrap();
+        assert!(cfg.pointer_auth.is_enabled("arm64e"));
+        assert!(!cfg.pointer_auth.is_enabled("x86_64"));
+
+        let yaml = r#"
+            pointer_auth:
+              architectures: [arm64e]
+              address_bits:
+                arm64e: 47
+        "#;
+        let cfg = Config::from_reader(yaml.as_bytes()).unwrap();
+        assert!(!cfg.pointer_auth.is_enabled("arm64"));
+        assert!(cfg.pointer_auth.is_enabled("arm64e"));
+        assert_eq!(cfg.pointer_auth.address_bits["arm64e"], 47);
+    }
+
     #[test]
     fn test_unknown_fields() {
         // Unknown fields should not cause failure
@@ -0,0 +1,277 @@
+//! Extraction of individual members out of multi-file archives.
+//!
+//! Some vendors publish their debug files as archives containing multiple files. Rather than
+//! extracting the whole archive, we pick the one member which matches the [`ObjectId`] that
+//! is being fetched, or which has the requested name.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+use symbolic::common::ByteView;
+use symbolic::debuginfo::{Archive, Object};
+use symbolicator_sources::ObjectId;
+use tempfile::NamedTempFile;
+
+use crate::config::DecompressionConfig;
+
+use super::compression::copy_limited;
+
+/// The maximum number of member names included in [`NoMatchingMember`].
+const MAX_REPORTED_MEMBERS: usize = 10;
+
+/// The error returned when no member of an archive matches the requested [`ObjectId`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoMatchingMember {
+    /// The names of the members that have been checked, up to [`MAX_REPORTED_MEMBERS`].
+    pub members: Vec<String>,
+    /// The total number of members that have been checked.
+    pub total: usize,
+}
+
+impl NoMatchingMember {
+    /// Returns the [`NoMatchingMember`] error wrapped in the given [`io::Error`], if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    /// A human readable description of the archive members that have been checked.
+    pub fn details(&self) -> String {
+        if self.total == 0 {
+            return "the archive does not contain any files".into();
+        }
+
+        let mut details = format!("checked {}", self.members.join(", "));
+        if self.total > self.members.len() {
+            details.push_str(&format!(" and {} more", self.total - self.members.len()));
+        }
+        details
+    }
+}
+
+impl fmt::Display for NoMatchingMember {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "archive does not contain a matching file, {}",
+            self.details()
+        )
+    }
+}
+
+impl Error for NoMatchingMember {}
+
+impl From<NoMatchingMember> for io::Error {
+    fn from(err: NoMatchingMember) -> Self {
+        io::Error::new(io::ErrorKind::NotFound, err)
+    }
+}
+
+/// Which member of an archive is extracted.
+#[derive(Debug, Clone)]
+pub enum ArchiveMember {
+    /// The first file in the archive.
+    First,
+    /// The file containing an object which matches the [`ObjectId`].
+    Object(ObjectId),
+    /// The file with the given name, in any directory of the archive.
+    ///
+    /// Names are compared case-insensitively.
+    Named(String),
+    /// The first file which is a minidump.
+    Minidump,
+}
+
+/// Decides which member of an archive should be extracted.
+#[derive(Debug)]
+pub struct MemberSelector<'a> {
+    member: &'a ArchiveMember,
+    /// The directory in which candidate members are extracted into temporary files.
+    dir: &'a Path,
+    config: &'a DecompressionConfig,
+    /// The members that have been checked so far, for diagnostics.
+    checked: NoMatchingMember,
+}
+
+impl<'a> MemberSelector<'a> {
+    pub fn new(member: &'a ArchiveMember, dir: &'a Path, config: &'a DecompressionConfig) -> Self {
+        Self {
+            member,
+            dir,
+            config,
+            checked: NoMatchingMember {
+                members: Vec::new(),
+                total: 0,
+            },
+        }
+    }
+
+    /// Extracts a single archive member, returning it if it is the one we are looking for.
+    fn check(&mut self, name: &str, reader: &mut dyn Read) -> io::Result<Option<NamedTempFile>> {
+        self.checked.total += 1;
+        if self.checked.members.len() < MAX_REPORTED_MEMBERS {
+            self.checked.members.push(name.to_owned());
+        }
+
+        if let ArchiveMember::Named(member_name) = self.member {
+            let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+            if !file_name.eq_ignore_ascii_case(member_name) {
+                // Some archive readers expect every member to be read in full.
+                copy_limited(reader, &mut io::sink(), self.config)?;
+                return Ok(None);
+            }
+        }
+
+        let mut file = NamedTempFile::new_in(self.dir)?;
+        copy_limited(reader, file.as_file_mut(), self.config)?;
+
+        let matches = match self.member {
+            ArchiveMember::First | ArchiveMember::Named(_) => return Ok(Some(file)),
+            ArchiveMember::Object(object_id) => {
+                let view = ByteView::map_file_ref(file.as_file())?;
+                match Archive::parse(&view) {
+                    Ok(archive) => archive
+                        .objects()
+                        .filter_map(Result::ok)
+                        .any(|object| object_matches_id(&object, object_id)),
+                    Err(_) => false,
+                }
+            }
+            ArchiveMember::Minidump => {
+                let view = ByteView::map_file_ref(file.as_file())?;
+                view.starts_with(b"MDMP")
+            }
+        };
+        tracing::trace!(name, matches, "Checked archive member");
+
+        Ok(matches.then_some(file))
+    }
+
+    /// Turns the result of iterating all the archive members into the extracted file.
+    fn finish(self, found: Option<NamedTempFile>) -> io::Result<NamedTempFile> {
+        found.ok_or_else(|| self.checked.into())
+    }
+}
+
+/// Extracts the selected member out of a 7z archive.
+pub fn extract_7z(src: &File, mut selector: MemberSelector<'_>) -> io::Result<NamedTempFile> {
+    let len = src.metadata()?.len();
+    let mut archive = sevenz_rust::SevenZReader::new(src, len, sevenz_rust::Password::empty())
+        .map_err(io::Error::other)?;
+
+    let mut found = None;
+    archive
+        .for_each_entries(|entry, reader| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            found = selector.check(entry.name(), reader)?;
+            // continue iterating until we found the member
+            Ok(found.is_none())
+        })
+        .map_err(io::Error::other)?;
+
+    selector.finish(found)
+}
+
+/// Extracts the selected member out of a CAB archive.
+pub fn extract_cab(src: &File, mut selector: MemberSelector<'_>) -> io::Result<NamedTempFile> {
+    let mut cabinet = cab::Cabinet::new(src)?;
+    let names: Vec<_> = cabinet
+        .folder_entries()
+        .flat_map(|folder| folder.file_entries())
+        .map(|file| file.name().to_owned())
+        .collect();
+
+    for name in names {
+        let mut reader = cabinet.read_file(&name)?;
+        if let Some(file) = selector.check(&name, &mut reader)? {
+            return Ok(file);
+        }
+    }
+
+    selector.finish(None)
+}
+
+/// Checks whether the file is a (ustar or GNU) tarball.
+///
+/// Tarballs do not have a magic at the start of the file, instead it is located within the
+/// header of the first member.
+pub fn is_tar(mut file: &File) -> io::Result<bool> {
+    let mut header = [0; 512];
+    file.rewind()?;
+    let is_tar = match file.read_exact(&mut header) {
+        Ok(()) => &header[257..262] == b"ustar",
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => false,
+        Err(err) => return Err(err),
+    };
+    file.rewind()?;
+
+    Ok(is_tar)
+}
+
+/// Extracts the selected member out of a tarball.
+pub fn extract_tar(src: &File, mut selector: MemberSelector<'_>) -> io::Result<NamedTempFile> {
+    let mut archive = tar::Archive::new(src);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path()?.display().to_string();
+        if let Some(file) = selector.check(&name, &mut entry)? {
+            return Ok(file);
+        }
+    }
+
+    selector.finish(None)
+}
+
+/// Extracts the selected member out of a zip archive.
+pub fn extract_zip(src: &File, mut selector: MemberSelector<'_>) -> io::Result<NamedTempFile> {
+    let mut archive = zip::ZipArchive::new(src)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if !entry.is_file() {
+            continue;
+        }
+
+        let name = entry.name().to_owned();
+        if let Some(file) = selector.check(&name, &mut entry)? {
+            return Ok(file);
+        }
+    }
+
+    selector.finish(None)
+}
+
+/// Validates that the object matches expected identifiers.
+pub fn object_matches_id(object: &Object<'_>, id: &ObjectId) -> bool {
+    if let Some(ref debug_id) = id.debug_id {
+        let parsed_id = object.debug_id();
+
+        // Microsoft symbol server sometimes stores updated files with a more recent
+        // (=higher) age, but resolves it for requests with lower ages as well. Thus, we
+        // need to check whether the parsed debug file fullfills the *miniumum* age bound.
+        // For example:
+        // `4A236F6A0B3941D1966B41A4FC77738C2` is reported as
+        // `4A236F6A0B3941D1966B41A4FC77738C4` from the server.
+        //                                  ^
+        return parsed_id.uuid() == debug_id.uuid() && parsed_id.appendix() >= debug_id.appendix();
+    }
+
+    if let Some(ref code_id) = id.code_id {
+        if let Some(ref object_code_id) = object.code_id() {
+            if object_code_id != code_id {
+                return false;
+            }
+        }
+    }
+
+    true
+}
@@ -0,0 +1,226 @@
+//! Support to download from Azure Blob Storage containers.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use symbolicator_sources::{AzureRemoteFile, AzureSourceKey};
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::utils::http::DownloadTimeouts;
+
+use super::Destination;
+
+/// The version of the Blob service REST API, which is required for OAuth authentication.
+const API_VERSION: &str = "2021-08-06";
+
+/// Tokens are refreshed this long before they expire.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// An LRU cache for Entra ID OAuth tokens.
+type AzureTokenCache = moka::future::Cache<Arc<AzureSourceKey>, CacheEntry<AzureToken>>;
+
+/// An OAuth token for the Azure storage API.
+#[derive(Debug, Clone)]
+struct AzureToken {
+    bearer_token: Arc<str>,
+    expires_at: Instant,
+}
+
+impl AzureToken {
+    fn is_expired(&self) -> bool {
+        self.expires_at < Instant::now()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// Downloader implementation that supports the Azure source.
+#[derive(Debug)]
+pub struct AzureDownloader {
+    token_cache: AzureTokenCache,
+    client: reqwest::Client,
+    timeouts: DownloadTimeouts,
+}
+
+impl AzureDownloader {
+    pub fn new(client: reqwest::Client, timeouts: DownloadTimeouts, token_capacity: u64) -> Self {
+        Self {
+            token_cache: AzureTokenCache::builder()
+                .max_capacity(token_capacity)
+                .build(),
+            client,
+            timeouts,
+        }
+    }
+
+    /// Resolves a valid OAuth token for a service principal.
+    ///
+    /// If the cache contains a valid token, then this token is returned. Otherwise, a new token is
+    /// requested from Entra ID and stored in the cache.
+    async fn get_token(&self, source_key: &Arc<AzureSourceKey>) -> CacheEntry<AzureToken> {
+        metric!(counter("source.azure.token.access") += 1);
+
+        let init = Box::pin(async {
+            metric!(counter("source.azure.token.computation") += 1);
+            self.request_new_token(source_key).await
+        });
+        let replace_if =
+            |entry: &CacheEntry<AzureToken>| entry.as_ref().map_or(true, |t| t.is_expired());
+
+        self.token_cache
+            .entry_by_ref(source_key)
+            .or_insert_with_if(init, replace_if)
+            .await
+            .into_value()
+    }
+
+    /// Requests a new token using the OAuth client credentials flow.
+    async fn request_new_token(&self, source_key: &AzureSourceKey) -> CacheEntry<AzureToken> {
+        let AzureSourceKey::ServicePrincipal {
+            tenant_id,
+            client_id,
+            client_secret,
+        } = source_key
+        else {
+            return Err(CacheError::InternalError);
+        };
+
+        let url = format!("https://login.microsoftonline.com/{tenant_id}/oauth2/v2.0/token");
+        let request = self.client.post(url).form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("scope", "https://storage.azure.com/.default"),
+        ]);
+
+        let requested_at = Instant::now();
+        let response = request.send().await.map_err(|err| {
+            tracing::debug!("Failed to authenticate against Azure: {}", err);
+            CacheError::DownloadError(format!("failed to send authentication request: {err}"))
+        })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            tracing::debug!("Failed to authenticate against Azure: {}", status);
+            return Err(CacheError::PermissionDenied(format!(
+                "authentication failed: {status}"
+            )));
+        }
+
+        let token = response
+            .json::<TokenResponse>()
+            .await
+            .map_err(|err| CacheError::DownloadError(format!("invalid token response: {err}")))?;
+
+        let lifetime = Duration::from_secs(token.expires_in).saturating_sub(TOKEN_EXPIRY_MARGIN);
+        Ok(AzureToken {
+            bearer_token: format!("Bearer {}", token.access_token).into(),
+            expires_at: requested_at + lifetime,
+        })
+    }
+
+    /// Downloads a source hosted on Azure Blob Storage.
+    pub async fn download_source(
+        &self,
+        source_name: &str,
+        file_source: &AzureRemoteFile,
+        destination: &mut Destination,
+    ) -> CacheEntry {
+        let mut url = file_source
+            .url()
+            .map_err(|_| CacheError::DownloadError("invalid Azure URL".into()))?;
+        tracing::debug!("Fetching from Azure: {}", url);
+
+        let source_key = &file_source.source.source_key;
+        let builder = match source_key.as_ref() {
+            AzureSourceKey::SasToken { sas_token } => {
+                url.set_query(Some(sas_token.trim_start_matches('?')));
+                self.client.get(url)
+            }
+            AzureSourceKey::ServicePrincipal { .. } => {
+                let token = self.get_token(source_key).await?;
+                tracing::debug!("Got valid Azure token");
+
+                self.client
+                    .get(url)
+                    .header("authorization", token.bearer_token.as_ref())
+                    .header("x-ms-version", API_VERSION)
+            }
+        };
+
+        super::download_reqwest(source_name, builder, &self.timeouts, destination).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use symbolicator_sources::{
+        AzureSourceConfig, CommonSourceConfig, DirectoryLayoutType, SourceId, SourceLocation,
+    };
+
+    use crate::test;
+
+    fn azure_source(endpoint: reqwest::Url, source_key: AzureSourceKey) -> Arc<AzureSourceConfig> {
+        Arc::new(AzureSourceConfig {
+            id: SourceId::new("azure-test"),
+            account: "devstoreaccount1".to_owned(),
+            container: "symbols".to_owned(),
+            prefix: "".to_owned(),
+            endpoint: Some(endpoint),
+            source_key: Arc::new(source_key),
+            files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
+        })
+    }
+
+    async fn download(file_source: &AzureRemoteFile) -> (CacheEntry, Vec<u8>) {
+        let downloader = AzureDownloader::new(reqwest::Client::new(), Default::default(), 100);
+
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let file = tokio::fs::File::create(tmpfile.path()).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
+        let download_status = downloader
+            .download_source("", file_source, &mut destination)
+            .await;
+
+        (download_status, std::fs::read(tmpfile.path()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_download_sas_token() {
+        test::setup();
+
+        // The test server serves the symbol fixtures at `/symbols/`, which matches the
+        // container name.
+        let server = test::Server::new();
+        let sas_token = AzureSourceKey::SasToken {
+            sas_token: "?sv=2022-11-02&sig=secret".into(),
+        };
+        let source = azure_source(server.url("/"), sas_token);
+
+        let file_source = AzureRemoteFile::new(source.clone(), SourceLocation::new("hello.txt"));
+        let (download_status, content) = download(&file_source).await;
+        assert_eq!(download_status, Ok(()));
+        assert_eq!(content, b"hello world\n");
+
+        // The SAS token is sent as the query string, but is not part of the URI.
+        assert_eq!(
+            server.all_hits(),
+            [("/symbols/hello.txt?sv=2022-11-02&sig=secret".to_owned(), 1)]
+        );
+        assert_eq!(
+            file_source.uri().to_string(),
+            server.url("symbols/hello.txt").to_string()
+        );
+
+        let file_source = AzureRemoteFile::new(source, SourceLocation::new("i-do-not-exist"));
+        let (download_status, _) = download(&file_source).await;
+        assert_eq!(download_status, Err(CacheError::NotFound));
+    }
+}
@@ -0,0 +1,125 @@
+//! Limits the bandwidth of downloads from a source.
+//!
+//! All downloads from a source share one [`BandwidthLimiter`]. Every downloaded chunk reserves a
+//! slot of time proportional to its size, and the download waits until its slot begins. This
+//! does not allow for bursts, but is simple and fair across concurrent downloads.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use symbolicator_sources::SourceId;
+
+/// Limiters which have not been used for this long are dropped.
+const LIMITER_IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// The maximum number of sources with a bandwidth limit.
+const LIMITER_CAPACITY: u64 = 10_000;
+
+/// Limits the bandwidth of all downloads sharing it.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_second: u64,
+    /// The point in time at which the next chunk may be downloaded.
+    next_slot: Mutex<Instant>,
+}
+
+impl BandwidthLimiter {
+    fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Reserves a slot for `len` bytes, and returns how long to wait for it.
+    fn reserve(&self, len: usize, now: Instant) -> Duration {
+        let duration = Duration::from_secs_f64(len as f64 / self.bytes_per_second as f64);
+
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let slot = (*next_slot).max(now);
+        *next_slot = slot + duration;
+        slot - now
+    }
+
+    /// Waits until `len` bytes may be downloaded.
+    pub async fn acquire(&self, len: usize) {
+        let delay = self.reserve(len, Instant::now());
+        if !delay.is_zero() {
+            metric!(timer("download.throttled") = delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// The [`BandwidthLimiter`]s of all sources with a bandwidth limit.
+#[derive(Debug)]
+pub struct BandwidthLimiters {
+    limiters: moka::sync::Cache<(SourceId, u64), Arc<BandwidthLimiter>>,
+}
+
+impl BandwidthLimiters {
+    pub fn new() -> Self {
+        Self {
+            limiters: moka::sync::Cache::builder()
+                .max_capacity(LIMITER_CAPACITY)
+                .time_to_idle(LIMITER_IDLE_TIMEOUT)
+                .build(),
+        }
+    }
+
+    /// Returns the limiter for the given source, if it has a bandwidth limit.
+    ///
+    /// Sources with the same ID but a different limit get separate limiters.
+    pub fn get(
+        &self,
+        source_id: &SourceId,
+        bytes_per_second: Option<u64>,
+    ) -> Option<Arc<BandwidthLimiter>> {
+        let bytes_per_second = bytes_per_second.filter(|&limit| limit > 0)?;
+        let key = (source_id.clone(), bytes_per_second);
+        Some(
+            self.limiters
+                .get_with(key, || Arc::new(BandwidthLimiter::new(bytes_per_second))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limiter = BandwidthLimiter::new(1000);
+        let now = Instant::now();
+
+        // The first chunk does not have to wait, subsequent ones wait for the previous ones.
+        assert_eq!(limiter.reserve(500, now), Duration::ZERO);
+        assert_eq!(limiter.reserve(1000, now), Duration::from_millis(500));
+        assert_eq!(limiter.reserve(10, now), Duration::from_millis(1500));
+
+        // Unused bandwidth does not accumulate.
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(100, later), Duration::ZERO);
+        assert_eq!(limiter.reserve(100, later), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_limiters() {
+        let limiters = BandwidthLimiters::new();
+        let id = SourceId::new("partner");
+
+        assert!(limiters.get(&id, None).is_none());
+        assert!(limiters.get(&id, Some(0)).is_none());
+
+        let limiter = limiters.get(&id, Some(1000)).unwrap();
+        assert!(Arc::ptr_eq(
+            &limiter,
+            &limiters.get(&id, Some(1000)).unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            &limiter,
+            &limiters.get(&id, Some(2000)).unwrap()
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+//! Verification of downloads against the checksums announced by their source.
+//!
+//! CDNs and caching proxies in front of symbol servers occasionally serve truncated or
+//! otherwise corrupted files. When the source tells us the checksum of the file, we verify it
+//! so that such files do not end up in our caches.
+
+use std::fmt;
+
+use base64::Engine as _;
+use md5::{Digest as _, Md5};
+
+/// A checksum of a download, as announced by its source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    Md5([u8; 16]),
+    Crc32c(u32),
+}
+
+impl Checksum {
+    /// Parses the `ETag` of an S3 object.
+    ///
+    /// The `ETag` is only the MD5 of the object for objects which have been uploaded in a
+    /// single part, and which are not encrypted using SSE-KMS or SSE-C. The caller has to
+    /// check the encryption.
+    pub fn from_s3_etag(etag: &str) -> Option<Self> {
+        let etag = etag.trim().trim_matches('"');
+        // Multipart uploads have ETags like `<md5 of part md5s>-<number of parts>`.
+        if etag.len() != 32 {
+            return None;
+        }
+
+        let mut digest = [0; 16];
+        for (byte, hex) in digest.iter_mut().zip(etag.as_bytes().chunks(2)) {
+            let hex = std::str::from_utf8(hex).ok()?;
+            *byte = u8::from_str_radix(hex, 16).ok()?;
+        }
+        Some(Self::Md5(digest))
+    }
+
+    /// Parses the `x-goog-hash` header of a GCS object, like `crc32c=n03x6A==,md5=Ojk9c3dh...`.
+    ///
+    /// The MD5 is preferred if available, as it is not present for composite objects.
+    pub fn from_goog_hash(header: &str) -> Option<Self> {
+        let mut crc32c = None;
+
+        for hash in header.split(',') {
+            let Some((algorithm, value)) = hash.trim().split_once('=') else {
+                continue;
+            };
+            let Ok(value) = base64::engine::general_purpose::STANDARD.decode(value) else {
+                continue;
+            };
+
+            match algorithm {
+                "md5" => {
+                    if let Ok(digest) = value.try_into() {
+                        return Some(Self::Md5(digest));
+                    }
+                }
+                "crc32c" => {
+                    if let Ok(crc) = value.try_into() {
+                        crc32c = Some(Self::Crc32c(u32::from_be_bytes(crc)));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        crc32c
+    }
+
+    /// Parses the base64 encoded MD5 of a `Content-MD5` header.
+    pub fn from_content_md5(header: &str) -> Option<Self> {
+        let value = base64::engine::general_purpose::STANDARD
+            .decode(header.trim())
+            .ok()?;
+        Some(Self::Md5(value.try_into().ok()?))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Md5(_) => "md5",
+            Self::Crc32c(_) => "crc32c",
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name())?;
+        match self {
+            Self::Md5(digest) => digest.iter().try_for_each(|byte| write!(f, "{byte:02x}")),
+            Self::Crc32c(crc) => write!(f, "{crc:08x}"),
+        }
+    }
+}
+
+/// Computes the checksum of a download while it is being written, to verify it at the end.
+pub struct ChecksumVerifier {
+    expected: Checksum,
+    state: HasherState,
+}
+
+enum HasherState {
+    Md5(Md5),
+    Crc32c(u32),
+}
+
+impl ChecksumVerifier {
+    pub fn new(expected: Checksum) -> Self {
+        let state = match expected {
+            Checksum::Md5(_) => HasherState::Md5(Md5::new()),
+            Checksum::Crc32c(_) => HasherState::Crc32c(0),
+        };
+        Self { expected, state }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        match &mut self.state {
+            HasherState::Md5(hasher) => hasher.update(chunk),
+            HasherState::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, chunk),
+        }
+    }
+
+    /// Verifies the checksum, returning a description of the mismatch if there is one.
+    pub fn verify(self) -> Result<(), String> {
+        let actual = match self.state {
+            HasherState::Md5(hasher) => Checksum::Md5(hasher.finalize().into()),
+            HasherState::Crc32c(crc) => Checksum::Crc32c(crc),
+        };
+
+        if actual == self.expected {
+            metric!(counter("download.checksum") += 1, "status" => "ok");
+            Ok(())
+        } else {
+            metric!(counter("download.checksum") += 1, "status" => "mismatch");
+            Err(format!("expected {}, got {}", self.expected, actual))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_s3_etag() {
+        // md5 of `hello world`
+        let checksum = Checksum::from_s3_etag("\"5eb63bbbe01eeed093cb22bb8f5acdc3\"").unwrap();
+        assert_eq!(checksum.to_string(), "md5:5eb63bbbe01eeed093cb22bb8f5acdc3");
+
+        let mut verifier = ChecksumVerifier::new(checksum);
+        verifier.update(b"hello ");
+        verifier.update(b"world");
+        assert_eq!(verifier.verify(), Ok(()));
+
+        let mut verifier = ChecksumVerifier::new(checksum);
+        verifier.update(b"hello");
+        assert_eq!(
+            verifier.verify(),
+            Err("expected md5:5eb63bbbe01eeed093cb22bb8f5acdc3, got md5:5d41402abc4b2a76b9719d911017c592".into())
+        );
+
+        // multipart upload
+        assert_eq!(
+            Checksum::from_s3_etag("\"d41d8cd98f00b204e9800998ecf8427e-2\""),
+            None
+        );
+    }
+
+    #[test]
+    fn test_goog_hash() {
+        // both for `hello world`
+        let md5 = "md5=XrY7u+Ae7tCTyyK7j1rNww==";
+        let crc32c = "crc32c=yZRlqg==";
+
+        let checksum = Checksum::from_goog_hash(&format!("{crc32c},{md5}")).unwrap();
+        assert!(matches!(checksum, Checksum::Md5(_)));
+
+        let checksum = Checksum::from_goog_hash(crc32c).unwrap();
+        assert_eq!(checksum, Checksum::Crc32c(0xc99465aa));
+
+        let mut verifier = ChecksumVerifier::new(checksum);
+        verifier.update(b"hello world");
+        assert_eq!(verifier.verify(), Ok(()));
+
+        assert_eq!(Checksum::from_goog_hash("sha1=nope"), None);
+    }
+
+    #[test]
+    fn test_content_md5() {
+        let checksum = Checksum::from_content_md5("XrY7u+Ae7tCTyyK7j1rNww==").unwrap();
+        assert_eq!(checksum.to_string(), "md5:5eb63bbbe01eeed093cb22bb8f5acdc3");
+
+        assert_eq!(Checksum::from_content_md5("yZRlqg=="), None);
+    }
+}
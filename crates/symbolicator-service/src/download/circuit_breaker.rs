@@ -0,0 +1,409 @@
+//! Circuit breakers which stop downloads from failing sources.
+//!
+//! Every source has its own [`CircuitBreaker`], which is in one of three states:
+//!
+//! - *closed*: Downloads are attempted, and their failure rate is measured.
+//! - *open*: The failure rate got too high, downloads are rejected right away.
+//! - *half-open*: The breaker has been open for long enough, and a limited number of probe
+//!   downloads are let through. If all of them succeed the breaker closes, otherwise it opens
+//!   again.
+//!
+//! See [`CircuitBreakerConfig`] for the available settings.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use symbolicator_sources::SourceId;
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::config::CircuitBreakerConfig;
+
+/// The maximum number of sources with a circuit breaker.
+const BREAKER_CAPACITY: u64 = 10_000;
+
+/// The state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed {
+        window_start: Instant,
+        downloads: usize,
+        failures: usize,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probes_started: usize,
+        probes_succeeded: usize,
+    },
+}
+
+impl State {
+    fn closed(now: Instant) -> Self {
+        Self::Closed {
+            window_start: now,
+            downloads: 0,
+            failures: 0,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Closed { .. } => "closed",
+            Self::Open { .. } => "open",
+            Self::HalfOpen { .. } => "half_open",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed { .. } => f.write_str("closed"),
+            Self::Open { .. } => f.write_str("open"),
+            Self::HalfOpen { .. } => f.write_str("half-open"),
+        }
+    }
+}
+
+/// The circuit breaker of a single source.
+#[derive(Debug)]
+struct CircuitBreaker {
+    source_id: SourceId,
+    /// The key of the source in metrics, which bounds the cardinality of custom sources.
+    metric_key: String,
+    config: Arc<CircuitBreakerConfig>,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    fn new(
+        source_id: SourceId,
+        metric_key: String,
+        config: Arc<CircuitBreakerConfig>,
+        now: Instant,
+    ) -> Self {
+        Self {
+            source_id,
+            metric_key,
+            config,
+            state: Mutex::new(State::closed(now)),
+        }
+    }
+
+    fn half_open_probes(&self) -> usize {
+        self.config.half_open_probes.max(1)
+    }
+
+    /// Checks whether a download may start.
+    ///
+    /// Returns whether the download is a probe, or the state which rejected it.
+    fn try_start(&self, now: Instant) -> Result<bool, State> {
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            State::Closed { .. } => Ok(false),
+            State::Open { until } if now < *until => Err(*state),
+            State::Open { .. } => {
+                self.transition(
+                    &mut state,
+                    State::HalfOpen {
+                        probes_started: 1,
+                        probes_succeeded: 0,
+                    },
+                );
+                Ok(true)
+            }
+            State::HalfOpen { probes_started, .. } if *probes_started < self.half_open_probes() => {
+                *probes_started += 1;
+                Ok(true)
+            }
+            State::HalfOpen { .. } => Err(*state),
+        }
+    }
+
+    /// Records the outcome of a download.
+    fn finish(&self, probe: bool, success: bool, now: Instant) {
+        let config = &self.config;
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            State::Closed {
+                window_start,
+                downloads,
+                failures,
+            } => {
+                if now.duration_since(*window_start) >= config.window {
+                    *window_start = now;
+                    *downloads = 0;
+                    *failures = 0;
+                }
+
+                *downloads += 1;
+                if !success {
+                    *failures += 1;
+                }
+
+                let failure_rate = *failures as f64 / *downloads as f64;
+                if *downloads >= config.min_downloads && failure_rate >= config.failure_rate {
+                    tracing::info!(
+                        source = self.source_id.as_str(),
+                        open_duration = %humantime::format_duration(config.open_duration),
+                        "Opening circuit breaker due to too many download failures"
+                    );
+                    let until = now + config.open_duration;
+                    self.transition(&mut state, State::Open { until });
+                }
+            }
+            State::HalfOpen {
+                probes_succeeded, ..
+            } if probe => {
+                if !success {
+                    let until = now + config.open_duration;
+                    self.transition(&mut state, State::Open { until });
+                } else {
+                    *probes_succeeded += 1;
+                    if *probes_succeeded >= self.half_open_probes() {
+                        self.transition(&mut state, State::closed(now));
+                    }
+                }
+            }
+            // Downloads which started before the state changed do not affect it anymore.
+            _ => {}
+        }
+    }
+
+    /// Gives back the slot of a probe which did not finish.
+    fn cancel_probe(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let State::HalfOpen { probes_started, .. } = &mut *state {
+            *probes_started = probes_started.saturating_sub(1);
+        }
+    }
+
+    fn transition(&self, state: &mut State, new_state: State) {
+        *state = new_state;
+        metric!(
+            counter("service.download.circuit_breaker") += 1,
+            "source" => &self.metric_key,
+            "state" => new_state.name(),
+        );
+    }
+}
+
+/// A permission to download from a source, handed out by [`CircuitBreakers::start`].
+///
+/// The outcome of the download has to be passed to [`finish`](Self::finish).
+#[derive(Debug)]
+pub struct CircuitPermit {
+    breaker: Arc<CircuitBreaker>,
+    probe: bool,
+    finished: bool,
+}
+
+impl CircuitPermit {
+    /// Records the outcome of the download.
+    pub fn finish<T>(mut self, result: &CacheEntry<T>) {
+        let success = !matches!(
+            result,
+            Err(CacheError::DownloadError(_) | CacheError::Timeout(_))
+        );
+        self.breaker.finish(self.probe, success, Instant::now());
+        self.finished = true;
+    }
+}
+
+impl Drop for CircuitPermit {
+    fn drop(&mut self) {
+        // The download has been cancelled, so another probe needs to take its place.
+        if self.probe && !self.finished {
+            self.breaker.cancel_probe();
+        }
+    }
+}
+
+/// The [`CircuitBreaker`]s of all sources.
+#[derive(Debug)]
+pub struct CircuitBreakers {
+    config: Arc<CircuitBreakerConfig>,
+    breakers: moka::sync::Cache<SourceId, Arc<CircuitBreaker>>,
+}
+
+impl CircuitBreakers {
+    /// Creates the circuit breakers, if they are enabled.
+    pub fn from_config(config: &CircuitBreakerConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        // A breaker which has not been used for that long would be closed anyway.
+        let idle_timeout = config.window.max(config.open_duration) * 2;
+        Some(Self {
+            config: Arc::new(config.clone()),
+            breakers: moka::sync::Cache::builder()
+                .max_capacity(BREAKER_CAPACITY)
+                .time_to_idle(idle_timeout)
+                .build(),
+        })
+    }
+
+    /// Checks whether a download from the given source may start.
+    ///
+    /// Metrics tag the source with its `metric_key`, as the IDs of custom sources are random.
+    /// Returns an [`Unavailable`](CacheError::Unavailable) error naming the state of the circuit
+    /// breaker if it does not. Such errors are not cached, so that downloads are attempted
+    /// again once the circuit breaker lets them through.
+    pub fn start(&self, source_id: &SourceId, metric_key: &str) -> CacheEntry<CircuitPermit> {
+        let breaker = self.breakers.get_with_by_ref(source_id, || {
+            let config = self.config.clone();
+            Arc::new(CircuitBreaker::new(
+                source_id.clone(),
+                metric_key.to_owned(),
+                config,
+                Instant::now(),
+            ))
+        });
+
+        match breaker.try_start(Instant::now()) {
+            Ok(probe) => Ok(CircuitPermit {
+                breaker,
+                probe,
+                finished: false,
+            }),
+            Err(state) => {
+                metric!(
+                    counter("service.download.circuit_breaker.rejected") += 1,
+                    "source" => metric_key,
+                    "state" => state.name(),
+                );
+                Err(CacheError::Unavailable(format!(
+                    "circuit breaker is {state}"
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn breaker(now: Instant) -> CircuitBreaker {
+        let config = CircuitBreakerConfig {
+            enabled: true,
+            window: Duration::from_secs(60),
+            min_downloads: 4,
+            failure_rate: 0.5,
+            open_duration: Duration::from_secs(30),
+            half_open_probes: 2,
+        };
+        CircuitBreaker::new(SourceId::new("test"), "http".into(), Arc::new(config), now)
+    }
+
+    fn state(breaker: &CircuitBreaker) -> &'static str {
+        breaker.state.lock().unwrap().name()
+    }
+
+    #[test]
+    fn test_opens_on_failure_rate() {
+        let now = Instant::now();
+        let breaker = breaker(now);
+
+        // Too few downloads to judge the failure rate.
+        breaker.finish(false, false, now);
+        breaker.finish(false, false, now);
+        breaker.finish(false, true, now);
+        assert_eq!(state(&breaker), "closed");
+        assert_eq!(breaker.try_start(now), Ok(false));
+
+        breaker.finish(false, true, now);
+        assert_eq!(state(&breaker), "open");
+        assert!(breaker.try_start(now).is_err());
+    }
+
+    #[test]
+    fn test_window() {
+        let now = Instant::now();
+        let breaker = breaker(now);
+
+        breaker.finish(false, false, now);
+        breaker.finish(false, false, now);
+        breaker.finish(false, false, now);
+
+        // The failures of the previous window are forgotten.
+        let later = now + Duration::from_secs(60);
+        breaker.finish(false, false, later);
+        assert_eq!(state(&breaker), "closed");
+    }
+
+    #[test]
+    fn test_half_open() {
+        let now = Instant::now();
+        let breaker = breaker(now);
+        for _ in 0..4 {
+            breaker.finish(false, false, now);
+        }
+        assert!(breaker.try_start(now).is_err());
+
+        // After the open duration, a limited number of probes are let through.
+        let now = now + Duration::from_secs(30);
+        assert_eq!(breaker.try_start(now), Ok(true));
+        assert_eq!(state(&breaker), "half_open");
+        assert_eq!(breaker.try_start(now), Ok(true));
+        assert!(breaker.try_start(now).is_err());
+
+        // A cancelled probe makes room for another one.
+        breaker.cancel_probe();
+        assert_eq!(breaker.try_start(now), Ok(true));
+
+        // Downloads which are not probes are ignored.
+        breaker.finish(false, true, now);
+        breaker.finish(true, true, now);
+        assert_eq!(state(&breaker), "half_open");
+        breaker.finish(true, true, now);
+        assert_eq!(state(&breaker), "closed");
+    }
+
+    #[test]
+    fn test_half_open_failure() {
+        let now = Instant::now();
+        let breaker = breaker(now);
+        for _ in 0..4 {
+            breaker.finish(false, false, now);
+        }
+
+        let now = now + Duration::from_secs(30);
+        assert_eq!(breaker.try_start(now), Ok(true));
+        breaker.finish(true, false, now);
+        assert_eq!(state(&breaker), "open");
+        assert!(breaker.try_start(now).is_err());
+    }
+
+    #[test]
+    fn test_circuit_breakers() {
+        assert!(CircuitBreakers::from_config(&Default::default()).is_none());
+
+        let config = CircuitBreakerConfig {
+            enabled: true,
+            min_downloads: 1,
+            ..Default::default()
+        };
+        let breakers = CircuitBreakers::from_config(&config).unwrap();
+        let id = SourceId::new("flaky");
+
+        let permit = breakers.start(&id, "http").unwrap();
+        permit.finish::<()>(&Err(CacheError::Timeout(Duration::from_secs(1))));
+
+        let error = breakers.start(&id, "http").unwrap_err();
+        assert_eq!(
+            error,
+            CacheError::Unavailable("circuit breaker is open".into())
+        );
+
+        // Other sources are not affected.
+        let other = SourceId::new("other");
+        let permit = breakers.start(&other, "http").unwrap();
+        permit.finish(&Ok(()));
+    }
+}
@@ -1,16 +1,72 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
 use std::io::{self, Read, Seek};
-use std::process::{Command, Stdio};
+use std::path::Path;
+use std::time::Instant;
+
 use tempfile::NamedTempFile;
-use tracing::{error, info};
+use tracing::info;
+
+use crate::caching::CacheError;
+use crate::config::DecompressionConfig;
+
+use super::archive::{self, ArchiveMember, MemberSelector, NoMatchingMember};
+use super::expand;
+use super::external::{self, ToolTimedOut};
+use super::worker_pool::Cancellable;
+
+/// The maximum memory in bytes the decoders of XZ and LZMA files may allocate.
+///
+/// Their headers declare the size of the dictionary, which would otherwise be allocated no matter
+/// how large it is. Files compressed with `xz -9` need 65 MiB.
+const MAX_LZMA_MEMORY: u64 = 256 * 1024 * 1024;
+
+/// The error returned when decompressing a file exceeds the
+/// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeLimitExceeded(pub u64);
+
+impl fmt::Display for SizeLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "decompressed size exceeds limit of {} bytes", self.0)
+    }
+}
+
+impl Error for SizeLimitExceeded {}
+
+impl SizeLimitExceeded {
+    /// Returns the [`SizeLimitExceeded`] error wrapped in the given [`io::Error`], if any.
+    pub fn from_io_error(err: &io::Error) -> Option<Self> {
+        err.get_ref()?.downcast_ref().copied()
+    }
+}
+
+impl From<SizeLimitExceeded> for io::Error {
+    fn from(err: SizeLimitExceeded) -> Self {
+        io::Error::other(err)
+    }
+}
 
 /// Decompresses a downloaded file.
 ///
-/// Some compression methods are implemented by spawning an external tool and can only
-/// process from a named pathname, hence we need a [`NamedTempFile`] as source.
+/// CAB files can optionally fall back to an external tool, which can only process from a
+/// named pathname, hence we need a [`NamedTempFile`] as source.
+///
+/// Archives containing multiple files are not extracted as a whole. Instead, only the selected
+/// [`ArchiveMember`] is extracted.
+///
+/// All decompression is limited to the configured
+/// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size), failing with a
+/// [`SizeLimitExceeded`] error otherwise.
 ///
 /// The passed [`NamedTempFile`] might be swapped with a fresh one in case decompression happens.
 /// That new temp file will be created in the same directory as the original one.
-pub fn maybe_decompress_file(src: &mut NamedTempFile) -> io::Result<()> {
+pub fn maybe_decompress_file(
+    src: &mut NamedTempFile,
+    config: &DecompressionConfig,
+    member: &ArchiveMember,
+) -> io::Result<()> {
     let mut file = src.as_file();
     file.sync_all()?;
 
@@ -26,85 +82,696 @@ pub fn maybe_decompress_file(src: &mut NamedTempFile) -> io::Result<()> {
     file.read_exact(&mut magic_bytes)?;
     file.rewind()?;
 
-    match magic_bytes {
-        [0x28, 0xb5, 0x2f, 0xfd] => { /* zstd logic */ }
-        [0x1f, 0x8b, _, _] => { /* gzip logic */ }
-        [0x78, 0x01, _, _] | [0x78, 0x9c, _, _] | [0x78, 0xda, _, _] => { /* zlib logic */ }
-        [0x50, 0x4b, 0x03, 0x04] => { /* zip logic */ }
-        [77, 83, 67, 70] => {
-            metric!(counter("compression") += 1, "type" => "cab");
+    let compression = match Compression::detect(magic_bytes) {
+        Some(compression) => compression,
+        None if archive::is_tar(file)? => Compression::Tar,
+        None => {
+            metric!(counter("compression") += 1, "type" => "none");
+            info!(
+                "File is not compressed, skipping decompression: {:?}",
+                src.path()
+            );
+            return Ok(());
+        }
+    };
+    metric!(counter("compression") += 1, "type" => compression.name());
 
-            let mut dst = tempfile_in_parent(src)?;
+    let started = Instant::now();
+    decompress(src, compression, config, member)?;
 
-            let tool = if cfg!(target_os = "windows") {
-                "expand"
-            } else {
-                "cabextract"
-            };
-
-            let mut command = Command::new(tool);
-
-            if cfg!(target_os = "windows") {
-                command
-                    .arg(src.path())
-                    .arg(dst.path())
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped());
-            } else {
-                command
-                    .arg("-sfqp")
-                    .arg(src.path())
-                    .stdout(Stdio::from(dst.reopen()?))
-                    .stderr(Stdio::piped());
-            }
+    let input_size = metadata.len();
+    let output_size = src.as_file().metadata()?.len();
+    let tag = compression.name();
+    metric!(timer("compression.duration") = started.elapsed(), "type" => tag);
+    metric!(histogram("compression.input_size") = input_size, "type" => tag);
+    metric!(histogram("compression.output_size") = output_size, "type" => tag);
+    metric!(
+        histogram("compression.ratio") = output_size as f64 / input_size as f64,
+        "type" => tag
+    );
+
+    Ok(())
+}
+
+/// The formats of compressed files and archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Zstd,
+    Gzip,
+    Zlib,
+    Zip,
+    Xz,
+    Lzma,
+    Bzip2,
+    SevenZip,
+    Cab,
+    Szdd,
+    Kwaj,
+    Tar,
+}
+
+impl Compression {
+    /// Detects the format of a file from its first 4 bytes.
+    ///
+    /// Tarballs can not be detected this way, see [`archive::is_tar`].
+    fn detect(magic_bytes: [u8; 4]) -> Option<Self> {
+        Some(match magic_bytes {
+            [0x28, 0xb5, 0x2f, 0xfd] => Self::Zstd,
+            [0x1f, 0x8b, _, _] => Self::Gzip,
+            [0x78, 0x01, _, _] | [0x78, 0x9c, _, _] | [0x78, 0xda, _, _] => Self::Zlib,
+            [0x50, 0x4b, 0x03, 0x04] => Self::Zip,
+            [0xfd, 0x37, 0x7a, 0x58] => Self::Xz,
+            // Legacy `.lzma` files do not have a magic, but start with the lzma properties byte
+            // and the dictionary size. Virtually all encoders use the default properties `0x5d`.
+            [0x5d, 0x00, 0x00, _] => Self::Lzma,
+            [b'B', b'Z', b'h', b'1'..=b'9'] => Self::Bzip2,
+            [0x37, 0x7a, 0xbc, 0xaf] => Self::SevenZip,
+            [77, 83, 67, 70] => Self::Cab,
+            [b'S', b'Z', b'D', b'D'] => Self::Szdd,
+            [b'K', b'W', b'A', b'J'] => Self::Kwaj,
+            _ => return None,
+        })
+    }
+
+    /// The name of the format, as used in metrics.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gz",
+            Self::Zlib => "zlib",
+            Self::Zip => "zip",
+            Self::Xz => "xz",
+            Self::Lzma => "lzma",
+            Self::Bzip2 => "bz2",
+            Self::SevenZip => "7z",
+            Self::Cab => "cab",
+            Self::Szdd => "szdd",
+            Self::Kwaj => "kwaj",
+            Self::Tar => "tar",
+        }
+    }
+}
+
+/// Decompresses `src` of the given format, swapping it with the decompressed file.
+fn decompress(
+    src: &mut NamedTempFile,
+    compression: Compression,
+    config: &DecompressionConfig,
+    member: &ArchiveMember,
+) -> io::Result<()> {
+    match compression {
+        Compression::Zstd => {
+            decompress_stream(src, config, zstd::stream::read::Decoder::new)?;
+        }
+        Compression::Gzip => {
+            decompress_stream(src, config, |file| {
+                Ok(flate2::read::MultiGzDecoder::new(file))
+            })?;
+
+            // This might have been a `.tar.gz`.
+            maybe_extract_tar(src, config, member)?;
+        }
+        Compression::Zlib => {
+            decompress_stream(src, config, |file| Ok(flate2::read::ZlibDecoder::new(file)))?;
+        }
+        Compression::Zip => {
+            let selector = MemberSelector::new(member, parent_dir(src)?, config);
+            let mut dst = archive::extract_zip(src.as_file(), selector)?;
+
+            std::mem::swap(src, &mut dst);
+        }
+        Compression::Xz => {
+            decompress_stream(src, config, |file| {
+                let stream = xz2::stream::Stream::new_stream_decoder(
+                    MAX_LZMA_MEMORY,
+                    xz2::stream::CONCATENATED,
+                )
+                .map_err(io::Error::other)?;
+                Ok(xz2::read::XzDecoder::new_stream(file, stream))
+            })?;
+
+            // This might have been a `.tar.xz`.
+            maybe_extract_tar(src, config, member)?;
+        }
+        Compression::Lzma => {
+            decompress_stream(src, config, |file| {
+                let stream = xz2::stream::Stream::new_lzma_decoder(MAX_LZMA_MEMORY)
+                    .map_err(io::Error::other)?;
+                Ok(xz2::read::XzDecoder::new_stream(file, stream))
+            })?;
+        }
+        Compression::Bzip2 => {
+            decompress_stream(src, config, |file| {
+                Ok(bzip2::read::MultiBzDecoder::new(file))
+            })?;
+
+            // This might have been a `.tar.bz2`.
+            maybe_extract_tar(src, config, member)?;
+        }
+        Compression::SevenZip => {
+            let selector = MemberSelector::new(member, parent_dir(src)?, config);
+            let mut dst = archive::extract_7z(src.as_file(), selector)?;
 
-            let output = command.output()?;
+            std::mem::swap(src, &mut dst);
+        }
+        // Cabinets on symbol servers hold a single file. Others, such as the archives of
+        // Windows Error Reporting, are searched for the requested member.
+        Compression::Cab if matches!(member, ArchiveMember::Named(_) | ArchiveMember::Minidump) => {
+            let selector = MemberSelector::new(member, parent_dir(src)?, config);
+            let mut dst = archive::extract_cab(src.as_file(), selector)?;
 
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            std::mem::swap(src, &mut dst);
+        }
+        Compression::Cab => {
+            let mut dst = tempfile_in_parent(src)?;
 
-            info!("Command executed: {:?}", command);
-            info!("Command stdout: {}", stdout);
-            info!("Command stderr: {}", stderr);
+            if let Err(err) = decompress_cab(src.as_file(), dst.as_file_mut(), config) {
+                if !config.cab_external_fallback || SizeLimitExceeded::from_io_error(&err).is_some()
+                {
+                    return Err(err);
+                }
 
-            if !output.status.success() {
-                error!(
-                    "Failed to decompress CAB file with '{}': {:?}, stderr: {}",
-                    tool,
-                    src.path(),
-                    stderr
+                info!(
+                    "Failed to decompress CAB file natively, falling back to external tool: {}",
+                    err
                 );
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!(
-                        "Failed to decompress CAB file with '{}': {}",
-                        tool, stderr
-                    ),
-                ));
+                metric!(counter("compression.cab.external_fallback") += 1);
+
+                // Start over with a fresh file, as native extraction might have written
+                // partial output already.
+                dst = tempfile_in_parent(src)?;
+                external::decompress_cab(src, &dst, config)?;
             }
 
-            info!(
-                "Successfully decompressed CAB file using '{}': {:?}",
-                tool,
-                src.path()
-            );
+            std::mem::swap(src, &mut dst);
+        }
+        Compression::Szdd => {
+            let mut dst = tempfile_in_parent(src)?;
+            expand::decompress_szdd(src.as_file(), dst.as_file_mut(), config)?;
 
             std::mem::swap(src, &mut dst);
         }
-        _ => {
-            metric!(counter("compression") += 1, "type" => "none");
-            info!("File is not compressed, skipping decompression: {:?}", src.path());
+        Compression::Kwaj => {
+            let mut dst = tempfile_in_parent(src)?;
+            expand::decompress_kwaj(src.as_file(), dst.as_file_mut(), config)?;
+
+            std::mem::swap(src, &mut dst);
         }
+        Compression::Tar => {
+            extract_tar(src, config, member)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts an error which occurred during decompression into a [`CacheError`].
+///
+/// Decompression errors are treated as malformed files. It is more likely that
+/// the error comes from a corrupt file than a local file system error.
+pub(super) fn decompression_error(err: io::Error) -> CacheError {
+    if let Some(SizeLimitExceeded(limit)) = SizeLimitExceeded::from_io_error(&err) {
+        metric!(counter("compression.size_limit_exceeded") += 1);
+        return CacheError::SizeLimitExceeded(limit);
+    }
+    if let Some(ToolTimedOut(timeout)) = ToolTimedOut::from_io_error(&err) {
+        return CacheError::Timeout(timeout);
+    }
+    if let Some(no_match) = NoMatchingMember::from_io_error(&err) {
+        return CacheError::NoMatchingArchiveMember(no_match.details());
+    }
+
+    CacheError::Malformed(err.to_string())
+}
+
+/// Copies `reader` to `writer`, enforcing the configured
+/// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size).
+///
+/// Copying stops early if the decompression job has been cancelled.
+pub(super) fn copy_limited<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    config: &DecompressionConfig,
+) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: io::Write + ?Sized,
+{
+    let mut reader = Cancellable(reader);
+    let Some(limit) = config.max_decompressed_size else {
+        return io::copy(&mut reader, writer);
+    };
+
+    // Read one more byte than allowed, to figure out if the limit was exceeded.
+    let written = io::copy(&mut reader.take(limit.saturating_add(1)), writer)?;
+    check_size_limit(written, config)?;
+
+    Ok(written)
+}
+
+/// A writer enforcing the configured
+/// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size).
+pub(super) struct LimitedWriter<W> {
+    inner: W,
+    written: u64,
+    limit: Option<u64>,
+}
+
+impl<W> LimitedWriter<W> {
+    pub fn new(inner: W, limit: Option<u64>) -> Self {
+        Self {
+            inner,
+            written: 0,
+            limit,
+        }
+    }
+
+    /// The number of bytes written so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl<W: io::Write> io::Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(limit) = self.limit {
+            if self.written.saturating_add(buf.len() as u64) > limit {
+                return Err(SizeLimitExceeded(limit).into());
+            }
+        }
+
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(super) fn check_size_limit(
+    size: u64,
+    config: &DecompressionConfig,
+) -> Result<(), SizeLimitExceeded> {
+    match config.max_decompressed_size {
+        Some(limit) if size > limit => Err(SizeLimitExceeded(limit)),
+        _ => Ok(()),
     }
+}
+
+/// Decompresses a single stream into a new temp file, which is swapped with `src`.
+///
+/// The `decoder` function wraps the compressed file in the appropriate decoder.
+fn decompress_stream<R, F>(
+    src: &mut NamedTempFile,
+    config: &DecompressionConfig,
+    decoder: F,
+) -> io::Result<()>
+where
+    R: Read,
+    F: FnOnce(File) -> io::Result<R>,
+{
+    let mut dst = tempfile_in_parent(src)?;
+
+    let mut reader = decoder(src.as_file().try_clone()?)?;
+    copy_limited(&mut reader, dst.as_file_mut(), config)?;
+
+    std::mem::swap(src, &mut dst);
+    Ok(())
+}
+
+/// Extracts the selected member in case the file is a tarball.
+///
+/// Returns whether the file was a tarball.
+fn maybe_extract_tar(
+    src: &mut NamedTempFile,
+    config: &DecompressionConfig,
+    member: &ArchiveMember,
+) -> io::Result<bool> {
+    if !archive::is_tar(src.as_file())? {
+        return Ok(false);
+    }
+
+    metric!(counter("compression") += 1, "type" => "tar");
+
+    extract_tar(src, config, member)?;
+    Ok(true)
+}
+
+/// Extracts the selected member of a tarball.
+fn extract_tar(
+    src: &mut NamedTempFile,
+    config: &DecompressionConfig,
+    member: &ArchiveMember,
+) -> io::Result<()> {
+    let selector = MemberSelector::new(member, parent_dir(src)?, config);
+    let mut dst = archive::extract_tar(src.as_file(), selector)?;
+
+    std::mem::swap(src, &mut dst);
+    Ok(())
+}
+
+/// Extracts the first file contained in a CAB archive.
+///
+/// Symbol servers store exactly one file per cabinet, which is named like the original file.
+fn decompress_cab(src: &File, dst: &mut File, config: &DecompressionConfig) -> io::Result<()> {
+    let mut cabinet = cab::Cabinet::new(src)?;
+
+    let name = cabinet
+        .folder_entries()
+        .flat_map(|folder| folder.file_entries())
+        .map(|file| file.name().to_owned())
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "CAB file is empty"))?;
+
+    let mut reader = cabinet.read_file(&name)?;
+    copy_limited(&mut reader, dst, config)?;
 
     Ok(())
 }
 
 // Helper function to create a temporary file in the same directory as the given file.
 pub fn tempfile_in_parent(file: &NamedTempFile) -> io::Result<NamedTempFile> {
-    let dir = file
-        .path()
+    NamedTempFile::new_in(parent_dir(file)?)
+}
+
+fn parent_dir(file: &NamedTempFile) -> io::Result<&Path> {
+    file.path()
         .parent()
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
-    NamedTempFile::new_in(dir)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use symbolic::common::DebugId;
+    use symbolic::debuginfo::Object;
+
+    use super::*;
+
+    fn write_cab(file: &mut File, name: &str, contents: &[u8]) {
+        write_cab_files(file, &[(name, contents)]);
+    }
+
+    fn write_cab_files(file: &mut File, files: &[(&str, &[u8])]) {
+        let mut builder = cab::CabinetBuilder::new();
+        let folder = builder.add_folder(cab::CompressionType::MsZip);
+        for (name, _) in files {
+            folder.add_file(*name);
+        }
+
+        let mut writer = builder.build(file).unwrap();
+        let mut contents = files.iter().map(|(_, contents)| contents);
+        while let Some(mut entry) = writer.next_file().unwrap() {
+            entry.write_all(contents.next().unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_decompress_cab() {
+        let dir = crate::test::tempdir();
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_cab(src.as_file_mut(), "hello.txt", b"hello world");
+
+        maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First).unwrap();
+
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world");
+    }
+
+    #[test]
+    fn test_decompress_cab_member_selection() {
+        let dir = crate::test::tempdir();
+        let config = DecompressionConfig::default();
+        let files: &[(&str, &[u8])] = &[
+            ("Report.wer", b"EventType=APPCRASH"),
+            ("memory.hdmp", b"MDMP\x93\xa7"),
+        ];
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_cab_files(src.as_file_mut(), files);
+        maybe_decompress_file(&mut src, &config, &ArchiveMember::Minidump).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"MDMP\x93\xa7");
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_cab_files(src.as_file_mut(), files);
+        maybe_decompress_file(
+            &mut src,
+            &config,
+            &ArchiveMember::Named("report.wer".into()),
+        )
+        .unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"EventType=APPCRASH");
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_cab_files(src.as_file_mut(), &files[..1]);
+        let err = maybe_decompress_file(&mut src, &config, &ArchiveMember::Minidump).unwrap_err();
+        assert_eq!(
+            decompression_error(err),
+            CacheError::NoMatchingArchiveMember("checked Report.wer".into())
+        );
+    }
+
+    #[test]
+    fn test_decompress_xz() {
+        let dir = crate::test::tempdir();
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+
+        let mut encoder = xz2::write::XzEncoder::new(src.as_file_mut(), 6);
+        encoder.write_all(b"hello world").unwrap();
+        encoder.finish().unwrap();
+
+        maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First).unwrap();
+
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world");
+    }
+
+    #[test]
+    fn test_decompress_bz2() {
+        let dir = crate::test::tempdir();
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+
+        let mut encoder =
+            bzip2::write::BzEncoder::new(src.as_file_mut(), bzip2::Compression::default());
+        encoder.write_all(b"hello world").unwrap();
+        encoder.finish().unwrap();
+
+        maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First).unwrap();
+
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world");
+    }
+
+    #[test]
+    fn test_decompress_lzma() {
+        let dir = crate::test::tempdir();
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+
+        let options = xz2::stream::LzmaOptions::new_preset(6).unwrap();
+        let stream = xz2::stream::Stream::new_lzma_encoder(&options).unwrap();
+        let mut encoder = xz2::write::XzEncoder::new_stream(src.as_file_mut(), stream);
+        encoder.write_all(b"hello world").unwrap();
+        encoder.finish().unwrap();
+
+        maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First).unwrap();
+
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world");
+    }
+
+    #[test]
+    fn test_decompress_lzma_huge_dictionary() {
+        let dir = crate::test::tempdir();
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+
+        // The header declares a 3 GiB dictionary and an unknown size.
+        src.write_all(&[0x5d, 0x00, 0x00, 0x00, 0xc0]).unwrap();
+        src.write_all(&[0xff; 8]).unwrap();
+        src.write_all(&[0x00; 32]).unwrap();
+
+        let error = maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First)
+            .unwrap_err();
+        let error = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<xz2::stream::Error>());
+        assert_eq!(error, Some(&xz2::stream::Error::MemLimit));
+    }
+
+    fn fixture_tempfile(dir: &Path, fixture: &str) -> NamedTempFile {
+        let mut src = NamedTempFile::new_in(dir).unwrap();
+        src.write_all(&crate::test::read_fixture(fixture)).unwrap();
+        src
+    }
+
+    #[test]
+    fn test_decompress_7z_member_selection() {
+        let dir = crate::test::tempdir();
+        let config = DecompressionConfig::default();
+
+        // Without an `ObjectId`, the first member is being extracted.
+        let mut src = fixture_tempfile(dir.path(), "archives/symbols.7z");
+        maybe_decompress_file(&mut src, &config, &ArchiveMember::First).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world\n");
+
+        let object = crate::test::read_fixture("symbols/bd/a18fd85d4a4eb893022d6bfad846b1.debug");
+        let debug_id = Object::parse(&object).unwrap().debug_id();
+
+        let mut src = fixture_tempfile(dir.path(), "archives/symbols.7z");
+        maybe_decompress_file(&mut src, &config, &ArchiveMember::Object(debug_id.into())).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, object);
+
+        let mut src = fixture_tempfile(dir.path(), "archives/symbols.7z");
+        let result = maybe_decompress_file(
+            &mut src,
+            &config,
+            &ArchiveMember::Object(DebugId::nil().into()),
+        );
+        assert!(result.is_err());
+    }
+
+    fn write_tar<W: Write>(writer: W, members: &[(&str, &[u8])]) -> W {
+        let mut builder = tar::Builder::new(writer);
+        for (name, contents) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *contents).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_decompress_tar_member_selection() {
+        let dir = crate::test::tempdir();
+        let config = DecompressionConfig::default();
+
+        let object = crate::test::read_fixture("symbols/bd/a18fd85d4a4eb893022d6bfad846b1.debug");
+        let member = ArchiveMember::Object(Object::parse(&object).unwrap().debug_id().into());
+        let members: &[(&str, &[u8])] = &[("hello.txt", b"hello world"), ("a.debug", &object)];
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_tar(src.as_file_mut(), members);
+        maybe_decompress_file(&mut src, &config, &member).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, object);
+
+        // `.tar.gz`
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        let encoder = flate2::write::GzEncoder::new(src.as_file_mut(), Default::default());
+        write_tar(encoder, members).finish().unwrap();
+        maybe_decompress_file(&mut src, &config, &member).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, object);
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_tar(src.as_file_mut(), members);
+        let result = maybe_decompress_file(
+            &mut src,
+            &config,
+            &ArchiveMember::Object(DebugId::nil().into()),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decompress_zip_member_selection() {
+        let dir = crate::test::tempdir();
+        let config = DecompressionConfig::default();
+
+        let object = crate::test::read_fixture("symbols/bd/a18fd85d4a4eb893022d6bfad846b1.debug");
+        let member = ArchiveMember::Object(Object::parse(&object).unwrap().debug_id().into());
+
+        let write_zip = |file: &mut File| {
+            let mut writer = zip::ZipWriter::new(file);
+            let options = zip::write::SimpleFileOptions::default();
+            writer.start_file("hello.txt", options).unwrap();
+            writer.write_all(b"hello world").unwrap();
+            writer.start_file("a.debug", options).unwrap();
+            writer.write_all(&object).unwrap();
+            writer.finish().unwrap();
+        };
+
+        // Without an `ObjectId`, the first member is being extracted.
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_zip(src.as_file_mut());
+        maybe_decompress_file(&mut src, &config, &ArchiveMember::First).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world");
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_zip(src.as_file_mut());
+        maybe_decompress_file(&mut src, &config, &member).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, object);
+
+        // Names are compared case-insensitively.
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_zip(src.as_file_mut());
+        maybe_decompress_file(&mut src, &config, &ArchiveMember::Named("A.DEBUG".into())).unwrap();
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, object);
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        write_zip(src.as_file_mut());
+        let err = maybe_decompress_file(
+            &mut src,
+            &config,
+            &ArchiveMember::Object(DebugId::nil().into()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            decompression_error(err),
+            CacheError::NoMatchingArchiveMember("checked hello.txt, a.debug".into())
+        );
+    }
+
+    #[test]
+    fn test_decompress_zstd() {
+        let dir = crate::test::tempdir();
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        zstd::stream::copy_encode(&b"hello world"[..], src.as_file_mut(), 0).unwrap();
+
+        maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First).unwrap();
+
+        let contents = std::fs::read(src.path()).unwrap();
+        assert_eq!(contents, b"hello world");
+    }
+
+    #[test]
+    fn test_decompress_size_limit() {
+        let dir = crate::test::tempdir();
+        let config = DecompressionConfig {
+            max_decompressed_size: Some(1024),
+            ..Default::default()
+        };
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(src.as_file_mut(), Default::default());
+        encoder.write_all(&[0; 1024]).unwrap();
+        encoder.finish().unwrap();
+
+        // Exactly at the limit is fine
+        maybe_decompress_file(&mut src, &config, &ArchiveMember::First).unwrap();
+        assert_eq!(src.as_file().metadata().unwrap().len(), 1024);
+
+        let mut src = NamedTempFile::new_in(dir.path()).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(src.as_file_mut(), Default::default());
+        encoder.write_all(&[0; 1025]).unwrap();
+        encoder.finish().unwrap();
+
+        let err = maybe_decompress_file(&mut src, &config, &ArchiveMember::First).unwrap_err();
+        assert_eq!(
+            SizeLimitExceeded::from_io_error(&err),
+            Some(SizeLimitExceeded(1024))
+        );
+    }
 }
@@ -0,0 +1,91 @@
+//! Limits the number of concurrent downloads from a source.
+//!
+//! This keeps a single slow source from taking up all the download capacity of symbolicator.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use symbolicator_sources::SourceId;
+
+/// Limits which have not been used for this long are dropped.
+const LIMIT_IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// The maximum number of sources with a concurrency limit.
+const LIMIT_CAPACITY: u64 = 10_000;
+
+/// The semaphores of all sources with a concurrency limit.
+#[derive(Debug)]
+pub struct ConcurrencyLimits {
+    semaphores: moka::sync::Cache<(SourceId, usize), Arc<Semaphore>>,
+}
+
+impl ConcurrencyLimits {
+    pub fn new() -> Self {
+        Self {
+            semaphores: moka::sync::Cache::builder()
+                .max_capacity(LIMIT_CAPACITY)
+                .time_to_idle(LIMIT_IDLE_TIMEOUT)
+                .build(),
+        }
+    }
+
+    /// Waits until another download from the given source may start.
+    ///
+    /// The download may run as long as the returned permit is held. Sources without a limit
+    /// do not need a permit, and sources with the same ID but a different limit are limited
+    /// separately.
+    pub async fn acquire(
+        &self,
+        source_id: &SourceId,
+        source_name: &str,
+        max_concurrent_downloads: Option<usize>,
+    ) -> Option<OwnedSemaphorePermit> {
+        let limit = max_concurrent_downloads.filter(|&limit| limit > 0)?;
+        let key = (source_id.clone(), limit);
+        let semaphore = self
+            .semaphores
+            .get_with(key, || Arc::new(Semaphore::new(limit)));
+
+        let start = Instant::now();
+        // The semaphore is never closed.
+        let permit = semaphore.acquire_owned().await.ok()?;
+        metric!(
+            timer("download.concurrency.wait") = start.elapsed(),
+            "source" => source_name,
+        );
+        Some(permit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire() {
+        let limits = ConcurrencyLimits::new();
+        let id = SourceId::new("partner");
+
+        assert!(limits.acquire(&id, "http", None).await.is_none());
+        assert!(limits.acquire(&id, "http", Some(0)).await.is_none());
+
+        let first = limits.acquire(&id, "http", Some(2)).await.unwrap();
+        let _second = limits.acquire(&id, "http", Some(2)).await.unwrap();
+
+        // The limit is used up, until one of the downloads finishes.
+        let third = limits.acquire(&id, "http", Some(2));
+        let third = tokio::time::timeout(Duration::from_millis(50), third).await;
+        assert!(third.is_err());
+
+        drop(first);
+        let third = limits.acquire(&id, "http", Some(2));
+        let third = tokio::time::timeout(Duration::from_millis(50), third).await;
+        assert!(third.unwrap().is_some());
+
+        // Other sources are not affected.
+        let other = SourceId::new("other");
+        assert!(limits.acquire(&other, "http", Some(2)).await.is_some());
+    }
+}
@@ -0,0 +1,226 @@
+//! Support to download from debuginfod servers.
+//!
+//! A debuginfod source consists of multiple servers, which are asked in order. The first server
+//! that has the file wins, just like with `DEBUGINFOD_URLS`. Servers not having a file are
+//! remembered for a while, so that subsequent downloads of the same build-id skip them.
+
+use std::time::Duration;
+
+use reqwest::{header, Client};
+
+use symbolicator_sources::DebuginfodRemoteFile;
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::config::InMemoryCacheConfig;
+use crate::utils::http::DownloadTimeouts;
+
+use super::{Destination, USER_AGENT};
+
+/// A cache of request URLs which the server responded to with "not found".
+type NegativeCache = moka::sync::Cache<String, ()>;
+
+/// Downloader implementation that supports the debuginfod source.
+#[derive(Debug)]
+pub struct DebuginfodDownloader {
+    client: Client,
+    timeouts: DownloadTimeouts,
+    not_found: NegativeCache,
+}
+
+impl DebuginfodDownloader {
+    pub fn new(
+        client: Client,
+        timeouts: DownloadTimeouts,
+        in_memory: &InMemoryCacheConfig,
+    ) -> Self {
+        Self::with_negative_cache(
+            client,
+            timeouts,
+            in_memory.debuginfod_negative_capacity,
+            in_memory.debuginfod_negative_ttl,
+        )
+    }
+
+    fn with_negative_cache(
+        client: Client,
+        timeouts: DownloadTimeouts,
+        capacity: u64,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            client,
+            timeouts,
+            not_found: NegativeCache::builder()
+                .max_capacity(capacity)
+                .time_to_live(ttl)
+                .build(),
+        }
+    }
+
+    /// Downloads a file from the first server of the debuginfod source that has it.
+    ///
+    /// Returns [`CacheError::NotFound`] if none of the servers has the file. If any of the
+    /// servers failed otherwise, the last such error is returned instead, as the file might
+    /// still exist on that server.
+    pub async fn download_source(
+        &self,
+        source_name: &str,
+        file_source: &DebuginfodRemoteFile,
+        destination: &mut Destination,
+    ) -> CacheEntry {
+        let mut result = Err(CacheError::NotFound);
+
+        for url in file_source.urls() {
+            let Ok(url) = url else {
+                result = Err(CacheError::DownloadError("invalid debuginfod URL".into()));
+                continue;
+            };
+
+            let cache_key = url.to_string();
+            if self.not_found.contains_key(&cache_key) {
+                metric!(counter("source.debuginfod.negative_cache_hit") += 1);
+                continue;
+            }
+
+            tracing::debug!("Fetching debug file from `{}`", url);
+
+            let mut builder = self.client.get(url);
+            for (key, value) in &file_source.source.headers {
+                if let Ok(key) = header::HeaderName::from_bytes(key.as_bytes()) {
+                    builder = builder.header(key, value.as_str());
+                }
+            }
+            builder = builder.header(header::USER_AGENT, USER_AGENT);
+
+            match super::download_reqwest(source_name, builder, &self.timeouts, destination).await {
+                Ok(()) => return Ok(()),
+                Err(CacheError::NotFound) => {
+                    self.not_found.insert(cache_key, ());
+                }
+                Err(err) => {
+                    tracing::debug!("Failed to fetch debug file from `{}`: {}", cache_key, err);
+                    // A partially written destination can not be reused for the next server.
+                    if !destination.is_untouched() {
+                        return Err(err);
+                    }
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    use symbolic::common::CodeId;
+    use symbolicator_sources::{
+        DebuginfodSourceConfig, FileType, ObjectId, SourceFilters, SourceId,
+    };
+
+    use crate::test;
+
+    const CODE_ID: &str = "dfb85de42daffd09640c8fe377d572de3e168920";
+
+    fn debuginfod_file(urls: Vec<reqwest::Url>) -> DebuginfodRemoteFile {
+        let source = Arc::new(DebuginfodSourceConfig {
+            id: SourceId::new("debuginfod-test"),
+            urls,
+            headers: Default::default(),
+            filters: SourceFilters::default(),
+            is_public: false,
+            limits: Default::default(),
+            priority: 0,
+        });
+        let object_id = ObjectId {
+            code_id: Some(CodeId::new(CODE_ID.into())),
+            ..Default::default()
+        };
+        DebuginfodRemoteFile::for_object(source, FileType::ElfDebug, &object_id).unwrap()
+    }
+
+    async fn download(
+        downloader: &DebuginfodDownloader,
+        file_source: &DebuginfodRemoteFile,
+    ) -> (CacheEntry, Vec<u8>) {
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let file = tokio::fs::File::create(tmpfile.path()).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
+        let download_status = downloader
+            .download_source("", file_source, &mut destination)
+            .await;
+
+        (download_status, std::fs::read(tmpfile.path()).unwrap())
+    }
+
+    fn downloader() -> DebuginfodDownloader {
+        DebuginfodDownloader::with_negative_cache(
+            Client::new(),
+            Default::default(),
+            100,
+            Duration::from_secs(3600),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_download_federation() {
+        test::setup();
+
+        let server = test::Server::new();
+        let downloader = downloader();
+
+        // `/garbage_data/` responds with the requested path.
+        let file_source = debuginfod_file(vec![
+            server.url("respond_statuscode/404/"),
+            server.url("garbage_data/"),
+        ]);
+        let (download_status, content) = download(&downloader, &file_source).await;
+        assert_eq!(download_status, Ok(()));
+        assert_eq!(content, format!("buildid/{CODE_ID}/debuginfo").as_bytes());
+        assert_eq!(server.accesses(), 2);
+
+        // The first server is not asked again for the same file.
+        let (download_status, _) = download(&downloader, &file_source).await;
+        assert_eq!(download_status, Ok(()));
+        assert_eq!(
+            server.all_hits(),
+            [(format!("/garbage_data/buildid/{CODE_ID}/debuginfo"), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_errors() {
+        test::setup();
+
+        let server = test::Server::new();
+        let downloader = downloader();
+
+        // Errors fall through to the next server.
+        let file_source = debuginfod_file(vec![
+            server.url("respond_statuscode/500/"),
+            server.url("garbage_data/"),
+        ]);
+        let (download_status, _) = download(&downloader, &file_source).await;
+        assert_eq!(download_status, Ok(()));
+
+        let file_source = debuginfod_file(vec![
+            server.url("respond_statuscode/404/"),
+            server.url("respond_statuscode/410/"),
+        ]);
+        let (download_status, _) = download(&downloader, &file_source).await;
+        assert_eq!(download_status, Err(CacheError::NotFound));
+
+        // Errors take precedence over files not being found.
+        let file_source = debuginfod_file(vec![
+            server.url("respond_statuscode/500/"),
+            server.url("respond_statuscode/404/"),
+        ]);
+        let (download_status, _) = download(&downloader, &file_source).await;
+        assert!(matches!(download_status, Err(CacheError::DownloadError(_))));
+    }
+}
@@ -0,0 +1,630 @@
+//! The destination downloads are written to.
+//!
+//! Known compression formats are decompressed while the download is in progress, which saves
+//! writing the compressed file to disk only to read it back again for decompression.
+
+use std::fs;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::config::DecompressionConfig;
+
+use super::bandwidth::BandwidthLimiter;
+use super::checksum::{Checksum, ChecksumVerifier};
+use super::compression::{decompression_error, LimitedWriter};
+use super::rate_limit::RateLimit;
+
+/// The number of bytes needed to detect the compression format.
+const MAGIC_LEN: usize = 4;
+
+/// The file a download is written to.
+///
+/// With [`streaming`](DecompressionConfig::streaming) decompression enabled, gzip, zlib and
+/// zstd compressed downloads are detected by their magic bytes and decompressed on the fly.
+/// Everything else is written as is, and is decompressed after the download finished.
+///
+/// Downloads with a `Content-Encoding` [announced](Self::set_content_encoding) by the source
+/// are always decoded on the fly. This is the only way Brotli compressed downloads are
+/// decompressed, as Brotli does not have a magic.
+///
+/// If the source announces a [checksum](Self::set_checksum), it is verified against the
+/// downloaded bytes before they are decompressed.
+///
+/// An interrupted download can be [resumed](Self::resume) where it left off, as long as the
+/// destination itself did not fail.
+///
+/// Downloads from sources with a bandwidth limit are [throttled](Self::throttle) to it.
+///
+/// [`finish`](Self::finish) has to be called once all the chunks have been written.
+pub struct Destination {
+    state: State,
+    streaming: bool,
+    max_decompressed_size: Option<u64>,
+    /// The compression format announced by the source, which takes precedence over detection.
+    content_encoding: Option<Format>,
+    verify_checksums: bool,
+    checksum: Option<ChecksumVerifier>,
+    /// The number of bytes written so far, before decompression.
+    received: u64,
+    /// How often the download may still be resumed.
+    resumes_left: u32,
+    /// Whether writing failed, or was cancelled midway.
+    poisoned: bool,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>,
+    rate_limit: Option<Arc<RateLimit>>,
+}
+
+enum State {
+    /// Not enough bytes have been downloaded yet to detect the compression format.
+    Detecting(File, Vec<u8>),
+    /// The download is written to the file as is.
+    Plain(File),
+    /// The download is decompressed while being written to the file.
+    Decompressing(Decoder),
+    /// The download has been [finished](Destination::finish).
+    Finished,
+}
+
+impl Destination {
+    /// Creates a new destination writing to `file`.
+    pub fn new(file: File, config: &DecompressionConfig) -> Self {
+        Self {
+            state: State::Detecting(file, Vec::with_capacity(MAGIC_LEN)),
+            streaming: config.streaming,
+            max_decompressed_size: config.max_decompressed_size,
+            content_encoding: None,
+            verify_checksums: true,
+            checksum: None,
+            received: 0,
+            resumes_left: 0,
+            poisoned: false,
+            bandwidth_limiter: None,
+            rate_limit: None,
+        }
+    }
+
+    /// Sets the limiter for the bandwidth of the source.
+    pub fn bandwidth_limiter(mut self, bandwidth_limiter: Option<Arc<BandwidthLimiter>>) -> Self {
+        self.bandwidth_limiter = bandwidth_limiter;
+        self
+    }
+
+    /// Sets the rate limit of the source, which is updated by rate limited responses.
+    pub fn rate_limit(mut self, rate_limit: Option<Arc<RateLimit>>) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Returns the rate limit of the source.
+    pub fn source_rate_limit(&self) -> Option<Arc<RateLimit>> {
+        self.rate_limit.clone()
+    }
+
+    /// Sets how often an interrupted download may be [resumed](Self::resume).
+    pub fn max_resumes(mut self, max_resumes: u32) -> Self {
+        self.resumes_left = max_resumes;
+        self
+    }
+
+    /// Enables or disables the verification of [checksums](Self::set_checksum).
+    pub fn verify_checksums(mut self, verify_checksums: bool) -> Self {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+
+    /// Sets the checksum that the source announced for the download.
+    pub fn set_checksum(&mut self, checksum: Checksum) {
+        if self.verify_checksums {
+            self.checksum = Some(ChecksumVerifier::new(checksum));
+        }
+    }
+
+    /// Sets the `Content-Encoding` that the source announced for the download.
+    ///
+    /// Unknown encodings are ignored, the download is then written as is.
+    pub fn set_content_encoding(&mut self, content_encoding: &str) {
+        if let Some(format) = Format::from_content_encoding(content_encoding) {
+            self.content_encoding = Some(format);
+        }
+    }
+
+    /// Whether nothing has been written to the destination, nor a checksum been announced.
+    ///
+    /// Such a destination can still be used to download the file from somewhere else.
+    pub fn is_untouched(&self) -> bool {
+        self.checksum.is_none()
+            && matches!(&self.state, State::Detecting(_, header) if header.is_empty())
+    }
+
+    /// Returns the offset from which an interrupted download can be resumed.
+    ///
+    /// This uses up one of the [resumes](Self::max_resumes). Returns `None` if there are no
+    /// resumes left, if the destination failed, or if it has already been finished.
+    pub fn resume(&mut self) -> Option<u64> {
+        if self.poisoned || self.resumes_left == 0 || matches!(self.state, State::Finished) {
+            return None;
+        }
+        self.resumes_left -= 1;
+        Some(self.received)
+    }
+
+    /// Waits until the next `len` bytes may be downloaded within the bandwidth limit.
+    pub async fn throttle(&self, len: usize) {
+        if let Some(limiter) = &self.bandwidth_limiter {
+            limiter.acquire(len).await;
+        }
+    }
+
+    /// Writes a chunk of the download.
+    pub async fn write_all(&mut self, chunk: &[u8]) -> CacheEntry {
+        // If this future is dropped midway, the chunk might have been written partially.
+        self.poisoned = true;
+        self.write_chunk(chunk).await?;
+        self.received += chunk.len() as u64;
+        self.poisoned = false;
+        Ok(())
+    }
+
+    async fn write_chunk(&mut self, chunk: &[u8]) -> CacheEntry {
+        if let Some(checksum) = &mut self.checksum {
+            checksum.update(chunk);
+        }
+
+        match &mut self.state {
+            State::Detecting(_, header) => {
+                header.extend_from_slice(chunk);
+                if header.len() >= MAGIC_LEN {
+                    self.start().await?;
+                }
+            }
+            State::Plain(file) => file.write_all(chunk).await?,
+            State::Decompressing(_) => {
+                let chunk = chunk.to_vec();
+                self.decode(move |decoder| decoder.write_all(&chunk))
+                    .await?;
+            }
+            State::Finished => {
+                return Err(io::Error::other("destination has already been finished").into())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flushes all the remaining data to the file.
+    ///
+    /// This verifies the checksum of the download, and for compressed downloads, that the
+    /// compressed stream is complete.
+    pub async fn finish(&mut self) -> CacheEntry {
+        self.poisoned = true;
+        // A truncated download would also fail decompression, but a checksum mismatch is
+        // the more accurate error.
+        if let Some(checksum) = self.checksum.take() {
+            checksum.verify().map_err(CacheError::ChecksumMismatch)?;
+        }
+
+        // The download might have been too short to detect the compression format so far.
+        if matches!(self.state, State::Detecting(..)) {
+            self.start().await?;
+        }
+
+        match std::mem::replace(&mut self.state, State::Finished) {
+            State::Plain(mut file) => file.flush().await?,
+            State::Decompressing(decoder) => {
+                let decoded = tokio::task::spawn_blocking(move || decoder.finish())
+                    .await
+                    .map_err(io::Error::other)?
+                    .map_err(decompression_error)?;
+                if let Some(format) = self.content_encoding {
+                    let tag = format.name();
+                    metric!(histogram("download.encoded_size") = self.received, "encoding" => tag);
+                    metric!(histogram("download.decoded_size") = decoded, "encoding" => tag);
+                }
+            }
+            State::Detecting(..) | State::Finished => {}
+        }
+
+        Ok(())
+    }
+
+    /// Detects the compression format from the buffered header and starts writing.
+    async fn start(&mut self) -> CacheEntry {
+        let State::Detecting(mut file, header) =
+            std::mem::replace(&mut self.state, State::Finished)
+        else {
+            unreachable!("compression format has already been detected");
+        };
+
+        let format = match self.content_encoding {
+            Some(format) => Some(format),
+            None if self.streaming => Format::detect(&header),
+            None => None,
+        };
+
+        self.state = match format {
+            Some(format) => {
+                metric!(counter("compression.streaming") += 1, "type" => format.name());
+
+                let writer = LimitedWriter::new(file.into_std().await, self.max_decompressed_size);
+                let decoder = Decoder::new(format, writer).map_err(decompression_error)?;
+                self.state = State::Decompressing(decoder);
+                self.decode(move |decoder| decoder.write_all(&header))
+                    .await?;
+                return Ok(());
+            }
+            None => {
+                file.write_all(&header).await?;
+                State::Plain(file)
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Runs `f` with the decoder on a blocking thread, so that decompression does not block
+    /// the runtime.
+    ///
+    /// The decoder is moved out of the state in the meantime. If this future is dropped midway,
+    /// the destination is left [`Finished`](State::Finished), and it is poisoned anyway.
+    async fn decode<F>(&mut self, f: F) -> CacheEntry
+    where
+        F: FnOnce(&mut Decoder) -> io::Result<()> + Send + 'static,
+    {
+        let State::Decompressing(mut decoder) = std::mem::replace(&mut self.state, State::Finished)
+        else {
+            unreachable!("the download is not being decompressed");
+        };
+
+        let (decoder, result) = tokio::task::spawn_blocking(move || {
+            let result = f(&mut decoder);
+            (decoder, result)
+        })
+        .await
+        .map_err(io::Error::other)?;
+
+        self.state = State::Decompressing(decoder);
+        result.map_err(decompression_error)
+    }
+}
+
+/// The compression formats which can be decompressed while downloading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Brotli,
+    Gzip,
+    Zlib,
+    Zstd,
+}
+
+impl Format {
+    fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.len() < MAGIC_LEN {
+            return None;
+        }
+
+        match magic {
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x78, 0x01 | 0x9c | 0xda, ..] => Some(Self::Zlib),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of a `Content-Encoding`.
+    ///
+    /// The `deflate` encoding is a zlib stream, despite its name.
+    fn from_content_encoding(content_encoding: &str) -> Option<Self> {
+        match content_encoding.trim().to_ascii_lowercase().as_str() {
+            "br" => Some(Self::Brotli),
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Zlib),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gz",
+            Self::Zlib => "zlib",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+/// A decoder which writes the decompressed output to the destination file.
+///
+/// The decoders are synchronous, and are only run on blocking threads by the [`Destination`].
+enum Decoder {
+    Brotli(Box<brotli::DecompressorWriter<LimitedWriter<fs::File>>>),
+    Gzip(flate2::write::MultiGzDecoder<LimitedWriter<fs::File>>),
+    Zlib(flate2::write::ZlibDecoder<LimitedWriter<fs::File>>),
+    Zstd(zstd::stream::write::Decoder<'static, LimitedWriter<fs::File>>),
+}
+
+impl Decoder {
+    fn new(format: Format, writer: LimitedWriter<fs::File>) -> io::Result<Self> {
+        Ok(match format {
+            Format::Brotli => {
+                let decoder = brotli::DecompressorWriter::new(writer, 64 * 1024);
+                Self::Brotli(Box::new(decoder))
+            }
+            Format::Gzip => Self::Gzip(flate2::write::MultiGzDecoder::new(writer)),
+            Format::Zlib => Self::Zlib(flate2::write::ZlibDecoder::new(writer)),
+            Format::Zstd => Self::Zstd(zstd::stream::write::Decoder::new(writer)?),
+        })
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Brotli(decoder) => decoder.write_all(buf),
+            Self::Gzip(decoder) => decoder.write_all(buf),
+            Self::Zlib(decoder) => decoder.write_all(buf),
+            Self::Zstd(decoder) => decoder.write_all(buf),
+        }
+    }
+
+    /// Finishes decompression, returning the size of the decompressed output.
+    fn finish(self) -> io::Result<u64> {
+        let mut writer = match self {
+            Self::Brotli(mut decoder) => {
+                decoder.flush()?;
+                (*decoder).into_inner().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "incomplete brotli stream")
+                })?
+            }
+            Self::Gzip(decoder) => decoder.finish()?,
+            Self::Zlib(decoder) => decoder.finish()?,
+            Self::Zstd(mut decoder) => {
+                decoder.flush()?;
+                decoder.into_inner()
+            }
+        };
+        writer.flush()?;
+        Ok(writer.written())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::caching::CacheError;
+
+    const CONTENT: &[u8] = b"hello world, hello world, hello world\n";
+
+    /// Writes `data` to a [`Destination`] in tiny chunks and returns what ended up in the file.
+    async fn download(data: &[u8], config: &DecompressionConfig) -> CacheEntry<Vec<u8>> {
+        download_encoded(data, config, None).await
+    }
+
+    async fn download_encoded(
+        data: &[u8],
+        config: &DecompressionConfig,
+        content_encoding: Option<&str>,
+    ) -> CacheEntry<Vec<u8>> {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let file = File::create(temp_file.path()).await.unwrap();
+
+        let mut destination = Destination::new(file, config);
+        if let Some(content_encoding) = content_encoding {
+            destination.set_content_encoding(content_encoding);
+        }
+        for chunk in data.chunks(3) {
+            destination.write_all(chunk).await?;
+        }
+        destination.finish().await?;
+
+        Ok(std::fs::read(temp_file.path()).unwrap())
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_plain() {
+        let config = DecompressionConfig::default();
+        assert_eq!(download(CONTENT, &config).await.unwrap(), CONTENT);
+        assert_eq!(download(b"hi", &config).await.unwrap(), b"hi");
+    }
+
+    #[tokio::test]
+    async fn test_gzip() {
+        let config = DecompressionConfig::default();
+
+        let mut compressed = gzip(CONTENT);
+        assert_eq!(download(&compressed, &config).await.unwrap(), CONTENT);
+
+        // multiple concatenated members
+        compressed.extend(gzip(CONTENT));
+        let expected = [CONTENT, CONTENT].concat();
+        assert_eq!(download(&compressed, &config).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_zlib() {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(CONTENT).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let config = DecompressionConfig::default();
+        assert_eq!(download(&compressed, &config).await.unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_zstd() {
+        let compressed = zstd::encode_all(CONTENT, 0).unwrap();
+
+        let config = DecompressionConfig::default();
+        assert_eq!(download(&compressed, &config).await.unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_brotli() {
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(CONTENT).unwrap();
+        let compressed = encoder.into_inner();
+
+        let config = DecompressionConfig::default();
+        let result = download_encoded(&compressed, &config, Some("br")).await;
+        assert_eq!(result.unwrap(), CONTENT);
+
+        // Brotli is not detected without the `Content-Encoding`
+        let result = download_encoded(&compressed, &config, Some("identity")).await;
+        assert_eq!(result.unwrap(), compressed);
+
+        // but it is decoded even when streaming decompression is disabled
+        let config = DecompressionConfig {
+            streaming: false,
+            ..Default::default()
+        };
+        let result = download_encoded(&compressed, &config, Some("br")).await;
+        assert_eq!(result.unwrap(), CONTENT);
+
+        let result = download_encoded(&compressed[..compressed.len() / 2], &config, Some("br"));
+        assert!(matches!(result.await, Err(CacheError::Malformed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_content_encoding() {
+        let config = DecompressionConfig {
+            streaming: false,
+            ..Default::default()
+        };
+
+        // Announced encodings are decoded even when streaming decompression is disabled.
+        let compressed = gzip(CONTENT);
+        let result = download_encoded(&compressed, &config, Some("gzip")).await;
+        assert_eq!(result.unwrap(), CONTENT);
+        let result = download_encoded(&compressed, &config, Some("X-GZIP")).await;
+        assert_eq!(result.unwrap(), CONTENT);
+
+        let compressed = zstd::encode_all(CONTENT, 0).unwrap();
+        let result = download_encoded(&compressed, &config, Some("zstd")).await;
+        assert_eq!(result.unwrap(), CONTENT);
+
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(CONTENT).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let result = download_encoded(&compressed, &config, Some("deflate")).await;
+        assert_eq!(result.unwrap(), CONTENT);
+
+        // Unknown encodings are written as is.
+        let result = download_encoded(&compressed, &config, Some("compress")).await;
+        assert_eq!(result.unwrap(), compressed);
+    }
+
+    async fn download_verified(
+        data: &[u8],
+        checksum: Checksum,
+        verify_checksums: bool,
+    ) -> CacheEntry<Vec<u8>> {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let file = File::create(temp_file.path()).await.unwrap();
+
+        let mut destination =
+            Destination::new(file, &Default::default()).verify_checksums(verify_checksums);
+        destination.set_checksum(checksum);
+        for chunk in data.chunks(3) {
+            destination.write_all(chunk).await?;
+        }
+        destination.finish().await?;
+
+        Ok(std::fs::read(temp_file.path()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_checksum() {
+        use md5::Digest as _;
+
+        let compressed = gzip(CONTENT);
+        let md5 = Checksum::Md5(md5::Md5::digest(&compressed).into());
+        let wrong = Checksum::Crc32c(0);
+
+        // the checksum applies to the compressed bytes
+        let result = download_verified(&compressed, md5, true).await;
+        assert_eq!(result.unwrap(), CONTENT);
+
+        let result = download_verified(&compressed, wrong, true).await;
+        assert!(matches!(result, Err(CacheError::ChecksumMismatch(_))));
+
+        let truncated = &compressed[..compressed.len() - 4];
+        let result = download_verified(truncated, md5, true).await;
+        assert!(matches!(result, Err(CacheError::ChecksumMismatch(_))));
+
+        let result = download_verified(&compressed, wrong, false).await;
+        assert_eq!(result.unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_disabled() {
+        let compressed = gzip(CONTENT);
+
+        let config = DecompressionConfig {
+            streaming: false,
+            ..Default::default()
+        };
+        assert_eq!(download(&compressed, &config).await.unwrap(), compressed);
+    }
+
+    #[tokio::test]
+    async fn test_truncated() {
+        let compressed = gzip(CONTENT);
+        let truncated = &compressed[..compressed.len() - 4];
+
+        let config = DecompressionConfig::default();
+        let result = download(truncated, &config).await;
+        assert!(matches!(result, Err(CacheError::Malformed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_size_limit() {
+        let compressed = gzip(CONTENT);
+
+        let config = DecompressionConfig {
+            max_decompressed_size: Some(CONTENT.len() as u64),
+            ..Default::default()
+        };
+        assert_eq!(download(&compressed, &config).await.unwrap(), CONTENT);
+
+        let config = DecompressionConfig {
+            max_decompressed_size: Some(CONTENT.len() as u64 - 1),
+            ..Default::default()
+        };
+        let result = download(&compressed, &config).await;
+        assert_eq!(
+            result,
+            Err(CacheError::SizeLimitExceeded(CONTENT.len() as u64 - 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let file = File::create(temp_file.path()).await.unwrap();
+
+        let mut destination = Destination::new(file, &Default::default()).max_resumes(3);
+        destination.write_all(&CONTENT[..10]).await.unwrap();
+        assert_eq!(destination.resume(), Some(10));
+        destination.write_all(&CONTENT[10..20]).await.unwrap();
+        assert_eq!(destination.resume(), Some(20));
+        assert_eq!(destination.resume(), Some(20));
+        // the budget is used up
+        assert_eq!(destination.resume(), None);
+
+        destination.write_all(&CONTENT[20..]).await.unwrap();
+        destination.finish().await.unwrap();
+        assert_eq!(std::fs::read(temp_file.path()).unwrap(), CONTENT);
+
+        // finished destinations can not be resumed
+        assert_eq!(destination.resume(), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! Native support for the legacy SZDD and KWAJ formats of Microsoft's `compress.exe`.
+//!
+//! Microsoft symbol servers store many binaries compressed this way, as `foo.dl_` or
+//! `foo.ex_`. The formats are documented by the libmspack project.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use miniz_oxide::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+
+use crate::config::DecompressionConfig;
+
+use super::compression::LimitedWriter;
+use super::worker_pool::Cancellable;
+
+/// The magic at the start of SZDD files, including the 4 bytes following the `SZDD`.
+pub const SZDD_MAGIC: &[u8; 8] = b"SZDD\x88\xf0\x27\x33";
+
+/// The magic at the start of KWAJ files, including the 4 bytes following the `KWAJ`.
+pub const KWAJ_MAGIC: &[u8; 8] = b"KWAJ\x88\xf0\x27\xd1";
+
+/// Decompresses an SZDD file.
+pub fn decompress_szdd(
+    mut src: &File,
+    dst: &mut File,
+    config: &DecompressionConfig,
+) -> io::Result<()> {
+    // magic, compression mode, missing character of the file name, decompressed length
+    let mut header = [0; 14];
+    src.rewind()?;
+    src.read_exact(&mut header)?;
+
+    if &header[..8] != SZDD_MAGIC {
+        return Err(invalid_data("invalid SZDD header"));
+    }
+    if header[8] != b'A' {
+        return Err(invalid_data("unsupported SZDD compression mode"));
+    }
+    let length = u32::from_le_bytes([header[10], header[11], header[12], header[13]]);
+
+    let mut reader = BufReader::new(Cancellable(src));
+    let mut writer = LimitedWriter::new(BufWriter::new(dst), config.max_decompressed_size);
+
+    decompress_lzss(&mut reader, &mut writer)?;
+    check_length(&writer, length)?;
+
+    writer.flush()
+}
+
+/// Decompresses a KWAJ file.
+pub fn decompress_kwaj(
+    mut src: &File,
+    dst: &mut File,
+    config: &DecompressionConfig,
+) -> io::Result<()> {
+    // magic, compression method, offset of the compressed data, header flags
+    let mut header = [0; 14];
+    src.rewind()?;
+    src.read_exact(&mut header)?;
+
+    if &header[..8] != KWAJ_MAGIC {
+        return Err(invalid_data("invalid KWAJ header"));
+    }
+    let method = u16::from_le_bytes([header[8], header[9]]);
+    let data_offset = u16::from_le_bytes([header[10], header[11]]);
+    let flags = u16::from_le_bytes([header[12], header[13]]);
+
+    // The decompressed length is the first of the optional headers. All other optional
+    // headers, like the original file name, are of no interest to us.
+    let length = if flags & 1 != 0 {
+        let mut length = [0; 4];
+        src.read_exact(&mut length)?;
+        Some(u32::from_le_bytes(length))
+    } else {
+        None
+    };
+
+    src.seek(SeekFrom::Start(data_offset.into()))?;
+    let mut reader = BufReader::new(Cancellable(src));
+    let mut writer = LimitedWriter::new(BufWriter::new(dst), config.max_decompressed_size);
+
+    match method {
+        // no compression
+        0 => {
+            io::copy(&mut reader, &mut writer)?;
+        }
+        // every byte is XOR-ed with `0xff`
+        1 => {
+            let mut buf = [0; 8192];
+            loop {
+                let read = reader.read(&mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                buf[..read].iter_mut().for_each(|byte| *byte ^= 0xff);
+                writer.write_all(&buf[..read])?;
+            }
+        }
+        2 => decompress_lzss(&mut reader, &mut writer)?,
+        4 => decompress_mszip(&mut reader, &mut writer)?,
+        // 3 is LZ+Huffman, which has never been seen in the wild.
+        _ => {
+            return Err(invalid_data(format!(
+                "unsupported KWAJ compression method {method}"
+            )))
+        }
+    }
+
+    if let Some(length) = length {
+        check_length(&writer, length)?;
+    }
+
+    writer.flush()
+}
+
+/// Decompresses the LZSS variant shared by SZDD and KWAJ.
+///
+/// The stream consists of control bytes, each followed by 8 items. Each bit of the
+/// control byte, starting with the least significant one, says whether the item is a literal
+/// byte, or a two byte reference into a 4K window of previous output.
+///
+/// The stream is read byte by byte, so the reader should be buffered.
+fn decompress_lzss<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<()> {
+    const WINDOW_SIZE: usize = 4096;
+
+    let mut window = [b' '; WINDOW_SIZE];
+    let mut pos = WINDOW_SIZE - 16;
+
+    let mut bytes = reader.bytes();
+    // The stream simply ends without an explicit terminator.
+    while let Some(control) = bytes.next().transpose()? {
+        for bit in 0..8 {
+            if control & (1 << bit) != 0 {
+                let Some(byte) = bytes.next().transpose()? else {
+                    return Ok(());
+                };
+
+                writer.write_all(&[byte])?;
+                window[pos] = byte;
+                pos = (pos + 1) % WINDOW_SIZE;
+            } else {
+                let (Some(lo), Some(hi)) = (bytes.next().transpose()?, bytes.next().transpose()?)
+                else {
+                    return Ok(());
+                };
+
+                let mut offset = lo as usize | ((hi as usize & 0xf0) << 4);
+                let length = (hi as usize & 0x0f) + 3;
+                for _ in 0..length {
+                    let byte = window[offset];
+                    writer.write_all(&[byte])?;
+                    window[pos] = byte;
+                    pos = (pos + 1) % WINDOW_SIZE;
+                    offset = (offset + 1) % WINDOW_SIZE;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Decompresses a sequence of MSZIP blocks.
+///
+/// Each block is prefixed with its length and the `CK` signature, followed by a deflate
+/// stream of at most 32K of output, which may refer back to the output of the previous block.
+/// The blocks are read and decompressed one at a time.
+fn decompress_mszip<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<()> {
+    const BLOCK_SIZE: usize = 32 * 1024;
+
+    // The output of the previous block is kept at the start of the buffer, so the deflate
+    // decoder can resolve back-references into it.
+    let mut buffer = vec![0; 2 * BLOCK_SIZE];
+    let mut history = 0;
+    let mut block = Vec::new();
+
+    // The stream ends with an empty block, or simply at the end of the file.
+    while let Some(len) = read_block_len(reader)? {
+        if len == 0 {
+            break;
+        }
+
+        // The length includes the signature.
+        block.resize(len.into(), 0);
+        reader.read_exact(&mut block)?;
+        let Some(data) = block.strip_prefix(b"CK") else {
+            return Err(invalid_data("invalid MSZIP block signature"));
+        };
+
+        let mut decompressor = DecompressorOxide::new();
+        let (status, _, written) = decompress(
+            &mut decompressor,
+            data,
+            &mut buffer[..history + BLOCK_SIZE],
+            history,
+            TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        match status {
+            TINFLStatus::Done => {}
+            TINFLStatus::HasMoreOutput => {
+                return Err(invalid_data("MSZIP block exceeds the maximum block size"))
+            }
+            _ => return Err(invalid_data(format!("invalid MSZIP block: {status:?}"))),
+        }
+
+        let end = history + written;
+        writer.write_all(&buffer[history..end])?;
+
+        let keep = end.min(BLOCK_SIZE);
+        buffer.copy_within(end - keep..end, 0);
+        history = keep;
+    }
+
+    Ok(())
+}
+
+/// Reads the length prefix of an MSZIP block, or `None` at the end of the file.
+fn read_block_len<R: Read>(reader: &mut R) -> io::Result<Option<u16>> {
+    let mut len = [0; 2];
+    match reader.read_exact(&mut len) {
+        Ok(()) => Ok(Some(u16::from_le_bytes(len))),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+fn check_length<W>(writer: &LimitedWriter<W>, length: u32) -> io::Result<()> {
+    if writer.written() != u64::from(length) {
+        return Err(invalid_data(
+            "decompressed size does not match the size in the header",
+        ));
+    }
+    Ok(())
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::download::archive::ArchiveMember;
+    use crate::download::compression::maybe_decompress_file;
+    use tempfile::NamedTempFile;
+
+    /// `abcabcabc`, as three literals followed by a reference to them.
+    const LZSS_DATA: &[u8] = &[0x07, b'a', b'b', b'c', 0xf0, 0xf3];
+    const CONTENT: &[u8] = b"abcabcabc";
+
+    fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut src = NamedTempFile::new()?;
+        src.write_all(data)?;
+
+        maybe_decompress_file(&mut src, &Default::default(), &ArchiveMember::First)?;
+
+        std::fs::read(src.path())
+    }
+
+    fn kwaj(method: u16, data: &[u8]) -> Vec<u8> {
+        let mut file = KWAJ_MAGIC.to_vec();
+        file.extend(method.to_le_bytes());
+        // the data offset: the fixed header, the decompressed length, and the file name
+        file.extend(26u16.to_le_bytes());
+        // flags: decompressed length and file name
+        file.extend(0b1001u16.to_le_bytes());
+        file.extend((CONTENT.len() as u32).to_le_bytes());
+        file.extend(b"abc.dll\0");
+        file.extend(data);
+        file
+    }
+
+    #[test]
+    fn test_szdd() {
+        let mut file = SZDD_MAGIC.to_vec();
+        file.extend(b"Al");
+        file.extend((CONTENT.len() as u32).to_le_bytes());
+        file.extend(LZSS_DATA);
+
+        assert_eq!(decompress(&file).unwrap(), CONTENT);
+
+        // the length in the header is checked
+        file[10] += 1;
+        assert!(decompress(&file).is_err());
+    }
+
+    #[test]
+    fn test_kwaj_uncompressed() {
+        assert_eq!(decompress(&kwaj(0, CONTENT)).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_kwaj_xor() {
+        let data: Vec<_> = CONTENT.iter().map(|byte| byte ^ 0xff).collect();
+        assert_eq!(decompress(&kwaj(1, &data)).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_kwaj_lzss() {
+        assert_eq!(decompress(&kwaj(2, LZSS_DATA)).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_kwaj_mszip() {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(CONTENT).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        let mut data = ((deflated.len() + 2) as u16).to_le_bytes().to_vec();
+        data.extend(b"CK");
+        data.extend(deflated);
+        data.extend([0, 0]);
+
+        assert_eq!(decompress(&kwaj(4, &data)).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_kwaj_mszip_blocks() {
+        let mut data = Vec::new();
+        for part in [&CONTENT[..3], &CONTENT[3..]] {
+            let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), Default::default());
+            encoder.write_all(part).unwrap();
+            let deflated = encoder.finish().unwrap();
+
+            data.extend(((deflated.len() + 2) as u16).to_le_bytes());
+            data.extend(b"CK");
+            data.extend(deflated);
+        }
+
+        // the terminating empty block is optional
+        assert_eq!(decompress(&kwaj(4, &data)).unwrap(), CONTENT);
+
+        // a block cut short is an error
+        data.pop();
+        assert!(decompress(&kwaj(4, &data)).is_err());
+    }
+
+    #[test]
+    fn test_kwaj_unsupported() {
+        assert!(decompress(&kwaj(3, LZSS_DATA)).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Running external decompression tools under a time and output limit.
+//!
+//! The external tools are only used as a fallback for files which cannot be decompressed
+//! natively. As they are processing untrusted input, they are killed if they take too long or
+//! produce more output than allowed. They can optionally be run in a [`Sandbox`].
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use tempfile::NamedTempFile;
+
+use crate::config::{DecompressionConfig, ExternalToolsConfig};
+
+use super::compression::{check_size_limit, SizeLimitExceeded};
+use super::sandbox::Sandbox;
+use super::worker_pool::is_cancelled;
+
+/// How often a running tool is checked for violating its limits.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The maximum number of bytes of `stderr` that are included in errors and logs.
+const MAX_STDERR_LEN: u64 = 4096;
+
+/// The error returned when an external tool exceeds the configured
+/// [`timeout`](ExternalToolsConfig::timeout).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolTimedOut(pub Duration);
+
+impl fmt::Display for ToolTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "external tool timed out after {:?}", self.0)
+    }
+}
+
+impl Error for ToolTimedOut {}
+
+impl ToolTimedOut {
+    /// Returns the [`ToolTimedOut`] error wrapped in the given [`io::Error`], if any.
+    pub fn from_io_error(err: &io::Error) -> Option<Self> {
+        err.get_ref()?.downcast_ref().copied()
+    }
+}
+
+impl From<ToolTimedOut> for io::Error {
+    fn from(err: ToolTimedOut) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+    }
+}
+
+/// Extracts a CAB archive by spawning the configured
+/// [`cab_tool`](ExternalToolsConfig::cab_tool).
+///
+/// This is `cabextract` by default, or `expand` on Windows.
+pub fn decompress_cab(
+    src: &NamedTempFile,
+    dst: &NamedTempFile,
+    config: &DecompressionConfig,
+) -> io::Result<()> {
+    let tool = &config.external_tools.cab_tool;
+    let mut command = Command::new(tool);
+
+    if cfg!(target_os = "windows") {
+        command
+            .arg(src.path())
+            .arg(dst.path())
+            .stdout(Stdio::null());
+    } else {
+        command
+            .arg("-sfqp")
+            .arg(src.path())
+            .stdout(Stdio::from(dst.reopen()?));
+    }
+
+    run(&mut command, dst.path(), config)?;
+
+    tracing::info!(
+        "Successfully decompressed CAB file using {:?}: {:?}",
+        tool,
+        src.path()
+    );
+
+    Ok(())
+}
+
+/// Runs the `command` to completion, killing it if it violates any of the configured limits.
+///
+/// The size of the file at `output` is checked against the
+/// [`max_output_size`](ExternalToolsConfig::max_output_size) and the
+/// [`max_decompressed_size`](DecompressionConfig::max_decompressed_size) while the tool is
+/// running. The tool is also killed if the decompression job is cancelled.
+///
+/// The output file is left to the caller to clean up, which happens automatically for
+/// [`NamedTempFile`]s dropped due to the returned error.
+fn run(command: &mut Command, output: &Path, config: &DecompressionConfig) -> io::Result<()> {
+    let tools = &config.external_tools;
+
+    // `stderr` goes to a file rather than a pipe, so that a chatty tool can not block on a
+    // full pipe while we are waiting for it.
+    let mut stderr = tempfile::tempfile()?;
+    command
+        .stdin(Stdio::null())
+        .stderr(Stdio::from(stderr.try_clone()?));
+
+    // The sandbox has to outlive the child process.
+    let sandbox = if tools.sandbox.enabled {
+        Some(Sandbox::new(command, &tools.sandbox)?)
+    } else {
+        None
+    };
+
+    tracing::debug!("Running external tool: {:?}", command);
+    let mut child = command.spawn()?;
+    let started = Instant::now();
+
+    let entered = match &sandbox {
+        Some(sandbox) => sandbox.enter(&child),
+        None => Ok(()),
+    };
+    let status = match entered.and_then(|_| wait(&mut child, started, output, config)) {
+        Ok(status) => status,
+        Err(err) => {
+            // The tool might have exited in the meantime, in which case killing it fails.
+            child.kill().ok();
+            child.wait()?;
+            return Err(err);
+        }
+    };
+
+    metric!(timer("compression.external_tool.duration") = started.elapsed());
+
+    if !status.success() {
+        let stderr = read_stderr(&mut stderr)?;
+        tracing::error!(
+            "External tool {:?} failed with {}, stderr: {}",
+            command.get_program(),
+            status,
+            stderr
+        );
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "external tool {:?} failed with {}: {}",
+                command.get_program(),
+                status,
+                stderr
+            ),
+        ));
+    }
+
+    // A tool might have written the last bit of output right before exiting.
+    check_output_size(output, config, tools)?;
+
+    Ok(())
+}
+
+/// Waits for the `child` to exit, returning an error as soon as it violates a limit.
+fn wait(
+    child: &mut Child,
+    started: Instant,
+    output: &Path,
+    config: &DecompressionConfig,
+) -> io::Result<ExitStatus> {
+    let tools = &config.external_tools;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if started.elapsed() > tools.timeout {
+            metric!(counter("compression.external_tool.killed") += 1, "reason" => "timeout");
+            return Err(ToolTimedOut(tools.timeout).into());
+        }
+        if let Err(err) = check_output_size(output, config, tools) {
+            metric!(counter("compression.external_tool.killed") += 1, "reason" => "size_limit");
+            return Err(err.into());
+        }
+        if is_cancelled() {
+            metric!(counter("compression.external_tool.killed") += 1, "reason" => "cancelled");
+            return Err(io::Error::other("decompression has been cancelled"));
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn check_output_size(
+    output: &Path,
+    config: &DecompressionConfig,
+    tools: &ExternalToolsConfig,
+) -> Result<(), SizeLimitExceeded> {
+    // The tool may not have created its output file yet.
+    let Ok(metadata) = std::fs::metadata(output) else {
+        return Ok(());
+    };
+    let size = metadata.len();
+
+    check_size_limit(size, config)?;
+    match tools.max_output_size {
+        Some(limit) if size > limit => Err(SizeLimitExceeded(limit)),
+        _ => Ok(()),
+    }
+}
+
+fn read_stderr(stderr: &mut File) -> io::Result<String> {
+    let mut buf = Vec::new();
+    stderr.rewind()?;
+    stderr.take(MAX_STDERR_LEN).read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).trim().to_owned())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::config::SandboxConfig;
+
+    use super::*;
+
+    fn config(timeout: Duration, max_output_size: Option<u64>) -> DecompressionConfig {
+        DecompressionConfig {
+            external_tools: ExternalToolsConfig {
+                timeout,
+                max_output_size,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn sh(script: &str, output: &NamedTempFile) -> Command {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::from(output.reopen().unwrap()));
+        command
+    }
+
+    #[test]
+    fn test_success() {
+        let output = NamedTempFile::new().unwrap();
+        let config = config(Duration::from_secs(10), None);
+
+        run(&mut sh("echo hello", &output), output.path(), &config).unwrap();
+        assert_eq!(std::fs::read(output.path()).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn test_failure() {
+        let output = NamedTempFile::new().unwrap();
+        let config = config(Duration::from_secs(10), None);
+
+        let mut command = sh("echo oh no >&2; exit 3", &output);
+        let err = run(&mut command, output.path(), &config).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("oh no"));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let output = NamedTempFile::new().unwrap();
+        let config = config(Duration::from_millis(100), None);
+
+        let started = Instant::now();
+        let err = run(&mut sh("sleep 10", &output), output.path(), &config).unwrap_err();
+        assert_eq!(
+            ToolTimedOut::from_io_error(&err),
+            Some(ToolTimedOut(Duration::from_millis(100)))
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_max_output_size() {
+        let output = NamedTempFile::new().unwrap();
+        let config = config(Duration::from_secs(10), Some(1024));
+
+        let mut command = sh("while true; do echo aaaaaaaaaaaaaaaa; done", &output);
+        let err = run(&mut command, output.path(), &config).unwrap_err();
+        assert_eq!(
+            SizeLimitExceeded::from_io_error(&err),
+            Some(SizeLimitExceeded(1024))
+        );
+    }
+
+    #[test]
+    fn test_sandbox_max_file_size() {
+        let output = NamedTempFile::new().unwrap();
+        let mut config = config(Duration::from_secs(10), None);
+        config.external_tools.sandbox = SandboxConfig {
+            enabled: true,
+            max_file_size: Some(1024),
+            ..Default::default()
+        };
+
+        let mut command = sh("head -c 4096 /dev/zero", &output);
+        let err = run(&mut command, output.path(), &config).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(std::fs::metadata(output.path()).unwrap().len() <= 1024);
+
+        // small outputs are unaffected
+        let mut command = sh("head -c 512 /dev/zero", &output);
+        run(&mut command, output.path(), &config).unwrap();
+    }
+}
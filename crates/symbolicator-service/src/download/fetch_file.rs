@@ -1,12 +1,14 @@
-use std::io::Seek;
+use std::io::{self, Seek};
+use std::path::Path;
 use std::sync::Arc;
 
-use symbolicator_sources::RemoteFile;
+use symbolicator_sources::{ObjectId, RemoteFile};
 use tempfile::NamedTempFile;
 
-use super::compression::maybe_decompress_file;
+use super::archive::ArchiveMember;
+use super::compression::{decompression_error, maybe_decompress_file, tempfile_in_parent};
 use super::DownloadService;
-use crate::caching::{CacheEntry, CacheError};
+use crate::caching::CacheEntry;
 
 /// Downloads the gives [`RemoteFile`] and decompresses it.
 ///
@@ -19,15 +21,95 @@ pub async fn fetch_file(
     downloader: Arc<DownloadService>,
     file_id: RemoteFile,
     temp_file: &mut NamedTempFile,
+) -> CacheEntry {
+    download_and_decompress(downloader, file_id, ArchiveMember::First, temp_file).await
+}
+
+/// Downloads the given [`RemoteFile`] and decompresses it, like [`fetch_file`].
+///
+/// In case the downloaded file is an archive containing multiple files, the one matching
+/// the given [`ObjectId`] is extracted.
+#[tracing::instrument(skip(downloader, object_id, temp_file), fields(%file_id))]
+pub async fn fetch_file_for_object(
+    downloader: Arc<DownloadService>,
+    file_id: RemoteFile,
+    object_id: &ObjectId,
+    temp_file: &mut NamedTempFile,
+) -> CacheEntry {
+    let member = ArchiveMember::Object(object_id.clone());
+    download_and_decompress(downloader, file_id, member, temp_file).await
+}
+
+/// Downloads the given [`RemoteFile`] and decompresses it, like [`fetch_file`].
+///
+/// In case the downloaded file is an archive containing multiple files, the one with the given
+/// name is extracted.
+#[tracing::instrument(skip(downloader, temp_file), fields(%file_id))]
+pub async fn fetch_file_member(
+    downloader: Arc<DownloadService>,
+    file_id: RemoteFile,
+    member_name: &str,
+    temp_file: &mut NamedTempFile,
+) -> CacheEntry {
+    let member = ArchiveMember::Named(member_name.to_owned());
+    download_and_decompress(downloader, file_id, member, temp_file).await
+}
+
+/// Extracts a member out of a local archive, decompressing it like a downloaded file.
+///
+/// The archive itself is left untouched, so that multiple members can be extracted from it.
+/// Files which are not compressed are returned as they are.
+#[tracing::instrument(skip(downloader))]
+pub async fn extract_archive_member(
+    downloader: Arc<DownloadService>,
+    archive: &Path,
+    member: ArchiveMember,
+) -> CacheEntry<NamedTempFile> {
+    let dir = archive
+        .parent()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    let mut file = NamedTempFile::new_in(dir)?;
+    std::fs::copy(archive, file.path())?;
+    let config = downloader.decompression.clone();
+
+    let decompressed = downloader
+        .decompression_pool
+        .run(move || -> io::Result<NamedTempFile> {
+            maybe_decompress_file(&mut file, &config, &member)?;
+            Ok(file)
+        })
+        .await?;
+    let mut file = decompressed.map_err(decompression_error)?;
+
+    file.as_file_mut().rewind()?;
+    Ok(file)
+}
+
+async fn download_and_decompress(
+    downloader: Arc<DownloadService>,
+    file_id: RemoteFile,
+    member: ArchiveMember,
+    temp_file: &mut NamedTempFile,
 ) -> CacheEntry {
     downloader
         .download(file_id, temp_file.path().to_owned())
         .await?;
     tracing::trace!("Finished download");
 
-    // Treat decompression errors as malformed files. It is more likely that
-    // the error comes from a corrupt file than a local file system error.
-    maybe_decompress_file(temp_file).map_err(|e| CacheError::Malformed(e.to_string()))?;
+    // Decompression happens on a dedicated worker thread, which needs to own the file.
+    // It is swapped with a placeholder in the meantime.
+    let placeholder = tempfile_in_parent(temp_file)?;
+    let mut file = std::mem::replace(temp_file, placeholder);
+    let config = downloader.decompression.clone();
+
+    let decompressed = downloader
+        .decompression_pool
+        .run(move || -> io::Result<NamedTempFile> {
+            maybe_decompress_file(&mut file, &config, &member)?;
+            Ok(file)
+        })
+        .await?;
+    *temp_file = decompressed.map_err(decompression_error)?;
 
     Ok(temp_file.as_file().rewind()?)
 }
@@ -3,13 +3,17 @@
 //! It allows sources to be present on the local filesystem, usually only used for testing.
 
 use std::io;
+use std::path::Path;
 
 use tokio::fs::File;
+use tokio::io::AsyncReadExt;
 
 use symbolicator_sources::FilesystemRemoteFile;
 
 use crate::caching::{CacheEntry, CacheError};
 
+use super::Destination;
+
 /// Downloader implementation that supports the filesystem source.
 #[derive(Debug)]
 pub struct FilesystemDownloader {}
@@ -23,16 +27,31 @@ impl FilesystemDownloader {
     pub async fn download_source(
         &self,
         file_source: &FilesystemRemoteFile,
-        destination: &mut File,
+        destination: &mut Destination,
     ) -> CacheEntry {
         let path = file_source.path();
         tracing::debug!("Fetching debug file from {:?}", path);
+        download_file(&path, destination).await
+    }
+}
 
-        let mut file = File::open(path).await.map_err(|e| match e.kind() {
-            io::ErrorKind::NotFound => CacheError::NotFound,
-            _ => e.into(),
-        })?;
-        tokio::io::copy(&mut file, destination).await?;
-        Ok(())
+/// Copies the file at `path` to the destination.
+///
+/// This is also used for `file://` URLs of HTTP sources.
+pub async fn download_file(path: &Path, destination: &mut Destination) -> CacheEntry {
+    let mut file = File::open(path).await.map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => CacheError::NotFound,
+        _ => e.into(),
+    })?;
+
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        destination.write_all(&buf[..read]).await?;
     }
+
+    destination.finish().await
 }
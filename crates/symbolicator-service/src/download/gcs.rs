@@ -1,46 +1,77 @@
 //! Support to download from Google Cloud Storage buckets.
 
+use std::fmt;
 use std::sync::Arc;
 
-use symbolicator_sources::{GcsRemoteFile, GcsSourceKey};
-use tokio::fs::File;
+use gcp_auth::TokenProvider;
+use symbolicator_sources::{GcsCredentialsProvider, GcsRemoteFile, GcsSourceKey};
+use tokio::sync::OnceCell;
 
 use crate::caching::{CacheEntry, CacheError};
-use crate::utils::gcs::{self, GcsToken};
+use crate::utils::gcs::{self, GcsError, GcsToken};
 use crate::utils::http::DownloadTimeouts;
 
+use super::Destination;
+
 /// An LRU cache for GCS OAuth tokens.
 type GcsTokenCache = moka::future::Cache<Arc<GcsSourceKey>, CacheEntry<GcsToken>>;
 
 /// Downloader implementation that supports the GCS source.
-#[derive(Debug)]
 pub struct GcsDownloader {
     token_cache: GcsTokenCache,
+    /// The provider of ambient credentials, which is detected on first use.
+    ambient_provider: OnceCell<Arc<dyn TokenProvider>>,
     client: reqwest::Client,
     timeouts: DownloadTimeouts,
 }
 
+impl fmt::Debug for GcsDownloader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GcsDownloader")
+            .field("timeouts", &self.timeouts)
+            .finish()
+    }
+}
+
 impl GcsDownloader {
     pub fn new(client: reqwest::Client, timeouts: DownloadTimeouts, token_capacity: u64) -> Self {
         Self {
             token_cache: GcsTokenCache::builder()
                 .max_capacity(token_capacity)
                 .build(),
+            ambient_provider: OnceCell::new(),
             client,
             timeouts,
         }
     }
 
+    /// Requests a new token using the ambient credentials of the environment.
+    ///
+    /// Detecting the ambient credentials is retried on the next request if it fails.
+    async fn request_ambient_token(&self) -> Result<GcsToken, GcsError> {
+        let provider = self
+            .ambient_provider
+            .get_or_try_init(gcp_auth::provider)
+            .await
+            .map_err(GcsError::Ambient)?;
+        gcs::request_ambient_token(provider.as_ref()).await
+    }
+
     /// Resolves a valid GCS OAuth token.
     ///
     /// If the cache contains a valid token, then this token is returned. Otherwise, a new token is
-    /// requested from GCS and stored in the cache.
+    /// requested and stored in the cache.
     async fn get_token(&self, source_key: &Arc<GcsSourceKey>) -> CacheEntry<GcsToken> {
         metric!(counter("source.gcs.token.access") += 1);
 
         let init = Box::pin(async {
             metric!(counter("source.gcs.token.computation") += 1);
-            let token = gcs::request_new_token(&self.client, source_key).await;
+            let token = match source_key.credentials_provider {
+                GcsCredentialsProvider::Static => {
+                    gcs::request_new_token(&self.client, source_key).await
+                }
+                GcsCredentialsProvider::Ambient => self.request_ambient_token().await,
+            };
             token.map_err(CacheError::from)
         });
         let replace_if =
@@ -58,7 +89,7 @@ impl GcsDownloader {
         &self,
         source_name: &str,
         file_source: &GcsRemoteFile,
-        destination: &mut File,
+        destination: &mut Destination,
     ) -> CacheEntry {
         let key = file_source.key();
         let bucket = &file_source.source.bucket;
@@ -96,6 +127,7 @@ mod tests {
             id: SourceId::new("gcs-test"),
             bucket: "sentryio-system-symbols-0".to_owned(),
             prefix: "/ios".to_owned(),
+            canary: None,
             source_key: Arc::new(source_key),
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         })
@@ -116,7 +148,8 @@ mod tests {
         let source_location = SourceLocation::new("e5/14c9464eed3be5943a2c61d9241fad/executable");
         let file_source = GcsRemoteFile::new(source, source_location);
 
-        let mut destination = tokio::fs::File::create(&target_path).await.unwrap();
+        let file = tokio::fs::File::create(&target_path).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
         let download_status = downloader
             .download_source("", &file_source, &mut destination)
             .await;
@@ -143,7 +176,8 @@ mod tests {
         let source_location = SourceLocation::new("does/not/exist");
         let file_source = GcsRemoteFile::new(source, source_location);
 
-        let mut destination = tokio::fs::File::create(&target_path).await.unwrap();
+        let file = tokio::fs::File::create(&target_path).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
         let download_status = downloader
             .download_source("", &file_source, &mut destination)
             .await;
@@ -156,6 +190,7 @@ mod tests {
         test::setup();
 
         let broken_credentials = GcsSourceKey {
+            credentials_provider: GcsCredentialsProvider::Static,
             private_key: "".to_owned(),
             client_email: "".to_owned(),
         };
@@ -170,7 +205,8 @@ mod tests {
         let source_location = SourceLocation::new("does/not/exist");
         let file_source = GcsRemoteFile::new(source, source_location);
 
-        let mut destination = tokio::fs::File::create(&target_path).await.unwrap();
+        let file = tokio::fs::File::create(&target_path).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
         downloader
             .download_source("", &file_source, &mut destination)
             .await
@@ -180,6 +216,7 @@ mod tests {
     #[test]
     fn test_gcs_remote_dif_uri() {
         let source_key = Arc::new(GcsSourceKey {
+            credentials_provider: GcsCredentialsProvider::Static,
             private_key: String::from("ABC"),
             client_email: String::from("someone@example.com"),
         });
@@ -187,6 +224,7 @@ mod tests {
             id: SourceId::new("gcs-id"),
             bucket: String::from("bucket"),
             prefix: String::from("prefix"),
+            canary: None,
             source_key,
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         });
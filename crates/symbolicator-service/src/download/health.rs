@@ -0,0 +1,155 @@
+//! Health probes of S3 and GCS sources.
+//!
+//! Misconfigured credentials or bucket prefixes do not cause any errors, the files are just not
+//! found. Sources with a [`canary`](symbolicator_sources::S3SourceConfig::canary) file are
+//! probed by downloading that file, which fails in either case.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use futures::future;
+use serde::Serialize;
+
+use symbolicator_sources::{
+    GcsRemoteFile, RemoteFile, RemoteFileUri, S3RemoteFile, SourceConfig, SourceId, SourceLocation,
+};
+
+use super::audit::result_name;
+use super::DownloadService;
+
+/// The result of probing the health of a source.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceHealth {
+    /// The probed source.
+    pub source: SourceId,
+    /// The canary file of the source.
+    pub location: RemoteFileUri,
+    /// Whether the canary file was downloaded successfully.
+    pub healthy: bool,
+    /// The result of the download, like `ok`, `not_found` or `permission_denied`.
+    pub status: &'static str,
+    /// Details on why the download failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When the probe started.
+    pub checked_at: DateTime<Utc>,
+    /// How long the probe took.
+    pub duration_ms: u64,
+}
+
+/// Returns the canary file of the source, if it has one.
+fn canary_file(source: &SourceConfig) -> Option<RemoteFile> {
+    match source {
+        SourceConfig::S3(cfg) => {
+            let location = SourceLocation::new(cfg.canary.as_deref()?);
+            Some(S3RemoteFile::new(cfg.clone(), location).into())
+        }
+        SourceConfig::Gcs(cfg) => {
+            let location = SourceLocation::new(cfg.canary.as_deref()?);
+            Some(GcsRemoteFile::new(cfg.clone(), location).into())
+        }
+        _ => None,
+    }
+}
+
+impl DownloadService {
+    /// Probes the health of the sources which have a canary file.
+    ///
+    /// Sources are probed concurrently, other sources are skipped. The result of every probe is
+    /// also reported as the `source.healthy` metric.
+    pub async fn probe_sources(self: &Arc<Self>, sources: &[SourceConfig]) -> Vec<SourceHealth> {
+        let probes = sources.iter().filter_map(|source| {
+            let file = canary_file(source)?;
+            let this = Arc::clone(self);
+            Some(self.runtime.spawn(async move { this.probe(file).await }))
+        });
+
+        future::join_all(probes)
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect()
+    }
+
+    async fn probe(&self, file: RemoteFile) -> SourceHealth {
+        let checked_at = Utc::now();
+        let started = Instant::now();
+
+        let result = match tempfile::NamedTempFile::new() {
+            Ok(temp_file) => self.dispatch_download(&file, temp_file.path()).await,
+            Err(err) => Err(err.into()),
+        };
+
+        let source = file.source_id().clone();
+        let healthy = result.is_ok();
+        metric!(
+            gauge("source.healthy") = u64::from(healthy),
+            "source" => source.as_str(),
+        );
+        if let Err(error) = &result {
+            tracing::warn!(%error, "Health probe of source `{}` failed", source);
+        }
+
+        SourceHealth {
+            source,
+            location: file.uri(),
+            healthy,
+            status: result_name(&result),
+            error: result.as_ref().err().map(ToString::to_string),
+            checked_at,
+            duration_ms: started.elapsed().as_millis().try_into().unwrap_or(u64::MAX),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Config;
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_probe_sources() {
+        test::setup();
+
+        let config = Config::default();
+        let service = DownloadService::new(&config, tokio::runtime::Handle::current());
+
+        // Only S3 and GCS sources are probed.
+        let sources = [test::local_source()];
+        assert!(service.probe_sources(&sources).await.is_empty());
+    }
+
+    #[test]
+    fn test_canary_file() {
+        let source: SourceConfig = serde_json::from_value(serde_json::json!({
+            "id": "s3-test",
+            "type": "s3",
+            "bucket": "symbols",
+            "prefix": "ios",
+            "region": "us-east-1",
+            "access_key": "abc",
+            "secret_key": "123",
+            "canary": "canary.txt",
+        }))
+        .unwrap();
+        let file = canary_file(&source).unwrap();
+        assert_eq!(
+            file.uri(),
+            RemoteFileUri::new("s3://symbols/ios/canary.txt")
+        );
+
+        let source: SourceConfig = serde_json::from_value(serde_json::json!({
+            "id": "s3-test",
+            "type": "s3",
+            "bucket": "symbols",
+            "region": "us-east-1",
+            "access_key": "abc",
+            "secret_key": "123",
+        }))
+        .unwrap();
+        assert!(canary_file(&source).is_none());
+    }
+}
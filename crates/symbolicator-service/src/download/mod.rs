@@ -10,36 +10,73 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime};
 
 use ::sentry::SentryFutureExt;
+use bytes::{Bytes, BytesMut};
 use futures::prelude::*;
 use reqwest::StatusCode;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
 
+use symbolicator_sources::{
+    AzureRemoteFile, CommonSourceConfig, DebuginfodRemoteFile, FilesystemRemoteFile, GcsRemoteFile,
+    HttpRemoteFile, NugetRemoteFile, S3RemoteFile, SftpRemoteFile, SourceLocationIter,
+};
 pub use symbolicator_sources::{
     DirectoryLayout, FileType, ObjectId, ObjectType, RemoteFile, RemoteFileUri, SourceConfig,
     SourceFilters, SourceLocation,
 };
-use symbolicator_sources::{
-    FilesystemRemoteFile, GcsRemoteFile, HttpRemoteFile, S3RemoteFile, SourceLocationIter,
-};
 
 use crate::caching::{CacheEntry, CacheError};
-use crate::config::Config;
+use crate::config::{ChunkedDownloadConfig, Config, DecompressionConfig};
+use crate::utils::dns::DnsResolver;
 use crate::utils::futures::{m, measure, CancelOnDrop};
 use crate::utils::gcs::GcsError;
-use crate::utils::http::DownloadTimeouts;
+use crate::utils::http::{BlockedDestination, BlockedDestinations, DownloadTimeouts};
 use crate::utils::sentry::ConfigureScope;
 
+mod archive;
+mod audit;
+mod azure;
+mod bandwidth;
+mod checksum;
+mod circuit_breaker;
 mod compression;
+mod concurrency;
+mod debuginfod;
+mod destination;
+mod expand;
+mod external;
 mod fetch_file;
 mod filesystem;
 mod gcs;
+mod health;
 mod http;
+mod nuget;
+mod rate_limit;
+mod redirect;
 mod s3;
+mod sandbox;
 pub mod sentry;
-
+mod sftp;
+mod symstore;
+#[cfg(unix)]
+mod unix_socket;
+mod worker_pool;
+
+use audit::AuditLog;
+use bandwidth::BandwidthLimiters;
+use checksum::Checksum;
+use circuit_breaker::CircuitBreakers;
+use concurrency::ConcurrencyLimits;
+use destination::Destination;
+use rate_limit::{retry_after, RateLimit, RateLimits};
+use redirect::RedirectPolicy;
+use symstore::SymstoreLayouts;
+use worker_pool::WorkerPool;
+
+pub use archive::{object_matches_id, ArchiveMember};
 pub use compression::tempfile_in_parent;
-pub use fetch_file::fetch_file;
+pub use fetch_file::{
+    extract_archive_member, fetch_file, fetch_file_for_object, fetch_file_member,
+};
+pub use health::SourceHealth;
 
 impl ConfigureScope for RemoteFile {
     fn to_scope(&self, scope: &mut ::sentry::Scope) {
@@ -53,10 +90,19 @@ impl ConfigureScope for RemoteFile {
 /// HTTP User-Agent string to use.
 const USER_AGENT: &str = concat!("symbolicator/", env!("CARGO_PKG_VERSION"));
 
+/// The encodings accepted for downloads, which are decoded by the [`Destination`].
+const ACCEPT_ENCODING: &str = "zstd, br, gzip, deflate";
+
 impl CacheError {
-    fn download_error(mut error: &dyn Error) -> Self {
-        while let Some(src) = error.source() {
-            error = src;
+    fn download_error(mut error: &(dyn Error + 'static)) -> Self {
+        loop {
+            if let Some(blocked) = error.downcast_ref::<BlockedDestination>() {
+                return Self::Blocked(blocked.to_string());
+            }
+            match error.source() {
+                Some(src) => error = src,
+                None => break,
+            }
         }
 
         let mut error_string = error.to_string();
@@ -225,11 +271,25 @@ pub struct DownloadService {
     pub trusted_client: reqwest::Client,
     sentry: sentry::SentryDownloader,
     http: http::HttpDownloader,
+    nuget: nuget::NugetDownloader,
     s3: s3::S3Downloader,
     gcs: gcs::GcsDownloader,
+    azure: azure::AzureDownloader,
+    sftp: sftp::SftpDownloader,
+    debuginfod: debuginfod::DebuginfodDownloader,
     fs: filesystem::FilesystemDownloader,
     host_deny_list: Option<HostDenyList>,
+    circuit_breakers: Option<CircuitBreakers>,
     connect_to_reserved_ips: bool,
+    decompression: DecompressionConfig,
+    decompression_pool: WorkerPool,
+    verify_checksums: bool,
+    max_download_resumes: u32,
+    bandwidth_limiters: BandwidthLimiters,
+    concurrency_limits: ConcurrencyLimits,
+    rate_limits: RateLimits,
+    symstore_layouts: SymstoreLayouts,
+    audit_log: Option<AuditLog>,
 }
 
 impl DownloadService {
@@ -242,11 +302,30 @@ impl DownloadService {
         // |   trusted  |             yes             |             no            |
         // | restricted | according to config setting |             no            |
         // |   no_ssl   | according to config setting |             yes           |
-        let trusted_client = crate::utils::http::create_client(&timeouts, true, false);
-        let restricted_client =
-            crate::utils::http::create_client(&timeouts, config.connect_to_reserved_ips, false);
-        let no_ssl_client =
-            crate::utils::http::create_client(&timeouts, config.connect_to_reserved_ips, true);
+        //
+        // Only the trusted client may connect to the `blocked_destinations`.
+        let blocked = BlockedDestinations::new(&config.blocked_destinations);
+        let trusted_dns = DnsResolver::new(&config.dns, Default::default());
+        let dns = DnsResolver::new(&config.dns, blocked);
+        let trusted_client =
+            crate::utils::http::create_client(&timeouts, true, false, &trusted_dns);
+        let restricted_client = crate::utils::http::create_client(
+            &timeouts,
+            config.connect_to_reserved_ips,
+            false,
+            &dns,
+        );
+        let no_ssl_client = crate::utils::http::create_client(
+            &timeouts,
+            config.connect_to_reserved_ips,
+            true,
+            &dns,
+        );
+
+        let audit_log = config
+            .audit_log
+            .as_ref()
+            .map(|audit_log| AuditLog::new(audit_log, trusted_client.clone(), &runtime));
 
         let in_memory = &config.caches.in_memory;
         Arc::new(Self {
@@ -260,24 +339,70 @@ impl DownloadService {
                 in_memory,
                 config.propagate_traces,
             ),
-            http: http::HttpDownloader::new(restricted_client.clone(), no_ssl_client, timeouts),
+            http: http::HttpDownloader::new(
+                restricted_client.clone(),
+                no_ssl_client,
+                timeouts,
+                config.connect_to_reserved_ips,
+                dns,
+                config.chunked_downloads.clone(),
+                in_memory,
+            ),
+            nuget: nuget::NugetDownloader::new(restricted_client.clone(), timeouts),
             s3: s3::S3Downloader::new(timeouts, in_memory.s3_client_capacity),
-            gcs: gcs::GcsDownloader::new(restricted_client, timeouts, in_memory.gcs_token_capacity),
+            gcs: gcs::GcsDownloader::new(
+                restricted_client.clone(),
+                timeouts,
+                in_memory.gcs_token_capacity,
+            ),
+            azure: azure::AzureDownloader::new(
+                restricted_client.clone(),
+                timeouts,
+                in_memory.azure_token_capacity,
+            ),
+            debuginfod: debuginfod::DebuginfodDownloader::new(
+                restricted_client,
+                timeouts,
+                in_memory,
+            ),
+            sftp: sftp::SftpDownloader::new(timeouts, config.connect_to_reserved_ips),
             fs: filesystem::FilesystemDownloader::new(),
             host_deny_list: config
                 .deny_list_enabled
                 .then_some(HostDenyList::from_config(config)),
+            circuit_breakers: CircuitBreakers::from_config(&config.circuit_breaker),
             connect_to_reserved_ips: config.connect_to_reserved_ips,
+            decompression: config.decompression.clone(),
+            decompression_pool: WorkerPool::new(config.decompression.workers),
+            verify_checksums: config.verify_checksums,
+            max_download_resumes: config.max_download_resumes,
+            bandwidth_limiters: BandwidthLimiters::new(),
+            concurrency_limits: ConcurrencyLimits::new(),
+            rate_limits: RateLimits::new(),
+            symstore_layouts: SymstoreLayouts::new(),
+            audit_log,
         })
     }
 
     /// Dispatches downloading of the given file to the appropriate source.
     async fn dispatch_download(&self, source: &RemoteFile, destination: &Path) -> CacheEntry {
         let source_name = source.source_metric_key();
+        let bandwidth_limiter = self
+            .bandwidth_limiters
+            .get(source.source_id(), source.limits().max_bytes_per_second);
+        let rate_limit = self.rate_limits.get(source.source_id());
         let result = retry(|| async {
+            // Retries of rate limited downloads wait here as well.
+            rate_limit.wait(source_name).await?;
+
             // XXX: we have to create the file here, as doing so outside in `download`
             // would run into borrow checker problems due to the `&mut`.
-            let mut destination = tokio::fs::File::create(destination).await?;
+            let file = tokio::fs::File::create(destination).await?;
+            let mut destination = Destination::new(file, &self.decompression)
+                .verify_checksums(self.verify_checksums)
+                .max_resumes(self.max_download_resumes)
+                .bandwidth_limiter(bandwidth_limiter.clone())
+                .rate_limit(Some(rate_limit.clone()));
             match source {
                 RemoteFile::Sentry(source) => {
                     self.sentry
@@ -289,6 +414,11 @@ impl DownloadService {
                         .download_source(source_name, source, &mut destination)
                         .await
                 }
+                RemoteFile::Nuget(source) => {
+                    self.nuget
+                        .download_source(source_name, source, &mut destination)
+                        .await
+                }
                 RemoteFile::S3(source) => {
                     self.s3
                         .download_source(source_name, source, &mut destination)
@@ -299,6 +429,21 @@ impl DownloadService {
                         .download_source(source_name, source, &mut destination)
                         .await
                 }
+                RemoteFile::Azure(source) => {
+                    self.azure
+                        .download_source(source_name, source, &mut destination)
+                        .await
+                }
+                RemoteFile::Debuginfod(source) => {
+                    self.debuginfod
+                        .download_source(source_name, source, &mut destination)
+                        .await
+                }
+                RemoteFile::Sftp(source) => {
+                    self.sftp
+                        .download_source(source_name, source, &mut destination)
+                        .await
+                }
                 RemoteFile::Filesystem(source) => {
                     self.fs.download_source(source, &mut destination).await
                 }
@@ -333,7 +478,7 @@ impl DownloadService {
         // want to put such sources on the block list.
         let source_metric_key = source.source_metric_key().to_string();
         // NOTE: This allow-lists every external non-http symbol server.
-        // This includes S3, GCS, and builtin http symbol servers that might misbehave.
+        // This includes S3, GCS, Azure, and builtin http symbol servers that might misbehave.
         // If we want to tighten that up to only allow-list the sentry internal source,
         // this should be `"sentry:project"` instead, as defined here:
         // <https://github.com/getsentry/sentry/blob/b27ef04df6ecbaa0a34a472f787a163ca8400cc0/src/sentry/lang/native/sources.py#L17>
@@ -351,6 +496,26 @@ impl DownloadService {
             ));
         }
 
+        let circuit_permit = match &self.circuit_breakers {
+            Some(breakers) => Some(breakers.start(source.source_id(), &source_metric_key)?),
+            None => None,
+        };
+
+        // Waiting for other downloads from the same source does not count towards the timeout.
+        let _permit = self
+            .concurrency_limits
+            .acquire(
+                source.source_id(),
+                &source_metric_key,
+                source.limits().max_concurrent_downloads,
+            )
+            .await;
+
+        let audit_entry = self
+            .audit_log
+            .as_ref()
+            .map(|audit_log| audit_log.start(&source, &destination));
+
         let timeout = self.timeouts.max_download;
         let slf = self.clone();
         let job = async move { slf.dispatch_download(&source, &destination).await };
@@ -366,6 +531,14 @@ impl DownloadService {
             Ok(Ok(res)) => res,
         };
 
+        if let Some(audit_entry) = audit_entry {
+            audit_entry.finish(&result).await;
+        }
+
+        if let Some(permit) = circuit_permit {
+            permit.finish(&result);
+        }
+
         if let Err(ref e @ (CacheError::DownloadError(_) | CacheError::Timeout(_))) = result {
             metric!(counter("service.download.failure") += 1, "source" => &source_metric_key);
 
@@ -402,11 +575,13 @@ impl DownloadService {
         object_id: &ObjectId,
     ) -> Vec<RemoteFile> {
         let mut remote_files = vec![];
+        // Symbol stores might use another layout than the configured one.
+        let mut layout: Option<DirectoryLayout>;
 
         macro_rules! check_source {
             ($source:ident => $file_ty:ty) => {{
                 let mut iter =
-                    SourceLocationIter::new(&$source.files, filetypes, object_id).peekable();
+                    source_locations(&$source.files, filetypes, object_id, layout).peekable();
                 if iter.peek().is_none() {
                     // TODO: create a special "no file on source" `RemoteFile`?
                 } else {
@@ -417,6 +592,8 @@ impl DownloadService {
         }
 
         for source in sources {
+            layout = self.symstore_layouts.detect(self, source).await;
+
             match source {
                 SourceConfig::Sentry(cfg) => {
                     let future = self.sentry.list_files(cfg.clone(), object_id, filetypes);
@@ -432,7 +609,7 @@ impl DownloadService {
                 }
                 SourceConfig::Http(cfg) => {
                     let mut iter =
-                        SourceLocationIter::new(&cfg.files, filetypes, object_id).peekable();
+                        source_locations(&cfg.files, filetypes, object_id, layout).peekable();
                     if iter.peek().is_none() {
                         // TODO: create a special "no file on source" `RemoteFile`?
                     } else {
@@ -450,8 +627,26 @@ impl DownloadService {
                         }))
                     }
                 }
+                SourceConfig::Nuget(cfg) => remote_files.extend(
+                    filetypes
+                        .iter()
+                        .filter_map(|&filetype| {
+                            NugetRemoteFile::for_object(cfg.clone(), filetype, object_id)
+                        })
+                        .map(RemoteFile::from),
+                ),
                 SourceConfig::S3(cfg) => check_source!(cfg => S3RemoteFile),
                 SourceConfig::Gcs(cfg) => check_source!(cfg => GcsRemoteFile),
+                SourceConfig::Azure(cfg) => check_source!(cfg => AzureRemoteFile),
+                SourceConfig::Debuginfod(cfg) => remote_files.extend(
+                    filetypes
+                        .iter()
+                        .filter_map(|&filetype| {
+                            DebuginfodRemoteFile::for_object(cfg.clone(), filetype, object_id)
+                        })
+                        .map(RemoteFile::from),
+                ),
+                SourceConfig::Sftp(cfg) => check_source!(cfg => SftpRemoteFile),
                 SourceConfig::Filesystem(cfg) => check_source!(cfg => FilesystemRemoteFile),
             }
         }
@@ -464,6 +659,20 @@ impl DownloadService {
     }
 }
 
+/// Returns the locations of an object on a source, using `layout` instead of the configured one.
+fn source_locations<'a>(
+    files: &'a CommonSourceConfig,
+    filetypes: &'a [FileType],
+    object_id: &'a ObjectId,
+    layout: Option<DirectoryLayout>,
+) -> SourceLocationIter<'a> {
+    let iter = SourceLocationIter::new(files, filetypes, object_id);
+    match layout {
+        Some(layout) => iter.with_layout(layout),
+        None => iter,
+    }
+}
+
 /// Try to run a future up to 3 times with 20 millisecond delays on failure.
 pub async fn retry<G, F, T>(task_gen: G) -> CacheEntry<T>
 where
@@ -478,7 +687,11 @@ where
         // its highly unlikely we get a different result when retrying these
         let should_not_retry = matches!(
             result,
-            Ok(_) | Err(CacheError::NotFound | CacheError::PermissionDenied(_))
+            Ok(_)
+                | Err(CacheError::NotFound
+                    | CacheError::PermissionDenied(_)
+                    | CacheError::Blocked(_)
+                    | CacheError::SizeLimitExceeded(_))
         );
 
         if should_not_retry || tries >= 3 {
@@ -495,7 +708,7 @@ where
 async fn download_stream(
     source_name: &str,
     stream: impl Stream<Item = Result<impl AsRef<[u8]>, CacheError>>,
-    destination: &mut File,
+    destination: &mut Destination,
 ) -> CacheEntry {
     futures::pin_mut!(stream);
 
@@ -506,6 +719,7 @@ async fn download_stream(
             let chunk = chunk?;
             let chunk = chunk.as_ref();
             throughput_recorder.add_bytes_transferred(chunk.len() as u64);
+            destination.throttle(chunk.len()).await;
             destination.write_all(chunk).await?;
         }
         Ok(())
@@ -514,47 +728,337 @@ async fn download_stream(
     throughput_recorder.done(&result);
     result?;
 
-    destination.flush().await?;
-    Ok(())
+    destination.finish().await
 }
 
+/// Downloads the response of a request built with `reqwest`.
+///
+/// If the body of the response is interrupted, the download is [resumed](Destination::resume)
+/// using a `Range` request, provided the server announced a validator for the file. The
+/// validator is sent as `If-Range`, so that the file can not change in between.
 async fn download_reqwest(
     source_name: &str,
     builder: reqwest::RequestBuilder,
     timeouts: &DownloadTimeouts,
-    destination: &mut File,
+    destination: &mut Destination,
 ) -> CacheEntry {
-    let (client, request) = builder.build_split();
-    let request = request?;
-    let source = request.url().to_string();
-    let request = client.execute(request);
+    download_reqwest_chunked(
+        source_name,
+        builder,
+        timeouts,
+        None,
+        None,
+        false,
+        destination,
+    )
+    .await
+}
+
+/// Like [`download_reqwest`], but downloads large files in [chunks](ChunkedDownloadConfig).
+///
+/// If a [`RedirectPolicy`] is given, the client of the `builder` must not follow redirects on
+/// its own. With `reject_html`, successful responses with an HTML page are treated as not
+/// found, for servers which respond to missing files with an error page.
+async fn download_reqwest_chunked(
+    source_name: &str,
+    builder: reqwest::RequestBuilder,
+    timeouts: &DownloadTimeouts,
+    chunked: Option<&ChunkedDownloadConfig>,
+    redirects: Option<&RedirectPolicy>,
+    reject_html: bool,
+    destination: &mut Destination,
+) -> CacheEntry {
+    // Requests without a streaming body can always be cloned.
+    let resume_builder = builder.try_clone();
+    let rate_limit = destination.source_rate_limit();
+    let response = send_reqwest(
+        source_name,
+        builder,
+        timeouts,
+        rate_limit.as_deref(),
+        redirects,
+    )
+    .await?;
+    let source = response.url().to_string();
+
+    if reject_html && is_html(&response) {
+        tracing::debug!("HTML page instead of a file at `{}`", source);
+        return Err(CacheError::NotFound);
+    }
+
+    let content_length = content_length(&response);
+
+    let headers = response.headers();
+    let content_encoding = headers
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|hv| hv.to_str().ok())
+        .filter(|encoding| !encoding.eq_ignore_ascii_case("identity"));
+    if let Some(content_encoding) = content_encoding {
+        destination.set_content_encoding(content_encoding);
+    }
 
-    let timeout = timeouts.head;
-    let request = tokio::time::timeout(timeout, request);
-    let request = measure_download_time(source_name, request);
+    // Checksums and the `Content-Length` apply to the encoded body, which is what the
+    // destination receives. The exception are the checksums GCS announces for objects stored
+    // with a `Content-Encoding`, which GCS decodes unless the request accepts the encoding.
+    let is_transcoded = headers
+        .get("x-goog-stored-content-encoding")
+        .is_some_and(|stored| stored != content_encoding.unwrap_or("identity"));
+    let checksum = headers
+        .get("x-goog-hash")
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(Checksum::from_goog_hash)
+        .or_else(|| {
+            let hv = headers.get("content-md5")?;
+            Checksum::from_content_md5(hv.to_str().ok()?)
+        });
+    if let Some(checksum) = checksum.filter(|_| !is_transcoded) {
+        destination.set_checksum(checksum);
+    }
+
+    // Ranges are requested without an encoding, so their offsets do not match an encoded body.
+    let is_encoded = content_encoding.is_some() || is_transcoded;
+    let ranges = match (resume_builder, resume_validator(headers)) {
+        (Some(builder), Some(validator)) if !is_encoded => Some(RangeRequests {
+            source_name,
+            builder,
+            validator,
+            timeouts,
+            rate_limit,
+            redirects,
+        }),
+        _ => None,
+    };
+
+    let accepts_ranges = headers
+        .get(reqwest::header::ACCEPT_RANGES)
+        .is_some_and(|hv| hv == "bytes");
+    let chunked = match (chunked, &ranges, content_length) {
+        (Some(config), Some(ranges), Some(len))
+            if config.enabled && accepts_ranges && len as u64 >= config.min_size =>
+        {
+            Some((config, ranges, len as u64))
+        }
+        _ => None,
+    };
+
+    let mut result = match chunked {
+        Some((config, ranges, len)) => {
+            stream_chunked(response, ranges, len, config, destination).await
+        }
+        None => stream_reqwest(source_name, response, timeouts, destination).await,
+    };
+
+    while let Err(err @ (CacheError::DownloadError(_) | CacheError::Timeout(_))) = &result {
+        let Some(ranges) = &ranges else {
+            break;
+        };
+        let Some(offset) = destination.resume() else {
+            break;
+        };
+
+        tracing::debug!(
+            "Resuming download of `{}` at offset {}: {}",
+            source,
+            offset,
+            err
+        );
+        metric!(counter("download.resume") += 1, "source" => source_name);
+
+        result = match ranges.send(offset, None).await {
+            Ok(Some(response)) => {
+                stream_reqwest(source_name, response, timeouts, destination).await
+            }
+            // The server does not support ranges, or the file has changed in the meantime.
+            Ok(None) => break,
+            Err(err @ (CacheError::DownloadError(_) | CacheError::Timeout(_))) => Err(err),
+            Err(_) => break,
+        };
+    }
+
+    result
+}
+
+/// Requests ranges of a file, making sure that the file does not change in between.
+struct RangeRequests<'a> {
+    source_name: &'a str,
+    /// The request for the whole file.
+    builder: reqwest::RequestBuilder,
+    /// The validator of the file, which is sent as `If-Range`.
+    validator: reqwest::header::HeaderValue,
+    timeouts: &'a DownloadTimeouts,
+    rate_limit: Option<Arc<RateLimit>>,
+    redirects: Option<&'a RedirectPolicy>,
+}
 
-    let response = request.await.map_err(|_| CacheError::Timeout(timeout))??;
+impl RangeRequests<'_> {
+    /// Requests the file from `start` up to `end`, or to the end of the file.
+    ///
+    /// Returns `None` if the server does not respond with the requested range.
+    async fn send(&self, start: u64, end: Option<u64>) -> CacheEntry<Option<reqwest::Response>> {
+        let Some(builder) = self.builder.try_clone() else {
+            return Ok(None);
+        };
+
+        // The end of HTTP ranges is inclusive.
+        let range = match end {
+            Some(end) => format!("bytes={start}-{}", end - 1),
+            None => format!("bytes={start}-"),
+        };
+        let builder = builder
+            .header(reqwest::header::RANGE, range)
+            .header(reqwest::header::IF_RANGE, self.validator.clone())
+            .header(reqwest::header::ACCEPT_ENCODING, "identity");
+
+        let response = send_reqwest(
+            self.source_name,
+            builder,
+            self.timeouts,
+            self.rate_limit.as_deref(),
+            self.redirects,
+        )
+        .await?;
+        Ok(is_resumed_at(&response, start).then_some(response))
+    }
+
+    /// Downloads the range from `start` to `end` into memory.
+    async fn fetch(&self, start: u64, end: u64) -> CacheEntry<Bytes> {
+        let Some(response) = self.send(start, Some(end)).await? else {
+            return Err(CacheError::DownloadError(
+                "server does not support ranges".into(),
+            ));
+        };
+        read_chunk(response, end - start).await
+    }
+}
+
+/// Writes a file to the destination, downloading multiple chunks of it concurrently.
+///
+/// The first chunk is taken from the body of the initial response, the others are requested
+/// as ranges. The chunks are written in order.
+async fn stream_chunked(
+    response: reqwest::Response,
+    ranges: &RangeRequests<'_>,
+    content_length: u64,
+    config: &ChunkedDownloadConfig,
+    destination: &mut Destination,
+) -> CacheEntry {
+    metric!(counter("download.chunked") += 1, "source" => ranges.source_name);
+
+    let chunk_size = config.chunk_size.max(1);
+    let first = read_chunk(response, chunk_size.min(content_length)).boxed();
+    let others = (chunk_size..content_length)
+        .step_by(chunk_size as usize)
+        .map(|start| {
+            let end = start.saturating_add(chunk_size).min(content_length);
+            ranges.fetch(start, end).boxed()
+        });
+    let chunks = stream::once(future::ready(first))
+        .chain(stream::iter(others))
+        .buffered(config.concurrency.max(1))
+        // Boxing erases the closure type, which the compiler fails to prove `Send` otherwise.
+        .boxed();
+
+    let timeout = content_length_timeout(content_length as i64, ranges.timeouts.streaming);
+    let future = download_stream(ranges.source_name, chunks, destination);
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| CacheError::Timeout(timeout))?
+}
+
+/// Reads the first `len` bytes of the body of a response into memory.
+async fn read_chunk(response: reqwest::Response, len: u64) -> CacheEntry<Bytes> {
+    let mut chunk = BytesMut::with_capacity(len as usize);
+    let mut stream = response.bytes_stream();
+    while (chunk.len() as u64) < len {
+        match stream.next().await {
+            Some(bytes) => chunk.extend_from_slice(&bytes?),
+            None => {
+                return Err(CacheError::DownloadError(
+                    "response ended before the end of the chunk".into(),
+                ))
+            }
+        }
+    }
+    chunk.truncate(len as usize);
+    Ok(chunk.freeze())
+}
+
+/// Sends a request, and maps unsuccessful responses to errors.
+///
+/// If the server asks to back off via `Retry-After`, this is recorded in the `rate_limit`.
+/// Redirects are followed by the client, unless a [`RedirectPolicy`] is given.
+async fn send_reqwest(
+    source_name: &str,
+    builder: reqwest::RequestBuilder,
+    timeouts: &DownloadTimeouts,
+    rate_limit: Option<&RateLimit>,
+    redirects: Option<&RedirectPolicy>,
+) -> CacheEntry<reqwest::Response> {
+    let (client, request) = builder.build_split();
+    let mut request = request?;
+    // Encoded downloads are decoded by the `Destination`, see `download_reqwest_chunked`.
+    request
+        .headers_mut()
+        .entry(reqwest::header::ACCEPT_ENCODING)
+        .or_insert(reqwest::header::HeaderValue::from_static(ACCEPT_ENCODING));
+    let mut redirected = 0;
+    let response = loop {
+        let next_request = redirects.and_then(|_| request.try_clone());
+        let source = request.url().to_string();
+        let response = client.execute(request);
+
+        let timeout = timeouts.head;
+        let response = tokio::time::timeout(timeout, response);
+        let response = measure_download_time(source_name, response);
+
+        let response = response.await.map_err(|_| CacheError::Timeout(timeout))??;
+
+        let (Some(redirects), Some(next_request)) = (redirects, next_request) else {
+            break response;
+        };
+        match redirects.follow(
+            next_request,
+            response.status(),
+            response.headers(),
+            redirected,
+        )? {
+            Some(next_request) => {
+                tracing::debug!("Following redirect from `{}`", source);
+                redirects.check_destination(next_request.url()).await?;
+                request = next_request;
+                redirected += 1;
+            }
+            None => break response,
+        }
+    };
+    let source = response.url().to_string();
+    check_response(source_name, &source, response, rate_limit)
+}
 
+/// Maps unsuccessful responses from `source` to errors.
+///
+/// If the server asks to back off via `Retry-After`, this is recorded in the `rate_limit`.
+fn check_response(
+    source_name: &str,
+    source: &str,
+    response: reqwest::Response,
+    rate_limit: Option<&RateLimit>,
+) -> CacheEntry<reqwest::Response> {
     let status = response.status();
+    if let Some(delay) = retry_after(status, response.headers(), SystemTime::now()) {
+        tracing::debug!(
+            "Rate limited by `{}` for {}",
+            source,
+            humantime::format_duration(delay)
+        );
+        if let Some(rate_limit) = rate_limit {
+            rate_limit.limit(source_name, delay);
+        }
+    }
+
     if status.is_success() {
         tracing::trace!("Success hitting `{}`", source);
-
-        let content_length = response
-            .headers()
-            .get(reqwest::header::CONTENT_LENGTH)
-            .and_then(|hv| hv.to_str().ok())
-            .and_then(|s| s.parse::<i64>().ok());
-
-        let timeout = content_length.map(|cl| content_length_timeout(cl, timeouts.streaming));
-        let stream = response.bytes_stream().map_err(CacheError::from);
-        let future = download_stream(source_name, stream, destination);
-
-        match timeout {
-            Some(timeout) => tokio::time::timeout(timeout, future)
-                .await
-                .map_err(|_| CacheError::Timeout(timeout))?,
-            None => future.await,
-        }
+        Ok(response)
     } else if matches!(status, StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED) {
         tracing::debug!(
             "Insufficient permissions to download `{}`: {}",
@@ -567,6 +1071,10 @@ async fn download_reqwest(
         let details = status.to_string();
 
         Err(CacheError::PermissionDenied(details))
+    } else if status == StatusCode::TOO_MANY_REQUESTS {
+        tracing::debug!("Too many requests to `{}`: {}", source, status);
+
+        Err(CacheError::DownloadError(status.to_string()))
         // If it's a client error, chances are it's a 404.
     } else if status.is_client_error() {
         tracing::debug!(
@@ -594,6 +1102,80 @@ async fn download_reqwest(
     }
 }
 
+/// Writes the body of a successful response to the destination.
+async fn stream_reqwest(
+    source_name: &str,
+    response: reqwest::Response,
+    timeouts: &DownloadTimeouts,
+    destination: &mut Destination,
+) -> CacheEntry {
+    let timeout =
+        content_length(&response).map(|cl| content_length_timeout(cl, timeouts.streaming));
+    let stream = response.bytes_stream().map_err(CacheError::from);
+    let future = download_stream(source_name, stream, destination);
+
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| CacheError::Timeout(timeout))?,
+        None => future.await,
+    }
+}
+
+/// Whether the `Content-Type` of a response is an HTML page.
+fn is_html(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|hv| hv.to_str().ok())
+        .is_some_and(|content_type| {
+            content_type
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("text/html")
+        })
+}
+
+/// Returns the `Content-Length` of a response.
+///
+/// For encoded responses, this is the length of the encoded body.
+fn content_length(response: &reqwest::Response) -> Option<i64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|s| s.parse::<i64>().ok())
+}
+
+/// Returns the validator to send as `If-Range` when resuming a download.
+///
+/// Weak entity tags can not be used for ranges, in which case this falls back to the
+/// `Last-Modified` date.
+fn resume_validator(headers: &reqwest::header::HeaderMap) -> Option<reqwest::header::HeaderValue> {
+    headers
+        .get(reqwest::header::ETAG)
+        .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+        .or_else(|| headers.get(reqwest::header::LAST_MODIFIED))
+        .cloned()
+}
+
+/// Whether the response contains the file starting at the given offset.
+fn is_resumed_at(response: &reqwest::Response, offset: u64) -> bool {
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return false;
+    }
+
+    // The header looks like `bytes 100-199/200`.
+    let start = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes "))
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.parse::<u64>().ok());
+    start == Some(offset)
+}
+
 /// State of the [`MeasureSourceDownloadGuard`].
 #[derive(Clone, Copy, Debug)]
 enum MeasureState {
@@ -717,6 +1299,9 @@ mod tests {
 
     use super::*;
 
+    use symbolicator_sources::{DirectoryLayoutType, FilesystemSourceConfig, SourceId};
+
+    use crate::config::CircuitBreakerConfig;
     use crate::test;
 
     #[tokio::test]
@@ -749,6 +1334,132 @@ mod tests {
         assert_eq!(content, "hello world\n")
     }
 
+    #[tokio::test]
+    async fn test_download_circuit_breaker() {
+        test::setup();
+
+        let server = test::Server::new();
+        let source = match server.source("flaky", "/") {
+            SourceConfig::Http(source) => source,
+            _ => panic!("unexpected source"),
+        };
+        let file = |path: &str| -> RemoteFile {
+            HttpRemoteFile::new(source.clone(), SourceLocation::new(path)).into()
+        };
+
+        let config = Config {
+            connect_to_reserved_ips: true,
+            circuit_breaker: CircuitBreakerConfig {
+                enabled: true,
+                min_downloads: 1,
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+        let service = DownloadService::new(&config, tokio::runtime::Handle::current());
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+
+        let result = service
+            .download(
+                file("respond_statuscode/500/hello.txt"),
+                temp_file.path().to_owned(),
+            )
+            .await;
+        assert!(matches!(result, Err(CacheError::DownloadError(_))));
+        assert!(server.accesses() > 0);
+
+        // The source is not contacted anymore.
+        let result = service
+            .download(file("symbols/hello.txt"), temp_file.path().to_owned())
+            .await;
+        assert_eq!(
+            result,
+            Err(CacheError::Unavailable("circuit breaker is open".into()))
+        );
+        assert_eq!(server.accesses(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_rate_limited() {
+        test::setup();
+
+        let server = test::Server::new();
+        let source = match server.source("limited", "/") {
+            SourceConfig::Http(source) => source,
+            _ => panic!("unexpected source"),
+        };
+        let file = |path: &str| -> RemoteFile {
+            HttpRemoteFile::new(source.clone(), SourceLocation::new(path)).into()
+        };
+
+        let config = Config {
+            connect_to_reserved_ips: true,
+            ..Config::default()
+        };
+        let service = DownloadService::new(&config, tokio::runtime::Handle::current());
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+
+        // The download is retried once the announced time has passed.
+        let start = Instant::now();
+        let result = service
+            .download(
+                file("rate_limited/1/hello.txt"),
+                temp_file.path().to_owned(),
+            )
+            .await;
+        assert_eq!(result, Ok(()));
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.accesses(), 2);
+        let content = std::fs::read_to_string(temp_file.path()).unwrap();
+        assert_eq!(content, "hello world\n");
+
+        // Downloads do not wait for long rate limits.
+        let result = service
+            .download(
+                file("rate_limited/3600/hello.txt"),
+                temp_file.path().to_owned(),
+            )
+            .await;
+        assert!(matches!(result, Err(CacheError::DownloadError(_))));
+        assert_eq!(server.accesses(), 1);
+
+        // The rate limit applies to all downloads from the source.
+        let result = service
+            .download(file("symbols/hello.txt"), temp_file.path().to_owned())
+            .await;
+        assert!(matches!(result, Err(CacheError::DownloadError(_))));
+        assert_eq!(server.accesses(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_encoded() {
+        test::setup();
+
+        let server = test::Server::new();
+        let source = match server.source("encoded", "/encoded/") {
+            SourceConfig::Http(source) => source,
+            _ => panic!("unexpected source"),
+        };
+        let file = |path: &str| -> RemoteFile {
+            HttpRemoteFile::new(source.clone(), SourceLocation::new(path)).into()
+        };
+
+        let config = Config {
+            connect_to_reserved_ips: true,
+            ..Config::default()
+        };
+        let service = DownloadService::new(&config, tokio::runtime::Handle::current());
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+
+        // The response is encoded, and decoded while downloading.
+        let result = service
+            .download(file("hello.txt"), temp_file.path().to_owned())
+            .await;
+        assert_eq!(result, Ok(()));
+        let content = std::fs::read_to_string(temp_file.path()).unwrap();
+        assert_eq!(content, "hello world\n");
+    }
+
     #[tokio::test]
     async fn test_list_files() {
         test::setup();
@@ -774,6 +1485,53 @@ mod tests {
         assert_eq!(item.source_id(), source.id());
     }
 
+    #[tokio::test]
+    async fn test_list_files_symstore_index2() {
+        test::setup();
+
+        let source = |path| {
+            SourceConfig::Filesystem(Arc::new(FilesystemSourceConfig {
+                id: SourceId::new("symstore"),
+                path: test::fixture(path),
+                files: CommonSourceConfig::with_layout(DirectoryLayoutType::Symstore),
+            }))
+        };
+        let objid = ObjectId {
+            debug_id: Some("3249d99d-0c40-4931-8610-f4e4fb0b6936-1".parse().unwrap()),
+            debug_file: Some("crash.pdb".into()),
+            object_type: ObjectType::Pe,
+            ..Default::default()
+        };
+
+        let config = Config::default();
+        let svc = DownloadService::new(&config, tokio::runtime::Handle::current());
+        let paths = |files: Vec<RemoteFile>| {
+            files
+                .iter()
+                .map(|file| match file {
+                    RemoteFile::Filesystem(file) => file.path(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Symbol stores without an `index2.txt` use the configured layout.
+        let files = svc
+            .list_files(&[source("symbols")], &[FileType::Pdb], &objid)
+            .await;
+        // The listed files do not have to exist.
+        let dir = test::fixture("symbols").join("crash.pdb/3249D99D0C4049318610F4E4FB0B69361");
+        assert_eq!(paths(files), [dir.join("crash.pdb"), dir.join("crash.pd_")]);
+
+        // Two-tier symbol stores are detected.
+        let files = svc
+            .list_files(&[source("symstore-index2")], &[FileType::Pdb], &objid)
+            .await;
+        let dir =
+            test::fixture("symstore-index2").join("cr/crash.pdb/3249D99D0C4049318610F4E4FB0B69361");
+        assert_eq!(paths(files), [dir.join("crash.pdb"), dir.join("crash.pd_")]);
+    }
+
     #[test]
     fn test_content_length_timeout() {
         let timeout_per_gb = Duration::from_secs(30);
@@ -0,0 +1,94 @@
+//! Support to download from NuGet symbol servers.
+
+use reqwest::{header, Client};
+
+use symbolicator_sources::NugetRemoteFile;
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::utils::http::DownloadTimeouts;
+
+use super::{Destination, USER_AGENT};
+
+/// The header by which clients prove that they know the checksum of the requested file.
+const SYMBOL_CHECKSUM: &str = "SymbolChecksum";
+
+/// Downloader implementation that supports the NuGet source.
+#[derive(Debug)]
+pub struct NugetDownloader {
+    client: Client,
+    timeouts: DownloadTimeouts,
+}
+
+impl NugetDownloader {
+    pub fn new(client: Client, timeouts: DownloadTimeouts) -> Self {
+        Self { client, timeouts }
+    }
+
+    /// Downloads a Portable PDB from a NuGet symbol server.
+    pub async fn download_source(
+        &self,
+        source_name: &str,
+        file_source: &NugetRemoteFile,
+        destination: &mut Destination,
+    ) -> CacheEntry {
+        let download_url = file_source.url().map_err(|_| CacheError::NotFound)?;
+
+        tracing::debug!("Fetching debug file from `{}`", download_url);
+
+        let mut builder = self.client.get(download_url);
+        for (key, value) in &file_source.source.headers {
+            if let Ok(key) = header::HeaderName::from_bytes(key.as_bytes()) {
+                builder = builder.header(key, value.as_str());
+            }
+        }
+        builder = builder
+            .header(SYMBOL_CHECKSUM, file_source.symbol_checksum.as_str())
+            .header(header::USER_AGENT, USER_AGENT);
+
+        super::download_reqwest(source_name, builder, &self.timeouts, destination).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    use symbolicator_sources::{NugetSourceConfig, SourceId, SourceLocation};
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_download_source() {
+        test::setup();
+
+        let server = test::Server::new();
+        let source = Arc::new(NugetSourceConfig {
+            id: SourceId::new("nuget-test"),
+            url: server.url("symbols/"),
+            headers: Default::default(),
+            filters: Default::default(),
+            is_public: false,
+            limits: Default::default(),
+            priority: 0,
+        });
+        let downloader = NugetDownloader::new(Client::new(), Default::default());
+
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let file = tokio::fs::File::create(tmpfile.path()).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
+
+        let file_source = NugetRemoteFile::new(
+            source,
+            SourceLocation::new("hello.txt"),
+            "SHA256:0000".into(),
+        );
+        let download_status = downloader
+            .download_source("", &file_source, &mut destination)
+            .await;
+
+        assert_eq!(download_status, Ok(()));
+        assert_eq!(std::fs::read(tmpfile.path()).unwrap(), b"hello world\n");
+    }
+}
@@ -0,0 +1,216 @@
+//! Honors the rate limits announced by sources.
+//!
+//! Servers announce rate limits by responding with `429 Too Many Requests` or
+//! `503 Service Unavailable`, along with a `Retry-After` header. All downloads from a source share
+//! one [`RateLimit`], so once a server asks to back off, no download from that source starts
+//! before the announced time, including the retries of the rate limited download.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+use symbolicator_sources::SourceId;
+
+use crate::caching::{CacheEntry, CacheError};
+
+/// Rate limits which have not been used for this long are dropped.
+const RATE_LIMIT_IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// The maximum number of sources with a rate limit.
+const RATE_LIMIT_CAPACITY: u64 = 10_000;
+
+/// The longest time a download waits for a rate limit to pass.
+///
+/// Downloads from sources which are rate limited for longer fail right away.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Returns how long the server asked to wait before sending the next request.
+///
+/// This is only the case for `429` and `503` responses with a valid `Retry-After` header, which
+/// contains either a number of seconds or an HTTP date.
+pub fn retry_after(status: StatusCode, headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if !matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // HTTP dates are a subset of RFC 2822 dates, like `Wed, 21 Oct 2015 07:28:00 GMT`.
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        SystemTime::from(date)
+            .duration_since(now)
+            .unwrap_or_default(),
+    )
+}
+
+/// The rate limit of a single source.
+#[derive(Debug)]
+pub struct RateLimit {
+    /// The point in time until which no download may start.
+    limited_until: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    fn new() -> Self {
+        Self {
+            limited_until: Mutex::new(None),
+        }
+    }
+
+    /// Records that the source asked to wait for `delay` before sending more requests.
+    pub fn limit(&self, source_name: &str, delay: Duration) {
+        metric!(
+            counter("download.rate_limited") += 1,
+            "source" => source_name,
+        );
+        self.limit_at(delay, Instant::now());
+    }
+
+    fn limit_at(&self, delay: Duration, now: Instant) {
+        let mut limited_until = self.limited_until.lock().unwrap();
+        let until = now + delay;
+        if limited_until.is_none_or(|current| current < until) {
+            *limited_until = Some(until);
+        }
+    }
+
+    /// Returns how long downloads still have to wait.
+    fn remaining(&self, now: Instant) -> Duration {
+        let limited_until = self.limited_until.lock().unwrap();
+        limited_until.map_or(Duration::ZERO, |until| until.saturating_duration_since(now))
+    }
+
+    /// Waits until the rate limit of the source has passed.
+    ///
+    /// Returns a `DownloadError` without waiting if the source is rate limited for longer than
+    /// a minute.
+    pub async fn wait(&self, source_name: &str) -> CacheEntry {
+        let delay = self.remaining(Instant::now());
+        if delay.is_zero() {
+            return Ok(());
+        }
+        if delay > MAX_RATE_LIMIT_WAIT {
+            metric!(
+                counter("download.rate_limit.rejected") += 1,
+                "source" => source_name,
+            );
+            return Err(CacheError::DownloadError(format!(
+                "Source is rate limited for another {}",
+                humantime::format_duration(Duration::from_secs(delay.as_secs()))
+            )));
+        }
+
+        metric!(
+            timer("download.rate_limit.wait") = delay,
+            "source" => source_name,
+        );
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+/// The [`RateLimit`]s of all sources.
+#[derive(Debug)]
+pub struct RateLimits {
+    limits: moka::sync::Cache<SourceId, Arc<RateLimit>>,
+}
+
+impl RateLimits {
+    pub fn new() -> Self {
+        Self {
+            limits: moka::sync::Cache::builder()
+                .max_capacity(RATE_LIMIT_CAPACITY)
+                .time_to_idle(RATE_LIMIT_IDLE_TIMEOUT)
+                .build(),
+        }
+    }
+
+    /// Returns the rate limit of the given source.
+    pub fn get(&self, source_id: &SourceId) -> Arc<RateLimit> {
+        self.limits
+            .get_with_by_ref(source_id, || Arc::new(RateLimit::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1445412420);
+        let status = StatusCode::TOO_MANY_REQUESTS;
+
+        assert_eq!(
+            retry_after(status, &headers("120"), now),
+            Some(Duration::from_secs(120))
+        );
+        // `now` is `Wed, 21 Oct 2015 07:27:00 GMT`.
+        assert_eq!(
+            retry_after(status, &headers("Wed, 21 Oct 2015 07:28:00 GMT"), now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            retry_after(status, &headers("Wed, 21 Oct 2015 07:00:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(status, &headers("soon"), now), None);
+        assert_eq!(retry_after(status, &HeaderMap::new(), now), None);
+
+        assert_eq!(
+            retry_after(StatusCode::SERVICE_UNAVAILABLE, &headers("5"), now),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(retry_after(StatusCode::OK, &headers("5"), now), None);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit::new();
+        let now = Instant::now();
+        assert_eq!(limit.remaining(now), Duration::ZERO);
+
+        limit.limit_at(Duration::from_secs(10), now);
+        assert_eq!(limit.remaining(now), Duration::from_secs(10));
+
+        // Shorter limits do not lift longer ones.
+        limit.limit_at(Duration::from_secs(1), now);
+        assert_eq!(limit.remaining(now), Duration::from_secs(10));
+
+        let later = now + Duration::from_secs(20);
+        assert_eq!(limit.remaining(later), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_wait() {
+        let limits = RateLimits::new();
+        let limit = limits.get(&SourceId::new("limited"));
+        assert!(Arc::ptr_eq(&limit, &limits.get(&SourceId::new("limited"))));
+
+        limit.limit("http", Duration::from_millis(20));
+        assert_eq!(limit.wait("http").await, Ok(()));
+        assert_eq!(limit.remaining(Instant::now()), Duration::ZERO);
+
+        // Downloads do not wait for long rate limits.
+        limit.limit("http", Duration::from_secs(3600));
+        let result = limit.wait("http").await;
+        assert!(matches!(result, Err(CacheError::DownloadError(_))));
+    }
+}
@@ -0,0 +1,274 @@
+//! Follows redirects according to the redirect settings of HTTP sources.
+//!
+//! Clients of sources with [`HttpRedirectConfig`] do not follow redirects on their own. Instead,
+//! [`send_reqwest`](super::send_reqwest) asks the [`RedirectPolicy`] of the source for the
+//! request to send next. Sources with a proxy always follow redirects this way, so their
+//! destinations are checked before the proxy resolves them.
+
+use std::sync::Arc;
+
+use reqwest::header::{self, HeaderMap, HeaderName};
+use reqwest::{Request, StatusCode, Url};
+
+use symbolicator_sources::HttpRedirectConfig;
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::utils::dns::DnsResolver;
+use crate::utils::http::BlockedDestinations;
+
+/// Headers which are always considered credentials.
+const CREDENTIAL_HEADERS: [HeaderName; 3] = [
+    header::AUTHORIZATION,
+    header::COOKIE,
+    header::PROXY_AUTHORIZATION,
+];
+
+/// Decides which redirects of a source are followed.
+#[derive(Debug)]
+pub struct RedirectPolicy {
+    config: Arc<HttpRedirectConfig>,
+    /// Additional headers which are stripped from redirects to other hosts.
+    credentials: Vec<HeaderName>,
+    blocked: BlockedDestinations,
+    /// Checks the redirect destinations of sources with a proxy, see [`Self::proxied`].
+    proxied: Option<DnsResolver>,
+}
+
+impl RedirectPolicy {
+    /// Creates the policy for a source.
+    ///
+    /// `credentials` are the headers configured for the source, which are treated like the
+    /// `Authorization` header.
+    pub fn new(
+        config: Arc<HttpRedirectConfig>,
+        credentials: Vec<HeaderName>,
+        blocked: BlockedDestinations,
+    ) -> Self {
+        Self {
+            config,
+            credentials,
+            blocked,
+            proxied: None,
+        }
+    }
+
+    /// Checks the hosts redirected to with `dns`, as the proxy of the source resolves them.
+    pub fn proxied(mut self, dns: DnsResolver) -> Self {
+        self.proxied = Some(dns);
+        self
+    }
+
+    /// Checks the destination of a followed redirect before it is sent through a proxy.
+    pub async fn check_destination(&self, url: &Url) -> CacheEntry {
+        if let Some(dns) = &self.proxied {
+            dns.check_proxied(url)
+                .await
+                .map_err(|err| CacheError::Blocked(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the request to send if the response to `request` is a redirect that should be
+    /// followed.
+    ///
+    /// `redirected` is the number of redirects that have already been followed. Redirects to
+    /// other hosts which are not allowed are rejected with `PermissionDenied`, and redirects to
+    /// blocked destinations with `Blocked`.
+    pub fn follow(
+        &self,
+        mut request: Request,
+        status: StatusCode,
+        headers: &HeaderMap,
+        redirected: usize,
+    ) -> CacheEntry<Option<Request>> {
+        if !matches!(
+            status,
+            StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT
+        ) {
+            return Ok(None);
+        }
+        // Like the default policy, do not follow Azure DevOps to its login page.
+        if status == StatusCode::FOUND && request.url().host_str() == Some("dev.azure.com") {
+            return Ok(None);
+        }
+        let Some(location) = headers.get(header::LOCATION) else {
+            return Ok(None);
+        };
+
+        let url = location
+            .to_str()
+            .ok()
+            .and_then(|location| request.url().join(location).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| CacheError::DownloadError("invalid redirect location".into()))?;
+        if redirected >= self.config.max_redirects {
+            return Err(CacheError::DownloadError(format!(
+                "too many redirects, the limit is {}",
+                self.config.max_redirects
+            )));
+        }
+
+        self.blocked
+            .check_url(&url)
+            .map_err(|err| CacheError::Blocked(err.to_string()))?;
+
+        let cross_host = !is_same_host(request.url(), &url);
+        if cross_host && !self.config.allow_cross_host {
+            return Err(CacheError::PermissionDenied(format!(
+                "redirect to another host is not allowed: {}",
+                url.host_str().unwrap_or_default()
+            )));
+        }
+
+        if cross_host && !self.config.forward_credentials {
+            let headers = request.headers_mut();
+            for name in CREDENTIAL_HEADERS.iter().chain(&self.credentials) {
+                headers.remove(name);
+            }
+        }
+
+        metric!(
+            counter("download.redirect") += 1,
+            "cross_host" => if cross_host { "true" } else { "false" },
+        );
+        *request.url_mut() = url;
+        Ok(Some(request))
+    }
+}
+
+/// Whether both URLs point to the same origin.
+fn is_same_host(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str) -> Request {
+        let mut request = Request::new(reqwest::Method::GET, url.parse().unwrap());
+        let headers = request.headers_mut();
+        headers.insert(header::AUTHORIZATION, "Bearer token".parse().unwrap());
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        headers.insert(header::USER_AGENT, "symbolicator".parse().unwrap());
+        request
+    }
+
+    fn location(location: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::LOCATION, location.parse().unwrap());
+        headers
+    }
+
+    fn policy(config: HttpRedirectConfig) -> RedirectPolicy {
+        let credentials = vec![HeaderName::from_static("x-api-key")];
+        RedirectPolicy::new(Arc::new(config), credentials, Default::default())
+    }
+
+    #[test]
+    fn test_follow_same_host() {
+        let policy = policy(Default::default());
+        let headers = location("/other/file.pdb");
+
+        let next = policy
+            .follow(
+                request("https://symbols.example.com/file.pdb"),
+                StatusCode::FOUND,
+                &headers,
+                0,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            next.url().as_str(),
+            "https://symbols.example.com/other/file.pdb"
+        );
+        assert!(next.headers().contains_key(header::AUTHORIZATION));
+        assert!(next.headers().contains_key("x-api-key"));
+
+        // Other responses are not followed.
+        let url = "https://symbols.example.com/";
+        let next = policy.follow(request(url), StatusCode::OK, &headers, 0);
+        assert!(next.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_follow_cross_host() {
+        let url = "https://symbols.example.com/file.pdb";
+        let status = StatusCode::TEMPORARY_REDIRECT;
+        let headers = location("https://cdn.example.net/file.pdb?signature=abc");
+
+        let next = policy(Default::default())
+            .follow(request(url), status, &headers, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.url().host_str(), Some("cdn.example.net"));
+        assert!(!next.headers().contains_key(header::AUTHORIZATION));
+        assert!(!next.headers().contains_key("x-api-key"));
+        assert!(next.headers().contains_key(header::USER_AGENT));
+
+        let config = HttpRedirectConfig {
+            forward_credentials: true,
+            ..Default::default()
+        };
+        let next = policy(config)
+            .follow(request(url), status, &headers, 0)
+            .unwrap()
+            .unwrap();
+        assert!(next.headers().contains_key(header::AUTHORIZATION));
+        assert!(next.headers().contains_key("x-api-key"));
+
+        let config = HttpRedirectConfig {
+            allow_cross_host: false,
+            ..Default::default()
+        };
+        let result = policy(config).follow(request(url), status, &headers, 0);
+        assert!(matches!(result, Err(CacheError::PermissionDenied(_))));
+    }
+
+    #[test]
+    fn test_max_redirects() {
+        let config = HttpRedirectConfig {
+            max_redirects: 1,
+            ..Default::default()
+        };
+        let policy = policy(config);
+        let status = StatusCode::MOVED_PERMANENTLY;
+        let headers = location("/next");
+
+        let url = "https://symbols.example.com/file.pdb";
+        assert!(policy.follow(request(url), status, &headers, 0).is_ok());
+        let result = policy.follow(request(url), status, &headers, 1);
+        assert!(matches!(result, Err(CacheError::DownloadError(_))));
+    }
+
+    #[test]
+    fn test_follow_blocked() {
+        let blocked = BlockedDestinations::new(&["169.254.169.254".into()]);
+        let policy = RedirectPolicy::new(Arc::new(Default::default()), Vec::new(), blocked);
+        let headers = location("http://169.254.169.254/latest/meta-data/");
+
+        let url = "https://symbols.example.com/file.pdb";
+        let result = policy.follow(request(url), StatusCode::FOUND, &headers, 0);
+        assert!(matches!(result, Err(CacheError::Blocked(_))));
+    }
+
+    #[tokio::test]
+    async fn test_check_proxied_destination() {
+        let blocked = BlockedDestinations::new(&["10.0.0.0/8".into()]);
+        let dns = DnsResolver::new(&Default::default(), blocked.clone());
+        let policy = RedirectPolicy::new(Arc::new(Default::default()), Vec::new(), blocked);
+        let url: Url = "http://internal.invalid/file.pdb".parse().unwrap();
+
+        // Without a proxy, the host is checked when the client resolves it.
+        assert!(policy.check_destination(&url).await.is_ok());
+        let result = policy.proxied(dns).check_destination(&url).await;
+        assert!(matches!(result, Err(CacheError::Blocked(_))));
+    }
+}
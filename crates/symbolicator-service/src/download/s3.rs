@@ -5,23 +5,39 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use aws_config::ecs::EcsCredentialsProvider;
+use aws_config::imds::credentials::ImdsCredentialsProvider;
+use aws_config::web_identity_token::WebIdentityTokenCredentialsProvider;
 use aws_credential_types::provider::ProvideCredentials;
 use aws_credential_types::Credentials;
 use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::builders::GetObjectFluentBuilder;
+use aws_sdk_s3::operation::get_object::GetObjectOutput;
+use aws_sdk_s3::types::{RequestPayer, ServerSideEncryption};
 use aws_sdk_s3::Client;
 pub use aws_sdk_s3::Error as S3Error;
 use futures::TryStreamExt as _;
 use symbolicator_sources::{AwsCredentialsProvider, S3Region, S3RemoteFile, S3SourceKey};
-use tokio::fs::File;
 
 use crate::caching::{CacheEntry, CacheError};
 use crate::utils::http::DownloadTimeouts;
 
-use super::content_length_timeout;
+use super::{content_length_timeout, Checksum, Destination};
 
 type ClientCache = moka::future::Cache<Arc<S3SourceKey>, Arc<Client>>;
 
+/// Whether the response contains the object starting at the given offset.
+fn is_resumed_at(response: &GetObjectOutput, offset: u64) -> bool {
+    // The range looks like `bytes 100-199/200`.
+    let start = response
+        .content_range
+        .as_deref()
+        .and_then(|range| range.strip_prefix("bytes "))
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.parse::<u64>().ok());
+    start == Some(offset)
+}
+
 /// Downloader implementation that supports the S3 source.
 pub struct S3Downloader {
     client_cache: ClientCache,
@@ -58,6 +74,17 @@ impl S3Downloader {
                     self.create_s3_client(EcsCredentialsProvider::builder().build(), &key.region)
                         .await
                 }
+                AwsCredentialsProvider::WebIdentity => {
+                    self.create_s3_client(
+                        WebIdentityTokenCredentialsProvider::builder().build(),
+                        &key.region,
+                    )
+                    .await
+                }
+                AwsCredentialsProvider::Instance => {
+                    self.create_s3_client(ImdsCredentialsProvider::builder().build(), &key.region)
+                        .await
+                }
                 AwsCredentialsProvider::Static => {
                     self.create_s3_client(
                         Credentials::from_keys(
@@ -84,6 +111,7 @@ impl S3Downloader {
         provider: impl ProvideCredentials + 'static,
         region: &S3Region,
     ) -> Client {
+        // The SDK caches the credentials of the provider, and refreshes them before they expire.
         let mut config_loader = aws_config::from_env()
             .credentials_provider(provider)
             .region(region.region.clone());
@@ -101,7 +129,7 @@ impl S3Downloader {
         &self,
         source_name: &str,
         file_source: &S3RemoteFile,
-        destination: &mut File,
+        destination: &mut Destination,
     ) -> CacheEntry {
         let key = file_source.key();
         let bucket = file_source.bucket();
@@ -109,7 +137,84 @@ impl S3Downloader {
 
         let source_key = file_source.source.source_key.clone();
         let client = self.get_s3_client(&source_key).await;
-        let request = client.get_object().bucket(&bucket).key(&key).send();
+        let request_payer = file_source
+            .source
+            .requester_pays
+            .then_some(RequestPayer::Requester);
+        let request = client
+            .get_object()
+            .bucket(&bucket)
+            .key(&key)
+            .set_request_payer(request_payer);
+
+        let response = self
+            .send_request(source_name, request.clone(), &bucket, &key)
+            .await?;
+
+        if response.content_length == Some(0) {
+            tracing::debug!(bucket, key, "Empty response from s3");
+            return Err(CacheError::NotFound);
+        }
+
+        // The ETag is not the MD5 of the object if it is encrypted using a KMS or customer key.
+        let is_plain_etag = matches!(
+            response.server_side_encryption,
+            None | Some(ServerSideEncryption::Aes256)
+        ) && response.sse_customer_algorithm.is_none();
+        let checksum = response.e_tag.as_deref().and_then(Checksum::from_s3_etag);
+        if let Some(checksum) = checksum.filter(|_| is_plain_etag) {
+            destination.set_checksum(checksum);
+        }
+
+        // Unlike `reqwest`, the S3 client does not transparently decode the content.
+        if let Some(content_encoding) = response.content_encoding.as_deref() {
+            destination.set_content_encoding(content_encoding);
+        }
+
+        // The ETag makes sure that a resumed download continues with the same object.
+        let e_tag = response.e_tag.clone();
+        let mut result = self
+            .stream_response(source_name, response, destination)
+            .await;
+
+        while let Err(err @ (CacheError::DownloadError(_) | CacheError::Timeout(_))) = &result {
+            let Some(e_tag) = &e_tag else {
+                break;
+            };
+            let Some(offset) = destination.resume() else {
+                break;
+            };
+
+            tracing::debug!(bucket, key, offset, "Resuming download from s3: {}", err);
+            metric!(counter("download.resume") += 1, "source" => source_name);
+
+            let request = request
+                .clone()
+                .range(format!("bytes={offset}-"))
+                .if_match(e_tag);
+            result = match self.send_request(source_name, request, &bucket, &key).await {
+                Ok(response) if is_resumed_at(&response, offset) => {
+                    self.stream_response(source_name, response, destination)
+                        .await
+                }
+                Ok(_) => break,
+                Err(err @ (CacheError::DownloadError(_) | CacheError::Timeout(_))) => Err(err),
+                Err(_) => break,
+            };
+        }
+
+        result
+    }
+
+    /// Sends a `GetObject` request, and maps failures to errors.
+    async fn send_request(
+        &self,
+        source_name: &str,
+        request: GetObjectFluentBuilder,
+        bucket: &str,
+        key: &str,
+    ) -> CacheEntry<GetObjectOutput> {
+        let request = request.send();
 
         let timeout = self.timeouts.head;
         let request = tokio::time::timeout(timeout, request);
@@ -117,8 +222,8 @@ impl S3Downloader {
 
         let response = request.await.map_err(|_| CacheError::Timeout(timeout))?; // Timeout
 
-        let response = match response {
-            Ok(response) => response,
+        match response {
+            Ok(response) => Ok(response),
             Err(err) => {
                 tracing::debug!("Skipping response from s3://{}/{}: {}", &bucket, &key, err);
 
@@ -155,7 +260,7 @@ impl S3Downloader {
                 };
 
                 let err = S3Error::from(err);
-                return match &err {
+                match &err {
                     S3Error::NoSuchBucket(_) | S3Error::NoSuchKey(_) | S3Error::NotFound(_) => {
                         Err(CacheError::NotFound)
                     }
@@ -180,15 +285,18 @@ impl S3Downloader {
                         let details = err.to_string();
                         Err(CacheError::DownloadError(details))
                     }
-                };
+                }
             }
-        };
-
-        if response.content_length == Some(0) {
-            tracing::debug!(bucket, key, "Empty response from s3");
-            return Err(CacheError::NotFound);
         }
+    }
 
+    /// Writes the body of a successful response to the destination.
+    async fn stream_response(
+        &self,
+        source_name: &str,
+        response: GetObjectOutput,
+        destination: &mut Destination,
+    ) -> CacheEntry {
         let timeout = response
             .content_length
             .map(|cl| content_length_timeout(cl, self.timeouts.streaming));
@@ -259,6 +367,8 @@ mod tests {
             id: SourceId::new("s3-test"),
             bucket: S3_BUCKET.to_owned(),
             prefix: String::new(),
+            requester_pays: false,
+            canary: None,
             source_key: Arc::new(source_key),
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         })
@@ -375,7 +485,8 @@ mod tests {
         let source_location = SourceLocation::new("50/2fc0a51ec13e479998684fa139dca7/debuginfo");
         let file_source = S3RemoteFile::new(source, source_location);
 
-        let mut destination = tokio::fs::File::create(&target_path).await.unwrap();
+        let file = tokio::fs::File::create(&target_path).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
         let download_status = downloader
             .download_source("", &file_source, &mut destination)
             .await;
@@ -404,7 +515,8 @@ mod tests {
         let source_location = SourceLocation::new("does/not/exist");
         let file_source = S3RemoteFile::new(source, source_location);
 
-        let mut destination = tokio::fs::File::create(&target_path).await.unwrap();
+        let file = tokio::fs::File::create(&target_path).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
         let download_status = downloader
             .download_source("", &file_source, &mut destination)
             .await;
@@ -431,7 +543,8 @@ mod tests {
         let source_location = SourceLocation::new("does/not/exist");
         let file_source = S3RemoteFile::new(source, source_location);
 
-        let mut destination = tokio::fs::File::create(&target_path).await.unwrap();
+        let file = tokio::fs::File::create(&target_path).await.unwrap();
+        let mut destination = Destination::new(file, &Default::default());
         let download_status = downloader
             .download_source("", &file_source, &mut destination)
             .await;
@@ -454,6 +567,8 @@ mod tests {
             id: SourceId::new("s3-id"),
             bucket: String::from("bucket"),
             prefix: String::from("prefix"),
+            requester_pays: false,
+            canary: None,
             source_key,
             files: CommonSourceConfig::with_layout(DirectoryLayoutType::Unified),
         });
@@ -0,0 +1,310 @@
+//! Restricting the resources available to external decompression tools.
+//!
+//! External tools are processing untrusted input. When the sandbox is enabled, they run with
+//! limits on memory, CPU time and the size of files they write. On Unix, these are enforced
+//! using resource limits, and on Linux, a seccomp filter additionally denies access to the
+//! network and to other processes. On Windows, the tools are spawned suspended and placed in a
+//! job object before they start running.
+
+use std::io;
+use std::process::{Child, Command};
+
+use crate::config::SandboxConfig;
+
+/// The sandbox of a single external tool process.
+///
+/// This has to be kept alive until the process has exited.
+pub struct Sandbox {
+    #[cfg(windows)]
+    job: windows::JobObject,
+}
+
+impl Sandbox {
+    /// Prepares the `command` to be spawned inside the sandbox.
+    pub fn new(command: &mut Command, config: &SandboxConfig) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            unix::configure(command, config)?;
+            Ok(Self {})
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+
+            // The tool may only start running once it has been assigned to the job.
+            command.creation_flags(windows::CREATE_SUSPENDED);
+            Ok(Self {
+                job: windows::JobObject::new(config)?,
+            })
+        }
+
+        #[cfg(not(any(unix, windows)))]
+        {
+            let _ = (command, config);
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "sandboxing external tools is not supported on this platform",
+            ))
+        }
+    }
+
+    /// Moves the spawned `child` into the sandbox.
+    ///
+    /// On Windows, this resumes the suspended `child` once it is in the job. On Unix, the
+    /// restrictions are applied before the tool is executed, so this does nothing.
+    pub fn enter(&self, child: &Child) -> io::Result<()> {
+        #[cfg(windows)]
+        {
+            self.job.assign(child)?;
+            windows::resume(child)
+        }
+
+        #[cfg(not(windows))]
+        {
+            let _ = child;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    use crate::config::SandboxConfig;
+
+    pub fn configure(command: &mut Command, config: &SandboxConfig) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_AS, config.max_memory),
+            (
+                libc::RLIMIT_CPU,
+                config.max_cpu_time.map(|time| time.as_secs().max(1)),
+            ),
+            (libc::RLIMIT_FSIZE, config.max_file_size),
+            // Crashing tools should not litter the disk with core dumps.
+            (libc::RLIMIT_CORE, Some(0)),
+        ];
+
+        // The filter is compiled ahead of time, as allocating after forking is not safe.
+        #[cfg(target_os = "linux")]
+        let filter = super::linux::seccomp_filter()?;
+
+        let pre_exec = move || {
+            for (resource, limit) in limits {
+                let Some(limit) = limit else {
+                    continue;
+                };
+                let limit = libc::rlimit {
+                    rlim_cur: limit as libc::rlim_t,
+                    rlim_max: limit as libc::rlim_t,
+                };
+                // SAFETY: `setrlimit` is async-signal-safe, and `limit` is a valid `rlimit`.
+                if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            #[cfg(target_os = "linux")]
+            seccompiler::apply_filter(&filter).map_err(|_| io::Error::last_os_error())?;
+
+            Ok(())
+        };
+
+        // SAFETY: The closure only performs async-signal-safe system calls, and does not
+        // allocate unless it fails.
+        unsafe { command.pre_exec(pre_exec) };
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::BTreeMap;
+    use std::io;
+
+    use seccompiler::{BpfProgram, SeccompAction, SeccompFilter, TargetArch};
+
+    /// The system calls which are denied to external tools.
+    ///
+    /// This is a denylist: the filter allows every system call by default, and only these
+    /// fail with `EPERM`. Decompression tools only need to read and write files, so this
+    /// denies everything related to the network and to inspecting or manipulating other
+    /// processes. The restrictions apply to the tool from its `execve` on, so that call itself
+    /// can not be denied.
+    const DENIED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept,
+        libc::SYS_accept4,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+    ];
+
+    pub fn seccomp_filter() -> io::Result<BpfProgram> {
+        let rules = DENIED_SYSCALLS
+            .iter()
+            .map(|&syscall| (syscall, vec![]))
+            .collect::<BTreeMap<_, _>>();
+        let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(io::Error::other)?;
+
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EPERM as u32),
+            arch,
+        )
+        .map_err(io::Error::other)?;
+
+        filter.try_into().map_err(io::Error::other)
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::mem;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+    use std::process::Child;
+
+    use windows_sys::Win32::Foundation::{HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+        JOB_OBJECT_LIMIT_PROCESS_TIME,
+    };
+
+    use windows_sys::Win32::System::Threading::{OpenThread, ResumeThread, THREAD_SUSPEND_RESUME};
+
+    pub use windows_sys::Win32::System::Threading::CREATE_SUSPENDED;
+
+    use crate::config::SandboxConfig;
+
+    /// A job object which kills its processes once it is closed.
+    ///
+    /// Job objects can not limit the size of written files. The output of external tools is
+    /// limited by the caller instead.
+    pub struct JobObject(OwnedHandle);
+
+    impl JobObject {
+        pub fn new(config: &SandboxConfig) -> io::Result<Self> {
+            // SAFETY: Both the security attributes and the name are optional.
+            let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+            if handle == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: The handle has just been created and is owned by nobody else.
+            let job = Self(unsafe { OwnedHandle::from_raw_handle(handle as _) });
+
+            // SAFETY: The struct only consists of integers, for which zero is a valid value.
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+            let basic = &mut info.BasicLimitInformation;
+            // Tools may not spawn further processes.
+            basic.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE
+                | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION
+                | JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+            basic.ActiveProcessLimit = 1;
+
+            if let Some(max_cpu_time) = config.max_cpu_time {
+                basic.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_TIME;
+                // in units of 100 nanoseconds
+                basic.PerProcessUserTimeLimit = (max_cpu_time.as_nanos() / 100) as i64;
+            }
+            if let Some(max_memory) = config.max_memory {
+                basic.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+                info.ProcessMemoryLimit = max_memory as usize;
+            }
+
+            // SAFETY: `info` matches the information class and its size.
+            let success = unsafe {
+                SetInformationJobObject(
+                    job.handle(),
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    mem::size_of_val(&info) as u32,
+                )
+            };
+            if success == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(job)
+        }
+
+        /// Assigns the `child` process to the job.
+        pub fn assign(&self, child: &Child) -> io::Result<()> {
+            // SAFETY: Both handles are valid for the duration of the call.
+            let success =
+                unsafe { AssignProcessToJobObject(self.handle(), child.as_raw_handle() as HANDLE) };
+            if success == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        fn handle(&self) -> HANDLE {
+            self.0.as_raw_handle() as HANDLE
+        }
+    }
+
+    /// Resumes the `child`, which has been spawned [suspended](CREATE_SUSPENDED).
+    ///
+    /// `std` does not expose the handle of the main thread, so the threads of the process are
+    /// looked up in a snapshot instead. A suspended process only has its main thread.
+    pub fn resume(child: &Child) -> io::Result<()> {
+        // SAFETY: The process ID is ignored for snapshots of threads.
+        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) };
+        if snapshot == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: The handle has just been created and is owned by nobody else.
+        let owned_snapshot = unsafe { OwnedHandle::from_raw_handle(snapshot as _) };
+        let snapshot = owned_snapshot.as_raw_handle() as HANDLE;
+
+        // SAFETY: The struct only consists of integers, for which zero is a valid value.
+        let mut entry: THREADENTRY32 = unsafe { mem::zeroed() };
+        entry.dwSize = mem::size_of::<THREADENTRY32>() as u32;
+
+        let mut resumed = false;
+        // SAFETY: The snapshot is valid, and the size of `entry` is set.
+        let mut found = unsafe { Thread32First(snapshot, &mut entry) };
+        while found != 0 {
+            if entry.th32OwnerProcessID == child.id() {
+                // SAFETY: Opening a thread by its ID has no preconditions.
+                let thread = unsafe { OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID) };
+                if thread == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // SAFETY: The handle has just been opened and is owned by nobody else.
+                let thread = unsafe { OwnedHandle::from_raw_handle(thread as _) };
+                // SAFETY: The handle is valid and has the access right to resume the thread.
+                if unsafe { ResumeThread(thread.as_raw_handle() as HANDLE) } == u32::MAX {
+                    return Err(io::Error::last_os_error());
+                }
+                resumed = true;
+            }
+            // SAFETY: As above.
+            found = unsafe { Thread32Next(snapshot, &mut entry) };
+        }
+
+        if !resumed {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the main thread of the external tool was not found",
+            ));
+        }
+        Ok(())
+    }
+}
@@ -2,21 +2,22 @@
 //!
 //! This allows to fetch files which were directly uploaded to Sentry itself.
 
+use std::collections::HashSet;
 use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
 
+use futures::future;
 use sentry::SentryFutureExt;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
-use tokio::fs::File;
 use url::Url;
 
 use symbolicator_sources::{
     ObjectId, RemoteFile, SentryFileId, SentryRemoteFile, SentrySourceConfig,
 };
 
-use super::{FileType, USER_AGENT};
+use super::{Destination, FileType, USER_AGENT};
 use crate::caching::{CacheEntry, CacheError};
 use crate::config::InMemoryCacheConfig;
 use crate::utils::futures::{m, measure, CancelOnDrop};
@@ -48,6 +49,7 @@ enum SentryFileType {
     BcSymbolMap,
     Il2cpp,
     Proguard,
+    Dwp,
 }
 
 impl From<FileType> for SentryFileType {
@@ -65,6 +67,7 @@ impl From<FileType> for SentryFileType {
             FileType::BcSymbolMap => Self::BcSymbolMap,
             FileType::Il2cpp => Self::Il2cpp,
             FileType::Proguard => Self::Proguard,
+            FileType::Dwp => Self::Dwp,
         }
     }
 }
@@ -159,11 +162,67 @@ impl SentryDownloader {
         }
     }
 
+    /// Lists the files of all the [endpoints](SentrySourceConfig::endpoints) of a source.
+    ///
+    /// The endpoints are queried concurrently, and their candidates are merged. This only fails
+    /// if all the endpoints fail.
     pub async fn list_files(
         &self,
         source: Arc<SentrySourceConfig>,
         object_id: &ObjectId,
         file_types: &[FileType],
+    ) -> CacheEntry<Vec<RemoteFile>> {
+        if source.organizations.is_empty() {
+            return self
+                .list_endpoint_files(source, object_id, file_types)
+                .await;
+        }
+
+        let endpoints = source.endpoints();
+        let results = future::join_all(
+            endpoints
+                .into_iter()
+                .map(|endpoint| self.list_endpoint_files(endpoint, object_id, file_types)),
+        )
+        .await;
+
+        let mut files = Vec::new();
+        let mut seen = HashSet::new();
+        let mut first_error = None;
+        let mut succeeded = false;
+        for result in results {
+            match result {
+                Ok(endpoint_files) => {
+                    succeeded = true;
+                    files.extend(
+                        endpoint_files
+                            .into_iter()
+                            .filter(|file| seen.insert(file.uri())),
+                    );
+                }
+                Err(error) => {
+                    let dyn_error: &dyn std::error::Error = &error;
+                    tracing::warn!(
+                        error = dyn_error,
+                        "Failed to list files of a Sentry organization"
+                    );
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+
+        match first_error {
+            Some(error) if !succeeded => Err(error),
+            _ => Ok(files),
+        }
+    }
+
+    /// Lists the files of a single endpoint.
+    async fn list_endpoint_files(
+        &self,
+        source: Arc<SentrySourceConfig>,
+        object_id: &ObjectId,
+        file_types: &[FileType],
     ) -> CacheEntry<Vec<RemoteFile>> {
         // There needs to be either a debug_id or a code_id filter in the query. Otherwise, this would
         // return a list of all debug files in the project.
@@ -256,7 +315,7 @@ impl SentryDownloader {
         &self,
         source_name: &str,
         file_source: &SentryRemoteFile,
-        destination: &mut File,
+        destination: &mut Destination,
     ) -> CacheEntry {
         let url = file_source.url();
         tracing::debug!("Fetching Sentry artifact from {}", url);
@@ -272,9 +331,11 @@ impl SentryDownloader {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
 
-    use symbolicator_sources::{RemoteFileUri, SourceId};
+    use symbolicator_sources::{RemoteFileUri, SentryOrganization, SourceId};
 
     #[test]
     fn test_download_url() {
@@ -282,6 +343,7 @@ mod tests {
             id: SourceId::new("test"),
             url: Url::parse("https://example.net/endpoint/").unwrap(),
             token: "token".into(),
+            organizations: Vec::new(),
         };
         let file_source =
             SentryRemoteFile::new(Arc::new(source), true, SentryFileId("abc123".into()), None);
@@ -295,6 +357,7 @@ mod tests {
             id: SourceId::new("test"),
             url: Url::parse("https://example.net/endpoint/").unwrap(),
             token: "token".into(),
+            organizations: Vec::new(),
         };
         let file_source =
             SentryRemoteFile::new(Arc::new(source), true, SentryFileId("abc123".into()), None);
@@ -304,4 +367,64 @@ mod tests {
             RemoteFileUri::new("sentry://project_debug_file/abc123")
         );
     }
+
+    #[tokio::test]
+    async fn test_list_files_organizations() {
+        crate::test::setup();
+
+        let lookup = |id: &str| {
+            let files = serde_json::json!([{ "id": id, "symbolType": "pdb" }]);
+            move |_: &str, _: &HashMap<String, String>| files.clone()
+        };
+        let (_server_a, source) = crate::test::sentry_server("", lookup("a"));
+        let (_server_b, other) = crate::test::sentry_server("", lookup("b"));
+        let failing = crate::test::Server::new();
+
+        let downloader = SentryDownloader::new(
+            reqwest::Client::new(),
+            tokio::runtime::Handle::current(),
+            Default::default(),
+            &Default::default(),
+            false,
+        );
+        let object_id = ObjectId {
+            debug_id: Some(Default::default()),
+            ..Default::default()
+        };
+
+        // Candidates of all organizations are merged, failing organizations are skipped.
+        let source = Arc::new(SentrySourceConfig {
+            organizations: vec![
+                SentryOrganization {
+                    url: other.url.clone(),
+                    token: "token".into(),
+                },
+                SentryOrganization {
+                    url: failing.url("/respond_statuscode/500/dsyms/"),
+                    token: "token".into(),
+                },
+            ],
+            ..source
+        });
+        let files = downloader
+            .list_files(source.clone(), &object_id, &[FileType::Pdb])
+            .await
+            .unwrap();
+        let uris: Vec<_> = files.iter().map(|file| file.uri()).collect();
+        assert_eq!(
+            uris,
+            [
+                RemoteFileUri::new("sentry://project_debug_file/a"),
+                RemoteFileUri::new("sentry://project_debug_file/b"),
+            ]
+        );
+
+        // Each file is downloaded from its own organization.
+        let RemoteFile::Sentry(file) = &files[1] else {
+            panic!("unexpected file");
+        };
+        assert_eq!(file.url().port(), other.url.port());
+        assert_eq!(file.source.token, "token");
+        assert_eq!(file.source.id, source.id);
+    }
 }
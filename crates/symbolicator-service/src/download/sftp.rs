@@ -0,0 +1,179 @@
+//! Support to download from SFTP servers.
+//!
+//! Every download opens a new SSH connection, which is authenticated using the private key of
+//! the source.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use russh::client;
+use russh_keys::key::PublicKey;
+use russh_sftp::client::error::Error as SftpError;
+use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::StatusCode;
+use tokio_util::io::ReaderStream;
+
+use symbolicator_sources::{SftpRemoteFile, SftpSourceConfig};
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::utils::http::{is_external_ip, DownloadTimeouts};
+
+use super::{content_length_timeout, Destination};
+
+/// Verifies the host key of the server against the configured one.
+struct HostKeyCheck {
+    expected: Option<PublicKey>,
+}
+
+#[async_trait::async_trait]
+impl client::Handler for HostKeyCheck {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        Ok(self
+            .expected
+            .as_ref()
+            .map_or(true, |expected| expected == server_public_key))
+    }
+}
+
+/// Downloader implementation that supports the SFTP source.
+#[derive(Debug)]
+pub struct SftpDownloader {
+    timeouts: DownloadTimeouts,
+    connect_to_reserved_ips: bool,
+}
+
+impl SftpDownloader {
+    pub fn new(timeouts: DownloadTimeouts, connect_to_reserved_ips: bool) -> Self {
+        Self {
+            timeouts,
+            connect_to_reserved_ips,
+        }
+    }
+
+    /// Resolves the address of the server, honoring `connect_to_reserved_ips`.
+    async fn resolve(&self, source: &SftpSourceConfig) -> CacheEntry<Vec<SocketAddr>> {
+        let addrs: Vec<_> = tokio::net::lookup_host((source.host.as_str(), source.port))
+            .await
+            .map_err(|err| CacheError::DownloadError(format!("failed to resolve host: {err}")))?
+            .filter(|addr| self.connect_to_reserved_ips || is_external_ip(addr.ip()))
+            .collect();
+
+        if addrs.is_empty() {
+            return Err(CacheError::DownloadError(
+                "no allowed address for host".into(),
+            ));
+        }
+        Ok(addrs)
+    }
+
+    /// Connects to the server and opens an SFTP session.
+    ///
+    /// The SSH session is closed once its handle is dropped, so it has to outlive the SFTP session.
+    async fn connect(
+        &self,
+        source: &SftpSourceConfig,
+    ) -> CacheEntry<(client::Handle<HostKeyCheck>, SftpSession)> {
+        let expected = match &source.host_key {
+            Some(host_key) => {
+                // The key is given as `<type> <base64> [comment]`, only the base64 part is needed.
+                let key = host_key.split_whitespace().nth(1).unwrap_or(host_key);
+                let key = russh_keys::parse_public_key_base64(key)
+                    .map_err(|err| CacheError::DownloadError(format!("invalid host key: {err}")))?;
+                Some(key)
+            }
+            None => None,
+        };
+
+        let source_key = &source.source_key;
+        let key_pair = russh_keys::decode_secret_key(
+            &source_key.private_key,
+            source_key.passphrase.as_deref(),
+        )
+        .map_err(|err| CacheError::DownloadError(format!("invalid private key: {err}")))?;
+
+        let addrs = self.resolve(source).await?;
+        let config = Arc::new(client::Config::default());
+        let connect = client::connect(config, addrs.as_slice(), HostKeyCheck { expected });
+        let mut session = tokio::time::timeout(self.timeouts.connect, connect)
+            .await
+            .map_err(|_| CacheError::Timeout(self.timeouts.connect))?
+            .map_err(|err| match err {
+                russh::Error::UnknownKey => {
+                    CacheError::PermissionDenied("host key verification failed".into())
+                }
+                err => CacheError::download_error(&err),
+            })?;
+
+        let authenticated = session
+            .authenticate_publickey(&source_key.username, Arc::new(key_pair))
+            .await
+            .map_err(|err| CacheError::download_error(&err))?;
+        if !authenticated {
+            return Err(CacheError::PermissionDenied("authentication failed".into()));
+        }
+
+        let channel = session
+            .channel_open_session()
+            .await
+            .map_err(|err| CacheError::download_error(&err))?;
+        channel
+            .request_subsystem(true, "sftp")
+            .await
+            .map_err(|err| CacheError::download_error(&err))?;
+
+        let sftp = SftpSession::new(channel.into_stream())
+            .await
+            .map_err(|err| CacheError::download_error(&err))?;
+
+        Ok((session, sftp))
+    }
+
+    /// Downloads a source hosted on an SFTP server.
+    pub async fn download_source(
+        &self,
+        source_name: &str,
+        file_source: &SftpRemoteFile,
+        destination: &mut Destination,
+    ) -> CacheEntry {
+        let path = file_source.path();
+        tracing::debug!("Fetching from SFTP: {}", file_source.uri());
+
+        let timeout = self.timeouts.head;
+        let (_session, sftp) = tokio::time::timeout(timeout, self.connect(&file_source.source))
+            .await
+            .map_err(|_| CacheError::Timeout(timeout))??;
+
+        let file = sftp.open(&path).await.map_err(|err| match err {
+            SftpError::Status(status) if status.status_code == StatusCode::NoSuchFile => {
+                CacheError::NotFound
+            }
+            SftpError::Status(status) if status.status_code == StatusCode::PermissionDenied => {
+                CacheError::PermissionDenied(status.error_message)
+            }
+            err => CacheError::download_error(&err),
+        })?;
+
+        let timeout = file
+            .metadata()
+            .await
+            .ok()
+            .and_then(|metadata| metadata.size)
+            .map(|size| content_length_timeout(size as i64, self.timeouts.streaming));
+
+        let stream = ReaderStream::new(file).map_err(CacheError::from);
+        let future = super::download_stream(source_name, stream, destination);
+
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, future)
+                .await
+                .map_err(|_| CacheError::Timeout(timeout))?,
+            None => future.await,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Detection of the two-tier layout of Microsoft symbol stores.
+//!
+//! `symstore.exe` can lay out a symbol store in two tiers, where files are put into an additional
+//! directory named after the first two characters of the file name. Such stores contain an
+//! `index2.txt` file at their root. Sources configured with the plain
+//! [`Symstore`](DirectoryLayoutType::Symstore) layout are checked for that file, and use the
+//! [`SymstoreIndex2`](DirectoryLayoutType::SymstoreIndex2) layout if it exists.
+
+use std::time::Duration;
+
+use futures::FutureExt;
+use symbolicator_sources::{
+    AzureRemoteFile, DirectoryLayout, DirectoryLayoutType, FilesystemRemoteFile, GcsRemoteFile,
+    HttpRemoteFile, RemoteFile, RemoteFileUri, S3RemoteFile, SftpRemoteFile, SourceConfig,
+    SourceLocation,
+};
+
+use crate::caching::CacheError;
+
+use super::DownloadService;
+
+/// The file marking two-tier symbol stores.
+const INDEX2_FILE: &str = "index2.txt";
+
+/// How long the detected layout of a symbol store is remembered.
+const LAYOUT_TIME_TO_LIVE: Duration = Duration::from_secs(3600);
+
+/// The maximum number of symbol stores whose layout is remembered.
+const LAYOUT_CAPACITY: u64 = 10_000;
+
+/// The detected layouts of symbol stores, keyed by the URI of their `index2.txt`.
+#[derive(Debug)]
+pub struct SymstoreLayouts {
+    layouts: moka::future::Cache<RemoteFileUri, bool>,
+}
+
+impl SymstoreLayouts {
+    pub fn new() -> Self {
+        Self {
+            layouts: moka::future::Cache::builder()
+                .max_capacity(LAYOUT_CAPACITY)
+                .time_to_live(LAYOUT_TIME_TO_LIVE)
+                .build(),
+        }
+    }
+
+    /// Returns the layout to use for the source, if it differs from the configured one.
+    ///
+    /// Only sources with the [`Symstore`](DirectoryLayoutType::Symstore) layout are checked.
+    /// If the check fails, the configured layout is used, and the source is checked again on
+    /// the next lookup.
+    pub async fn detect(
+        &self,
+        service: &DownloadService,
+        source: &SourceConfig,
+    ) -> Option<DirectoryLayout> {
+        let (layout, index2) = index2_file(source)?;
+        if layout.ty != DirectoryLayoutType::Symstore {
+            return None;
+        }
+
+        let is_two_tier = self
+            .layouts
+            .optionally_get_with(index2.uri(), async {
+                metric!(counter("service.download.symstore.index2") += 1);
+                let temp_file = tempfile::NamedTempFile::new().ok()?;
+                // Boxed, as the download would otherwise be inlined into every lookup of objects.
+                let download = service.dispatch_download(&index2, temp_file.path()).boxed();
+                match download.await {
+                    Ok(()) => Some(true),
+                    Err(CacheError::NotFound) => Some(false),
+                    Err(error) => {
+                        tracing::debug!(%error, "Failed to check for a two-tier symbol store");
+                        None
+                    }
+                }
+            })
+            .await
+            .unwrap_or(false);
+
+        is_two_tier.then_some(DirectoryLayout {
+            ty: DirectoryLayoutType::SymstoreIndex2,
+            ..layout
+        })
+    }
+}
+
+/// Returns the configured layout of the source and its `index2.txt` file.
+///
+/// Returns `None` for sources which do not have a configurable layout.
+fn index2_file(source: &SourceConfig) -> Option<(DirectoryLayout, RemoteFile)> {
+    let location = SourceLocation::new(INDEX2_FILE);
+    Some(match source {
+        SourceConfig::Http(cfg) => (
+            cfg.files.layout,
+            HttpRemoteFile::new(cfg.clone(), location).into(),
+        ),
+        SourceConfig::S3(cfg) => (
+            cfg.files.layout,
+            S3RemoteFile::new(cfg.clone(), location).into(),
+        ),
+        SourceConfig::Gcs(cfg) => (
+            cfg.files.layout,
+            GcsRemoteFile::new(cfg.clone(), location).into(),
+        ),
+        SourceConfig::Azure(cfg) => (
+            cfg.files.layout,
+            AzureRemoteFile::new(cfg.clone(), location).into(),
+        ),
+        SourceConfig::Sftp(cfg) => (
+            cfg.files.layout,
+            SftpRemoteFile::new(cfg.clone(), location).into(),
+        ),
+        SourceConfig::Filesystem(cfg) => (
+            cfg.files.layout,
+            FilesystemRemoteFile::new(cfg.clone(), location).into(),
+        ),
+        SourceConfig::Sentry(_) | SourceConfig::Nuget(_) | SourceConfig::Debuginfod(_) => {
+            return None
+        }
+    })
+}
@@ -0,0 +1,90 @@
+//! Support to send the requests of HTTP sources over Unix domain sockets.
+//!
+//! Sources with a [`unix_socket`](symbolicator_sources::HttpSourceConfig::unix_socket) connect
+//! to that socket instead of the host of their URL, which suits symbol proxies running as a
+//! sidecar. Every request opens a new HTTP/1 connection.
+
+use std::path::Path;
+
+use bytes::Bytes;
+use futures::{future, TryStreamExt};
+use http_body_util::{BodyStream, Empty};
+use hyper_util::rt::TokioIo;
+use tokio::net::UnixStream;
+
+use crate::caching::{CacheEntry, CacheError};
+
+/// Sends the request over the Unix domain socket at `path`.
+///
+/// Responses are returned regardless of their status. The request must not have a body.
+pub async fn send_request(path: &Path, request: reqwest::Request) -> CacheEntry<reqwest::Response> {
+    let request = to_hyper_request(&request)?;
+
+    let stream = UnixStream::connect(path).await.map_err(|err| {
+        CacheError::DownloadError(format!("failed to connect to `{}`: {err}", path.display()))
+    })?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|err| CacheError::DownloadError(err.to_string()))?;
+    // The connection needs to be driven until the body of the response has been read.
+    tokio::spawn(async move {
+        if let Err(error) = connection.await {
+            tracing::debug!(%error, "Connection to Unix socket failed");
+        }
+    });
+
+    let response = sender
+        .send_request(request)
+        .await
+        .map_err(|err| CacheError::DownloadError(err.to_string()))?;
+    let response = response.map(|body| {
+        let data =
+            BodyStream::new(body).try_filter_map(|frame| future::ready(Ok(frame.into_data().ok())));
+        reqwest::Body::wrap_stream(data)
+    });
+    Ok(response.into())
+}
+
+/// Converts the request for sending it with `hyper`.
+fn to_hyper_request(request: &reqwest::Request) -> CacheEntry<hyper::Request<Empty<Bytes>>> {
+    let url = request.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    };
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => "localhost".to_owned(),
+    };
+
+    let mut builder = hyper::Request::builder()
+        .method(request.method().clone())
+        .uri(path)
+        .header(hyper::header::HOST, host);
+    for (name, value) in request.headers() {
+        builder = builder.header(name, value);
+    }
+    builder
+        .body(Empty::new())
+        .map_err(|err| CacheError::DownloadError(format!("invalid request: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_hyper_request() {
+        let url = "http://symbols.example.com:8080/path/file.pdb?token=abc";
+        let mut request = reqwest::Request::new(reqwest::Method::GET, url.parse().unwrap());
+        request
+            .headers_mut()
+            .insert("x-api-key", "secret".parse().unwrap());
+
+        let request = to_hyper_request(&request).unwrap();
+        assert_eq!(request.uri(), "/path/file.pdb?token=abc");
+        assert_eq!(request.headers()["host"], "symbols.example.com:8080");
+        assert_eq!(request.headers()["x-api-key"], "secret");
+    }
+}
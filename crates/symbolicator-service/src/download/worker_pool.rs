@@ -0,0 +1,173 @@
+//! A dedicated pool of threads for decompressing downloaded files.
+//!
+//! Decompressing large files is CPU and disk intensive. Doing that on a bounded number of
+//! dedicated threads ensures that it does not starve other work of resources. Jobs are
+//! cancelled as soon as the request they belong to loses interest in them.
+
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Instant;
+
+use crate::caching::{CacheEntry, CacheError};
+use crate::utils::futures::CallOnDrop;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    /// The cancellation flag of the job currently running on this worker thread.
+    static CANCELLED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// A fixed number of worker threads running blocking jobs.
+#[derive(Debug)]
+pub struct WorkerPool {
+    sender: mpsc::Sender<Job>,
+}
+
+impl WorkerPool {
+    /// Spawns a new pool with the given number of worker threads.
+    ///
+    /// The threads exit once the pool is dropped and all queued jobs have finished.
+    pub fn new(workers: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..workers.max(1) {
+            let receiver = Arc::clone(&receiver);
+            std::thread::Builder::new()
+                .name(format!("sym-decompress-{index}"))
+                .spawn(move || loop {
+                    // The lock has to be released before running the job, so that other
+                    // workers can pick up jobs in the meantime.
+                    let job = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn decompression worker thread");
+        }
+
+        Self { sender }
+    }
+
+    /// Runs `f` on one of the worker threads and waits for its result.
+    ///
+    /// Dropping the returned future cancels the job. Jobs that have not started yet are
+    /// skipped, and running jobs stop at the next read through a [`Cancellable`] reader.
+    pub async fn run<F, T>(&self, f: F) -> CacheEntry<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (result_sender, result_receiver) = tokio::sync::oneshot::channel();
+
+        let job_cancelled = Arc::clone(&cancelled);
+        let queued_at = Instant::now();
+        let job = Box::new(move || {
+            metric!(timer("compression.queue_wait") = queued_at.elapsed());
+
+            // The request might have been abandoned while the job was waiting in the queue.
+            if job_cancelled.load(Ordering::Relaxed) {
+                metric!(counter("compression.cancelled") += 1, "state" => "queued");
+                return;
+            }
+
+            CANCELLED.with(|c| *c.borrow_mut() = Some(job_cancelled));
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            CANCELLED.with(|c| c.borrow_mut().take());
+
+            match result {
+                Ok(result) => {
+                    // The receiver is gone if the job has been cancelled in the meantime.
+                    result_sender.send(result).ok();
+                }
+                Err(_) => tracing::error!("Decompression job panicked"),
+            }
+        });
+
+        if self.sender.send(job).is_err() {
+            tracing::error!("Decompression worker pool has shut down");
+            return Err(CacheError::InternalError);
+        }
+
+        let _cancel_on_drop = CallOnDrop::new(move || cancelled.store(true, Ordering::Relaxed));
+
+        // An error means the sender was dropped without sending, because the job panicked.
+        result_receiver.await.map_err(|_| CacheError::InternalError)
+    }
+}
+
+/// Whether the job running on the current thread has been cancelled.
+///
+/// This is always `false` outside of a [`WorkerPool`].
+pub(super) fn is_cancelled() -> bool {
+    CANCELLED.with(|c| {
+        c.borrow()
+            .as_ref()
+            .is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+    })
+}
+
+/// A reader which fails once the [`WorkerPool`] job it is being read from is cancelled.
+pub struct Cancellable<R>(pub R);
+
+impl<R: Read> Read for Cancellable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if is_cancelled() {
+            metric!(counter("compression.cancelled") += 1, "state" => "running");
+            return Err(io::Error::other("decompression has been cancelled"));
+        }
+        self.0.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run() {
+        let pool = WorkerPool::new(2);
+
+        let results = futures::future::join_all((0..8).map(|i| pool.run(move || i * 2))).await;
+        let results: Vec<_> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, [0, 2, 4, 6, 8, 10, 12, 14]);
+    }
+
+    #[tokio::test]
+    async fn test_panic() {
+        let pool = WorkerPool::new(1);
+
+        let result = pool.run(|| panic!("oh no")).await;
+        assert_eq!(result, Err::<(), _>(CacheError::InternalError));
+
+        // the worker thread survives the panic
+        assert_eq!(pool.run(|| 42).await, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation() {
+        let pool = WorkerPool::new(1);
+
+        let (sender, receiver) = mpsc::channel();
+        let job = pool.run(move || {
+            // This would never finish without being cancelled.
+            let result = io::copy(&mut Cancellable(io::repeat(0)), &mut io::sink());
+            sender.send(result.is_err()).unwrap();
+        });
+        let timeout = tokio::time::timeout(Duration::from_millis(50), job).await;
+        assert!(timeout.is_err());
+
+        let was_cancelled = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(was_cancelled);
+
+        assert_eq!(pool.run(|| 42).await, Ok(42));
+    }
+}
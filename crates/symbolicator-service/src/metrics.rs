@@ -235,6 +235,12 @@ impl IntoDistributionValue for u64 {
     }
 }
 
+impl IntoDistributionValue for f64 {
+    fn into_value(self) -> f64 {
+        self
+    }
+}
+
 impl IntoDistributionValue for i32 {
     fn into_value(self) -> f64 {
         self as f64
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 
 use symbolicator_sources::{RemoteFileUri, SourceId};
 
+use crate::caching::CacheError;
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ObjectFeatures {
     /// The object file contains full debug info.
@@ -57,6 +59,12 @@ pub struct ObjectCandidate {
     ///
     /// This section is always present and will at least have a `status` field.
     pub download: ObjectDownloadInfo,
+    /// Why this DIF object was discarded, if it was.
+    ///
+    /// This is only present if the DIF object could not be used for a reason other than not
+    /// being found on the object source.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rejection: Option<CandidateRejection>,
     /// Information about any unwind info in this DIF object.
     ///
     /// This section is only present if this DIF object was used for unwinding by the
@@ -93,6 +101,11 @@ pub enum ObjectDownloadInfo {
     /// More details should be available in the `details` field, which is not meant to be
     /// machine parsable.
     NoPerm { details: String },
+    /// Symbolicator did not download the DIF object, as its source is a blocked destination.
+    ///
+    /// More details should be available in the `details` field, which is not meant to be
+    /// machine parsable.
+    Blocked { details: String },
     /// The DIF object was not found.
     ///
     /// This is considered a *regular notfound* where the object was simply not available at
@@ -108,6 +121,41 @@ pub enum ObjectDownloadInfo {
     Error { details: String },
 }
 
+/// The reason a DIF object was discarded.
+///
+/// This is part of the larger [`ObjectCandidate`] struct.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateRejection {
+    /// The DIF object is a multi-architecture file without an object for the requested
+    /// architecture.
+    WrongArch,
+    /// The DIF object is an archive which does not contain a file with the requested debug ID.
+    DebugIdMismatch,
+    /// The DIF object could not be parsed.
+    Malformed,
+    /// The DIF object was needed for unwinding, but does not contain unwind info.
+    NoUnwindInfo,
+    /// Symbolicator had insufficient permissions to download the DIF object.
+    PermissionDenied,
+}
+
+impl CandidateRejection {
+    /// Returns the reason for discarding a DIF object which failed to download with `error`.
+    ///
+    /// Returns `None` for errors which do not tell anything about the DIF object, like a
+    /// regular not found or a transient download error.
+    pub fn from_error(error: &CacheError) -> Option<Self> {
+        match error {
+            CacheError::NoMatchingArch(_) => Some(Self::WrongArch),
+            CacheError::NoMatchingArchiveMember(_) => Some(Self::DebugIdMismatch),
+            CacheError::Malformed(_) => Some(Self::Malformed),
+            CacheError::PermissionDenied(_) => Some(Self::PermissionDenied),
+            _ => None,
+        }
+    }
+}
+
 /// Information about the use of a DIF object.
 ///
 /// This information is applicable to both "unwind" and "debug" use cases, in each case the
@@ -232,7 +280,8 @@ impl AllObjectCandidates {
     /// will be overwritten by the data in `candidate`.  Practically that means
     /// [`ObjectCandidate::download`] will be overwritten by `candidate` and for
     /// [`ObjectCandidate::unwind`] and [`ObjectCandidate::debug`] it will be overwritten by
-    /// `candidate` if they are not [`ObjectUseInfo::None`].
+    /// `candidate` if they are not [`ObjectUseInfo::None`]. The same goes for
+    /// [`ObjectCandidate::rejection`] if it is set.
     pub fn merge_one(&mut self, candidate: &ObjectCandidate) {
         let key = (&candidate.source, &candidate.location);
         let found_pos = self
@@ -242,6 +291,9 @@ impl AllObjectCandidates {
             Ok(index) => {
                 if let Some(info) = self.0.get_mut(index) {
                     info.download = candidate.download.clone();
+                    if candidate.rejection.is_some() {
+                        info.rejection = candidate.rejection;
+                    }
                     if candidate.unwind != ObjectUseInfo::None {
                         info.unwind = candidate.unwind.clone();
                     }
@@ -284,6 +336,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::Ok,
             debug: ObjectUseInfo::Ok,
         };
@@ -293,6 +346,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::Ok,
             debug: ObjectUseInfo::Ok,
         };
@@ -302,6 +356,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::Ok,
             debug: ObjectUseInfo::Ok,
         };
@@ -322,6 +377,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::Ok,
             debug: ObjectUseInfo::None,
         };
@@ -331,6 +387,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::Malformed,
             debug: ObjectUseInfo::Ok,
         };
@@ -353,6 +410,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::Ok,
             debug: ObjectUseInfo::Ok,
         };
@@ -362,6 +420,7 @@ mod tests {
             download: ObjectDownloadInfo::Ok {
                 features: Default::default(),
             },
+            rejection: None,
             unwind: ObjectUseInfo::None,
             debug: ObjectUseInfo::None,
         };
@@ -375,4 +434,36 @@ mod tests {
         assert_eq!(all.0[0].unwind, ObjectUseInfo::Ok);
         assert_eq!(all.0[0].debug, ObjectUseInfo::Ok);
     }
+
+    #[test]
+    fn test_all_object_info_merge_rejection() {
+        let rejected = ObjectCandidate {
+            source: SourceId::new("A"),
+            location: RemoteFileUri::new("a"),
+            download: ObjectDownloadInfo::Ok {
+                features: Default::default(),
+            },
+            rejection: Some(CandidateRejection::NoUnwindInfo),
+            unwind: ObjectUseInfo::None,
+            debug: ObjectUseInfo::None,
+        };
+        let used = ObjectCandidate {
+            rejection: None,
+            debug: ObjectUseInfo::Ok,
+            ..rejected.clone()
+        };
+
+        // The rejection of one lookup is kept when another lookup uses the candidate.
+        let mut all: AllObjectCandidates = vec![rejected].into();
+        let other: AllObjectCandidates = vec![used].into();
+        all.merge(&other);
+        assert_eq!(all.0[0].rejection, Some(CandidateRejection::NoUnwindInfo));
+        assert_eq!(all.0[0].debug, ObjectUseInfo::Ok);
+
+        assert_eq!(
+            CandidateRejection::from_error(&CacheError::NoMatchingArch("arm64".into())),
+            Some(CandidateRejection::WrongArch)
+        );
+        assert_eq!(CandidateRejection::from_error(&CacheError::NotFound), None);
+    }
 }
@@ -22,7 +22,9 @@ use symbolicator_sources::{ObjectId, RemoteFile};
 use crate::caches::versions::OBJECTS_CACHE_VERSIONS;
 use crate::caching::CacheVersions;
 use crate::caching::{CacheEntry, CacheError, CacheItemRequest, CacheKey};
-use crate::download::{fetch_file, tempfile_in_parent, DownloadService};
+use crate::download::{
+    fetch_file_for_object, object_matches_id, tempfile_in_parent, DownloadService,
+};
 use crate::types::Scope;
 use crate::utils::sentry::ConfigureScope;
 
@@ -126,7 +128,7 @@ async fn fetch_object_file(
         object_id.to_scope(scope);
     });
 
-    fetch_file(downloader, file_id, temp_file).await?;
+    fetch_file_for_object(downloader, file_id, object_id, temp_file).await?;
 
     // Since objects in Sentry (and potentially also other sources) might be
     // multi-arch files (e.g. FatMach), we parse as Archive and try to
@@ -148,9 +150,14 @@ async fn fetch_object_file(
             None => {
                 if let Some(Err(err)) = archive.objects().find(|r| r.is_err()) {
                     return Err(CacheError::Malformed(err.to_string()));
-                } else {
-                    return Err(CacheError::NotFound);
                 }
+                // The file is for another build, or lacks the architecture of this one.
+                let arches: Vec<_> = archive
+                    .objects()
+                    .filter_map(Result::ok)
+                    .map(|object| object.arch().name())
+                    .collect();
+                return Err(CacheError::NoMatchingArch(arches.join(", ")));
             }
         };
 
@@ -170,32 +177,6 @@ async fn fetch_object_file(
     Ok(())
 }
 
-/// Validates that the object matches expected identifiers.
-fn object_matches_id(object: &Object<'_>, id: &ObjectId) -> bool {
-    if let Some(ref debug_id) = id.debug_id {
-        let parsed_id = object.debug_id();
-
-        // Microsoft symbol server sometimes stores updated files with a more recent
-        // (=higher) age, but resolves it for requests with lower ages as well. Thus, we
-        // need to check whether the parsed debug file fullfills the *miniumum* age bound.
-        // For example:
-        // `4A236F6A0B3941D1966B41A4FC77738C2` is reported as
-        // `4A236F6A0B3941D1966B41A4FC77738C4` from the server.
-        //                                  ^
-        return parsed_id.uuid() == debug_id.uuid() && parsed_id.appendix() >= debug_id.appendix();
-    }
-
-    if let Some(ref code_id) = id.code_id {
-        if let Some(ref object_code_id) = object.code_id() {
-            if object_code_id != code_id {
-                return false;
-            }
-        }
-    }
-
-    true
-}
-
 impl CacheItemRequest for FetchFileDataRequest {
     type Item = Arc<ObjectHandle>;
 
@@ -242,17 +223,26 @@ mod tests {
     use std::time::Duration;
 
     use symbolic::common::DebugId;
-    use symbolicator_sources::FileType;
+    use symbolicator_sources::{
+        CommonSourceConfig, FileType, ObjectId, ObjectType, RemoteFileUri, SourceFilters, SourceId,
+    };
     use tempfile::TempDir;
 
     use crate::caching::{Cache, CacheName};
-    use crate::config::{CacheConfig, CacheConfigs, Config};
-    use crate::objects::{FindObject, ObjectPurpose, ObjectsActor};
+    use crate::config::{CacheConfig, CacheConfigs, Config, SourceLookupPolicy};
+    use crate::objects::{FindObject, ObjectDownloadInfo, ObjectPurpose, ObjectsActor};
     use crate::test::{self, tempdir};
 
     use super::*;
 
     async fn make_objects_actor(tempdir: &TempDir) -> ObjectsActor {
+        make_objects_actor_with_policy(tempdir, Default::default()).await
+    }
+
+    async fn make_objects_actor_with_policy(
+        tempdir: &TempDir,
+        lookup_policy: SourceLookupPolicy,
+    ) -> ObjectsActor {
         let config = Config {
             connect_to_reserved_ips: true,
             max_download_timeout: Duration::from_millis(100),
@@ -279,7 +269,13 @@ mod tests {
         .unwrap();
 
         let download_svc = DownloadService::new(&config, tokio::runtime::Handle::current());
-        ObjectsActor::new(meta_cache, data_cache, Default::default(), download_svc)
+        ObjectsActor::new(
+            meta_cache,
+            data_cache,
+            Default::default(),
+            download_svc,
+            lookup_policy,
+        )
     }
 
     #[tokio::test]
@@ -486,4 +482,66 @@ mod tests {
         assert_eq!(result, err);
         assert_eq!(hitcounter.accesses(), 0);
     }
+
+    #[tokio::test]
+    async fn test_source_priority() {
+        test::setup();
+
+        let server = test::Server::new();
+        let source = |id: &str, priority| {
+            let files = CommonSourceConfig {
+                filters: SourceFilters {
+                    filetypes: vec![FileType::Pdb],
+                    ..Default::default()
+                },
+                priority,
+                ..Default::default()
+            };
+            server.source_with_config(id, "/symbols/", files)
+        };
+
+        let find_object = FindObject {
+            filetypes: &[FileType::Pdb],
+            purpose: ObjectPurpose::Debug,
+            scope: Scope::Global,
+            identifier: ObjectId {
+                debug_id: Some("3249d99d-0c40-4931-8610-f4e4fb0b6936-1".parse().unwrap()),
+                debug_file: Some("crash.pdb".into()),
+                object_type: ObjectType::Pe,
+                ..Default::default()
+            },
+            sources: Arc::new([source("low", 0), source("high", 1)]),
+        };
+
+        // Sources with a higher priority are preferred.
+        let cachedir = tempdir();
+        let objects_actor = make_objects_actor(&cachedir).await;
+        let result = objects_actor.find(find_object.clone()).await;
+        let meta = result.meta.unwrap();
+        assert!(meta.handle.is_ok());
+        assert_eq!(meta.file_source.source_id(), &SourceId::new("high"));
+        // Every source is asked for the compressed `crash.pd_` as well.
+        assert_eq!(server.accesses(), 4);
+
+        // Sources with a lower priority are not queried once an object has been found.
+        let cachedir = tempdir();
+        let objects_actor =
+            make_objects_actor_with_policy(&cachedir, SourceLookupPolicy::FirstMatch).await;
+        let result = objects_actor.find(find_object).await;
+        let meta = result.meta.unwrap();
+        assert_eq!(meta.file_source.source_id(), &SourceId::new("high"));
+        assert_eq!(server.accesses(), 2);
+
+        let skipped = result
+            .candidates
+            .into_inner()
+            .into_iter()
+            .find(|candidate| candidate.source == SourceId::new("low"))
+            .unwrap();
+        assert_eq!(
+            skipped.location,
+            RemoteFileUri::new("Not queried, an object was found on a source with higher priority")
+        );
+        assert_eq!(skipped.download, ObjectDownloadInfo::NotFound);
+    }
 }
@@ -0,0 +1,70 @@
+//! Support for GNU debug links.
+//!
+//! Stripped ELF objects often refer to their separate debug file with a `.gnu_debuglink`
+//! section, which holds the file name of the debug file. GDB looks up that file next to the
+//! object and in a global debug directory, see
+//! <https://sourceware.org/gdb/current/onlinedocs/gdb.html/Separate-Debug-Files.html>. The same
+//! locations are looked up on the source the object was found on.
+
+use std::collections::HashSet;
+
+use symbolic::debuginfo::Object;
+use symbolicator_sources::{ObjectId, RemoteFile};
+
+use crate::caching::CacheError;
+
+use super::FoundMeta;
+
+/// Returns the file name of the debug file an object refers to with a debug link.
+///
+/// Objects with debug info do not need their debug file and return `None`.
+pub(super) fn debug_link(object: &Object<'_>) -> Option<String> {
+    let Object::Elf(elf) = object else {
+        return None;
+    };
+    if object.has_debug_info() {
+        return None;
+    }
+
+    match elf.debug_link() {
+        Ok(link) => link.map(|link| link.filename().to_string_lossy().into_owned()),
+        Err(err) => {
+            tracing::debug!(error = %err, "Invalid debug link");
+            None
+        }
+    }
+}
+
+/// Returns the files that may hold the debug files which the found objects refer to with debug
+/// links.
+///
+/// No files are returned if one of the objects has debug info already.
+pub(super) fn debug_link_files(lookups: &[FoundMeta], identifier: &ObjectId) -> Vec<RemoteFile> {
+    let handles = lookups
+        .iter()
+        .filter_map(|lookup| lookup.handle.as_ref().ok());
+    if handles.clone().any(|handle| handle.features.has_debug_info) {
+        return Vec::new();
+    }
+
+    let mut seen: HashSet<_> = lookups
+        .iter()
+        .map(|lookup| lookup.file_source.uri())
+        .collect();
+    handles
+        .filter_map(|handle| {
+            let debug_link = handle.debug_link.as_deref()?;
+            Some(handle.file_source.debug_link_files(debug_link, identifier))
+        })
+        .flatten()
+        .filter(|file_source| seen.insert(file_source.uri()))
+        .collect()
+}
+
+/// Whether a debug file was found at the looked up location.
+///
+/// Most of the locations derived from debug links do not exist, and are not reported as
+/// candidates.
+pub(super) fn is_found(lookup: &FoundMeta) -> bool {
+    !matches!(lookup.handle, Err(CacheError::NotFound))
+}
@@ -0,0 +1,422 @@
+//! Support for the line tables of the Go runtime.
+//!
+//! Go binaries embed a table of all their functions in the `.gopclntab` section, which the
+//! runtime uses for its own stack traces. The table maps every instruction address to its
+//! function, file and line, and remains in binaries which are stripped of their DWARF. See
+//! <https://go.dev/src/debug/gosym/pclntab.go> for the supported formats.
+
+use std::collections::HashMap;
+use std::io;
+
+use symbolic::common::{Language, Name, NameMangling};
+use symbolic::debuginfo::dwarf::{Dwarf, DwarfSection, Endian};
+use symbolic::debuginfo::{FileInfo, Function, LineInfo, Object};
+
+/// The name of the section holding the line table.
+const SECTION_NAME: &str = "gopclntab";
+
+/// The format of the line table, by the Go version which introduced it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Version {
+    Go12,
+    Go116,
+    Go118,
+    Go120,
+}
+
+impl Version {
+    fn from_magic(magic: u32) -> Option<Self> {
+        match magic {
+            0xffff_fffb => Some(Self::Go12),
+            0xffff_fffa => Some(Self::Go116),
+            0xffff_fff0 => Some(Self::Go118),
+            0xffff_fff1 => Some(Self::Go120),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the object has the line table of the Go runtime.
+pub(super) fn has_go_pclntab(object: &Object<'_>) -> bool {
+    section(object, SECTION_NAME)
+        .is_some_and(|section| Pclntab::parse(&section.data, is_big_endian(object), 0).is_some())
+}
+
+/// Calls `f` with the functions of the Go line table of `object`.
+///
+/// The functions have line records, but no inlinees. Their addresses are relative to the load
+/// address of `object`. Returns the number of functions, which is zero for objects without a Go
+/// line table.
+pub fn merge_go_pclntab<F>(object: &Object<'_>, mut f: F) -> io::Result<usize>
+where
+    F: FnMut(&Function<'_>),
+{
+    let Some(table) = section(object, SECTION_NAME) else {
+        return Ok(0);
+    };
+    // Since Go 1.18, addresses are relative to the start of the text section.
+    let text_address = section(object, "text").map_or(0, |text| text.address);
+    let pclntab = Pclntab::parse(&table.data, is_big_endian(object), text_address)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid Go line table"))?;
+
+    let load_address = object.load_address();
+    let mut file_names = HashMap::new();
+    let mut merged = 0;
+    for index in 0..pclntab.nfunc {
+        let Some(func) = pclntab.func(index) else {
+            continue;
+        };
+        let Some(name) = pclntab.func_name(&func) else {
+            continue;
+        };
+        if func.entry >= func.end {
+            continue;
+        }
+
+        // The file and line tables change independently, so every line record covers a range
+        // in which both are constant.
+        let files: Vec<_> = pclntab.pcvalues(func.pcfile, &func).collect();
+        let mut file_index = 0;
+        let mut lines = Vec::new();
+        for (start, end, line) in pclntab.pcvalues(func.pcln, &func) {
+            let mut pc = start;
+            while pc < end {
+                while files
+                    .get(file_index)
+                    .is_some_and(|&(_, file_end, _)| file_end <= pc)
+                {
+                    file_index += 1;
+                }
+                let Some(&(file_start, file_end, file)) = files.get(file_index) else {
+                    break;
+                };
+                if file_start > pc {
+                    pc = file_start;
+                    continue;
+                }
+
+                let range_end = end.min(file_end);
+                let path = *file_names
+                    .entry((func.cu_offset, file))
+                    .or_insert_with(|| pclntab.file_name(func.cu_offset, file));
+                if let Some(path) = path {
+                    lines.push(LineInfo {
+                        address: pc.saturating_sub(load_address),
+                        size: Some(range_end - pc),
+                        file: FileInfo::from_path(path),
+                        line: u64::try_from(line).unwrap_or(0),
+                    });
+                }
+                pc = range_end;
+            }
+        }
+
+        f(&Function {
+            address: func.entry.saturating_sub(load_address),
+            size: func.end - func.entry,
+            name: Name::new(
+                String::from_utf8_lossy(name),
+                NameMangling::Unmangled,
+                Language::Go,
+            ),
+            compilation_dir: &[],
+            lines,
+            inlinees: Vec::new(),
+            inline: false,
+        });
+        merged += 1;
+    }
+
+    Ok(merged)
+}
+
+/// A function in the line table.
+#[derive(Debug)]
+struct Func {
+    entry: u64,
+    end: u64,
+    name_offset: u32,
+    pcfile: u32,
+    pcln: u32,
+    cu_offset: u32,
+}
+
+/// The header of a line table, with the offsets of its subtables.
+#[derive(Debug)]
+struct Pclntab<'data> {
+    data: &'data [u8],
+    version: Version,
+    big_endian: bool,
+    quantum: u64,
+    ptr_size: usize,
+    nfunc: usize,
+    text_start: u64,
+    funcnametab: usize,
+    cutab: usize,
+    filetab: usize,
+    pctab: usize,
+    functab: usize,
+}
+
+impl<'data> Pclntab<'data> {
+    /// Parses the header of the line table, using the given address of the text section if the
+    /// table does not specify it.
+    fn parse(data: &'data [u8], big_endian: bool, text_address: u64) -> Option<Self> {
+        let magic = read_uint(data, 0, 4, big_endian)?;
+        let version = Version::from_magic(magic as u32)?;
+        let (quantum, ptr_size) = (*data.get(6)?, usize::from(*data.get(7)?));
+        if data[4] != 0
+            || data[5] != 0
+            || !matches!(quantum, 1 | 2 | 4)
+            || !matches!(ptr_size, 4 | 8)
+        {
+            return None;
+        }
+
+        let word = |index: usize| {
+            let value = read_uint(data, 8 + index * ptr_size, ptr_size, big_endian)?;
+            usize::try_from(value).ok()
+        };
+        let nfunc = word(0)?;
+
+        let mut table = Self {
+            data,
+            version,
+            big_endian,
+            quantum: quantum.into(),
+            ptr_size,
+            nfunc,
+            text_start: 0,
+            funcnametab: 0,
+            cutab: 0,
+            filetab: 0,
+            pctab: 0,
+            functab: 0,
+        };
+        let entry_size = match version {
+            Version::Go12 => {
+                table.functab = 8 + ptr_size;
+                let filetab = nfunc
+                    .checked_mul(2)?
+                    .checked_add(1)?
+                    .checked_mul(ptr_size)?;
+                table.filetab = table.u32(table.functab.checked_add(filetab)?)? as usize;
+                2 * ptr_size
+            }
+            Version::Go116 => {
+                (table.funcnametab, table.cutab) = (word(2)?, word(3)?);
+                (table.filetab, table.pctab, table.functab) = (word(4)?, word(5)?, word(6)?);
+                2 * ptr_size
+            }
+            Version::Go118 | Version::Go120 => {
+                table.text_start = match word(2)? {
+                    0 => text_address,
+                    text_start => text_start as u64,
+                };
+                (table.funcnametab, table.cutab) = (word(3)?, word(4)?);
+                (table.filetab, table.pctab, table.functab) = (word(5)?, word(6)?, word(7)?);
+                8
+            }
+        };
+
+        // The function table ends with the end address of the last function.
+        let functab_size = nfunc.checked_mul(entry_size)?.checked_add(entry_size)?;
+        if table.functab.checked_add(functab_size)? > data.len() {
+            return None;
+        }
+        Some(table)
+    }
+
+    /// Returns the function with the given index.
+    fn func(&self, index: usize) -> Option<Func> {
+        let (entry, end, offset) = if self.version >= Version::Go118 {
+            let field = self.functab + index * 8;
+            let entry = self.text_start.saturating_add(self.u32(field)?.into());
+            let end = self.text_start.saturating_add(self.u32(field + 8)?.into());
+            (entry, end, self.u32(field + 4)? as usize)
+        } else {
+            let field = self.functab + index * 2 * self.ptr_size;
+            let offset = self.uintptr(field + self.ptr_size)?;
+            let end = self.uintptr(field + 2 * self.ptr_size)?;
+            (self.uintptr(field)?, end, usize::try_from(offset).ok()?)
+        };
+
+        // Since Go 1.16, functions are relative to the function table.
+        let base = match self.version {
+            Version::Go12 => offset,
+            _ => self.functab.checked_add(offset)?,
+        };
+        let fields = match self.version {
+            Version::Go118 | Version::Go120 => base.checked_add(4)?,
+            _ => base.checked_add(self.ptr_size)?,
+        };
+        Some(Func {
+            entry,
+            end,
+            name_offset: self.u32(fields)?,
+            pcfile: self.u32(fields + 16)?,
+            pcln: self.u32(fields + 20)?,
+            cu_offset: match self.version {
+                Version::Go12 => 0,
+                _ => self.u32(fields + 28)?,
+            },
+        })
+    }
+
+    fn func_name(&self, func: &Func) -> Option<&'data [u8]> {
+        let offset = func.name_offset as usize;
+        match self.version {
+            Version::Go12 => self.cstr(offset),
+            _ => self.cstr(self.funcnametab.checked_add(offset)?),
+        }
+    }
+
+    /// Returns the path of a file, given by its index in the compilation unit of a function.
+    fn file_name(&self, cu_offset: u32, file: i32) -> Option<&'data [u8]> {
+        let file = u32::try_from(file).ok()?;
+        match self.version {
+            Version::Go12 => {
+                let index = file as usize * 4;
+                self.cstr(self.u32(self.filetab.checked_add(index)?)? as usize)
+            }
+            _ => {
+                let index = cu_offset.checked_add(file)? as usize * 4;
+                match self.u32(self.cutab.checked_add(index)?)? {
+                    u32::MAX => None,
+                    offset => self.cstr(self.filetab.checked_add(offset as usize)?),
+                }
+            }
+        }
+    }
+
+    /// Iterates the ranges of a table of values, like lines, of a function.
+    fn pcvalues(&self, offset: u32, func: &Func) -> PcValues<'data> {
+        // Since Go 1.16, tables are relative to their own section of the line table.
+        let start = match (offset, self.version) {
+            (0, _) => None,
+            (offset, Version::Go12) => Some(offset as usize),
+            (offset, _) => self.pctab.checked_add(offset as usize),
+        };
+        PcValues {
+            data: start
+                .and_then(|start| self.data.get(start..))
+                .unwrap_or_default(),
+            pc: func.entry,
+            end: func.end,
+            value: -1,
+            quantum: self.quantum,
+            first: true,
+        }
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        read_uint(self.data, offset, 4, self.big_endian).map(|value| value as u32)
+    }
+
+    fn uintptr(&self, offset: usize) -> Option<u64> {
+        read_uint(self.data, offset, self.ptr_size, self.big_endian)
+    }
+
+    fn cstr(&self, offset: usize) -> Option<&'data [u8]> {
+        let data = self.data.get(offset..)?;
+        let end = data.iter().position(|&byte| byte == 0)?;
+        Some(&data[..end])
+    }
+}
+
+/// An iterator over the address ranges of a table of values, with their value.
+#[derive(Debug)]
+struct PcValues<'data> {
+    data: &'data [u8],
+    pc: u64,
+    end: u64,
+    value: i32,
+    quantum: u64,
+    first: bool,
+}
+
+impl Iterator for PcValues<'_> {
+    type Item = (u64, u64, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pc >= self.end {
+            return None;
+        }
+        let uvdelta = read_varint(&mut self.data)?;
+        if uvdelta == 0 && !self.first {
+            return None;
+        }
+        self.first = false;
+
+        let vdelta = match uvdelta & 1 {
+            0 => uvdelta >> 1,
+            _ => !(uvdelta >> 1),
+        };
+        self.value = self.value.wrapping_add(vdelta as i32);
+        let pcdelta = u64::from(read_varint(&mut self.data)?) * self.quantum;
+
+        let start = self.pc;
+        self.pc = start.saturating_add(pcdelta).min(self.end);
+        Some((start, self.pc, self.value))
+    }
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u32> {
+    let mut value = 0;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn read_uint(data: &[u8], offset: usize, size: usize, big_endian: bool) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(size)?)?;
+    let fold = |value: u64, &byte: &u8| (value << 8) | u64::from(byte);
+    Some(match big_endian {
+        true => bytes.iter().fold(0, fold),
+        false => bytes.iter().rev().fold(0, fold),
+    })
+}
+
+fn section<'data>(object: &Object<'data>, name: &str) -> Option<DwarfSection<'data>> {
+    match object {
+        Object::Elf(elf) => elf.section(name),
+        Object::MachO(macho) => macho.section(name),
+        _ => None,
+    }
+}
+
+fn is_big_endian(object: &Object<'_>) -> bool {
+    let endian = match object {
+        Object::Elf(elf) => elf.endianity(),
+        Object::MachO(macho) => macho.endianity(),
+        _ => return false,
+    };
+    matches!(endian, Endian::Big)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcvalues() {
+        // Line 3 for 4 bytes, then line 4 for 16 bytes.
+        let values = PcValues {
+            data: &[0x08, 0x04, 0x02, 0x10, 0x00],
+            pc: 0x1000,
+            end: 0x1014,
+            value: -1,
+            quantum: 1,
+            first: true,
+        };
+
+        let values: Vec<_> = values.collect();
+        assert_eq!(values, [(0x1000, 0x1004, 3), (0x1004, 0x1014, 4)]);
+    }
+}
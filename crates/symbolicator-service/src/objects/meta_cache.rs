@@ -10,8 +10,9 @@
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
 
-use symbolic::common::ByteView;
+use symbolic::common::{ByteView, DebugId};
 use symbolicator_sources::{ObjectId, RemoteFile};
 use tempfile::NamedTempFile;
 
@@ -23,6 +24,10 @@ use crate::download::DownloadService;
 use crate::types::Scope;
 
 use super::candidates::ObjectFeatures;
+use super::debug_link::debug_link;
+use super::go_pclntab::has_go_pclntab;
+use super::mini_debuginfo::has_mini_debuginfo;
+use super::split_dwarf::{has_skeleton_units, is_dwarf_package};
 use super::FetchFileDataRequest;
 
 /// This requests metadata of a single file at a specific path/url.
@@ -41,6 +46,22 @@ pub(super) struct FetchFileMetaRequest {
     pub(super) download_svc: Arc<DownloadService>,
 }
 
+/// The metadata of an object, as stored in the metadata cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct ObjectMeta {
+    #[serde(flatten)]
+    features: ObjectFeatures,
+    /// The object has skeleton units, whose debug information is in a DWARF package.
+    #[serde(default)]
+    has_split_dwarf: bool,
+    /// The file name of the debug file the object refers to, if it has no debug info.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    debug_link: Option<String>,
+    /// The debug id of the object, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    debug_id: Option<DebugId>,
+}
+
 /// Handle to local metadata file of an object.
 ///
 /// Having an instance of this type does not mean there is a downloaded object file behind
@@ -52,6 +73,9 @@ pub struct ObjectMetaHandle {
     pub(super) object_id: ObjectId,
     pub(super) file_source: RemoteFile,
     pub(super) features: ObjectFeatures,
+    pub(super) has_split_dwarf: bool,
+    pub(super) debug_link: Option<String>,
+    pub(super) debug_id: Option<DebugId>,
 }
 
 impl ObjectMetaHandle {
@@ -62,6 +86,9 @@ impl ObjectMetaHandle {
             file_source,
             object_id: Default::default(),
             features: Default::default(),
+            has_split_dwarf: false,
+            debug_link: None,
+            debug_id: None,
         })
     }
 
@@ -79,6 +106,13 @@ impl ObjectMetaHandle {
         self.features
     }
 
+    /// Whether the debug information of the object is split into a DWARF package.
+    ///
+    /// The package can be found with [`dwp_object_id`](super::dwp_object_id).
+    pub fn has_split_dwarf(&self) -> bool {
+        self.has_split_dwarf
+    }
+
     pub fn scope(&self) -> &Scope {
         &self.scope
     }
@@ -86,6 +120,19 @@ impl ObjectMetaHandle {
     pub fn object_id(&self) -> &ObjectId {
         &self.object_id
     }
+
+    /// Whether the object only loosely matches the given identifier.
+    ///
+    /// This is the case for PDBs and PEs with the same GUID, but a different age, which are
+    /// looked up on sources with `loose_matching` enabled.
+    pub fn is_loose_match(&self, identifier: &ObjectId) -> bool {
+        match (self.debug_id, identifier.debug_id) {
+            (Some(found), Some(wanted)) => {
+                found.uuid() == wanted.uuid() && found.appendix() != wanted.appendix()
+            }
+            _ => false,
+        }
+    }
 }
 
 impl FetchFileMetaRequest {
@@ -111,12 +158,23 @@ impl FetchFileMetaRequest {
 
         let object = object_handle.object();
 
-        let meta = ObjectFeatures {
-            has_debug_info: object.has_debug_info(),
+        // DWARF packages only hold `.dwo` sections, which symbolic does not consider debug info.
+        // Stripped Go binaries keep the line tables of the Go runtime, and symbols of stripped
+        // objects may still be embedded as MiniDebugInfo.
+        let features = ObjectFeatures {
+            has_debug_info: object.has_debug_info()
+                || is_dwarf_package(object)
+                || has_go_pclntab(object),
             has_unwind_info: object.has_unwind_info(),
-            has_symbols: object.has_symbols(),
+            has_symbols: object.has_symbols() || has_mini_debuginfo(object),
             has_sources: object.has_sources(),
         };
+        let meta = ObjectMeta {
+            features,
+            has_split_dwarf: has_skeleton_units(object),
+            debug_link: debug_link(object),
+            debug_id: Some(object.debug_id()).filter(|id| !id.is_nil()),
+        };
 
         tracing::trace!("Persisting object meta for {}: {:?}", cache_key, meta);
         serde_json::to_writer(temp_file.as_file_mut(), &meta)?;
@@ -136,12 +194,15 @@ impl CacheItemRequest for FetchFileMetaRequest {
 
     /// Returns the [`ObjectMetaHandle`] at the given cache key.
     fn load(&self, data: ByteView<'static>) -> CacheEntry<Self::Item> {
-        let features = serde_json::from_slice(&data)?;
+        let meta: ObjectMeta = serde_json::from_slice(&data)?;
         Ok(Arc::new(ObjectMetaHandle {
             scope: self.scope.clone(),
             object_id: self.object_id.clone(),
             file_source: self.file_source.clone(),
-            features,
+            features: meta.features,
+            has_split_dwarf: meta.has_split_dwarf,
+            debug_link: meta.debug_link,
+            debug_id: meta.debug_id,
         }))
     }
 }
@@ -0,0 +1,63 @@
+//! Support for MiniDebugInfo.
+//!
+//! Android system libraries and many distribution binaries are stripped of their symbol table,
+//! but embed a reduced one in the `.gnu_debugdata` section. The section holds an xz-compressed ELF
+//! file, which only contains the symbols of functions that are not exported already. See
+//! <https://sourceware.org/gdb/current/onlinedocs/gdb.html/MiniDebugInfo.html>.
+
+use std::io::{self, Read};
+
+use symbolic::debuginfo::dwarf::Dwarf;
+use symbolic::debuginfo::elf::ElfObject;
+use symbolic::debuginfo::{Object, Symbol};
+
+/// The name of the section holding the MiniDebugInfo.
+const SECTION_NAME: &str = "gnu_debugdata";
+
+/// The maximum size of decompressed MiniDebugInfo.
+const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Whether the object embeds MiniDebugInfo.
+pub(super) fn has_mini_debuginfo(object: &Object<'_>) -> bool {
+    matches!(object, Object::Elf(elf) if elf.section(SECTION_NAME).is_some())
+}
+
+/// Calls `f` with the symbols of the MiniDebugInfo embedded in `object`.
+///
+/// The addresses of the symbols are relative to the load address of `object`. Returns the
+/// number of symbols, which is zero for objects without MiniDebugInfo.
+pub fn merge_mini_debuginfo<F>(object: &Object<'_>, mut f: F) -> io::Result<usize>
+where
+    F: FnMut(&Symbol<'_>),
+{
+    let Object::Elf(elf) = object else {
+        return Ok(0);
+    };
+    let Some(section) = elf.section(SECTION_NAME) else {
+        return Ok(0);
+    };
+
+    let mut data = Vec::new();
+    xz2::read::XzDecoder::new(&*section.data)
+        .take(MAX_SIZE + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "MiniDebugInfo exceeds the maximum size",
+        ));
+    }
+    let mini =
+        ElfObject::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut merged = 0;
+    for symbol in mini.symbols() {
+        let address = symbol
+            .address
+            .saturating_add(mini.load_address())
+            .saturating_sub(elf.load_address());
+        f(&Symbol { address, ..symbol });
+        merged += 1;
+    }
+    Ok(merged)
+}
@@ -7,19 +7,29 @@ use sentry::{Hub, SentryFutureExt};
 use symbolicator_sources::{FileType, ObjectId, RemoteFile, RemoteFileUri, SourceConfig, SourceId};
 
 use crate::caching::{Cache, CacheEntry, CacheError, CacheKey, Cacher, SharedCacheRef};
+use crate::config::SourceLookupPolicy;
 use crate::download::DownloadService;
 use crate::types::Scope;
 
 mod candidates;
 mod data_cache;
+mod debug_link;
+mod go_pclntab;
 mod meta_cache;
+mod mini_debuginfo;
+mod split_dwarf;
+mod variables;
 
 use data_cache::FetchFileDataRequest;
 use meta_cache::FetchFileMetaRequest;
 
 pub use candidates::*;
 pub use data_cache::ObjectHandle;
+pub use go_pclntab::merge_go_pclntab;
 pub use meta_cache::ObjectMetaHandle;
+pub use mini_debuginfo::merge_mini_debuginfo;
+pub use split_dwarf::{dwp_object_id, merge_split_dwarf};
+pub use variables::{recover_variables, RecoveredVariable, VariableContext};
 
 /// Wrapper around [`CacheError`] to also pass the file information along.
 ///
@@ -72,6 +82,33 @@ pub struct FindResult {
     /// This is a list of some meta information on all objects which have been considered
     /// for this object.  It could be populated even if no matching object is found.
     pub candidates: AllObjectCandidates,
+    /// Whether the found object only loosely matches the requested identifier.
+    ///
+    /// See [`ObjectMetaHandle::is_loose_match`].
+    pub loose_match: bool,
+}
+
+/// Purge the cached object files of an object.
+#[derive(Debug, Clone)]
+pub struct PurgeObject {
+    pub scope: Scope,
+    pub identifier: ObjectId,
+    pub sources: Arc<[SourceConfig]>,
+    /// Whether to purge the shared cache as well.
+    pub shared_cache: bool,
+}
+
+/// The response for [`ObjectsActor::purge`].
+#[derive(Debug, Clone, Default)]
+pub struct PurgedObject {
+    /// The cache keys of all object files which have been purged.
+    ///
+    /// Derived caches use the same keys.
+    pub cache_keys: Vec<CacheKey>,
+    /// The number of removed object meta cache entries.
+    pub object_meta: usize,
+    /// The number of removed object cache entries.
+    pub objects: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -84,6 +121,7 @@ pub struct ObjectsActor {
     meta_cache: Arc<Cacher<FetchFileMetaRequest>>,
     data_cache: Arc<Cacher<FetchFileDataRequest>>,
     download_svc: Arc<DownloadService>,
+    lookup_policy: SourceLookupPolicy,
 }
 
 impl ObjectsActor {
@@ -92,11 +130,13 @@ impl ObjectsActor {
         data_cache: Cache,
         shared_cache: SharedCacheRef,
         download_svc: Arc<DownloadService>,
+        lookup_policy: SourceLookupPolicy,
     ) -> Self {
         ObjectsActor {
             meta_cache: Arc::new(Cacher::new(meta_cache, Arc::clone(&shared_cache))),
             data_cache: Arc::new(Cacher::new(data_cache, shared_cache)),
             download_svc,
+            lookup_policy,
         }
     }
 
@@ -123,6 +163,9 @@ impl ObjectsActor {
     /// object metadata of each matching object in the metadata cache.  These are then
     /// ranked and the best matching object metadata is returned.
     ///
+    /// Sources are queried in order of their priority. Depending on the [`SourceLookupPolicy`],
+    /// sources with a lower priority are skipped once a suitable object has been found.
+    ///
     /// Asking for the objects metadata from the data cache also triggers a download of each
     /// object, which will then be cached in the data cache.  The metadata itself is cached
     /// in the metadata cache which usually lives longer.
@@ -134,17 +177,94 @@ impl ObjectsActor {
             sources,
             purpose,
         } = request;
-        let file_ids = self
-            .download_svc
-            .list_files(&sources, filetypes, &identifier)
-            .await;
 
-        let file_metas = self.fetch_file_metas(file_ids, &identifier, scope).await;
+        // The sort is stable, so sources with the same priority keep their order.
+        let mut sources = sources.to_vec();
+        sources.sort_by_key(|source| std::cmp::Reverse(source.priority()));
+
+        let groups: Vec<&[SourceConfig]> = match self.lookup_policy {
+            SourceLookupPolicy::All => vec![sources.as_slice()],
+            _ => sources
+                .chunk_by(|a, b| a.priority() == b.priority())
+                .collect(),
+        };
+
+        let mut file_metas = Vec::new();
+        let mut queried = 0;
+        for group in groups {
+            let file_ids = self
+                .download_svc
+                .list_files(group, filetypes, &identifier)
+                .await;
+            file_metas.extend(
+                self.fetch_file_metas(file_ids, &identifier, scope.clone())
+                    .await,
+            );
+            queried += group.len();
+
+            if lookup_satisfied(self.lookup_policy, &file_metas, &identifier, purpose) {
+                break;
+            }
+        }
+
+        // Objects without debug info may refer to a separate debug file with a debug link.
+        if matches!(purpose, ObjectPurpose::Debug) {
+            let file_ids = debug_link::debug_link_files(&file_metas, &identifier);
+            if !file_ids.is_empty() {
+                let mut debug_link_metas = self
+                    .fetch_file_metas(file_ids, &identifier, scope.clone())
+                    .await;
+                debug_link_metas.retain(debug_link::is_found);
+                file_metas.extend(debug_link_metas);
+            }
+        }
+
+        let (queried, skipped) = sources.split_at(queried);
+        let candidates = create_candidates(queried, skipped, &file_metas, purpose);
+        let meta = select_meta(file_metas, &identifier, purpose);
+        let loose_match = meta
+            .as_ref()
+            .and_then(|meta| meta.handle.as_ref().ok())
+            .is_some_and(|handle| handle.is_loose_match(&identifier));
+
+        FindResult {
+            meta,
+            candidates,
+            loose_match,
+        }
+    }
+
+    /// Removes all object files matching the identifier from the caches.
+    ///
+    /// The files are listed on all sources and for all file types, and then purged from both the
+    /// meta and the data cache. This covers negative and error entries, which is useful when the
+    /// files have been replaced on a source.
+    pub async fn purge(&self, request: PurgeObject) -> PurgedObject {
+        let PurgeObject {
+            scope,
+            identifier,
+            sources,
+            shared_cache,
+        } = request;
 
-        let candidates = create_candidates(&sources, &file_metas);
-        let meta = select_meta(file_metas, purpose);
+        let file_sources = self
+            .download_svc
+            .list_files(&sources, FileType::all(), &identifier)
+            .await;
 
-        FindResult { meta, candidates }
+        let mut purged = PurgedObject::default();
+        for file_source in file_sources {
+            let scope = if file_source.is_public() {
+                Scope::Global
+            } else {
+                scope.clone()
+            };
+            let cache_key = CacheKey::from_scoped_file(&scope, &file_source);
+            purged.object_meta += self.meta_cache.purge(&cache_key, shared_cache).await;
+            purged.objects += self.data_cache.purge(&cache_key, shared_cache).await;
+            purged.cache_keys.push(cache_key);
+        }
+        purged
     }
 
     /// Fetch all [`ObjectMetaHandle`]s for the files.
@@ -194,10 +314,15 @@ impl ObjectsActor {
 /// the first one will be chosen.  If the file list is emtpy, `None` is returned in the
 /// result, if there were no suitable files and only lookup errors one of the lookup errors
 /// is propagated.  If there were no suitlable files and no errors `None` is also returned
-/// in the result.
-fn select_meta(all_lookups: Vec<FoundMeta>, purpose: ObjectPurpose) -> Option<FoundMeta> {
+/// in the result.  Files which match the `identifier` exactly are chosen over equally good
+/// files which only match it loosely.
+fn select_meta(
+    all_lookups: Vec<FoundMeta>,
+    identifier: &ObjectId,
+    purpose: ObjectPurpose,
+) -> Option<FoundMeta> {
     let mut selected_meta = None;
-    let mut selected_quality = u8::MAX;
+    let mut selected_quality = (u8::MAX, true);
 
     for meta_lookup in all_lookups {
         // Skip objects which and not suitable for what we're asked to provide.  Keep errors
@@ -210,7 +335,10 @@ fn select_meta(all_lookups: Vec<FoundMeta>, purpose: ObjectPurpose) -> Option<Fo
 
         // We iterate in order of preferred sources, so only select a later object if the
         // quality is better.
-        let quality = object_quality(&meta_lookup, purpose);
+        let quality = (
+            object_quality(&meta_lookup, purpose),
+            is_loose_match(&meta_lookup, identifier),
+        );
         if quality < selected_quality {
             selected_meta = Some(meta_lookup);
             selected_quality = quality;
@@ -220,6 +348,27 @@ fn select_meta(all_lookups: Vec<FoundMeta>, purpose: ObjectPurpose) -> Option<Fo
     selected_meta
 }
 
+/// Whether the lookups found an object which makes querying further sources unnecessary.
+fn lookup_satisfied(
+    policy: SourceLookupPolicy,
+    lookups: &[FoundMeta],
+    identifier: &ObjectId,
+    purpose: ObjectPurpose,
+) -> bool {
+    lookups.iter().any(|meta_lookup| match policy {
+        SourceLookupPolicy::All => false,
+        SourceLookupPolicy::BestMatch => {
+            meta_lookup.handle.is_ok()
+                && object_quality(meta_lookup, purpose) == 0
+                && !is_loose_match(meta_lookup, identifier)
+        }
+        SourceLookupPolicy::FirstMatch => meta_lookup
+            .handle
+            .as_ref()
+            .is_ok_and(|meta_handle| object_has_features(meta_handle, purpose)),
+    })
+}
+
 /// Returns a sortable quality measure of this object for the given purpose.
 ///
 /// Lower quality number is better.
@@ -236,6 +385,14 @@ fn object_quality(meta_lookup: &FoundMeta, purpose: ObjectPurpose) -> u8 {
     }
 }
 
+/// Whether the found object only loosely matches the identifier.
+fn is_loose_match(meta_lookup: &FoundMeta, identifier: &ObjectId) -> bool {
+    meta_lookup
+        .handle
+        .as_ref()
+        .is_ok_and(|meta_handle| meta_handle.is_loose_match(identifier))
+}
+
 /// Whether the object provides the required features for the given purpose.
 fn object_has_features(meta_handle: &ObjectMetaHandle, purpose: ObjectPurpose) -> bool {
     match purpose {
@@ -253,23 +410,42 @@ fn object_has_features(meta_handle: &ObjectMetaHandle, purpose: ObjectPurpose) -
 /// results they will get a [`ObjectDownloadInfo::NotFound`] entry with a location of `*`.
 /// In practice this will only affect the `sentry` source for now as all other sources
 /// always return [`DownloadService::list_files`] results.
-fn create_candidates(sources: &[SourceConfig], lookups: &[FoundMeta]) -> AllObjectCandidates {
+///
+/// The `skipped` sources have not been queried due to the [`SourceLookupPolicy`], they also
+/// get a [`ObjectDownloadInfo::NotFound`] entry noting so.
+///
+/// Candidates which are unsuitable for the `purpose` get a [`CandidateRejection`].
+fn create_candidates(
+    sources: &[SourceConfig],
+    skipped: &[SourceConfig],
+    lookups: &[FoundMeta],
+    purpose: ObjectPurpose,
+) -> AllObjectCandidates {
     let mut source_ids: BTreeSet<SourceId> =
         sources.iter().map(|source| source.id()).cloned().collect();
-    let mut candidates: Vec<ObjectCandidate> = Vec::with_capacity(lookups.len() + source_ids.len());
+    let mut candidates: Vec<ObjectCandidate> =
+        Vec::with_capacity(lookups.len() + source_ids.len() + skipped.len());
 
     for meta_lookup in lookups.iter() {
         let source_id = meta_lookup.file_source.source_id();
         source_ids.take(source_id);
-        candidates.push(create_candidate_info(meta_lookup));
+        candidates.push(create_candidate_info(meta_lookup, purpose));
     }
 
     // Create a NotFound entry for each source from which we did not try and fetch anything.
-    for source_id in source_ids {
+    let not_listed = source_ids
+        .into_iter()
+        .map(|source_id| (source_id, "No object files listed on this source"));
+    let skipped = skipped.iter().map(|source| {
+        let reason = "Not queried, an object was found on a source with higher priority";
+        (source.id().clone(), reason)
+    });
+    for (source_id, reason) in not_listed.chain(skipped) {
         let info = ObjectCandidate {
             source: source_id,
-            location: RemoteFileUri::new("No object files listed on this source"),
+            location: RemoteFileUri::new(reason),
             download: ObjectDownloadInfo::NotFound,
+            rejection: None,
             unwind: Default::default(),
             debug: Default::default(),
         };
@@ -281,7 +457,7 @@ fn create_candidates(sources: &[SourceConfig], lookups: &[FoundMeta]) -> AllObje
 }
 
 /// Build the [`ObjectCandidate`] info for the provided meta lookup result.
-fn create_candidate_info(meta_lookup: &FoundMeta) -> ObjectCandidate {
+fn create_candidate_info(meta_lookup: &FoundMeta, purpose: ObjectPurpose) -> ObjectCandidate {
     let source = meta_lookup.file_source.source_id().clone();
     let location = meta_lookup.file_source.uri();
     let download = match &meta_lookup.handle {
@@ -293,6 +469,9 @@ fn create_candidate_info(meta_lookup: &FoundMeta) -> ObjectCandidate {
             CacheError::PermissionDenied(msg) => ObjectDownloadInfo::NoPerm {
                 details: msg.clone(),
             },
+            CacheError::Blocked(msg) => ObjectDownloadInfo::Blocked {
+                details: msg.clone(),
+            },
             CacheError::Malformed(_) => ObjectDownloadInfo::Malformed,
             err => ObjectDownloadInfo::Error {
                 details: err.to_string(),
@@ -300,10 +479,21 @@ fn create_candidate_info(meta_lookup: &FoundMeta) -> ObjectCandidate {
         },
     };
 
+    let rejection = match &meta_lookup.handle {
+        Ok(handle) => match purpose {
+            ObjectPurpose::Unwind if !handle.features().has_unwind_info => {
+                Some(CandidateRejection::NoUnwindInfo)
+            }
+            _ => None,
+        },
+        Err(error) => CandidateRejection::from_error(error),
+    };
+
     ObjectCandidate {
         source,
         location,
         download,
+        rejection,
         unwind: Default::default(),
         debug: Default::default(),
     }
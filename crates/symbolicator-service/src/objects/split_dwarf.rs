@@ -0,0 +1,319 @@
+//! Support for split DWARF.
+//!
+//! Objects built with `-gsplit-dwarf` only contain skeleton units. These hold the line tables of
+//! their compilation units, but the rest of the debug information, like functions, lives in
+//! separate `.dwo` files. The `.dwo` files of an object are usually combined into a DWARF package
+//! (`.dwp`), which is looked up with [`FileType::Dwp`](symbolicator_sources::FileType::Dwp)
+//! using [`dwp_object_id`]. The split units of the package are then merged with the skeleton
+//! units when computing derived caches.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+use gimli::{DwarfPackageSections, DwarfSections, EndianSlice, RunTimeEndian, UnitOffset};
+use symbolic::common::{Language, Name, NameMangling};
+use symbolic::debuginfo::dwarf::{Dwarf, Endian};
+use symbolic::debuginfo::elf::ElfObject;
+use symbolic::debuginfo::{FileInfo, Function, LineInfo, Object};
+use symbolicator_sources::{ObjectId, ObjectType};
+
+type Slice<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// The maximum number of `DW_AT_abstract_origin` or `DW_AT_specification` references followed
+/// to find the name of a function.
+const MAX_REFERENCE_DEPTH: usize = 16;
+
+/// Whether the object has skeleton units, whose debug information is split into `.dwo` files.
+pub(super) fn has_skeleton_units(object: &Object<'_>) -> bool {
+    let Object::Elf(elf) = object else {
+        return false;
+    };
+    let endian = endianity(elf);
+    let Ok(sections) = DwarfSections::load(|id| Ok::<_, gimli::Error>(section(elf, id.name())))
+    else {
+        return false;
+    };
+    let dwarf = sections.borrow(|section| EndianSlice::new(Cow::as_ref(section), endian));
+
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        if dwarf.unit(header).is_ok_and(|unit| unit.dwo_id.is_some()) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether the object is a DWARF package.
+pub(super) fn is_dwarf_package(object: &Object<'_>) -> bool {
+    matches!(object, Object::Elf(elf) if elf.section("debug_cu_index").is_some())
+}
+
+/// Returns the identifier of the DWARF package holding the split DWARF of an object.
+///
+/// Packages are looked up by the build id of their object, and are named after its files with
+/// a `.dwp` extension, like `dwp` does by default.
+pub fn dwp_object_id(object_id: &ObjectId) -> ObjectId {
+    let dwp_file = |file: Option<&String>| file.map(|file| format!("{file}.dwp"));
+    ObjectId {
+        code_id: object_id.code_id.clone(),
+        code_file: dwp_file(object_id.code_file.as_ref()),
+        debug_id: object_id.debug_id,
+        debug_file: dwp_file(object_id.debug_file.as_ref()),
+        debug_checksum: None,
+        object_type: ObjectType::Elf,
+    }
+}
+
+/// Calls `f` with the functions of the split units of `object`, which are read from the DWARF
+/// package `dwp`.
+///
+/// The functions get their line records from the line tables of the skeleton units in
+/// `object`. Inlined functions are not resolved. Returns the number of merged split units.
+pub fn merge_split_dwarf<F>(object: &Object<'_>, dwp: &Object<'_>, mut f: F) -> gimli::Result<usize>
+where
+    F: FnMut(&Function<'_>),
+{
+    let (Object::Elf(elf), Object::Elf(dwp_elf)) = (object, dwp) else {
+        return Ok(0);
+    };
+    let endian = endianity(elf);
+
+    let sections = DwarfSections::load(|id| Ok::<_, gimli::Error>(section(elf, id.name())))?;
+    let dwarf = sections.borrow(|section| EndianSlice::new(Cow::as_ref(section), endian));
+    let dwp_sections = DwarfPackageSections::load(|id| {
+        Ok::<_, gimli::Error>(
+            id.dwo_name()
+                .map_or_else(Cow::default, |name| section(dwp_elf, name)),
+        )
+    })?;
+    let dwp = dwp_sections.borrow(
+        |section| EndianSlice::new(Cow::as_ref(section), endian),
+        EndianSlice::new(&[], endian),
+    )?;
+
+    let mut merged = 0;
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let skeleton = dwarf.unit(header)?;
+        let Some(dwo_id) = skeleton.dwo_id else {
+            continue;
+        };
+        let Some(split_dwarf) = dwp.find_cu(dwo_id, &dwarf)? else {
+            tracing::debug!("Split unit {:#x} is missing in DWARF package", dwo_id.0);
+            continue;
+        };
+        let Some(split_header) = split_dwarf.units().next()? else {
+            continue;
+        };
+        let mut split = split_dwarf.unit(split_header)?;
+        split.copy_relocated_attributes(&skeleton);
+
+        let lines = LineTable::parse(&dwarf, &skeleton)?;
+        let compilation_dir = skeleton.comp_dir.map(|dir| dir.slice()).unwrap_or_default();
+
+        let mut entries = split.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let mut ranges = split_dwarf.die_ranges(&split, entry)?;
+            let Some(name) = function_name(&split_dwarf, &split, entry.offset())? else {
+                continue;
+            };
+
+            while let Some(range) = ranges.next()? {
+                // Functions removed by the linker end up at address zero.
+                if range.begin == 0 || range.begin >= range.end {
+                    continue;
+                }
+                f(&Function {
+                    address: range.begin,
+                    size: range.end - range.begin,
+                    name: Name::new(name.as_str(), NameMangling::Unknown, Language::Unknown),
+                    compilation_dir,
+                    lines: lines.lines(range.begin, range.end),
+                    inlinees: Vec::new(),
+                    inline: false,
+                });
+            }
+        }
+        merged += 1;
+    }
+
+    Ok(merged)
+}
+
+/// Resolves the name of the function at `offset`, following references to its declaration.
+fn function_name(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    mut offset: UnitOffset,
+) -> gimli::Result<Option<String>> {
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        let entry = unit.entry(offset)?;
+        for attr in [
+            gimli::DW_AT_linkage_name,
+            gimli::DW_AT_MIPS_linkage_name,
+            gimli::DW_AT_name,
+        ] {
+            if let Some(value) = entry.attr_value(attr)? {
+                let name = dwarf.attr_string(unit, value)?;
+                return Ok(Some(name.to_string_lossy().into_owned()));
+            }
+        }
+
+        let reference = match entry.attr_value(gimli::DW_AT_abstract_origin)? {
+            Some(reference) => Some(reference),
+            None => entry.attr_value(gimli::DW_AT_specification)?,
+        };
+        match reference {
+            Some(gimli::AttributeValue::UnitRef(reference)) => offset = reference,
+            _ => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// The rows of the line table of a skeleton unit.
+#[derive(Debug, Default)]
+struct LineTable {
+    /// The paths of the referenced files.
+    files: Vec<Vec<u8>>,
+    /// The rows, sorted by their address.
+    rows: Vec<LineRow>,
+}
+
+#[derive(Debug)]
+struct LineRow {
+    address: u64,
+    size: Option<u64>,
+    file: usize,
+    line: u64,
+}
+
+impl LineTable {
+    fn parse(
+        dwarf: &gimli::Dwarf<Slice<'_>>,
+        unit: &gimli::Unit<Slice<'_>>,
+    ) -> gimli::Result<Self> {
+        let mut table = Self::default();
+        let Some(program) = unit.line_program.clone() else {
+            return Ok(table);
+        };
+
+        let mut file_indices = HashMap::new();
+        let mut sequence: Vec<LineRow> = Vec::new();
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            let address = row.address();
+            if let Some(previous) = sequence.last_mut() {
+                previous.size = Some(address.saturating_sub(previous.address));
+                // Only the last of multiple rows for the same address is kept.
+                if previous.address == address {
+                    sequence.pop();
+                }
+            }
+            if row.end_sequence() {
+                table.rows.append(&mut sequence);
+                continue;
+            }
+
+            let file = match file_indices.get(&row.file_index()) {
+                Some(&file) => file,
+                None => {
+                    table
+                        .files
+                        .push(file_path(dwarf, unit, header, row.file_index())?);
+                    file_indices.insert(row.file_index(), table.files.len() - 1);
+                    table.files.len() - 1
+                }
+            };
+            sequence.push(LineRow {
+                address,
+                size: None,
+                file,
+                line: row.line().map_or(0, NonZeroU64::get),
+            });
+        }
+
+        table.rows.sort_by_key(|row| row.address);
+        Ok(table)
+    }
+
+    /// Returns the line records within the given address range.
+    fn lines(&self, begin: u64, end: u64) -> Vec<LineInfo<'_>> {
+        let start = self.rows.partition_point(|row| row.address < begin);
+        self.rows[start..]
+            .iter()
+            .take_while(|row| row.address < end)
+            .map(|row| LineInfo {
+                address: row.address,
+                size: row.size,
+                file: FileInfo::from_path(&self.files[row.file]),
+                line: row.line,
+            })
+            .collect()
+    }
+}
+
+/// Returns the path of the file with the given index in a line table.
+fn file_path(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    header: &gimli::LineProgramHeader<Slice<'_>>,
+    index: u64,
+) -> gimli::Result<Vec<u8>> {
+    let Some(file) = header.file(index) else {
+        return Ok(Vec::new());
+    };
+    let name = dwarf.attr_string(unit, file.path_name())?;
+    let mut path = match file.directory(header) {
+        Some(directory) => dwarf.attr_string(unit, directory)?.to_vec(),
+        None => Vec::new(),
+    };
+    if path.is_empty() || name.starts_with(b"/") {
+        return Ok(name.to_vec());
+    }
+    if !path.ends_with(b"/") {
+        path.push(b'/');
+    }
+    path.extend_from_slice(&name);
+    Ok(path)
+}
+
+fn endianity(elf: &ElfObject<'_>) -> RunTimeEndian {
+    match elf.endianity() {
+        Endian::Little => RunTimeEndian::Little,
+        Endian::Big => RunTimeEndian::Big,
+    }
+}
+
+/// Returns the data of a section, or an empty section if it does not exist.
+fn section<'data>(elf: &ElfObject<'data>, name: &str) -> Cow<'data, [u8]> {
+    name.strip_prefix('.')
+        .and_then(|name| elf.section(name))
+        .map_or_else(Cow::default, |section| section.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dwp_object_id() {
+        let object_id = ObjectId {
+            code_id: Some("fe4f51b335e68299e1342d12f4b4203acb0720a8".parse().unwrap()),
+            code_file: Some("/usr/bin/split".into()),
+            debug_file: Some("/usr/bin/split".into()),
+            object_type: ObjectType::Elf,
+            ..Default::default()
+        };
+
+        let dwp_id = dwp_object_id(&object_id);
+        assert_eq!(dwp_id.code_id, object_id.code_id);
+        assert_eq!(dwp_id.code_file.as_deref(), Some("/usr/bin/split.dwp"));
+        assert_eq!(dwp_id.debug_file.as_deref(), Some("/usr/bin/split.dwp"));
+    }
+}
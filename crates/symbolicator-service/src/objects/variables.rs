@@ -0,0 +1,483 @@
+//! Recovery of function arguments and local variables from DWARF.
+//!
+//! The locations of variables are described by DWARF expressions, or by location lists of
+//! expressions which apply to ranges of addresses. Evaluating them against the registers and
+//! memory of a stack frame yields the values of variables which were live at its address.
+
+use std::borrow::Cow;
+
+use gimli::{
+    AttributeValue, DwarfSections, EndianSlice, EvaluationResult, Expression, Location,
+    RunTimeEndian, UnitOffset, Value,
+};
+use symbolic::debuginfo::dwarf::{Dwarf, Endian};
+use symbolic::debuginfo::Object;
+
+type Slice<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// The maximum number of type or `DW_AT_abstract_origin` references followed.
+const MAX_REFERENCE_DEPTH: usize = 16;
+
+/// The maximum size in bytes of the values which are recovered.
+const MAX_VALUE_SIZE: u64 = 8;
+
+/// The registers and memory of a stack frame, against which locations are evaluated.
+pub trait VariableContext {
+    /// Returns the value of the register with the given DWARF register number.
+    fn register(&self, register: u16) -> Option<u64>;
+
+    /// Returns the canonical frame address, which is the stack pointer before the call.
+    fn cfa(&self) -> Option<u64>;
+
+    /// Reads a little- or big-endian value of `size` bytes from memory.
+    fn read_memory(&self, address: u64, size: u8) -> Option<u64>;
+}
+
+/// An argument or local variable of a function, along with its value in a stack frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredVariable {
+    /// The name of the variable.
+    pub name: String,
+    /// Whether the variable is an argument of the function.
+    pub is_argument: bool,
+    /// The name of the type of the variable.
+    pub type_name: Option<String>,
+    /// The value of the variable, if it could be recovered.
+    ///
+    /// Values are only recovered if they are not larger than eight bytes.
+    pub value: Option<u64>,
+}
+
+/// Recovers the variables of the innermost function at `address` in the object.
+///
+/// The address is in the address space of the object, like the addresses of its debug
+/// information. Only ELF and Mach-O objects with DWARF are supported.
+pub fn recover_variables(
+    object: &Object<'_>,
+    address: u64,
+    context: &dyn VariableContext,
+) -> gimli::Result<Vec<RecoveredVariable>> {
+    let (endian, sections) = match object {
+        Object::Elf(elf) => (endianity(elf.endianity()), load_sections(elf)?),
+        Object::MachO(macho) => (endianity(macho.endianity()), load_sections(macho)?),
+        _ => return Ok(Vec::new()),
+    };
+    let dwarf = sections.borrow(|section| EndianSlice::new(Cow::as_ref(section), endian));
+
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        if !unit_contains(&dwarf, &unit, address)? {
+            continue;
+        }
+
+        let mut scope = FunctionScope::default();
+        let mut tree = unit.entries_tree(None)?;
+        if !scope.collect(&dwarf, &unit, tree.root()?, address, false)? {
+            continue;
+        }
+
+        let frame_base = scope
+            .frame_base
+            .and_then(|expression| evaluate(expression, &unit, None, context))
+            .and_then(|location| match location {
+                Resolved::Register(register) => context.register(register),
+                Resolved::Address(address) | Resolved::Value(address) => Some(address),
+            });
+
+        let mut variables = Vec::with_capacity(scope.variables.len());
+        for offset in scope.variables {
+            let variable = read_variable(&dwarf, &unit, offset, address, frame_base, context)?;
+            variables.extend(variable);
+        }
+        return Ok(variables);
+    }
+
+    Ok(Vec::new())
+}
+
+/// The variables of the innermost function containing an address.
+#[derive(Default)]
+struct FunctionScope<'a> {
+    /// The frame base of the enclosing subprogram, which inlined functions share.
+    frame_base: Option<Expression<Slice<'a>>>,
+    /// The entries of the parameters and variables in scope.
+    variables: Vec<UnitOffset>,
+}
+
+impl<'a> FunctionScope<'a> {
+    /// Collects the variables of the function containing the address among the children of
+    /// `node`.
+    ///
+    /// Returns `true` once a function containing the address was found, after which the
+    /// remaining entries of the enclosing function are skipped.
+    fn collect(
+        &mut self,
+        dwarf: &gimli::Dwarf<Slice<'a>>,
+        unit: &gimli::Unit<Slice<'a>>,
+        node: gimli::EntriesTreeNode<'_, '_, '_, Slice<'a>>,
+        address: u64,
+        in_function: bool,
+    ) -> gimli::Result<bool> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
+                    if !die_contains(dwarf, unit, entry, address)? {
+                        continue;
+                    }
+                    if let Some(AttributeValue::Exprloc(expression)) =
+                        entry.attr_value(gimli::DW_AT_frame_base)?
+                    {
+                        self.frame_base = Some(expression);
+                    }
+                    self.variables.clear();
+                    self.collect(dwarf, unit, child, address, true)?;
+                    return Ok(true);
+                }
+                gimli::DW_TAG_lexical_block if in_function => {
+                    let found = die_contains(dwarf, unit, entry, address)?
+                        && self.collect(dwarf, unit, child, address, true)?;
+                    if found {
+                        return Ok(true);
+                    }
+                }
+                gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable if in_function => {
+                    self.variables.push(entry.offset());
+                }
+                gimli::DW_TAG_namespace
+                | gimli::DW_TAG_class_type
+                | gimli::DW_TAG_structure_type
+                | gimli::DW_TAG_union_type
+                    if !in_function =>
+                {
+                    let found = self.collect(dwarf, unit, child, address, false)?;
+                    if found {
+                        return Ok(true);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Reads the name, type and value of the variable at `offset`.
+///
+/// Returns `None` for variables without a name.
+fn read_variable(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    offset: UnitOffset,
+    address: u64,
+    frame_base: Option<u64>,
+    context: &dyn VariableContext,
+) -> gimli::Result<Option<RecoveredVariable>> {
+    let entry = unit.entry(offset)?;
+    let is_argument = entry.tag() == gimli::DW_TAG_formal_parameter;
+
+    // Concrete instances of inlined functions refer to their abstract instance for the name and
+    // type of their variables, but have their own locations.
+    let name = match origin_attr(unit, offset, gimli::DW_AT_name)? {
+        Some(value) => dwarf.attr_string(unit, value)?,
+        None => return Ok(None),
+    };
+    let (type_name, size) = match origin_attr(unit, offset, gimli::DW_AT_type)? {
+        Some(AttributeValue::UnitRef(type_offset)) => type_info(dwarf, unit, type_offset)?,
+        _ => (None, None),
+    };
+
+    let expression = match entry.attr_value(gimli::DW_AT_location)? {
+        Some(AttributeValue::Exprloc(expression)) => Some(expression),
+        Some(value) => match dwarf.attr_locations(unit, value)? {
+            Some(mut locations) => {
+                let mut expression = None;
+                while let Some(location) = locations.next()? {
+                    if location.range.begin <= address && address < location.range.end {
+                        expression = Some(location.data);
+                        break;
+                    }
+                }
+                expression
+            }
+            None => None,
+        },
+        None => None,
+    };
+
+    let size = size.unwrap_or(u64::from(unit.encoding().address_size));
+    let value = expression
+        .filter(|_| size <= MAX_VALUE_SIZE)
+        .and_then(|expression| evaluate(expression, unit, frame_base, context))
+        .and_then(|location| match location {
+            Resolved::Register(register) => context.register(register).map(|value| {
+                // Registers are wider than smaller values.
+                match size {
+                    MAX_VALUE_SIZE => value,
+                    size => value & ((1 << (size * 8)) - 1),
+                }
+            }),
+            Resolved::Address(address) => context.read_memory(address, size as u8),
+            Resolved::Value(value) => Some(value),
+        });
+
+    Ok(Some(RecoveredVariable {
+        name: name.to_string_lossy().into_owned(),
+        is_argument,
+        type_name,
+        value,
+    }))
+}
+
+/// Returns an attribute of the entry, or of the entry it refers to as its abstract origin.
+fn origin_attr<'a>(
+    unit: &gimli::Unit<Slice<'a>>,
+    mut offset: UnitOffset,
+    name: gimli::DwAt,
+) -> gimli::Result<Option<AttributeValue<Slice<'a>>>> {
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        let entry = unit.entry(offset)?;
+        if let Some(value) = entry.attr_value(name)? {
+            return Ok(Some(value));
+        }
+        match entry.attr_value(gimli::DW_AT_abstract_origin)? {
+            Some(AttributeValue::UnitRef(origin)) => offset = origin,
+            _ => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the name and size in bytes of the type at `offset`.
+///
+/// Pointer types are named after the type they point to, like `char*`.
+fn type_info(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    mut offset: UnitOffset,
+) -> gimli::Result<(Option<String>, Option<u64>)> {
+    let mut size = None;
+    let mut pointers = 0;
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        let entry = unit.entry(offset)?;
+        let is_pointer = matches!(
+            entry.tag(),
+            gimli::DW_TAG_pointer_type
+                | gimli::DW_TAG_reference_type
+                | gimli::DW_TAG_rvalue_reference_type
+        );
+        if size.is_none() {
+            size = match entry.attr_value(gimli::DW_AT_byte_size)? {
+                Some(value) => value.udata_value(),
+                None if is_pointer => Some(u64::from(unit.encoding().address_size)),
+                None => None,
+            };
+        }
+
+        // Pointers to named types are named after them, while typedefs keep their own name.
+        if !is_pointer {
+            if let Some(value) = entry.attr_value(gimli::DW_AT_name)? {
+                let name = dwarf.attr_string(unit, value)?.to_string_lossy();
+                return Ok((Some(format!("{name}{}", "*".repeat(pointers))), size));
+            }
+        }
+        if is_pointer {
+            pointers += 1;
+        }
+
+        match entry.attr_value(gimli::DW_AT_type)? {
+            Some(AttributeValue::UnitRef(target)) => offset = target,
+            _ if pointers > 0 => return Ok((Some(format!("void{}", "*".repeat(pointers))), size)),
+            _ => break,
+        }
+    }
+    Ok((None, size))
+}
+
+/// The location of a value, as described by a DWARF expression.
+enum Resolved {
+    /// The value is in a register.
+    Register(u16),
+    /// The value is in memory at an address.
+    Address(u64),
+    /// The value itself, which is not stored anywhere.
+    Value(u64),
+}
+
+/// Evaluates the expression, returning `None` if it needs information which is not available.
+fn evaluate(
+    expression: Expression<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    frame_base: Option<u64>,
+    context: &dyn VariableContext,
+) -> Option<Resolved> {
+    let mut evaluation = expression.evaluation(unit.encoding());
+    let mut result = evaluation.evaluate().ok()?;
+    loop {
+        result = match result {
+            EvaluationResult::Complete => break,
+            EvaluationResult::RequiresMemory { address, size, .. } => {
+                let value = context.read_memory(address, size)?;
+                evaluation.resume_with_memory(Value::Generic(value)).ok()?
+            }
+            EvaluationResult::RequiresRegister { register, .. } => {
+                let value = context.register(register.0)?;
+                evaluation
+                    .resume_with_register(Value::Generic(value))
+                    .ok()?
+            }
+            EvaluationResult::RequiresFrameBase => {
+                evaluation.resume_with_frame_base(frame_base?).ok()?
+            }
+            EvaluationResult::RequiresCallFrameCfa => {
+                evaluation.resume_with_call_frame_cfa(context.cfa()?).ok()?
+            }
+            // Thread-local storage, entry values, and relocated addresses are not supported.
+            _ => return None,
+        };
+    }
+
+    // Variables which are split into multiple pieces are not supported.
+    let pieces = evaluation.result();
+    let [piece] = pieces.as_slice() else {
+        return None;
+    };
+    match piece.location {
+        Location::Register { register } => Some(Resolved::Register(register.0)),
+        Location::Address { address } => Some(Resolved::Address(address)),
+        Location::Value { value } => value.to_u64(u64::MAX).ok().map(Resolved::Value),
+        _ => None,
+    }
+}
+
+/// Whether the ranges of the unit contain the address.
+fn unit_contains(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    address: u64,
+) -> gimli::Result<bool> {
+    let mut ranges = dwarf.unit_ranges(unit)?;
+    while let Some(range) = ranges.next()? {
+        if range.begin <= address && address < range.end {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Whether the ranges of the entry contain the address.
+fn die_contains(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    entry: &gimli::DebuggingInformationEntry<'_, '_, Slice<'_>>,
+    address: u64,
+) -> gimli::Result<bool> {
+    let mut ranges = dwarf.die_ranges(unit, entry)?;
+    while let Some(range) = ranges.next()? {
+        if range.begin <= address && address < range.end {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn load_sections<'data>(
+    object: &impl Dwarf<'data>,
+) -> gimli::Result<DwarfSections<Cow<'data, [u8]>>> {
+    DwarfSections::load(|id| {
+        let section = id
+            .name()
+            .strip_prefix('.')
+            .and_then(|name| object.section(name))
+            .map_or_else(Cow::default, |section| section.data);
+        Ok::<_, gimli::Error>(section)
+    })
+}
+
+fn endianity(endian: Endian) -> RunTimeEndian {
+    match endian {
+        Endian::Little => RunTimeEndian::Little,
+        Endian::Big => RunTimeEndian::Big,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    use crate::test;
+
+    /// A frame whose canonical frame address is `cfa`, with values at some addresses of memory.
+    struct TestContext {
+        cfa: u64,
+        memory: HashMap<u64, u64>,
+    }
+
+    impl VariableContext for TestContext {
+        fn register(&self, _register: u16) -> Option<u64> {
+            None
+        }
+
+        fn cfa(&self) -> Option<u64> {
+            Some(self.cfa)
+        }
+
+        fn read_memory(&self, address: u64, _size: u8) -> Option<u64> {
+            self.memory.get(&address).copied()
+        }
+    }
+
+    fn variable(name: &str, is_argument: bool, type_name: &str, value: u64) -> RecoveredVariable {
+        RecoveredVariable {
+            name: name.into(),
+            is_argument,
+            type_name: Some(type_name.into()),
+            value: Some(value),
+        }
+    }
+
+    #[test]
+    fn test_recover_variables() {
+        let data = test::read_fixture("symbols/variables/variables");
+        let object = Object::parse(&data).unwrap();
+
+        // The variables of `sum` are on the stack, relative to the canonical frame address.
+        let context = TestContext {
+            cfa: 0x7ff0_0000,
+            memory: [
+                (0x7ff0_0000 - 36, 21),
+                (0x7ff0_0000 - 48, 0x5555_1000),
+                (0x7ff0_0000 - 24, 42),
+            ]
+            .into(),
+        };
+
+        let variables = recover_variables(&object, 0x1140, &context).unwrap();
+        assert_eq!(
+            variables,
+            [
+                variable("count", true, "int", 21),
+                variable("name", true, "char*", 0x5555_1000),
+                variable("total", false, "long int", 42),
+            ]
+        );
+
+        // Without the memory of the frame, only the names and types are known.
+        let context = TestContext {
+            cfa: 0x7ff0_0000,
+            memory: HashMap::new(),
+        };
+        let variables = recover_variables(&object, 0x1160, &context).unwrap();
+        let names: Vec<_> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["argc", "argv"]);
+        assert_eq!(variables[1].type_name.as_deref(), Some("char**"));
+        assert!(variables.iter().all(|v| v.value.is_none()));
+
+        // Addresses outside of any function have no variables.
+        assert!(recover_variables(&object, 0x10, &context).unwrap().is_empty());
+    }
+}
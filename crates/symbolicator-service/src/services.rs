@@ -49,6 +49,7 @@ impl SharedServices {
             caches.objects.clone(),
             shared_cache.clone(),
             download_svc.clone(),
+            config.source_lookup_policy,
         );
 
         Ok(Self {
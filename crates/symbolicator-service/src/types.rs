@@ -10,6 +10,7 @@ use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use symbolicator_sources::ObjectType;
+use url::Url;
 
 use crate::utils::hex::HexValue;
 
@@ -69,6 +70,12 @@ pub struct ScrapingConfig {
     pub allowed_origins: Vec<String>,
     /// A map of headers to send with every HTTP request while scraping.
     pub headers: BTreeMap<String, String>,
+    /// Repositories that source files of native frames are fetched from.
+    ///
+    /// This is used for source context if the sources are not embedded in a debug file or
+    /// source bundle.
+    #[serde(default)]
+    pub source_repositories: Vec<SourceRepository>,
 }
 
 impl Default for ScrapingConfig {
@@ -79,7 +86,51 @@ impl Default for ScrapingConfig {
             allowed_origins: vec!["*".to_string()],
             headers: Default::default(),
             verify_ssl: true,
+            source_repositories: Vec::new(),
+        }
+    }
+}
+
+/// A repository that source files of native frames are fetched from.
+///
+/// Files whose absolute path starts with the `path_prefix`, usually the compilation directory
+/// of a build, are fetched from the `url` with the rest of their path appended.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SourceRepository {
+    /// The prefix of the absolute paths of files in the repository.
+    pub path_prefix: String,
+    /// The URL of the raw files of the repository at the revision of the build, like
+    /// `https://raw.githubusercontent.com/<owner>/<repo>/<commit>/`.
+    pub url: Url,
+    /// A map of headers to send with requests for files, like `Authorization`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl SourceRepository {
+    /// Returns the URL of the file at `abs_path`, if it is in this repository.
+    ///
+    /// Windows and Unix path separators are treated the same. Paths which leave the repository
+    /// with `..` are not mapped.
+    pub fn url_for_path(&self, abs_path: &str) -> Option<Url> {
+        let prefix = self.path_prefix.replace('\\', "/");
+        let path = abs_path.replace('\\', "/");
+        let rest = path.strip_prefix(prefix.trim_end_matches('/'))?;
+        if !rest.starts_with('/') {
+            return None;
+        }
+
+        let segments: Vec<_> = rest.split('/').filter(|s| !s.is_empty()).collect();
+        if segments.is_empty() || segments.iter().any(|s| *s == "." || *s == "..") {
+            return None;
         }
+
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .ok()?
+            .pop_if_empty()
+            .extend(segments);
+        Some(url)
     }
 }
 
@@ -312,3 +363,42 @@ impl fmt::Display for Platform {
         self.as_ref().fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_repository(path_prefix: &str) -> SourceRepository {
+        SourceRepository {
+            path_prefix: path_prefix.into(),
+            url: "https://raw.githubusercontent.com/acme/app/abc123/"
+                .parse()
+                .unwrap(),
+            headers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_source_repository_url_for_path() {
+        let repository = make_repository("/home/ci/build/");
+        assert_eq!(
+            repository
+                .url_for_path("/home/ci/build/src/main file.c")
+                .unwrap()
+                .as_str(),
+            "https://raw.githubusercontent.com/acme/app/abc123/src/main%20file.c"
+        );
+        assert_eq!(repository.url_for_path("/home/ci/buildtools/x.c"), None);
+        assert_eq!(repository.url_for_path("/home/ci/build/../secret"), None);
+        assert_eq!(repository.url_for_path("/usr/include/stdio.h"), None);
+
+        let repository = make_repository("C:\\projects\\app");
+        assert_eq!(
+            repository
+                .url_for_path("C:\\projects\\app\\src\\main.cpp")
+                .unwrap()
+                .as_str(),
+            "https://raw.githubusercontent.com/acme/app/abc123/src/main.cpp"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! A DNS resolver for clients connecting to sources.
+//!
+//! The resolver rejects [`BlockedDestinations`] by host name before the name is resolved, and by
+//! IP address after, which covers host names pointing to internal addresses. It also applies the
+//! [`DnsConfig`], which overrides the addresses of hosts and orders them by IP family.
+//!
+//! Requests sent through a proxy are resolved by the proxy, so their hosts are checked with
+//! [`DnsResolver::check_proxied`] before they are sent.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use hickory_resolver::config::{LookupIpStrategy, ResolverConfig, ResolverOpts};
+use hickory_resolver::{system_conf, TokioAsyncResolver};
+use once_cell::sync::OnceCell;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use url::Host;
+
+use crate::config::{DnsConfig, IpFamily};
+use crate::utils::http::{normalize_host, BlockedDestination, BlockedDestinations};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Resolves host names with `hickory-dns`, according to the [`DnsConfig`].
+///
+/// Clones share the underlying resolver and its cache.
+#[derive(Clone, Debug, Default)]
+pub struct DnsResolver {
+    ip_family: IpFamily,
+    overrides: Arc<BTreeMap<String, Vec<IpAddr>>>,
+    blocked: BlockedDestinations,
+    /// The resolver is created lazily, as it reads the system configuration.
+    resolver: Arc<OnceCell<TokioAsyncResolver>>,
+}
+
+impl DnsResolver {
+    pub fn new(config: &DnsConfig, blocked: BlockedDestinations) -> Self {
+        let overrides = config
+            .overrides
+            .iter()
+            .map(|(host, addrs)| (normalize_host(host), addrs.clone()))
+            .collect();
+        Self {
+            ip_family: config.ip_family,
+            overrides: Arc::new(overrides),
+            blocked,
+            resolver: Default::default(),
+        }
+    }
+
+    /// The destinations which are never resolved.
+    pub fn blocked(&self) -> &BlockedDestinations {
+        &self.blocked
+    }
+
+    /// Whether this resolves hosts like the default resolver of `reqwest`.
+    pub fn is_default(&self) -> bool {
+        self.ip_family == IpFamily::Any && self.overrides.is_empty() && self.blocked.is_empty()
+    }
+
+    async fn resolve_host(self, host: String) -> Result<Addrs, BoxError> {
+        let host = host.as_str();
+        if self.blocked.is_blocked_host(host) {
+            return Err(self.blocked.blocked(host).into());
+        }
+
+        let ips = self.lookup(host).await?;
+
+        // Blocked addresses are skipped, the host is only rejected if none remain.
+        let mut ips: Vec<_> = ips
+            .into_iter()
+            .filter(|ip| !self.blocked.is_blocked_ip(*ip))
+            .collect();
+        if ips.is_empty() {
+            return Err(self.blocked.blocked(host).into());
+        }
+
+        // Connections start with the family of the first address, so sorting the addresses
+        // prefers a family.
+        ips.retain(|ip| self.ip_family.allows(*ip));
+        match self.ip_family {
+            IpFamily::PreferIpv4 => ips.sort_by_key(|ip| !ip.is_ipv4()),
+            IpFamily::PreferIpv6 => ips.sort_by_key(|ip| !ip.is_ipv6()),
+            _ => {}
+        }
+        if ips.is_empty() {
+            let message = format!("`{host}` has no addresses of the configured IP family");
+            return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+        }
+
+        let addrs: Vec<_> = ips.into_iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+        Ok(Box::new(addrs.into_iter()))
+    }
+
+    /// Looks up the addresses of a host, preferring the configured overrides.
+    async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, BoxError> {
+        if let Some(ips) = self.overrides.get(&normalize_host(host)) {
+            return Ok(ips.clone());
+        }
+        let resolver = self.resolver.get_or_init(|| new_resolver(self.ip_family));
+        Ok(resolver.lookup_ip(host).await?.iter().collect())
+    }
+
+    /// Checks the host of a URL which is requested through a proxy.
+    ///
+    /// The proxy resolves the host on its own and may connect to any of its addresses. Hence, the
+    /// host is rejected if one of its addresses is blocked, or if it cannot be resolved here.
+    pub async fn check_proxied(&self, url: &Url) -> Result<(), BlockedDestination> {
+        if self.blocked.is_empty() {
+            return Ok(());
+        }
+        self.blocked.check_url(url)?;
+        let Some(Host::Domain(host)) = url.host() else {
+            return Ok(());
+        };
+
+        match self.lookup(host).await {
+            Ok(ips) if !ips.iter().any(|ip| self.blocked.is_blocked_ip(*ip)) => Ok(()),
+            Ok(_) => Err(self.blocked.blocked(host)),
+            Err(error) => {
+                tracing::debug!(%error, "Failed to resolve the host of a proxied request");
+                Err(self.blocked.blocked(host))
+            }
+        }
+    }
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().resolve_host(name.as_str().to_owned()))
+    }
+}
+
+/// Creates a resolver from the system configuration, like `reqwest` does.
+fn new_resolver(ip_family: IpFamily) -> TokioAsyncResolver {
+    let (config, mut opts) = match system_conf::read_system_conf() {
+        Ok(conf) => conf,
+        Err(error) => {
+            tracing::warn!(%error, "Failed to read the system DNS configuration");
+            (ResolverConfig::default(), ResolverOpts::default())
+        }
+    };
+    // Unless only one family is allowed, addresses of both families are resolved, so hosts are
+    // not rejected just because one of them is blocked, and connections can fall back to the
+    // other family.
+    opts.ip_strategy = match ip_family {
+        IpFamily::Ipv4Only => LookupIpStrategy::Ipv4Only,
+        IpFamily::Ipv6Only => LookupIpStrategy::Ipv6Only,
+        _ => LookupIpStrategy::Ipv4AndIpv6,
+    };
+    TokioAsyncResolver::tokio(config, opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn resolve(resolver: &DnsResolver, host: &str) -> Result<Vec<IpAddr>, BoxError> {
+        let addrs = resolver.clone().resolve_host(host.to_owned()).await?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn config(ip_family: IpFamily) -> DnsConfig {
+        let addrs = vec![ip("2001:db8::1"), ip("192.0.2.1")];
+        DnsConfig {
+            ip_family,
+            overrides: [("Symbols.Example.com".to_owned(), addrs)].into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overrides() {
+        assert!(DnsResolver::default().is_default());
+
+        let resolver = DnsResolver::new(&config(IpFamily::Any), Default::default());
+        assert!(!resolver.is_default());
+
+        let ips = resolve(&resolver, "symbols.example.com").await.unwrap();
+        assert_eq!(ips, [ip("2001:db8::1"), ip("192.0.2.1")]);
+    }
+
+    #[tokio::test]
+    async fn test_ip_family() {
+        let resolver = DnsResolver::new(&config(IpFamily::PreferIpv4), Default::default());
+        let ips = resolve(&resolver, "symbols.example.com").await.unwrap();
+        assert_eq!(ips, [ip("192.0.2.1"), ip("2001:db8::1")]);
+
+        let resolver = DnsResolver::new(&config(IpFamily::Ipv6Only), Default::default());
+        let ips = resolve(&resolver, "symbols.example.com").await.unwrap();
+        assert_eq!(ips, [ip("2001:db8::1")]);
+    }
+
+    #[tokio::test]
+    async fn test_check_proxied() {
+        let url = |url: &str| Url::parse(url).unwrap();
+
+        let resolver = DnsResolver::new(&config(IpFamily::Any), Default::default());
+        let proxied = url("http://symbols.example.com/file");
+        assert!(resolver.check_proxied(&proxied).await.is_ok());
+
+        // A single blocked address rejects the host, as the proxy may connect to it.
+        let blocked = BlockedDestinations::new(&["192.0.2.0/24".into()]);
+        let resolver = DnsResolver::new(&config(IpFamily::Any), blocked);
+        let error = resolver.check_proxied(&proxied).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "connections to `symbols.example.com` are not allowed"
+        );
+        assert!(resolver
+            .check_proxied(&url("http://192.0.2.7/file"))
+            .await
+            .is_err());
+        assert!(resolver
+            .check_proxied(&url("http://198.51.100.7/file"))
+            .await
+            .is_ok());
+
+        // Hosts which cannot be resolved are rejected, as the proxy may resolve them differently.
+        let error = resolver
+            .check_proxied(&url("http://symbols.invalid/file"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "connections to `symbols.invalid` are not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocked_override() {
+        let blocked = BlockedDestinations::new(&["192.0.2.0/24".into(), "2001:db8::/32".into()]);
+        let resolver = DnsResolver::new(&config(IpFamily::Any), blocked);
+        let error = resolve(&resolver, "symbols.example.com").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "connections to `symbols.example.com` are not allowed"
+        );
+    }
+}
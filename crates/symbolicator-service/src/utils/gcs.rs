@@ -3,6 +3,7 @@
 use std::sync::Arc;
 
 use chrono::{DateTime, Duration, Utc};
+use gcp_auth::TokenProvider;
 use jsonwebtoken::errors::Error as JwtError;
 use jsonwebtoken::EncodingKey;
 use reqwest::Client;
@@ -12,6 +13,9 @@ use url::Url;
 
 use symbolicator_sources::GcsSourceKey;
 
+/// The OAuth scope needed to download objects.
+const READ_ONLY_SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_only";
+
 /// A JWT token usable for GCS.
 #[derive(Debug, Clone)]
 pub struct GcsToken {
@@ -63,6 +67,8 @@ pub enum GcsError {
     Jwt(#[from] JwtError),
     #[error("failed to send authentication request")]
     Auth(#[source] reqwest::Error),
+    #[error("failed to obtain ambient credentials")]
+    Ambient(#[source] gcp_auth::Error),
 }
 
 /// Returns the URL for an object.
@@ -101,7 +107,7 @@ fn get_auth_jwt(source_key: &GcsSourceKey, expiration: i64) -> Result<String, Jw
 
     let jwt_claims = JwtClaims {
         issuer: &source_key.client_email,
-        scope: "https://www.googleapis.com/auth/devstorage.read_only",
+        scope: READ_ONLY_SCOPE,
         audience: "https://www.googleapis.com/oauth2/v4/token",
         expiration,
         issued_at: Utc::now().timestamp(),
@@ -144,6 +150,23 @@ pub async fn request_new_token(
     })
 }
 
+/// Requests a GCS OAuth token using the ambient credentials of the `provider`.
+pub async fn request_ambient_token(provider: &dyn TokenProvider) -> Result<GcsToken, GcsError> {
+    let token = provider
+        .token(&[READ_ONLY_SCOPE])
+        .await
+        .map_err(GcsError::Ambient)?;
+
+    // Refresh the token a bit before it expires, so it does not expire mid-request.
+    let expires_at = token.expires_at() - Duration::minutes(2);
+    let bearer_token = format!("Bearer {}", token.as_str()).into();
+
+    Ok(GcsToken {
+        bearer_token,
+        expires_at,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
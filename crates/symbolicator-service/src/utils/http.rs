@@ -1,12 +1,16 @@
 use std::fmt::Write;
 use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
-use ipnetwork::Ipv4Network;
+use ipnetwork::{IpNetwork, Ipv4Network};
 use once_cell::sync::Lazy;
 use reqwest::{redirect, StatusCode, Url};
+use thiserror::Error;
+use url::Host;
 
 use crate::config::Config;
+use crate::utils::dns::DnsResolver;
 
 static RESERVED_IP_BLOCKS: Lazy<Vec<Ipv4Network>> = Lazy::new(|| {
     [
@@ -32,7 +36,7 @@ static RESERVED_IP_BLOCKS: Lazy<Vec<Ipv4Network>> = Lazy::new(|| {
     .collect()
 });
 
-fn is_external_ip(ip: std::net::IpAddr) -> bool {
+pub(crate) fn is_external_ip(ip: std::net::IpAddr) -> bool {
     let IpAddr::V4(addr) = ip else {
         // We don't know what is an internal service in IPv6 and what is not. Just
         // bail out. This effectively means that we don't support IPv6.
@@ -53,6 +57,105 @@ fn is_external_ip(ip: std::net::IpAddr) -> bool {
     true
 }
 
+/// A connection attempt to one of the [`BlockedDestinations`].
+#[derive(Debug, Error)]
+#[error("connections to `{0}` are not allowed")]
+pub struct BlockedDestination(String);
+
+/// Hosts and IP networks which clients may never connect to.
+///
+/// Host names are checked before they are resolved, and IP addresses after, so host names
+/// resolving to blocked networks are rejected as well. See [`Config::blocked_destinations`] for
+/// the syntax of the entries.
+#[derive(Clone, Debug, Default)]
+pub struct BlockedDestinations {
+    networks: Arc<[IpNetwork]>,
+    hosts: Arc<[String]>,
+}
+
+impl BlockedDestinations {
+    /// Parses the blocked destinations from their configured entries.
+    ///
+    /// Entries which are neither an IP address nor an IP network are treated as host names.
+    pub fn new(entries: &[String]) -> Self {
+        let mut networks = Vec::new();
+        let mut hosts = Vec::new();
+        for entry in entries {
+            let entry = entry.trim();
+            if let Ok(addr) = entry.parse::<IpAddr>() {
+                networks.push(IpNetwork::from(addr));
+            } else if let Ok(network) = entry.parse::<IpNetwork>() {
+                networks.push(network);
+            } else {
+                hosts.push(normalize_host(entry));
+            }
+        }
+
+        Self {
+            networks: networks.into(),
+            hosts: hosts.into(),
+        }
+    }
+
+    /// Whether no destinations are blocked.
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty() && self.hosts.is_empty()
+    }
+
+    /// Whether the IP address is contained in one of the blocked networks.
+    ///
+    /// IPv4 addresses mapped to IPv6 are checked against the IPv4 networks.
+    pub fn is_blocked_ip(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// Whether the host name is blocked, either exactly or by a `*.` wildcard.
+    pub fn is_blocked_host(&self, host: &str) -> bool {
+        let host = normalize_host(host);
+        self.hosts
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => {
+                    host == domain
+                        || host
+                            .strip_suffix(domain)
+                            .is_some_and(|subdomain| subdomain.ends_with('.'))
+                }
+                None => host == *pattern,
+            })
+    }
+
+    /// Checks the host of the URL, which is either a host name or an IP address.
+    pub fn check_url(&self, url: &Url) -> Result<(), BlockedDestination> {
+        let blocked = match url.host() {
+            Some(Host::Domain(domain)) => self.is_blocked_host(domain),
+            Some(Host::Ipv4(ip)) => self.is_blocked_ip(ip.into()),
+            Some(Host::Ipv6(ip)) => self.is_blocked_ip(ip.into()),
+            None => false,
+        };
+        if blocked {
+            return Err(self.blocked(url.host_str().unwrap_or_default()));
+        }
+        Ok(())
+    }
+
+    /// Records a blocked connection attempt to `destination`.
+    pub(crate) fn blocked(&self, destination: &str) -> BlockedDestination {
+        metric!(counter("http.blocked_destination") += 1);
+        tracing::debug!("Blocked attempt to connect to `{}`", destination);
+        BlockedDestination(destination.to_owned())
+    }
+}
+
+/// Lowercases a host name and removes the trailing dot of fully qualified names.
+pub(crate) fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
 /// Various timeouts for all the Downloaders
 #[derive(Copy, Clone, Debug)]
 pub struct DownloadTimeouts {
@@ -95,46 +198,78 @@ impl Default for DownloadTimeouts {
 ///   connect to reserved IPs (as defined in `RESERVED_IP_BLOCKS`).
 /// * `accept_invalid_certs` determines whether the client accepts invalid
 ///   SSL certificates.
+/// * `dns` resolves hosts. Its [blocked destinations](DnsResolver::blocked) are never resolved
+///   or followed redirects to, regardless of `connect_to_reserved_ips`. URLs with IP addresses
+///   are not resolved, callers check them with [`BlockedDestinations::check_url`].
 /// * Uses a custom redirect policy that limits redirects from certain hosts
 ///   to avoid fetching login pages.
+/// * Does not decode compressed responses. Downloads negotiate their encoding on their own and
+///   are decoded while they are written to disk.
 pub fn create_client(
     timeouts: &DownloadTimeouts,
     connect_to_reserved_ips: bool,
     accept_invalid_certs: bool,
+    dns: &DnsResolver,
 ) -> reqwest::Client {
+    client_builder(timeouts, connect_to_reserved_ips, accept_invalid_certs, dns)
+        .build()
+        .unwrap()
+}
+
+/// Creates a [`reqwest::ClientBuilder`] with the options described in [`create_client`].
+///
+/// This allows to customize clients further, for instance with source-specific TLS settings.
+pub fn client_builder(
+    timeouts: &DownloadTimeouts,
+    connect_to_reserved_ips: bool,
+    accept_invalid_certs: bool,
+    dns: &DnsResolver,
+) -> reqwest::ClientBuilder {
+    let redirect_blocked = dns.blocked().clone();
     let mut builder = reqwest::ClientBuilder::new()
-        .gzip(true)
+        .no_gzip()
+        .no_brotli()
+        .no_deflate()
         .hickory_dns(true)
         .connect_timeout(timeouts.connect)
         .timeout(timeouts.max_download)
         .pool_idle_timeout(Duration::from_secs(30))
         .danger_accept_invalid_certs(accept_invalid_certs)
-        .redirect(redirect::Policy::custom(|attempt: redirect::Attempt| {
-            // The default redirect policy allows to follow up to 10 redirects. This is problematic
-            // when symbolicator tries to fetch native source files from a web source, as a redirect
-            // might land us on a login page, which is then used for source context.
-            // To avoid this, symbolicator's redirect policy is to not follow temporary redirects
-            // on hosts that are known to redirect to login pages.
-
-            if attempt.status() == StatusCode::FOUND {
-                let is_from_azure = attempt
-                    .previous()
-                    .last()
-                    .and_then(|url| url.host_str())
-                    .map_or(false, |host| host == "dev.azure.com");
-
-                if is_from_azure {
-                    return attempt.stop();
+        .redirect(redirect::Policy::custom(
+            move |attempt: redirect::Attempt| {
+                if let Err(err) = redirect_blocked.check_url(attempt.url()) {
+                    return attempt.error(err);
                 }
-            }
-            redirect::Policy::default().redirect(attempt)
-        }));
+
+                // The default redirect policy allows to follow up to 10 redirects. This is problematic
+                // when symbolicator tries to fetch native source files from a web source, as a redirect
+                // might land us on a login page, which is then used for source context.
+                // To avoid this, symbolicator's redirect policy is to not follow temporary redirects
+                // on hosts that are known to redirect to login pages.
+
+                if attempt.status() == StatusCode::FOUND {
+                    let is_from_azure = attempt
+                        .previous()
+                        .last()
+                        .and_then(|url| url.host_str())
+                        .is_some_and(|host| host == "dev.azure.com");
+
+                    if is_from_azure {
+                        return attempt.stop();
+                    }
+                }
+                redirect::Policy::default().redirect(attempt)
+            },
+        ));
 
     if !connect_to_reserved_ips {
         builder = builder.ip_filter(is_external_ip);
     }
+    if !dns.is_default() {
+        builder = builder.dns_resolver(Arc::new(dns.clone()));
+    }
 
-    builder.build().unwrap()
+    builder
 }
 
 #[derive(Debug, Clone)]
@@ -247,6 +382,8 @@ pub fn is_valid_origin(origin: &Url, allowed: &[String]) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use crate::caching::CacheError;
+
     use super::*;
 
     #[tokio::test]
@@ -255,7 +392,7 @@ mod tests {
 
         let server = symbolicator_test::Server::new();
 
-        let result = create_client(&Default::default(), false, false) // untrusted
+        let result = create_client(&Default::default(), false, false, &Default::default()) // untrusted
             .get(server.url("/"))
             .send()
             .await;
@@ -271,7 +408,7 @@ mod tests {
 
         let mut url = server.url("/");
         url.set_host(Some("127.0.0.1")).unwrap();
-        let result = create_client(&Default::default(), false, false) // untrusted
+        let result = create_client(&Default::default(), false, false, &Default::default()) // untrusted
             .get(url)
             .send()
             .await;
@@ -285,7 +422,7 @@ mod tests {
 
         let server = symbolicator_test::Server::new();
 
-        let response = create_client(&Default::default(), true, false) // allowed to connect to reserved IPs
+        let response = create_client(&Default::default(), true, false, &Default::default()) // allowed to connect to reserved IPs
             .get(server.url("/garbage_data/OK"))
             .send()
             .await
@@ -295,9 +432,64 @@ mod tests {
         assert_eq!(text, "OK");
     }
 
+    #[test]
+    fn test_blocked_destinations() {
+        let blocked = BlockedDestinations::new(&[
+            "169.254.169.254".into(),
+            "10.0.0.0/8".into(),
+            "fd00::/8".into(),
+            "metadata.google.internal".into(),
+            "*.cluster.local".into(),
+        ]);
+
+        assert!(blocked.is_blocked_ip("169.254.169.254".parse().unwrap()));
+        assert!(!blocked.is_blocked_ip("169.254.169.253".parse().unwrap()));
+        assert!(blocked.is_blocked_ip("10.1.2.3".parse().unwrap()));
+        assert!(blocked.is_blocked_ip("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(blocked.is_blocked_ip("fd12::1".parse().unwrap()));
+        assert!(!blocked.is_blocked_ip("8.8.8.8".parse().unwrap()));
+
+        assert!(blocked.is_blocked_host("metadata.google.internal"));
+        assert!(blocked.is_blocked_host("Metadata.Google.Internal."));
+        assert!(blocked.is_blocked_host("cluster.local"));
+        assert!(blocked.is_blocked_host("svc.cluster.local"));
+        assert!(!blocked.is_blocked_host("evilcluster.local"));
+        assert!(!blocked.is_blocked_host("google.internal"));
+
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert!(blocked
+            .check_url(&url("http://169.254.169.254/latest/"))
+            .is_err());
+        assert!(blocked.check_url(&url("http://[fd00::1]:8080/")).is_err());
+        assert!(blocked
+            .check_url(&url("http://svc.cluster.local/"))
+            .is_err());
+        assert!(blocked.check_url(&url("https://example.com/")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_blocked_host() {
+        symbolicator_test::setup();
+
+        let server = symbolicator_test::Server::new();
+
+        let blocked = BlockedDestinations::new(&["localhost".into()]);
+        let dns = DnsResolver::new(&Default::default(), blocked);
+        let result = create_client(&Default::default(), true, false, &dns)
+            .get(server.url("/garbage_data/OK"))
+            .send()
+            .await;
+
+        let error = CacheError::from(result.unwrap_err());
+        assert_eq!(
+            error,
+            CacheError::Blocked("connections to `localhost` are not allowed".into())
+        );
+    }
+
     #[tokio::test]
     async fn test_client_redirect_policy() {
-        let client = create_client(&Default::default(), false, false);
+        let client = create_client(&Default::default(), false, false, &Default::default());
 
         let response = client
             .get("https://dev.azure.com/foo/bar.cs")
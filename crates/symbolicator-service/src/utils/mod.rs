@@ -1,3 +1,4 @@
+pub mod dns;
 pub mod futures;
 pub mod gcs;
 pub mod hex;
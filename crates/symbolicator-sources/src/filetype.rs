@@ -57,6 +57,8 @@ pub enum FileType {
     Il2cpp,
     /// A proguard debug file.
     Proguard,
+    /// A DWARF package (`.dwp`) holding the split DWARF of a Linux/ELF object.
+    Dwp,
 }
 
 impl FileType {
@@ -79,6 +81,7 @@ impl FileType {
             BcSymbolMap,
             PortablePdb,
             Proguard,
+            Dwp,
         ]
     }
 
@@ -148,6 +151,7 @@ impl AsRef<str> for FileType {
             FileType::Il2cpp => "il2cpp",
             FileType::PortablePdb => "portablepdb",
             FileType::Proguard => "proguard",
+            FileType::Dwp => "dwp",
         }
     }
 }
@@ -46,7 +46,7 @@ fn get_lldb_path(identifier: &ObjectId) -> Option<String> {
     Some(path)
 }
 
-fn get_pdb_symstore_path(identifier: &ObjectId, ssqp_casing: bool) -> Option<String> {
+pub(crate) fn get_pdb_symstore_path(identifier: &ObjectId, ssqp_casing: bool) -> Option<String> {
     let debug_file = identifier.validated_debug_file_basename()?;
     let debug_id = identifier.debug_id.as_ref()?;
 
@@ -103,7 +103,14 @@ fn get_breakpad_path(identifier: &ObjectId) -> Option<String> {
         return None;
     }
 
-    let debug_file = identifier.validated_debug_file_basename()?;
+    // Breakpad names the symbols of ELF and MachO objects after the object itself, which is
+    // often all that is known about modules of these platforms.
+    let debug_file = match identifier.object_type {
+        ObjectType::Elf | ObjectType::Macho => identifier
+            .validated_debug_file_basename()
+            .or_else(|| identifier.validated_code_file_basename())?,
+        _ => identifier.validated_debug_file_basename()?,
+    };
     let debug_id = identifier.debug_id.as_ref()?;
     let new_debug_file = debug_file
         .strip_suffix(".exe")
@@ -203,8 +210,34 @@ fn get_native_paths(filetype: FileType, identifier: &ObjectId) -> Vec<String> {
             rv
         }
 
-        FileType::UuidMap => Vec::new(),
-        FileType::BcSymbolMap => Vec::new(),
+        // DWARF packages are stored next to the debug file, like `dwp` names them.
+        FileType::Dwp => {
+            if let Some(mut path) = get_gdb_path(identifier) {
+                path.push_str(".dwp");
+                vec![path]
+            } else {
+                vec![]
+            }
+        }
+
+        // Auxiliary files of Apple bitcode builds are stored next to the dSYM, with the
+        // extensions Xcode uses for them.
+        FileType::UuidMap => {
+            if let Some(mut path) = get_lldb_path(identifier) {
+                path.push_str(".plist");
+                vec![path]
+            } else {
+                vec![]
+            }
+        }
+        FileType::BcSymbolMap => {
+            if let Some(mut path) = get_lldb_path(identifier) {
+                path.push_str(".bcsymbolmap");
+                vec![path]
+            } else {
+                vec![]
+            }
+        }
         FileType::Il2cpp => Vec::new(),
         FileType::Proguard => Vec::new(),
     }
@@ -281,6 +314,7 @@ fn get_symstore_path(
         // Microsoft SymbolServer does not specify the following file types:
         FileType::Breakpad => None,
         FileType::WasmDebug | FileType::WasmCode => None,
+        FileType::Dwp => None,
         FileType::UuidMap => None,
         FileType::BcSymbolMap => None,
         FileType::Il2cpp => None,
@@ -303,7 +337,7 @@ fn get_symstore_index2_path(filetype: FileType, identifier: &ObjectId) -> Option
 /// Returns the relative location of the requested DIF on the debuginfod symbol server.
 ///
 /// Some file types are not supported by this symbol server and will return no result.
-fn get_debuginfod_path(filetype: FileType, identifier: &ObjectId) -> Option<String> {
+pub(crate) fn get_debuginfod_path(filetype: FileType, identifier: &ObjectId) -> Option<String> {
     match filetype {
         FileType::ElfCode => {
             let code_id = identifier.code_id.as_ref()?.as_str();
@@ -314,6 +348,9 @@ fn get_debuginfod_path(filetype: FileType, identifier: &ObjectId) -> Option<Stri
             Some(format!("{code_id}/debuginfo"))
         }
 
+        // DWARF packages are not supported
+        FileType::Dwp => None,
+
         // Mach is not supported
         FileType::MachCode | FileType::MachDebug => None,
 
@@ -384,7 +421,7 @@ fn get_search_target_id(filetype: FileType, identifier: &ObjectId) -> Option<Cow
         // For ELF we always use the code ID.  If it's not available we can't actually
         // find this file at all.  See symsorter which will never use the debug ID for
         // such files.
-        FileType::ElfCode | FileType::ElfDebug => {
+        FileType::ElfCode | FileType::ElfDebug | FileType::Dwp => {
             Some(Cow::Borrowed(identifier.code_id.as_ref()?.as_str()))
         }
         FileType::Proguard => None,
@@ -406,6 +443,7 @@ fn get_unified_path(filetype: FileType, identifier: &ObjectId) -> Option<String>
         FileType::BcSymbolMap => "bcsymbolmap",
         FileType::Il2cpp => "il2cpp",
         FileType::Proguard => "proguard",
+        FileType::Dwp => "dwp",
     };
 
     // determine the ID we use for the path
@@ -448,6 +486,10 @@ pub fn get_directory_paths(
         DirectoryLayoutType::SlashSymbols => {
             get_slashsymbols_path(identifier).into_iter().collect()
         }
+        DirectoryLayoutType::Breakpad => match filetype {
+            FileType::Breakpad => get_breakpad_path(identifier).into_iter().collect(),
+            _ => Vec::new(),
+        },
     };
 
     for path in paths.iter_mut() {
@@ -475,6 +517,51 @@ pub fn get_directory_paths(
     paths
 }
 
+/// Determines the paths of the debug file that a `.gnu_debuglink` of the file at `location`
+/// refers to.
+///
+/// Like GDB, the debug file is looked up next to the file, in a `.debug` directory next to it,
+/// and below `usr/lib/debug` in the directory of the code file of the object. Returns no paths if
+/// the debug link is not a plain file name.
+pub fn get_debug_link_paths(
+    location: &str,
+    debug_link: &str,
+    identifier: &ObjectId,
+) -> Vec<String> {
+    if debug_link.is_empty()
+        || debug_link == "."
+        || debug_link == ".."
+        || debug_link.contains(['/', '\\'])
+    {
+        return Vec::new();
+    }
+
+    let directory = match location.rsplit_once('/') {
+        Some((directory, _)) => format!("{directory}/"),
+        None => String::new(),
+    };
+    let mut paths = vec![
+        format!("{directory}{debug_link}"),
+        format!("{directory}.debug/{debug_link}"),
+    ];
+
+    let code_directory = identifier
+        .code_file
+        .as_deref()
+        .and_then(|code_file| code_file.strip_prefix('/'))
+        .map(|code_file| code_file.rsplit_once('/').map_or("", |(dir, _)| dir))
+        .filter(|dir| !dir.split('/').any(|segment| segment == ".."));
+    match code_directory {
+        Some("") => paths.push(format!("usr/lib/debug/{debug_link}")),
+        Some(dir) => paths.push(format!("usr/lib/debug/{dir}/{debug_link}")),
+        None => (),
+    }
+
+    // A debug link may name the file itself, which has no debug info.
+    paths.retain(|path| path != location);
+    paths
+}
+
 /// Parses a symstore path into a possible [`FileType`] and an [`ObjectId`].
 pub fn parse_symstore_path(path: &str) -> Option<(&'static [FileType], ObjectId)> {
     let mut split = path.splitn(3, '/');
@@ -591,6 +678,37 @@ pub fn matches_path_patterns(object_id: &ObjectId, patterns: &[Glob]) -> bool {
     false
 }
 
+/// Checks whether a path on a source matches any of the [`Glob`] patterns.
+pub fn matches_lookup_path_patterns(path: &str, patterns: &[Glob]) -> bool {
+    patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(path, GLOB_OPTIONS))
+}
+
+/// Checks whether the debug ID of an [`ObjectId`] starts with any of the prefixes.
+pub fn matches_debug_id_prefixes(object_id: &ObjectId, prefixes: &[String]) -> bool {
+    fn canonicalize(s: &str) -> String {
+        s.chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    if prefixes.is_empty() {
+        return true;
+    }
+
+    let Some(debug_id) = object_id.debug_id else {
+        return false;
+    };
+
+    let debug_id = canonicalize(&debug_id.to_string());
+    prefixes
+        .iter()
+        .any(|prefix| debug_id.starts_with(&canonicalize(prefix)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -648,6 +766,8 @@ mod tests {
         path_test!(FileType::SourceBundle, PE_OBJECT_ID, @"crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.src.zip");
         path_test!(FileType::MachCode, MACHO_OBJECT_ID, @"67E9/247C/814E/392B/A027/DBDE6748FCBF.app");
         path_test!(FileType::MachDebug, MACHO_OBJECT_ID, @"67E9/247C/814E/392B/A027/DBDE6748FCBF");
+        path_test!(FileType::UuidMap, MACHO_OBJECT_ID, @"67E9/247C/814E/392B/A027/DBDE6748FCBF.plist");
+        path_test!(FileType::BcSymbolMap, MACHO_OBJECT_ID, @"67E9/247C/814E/392B/A027/DBDE6748FCBF.bcsymbolmap");
         path_test!(FileType::Breakpad, MACHO_OBJECT_ID, @"crash/67E9247C814E392BA027DBDE6748FCBF0/crash.sym");
         path_test!(FileType::SourceBundle, MACHO_OBJECT_ID, @r###"
         crash/67E9247C814E392BA027DBDE6748FCBF0/crash.src.zip
@@ -658,6 +778,7 @@ mod tests {
         path_test!(FileType::SourceBundle, WASM_OBJECT_ID, @"67/e9247c814e392ba027dbde6748fcbf.src.zip");
         path_test!(FileType::ElfCode, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920");
         path_test!(FileType::ElfDebug, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920.debug");
+        path_test!(FileType::Dwp, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920.dwp");
         path_test!(FileType::Breakpad, ELF_OBJECT_ID, @"libm-2.23.so/E45DB8DFAF2D09FD640C8FE377D572DE0/libm-2.23.so.sym");
         path_test!(FileType::SourceBundle, ELF_OBJECT_ID, @r###"
         libm-2.23.so/E45DB8DFAF2D09FD640C8FE377D572DE0/libm-2.23.so.src.zip
@@ -685,11 +806,45 @@ mod tests {
         path_test!(FileType::SourceBundle, MACHO_OBJECT_ID, @"67/e9247c814e392ba027dbde6748fcbf/sourcebundle");
         path_test!(FileType::ElfCode, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920/executable");
         path_test!(FileType::ElfDebug, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920/debuginfo");
+        path_test!(FileType::Dwp, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920/dwp");
         path_test!(FileType::Breakpad, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920/breakpad");
         path_test!(FileType::SourceBundle, ELF_OBJECT_ID, @"df/b85de42daffd09640c8fe377d572de3e168920/sourcebundle");
         path_test!(FileType::SourceBundle, WASM_OBJECT_ID, @"67/e9247c814e392ba027dbde6748fcbf/sourcebundle");
     }
 
+    #[test]
+    fn test_get_debug_link_paths() {
+        let paths = get_debug_link_paths(
+            "df/b85de42daffd09640c8fe377d572de3e168920/executable",
+            "libm-2.23.so.debug",
+            &ELF_OBJECT_ID,
+        );
+        assert_eq!(
+            paths,
+            [
+                "df/b85de42daffd09640c8fe377d572de3e168920/libm-2.23.so.debug",
+                "df/b85de42daffd09640c8fe377d572de3e168920/.debug/libm-2.23.so.debug",
+                "usr/lib/debug/lib/x86_64-linux-gnu/libm-2.23.so.debug",
+            ]
+        );
+
+        // Files at the root of a source, and objects without absolute code files.
+        let paths = get_debug_link_paths("crash", "crash.debug", &WASM_OBJECT_ID);
+        assert_eq!(paths, ["crash.debug", ".debug/crash.debug"]);
+
+        // Debug links naming the file itself, or other directories.
+        let paths = get_debug_link_paths("libm.so", "libm.so", &ELF_OBJECT_ID);
+        assert_eq!(
+            paths,
+            [
+                ".debug/libm.so",
+                "usr/lib/debug/lib/x86_64-linux-gnu/libm.so"
+            ]
+        );
+        assert!(get_debug_link_paths("libm.so", "../libm.so", &ELF_OBJECT_ID).is_empty());
+        assert!(get_debug_link_paths("libm.so", "..", &ELF_OBJECT_ID).is_empty());
+    }
+
     #[test]
     fn test_get_debuginfod_path() {
         macro_rules! path_test {
@@ -753,6 +908,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_breakpad() {
+        let layout = DirectoryLayout {
+            ty: DirectoryLayoutType::Breakpad,
+            ..Default::default()
+        };
+
+        let paths = get_directory_paths(layout, FileType::Breakpad, &PE_OBJECT_ID);
+        assert_eq!(
+            paths,
+            ["crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.sym"]
+        );
+        let paths = get_directory_paths(layout, FileType::Breakpad, &ELF_OBJECT_ID);
+        assert_eq!(
+            paths,
+            ["libm-2.23.so/E45DB8DFAF2D09FD640C8FE377D572DE0/libm-2.23.so.sym"]
+        );
+
+        // Without a debug file, ELF and MachO symbols are named after the code file.
+        let identifier = ObjectId {
+            code_file: Some("/usr/lib/libm-2.23.so".into()),
+            debug_file: None,
+            ..ELF_OBJECT_ID.clone()
+        };
+        let paths = get_directory_paths(layout, FileType::Breakpad, &identifier);
+        assert_eq!(
+            paths,
+            ["libm-2.23.so/E45DB8DFAF2D09FD640C8FE377D572DE0/libm-2.23.so.sym"]
+        );
+        let identifier = ObjectId {
+            debug_file: None,
+            ..PE_OBJECT_ID.clone()
+        };
+        assert!(get_directory_paths(layout, FileType::Breakpad, &identifier).is_empty());
+
+        // Breakpad symbol stores do not contain any other files.
+        assert!(get_directory_paths(layout, FileType::Pdb, &PE_OBJECT_ID).is_empty());
+        assert!(get_directory_paths(layout, FileType::ElfDebug, &ELF_OBJECT_ID).is_empty());
+    }
+
     #[test]
     fn test_matches_path_patterns_empty() {
         assert!(matches_path_patterns(
@@ -797,6 +992,45 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_matches_lookup_path_patterns() {
+        let path = "crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.pdb";
+        assert!(matches_lookup_path_patterns(path, &[]));
+        assert!(matches_lookup_path_patterns(path, &[pattern("*.pdb/**")]));
+        assert!(matches_lookup_path_patterns(
+            path,
+            &[pattern("CRASH.PDB/*/*")]
+        ));
+        assert!(!matches_lookup_path_patterns(
+            path,
+            &[pattern("*.dylib/**")]
+        ));
+    }
+
+    #[test]
+    fn test_matches_debug_id_prefixes() {
+        let prefixes =
+            |prefixes: &[&str]| prefixes.iter().map(|&p| p.to_owned()).collect::<Vec<_>>();
+
+        assert!(matches_debug_id_prefixes(&PE_OBJECT_ID, &[]));
+        assert!(matches_debug_id_prefixes(
+            &PE_OBJECT_ID,
+            &prefixes(&["3249d99d-0c40"])
+        ));
+        assert!(matches_debug_id_prefixes(
+            &PE_OBJECT_ID,
+            &prefixes(&["67e9", "3249D99D0C"])
+        ));
+        assert!(!matches_debug_id_prefixes(
+            &PE_OBJECT_ID,
+            &prefixes(&["67e9"])
+        ));
+        assert!(!matches_debug_id_prefixes(
+            &ObjectId::default(),
+            &prefixes(&["3249"])
+        ));
+    }
+
     #[test]
     fn test_parsing_symstore_paths() {
         let (types, id) = parse_symstore_path("foo.exe/542D574Ec2000/foo.exe").unwrap();
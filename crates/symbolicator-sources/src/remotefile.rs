@@ -8,12 +8,14 @@ use std::path::Path;
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
+use symbolic::common::DebugId;
 use url::Url;
 
 use crate::{
-    get_directory_paths, CommonSourceConfig, DirectoryLayout, FileType, FilesystemRemoteFile,
-    GcsRemoteFile, HttpRemoteFile, ObjectId, S3RemoteFile, SentryRemoteFile, SourceFilters,
-    SourceId,
+    get_debug_link_paths, get_directory_paths, AzureRemoteFile, CommonSourceConfig,
+    DebuginfodRemoteFile, DirectoryLayout, FileType, FilesystemRemoteFile, GcsRemoteFile,
+    HttpRemoteFile, NugetRemoteFile, ObjectId, S3RemoteFile, SentryRemoteFile, SftpRemoteFile,
+    SourceFilters, SourceId, SourceLimits,
 };
 
 /// A location for a file retrievable from many source configs.
@@ -94,6 +96,9 @@ pub struct SourceLocationIter<'a> {
     /// Directory from `SourceConfig` to define what kind of paths we generate.
     layout: DirectoryLayout,
 
+    /// Whether PDBs with an age that differs by one are looked up too.
+    loose_matching: bool,
+
     /// Remaining locations to iterate.
     next: Vec<String>,
 }
@@ -110,9 +115,16 @@ impl<'a> SourceLocationIter<'a> {
             filters: &config.filters,
             object_id,
             layout: config.layout,
+            loose_matching: config.loose_matching,
             next: vec![],
         }
     }
+
+    /// Generates paths for the given layout instead of the one configured on the source.
+    pub fn with_layout(mut self, layout: DirectoryLayout) -> Self {
+        self.layout = layout;
+        self
+    }
 }
 
 impl Iterator for SourceLocationIter<'_> {
@@ -125,6 +137,16 @@ impl Iterator for SourceLocationIter<'_> {
                     continue;
                 }
                 self.next = get_directory_paths(self.layout, filetype, self.object_id);
+                if self.loose_matching && filetype == FileType::Pdb {
+                    // Paths are popped from the end, so the exact ones are looked up first.
+                    let mut paths: Vec<_> = loose_object_ids(self.object_id)
+                        .flat_map(|id| get_directory_paths(self.layout, filetype, &id))
+                        .collect();
+                    paths.append(&mut self.next);
+                    self.next = paths;
+                }
+                self.next
+                    .retain(|path| self.filters.is_lookup_path_allowed(path));
             } else {
                 return None;
             }
@@ -134,6 +156,18 @@ impl Iterator for SourceLocationIter<'_> {
     }
 }
 
+/// Returns the identifiers of the object with an age that differs by one.
+fn loose_object_ids(object_id: &ObjectId) -> impl Iterator<Item = ObjectId> + '_ {
+    let debug_id = object_id.debug_id;
+    let ages = debug_id.map(|debug_id| debug_id.appendix()).into_iter();
+    ages.flat_map(|age| [age.checked_sub(1), age.checked_add(1)])
+        .flatten()
+        .map(move |age| ObjectId {
+            debug_id: debug_id.map(|debug_id| DebugId::from_parts(debug_id.uuid(), age)),
+            ..object_id.clone()
+        })
+}
+
 /// Represents a single Debug Information File stored on a source.
 ///
 /// This joins the file location together with a [`SourceConfig`](crate::SourceConfig) and thus
@@ -141,16 +175,24 @@ impl Iterator for SourceLocationIter<'_> {
 /// an auxiliary DIF or an object file.
 #[derive(Debug, Clone)]
 pub enum RemoteFile {
+    /// A file on an Azure source.
+    Azure(AzureRemoteFile),
+    /// A file on a debuginfod source.
+    Debuginfod(DebuginfodRemoteFile),
     /// A file on a filesystem source.
     Filesystem(FilesystemRemoteFile),
     /// A file on a gcs source.
     Gcs(GcsRemoteFile),
     /// A file on a http source.
     Http(HttpRemoteFile),
+    /// A file on a NuGet source.
+    Nuget(NugetRemoteFile),
     /// A file on a S3 source.
     S3(S3RemoteFile),
     /// A file on a Sentry source.
     Sentry(SentryRemoteFile),
+    /// A file on an SFTP source.
+    Sftp(SftpRemoteFile),
 }
 
 impl fmt::Display for RemoteFile {
@@ -162,12 +204,36 @@ impl fmt::Display for RemoteFile {
             Self::Http(ref s) => {
                 write!(f, "HTTP source '{}' location '{}'", s.source.id, s.location)
             }
+            Self::Nuget(ref s) => {
+                write!(
+                    f,
+                    "NuGet source '{}' location '{}'",
+                    s.source.id, s.location
+                )
+            }
             Self::S3(ref s) => {
                 write!(f, "S3 source '{}' location '{}'", s.source.id, s.location)
             }
             Self::Gcs(ref s) => {
                 write!(f, "GCS source '{}' location '{}'", s.source.id, s.location)
             }
+            Self::Azure(ref s) => {
+                write!(
+                    f,
+                    "Azure source '{}' location '{}'",
+                    s.source.id, s.location
+                )
+            }
+            Self::Debuginfod(ref s) => {
+                write!(
+                    f,
+                    "debuginfod source '{}' location '{}'",
+                    s.source.id, s.location
+                )
+            }
+            Self::Sftp(ref s) => {
+                write!(f, "SFTP source '{}' location '{}'", s.source.id, s.location)
+            }
             Self::Filesystem(ref s) => {
                 write!(
                     f,
@@ -185,8 +251,12 @@ impl RemoteFile {
         match self {
             Self::Sentry(_) => false,
             Self::Http(ref x) => x.source.files.is_public,
+            Self::Nuget(ref x) => x.source.is_public,
             Self::S3(ref x) => x.source.files.is_public,
             Self::Gcs(ref x) => x.source.files.is_public,
+            Self::Azure(ref x) => x.source.files.is_public,
+            Self::Debuginfod(ref x) => x.source.is_public,
+            Self::Sftp(ref x) => x.source.files.is_public,
             Self::Filesystem(ref x) => x.source.files.is_public,
         }
     }
@@ -200,12 +270,24 @@ impl RemoteFile {
             Self::Http(ref x) => {
                 format!("{}.{}", x.source.id, x.location)
             }
+            Self::Nuget(ref x) => {
+                format!("{}.{}", x.source.id, x.location)
+            }
             Self::S3(ref x) => {
                 format!("{}.{}", x.source.id, x.location)
             }
             Self::Gcs(ref x) => {
                 format!("{}.{}", x.source.id, x.location)
             }
+            Self::Azure(ref x) => {
+                format!("{}.{}", x.source.id, x.location)
+            }
+            Self::Debuginfod(ref x) => {
+                format!("{}.{}", x.source.id, x.location)
+            }
+            Self::Sftp(ref x) => {
+                format!("{}.{}", x.source.id, x.location)
+            }
             Self::Filesystem(ref x) => {
                 format!("{}.{}", x.source.id, x.location)
             }
@@ -220,12 +302,33 @@ impl RemoteFile {
         match self {
             Self::Sentry(ref x) => &x.source.id,
             Self::Http(ref x) => &x.source.id,
+            Self::Nuget(ref x) => &x.source.id,
             Self::S3(ref x) => &x.source.id,
             Self::Gcs(ref x) => &x.source.id,
+            Self::Azure(ref x) => &x.source.id,
+            Self::Debuginfod(ref x) => &x.source.id,
+            Self::Sftp(ref x) => &x.source.id,
             Self::Filesystem(ref x) => &x.source.id,
         }
     }
 
+    /// Returns the [`SourceLimits`] of the source.
+    ///
+    /// The internal Sentry source is never limited.
+    pub fn limits(&self) -> &SourceLimits {
+        match self {
+            Self::Sentry(_) => &SourceLimits::UNLIMITED,
+            Self::Http(ref x) => &x.source.files.limits,
+            Self::Nuget(ref x) => &x.source.limits,
+            Self::S3(ref x) => &x.source.files.limits,
+            Self::Gcs(ref x) => &x.source.files.limits,
+            Self::Azure(ref x) => &x.source.files.limits,
+            Self::Debuginfod(ref x) => &x.source.limits,
+            Self::Sftp(ref x) => &x.source.files.limits,
+            Self::Filesystem(ref x) => &x.source.files.limits,
+        }
+    }
+
     /// Returns a key that uniquely identifies the source for metrics.
     ///
     /// If this is a built-in source the source_id is returned, otherwise this falls
@@ -243,7 +346,11 @@ impl RemoteFile {
             Self::Sentry(..) => "sentry",
             Self::S3(..) => "s3",
             Self::Gcs(..) => "gcs",
+            Self::Azure(..) => "azure",
+            Self::Debuginfod(..) => "debuginfod",
             Self::Http(..) => "http",
+            Self::Nuget(..) => "nuget",
+            Self::Sftp(..) => "sftp",
             Self::Filesystem(..) => "filesystem",
         }
     }
@@ -258,8 +365,12 @@ impl RemoteFile {
         match self {
             Self::Sentry(file_source) => file_source.uri(),
             Self::Http(file_source) => file_source.uri(),
+            Self::Nuget(file_source) => file_source.uri(),
             Self::S3(file_source) => file_source.uri(),
             Self::Gcs(file_source) => file_source.uri(),
+            Self::Azure(file_source) => file_source.uri(),
+            Self::Debuginfod(file_source) => file_source.uri(),
+            Self::Sftp(file_source) => file_source.uri(),
             Self::Filesystem(file_source) => file_source.uri(),
         }
     }
@@ -267,17 +378,24 @@ impl RemoteFile {
     /// Returns a string representation of the host this file resides on.
     ///
     /// This is:
-    /// * The host name for http;
+    /// * The host name for http and NuGet;
     /// * The bucket name for GCS and S3;
+    /// * The host name of the storage account for Azure;
+    /// * The host name of the first server for debuginfod;
     /// * The URL for Sentry;
+    /// * The host name of the server for SFTP;
     /// * A placeholder string for the filesystem.
     pub fn host(&self) -> String {
         match self {
             RemoteFile::Filesystem(source) => source.host(),
             RemoteFile::Gcs(source) => source.host(),
+            RemoteFile::Azure(source) => source.host(),
+            RemoteFile::Debuginfod(source) => source.host(),
             RemoteFile::Http(source) => source.host(),
+            RemoteFile::Nuget(source) => source.host(),
             RemoteFile::S3(source) => source.host(),
             RemoteFile::Sentry(source) => source.host(),
+            RemoteFile::Sftp(source) => source.host(),
         }
     }
 
@@ -297,6 +415,57 @@ impl RemoteFile {
             _ => true,
         }
     }
+
+    /// Returns the files that may hold the debug file a `.gnu_debuglink` of this file refers to.
+    ///
+    /// The files are on the same source, see [`get_debug_link_paths`] for their locations. This
+    /// is only supported for filesystem and HTTP sources, and no files are returned if the source
+    /// does not allow [`FileType::ElfDebug`] files for the object.
+    pub fn debug_link_files(&self, debug_link: &str, identifier: &ObjectId) -> Vec<RemoteFile> {
+        match self {
+            Self::Filesystem(file) => {
+                if !file
+                    .source
+                    .files
+                    .filters
+                    .is_allowed(identifier, FileType::ElfDebug)
+                {
+                    return Vec::new();
+                }
+                get_debug_link_paths(&file.location.0, debug_link, identifier)
+                    .into_iter()
+                    .filter(|path| file.source.files.filters.is_lookup_path_allowed(path))
+                    .map(|path| {
+                        FilesystemRemoteFile::new(file.source.clone(), SourceLocation::new(path))
+                            .into()
+                    })
+                    .collect()
+            }
+            Self::Http(file) => {
+                if !file
+                    .source
+                    .files
+                    .filters
+                    .is_allowed(identifier, FileType::ElfDebug)
+                {
+                    return Vec::new();
+                }
+                get_debug_link_paths(&file.location.0, debug_link, identifier)
+                    .into_iter()
+                    .filter(|path| file.source.files.filters.is_lookup_path_allowed(path))
+                    .map(|path| {
+                        HttpRemoteFile {
+                            source: file.source.clone(),
+                            location: SourceLocation::new(path),
+                            headers: file.headers.clone(),
+                        }
+                        .into()
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 /// A URI representing an [`RemoteFile`].
@@ -363,6 +532,8 @@ mod tests {
 
     use super::*;
 
+    use crate::{DirectoryLayoutType, Glob};
+
     #[test]
     fn test_location_prefix() {
         let key = SourceLocation::new("spam/ham").prefix("");
@@ -458,4 +629,51 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_iter_lookup_path_patterns() {
+        let code_id = CodeId::new(String::from("abcdefghijklmnopqrstuvwxyz1234567890abcd"));
+        let uuid = Uuid::from_slice(&code_id.as_str().as_bytes()[..16]).unwrap();
+        let debug_id = DebugId::from_uuid(uuid);
+
+        let mut config = CommonSourceConfig::default();
+        config.filters.lookup_path_patterns = vec![Glob("*.debug".parse().unwrap())];
+
+        let all: Vec<_> = SourceLocationIter::new(
+            &config,
+            &[FileType::ElfCode, FileType::ElfDebug],
+            &ObjectId {
+                debug_id: Some(debug_id),
+                code_id: Some(code_id),
+                ..Default::default()
+            },
+        )
+        .collect();
+
+        assert_eq!(all, [SourceLocation::new("ab/cdef1234567890abcd.debug")]);
+    }
+
+    #[test]
+    fn test_iter_loose_matching() {
+        let object_id = ObjectId {
+            debug_file: Some("crash.pdb".into()),
+            debug_id: Some("3249d99d-0c40-4931-8610-f4e4fb0b6936-2".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut config = CommonSourceConfig::with_layout(DirectoryLayoutType::Symstore);
+        config.loose_matching = true;
+
+        let all: Vec<_> = SourceLocationIter::new(&config, &[FileType::Pdb], &object_id).collect();
+        assert_eq!(
+            all,
+            [
+                SourceLocation::new("crash.pdb/3249D99D0C4049318610F4E4FB0B69362/crash.pdb"),
+                SourceLocation::new("crash.pdb/3249D99D0C4049318610F4E4FB0B69362/crash.pd_"),
+                SourceLocation::new("crash.pdb/3249D99D0C4049318610F4E4FB0B69363/crash.pdb"),
+                SourceLocation::new("crash.pdb/3249D99D0C4049318610F4E4FB0B69363/crash.pd_"),
+                SourceLocation::new("crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.pdb"),
+                SourceLocation::new("crash.pdb/3249D99D0C4049318610F4E4FB0B69361/crash.pd_"),
+            ]
+        );
+    }
 }
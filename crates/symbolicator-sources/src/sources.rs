@@ -9,16 +9,24 @@ use crate::filetype::FileType;
 use crate::paths;
 use crate::types::{Glob, ObjectId};
 
+mod azure;
+mod debuginfod;
 mod filesystem;
 mod gcs;
 mod http;
+mod nuget;
 mod s3;
 mod sentry;
+mod sftp;
+pub use azure::*;
+pub use debuginfod::*;
 pub use filesystem::*;
 pub use gcs::*;
 pub use http::*;
+pub use nuget::*;
 pub use s3::*;
 pub use sentry::*;
+pub use sftp::*;
 
 /// An identifier for DIF sources.
 ///
@@ -55,38 +63,71 @@ impl fmt::Display for SourceId {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum SourceConfig {
+    /// An Azure Blob Storage container.
+    Azure(Arc<AzureSourceConfig>),
+    /// A federation of debuginfod servers.
+    Debuginfod(Arc<DebuginfodSourceConfig>),
     /// Local file system.
     Filesystem(Arc<FilesystemSourceConfig>),
     /// A google cloud storage bucket.
     Gcs(Arc<GcsSourceConfig>),
     /// Http server implementing the Microsoft Symbol Server protocol.
     Http(Arc<HttpSourceConfig>),
+    /// NuGet symbol server serving Portable PDBs from symbol packages.
+    Nuget(Arc<NugetSourceConfig>),
     /// Amazon S3 bucket containing symbols in a directory hierarchy.
     S3(Arc<S3SourceConfig>),
     /// Sentry debug files endpoint.
     Sentry(Arc<SentrySourceConfig>),
+    /// Directories on an SFTP server.
+    Sftp(Arc<SftpSourceConfig>),
 }
 
 impl SourceConfig {
     /// The unique identifier of this source.
     pub fn id(&self) -> &SourceId {
         match self {
+            Self::Azure(x) => &x.id,
+            Self::Debuginfod(x) => &x.id,
             Self::Filesystem(x) => &x.id,
             Self::Gcs(x) => &x.id,
             Self::Http(x) => &x.id,
+            Self::Nuget(x) => &x.id,
             Self::S3(x) => &x.id,
             Self::Sentry(x) => &x.id,
+            Self::Sftp(x) => &x.id,
+        }
+    }
+
+    /// The priority of this source, see [`CommonSourceConfig::priority`].
+    ///
+    /// Sentry sources always have the default priority of `0`.
+    pub fn priority(&self) -> i32 {
+        match self {
+            Self::Azure(x) => x.files.priority,
+            Self::Debuginfod(x) => x.priority,
+            Self::Filesystem(x) => x.files.priority,
+            Self::Gcs(x) => x.files.priority,
+            Self::Http(x) => x.files.priority,
+            Self::Nuget(x) => x.priority,
+            Self::S3(x) => x.files.priority,
+            Self::Sentry(_) => 0,
+            Self::Sftp(x) => x.files.priority,
         }
     }
 
     /// Name of this source.
     pub fn type_name(&self) -> &'static str {
         match self {
+            Self::Azure(..) => "azure",
+            Self::Debuginfod(..) => "debuginfod",
             Self::Filesystem(..) => "filesystem",
             Self::Gcs(..) => "gcs",
             Self::Http(..) => "http",
+            Self::Nuget(..) => "nuget",
             Self::S3(..) => "s3",
             Self::Sentry(..) => "sentry",
+            Self::Sftp(..) => "sftp",
         }
     }
 }
@@ -103,6 +144,22 @@ pub struct CommonSourceConfig {
 
     /// Whether debug files are shared across scopes.
     pub is_public: bool,
+
+    /// Limits on how the source is used.
+    pub limits: SourceLimits,
+
+    /// The priority of this source.
+    ///
+    /// Sources with a higher priority are queried first, and files from them are preferred
+    /// over equally good files from sources with a lower priority.
+    pub priority: i32,
+
+    /// Whether PDB files with the same GUID, but an age that differs by one, are looked up too.
+    ///
+    /// Rebuilt binaries sometimes refer to a PDB with a different age than the one on the source.
+    /// Exact matches are preferred over such files, and frames symbolicated with them are flagged
+    /// as inexact.
+    pub loose_matching: bool,
 }
 
 impl CommonSourceConfig {
@@ -118,6 +175,27 @@ impl CommonSourceConfig {
     }
 }
 
+/// Limits on how symbolicator uses a source, to avoid overloading the servers behind it.
+///
+/// The limits apply across all downloads from sources with the same [`SourceId`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SourceLimits {
+    /// The maximum bandwidth in bytes per second that downloads from this source may use.
+    pub max_bytes_per_second: Option<u64>,
+
+    /// The maximum number of files downloaded from this source at the same time.
+    pub max_concurrent_downloads: Option<usize>,
+}
+
+impl SourceLimits {
+    /// Limits which allow unrestricted use of a source.
+    pub const UNLIMITED: Self = Self {
+        max_bytes_per_second: None,
+        max_concurrent_downloads: None,
+    };
+}
+
 /// Common attributes to make the symbolicator skip/consider sources by certain criteria.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(default)]
@@ -134,6 +212,20 @@ pub struct SourceFilters {
     /// If a debug image does not contain any path information it will be treated like an image
     /// whose path doesn't match any pattern.
     pub path_patterns: Vec<Glob>,
+
+    /// When nonempty, a list of glob patterns to match the paths of files on this source against.
+    /// Only files whose path matches one of the patterns are looked up.
+    ///
+    /// The paths are the ones generated by the [layout](DirectoryLayout) of the source, relative
+    /// to its root, for instance `wkernel32.pdb/<signature>/wkernel32.pdb`. Casing is ignored.
+    pub lookup_path_patterns: Vec<Glob>,
+
+    /// When nonempty, a list of prefixes of debug IDs which are available on this source. The
+    /// source is then only used if the debug ID of the image starts with one of the prefixes.
+    ///
+    /// Casing and `-` separators are ignored. If a debug image does not have a debug ID it will
+    /// be treated like an image whose debug ID doesn't match any prefix.
+    pub debug_id_prefixes: Vec<String>,
 }
 
 impl SourceFilters {
@@ -141,6 +233,12 @@ impl SourceFilters {
     pub fn is_allowed(&self, object_id: &ObjectId, filetype: FileType) -> bool {
         (self.filetypes.is_empty() || self.filetypes.contains(&filetype))
             && paths::matches_path_patterns(object_id, &self.path_patterns)
+            && paths::matches_debug_id_prefixes(object_id, &self.debug_id_prefixes)
+    }
+
+    /// Whether a file with the given path on this source is allowed to be looked up.
+    pub fn is_lookup_path_allowed(&self, path: &str) -> bool {
+        paths::matches_lookup_path_patterns(path, &self.lookup_path_patterns)
     }
 }
 
@@ -194,6 +292,9 @@ pub enum DirectoryLayoutType {
     /// A simple symbol source using the `{code_id}/symbols` as its search path.
     #[serde(rename = "slashsymbols")]
     SlashSymbols,
+    /// Uses Breakpad symbol store conventions, which only contain Breakpad symbol files.
+    #[serde(rename = "breakpad")]
+    Breakpad,
 }
 
 /// Casing of filenames on the symbol server
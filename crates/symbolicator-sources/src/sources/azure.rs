@@ -0,0 +1,238 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{CommonSourceConfig, RemoteFile, RemoteFileUri, SourceId, SourceLocation};
+
+/// Configuration for Azure Blob Storage containers.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AzureSourceConfig {
+    /// Unique source identifier.
+    pub id: SourceId,
+
+    /// Name of the storage account.
+    pub account: String,
+
+    /// Name of the container within the storage account.
+    pub container: String,
+
+    /// A path from the root of the container where files are located.
+    #[serde(default)]
+    pub prefix: String,
+
+    /// The blob service endpoint of the storage account.
+    ///
+    /// Defaults to `https://<account>.blob.core.windows.net/`. This only needs to be set for
+    /// national clouds or emulators such as Azurite.
+    #[serde(default)]
+    pub endpoint: Option<Url>,
+
+    /// Authorization information for this container. Needs read access.
+    #[serde(flatten)]
+    pub source_key: Arc<AzureSourceKey>,
+
+    /// Configuration common to all sources.
+    #[serde(flatten)]
+    pub files: CommonSourceConfig,
+}
+
+impl AzureSourceConfig {
+    /// Returns the URL of the container.
+    pub fn container_url(&self) -> anyhow::Result<Url> {
+        let mut url = match self.endpoint {
+            Some(ref endpoint) => endpoint.clone(),
+            None => Url::parse(&format!("https://{}.blob.core.windows.net/", self.account))?,
+        };
+        url.path_segments_mut()
+            .map_err(|_| anyhow::Error::msg("URL cannot-be-a-base"))?
+            .pop_if_empty()
+            .push(&self.container);
+        Ok(url)
+    }
+}
+
+/// The Azure-specific [`RemoteFile`].
+#[derive(Debug, Clone)]
+pub struct AzureRemoteFile {
+    /// The underlying [`AzureSourceConfig`].
+    pub source: Arc<AzureSourceConfig>,
+    pub(crate) location: SourceLocation,
+}
+
+impl From<AzureRemoteFile> for RemoteFile {
+    fn from(source: AzureRemoteFile) -> Self {
+        Self::Azure(source)
+    }
+}
+
+impl AzureRemoteFile {
+    /// Creates a new [`AzureRemoteFile`].
+    pub fn new(source: Arc<AzureSourceConfig>, location: SourceLocation) -> Self {
+        Self { source, location }
+    }
+
+    /// Returns the name of the blob.
+    ///
+    /// This is equivalent to the pathname within the container.
+    pub fn blob_name(&self) -> String {
+        self.location.prefix(&self.source.prefix)
+    }
+
+    /// Returns the URL from which to download this object file.
+    ///
+    /// This does not contain any credentials.
+    pub fn url(&self) -> anyhow::Result<Url> {
+        SourceLocation::new(self.blob_name()).to_url(&self.source.container_url()?)
+    }
+
+    /// Returns the URI from which to download this object file.
+    pub fn uri(&self) -> RemoteFileUri {
+        match self.url() {
+            Ok(url) => url.as_str().into(),
+            Err(_) => "".into(),
+        }
+    }
+
+    pub(crate) fn host(&self) -> String {
+        self.source
+            .container_url()
+            .ok()
+            .and_then(|url| url.host_str().map(ToOwned::to_owned))
+            .unwrap_or_default()
+    }
+}
+
+/// Azure Blob Storage authorization information.
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum AzureSourceKey {
+    /// A shared access signature, which is appended to the URL of every request.
+    SasToken {
+        /// The SAS token, with or without the leading `?`.
+        sas_token: String,
+    },
+    /// A service principal authenticating against Microsoft Entra ID with a client secret.
+    ///
+    /// The service principal needs the "Storage Blob Data Reader" role on the container.
+    ServicePrincipal {
+        /// The ID of the Entra ID tenant of the service principal.
+        tenant_id: String,
+        /// The application (client) ID of the service principal.
+        client_id: String,
+        /// A client secret of the service principal.
+        client_secret: String,
+    },
+}
+
+// The credentials must not end up in logs.
+impl fmt::Debug for AzureSourceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SasToken { .. } => f.debug_struct("SasToken").finish_non_exhaustive(),
+            Self::ServicePrincipal {
+                tenant_id,
+                client_id,
+                ..
+            } => f
+                .debug_struct("ServicePrincipal")
+                .field("tenant_id", tenant_id)
+                .field("client_id", client_id)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SourceConfig;
+
+    fn parse(text: &str) -> Arc<AzureSourceConfig> {
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(text).unwrap();
+        match &sources[0] {
+            SourceConfig::Azure(cfg) => cfg.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_azure_config_sas_token() {
+        let cfg = parse(
+            r#"
+          - id: azure
+            type: azure
+            account: myaccount
+            container: symbols
+            prefix: /windows
+            sas_token: "?sv=2022-11-02&sig=secret"
+            layout:
+              type: symstore
+            "#,
+        );
+        assert_eq!(cfg.account, "myaccount");
+        assert_eq!(cfg.container, "symbols");
+        assert_eq!(
+            *cfg.source_key,
+            AzureSourceKey::SasToken {
+                sas_token: "?sv=2022-11-02&sig=secret".into()
+            }
+        );
+
+        let file = AzureRemoteFile::new(cfg, SourceLocation::new("foo.pdb/ABC1/foo.pdb"));
+        assert_eq!(
+            file.uri(),
+            RemoteFileUri::new(
+                "https://myaccount.blob.core.windows.net/symbols/windows/foo.pdb/ABC1/foo.pdb"
+            )
+        );
+        assert_eq!(file.host(), "myaccount.blob.core.windows.net");
+    }
+
+    #[test]
+    fn test_azure_config_service_principal() {
+        let cfg = parse(
+            r#"
+          - id: azure
+            type: azure
+            account: myaccount
+            container: symbols
+            endpoint: http://127.0.0.1:10000/devstoreaccount1
+            tenant_id: the-tenant
+            client_id: the-client
+            client_secret: the-secret
+            "#,
+        );
+        assert_eq!(
+            *cfg.source_key,
+            AzureSourceKey::ServicePrincipal {
+                tenant_id: "the-tenant".into(),
+                client_id: "the-client".into(),
+                client_secret: "the-secret".into(),
+            }
+        );
+        assert!(!format!("{:?}", cfg.source_key).contains("the-secret"));
+
+        let file = AzureRemoteFile::new(cfg, SourceLocation::new("a/key/with spaces"));
+        assert_eq!(
+            file.uri(),
+            RemoteFileUri::new(
+                "http://127.0.0.1:10000/devstoreaccount1/symbols/a/key/with%20spaces"
+            )
+        );
+    }
+
+    #[test]
+    fn test_azure_config_missing_credentials() {
+        let text = r#"
+          - id: azure
+            type: azure
+            account: myaccount
+            container: symbols
+            "#;
+        let result: Result<Vec<SourceConfig>, _> = serde_yaml::from_str(text);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
+
+use crate::paths::get_debuginfod_path;
+use crate::{
+    FileType, ObjectId, RemoteFile, RemoteFileUri, SourceFilters, SourceId, SourceLimits,
+    SourceLocation,
+};
+
+/// Configuration for a federation of [debuginfod](https://sourceware.org/elfutils/Debuginfod.html)
+/// servers.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DebuginfodSourceConfig {
+    /// Unique source identifier.
+    pub id: SourceId,
+
+    /// The URLs of the debuginfod servers, in order of priority.
+    ///
+    /// Just like `DEBUGINFOD_URLS`, this can also be given as a single string of
+    /// whitespace-separated URLs.
+    #[serde(deserialize_with = "deserialize_urls")]
+    pub urls: Vec<Url>,
+
+    /// Additional headers to be sent to the servers with every request.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// Influence whether this source will be selected.
+    #[serde(default)]
+    pub filters: SourceFilters,
+
+    /// Whether debug files are shared across scopes.
+    #[serde(default)]
+    pub is_public: bool,
+
+    /// Limits on how the source is used.
+    #[serde(default)]
+    pub limits: SourceLimits,
+
+    /// The priority of this source, see [`CommonSourceConfig::priority`].
+    ///
+    /// [`CommonSourceConfig::priority`]: crate::CommonSourceConfig::priority
+    #[serde(default)]
+    pub priority: i32,
+}
+
+fn deserialize_urls<'de, D>(deserializer: D) -> Result<Vec<Url>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Urls {
+        List(Vec<Url>),
+        String(String),
+    }
+
+    match Urls::deserialize(deserializer)? {
+        Urls::List(urls) => Ok(urls),
+        Urls::String(urls) => urls
+            .split_whitespace()
+            .map(|url| Url::parse(url).map_err(serde::de::Error::custom))
+            .collect(),
+    }
+}
+
+/// The debuginfod-specific [`RemoteFile`].
+///
+/// The file is looked up on all the [servers](DebuginfodSourceConfig::urls) of the source in
+/// order, until one of them has it.
+#[derive(Debug, Clone)]
+pub struct DebuginfodRemoteFile {
+    /// The underlying [`DebuginfodSourceConfig`].
+    pub source: Arc<DebuginfodSourceConfig>,
+    pub(crate) location: SourceLocation,
+}
+
+impl From<DebuginfodRemoteFile> for RemoteFile {
+    fn from(source: DebuginfodRemoteFile) -> Self {
+        Self::Debuginfod(source)
+    }
+}
+
+impl DebuginfodRemoteFile {
+    /// Creates a new [`DebuginfodRemoteFile`].
+    pub fn new(source: Arc<DebuginfodSourceConfig>, location: SourceLocation) -> Self {
+        Self { source, location }
+    }
+
+    /// Creates the [`DebuginfodRemoteFile`] for the given object and file type.
+    ///
+    /// Returns `None` if the file type is not supported by debuginfod, or if the source is not
+    /// allowed to serve it.
+    pub fn for_object(
+        source: Arc<DebuginfodSourceConfig>,
+        filetype: FileType,
+        object_id: &ObjectId,
+    ) -> Option<Self> {
+        if !source.filters.is_allowed(object_id, filetype) {
+            return None;
+        }
+
+        let path = format!("buildid/{}", get_debuginfod_path(filetype, object_id)?);
+        if !source.filters.is_lookup_path_allowed(&path) {
+            return None;
+        }
+        let location = SourceLocation::new(path);
+        Some(Self::new(source, location))
+    }
+
+    /// Returns the URLs at which this file is looked up, in order of priority.
+    pub fn urls(&self) -> impl Iterator<Item = anyhow::Result<Url>> + '_ {
+        self.source
+            .urls
+            .iter()
+            .map(|base| self.location.to_url(base))
+    }
+
+    /// Returns a [`RemoteFileUri`] for the file on the first server.
+    pub fn uri(&self) -> RemoteFileUri {
+        match self.urls().next() {
+            Some(Ok(url)) => url.as_ref().into(),
+            _ => "".into(),
+        }
+    }
+
+    pub(crate) fn host(&self) -> String {
+        self.source
+            .urls
+            .first()
+            .and_then(|url| url.host_str())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolic::common::CodeId;
+
+    use super::*;
+
+    use crate::SourceConfig;
+
+    fn parse(text: &str) -> Arc<DebuginfodSourceConfig> {
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(text).unwrap();
+        match &sources[0] {
+            SourceConfig::Debuginfod(cfg) => cfg.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_debuginfod_config() {
+        let list = parse(
+            r#"
+          - id: debuginfod
+            type: debuginfod
+            urls:
+              - https://debuginfod.fedoraproject.org/
+              - https://debuginfod.ubuntu.com
+            "#,
+        );
+        let string = parse(
+            r#"
+          - id: debuginfod
+            type: debuginfod
+            urls: "https://debuginfod.fedoraproject.org/ https://debuginfod.ubuntu.com"
+            "#,
+        );
+        assert_eq!(list.urls, string.urls);
+        assert_eq!(list.urls.len(), 2);
+    }
+
+    #[test]
+    fn test_debuginfod_remote_file() {
+        let source = parse(
+            r#"
+          - id: debuginfod
+            type: debuginfod
+            urls:
+              - https://debuginfod.fedoraproject.org/
+              - https://debuginfod.ubuntu.com/prefix
+            "#,
+        );
+        let object_id = ObjectId {
+            code_id: Some(CodeId::new(
+                "dfb85de42daffd09640c8fe377d572de3e168920".into(),
+            )),
+            ..Default::default()
+        };
+
+        let file = DebuginfodRemoteFile::for_object(source.clone(), FileType::ElfDebug, &object_id)
+            .unwrap();
+        let urls: Vec<_> = file.urls().map(|url| url.unwrap().to_string()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://debuginfod.fedoraproject.org/buildid/dfb85de42daffd09640c8fe377d572de3e168920/debuginfo",
+                "https://debuginfod.ubuntu.com/prefix/buildid/dfb85de42daffd09640c8fe377d572de3e168920/debuginfo",
+            ]
+        );
+        assert_eq!(file.host(), "debuginfod.fedoraproject.org");
+
+        let file = DebuginfodRemoteFile::for_object(source.clone(), FileType::ElfCode, &object_id)
+            .unwrap();
+        assert!(file.uri().to_string().ends_with("/executable"));
+
+        assert!(DebuginfodRemoteFile::for_object(source, FileType::Pdb, &object_id).is_none());
+    }
+}
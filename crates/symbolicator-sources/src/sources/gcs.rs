@@ -17,6 +17,14 @@ pub struct GcsSourceConfig {
     #[serde(default)]
     pub prefix: String,
 
+    /// A file in the bucket, relative to the `prefix`, which is downloaded to probe the health
+    /// of the source.
+    ///
+    /// Probes detect broken credentials or a wrong `prefix`, which otherwise only show up as
+    /// files which are not found.
+    #[serde(default)]
+    pub canary: Option<String>,
+
     /// Authorization information for this bucket. Needs read access.
     #[serde(flatten)]
     pub source_key: Arc<GcsSourceKey>,
@@ -63,12 +71,84 @@ impl GcsRemoteFile {
     }
 }
 
+/// The ways of obtaining GCS credentials we support.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GcsCredentialsProvider {
+    /// The service account key given in the source.
+    #[default]
+    Static,
+    /// The ambient credentials of the environment Symbolicator runs in.
+    ///
+    /// These are the [Application Default Credentials], which includes the service account of
+    /// GKE Workload Identity as provided by the metadata server.
+    ///
+    /// [Application Default Credentials]: https://cloud.google.com/docs/authentication/application-default-credentials
+    Ambient,
+}
+
 /// GCS authorization information.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct GcsSourceKey {
+    /// How to obtain credentials for this bucket.
+    #[serde(default)]
+    pub credentials_provider: GcsCredentialsProvider,
+
     /// Gcs authorization key.
+    #[serde(default)]
     pub private_key: String,
 
     /// The client email.
+    #[serde(default)]
     pub client_email: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SourceConfig;
+
+    fn parse(text: &str) -> Arc<GcsSourceConfig> {
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(text).unwrap();
+        match &sources[0] {
+            SourceConfig::Gcs(cfg) => cfg.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_gcs_config_static() {
+        let cfg = parse(
+            r#"
+          - id: gcs
+            type: gcs
+            bucket: symbols
+            private_key: the-private-key
+            client_email: someone@example.com
+            "#,
+        );
+        assert_eq!(
+            cfg.source_key.credentials_provider,
+            GcsCredentialsProvider::Static
+        );
+        assert_eq!(cfg.source_key.client_email, "someone@example.com");
+    }
+
+    #[test]
+    fn test_gcs_config_ambient() {
+        let cfg = parse(
+            r#"
+          - id: gcs
+            type: gcs
+            bucket: symbols
+            credentials_provider: ambient
+            "#,
+        );
+        assert_eq!(
+            cfg.source_key.credentials_provider,
+            GcsCredentialsProvider::Ambient
+        );
+        assert!(cfg.source_key.private_key.is_empty());
+    }
+}
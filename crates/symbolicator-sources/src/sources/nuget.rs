@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::paths::get_pdb_symstore_path;
+use crate::{
+    FileType, ObjectId, RemoteFile, RemoteFileUri, SourceFilters, SourceId, SourceLimits,
+    SourceLocation,
+};
+
+/// The symbol server of nuget.org.
+const NUGET_ORG_URL: &str = "https://symbols.nuget.org/download/symbols/";
+
+fn default_url() -> Url {
+    Url::parse(NUGET_ORG_URL).unwrap()
+}
+
+/// Configuration for a NuGet symbol server.
+///
+/// NuGet symbol servers serve the Portable PDB files contained in symbol packages (`.snupkg`),
+/// following the [NuGet symbol server protocol].
+///
+/// [NuGet symbol server protocol]: https://github.com/NuGet/Home/wiki/NuGet-Package-Debugging-&-Symbols-Improvements
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NugetSourceConfig {
+    /// Unique source identifier.
+    pub id: SourceId,
+
+    /// Absolute URL of the symbol server.
+    ///
+    /// Defaults to the symbol server of nuget.org.
+    #[serde(default = "default_url")]
+    pub url: Url,
+
+    /// Additional headers to be sent to the symbol server with every request.
+    ///
+    /// Private feeds usually require an `Authorization` header.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// Influence whether this source will be selected.
+    #[serde(default)]
+    pub filters: SourceFilters,
+
+    /// Whether debug files are shared across scopes.
+    #[serde(default)]
+    pub is_public: bool,
+
+    /// Limits on how the source is used.
+    #[serde(default)]
+    pub limits: SourceLimits,
+
+    /// The priority of this source, see [`CommonSourceConfig::priority`].
+    ///
+    /// [`CommonSourceConfig::priority`]: crate::CommonSourceConfig::priority
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// The NuGet-specific [`RemoteFile`].
+#[derive(Debug, Clone)]
+pub struct NugetRemoteFile {
+    /// The underlying [`NugetSourceConfig`].
+    pub source: Arc<NugetSourceConfig>,
+    pub(crate) location: SourceLocation,
+    /// The checksum of the requested file, which is sent as `SymbolChecksum` header.
+    pub symbol_checksum: String,
+}
+
+impl From<NugetRemoteFile> for RemoteFile {
+    fn from(source: NugetRemoteFile) -> Self {
+        Self::Nuget(source)
+    }
+}
+
+impl NugetRemoteFile {
+    /// Creates a new [`NugetRemoteFile`].
+    pub fn new(
+        source: Arc<NugetSourceConfig>,
+        location: SourceLocation,
+        symbol_checksum: String,
+    ) -> Self {
+        Self {
+            source,
+            location,
+            symbol_checksum,
+        }
+    }
+
+    /// Creates the [`NugetRemoteFile`] for the given object and file type.
+    ///
+    /// NuGet symbol servers only serve Portable PDB files, and refuse to serve them unless the
+    /// client proves knowledge of the file by sending its checksum. This returns `None` for all
+    /// other file types, objects without a [`debug_checksum`](ObjectId::debug_checksum), and if
+    /// the source is not allowed to serve the file.
+    pub fn for_object(
+        source: Arc<NugetSourceConfig>,
+        filetype: FileType,
+        object_id: &ObjectId,
+    ) -> Option<Self> {
+        if filetype != FileType::PortablePdb || !source.filters.is_allowed(object_id, filetype) {
+            return None;
+        }
+
+        let symbol_checksum = object_id.debug_checksum.clone()?;
+        // The key follows the SSQP conventions for Portable PDB signatures, that is
+        // `<file>/<guid>FFFFFFFF/<file>`.
+        let path = get_pdb_symstore_path(object_id, true)?;
+        if !source.filters.is_lookup_path_allowed(&path) {
+            return None;
+        }
+        Some(Self::new(
+            source,
+            SourceLocation::new(path),
+            symbol_checksum,
+        ))
+    }
+
+    /// Returns the URL from which to download this object file.
+    pub fn url(&self) -> anyhow::Result<Url> {
+        self.location.to_url(&self.source.url)
+    }
+
+    /// Returns a [`RemoteFileUri`] for the file.
+    pub fn uri(&self) -> RemoteFileUri {
+        match self.url() {
+            Ok(url) => url.as_ref().into(),
+            Err(_) => "".into(),
+        }
+    }
+
+    pub(crate) fn host(&self) -> String {
+        self.source.url.host_str().unwrap_or_default().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use symbolic::common::DebugId;
+
+    use super::*;
+
+    use crate::{ObjectType, SourceConfig};
+
+    fn parse(text: &str) -> Arc<NugetSourceConfig> {
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(text).unwrap();
+        match &sources[0] {
+            SourceConfig::Nuget(cfg) => cfg.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn object_id(debug_checksum: Option<&str>) -> ObjectId {
+        ObjectId {
+            debug_id: Some(DebugId::from_guid_age(&[0xab; 16], 1).unwrap()),
+            debug_file: Some("C:\\projects\\Example.Library.pdb".into()),
+            debug_checksum: debug_checksum.map(Into::into),
+            object_type: ObjectType::PeDotnet,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_nuget_default_url() {
+        let source = parse(
+            r#"
+          - id: nuget
+            type: nuget
+            "#,
+        );
+        assert_eq!(source.url.as_str(), NUGET_ORG_URL);
+    }
+
+    #[test]
+    fn test_nuget_remote_file() {
+        let source = parse(
+            r#"
+          - id: nuget
+            type: nuget
+            url: https://pkgs.dev.azure.com/org/_packaging/feed/nuget/v3/symbols
+            headers:
+              Authorization: Basic dG9rZW4=
+            "#,
+        );
+        let checksum = "SHA256:87a82c4e5e82f386968f25b41ae1b5f3cc3f6d9e79cfb4464f8240400fc47dcd";
+
+        let file = NugetRemoteFile::for_object(
+            source.clone(),
+            FileType::PortablePdb,
+            &object_id(Some(checksum)),
+        )
+        .unwrap();
+        assert_eq!(file.symbol_checksum, checksum);
+        assert_eq!(
+            file.uri(),
+            RemoteFileUri::new(
+                "https://pkgs.dev.azure.com/org/_packaging/feed/nuget/v3/symbols/example.library.pdb/ababababababababababababababababFFFFFFFF/example.library.pdb"
+            )
+        );
+        assert_eq!(file.host(), "pkgs.dev.azure.com");
+
+        // Files are not served without a checksum.
+        assert!(NugetRemoteFile::for_object(
+            source.clone(),
+            FileType::PortablePdb,
+            &object_id(None)
+        )
+        .is_none());
+        assert!(
+            NugetRemoteFile::for_object(source, FileType::Pdb, &object_id(Some(checksum)))
+                .is_none()
+        );
+    }
+}
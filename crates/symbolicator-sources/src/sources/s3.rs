@@ -20,6 +20,21 @@ pub struct S3SourceConfig {
     #[serde(default)]
     pub prefix: String,
 
+    /// Whether the bucket is a requester-pays bucket.
+    ///
+    /// If enabled, requests acknowledge that the requester is charged for the download, which
+    /// such buckets require. Otherwise, they respond with `403 Forbidden`.
+    #[serde(default)]
+    pub requester_pays: bool,
+
+    /// A file in the bucket, relative to the `prefix`, which is downloaded to probe the health
+    /// of the source.
+    ///
+    /// Probes detect broken credentials or a wrong `prefix`, which otherwise only show up as
+    /// files which are not found.
+    #[serde(default)]
+    pub canary: Option<String>,
+
     /// Authorization information for this bucket. Needs read access.
     #[serde(flatten)]
     pub source_key: Arc<S3SourceKey>,
@@ -130,10 +145,13 @@ where
 
 /// The types of Amazon IAM credentials providers we support.
 ///
+/// Except for static credentials, the credentials are temporary and are refreshed automatically
+/// before they expire.
+///
 /// For details on the AWS side, see:
 /// <https://docs.aws.amazon.com/AmazonECS/latest/developerguide/task-iam-roles.html>.
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 #[derive(Default)]
 pub enum AwsCredentialsProvider {
     /// Static Credentials
@@ -141,6 +159,15 @@ pub enum AwsCredentialsProvider {
     Static,
     /// Credentials derived from the container.
     Container,
+    /// Credentials obtained by assuming a role with a web identity token.
+    ///
+    /// This is used for IAM Roles for Service Accounts (IRSA) on EKS. The role and token file
+    /// are read from the `AWS_ROLE_ARN` and `AWS_WEB_IDENTITY_TOKEN_FILE` environment variables.
+    /// See <https://docs.aws.amazon.com/eks/latest/userguide/iam-roles-for-service-accounts.html>.
+    WebIdentity,
+    /// Credentials of the role attached to the EC2 instance, obtained from the instance metadata
+    /// service using IMDSv2.
+    Instance,
 }
 
 /// Amazon S3 authorization information.
@@ -187,7 +214,8 @@ impl From<&str> for S3Region {
 
 impl PartialEq for S3SourceKey {
     fn eq(&self, other: &S3SourceKey) -> bool {
-        self.access_key == other.access_key
+        self.aws_credentials_provider == other.aws_credentials_provider
+            && self.access_key == other.access_key
             && self.secret_key == other.secret_key
             && self.region == other.region
     }
@@ -197,6 +225,7 @@ impl Eq for S3SourceKey {}
 
 impl std::hash::Hash for S3SourceKey {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.aws_credentials_provider.hash(state);
         self.access_key.hash(state);
         self.secret_key.hash(state);
         self.region.hash(state);
@@ -230,6 +259,7 @@ mod tests {
                 assert_eq!(cfg.source_key.region, S3Region::from("us-east-1"));
                 assert_eq!(cfg.source_key.access_key, "the-access-key");
                 assert_eq!(cfg.source_key.secret_key, "the-secret-key");
+                assert!(!cfg.requester_pays);
             }
             _ => unreachable!(),
         }
@@ -265,6 +295,69 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_s3_config_credentials_providers() {
+        let text = r#"
+          - id: irsa
+            type: s3
+            bucket: symbols
+            region: us-east-1
+            aws_credentials_provider: web_identity
+          - id: instance
+            type: s3
+            bucket: symbols
+            region: us-east-1
+            aws_credentials_provider: instance
+          - id: container
+            type: s3
+            bucket: symbols
+            region: us-east-1
+            aws_credentials_provider: container
+                  "#;
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(text).unwrap();
+        let providers: Vec<_> = sources
+            .iter()
+            .map(|source| match source {
+                SourceConfig::S3(cfg) => cfg.source_key.aws_credentials_provider,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            providers,
+            [
+                AwsCredentialsProvider::WebIdentity,
+                AwsCredentialsProvider::Instance,
+                AwsCredentialsProvider::Container,
+            ]
+        );
+
+        // Sources using different providers must not share clients.
+        match (&sources[0], &sources[1]) {
+            (SourceConfig::S3(irsa), SourceConfig::S3(instance)) => {
+                assert_ne!(irsa.source_key, instance.source_key)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_s3_config_requester_pays() {
+        let text = r#"
+          - id: requester-pays
+            type: s3
+            bucket: public-symbols
+            region: us-east-1
+            requester_pays: true
+            access_key: the-access-key
+            secret_key: the-secret-key
+                  "#;
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(text).unwrap();
+        match &sources[0] {
+            SourceConfig::S3(cfg) => assert!(cfg.requester_pays),
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn test_s3_config_plain_empty_region() {
         let text = r#"
@@ -17,6 +17,43 @@ pub struct SentrySourceConfig {
 
     /// Bearer authorization token.
     pub token: String,
+
+    /// Further organizations or projects whose debug files are looked up along with the ones
+    /// at [`url`](Self::url).
+    ///
+    /// The candidates of all endpoints are merged. Debug files are identified by their ID, so
+    /// all endpoints need to be part of the same Sentry installation.
+    #[serde(default)]
+    pub organizations: Vec<SentryOrganization>,
+}
+
+impl SentrySourceConfig {
+    /// Returns the configurations of all endpoints of this source.
+    ///
+    /// The first one is this source itself, followed by one for each of its
+    /// [`organizations`](Self::organizations). They all share the [`id`](Self::id) of this
+    /// source.
+    pub fn endpoints(self: &Arc<Self>) -> Vec<Arc<Self>> {
+        let organizations = self.organizations.iter().map(|organization| {
+            Arc::new(Self {
+                id: self.id.clone(),
+                url: organization.url.clone(),
+                token: organization.token.clone(),
+                organizations: Vec::new(),
+            })
+        });
+        std::iter::once(self.clone()).chain(organizations).collect()
+    }
+}
+
+/// A further endpoint of a [`SentrySourceConfig`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SentryOrganization {
+    /// Absolute URL of the endpoint.
+    pub url: Url,
+
+    /// Bearer authorization token.
+    pub token: String,
 }
 
 /// The Sentry-specific [`RemoteFile`].
@@ -95,3 +132,37 @@ impl fmt::Display for SentryFileId {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SourceConfig;
+
+    #[test]
+    fn test_sentry_endpoints() {
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(
+            r#"
+          - id: sentry:project
+            type: sentry
+            url: https://sentry.example.com/api/0/projects/org-a/project/files/dsyms/
+            token: token-a
+            organizations:
+              - url: https://sentry.example.com/api/0/projects/org-b/project/files/dsyms/
+                token: token-b
+            "#,
+        )
+        .unwrap();
+        let SourceConfig::Sentry(source) = &sources[0] else {
+            panic!("unexpected source");
+        };
+
+        let endpoints = source.endpoints();
+        assert_eq!(endpoints.len(), 2);
+        assert!(Arc::ptr_eq(&endpoints[0], source));
+        assert_eq!(endpoints[1].id, source.id);
+        assert_eq!(endpoints[1].token, "token-b");
+        assert!(endpoints[1].url.path().contains("/org-b/"));
+        assert!(endpoints[1].organizations.is_empty());
+    }
+}
@@ -170,6 +170,7 @@ pub async fn process_payload(
                     temp_path,
                     Arc::clone(sources),
                     Default::default(),
+                    Default::default(),
                 )
                 .await
                 .unwrap();
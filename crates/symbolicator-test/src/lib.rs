@@ -16,16 +16,20 @@
 //!    source) = symbol_server();`. Alternatively, use [`local_source`] to test without
 //!    HTTP connections.
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
 use std::net::{SocketAddr, TcpListener};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Redirect};
-use axum::routing::{get, get_service};
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect};
+use axum::routing::{get, get_service, post};
 use axum::{extract, Json};
 use axum::{middleware, Router};
+use futures::StreamExt;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use tokio::sync::OnceCell;
@@ -120,6 +124,12 @@ pub fn microsoft_symsrv() -> SourceConfig {
             .parse()
             .unwrap(),
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files: CommonSourceConfig {
             filters: SourceFilters {
                 filetypes: vec![FileType::Pe, FileType::Pdb],
@@ -146,6 +156,92 @@ pub fn source_config(ty: DirectoryLayoutType, filetypes: Vec<FileType>) -> Commo
     }
 }
 
+/// Checks whether the request carries the given `Authorization` header.
+fn has_authorization(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| value == expected)
+}
+
+/// Serves a fixture symbol, breaking off the connection unless a `Range` is requested.
+fn interrupted_download(headers: &HeaderMap, path: &str) -> axum::response::Response {
+    let Ok(content) = std::fs::read(fixture("symbols").join(path)) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let etag = (header::ETAG, "\"interrupted\"".to_owned());
+
+    let offset = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes="))
+        .and_then(|range| range.strip_suffix('-'))
+        .and_then(|offset| offset.parse::<usize>().ok());
+
+    match offset {
+        Some(offset) if offset < content.len() => {
+            let range = format!("bytes {offset}-{}/{}", content.len() - 1, content.len());
+            let headers = [etag, (header::CONTENT_RANGE, range)];
+            (
+                StatusCode::PARTIAL_CONTENT,
+                headers,
+                content[offset..].to_vec(),
+            )
+                .into_response()
+        }
+        _ => {
+            let half = Bytes::copy_from_slice(&content[..content.len() / 2]);
+            // The connection is only broken off after the first half has been sent.
+            let interrupted = async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Err(io::Error::other("connection interrupted"))
+            };
+            let chunks =
+                futures::stream::iter([Ok(half)]).chain(futures::stream::once(interrupted));
+            let headers = [etag, (header::CONTENT_LENGTH, content.len().to_string())];
+            (headers, Body::from_stream(chunks)).into_response()
+        }
+    }
+}
+
+/// Serves the fixtures symbols like an Artifactory repository.
+///
+/// Requests need the API key `test-key`, and missing files get an HTML page with `200 OK`.
+fn artifactory_download(headers: &HeaderMap, path: &str) -> axum::response::Response {
+    if headers
+        .get("x-jfrog-art-api")
+        .is_none_or(|key| key != "test-key")
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match std::fs::read(fixture("symbols").join(path)) {
+        Ok(content) => content.into_response(),
+        Err(_) => Html("<html><body>Not Found</body></html>").into_response(),
+    }
+}
+
+/// Responds with `429 Too Many Requests` to the first request for every combination of
+/// `retry_after` and `path`, and serves the fixtures symbols afterwards.
+fn rate_limited_download(
+    limited: &Mutex<BTreeSet<String>>,
+    retry_after: &str,
+    path: &str,
+) -> axum::response::Response {
+    if limited
+        .lock()
+        .unwrap()
+        .insert(format!("{retry_after}/{path}"))
+    {
+        let headers = [(header::RETRY_AFTER, retry_after.to_owned())];
+        return (StatusCode::TOO_MANY_REQUESTS, headers).into_response();
+    }
+
+    match std::fs::read(fixture("symbols").join(path)) {
+        Ok(content) => content.into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 /// A test server that binds to a random port and serves a web app.
 ///
 /// The server counts all the requests that happen, to be accessed via `accesses` or `all_hits`.
@@ -157,7 +253,21 @@ pub fn source_config(ty: DirectoryLayoutType, filetypes: Vec<FileType>) -> Commo
 /// - `/msdl/` will redirect to the public microsoft symbol server.
 /// - `/respond_statuscode/$num` responds with the status code given in `$num`.
 /// - `/garbage_data/$data` responds back with `$data`.
+/// - `/oauth2/token` issues the OAuth2 access token `test-token` to the client `client` with the
+///   secret `secret`.
+/// - `/oauth2/protected/$data` responds back with `$data` if the request carries the
+///   `test-token` bearer token, and with `401` otherwise.
 /// - `/symbols/` serves the fixtures symbols.
+/// - `/interrupted/$path` serves the fixtures symbols as well, but breaks off the connection
+///   halfway through the file. Only `Range` requests are served completely.
+/// - `/rate_limited/$retry_after/$path` responds with `429` and a `Retry-After: $retry_after`
+///   header to the first request for `$retry_after/$path`, and serves the fixtures symbols
+///   afterwards.
+/// - `/artifactory/symbols/$path` serves the fixtures symbols if the request carries the
+///   `X-JFrog-Art-Api: test-key` header, and responds with `401` otherwise. Like Artifactory,
+///   it responds to missing files with an HTML page and `200`.
+/// - `/encoded/$path` serves the fixtures symbols with a `Content-Encoding`, if there is a
+///   `$path.gz` or `$path.zst` fixture matching the `Accept-Encoding` of the request.
 ///
 /// This server requires a `tokio` runtime and is supposed to be run in a `tokio::test`. It
 /// automatically stops serving when dropped.
@@ -217,6 +327,12 @@ impl Server {
     /// Creates a new [`Router`] with the configuration as described in the main [`Server`] docs.
     pub fn test_router() -> Router {
         let serve_dir = get_service(ServeDir::new(fixture("symbols")));
+        let serve_encoded = get_service(
+            ServeDir::new(fixture("symbols"))
+                .precompressed_gzip()
+                .precompressed_zstd(),
+        );
+        let rate_limited = Arc::new(Mutex::new(BTreeSet::new()));
 
         Router::new()
             .route(
@@ -255,7 +371,58 @@ impl Server {
                 "/garbage_data/*tail",
                 get(|extract::Path(tail): extract::Path<String>| async move { tail }),
             )
+            .route(
+                "/oauth2/token",
+                post(|headers: HeaderMap| async move {
+                    // Basic authentication with `client:secret`.
+                    if !has_authorization(&headers, "Basic Y2xpZW50OnNlY3JldA==") {
+                        return StatusCode::UNAUTHORIZED.into_response();
+                    }
+                    Json(serde_json::json!({
+                        "access_token": "test-token",
+                        "token_type": "Bearer",
+                        "expires_in": 3600,
+                    }))
+                    .into_response()
+                }),
+            )
+            .route(
+                "/oauth2/protected/*tail",
+                get(
+                    |headers: HeaderMap, extract::Path(tail): extract::Path<String>| async move {
+                        if !has_authorization(&headers, "Bearer test-token") {
+                            return StatusCode::UNAUTHORIZED.into_response();
+                        }
+                        tail.into_response()
+                    },
+                ),
+            )
+            .route(
+                "/interrupted/*path",
+                get(
+                    |headers: HeaderMap, extract::Path(path): extract::Path<String>| async move {
+                        interrupted_download(&headers, &path)
+                    },
+                ),
+            )
+            .route(
+                "/rate_limited/:retry_after/*path",
+                get(
+                    move |extract::Path((retry_after, path)): extract::Path<(String, String)>| async move {
+                        rate_limited_download(&rate_limited, &retry_after, &path)
+                    },
+                ),
+            )
+            .route(
+                "/artifactory/symbols/*path",
+                get(
+                    |headers: HeaderMap, extract::Path(path): extract::Path<String>| async move {
+                        artifactory_download(&headers, &path)
+                    },
+                ),
+            )
             .nest_service("/symbols", serve_dir)
+            .nest_service("/encoded", serve_encoded)
     }
 
     /// Returns the sum total of hits and clears the hit counts.
@@ -285,10 +452,13 @@ impl Server {
         let files = CommonSourceConfig {
             filters: SourceFilters {
                 filetypes: vec![FileType::MachCode],
-                path_patterns: vec![],
+                ..Default::default()
             },
             layout: Default::default(),
             is_public: false,
+            limits: Default::default(),
+            priority: 0,
+            loose_matching: false,
         };
         self.source_with_config(id, path, files)
     }
@@ -305,6 +475,12 @@ impl Server {
             id: SourceId::new(id),
             url: self.url(path),
             headers: Default::default(),
+            oauth2: None,
+            tls: None,
+            proxy: None,
+            redirects: None,
+            artifactory: None,
+            unix_socket: None,
             files,
             accept_invalid_certs: false,
         }))
@@ -341,6 +517,12 @@ pub fn symbol_server() -> (Server, SourceConfig) {
         id: SourceId::new("local"),
         url: server.url("symbols/"),
         headers: Default::default(),
+        oauth2: None,
+        tls: None,
+        proxy: None,
+        redirects: None,
+        artifactory: None,
+        unix_socket: None,
         files: Default::default(),
         accept_invalid_certs: false,
     }));
@@ -390,6 +572,7 @@ where
         id: SourceId::new("sentry:project"),
         url: server.url("/lookup"),
         token: String::new(),
+        organizations: Vec::new(),
     };
 
     (server, source)
@@ -431,6 +614,7 @@ where
         id: SourceId::new("sentry:project"),
         url: server.url("/files/dsyms/"),
         token: String::new(),
+        organizations: Vec::new(),
     };
 
     (server, source)
@@ -447,6 +631,7 @@ pub fn gcs_source_key_from_env() -> Option<GcsSourceKey> {
         None
     } else {
         Some(GcsSourceKey {
+            credentials_provider: Default::default(),
             private_key,
             client_email,
         })
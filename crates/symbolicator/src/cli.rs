@@ -1,16 +1,21 @@
 //! Exposes the command line application.
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
-use clap::{arg, Parser};
+use clap::{Parser, Subcommand};
 
 use symbolicator_service::caching;
 use symbolicator_service::metrics;
 
 use crate::config::Config;
+use crate::inspect;
 use crate::logging;
+use crate::object::{self, ObjectArgs};
+use crate::prefetch;
 use crate::server;
+use crate::service::Scope;
 
 fn get_crate_version() -> &'static str {
     env!("CARGO_PKG_VERSION")
@@ -40,6 +45,141 @@ enum Command {
         #[arg(long)]
         dry_run: bool,
     },
+
+    /// Prefetch the symbol and unwind caches of modules from the configured sources.
+    #[command(name = "prefetch")]
+    Prefetch {
+        /// A JSON file with the list of modules, like the `modules` of a symbolication request.
+        #[arg(value_name = "FILE")]
+        modules: PathBuf,
+
+        /// The scope of the cached files, defaults to `global`.
+        #[arg(long)]
+        scope: Option<String>,
+    },
+
+    /// Fetch a debug file from the configured sources and print its features.
+    #[command(name = "object")]
+    Object {
+        /// The debug id of the file.
+        #[arg(long)]
+        debug_id: Option<String>,
+
+        /// The code id of the file.
+        #[arg(long)]
+        code_id: Option<String>,
+
+        /// The name of the debug file, like `crash.pdb`.
+        #[arg(long)]
+        debug_file: Option<String>,
+
+        /// The name of the code file, like `crash.exe`.
+        #[arg(long)]
+        code_file: Option<String>,
+
+        /// The scope of the cached files, defaults to `global`.
+        #[arg(long)]
+        scope: Option<String>,
+    },
+
+    /// Fetch a debug file from the configured sources and print its Breakpad symbols.
+    #[command(name = "breakpad")]
+    Breakpad {
+        /// The debug id of the file.
+        #[arg(long)]
+        debug_id: Option<String>,
+
+        /// The code id of the file.
+        #[arg(long)]
+        code_id: Option<String>,
+
+        /// The name of the debug file, like `crash.pdb`.
+        #[arg(long)]
+        debug_file: Option<String>,
+
+        /// The name of the code file, like `crash.exe`.
+        #[arg(long)]
+        code_file: Option<String>,
+
+        /// The scope of the cached files, defaults to `global`.
+        #[arg(long)]
+        scope: Option<String>,
+    },
+
+    /// Inspect the files of local caches.
+    #[command(name = "cache", subcommand)]
+    Cache(CacheCommand),
+}
+
+/// Commands to inspect local caches.
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// List the files of the caches, with their size, age, and stored errors.
+    #[command(name = "list")]
+    List {
+        /// Only list the files of this cache, like `symcaches`.
+        #[arg(long)]
+        cache: Option<String>,
+
+        /// Only list files storing an error.
+        #[arg(long)]
+        errors: bool,
+    },
+
+    /// Show the metadata and decoded header of a cache file.
+    #[command(name = "show")]
+    Show {
+        /// The cache of the file, like `symcaches`.
+        cache: String,
+
+        /// The path of the file, as listed by `cache list`.
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+    },
+
+    /// Move the existing files of deduplicated caches into their content store.
+    #[command(name = "dedup")]
+    Dedup {
+        /// Only migrate the files of this cache, like `objects`.
+        #[arg(long)]
+        cache: Option<String>,
+    },
+
+    /// Upgrade the files of fallback cache versions to the current versions, where possible.
+    #[command(name = "migrate")]
+    Migrate {
+        /// Only upgrade the files of this cache, like `symcaches`.
+        #[arg(long)]
+        cache: Option<String>,
+    },
+
+    /// Export the good files of the caches into a bundle, to import them elsewhere.
+    #[command(name = "export")]
+    Export {
+        /// Only export the files of this cache, like `symcaches`.
+        #[arg(long)]
+        cache: Option<String>,
+
+        /// A file with one debug id per line, to only export objects and symcaches of these.
+        #[arg(long, value_name = "FILE")]
+        debug_ids: Option<PathBuf>,
+
+        /// Only export files used within this duration, like `7d`.
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_age: Option<Duration>,
+
+        /// The path of the bundle to write.
+        #[arg(value_name = "BUNDLE")]
+        output: PathBuf,
+    },
+
+    /// Import the files of a bundle into the caches, keeping existing files.
+    #[command(name = "import")]
+    Import {
+        /// The path of the bundle, as written by `cache export`.
+        #[arg(value_name = "BUNDLE")]
+        bundle: PathBuf,
+    },
 }
 
 /// Command line interface parser.
@@ -156,10 +296,84 @@ pub fn execute() -> Result<()> {
     }
 
     match cli.command {
-        Command::Run => server::run(config).context("failed to start the server")?,
+        Command::Run => server::run(config, cli.config()).context("failed to start the server")?,
         Command::Cleanup { dry_run } => {
             caching::cleanup(config, dry_run).context("failed to clean up caches")?
         }
+        Command::Prefetch { modules, scope } => {
+            let scope = match scope.as_deref() {
+                None | Some("global") => Scope::Global,
+                Some(scope) => Scope::Scoped(scope.into()),
+            };
+            prefetch::prefetch(config, &modules, scope).context("failed to prefetch caches")?
+        }
+        Command::Object {
+            debug_id,
+            code_id,
+            debug_file,
+            code_file,
+            scope,
+        } => {
+            let scope = match scope.as_deref() {
+                None | Some("global") => Scope::Global,
+                Some(scope) => Scope::Scoped(scope.into()),
+            };
+            let args = ObjectArgs {
+                debug_id,
+                code_id,
+                debug_file,
+                code_file,
+            };
+            object::inspect(config, args, scope).context("failed to inspect debug file")?
+        }
+        Command::Breakpad {
+            debug_id,
+            code_id,
+            debug_file,
+            code_file,
+            scope,
+        } => {
+            let scope = match scope.as_deref() {
+                None | Some("global") => Scope::Global,
+                Some(scope) => Scope::Scoped(scope.into()),
+            };
+            let args = ObjectArgs {
+                debug_id,
+                code_id,
+                debug_file,
+                code_file,
+            };
+            object::dump_breakpad(config, args, scope)
+                .context("failed to generate breakpad symbols")?
+        }
+        Command::Cache(CacheCommand::List { cache, errors }) => {
+            inspect::list(config, cache.as_deref(), errors).context("failed to list caches")?
+        }
+        Command::Cache(CacheCommand::Show { cache, path }) => {
+            inspect::show(config, &cache, &path).context("failed to inspect cache file")?
+        }
+        Command::Cache(CacheCommand::Dedup { cache }) => {
+            inspect::dedup(config, cache.as_deref()).context("failed to deduplicate caches")?
+        }
+        Command::Cache(CacheCommand::Migrate { cache }) => {
+            inspect::migrate(config, cache.as_deref()).context("failed to migrate caches")?
+        }
+        Command::Cache(CacheCommand::Export {
+            cache,
+            debug_ids,
+            max_age,
+            output,
+        }) => inspect::export(
+            config,
+            cache.as_deref(),
+            debug_ids.as_deref(),
+            max_age,
+            &output,
+        )
+        .context("failed to export caches")?,
+        Command::Cache(CacheCommand::Import { bundle }) => {
+            inspect::import(config, &bundle).context("failed to import cache bundle")?
+        }
     }
 
     Ok(())
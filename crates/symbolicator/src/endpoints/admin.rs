@@ -0,0 +1,249 @@
+use axum::extract;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Json;
+use serde::Deserialize;
+
+use symbolicator_native::interface::{PurgeCaches, PurgedCaches};
+use symbolicator_sources::{ObjectId, SourceConfig};
+
+use crate::endpoints::ResponseError;
+use crate::service::{RequestService, Scope};
+
+/// Query parameters of the purge request.
+#[derive(Deserialize)]
+pub struct PurgeRequestQueryParams {
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+/// JSON body of the purge request.
+#[derive(Deserialize)]
+pub struct PurgeRequestBody {
+    #[serde(default)]
+    pub sources: Option<Vec<SourceConfig>>,
+    #[serde(default)]
+    pub debug_id: Option<String>,
+    #[serde(default)]
+    pub code_id: Option<String>,
+    #[serde(default)]
+    pub debug_file: Option<String>,
+    #[serde(default)]
+    pub code_file: Option<String>,
+    #[serde(default)]
+    pub shared_cache: bool,
+}
+
+/// Removes all cached files of a debug file.
+pub async fn purge_caches(
+    extract::State(service): extract::State<RequestService>,
+    headers: HeaderMap,
+    extract::Query(params): extract::Query<PurgeRequestQueryParams>,
+    extract::Json(body): extract::Json<PurgeRequestBody>,
+) -> Result<Json<PurgedCaches>, ResponseError> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("POST /admin/purge"));
+    });
+
+    authorize(service.config().admin_token.as_deref(), &headers)?;
+
+    let identifier = parse_object_id(
+        body.debug_id.as_deref(),
+        body.code_id.as_deref(),
+        body.debug_file,
+        body.code_file,
+    )?;
+
+    let sources = match body.sources {
+        Some(sources) => sources.into(),
+        None => service.default_sources(),
+    };
+
+    let purged = service
+        .purge_caches(PurgeCaches {
+            scope: params.scope,
+            sources,
+            identifier,
+            shared_cache: body.shared_cache,
+        })
+        .await;
+    Ok(Json(purged))
+}
+
+/// Parses the identifiers of a debug file, of which a `debug_id` or `code_id` is required.
+pub(super) fn parse_object_id(
+    debug_id: Option<&str>,
+    code_id: Option<&str>,
+    debug_file: Option<String>,
+    code_file: Option<String>,
+) -> Result<ObjectId, ResponseError> {
+    let identifier = ObjectId {
+        debug_id: match debug_id {
+            None | Some("") => None,
+            Some(debug_id) => Some(
+                debug_id
+                    .parse()
+                    .map_err(|_| (StatusCode::BAD_REQUEST, "invalid `debug_id`"))?,
+            ),
+        },
+        code_id: match code_id {
+            None | Some("") => None,
+            Some(code_id) => Some(
+                code_id
+                    .parse()
+                    .map_err(|_| (StatusCode::BAD_REQUEST, "invalid `code_id`"))?,
+            ),
+        },
+        debug_file,
+        code_file,
+        ..Default::default()
+    };
+    if identifier.debug_id.is_none() && identifier.code_id.is_none() {
+        let message = "either a `debug_id` or a `code_id` is required";
+        return Err((StatusCode::BAD_REQUEST, message).into());
+    }
+    Ok(identifier)
+}
+
+/// Checks the bearer token of an admin request.
+///
+/// Without a configured `admin_token`, the admin endpoints do not exist.
+fn authorize(admin_token: Option<&str>, headers: &HeaderMap) -> Result<(), ResponseError> {
+    let Some(admin_token) = admin_token else {
+        return Err((StatusCode::NOT_FOUND, "admin endpoints are disabled").into());
+    };
+
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "invalid admin token").into()),
+    }
+}
+
+/// Compares two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, StatusCode};
+    use serde_json::Value;
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_purge_unauthorized() {
+        test::setup();
+
+        let client = Client::new();
+        let payload = serde_json::json!({
+            "debug_id": "ff9f9f78-41db-88f0-cded-a9e1e9bff3b5-1",
+        });
+
+        // The endpoint is disabled without a token.
+        let server = test::server_with_default_service();
+        let response = client
+            .post(server.url("/admin/purge"))
+            .bearer_auth("secret")
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let server = test::server_with_config(|config| {
+            config.admin_token = Some("secret".into());
+        });
+        let response = client
+            .post(server.url("/admin/purge"))
+            .bearer_auth("wrong")
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_purge_caches() {
+        test::setup();
+
+        let cache_dir = test::tempdir();
+        let server = test::server_with_config(|config| {
+            config.cache_dir = Some(cache_dir.path().to_owned());
+            config.admin_token = Some("secret".into());
+        });
+        let client = Client::new();
+
+        // Populate the caches with a symbolication request.
+        let payload = serde_json::json!({
+            "stacktraces": [{
+                "registers": {"eip": "0x0000000001509530"},
+                "frames": [{"instruction_addr": "0x749d37f2"}],
+            }],
+            "modules": [{
+                "type": "pe",
+                "debug_id": "3249d99d-0c40-4931-8610-f4e4fb0b6936-1",
+                "code_file": "C:\\Windows\\System32\\kernel32.dll",
+                "debug_file": "C:\\Windows\\System32\\crash.pdb",
+                "image_addr": "0x749d0000",
+                "image_size": 851968,
+            }],
+            "sources": [test::local_source()],
+        });
+        let response = client
+            .post(server.url("/symbolicate"))
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let payload = serde_json::json!({
+            "sources": [test::local_source()],
+            "debug_id": "3249d99d-0c40-4931-8610-f4e4fb0b6936-1",
+            "debug_file": "C:\\Windows\\System32\\crash.pdb",
+        });
+        let purge = || {
+            client
+                .post(server.url("/admin/purge"))
+                .bearer_auth("secret")
+                .json(&payload)
+                .send()
+        };
+
+        let response = purge().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let purged: Value = response.json().await.unwrap();
+        // Besides the PDB, this purges the negative entries of the other candidates.
+        assert_eq!(purged["objects"], 4);
+        assert_eq!(purged["object_meta"], 4);
+        assert_eq!(purged["symcaches"], 1);
+
+        // Everything has been purged already.
+        let purged: Value = purge().await.unwrap().json().await.unwrap();
+        assert_eq!(purged["objects"], 0);
+        assert_eq!(purged["object_meta"], 0);
+        assert_eq!(purged["symcaches"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_purge_requires_identifier() {
+        test::setup();
+
+        let server = test::server_with_config(|config| {
+            config.admin_token = Some("secret".into());
+        });
+        let response = Client::new()
+            .post(server.url("/admin/purge"))
+            .bearer_auth("secret")
+            .json(&serde_json::json!({ "debug_file": "crash.pdb" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
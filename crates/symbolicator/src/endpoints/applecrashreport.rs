@@ -20,7 +20,7 @@ pub async fn handle_apple_crash_report_request(
     params.configure_scope();
 
     let mut report = None;
-    let mut sources = service.config().default_sources();
+    let mut sources = service.default_sources();
     let mut scraping = Default::default();
     let mut options = RequestOptions::default();
     let mut platform = None;
@@ -103,6 +103,32 @@ mod tests {
         test::assert_snapshot!(response);
     }
 
+    #[tokio::test]
+    async fn test_ips() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let file_contents = test::read_fixture("apple_crash_report.ips");
+        let file_part = multipart::Part::bytes(file_contents).file_name("apple_crash_report.ips");
+
+        let form = multipart::Form::new()
+            .part("apple_crash_report", file_part)
+            .text("sources", "[]");
+
+        let response = Client::new()
+            .post(server.url("/applecrashreport"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.text().await.unwrap();
+        assert!(body.contains(r#""crash_reason":"EXC_BAD_ACCESS (SIGSEGV)""#));
+    }
+
     #[tokio::test]
     async fn test_unknown_field() {
         test::setup();
@@ -0,0 +1,112 @@
+use anyhow::Context;
+use axum::body::Body;
+use axum::extract;
+use axum::http::{Response, StatusCode};
+
+use symbolicator_native::interface::DumpBreakpadSymbols;
+use symbolicator_service::caching::CacheError;
+
+use crate::endpoints::ResponseError;
+use crate::service::RequestService;
+
+use super::admin::parse_object_id;
+use super::inspect::{InspectRequestBody, InspectRequestQueryParams};
+
+/// Fetches a debug file and responds with the Breakpad symbols generated from it.
+pub async fn breakpad_symbols(
+    extract::State(service): extract::State<RequestService>,
+    extract::Query(params): extract::Query<InspectRequestQueryParams>,
+    extract::Json(body): extract::Json<InspectRequestBody>,
+) -> Result<Response<Body>, ResponseError> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("POST /breakpad"));
+    });
+
+    let identifier = parse_object_id(
+        body.debug_id.as_deref(),
+        body.code_id.as_deref(),
+        body.debug_file,
+        body.code_file,
+    )?;
+
+    let sources = match body.sources {
+        Some(sources) => sources.into(),
+        None => service.default_sources(),
+    };
+
+    let symbols = service
+        .breakpad_symbols(DumpBreakpadSymbols {
+            scope: params.scope,
+            sources,
+            identifier,
+        })
+        .await;
+    let symbols = match symbols {
+        Ok(symbols) => symbols,
+        Err(CacheError::NotFound) => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())?)
+        }
+        Err(e) => {
+            return Err(e)
+                .context("failed to generate breakpad symbols")
+                .map_err(|e| e.into())
+        }
+    };
+
+    Ok(Response::builder()
+        .header("content-length", symbols.len())
+        .header("content-type", "text/plain; charset=utf-8")
+        .body(Body::from(symbols.to_vec()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, StatusCode};
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_breakpad_symbols() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+        let response = Client::new()
+            .post(server.url("/breakpad"))
+            .json(&serde_json::json!({
+                "sources": [test::local_source()],
+                "debug_id": "3249d99d-0c40-4931-8610-f4e4fb0b6936-1",
+                "debug_file": "C:\\projects\\breakpad-tools\\windows\\Release\\crash.pdb",
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let symbols = response.text().await.unwrap();
+        let module = symbols.lines().next().unwrap();
+        assert_eq!(
+            module,
+            "MODULE windows x86 3249D99D0C4049318610F4E4FB0B69361 crash.pdb"
+        );
+        assert!(symbols.lines().any(|line| line.starts_with("FUNC ")));
+    }
+
+    #[tokio::test]
+    async fn test_breakpad_symbols_missing() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+        let response = Client::new()
+            .post(server.url("/breakpad"))
+            .json(&serde_json::json!({
+                "sources": [],
+                "debug_id": "ff9f9f78-41db-88f0-cded-a9e1e9bff3b5-1",
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,51 @@
+use axum::extract;
+use axum::http::StatusCode;
+use axum::response::Json;
+
+use crate::service::{RequestService, SourceHealth};
+
+/// Probes the default sources which have a `canary` file.
+///
+/// Responds with `503 Service Unavailable` if any of the probes fails.
+pub async fn probe_sources(
+    extract::State(service): extract::State<RequestService>,
+) -> (StatusCode, Json<Vec<SourceHealth>>) {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("GET /sources/health"));
+    });
+
+    let health = service.probe_sources().await;
+    let status = if health.iter().all(|source| source.healthy) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(health))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_probe_sources() {
+        test::setup();
+
+        // Sources without a canary file are not probed.
+        let server = test::server_with_config(|config| {
+            config.sources = vec![test::local_source()].into();
+        });
+
+        let response = Client::new()
+            .get(server.url("/sources/health"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let health: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(health, serde_json::json!([]));
+    }
+}
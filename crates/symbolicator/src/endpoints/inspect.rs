@@ -0,0 +1,123 @@
+use axum::extract;
+use axum::response::Json;
+use serde::Deserialize;
+
+use symbolicator_native::interface::{InspectObject, InspectedObject};
+use symbolicator_sources::SourceConfig;
+
+use crate::endpoints::ResponseError;
+use crate::service::{RequestService, Scope};
+
+use super::admin::parse_object_id;
+
+/// Query parameters of the inspect request.
+#[derive(Deserialize)]
+pub struct InspectRequestQueryParams {
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+/// JSON body of the inspect request.
+#[derive(Deserialize)]
+pub struct InspectRequestBody {
+    #[serde(default)]
+    pub sources: Option<Vec<SourceConfig>>,
+    #[serde(default)]
+    pub debug_id: Option<String>,
+    #[serde(default)]
+    pub code_id: Option<String>,
+    #[serde(default)]
+    pub debug_file: Option<String>,
+    #[serde(default)]
+    pub code_file: Option<String>,
+}
+
+/// Fetches a debug file and reports its features.
+pub async fn inspect_object(
+    extract::State(service): extract::State<RequestService>,
+    extract::Query(params): extract::Query<InspectRequestQueryParams>,
+    extract::Json(body): extract::Json<InspectRequestBody>,
+) -> Result<Json<InspectedObject>, ResponseError> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("POST /inspect"));
+    });
+
+    let identifier = parse_object_id(
+        body.debug_id.as_deref(),
+        body.code_id.as_deref(),
+        body.debug_file,
+        body.code_file,
+    )?;
+
+    let sources = match body.sources {
+        Some(sources) => sources.into(),
+        None => service.default_sources(),
+    };
+
+    let inspected = service
+        .inspect_object(InspectObject {
+            scope: params.scope,
+            sources,
+            identifier,
+        })
+        .await;
+    Ok(Json(inspected))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, StatusCode};
+    use serde_json::Value;
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_inspect_object() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+        let client = Client::new();
+
+        let payload = serde_json::json!({
+            "sources": [test::local_source()],
+            "debug_id": "3249d99d-0c40-4931-8610-f4e4fb0b6936-1",
+            "debug_file": "C:\\Windows\\System32\\crash.pdb",
+        });
+        let response = client
+            .post(server.url("/inspect"))
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let inspected: Value = response.json().await.unwrap();
+        assert_eq!(inspected["status"], "found");
+        // The fixture of this debug id holds Breakpad symbols.
+        assert_eq!(inspected["file_format"], "breakpad");
+        assert_eq!(inspected["arch"], "x86");
+        assert_eq!(inspected["features"]["has_debug_info"], true);
+        assert!(inspected["size"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_missing_object() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+        let response = Client::new()
+            .post(server.url("/inspect"))
+            .json(&serde_json::json!({
+                "sources": [],
+                "debug_id": "ff9f9f78-41db-88f0-cded-a9e1e9bff3b5-1",
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let inspected: Value = response.json().await.unwrap();
+        assert_eq!(inspected["status"], "missing");
+        assert_eq!(inspected["features"]["has_debug_info"], false);
+    }
+}
@@ -0,0 +1,127 @@
+use axum::extract;
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+
+use symbolicator_native::interface::{MetricKitPayload, SymbolicateMetricKitPayload};
+use symbolicator_service::types::Platform;
+use symbolicator_sources::SourceConfig;
+
+use crate::service::{RequestOptions, RequestService, ScrapingConfig, SymbolicationResponse};
+use crate::utils::sentry::ConfigureScope;
+
+use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
+use crate::endpoints::ResponseError;
+
+/// JSON body of the MetricKit symbolication request.
+#[derive(Serialize, Deserialize)]
+pub struct MetricKitRequestBody {
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub sources: Option<Vec<SourceConfig>>,
+    /// The JSON representation of an `MXDiagnosticPayload`.
+    pub payload: MetricKitPayload,
+    #[serde(default)]
+    pub options: RequestOptions,
+    #[serde(default)]
+    pub scraping: ScrapingConfig,
+}
+
+pub async fn handle_metrickit_request(
+    extract::State(service): extract::State<RequestService>,
+    extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
+    extract::Json(body): extract::Json<MetricKitRequestBody>,
+) -> Result<Json<SymbolicationResponse>, ResponseError> {
+    sentry::start_session();
+
+    params.configure_scope();
+
+    let sources = match body.sources {
+        Some(sources) => sources.into(),
+        None => service.default_sources(),
+    };
+
+    let request_id = service.symbolicate_metrickit(
+        SymbolicateMetricKitPayload {
+            platform: body.platform,
+            scope: params.scope,
+            sources,
+            payload: body.payload,
+            apply_source_context: body.options.apply_source_context,
+            scraping: body.scraping,
+        },
+        body.options,
+    )?;
+
+    match service.get_response(request_id, params.timeout).await {
+        Some(response) => Ok(Json(response)),
+        None => Err("symbolication request did not start".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, StatusCode};
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_missing_payload() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let response = Client::new()
+            .post(server.url("/metrickit"))
+            .header("Content-Type", "application/json")
+            .body(r#"{"sources": []}"#)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_binary() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let payload = r#"{
+            "sources": [],
+            "payload": {
+                "crashDiagnostics": [{
+                    "callStackTree": {
+                        "callStackPerThread": true,
+                        "callStacks": [{
+                            "threadAttributed": true,
+                            "callStackRootFrames": [{
+                                "binaryUUID": "2B4E1F6F-1E2D-3B5A-9C8D-7E6F5A4B3C2D",
+                                "offsetIntoBinaryTextSegment": 4660,
+                                "sampleCount": 1,
+                                "binaryName": "App",
+                                "address": 4295037492
+                            }]
+                        }]
+                    },
+                    "diagnosticMetaData": {"signal": 11}
+                }]
+            }
+        }"#;
+
+        let response = Client::new()
+            .post(server.url("/metrickit"))
+            .header("Content-Type", "application/json")
+            .body(payload)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.text().await.unwrap();
+        assert!(body.contains(r#""status":"completed""#));
+        assert!(body.contains(r#""type":"crash""#));
+        assert!(body.contains(r#""status":"missing""#));
+    }
+}
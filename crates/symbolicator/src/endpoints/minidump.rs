@@ -22,7 +22,7 @@ pub async fn handle_minidump_request(
     params.configure_scope();
 
     let mut minidump = None;
-    let mut sources = service.config().default_sources();
+    let mut sources = service.default_sources();
     let mut scraping = Default::default();
     let mut options = RequestOptions::default();
     let mut platform = None;
@@ -151,6 +151,67 @@ mod tests {
         test::assert_snapshot!(response);
     }
 
+    #[tokio::test]
+    async fn test_frame_registers() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let file_contents = test::read_fixture("windows.dmp");
+        let file_part = multipart::Part::bytes(file_contents).file_name("windows.dmp");
+
+        let form = multipart::Form::new()
+            .part("upload_file_minidump", file_part)
+            .text("sources", "[]")
+            .text("options", r#"{"frame_registers":true}"#);
+
+        let response = Client::new()
+            .post(server.url("/minidump"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response: serde_json::Value = response.json().await.unwrap();
+        let frames = &response["stacktraces"][0]["frames"];
+        assert_eq!(frames[0]["registers"]["eip"], "0x2a2a3d");
+        assert!(frames[1]["registers"]["esp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_best_effort() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let mut file_contents = test::read_fixture("windows.dmp");
+        file_contents.truncate(file_contents.len() / 2);
+        let file_part = multipart::Part::bytes(file_contents).file_name("windows.dmp");
+
+        let form = multipart::Form::new()
+            .part("upload_file_minidump", file_part)
+            .text("sources", "[]")
+            .text("options", r#"{"best_effort":true}"#);
+
+        let response = Client::new()
+            .post(server.url("/minidump"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(response["status"], "completed");
+        let warnings = response["warnings"].as_array().unwrap();
+        assert!(warnings
+            .iter()
+            .any(|warning| warning["kind"] == "truncated_stream"));
+    }
+
     #[tokio::test]
     async fn test_unknown_field() {
         test::setup();
@@ -6,27 +6,45 @@ use tower::ServiceBuilder;
 
 use crate::service::RequestService;
 
+mod admin;
 mod applecrashreport;
+mod breakpad;
+mod coredump;
 mod error;
+mod health;
+mod inspect;
+mod metrickit;
 mod metrics;
 mod minidump;
 mod multipart;
+mod prefetch;
 mod proxy;
 mod requests;
+mod stackwalk;
 mod symbolicate;
 mod symbolicate_js;
 mod symbolicate_jvm;
+mod wer;
 
 pub use error::ResponseError;
 use metrics::MetricsLayer;
 
 use self::minidump::handle_minidump_request as minidump;
+use admin::purge_caches;
 use applecrashreport::handle_apple_crash_report_request as applecrashreport;
+use breakpad::breakpad_symbols;
+use coredump::handle_coredump_request as coredump;
+use health::probe_sources as sources_health;
+use inspect::inspect_object as inspect;
+use metrickit::handle_metrickit_request as metrickit;
+use prefetch::{poll_prefetch, prefetch_modules as prefetch};
 use proxy::proxy_symstore_request as proxy;
 use requests::poll_request as requests;
+use stackwalk::handle_stackwalk_request as stackwalk;
 use symbolicate::symbolicate_frames as symbolicate;
 use symbolicate_js::handle_symbolication_request as symbolicate_js;
 use symbolicate_jvm::handle_symbolication_request as symbolicate_jvm;
+use wer::handle_wer_request as wer;
 
 pub async fn healthcheck() -> &'static str {
     crate::metric!(counter("healthcheck") += 1);
@@ -43,10 +61,20 @@ pub fn create_app(service: RequestService) -> Router {
     // We have a global 100M body limit, but a 5M symbolicate body limit
     let symbolicate_route = post(symbolicate).layer(DefaultBodyLimit::max(5 * 1024 * 1024));
     Router::new()
+        .route("/admin/purge", post(purge_caches))
+        .route("/prefetch", post(prefetch))
+        .route("/prefetch/:prefetch_id", get(poll_prefetch))
         .route("/proxy/*path", get(proxy).head(proxy))
         .route("/requests/:request_id", get(requests))
+        .route("/sources/health", get(sources_health))
+        .route("/inspect", post(inspect))
+        .route("/breakpad", post(breakpad_symbols))
         .route("/applecrashreport", post(applecrashreport))
         .route("/minidump", post(minidump))
+        .route("/coredump", post(coredump))
+        .route("/wer", post(wer))
+        .route("/stackwalk", post(stackwalk))
+        .route("/metrickit", post(metrickit))
         .route("/symbolicate-js", post(symbolicate_js))
         .route("/symbolicate", symbolicate_route)
         .route("/symbolicate-jvm", post(symbolicate_jvm))
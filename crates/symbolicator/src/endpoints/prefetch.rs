@@ -0,0 +1,131 @@
+use axum::extract;
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::Deserialize;
+
+use symbolicator_service::types::RawObjectInfo;
+use symbolicator_sources::SourceConfig;
+
+use crate::service::{PrefetchProgress, RequestId, RequestService, Scope};
+
+/// Query parameters of the prefetch request.
+#[derive(Deserialize)]
+pub struct PrefetchRequestQueryParams {
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+/// JSON body of the prefetch request.
+#[derive(Deserialize)]
+pub struct PrefetchRequestBody {
+    #[serde(default)]
+    pub sources: Option<Vec<SourceConfig>>,
+    pub modules: Vec<RawObjectInfo>,
+}
+
+/// Starts prefetching the caches of the modules in the background.
+pub async fn prefetch_modules(
+    extract::State(service): extract::State<RequestService>,
+    extract::Query(params): extract::Query<PrefetchRequestQueryParams>,
+    extract::Json(body): extract::Json<PrefetchRequestBody>,
+) -> Result<Json<PrefetchProgress>, StatusCode> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("POST /prefetch"));
+    });
+
+    let sources = match body.sources {
+        Some(sources) => sources.into(),
+        None => service.default_sources(),
+    };
+
+    let prefetch_id = service.prefetch_modules(params.scope, sources, body.modules);
+    match service.prefetch_progress(prefetch_id) {
+        Some(progress) => Ok(Json(progress)),
+        None => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Polls the progress of a prefetch.
+pub async fn poll_prefetch(
+    extract::State(service): extract::State<RequestService>,
+    extract::Path(prefetch_id): extract::Path<RequestId>,
+) -> Result<Json<PrefetchProgress>, StatusCode> {
+    sentry::configure_scope(|scope| {
+        scope.set_transaction(Some("GET /prefetch"));
+    });
+
+    match service.prefetch_progress(prefetch_id) {
+        Some(progress) => Ok(Json(progress)),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Client;
+    use serde_json::Value;
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_prefetch_modules() {
+        test::setup();
+
+        let client = Client::new();
+        let server = test::server_with_default_service();
+
+        let payload = serde_json::json!({
+            "sources": [],
+            "modules": [{
+                "type": "pe",
+                "debug_id": "ff9f9f78-41db-88f0-cded-a9e1e9bff3b5-1",
+                "code_file": "C:\\Windows\\System32\\kernel32.dll",
+                "debug_file": "C:\\Windows\\System32\\wkernel32.pdb",
+            }],
+        });
+        let response = client
+            .post(server.url("/prefetch"))
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let mut progress: Value = response.json().await.unwrap();
+        let prefetch_id = progress["prefetch_id"].as_str().unwrap().to_owned();
+
+        for _ in 0..100 {
+            if progress["status"] == "completed" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            progress = client
+                .get(server.url(&format!("/prefetch/{prefetch_id}")))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(progress["status"], "completed");
+        assert_eq!(progress["completed"], 1);
+        let module = &progress["modules"][0];
+        assert_eq!(module["status"], "completed");
+        assert_eq!(module["debug_status"], "missing");
+        assert_eq!(module["unwind_status"], "missing");
+        assert_eq!(module["debug_id"], "ff9f9f78-41db-88f0-cded-a9e1e9bff3b5-1");
+    }
+
+    #[tokio::test]
+    async fn test_poll_unknown_prefetch() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+        let url = server.url("/prefetch/c6a2d0b5-7ad6-4b1e-8b32-3aa4c1d3d4f7");
+        let response = Client::new().get(url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
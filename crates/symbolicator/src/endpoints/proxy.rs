@@ -29,7 +29,7 @@ async fn load_object(service: RequestService, path: String) -> CacheEntry<Arc<Ob
         .find_object(FindObject {
             filetypes,
             identifier: object_id,
-            sources: config.default_sources(),
+            sources: service.default_sources(),
             scope: Scope::Global,
             purpose: ObjectPurpose::Debug,
         })
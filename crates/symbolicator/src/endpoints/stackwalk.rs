@@ -0,0 +1,174 @@
+use axum::extract;
+use axum::http::StatusCode;
+use axum::response::Json;
+use symbolicator_native::interface::{RawProcess, StackwalkStackMemory};
+
+use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
+use crate::service::{RequestOptions, RequestService, SymbolicationResponse};
+use crate::utils::sentry::ConfigureScope;
+
+use super::multipart::read_multipart_data;
+use super::ResponseError;
+
+/// The most stack memory which can be uploaded for a single thread.
+const MAX_STACK_SIZE: usize = 1024 * 1024;
+
+pub async fn handle_stackwalk_request(
+    extract::State(service): extract::State<RequestService>,
+    extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
+    mut multipart: extract::Multipart,
+) -> Result<Json<SymbolicationResponse>, ResponseError> {
+    sentry::start_session();
+
+    params.configure_scope();
+
+    let mut process: Option<RawProcess> = None;
+    let mut stacks = Vec::new();
+    let mut sources = service.default_sources();
+    let mut scraping = Default::default();
+    let mut options = RequestOptions::default();
+    let mut platform = None;
+
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("process") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                process = Some(serde_json::from_slice(&data)?);
+            }
+            Some("upload_file_stack_memory") => {
+                stacks.push(read_multipart_data(field, MAX_STACK_SIZE).await?);
+            }
+            Some("sources") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                sources = serde_json::from_slice(&data)?;
+            }
+            Some("scraping") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                scraping = serde_json::from_slice(&data)?;
+            }
+            Some("options") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                options = serde_json::from_slice(&data)?;
+            }
+            Some("platform") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                platform = serde_json::from_slice(&data)?
+            }
+            _ => (), // Always ignore unknown fields.
+        }
+    }
+
+    let mut process = process.ok_or((StatusCode::BAD_REQUEST, "missing process"))?;
+    if stacks.len() > process.threads.len() {
+        return Err((StatusCode::BAD_REQUEST, "more stack memory than threads").into());
+    }
+    // The stack memory is uploaded in the order of the threads.
+    for (thread, stack) in process.threads.iter_mut().zip(stacks) {
+        thread.stack_memory = stack;
+    }
+
+    let request_id = service.process_stack_memory(
+        StackwalkStackMemory {
+            platform,
+            scope: params.scope,
+            sources,
+            process,
+            scraping,
+            options: options.stackwalk_options(),
+        },
+        options,
+    )?;
+
+    match service.get_response(request_id, params.timeout).await {
+        Some(response) => Ok(Json(response)),
+        None => Err("symbolication request did not start".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{multipart, Client, StatusCode};
+
+    use crate::test;
+
+    const PROCESS: &str = r#"{
+        "arch": "x86_64",
+        "os": "linux",
+        "threads": [{
+            "thread_id": 42,
+            "registers": {"rip": "0x10100", "rsp": "0x70001000"}
+        }]
+    }"#;
+
+    #[tokio::test]
+    async fn test_missing_process() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let form = multipart::Form::new().text("sources", "[]");
+        let response = Client::new()
+            .post(server.url("/stackwalk"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_too_many_stacks() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let form = multipart::Form::new()
+            .text("sources", "[]")
+            .text("process", PROCESS)
+            .part(
+                "upload_file_stack_memory",
+                multipart::Part::bytes(vec![0; 16]),
+            )
+            .part(
+                "upload_file_stack_memory",
+                multipart::Part::bytes(vec![0; 16]),
+            );
+        let response = Client::new()
+            .post(server.url("/stackwalk"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_stackwalk_without_modules() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let form = multipart::Form::new()
+            .text("sources", "[]")
+            .text("process", PROCESS)
+            .part(
+                "upload_file_stack_memory",
+                multipart::Part::bytes(vec![0; 256]),
+            );
+        let response = Client::new()
+            .post(server.url("/stackwalk"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.text().await.unwrap();
+        assert!(body.contains(r#""status":"completed""#));
+        assert!(body.contains(r#""thread_id":42"#));
+        assert!(body.contains(r#""instruction_addr":"0x10100""#));
+    }
+}
@@ -64,7 +64,7 @@ pub async fn symbolicate_frames(
 
     let sources = match body.sources {
         Some(sources) => sources.into(),
-        None => service.config().default_sources(),
+        None => service.default_sources(),
     };
 
     let request_id = service.symbolicate_stacktraces(
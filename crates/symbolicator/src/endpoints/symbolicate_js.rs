@@ -4,11 +4,12 @@ use axum::extract;
 use axum::response::Json;
 use serde::{Deserialize, Serialize};
 use symbolicator_js::interface::{JsModule, JsStacktrace, SymbolicateJsStacktraces};
+use symbolicator_native::interface::DemanglingOptions;
 use symbolicator_service::types::Platform;
 use symbolicator_sources::SentrySourceConfig;
 
 use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
-use crate::service::{RequestService, ScrapingConfig, SymbolicationResponse};
+use crate::service::{RequestOptions, RequestService, ScrapingConfig, SymbolicationResponse};
 use crate::utils::sentry::ConfigureScope;
 
 use super::ResponseError;
@@ -44,6 +45,11 @@ pub struct JsRequestOptions {
     /// Whether to apply source context for the stack frames.
     #[serde(default = "default_apply_source_context")]
     pub apply_source_context: bool,
+
+    /// Demangles the function names of mangled frames, like the ones of C++ compiled to
+    /// JavaScript.
+    #[serde(default)]
+    pub demangling: Option<DemanglingOptions>,
 }
 
 fn default_apply_source_context() -> bool {
@@ -54,6 +60,7 @@ impl Default for JsRequestOptions {
     fn default() -> Self {
         Self {
             apply_source_context: true,
+            demangling: None,
         }
     }
 }
@@ -82,17 +89,24 @@ pub async fn handle_symbolication_request(
     // Turn off scraping if `allow_scraping` is false
     scraping.enabled &= allow_scraping;
 
-    let request_id = service.symbolicate_js_stacktraces(SymbolicateJsStacktraces {
-        platform,
-        scope: params.scope,
-        source: Arc::new(source),
-        stacktraces,
-        modules,
-        release,
-        dist,
-        scraping,
-        apply_source_context: options.apply_source_context,
-    })?;
+    let request_options = RequestOptions {
+        demangling: options.demangling,
+        ..RequestOptions::default()
+    };
+    let request_id = service.symbolicate_js_stacktraces(
+        SymbolicateJsStacktraces {
+            platform,
+            scope: params.scope,
+            source: Arc::new(source),
+            stacktraces,
+            modules,
+            release,
+            dist,
+            scraping,
+            apply_source_context: options.apply_source_context,
+        },
+        request_options,
+    )?;
 
     match service.get_response(request_id, params.timeout).await {
         Some(response) => Ok(Json(response)),
@@ -0,0 +1,132 @@
+use axum::extract;
+use axum::http::StatusCode;
+use axum::response::Json;
+use tokio::fs::File;
+
+use crate::endpoints::symbolicate::SymbolicationRequestQueryParams;
+use crate::service::{RequestOptions, RequestService, SymbolicationResponse};
+use crate::utils::sentry::ConfigureScope;
+
+use super::multipart::{read_multipart_data, stream_multipart_file};
+use super::ResponseError;
+
+pub async fn handle_wer_request(
+    extract::State(service): extract::State<RequestService>,
+    extract::Query(params): extract::Query<SymbolicationRequestQueryParams>,
+    mut multipart: extract::Multipart,
+) -> Result<Json<SymbolicationResponse>, ResponseError> {
+    sentry::start_session();
+
+    params.configure_scope();
+
+    let mut archive = None;
+    let mut sources = service.default_sources();
+    let mut scraping = Default::default();
+    let mut options = RequestOptions::default();
+    let mut platform = None;
+
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("upload_file_wer") => {
+                let mut archive_file = tempfile::Builder::new();
+                archive_file.prefix("wer").suffix(".cab");
+                let archive_file = if let Some(tmp_dir) = service.config().cache_dir("tmp") {
+                    archive_file.tempfile_in(tmp_dir)
+                } else {
+                    archive_file.tempfile()
+                }?;
+                let (file, temp_path) = archive_file.into_parts();
+                let mut file = File::from_std(file);
+                stream_multipart_file(field, &mut file).await?;
+                archive = Some(temp_path)
+            }
+            Some("sources") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                sources = serde_json::from_slice(&data)?;
+            }
+            Some("scraping") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                scraping = serde_json::from_slice(&data)?;
+            }
+            Some("options") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                options = serde_json::from_slice(&data)?;
+            }
+            Some("platform") => {
+                let data = read_multipart_data(field, 1024 * 1024).await?; // 1Mb
+                platform = serde_json::from_slice(&data)?
+            }
+            _ => (), // Always ignore unknown fields.
+        }
+    }
+
+    let archive_file = archive.ok_or((StatusCode::BAD_REQUEST, "missing WER archive"))?;
+
+    let request_id = service.process_wer_archive(
+        platform,
+        params.scope,
+        archive_file,
+        sources,
+        scraping,
+        options,
+    )?;
+
+    match service.get_response(request_id, params.timeout).await {
+        Some(response) => Ok(Json(response)),
+        None => Err("symbolication request did not start".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{multipart, Client, StatusCode};
+
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_missing_archive() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let form = multipart::Form::new().text("sources", "[]");
+
+        let response = Client::new()
+            .post(server.url("/wer"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_not_an_archive() {
+        test::setup();
+
+        let server = test::server_with_default_service();
+
+        let file_contents = test::read_fixture("windows.dmp");
+        let file_part = multipart::Part::bytes(file_contents).file_name("windows.dmp");
+
+        let form = multipart::Form::new()
+            .part("upload_file_wer", file_part)
+            .text("sources", "[]");
+
+        let response = Client::new()
+            .post(server.url("/wer"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.text().await.unwrap();
+        assert_eq!(
+            &body,
+            "{\"status\":\"failed\",\"message\":\"not a CAB or zip archive\"}"
+        );
+    }
+}
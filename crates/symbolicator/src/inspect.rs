@@ -0,0 +1,224 @@
+//! Inspection and maintenance of the local caches from the command line.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use symbolic::common::DebugId;
+use symbolicator_service::caches::versions::cache_versions;
+use symbolicator_service::caching::{self, BundleFilter, Cache, CacheFileInfo, Caches};
+
+use crate::config::Config;
+
+/// Lists the files of all caches, or only of the cache with the given name.
+///
+/// If `errors_only` is `true`, only files storing an error are listed.
+pub fn list(config: Config, cache: Option<&str>, errors_only: bool) -> Result<()> {
+    let caches = Caches::from_config(&config).context("failed to open caches")?;
+    let caches = match cache {
+        Some(name) => vec![get_cache(&caches, name)?],
+        None => caches.all().to_vec(),
+    };
+
+    for cache in caches {
+        let files = cache.list_files()?;
+        let total_size: u64 = files.iter().map(|file| file.size).sum();
+        let errors = files.iter().filter(|file| file.error.is_some()).count();
+        println!(
+            "{}: {} files, {errors} errors, {total_size} bytes",
+            cache.name(),
+            files.len()
+        );
+
+        for file in files {
+            if errors_only && file.error.is_none() {
+                continue;
+            }
+            println!("  {}", describe(&file));
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the metadata and decoded header of the file at `path` in the cache with the given name.
+pub fn show(config: Config, cache: &str, path: &Path) -> Result<()> {
+    let caches = Caches::from_config(&config).context("failed to open caches")?;
+    let details = get_cache(&caches, cache)?.inspect_file(path)?;
+    let info = &details.info;
+
+    println!("path: {}", info.path.display());
+    println!("version: {}", info.version);
+    println!("size: {} bytes", info.size);
+    println!("compressed: {}", info.compressed);
+    println!("age: {}", format_age(info.age));
+    match &info.error {
+        Some(error) => println!("error: {error}"),
+        None => println!("status: ok"),
+    }
+    for (field, value) in &details.header {
+        println!("{field}: {value}");
+    }
+
+    Ok(())
+}
+
+/// Moves the existing files of the deduplicated caches, or of the cache with the given name,
+/// into their content store.
+pub fn dedup(config: Config, cache: Option<&str>) -> Result<()> {
+    let caches = Caches::from_config(&config).context("failed to open caches")?;
+    let caches = match cache {
+        Some(name) => vec![get_cache(&caches, name)?],
+        None => caches
+            .all()
+            .into_iter()
+            .filter(|cache| cache.is_deduplicated())
+            .collect(),
+    };
+
+    for cache in caches {
+        let migrated = cache.deduplicate()?;
+        println!(
+            "{}: moved {migrated} files into the content store",
+            cache.name()
+        );
+    }
+
+    Ok(())
+}
+
+/// Upgrades the files of all caches, or of the cache with the given name, to their current
+/// version, if there are migrations for them.
+pub fn migrate(config: Config, cache: Option<&str>) -> Result<()> {
+    let caches = Caches::from_config(&config).context("failed to open caches")?;
+    let caches = match cache {
+        Some(name) => vec![get_cache(&caches, name)?],
+        None => caches.all().to_vec(),
+    };
+
+    for cache in caches {
+        let Some(versions) = cache_versions(cache.name()) else {
+            continue;
+        };
+        let migrated = cache.migrate(&versions)?;
+        println!(
+            "{}: upgraded {migrated} files to version {}",
+            cache.name(),
+            versions.current
+        );
+    }
+
+    Ok(())
+}
+
+/// Exports the good files of all caches, or only of the cache with the given name, into a bundle.
+///
+/// With `debug_ids`, only files with one of the debug ids listed in that file are exported. With
+/// `max_age`, only files used within that duration are exported.
+pub fn export(
+    config: Config,
+    cache: Option<&str>,
+    debug_ids: Option<&Path>,
+    max_age: Option<Duration>,
+    output: &Path,
+) -> Result<()> {
+    let caches = Caches::from_config(&config).context("failed to open caches")?;
+    let caches = match cache {
+        Some(name) => vec![get_cache(&caches, name)?],
+        None => caches.all().to_vec(),
+    };
+    let debug_ids = match debug_ids {
+        Some(path) => Some(read_debug_ids(path)?),
+        None => None,
+    };
+
+    let filter = BundleFilter { debug_ids, max_age };
+    let file =
+        File::create(output).with_context(|| format!("failed to create `{}`", output.display()))?;
+    let exported = caching::export_bundle(&caches, &filter, BufWriter::new(file))?;
+    println!("exported {exported} files into {}", output.display());
+
+    Ok(())
+}
+
+/// Imports the files of the bundle at `path` into the caches.
+pub fn import(config: Config, path: &Path) -> Result<()> {
+    let caches = Caches::from_config(&config).context("failed to open caches")?;
+    let file = File::open(path).with_context(|| format!("failed to open `{}`", path.display()))?;
+    let import = caching::import_bundle(&caches, BufReader::new(file))?;
+    println!(
+        "imported {} files, skipped {} existing and {} unsupported files",
+        import.imported, import.existing, import.unsupported
+    );
+
+    Ok(())
+}
+
+/// Reads a file with one debug id per line, ignoring empty lines.
+fn read_debug_ids(path: &Path) -> Result<HashSet<DebugId>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .with_context(|| format!("invalid debug id `{line}`"))
+        })
+        .collect()
+}
+
+fn get_cache<'a>(caches: &'a Caches, name: &str) -> Result<&'a Cache> {
+    caches
+        .get(name)
+        .with_context(|| format!("unknown cache `{name}`"))
+}
+
+/// Describes a file on a single line.
+fn describe(file: &CacheFileInfo) -> String {
+    let status = match &file.error {
+        Some(error) => error.to_string(),
+        None => "ok".to_owned(),
+    };
+    format!(
+        "{}  {} bytes  {}  {status}",
+        file.path.display(),
+        file.size,
+        format_age(file.age)
+    )
+}
+
+/// Formats an age with its two most significant units, like `3d 4h`.
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let (days, hours, minutes, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(42)), "42s");
+        assert_eq!(format_age(Duration::from_secs(3 * 60 + 5)), "3m 5s");
+        assert_eq!(format_age(Duration::from_secs(2 * 3600 + 59)), "2h 0m");
+        assert_eq!(
+            format_age(Duration::from_secs(3 * 86400 + 4 * 3600)),
+            "3d 4h"
+        );
+    }
+}
@@ -23,7 +23,11 @@ pub use symbolicator_service::{config, metric, utils};
 
 mod cli;
 mod endpoints;
+mod inspect;
 mod logging;
+mod object;
+mod prefetch;
+mod reload;
 mod server;
 mod service;
 
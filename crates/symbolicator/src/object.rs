@@ -0,0 +1,102 @@
+//! Inspection of debug files from the command line.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use symbolic::common::{CodeId, DebugId};
+use symbolicator_native::interface::{DumpBreakpadSymbols, InspectObject};
+use symbolicator_sources::ObjectId;
+use tokio::runtime::Runtime;
+
+use crate::config::Config;
+use crate::service::{RequestService, Scope};
+
+/// The identifiers of a debug file, as passed on the command line.
+#[derive(Debug)]
+pub struct ObjectArgs {
+    pub debug_id: Option<String>,
+    pub code_id: Option<String>,
+    pub debug_file: Option<String>,
+    pub code_file: Option<String>,
+}
+
+impl ObjectArgs {
+    /// Parses the identifiers, of which a debug id or code id is required.
+    fn identifier(self) -> Result<ObjectId> {
+        let identifier = ObjectId {
+            debug_id: match self.debug_id {
+                Some(debug_id) => Some(debug_id.parse::<DebugId>().context("invalid debug id")?),
+                None => None,
+            },
+            code_id: match self.code_id {
+                Some(code_id) => Some(code_id.parse::<CodeId>().ok().context("invalid code id")?),
+                None => None,
+            },
+            debug_file: self.debug_file,
+            code_file: self.code_file,
+            ..Default::default()
+        };
+        if identifier.debug_id.is_none() && identifier.code_id.is_none() {
+            anyhow::bail!("either a debug id or a code id is required");
+        }
+        Ok(identifier)
+    }
+}
+
+/// Fetches the debug file from the configured sources, and prints its features as JSON.
+pub fn inspect(config: Config, args: ObjectArgs, scope: Scope) -> Result<()> {
+    let identifier = args.identifier()?;
+    let (io_pool, _cpu_pool, service) = create_service(config)?;
+
+    let sources = service.default_sources();
+    let inspected = io_pool.block_on(service.inspect_object(InspectObject {
+        scope,
+        sources,
+        identifier,
+    }));
+
+    let output = serde_json::to_string_pretty(&inspected)?;
+    println!("{output}");
+    Ok(())
+}
+
+/// Fetches the debug file from the configured sources, and prints the Breakpad symbols
+/// generated from it.
+pub fn dump_breakpad(config: Config, args: ObjectArgs, scope: Scope) -> Result<()> {
+    let identifier = args.identifier()?;
+    let (io_pool, _cpu_pool, service) = create_service(config)?;
+
+    let sources = service.default_sources();
+    let symbols = io_pool.block_on(service.breakpad_symbols(DumpBreakpadSymbols {
+        scope,
+        sources,
+        identifier,
+    }))?;
+
+    std::io::stdout().lock().write_all(&symbols)?;
+    Ok(())
+}
+
+/// Creates the service along with the runtimes it runs on.
+fn create_service(config: Config) -> Result<(Runtime, Runtime, RequestService)> {
+    let megs = 1024 * 1024;
+    let io_pool = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("sym-io")
+        .enable_all()
+        .thread_stack_size(8 * megs)
+        .build()?;
+    let cpu_pool = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("sym-cpu")
+        .enable_all()
+        .thread_stack_size(8 * megs)
+        .build()?;
+
+    let service = RequestService::create(
+        config,
+        io_pool.handle().to_owned(),
+        cpu_pool.handle().to_owned(),
+    )
+    .context("failed to create service state")?;
+
+    Ok((io_pool, cpu_pool, service))
+}
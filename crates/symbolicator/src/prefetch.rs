@@ -0,0 +1,112 @@
+//! Prefetching of caches from the command line.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use symbolicator_service::types::{ObjectFileStatus, RawObjectInfo};
+
+use crate::config::Config;
+use crate::service::{PrefetchModuleProgress, PrefetchStatus, RequestService, Scope};
+
+/// The interval in which the progress is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Prefetches the caches of the modules listed in the JSON file at `modules_path`.
+///
+/// Modules are fetched from the configured sources, and reported as they complete.
+pub fn prefetch(config: Config, modules_path: &Path, scope: Scope) -> Result<()> {
+    let modules = std::fs::read(modules_path)
+        .with_context(|| format!("failed to read `{}`", modules_path.display()))?;
+    let modules: Vec<RawObjectInfo> =
+        serde_json::from_slice(&modules).context("failed to parse the list of modules")?;
+
+    let megs = 1024 * 1024;
+    let io_pool = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("sym-io")
+        .enable_all()
+        .thread_stack_size(8 * megs)
+        .build()?;
+    let cpu_pool = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("sym-cpu")
+        .enable_all()
+        .thread_stack_size(8 * megs)
+        .build()?;
+
+    let service = RequestService::create(
+        config,
+        io_pool.handle().to_owned(),
+        cpu_pool.handle().to_owned(),
+    )
+    .context("failed to create service state")?;
+
+    let total = modules.len();
+    let sources = service.default_sources();
+    let prefetch_id = service.prefetch_modules(scope, sources, modules);
+
+    io_pool.block_on(async {
+        let mut reported = vec![false; total];
+        let mut failed = 0;
+        loop {
+            let progress = service
+                .prefetch_progress(prefetch_id)
+                .context("prefetch has been dropped")?;
+
+            for (module, reported) in progress.modules.iter().zip(&mut reported) {
+                if *reported || module.status != PrefetchStatus::Completed {
+                    continue;
+                }
+                *reported = true;
+                if !is_found(module) {
+                    failed += 1;
+                }
+                println!("[{}/{total}] {}", progress.completed, describe(module));
+            }
+
+            if progress.status == PrefetchStatus::Completed {
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        println!("Prefetched {total} modules, {failed} of them incompletely");
+        Ok(())
+    })
+}
+
+/// Whether all caches of the module have been found.
+fn is_found(module: &PrefetchModuleProgress) -> bool {
+    match module.result {
+        Some(result) => {
+            result.debug_status == ObjectFileStatus::Found
+                && matches!(result.unwind_status, None | Some(ObjectFileStatus::Found))
+        }
+        None => false,
+    }
+}
+
+/// Describes the module and the outcome of fetching its caches.
+fn describe(module: &PrefetchModuleProgress) -> String {
+    let raw = &module.module;
+    let name = raw
+        .debug_file
+        .as_deref()
+        .or(raw.code_file.as_deref())
+        .unwrap_or("<unknown>");
+    let id = raw
+        .debug_id
+        .as_deref()
+        .or(raw.code_id.as_deref())
+        .unwrap_or("<none>");
+
+    let mut description = format!("{name} ({id})");
+    if let Some(result) = module.result {
+        description.push_str(": debug ");
+        description.push_str(result.debug_status.name());
+        if let Some(unwind_status) = result.unwind_status {
+            description.push_str(", unwind ");
+            description.push_str(unwind_status.name());
+        }
+    }
+    description
+}
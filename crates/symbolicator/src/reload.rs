@@ -0,0 +1,115 @@
+//! Reloads the sources from the configuration file while the server is running.
+//!
+//! Sources are reloaded when the process receives `SIGHUP`, and whenever the modification time of
+//! the configuration file changes if [`sources_reload_interval`](Config::sources_reload_interval)
+//! is configured. Only the `sources` are taken from the reloaded file, changes to all other
+//! settings require a restart.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::metric;
+use crate::service::RequestService;
+
+/// Starts watching the configuration file at `path` on the given runtime.
+pub fn spawn(service: RequestService, path: PathBuf, runtime: &tokio::runtime::Handle) {
+    #[cfg(unix)]
+    {
+        let service = service.clone();
+        let path = path.clone();
+        runtime.spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangups = match signal(SignalKind::hangup()) {
+                Ok(hangups) => hangups,
+                Err(error) => {
+                    tracing::error!(error = %error, "Failed to listen for SIGHUP");
+                    return;
+                }
+            };
+            while hangups.recv().await.is_some() {
+                tracing::info!("Received SIGHUP, reloading sources");
+                reload_sources(&service, &path);
+            }
+        });
+    }
+
+    if let Some(interval) = service.config().sources_reload_interval {
+        runtime.spawn(async move {
+            let mut last_modified = modified(&path);
+            loop {
+                tokio::time::sleep(interval).await;
+                let current = modified(&path);
+                if current != last_modified {
+                    last_modified = current;
+                    reload_sources(&service, &path);
+                }
+            }
+        });
+    }
+}
+
+/// Returns the modification time of the file, if it exists.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Replaces the default sources of the service with the ones from the configuration file.
+///
+/// If the file can not be loaded, the current sources are kept. Returns whether the sources
+/// have been replaced.
+pub fn reload_sources(service: &RequestService, path: &Path) -> bool {
+    match Config::get(Some(path)) {
+        Ok(config) => {
+            tracing::info!(
+                sources = config.sources.len(),
+                "Reloaded sources from `{}`",
+                path.display()
+            );
+            metric!(counter("config.reload") += 1, "result" => "ok");
+            service.set_default_sources(config.sources);
+            true
+        }
+        Err(err) => {
+            let error: &dyn std::error::Error = err.as_ref();
+            tracing::error!(error, "Failed to reload sources");
+            metric!(counter("config.reload") += 1, "result" => "error");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_sources() {
+        let handle = tokio::runtime::Handle::current();
+        let service = RequestService::create(Config::default(), handle.clone(), handle).unwrap();
+        assert!(service.default_sources().is_empty());
+
+        let config_file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(
+            config_file.path(),
+            r#"
+sources:
+  - id: local
+    type: filesystem
+    path: /symbols
+"#,
+        )
+        .unwrap();
+        assert!(reload_sources(&service, config_file.path()));
+        let sources = service.default_sources();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].id().as_str(), "local");
+
+        // Invalid files keep the current sources.
+        fs::write(config_file.path(), "sources: 42").unwrap();
+        assert!(!reload_sources(&service, config_file.path()));
+        assert_eq!(service.default_sources().len(), 1);
+    }
+}
@@ -2,6 +2,7 @@
 use std::fs::read;
 use std::net::SocketAddr;
 use std::net::TcpListener;
+use std::path::Path;
 #[cfg(feature = "https")]
 use std::path::PathBuf;
 
@@ -15,6 +16,7 @@ use futures::future::BoxFuture;
 use crate::config::Config;
 use crate::endpoints;
 use crate::metric;
+use crate::reload;
 use crate::service::RequestService;
 
 #[cfg(feature = "https")]
@@ -23,7 +25,10 @@ fn read_pem_file(path: &PathBuf) -> Result<Vec<u8>> {
 }
 
 /// Starts all actors and HTTP (and optionally HTTPS) server based on loaded config.
-pub fn run(config: Config) -> Result<()> {
+///
+/// If the config has been loaded from `config_path`, its sources are [reloaded](reload) from
+/// there while the server is running.
+pub fn run(config: Config, config_path: Option<&Path>) -> Result<()> {
     // Log this metric before actually starting the server. This allows to see restarts even if
     // service creation fails.
     metric!(counter("server.starting") += 1);
@@ -54,6 +59,20 @@ pub fn run(config: Config) -> Result<()> {
     )
     .context("failed to create service state")?;
 
+    if let Some(config_path) = config_path {
+        reload::spawn(service.clone(), config_path.to_owned(), io_pool.handle());
+    }
+
+    if let Some(interval) = config.source_health_interval {
+        let service = service.clone();
+        io_pool.spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                service.probe_sources().await;
+            }
+        });
+    }
+
     let svc = endpoints::create_app(service).into_make_service();
 
     let socket_http = TcpListener::bind(config.bind.parse::<SocketAddr>()?)?;
@@ -15,35 +15,43 @@ use std::fmt;
 use std::fs::File;
 use std::future::Future;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use futures::future;
-use futures::{channel::oneshot, FutureExt as _};
+use futures::{channel::oneshot, stream, FutureExt as _, StreamExt as _};
 use sentry::protocol::SessionStatus;
 use sentry::SentryFutureExt;
 use serde::{Deserialize, Deserializer, Serialize};
+use symbolic::common::{ByteView, Language};
 use symbolicator_js::interface::{CompletedJsSymbolicationResponse, SymbolicateJsStacktraces};
 use symbolicator_js::SourceMapService;
-use symbolicator_native::interface::{CompletedSymbolicationResponse, SymbolicateStacktraces};
+use symbolicator_native::interface::{
+    CompleteObjectInfo, CompletedMetricKitResponse, CompletedSymbolicationResponse,
+    DemanglingOptions, DumpBreakpadSymbols, InspectObject, InspectedObject, PrefetchModule,
+    PrefetchedModule, PurgeCaches, PurgedCaches, StackwalkOptions, StackwalkStackMemory,
+    SymbolicateMetricKitPayload, SymbolicateStacktraces,
+};
 use symbolicator_native::SymbolicationActor;
 use symbolicator_proguard::interface::{
     CompletedJvmSymbolicationResponse, SymbolicateJvmStacktraces,
 };
 use symbolicator_proguard::ProguardService;
 use symbolicator_service::caching::CacheEntry;
-use symbolicator_service::config::Config;
+use symbolicator_service::config::{Config, UnwindingOptions};
+use symbolicator_service::download::DownloadService;
 use symbolicator_service::metric;
 use symbolicator_service::objects::ObjectsActor;
 use symbolicator_service::services::SharedServices;
-use symbolicator_service::types::Platform;
+use symbolicator_service::types::{Platform, RawObjectInfo};
 use symbolicator_service::utils::futures::CallOnDrop;
 use symbolicator_service::utils::futures::{m, measure};
 use symbolicator_sources::SourceConfig;
 use tempfile::TempPath;
 use uuid::Uuid;
 
+pub use symbolicator_service::download::SourceHealth;
 pub use symbolicator_service::objects::{
     FindObject, FindResult, ObjectHandle, ObjectMetaHandle, ObjectPurpose,
 };
@@ -109,11 +117,13 @@ pub enum SymbolicationResponse {
 /// A completed symbolication response.
 pub enum CompletedResponse {
     /// A native symbolication response.
-    Native(CompletedSymbolicationResponse),
+    Native(Box<CompletedSymbolicationResponse>),
     /// A JS symbolication response.
     Js(CompletedJsSymbolicationResponse),
     /// A JVM symbolication response.
     Jvm(CompletedJvmSymbolicationResponse),
+    /// A MetricKit symbolication response.
+    MetricKit(CompletedMetricKitResponse),
 }
 
 /// Common options for all symbolication API requests.
@@ -136,6 +146,33 @@ pub struct RequestOptions {
     /// Whether to apply source context for the stack frames.
     #[serde(default = "default_apply_source_context")]
     pub apply_source_context: bool,
+
+    /// Overrides the unwinding options configured for the scope when stackwalking threads.
+    #[serde(default)]
+    pub unwinding: Option<UnwindingOptions>,
+
+    /// Whether to add the registers recovered during stackwalking to every frame.
+    #[serde(default)]
+    pub frame_registers: bool,
+
+    /// The number of top frames of the crashing thread whose arguments and local variables are
+    /// recovered from DWARF debug information.
+    #[serde(default)]
+    pub frame_variables: usize,
+
+    /// Whether to add the handles, module versions and drivers of the process to the response.
+    #[serde(default)]
+    pub process_resources: bool,
+
+    /// Whether to process truncated or corrupt minidumps as far as possible instead of failing.
+    #[serde(default)]
+    pub best_effort: bool,
+
+    /// Demangles the function names of frames with these options instead of the defaults.
+    ///
+    /// The function names of JavaScript frames are only demangled if this is set.
+    #[serde(default)]
+    pub demangling: Option<DemanglingOptions>,
 }
 
 fn default_apply_source_context() -> bool {
@@ -147,6 +184,46 @@ impl Default for RequestOptions {
         Self {
             dif_candidates: false,
             apply_source_context: true,
+            unwinding: None,
+            frame_registers: false,
+            frame_variables: 0,
+            process_resources: false,
+            best_effort: false,
+            demangling: None,
+        }
+    }
+}
+
+impl RequestOptions {
+    /// Returns the options which control the stackwalking of minidumps.
+    pub fn stackwalk_options(&self) -> StackwalkOptions {
+        StackwalkOptions {
+            unwinding: self.unwinding,
+            frame_registers: self.frame_registers,
+            frame_variables: self.frame_variables,
+            process_resources: self.process_resources,
+            best_effort: self.best_effort,
+        }
+    }
+}
+
+/// Demangles the function names of JavaScript frames which are mangled, like the ones of
+/// functions compiled to JavaScript from C++ or Rust.
+fn demangle_js_frames(
+    response: &mut CompletedJsSymbolicationResponse,
+    options: &DemanglingOptions,
+) {
+    for frame in response
+        .stacktraces
+        .iter_mut()
+        .flat_map(|trace| &mut trace.frames)
+    {
+        let demangled = frame
+            .function
+            .as_deref()
+            .and_then(|function| options.demangle(function, Language::Unknown));
+        if let Some(demangled) = demangled {
+            frame.function = Some(demangled);
         }
     }
 }
@@ -155,11 +232,48 @@ impl Default for RequestOptions {
 ///
 /// This will avoid this from being serialised as the DIF object candidates list is not
 /// serialised when it is empty.
-fn clear_dif_candidates(response: &mut CompletedSymbolicationResponse) {
-    for module in response.modules.iter_mut() {
+fn clear_dif_candidates(modules: &mut [CompleteObjectInfo]) {
+    for module in modules.iter_mut() {
         module.candidates.clear()
     }
 }
+
+/// The status of a prefetch, or of one of its modules.
+#[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefetchStatus {
+    /// The caches are still being fetched.
+    Pending,
+    /// All caches have been fetched.
+    Completed,
+}
+
+/// The progress of prefetching the caches of a single module.
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefetchModuleProgress {
+    /// Whether the caches of the module have been fetched.
+    pub status: PrefetchStatus,
+    /// The outcome of fetching the caches, once completed.
+    #[serde(flatten)]
+    pub result: Option<PrefetchedModule>,
+    /// The prefetched module.
+    #[serde(flatten)]
+    pub module: RawObjectInfo,
+}
+
+/// The progress of a prefetch started with [`RequestService::prefetch_modules`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefetchProgress {
+    /// The id with which the progress can be polled.
+    pub prefetch_id: RequestId,
+    /// Whether all modules have been prefetched.
+    pub status: PrefetchStatus,
+    /// The number of modules which have been prefetched.
+    pub completed: usize,
+    /// The progress of every module, in the order of the request.
+    pub modules: Vec<PrefetchModuleProgress>,
+}
+
 /// The underlying service for the HTTP request handlers.
 #[derive(Clone)]
 pub struct RequestService {
@@ -171,16 +285,22 @@ type ComputationChannel = future::Shared<oneshot::Receiver<(Instant, Symbolicati
 
 type ComputationMap = Arc<Mutex<BTreeMap<RequestId, ComputationChannel>>>;
 
+type PrefetchMap = Arc<Mutex<BTreeMap<RequestId, Arc<Mutex<PrefetchProgress>>>>>;
+
 struct RequestServiceInner {
     config: Config,
+    /// The default sources, which start out as the sources of the `config`.
+    sources: RwLock<Arc<[SourceConfig]>>,
 
     native: SymbolicationActor,
     js: SourceMapService,
     jvm: ProguardService,
     objects: ObjectsActor,
+    download_svc: Arc<DownloadService>,
 
     cpu_pool: tokio::runtime::Handle,
     requests: ComputationMap,
+    prefetches: PrefetchMap,
     max_concurrent_requests: Option<usize>,
     current_requests: Arc<AtomicUsize>,
     symbolication_taskmon: tokio_metrics::TaskMonitor,
@@ -205,7 +325,10 @@ impl RequestService {
         let js = SourceMapService::new(&shared_services);
         let jvm = ProguardService::new(&shared_services);
         let SharedServices {
-            objects, config, ..
+            objects,
+            config,
+            download_svc,
+            ..
         } = shared_services;
 
         let symbolication_taskmon = tokio_metrics::TaskMonitor::new();
@@ -222,15 +345,18 @@ impl RequestService {
         let max_concurrent_requests = config.max_concurrent_requests;
 
         let inner = RequestServiceInner {
+            sources: RwLock::new(config.sources.clone()),
             config,
 
             native,
             js,
             jvm,
             objects,
+            download_svc,
 
             cpu_pool,
             requests: Arc::new(Mutex::new(BTreeMap::new())),
+            prefetches: Arc::new(Mutex::new(BTreeMap::new())),
             max_concurrent_requests,
             current_requests: Arc::new(AtomicUsize::new(0)),
             symbolication_taskmon,
@@ -246,6 +372,28 @@ impl RequestService {
         &self.inner.config
     }
 
+    /// Returns the sources used for requests which do not bring their own.
+    ///
+    /// These are the `sources` of the [`Config`], unless they have been
+    /// [replaced](Self::set_default_sources) since.
+    pub fn default_sources(&self) -> Arc<[SourceConfig]> {
+        self.inner.sources.read().unwrap().clone()
+    }
+
+    /// Replaces the default sources.
+    ///
+    /// Requests which have already started keep using the previous sources. Caches are kept,
+    /// as they are keyed by the sources files are downloaded from.
+    pub fn set_default_sources(&self, sources: Arc<[SourceConfig]>) {
+        *self.inner.sources.write().unwrap() = sources;
+    }
+
+    /// Probes the health of the default sources which have a `canary` file.
+    pub async fn probe_sources(&self) -> Vec<SourceHealth> {
+        let sources = self.default_sources();
+        self.inner.download_svc.probe_sources(&sources).await
+    }
+
     /// Looks up the object according to the [`FindObject`] request.
     pub async fn find_object(&self, request: FindObject) -> FindResult {
         self.inner.objects.find(request).await
@@ -259,6 +407,111 @@ impl RequestService {
         self.inner.objects.fetch(handle).await
     }
 
+    /// Starts prefetching the symbol and unwind caches of the given modules in the background.
+    ///
+    /// This warms the caches for modules which are expected to be symbolicated soon. The progress
+    /// can be [polled](Self::prefetch_progress) until a while after all modules are completed.
+    pub fn prefetch_modules(
+        &self,
+        scope: Scope,
+        sources: Arc<[SourceConfig]>,
+        modules: Vec<RawObjectInfo>,
+    ) -> RequestId {
+        let prefetch_id = RequestId::new(Uuid::new_v4());
+        let progress = PrefetchProgress {
+            prefetch_id,
+            status: match modules.is_empty() {
+                true => PrefetchStatus::Completed,
+                false => PrefetchStatus::Pending,
+            },
+            completed: 0,
+            modules: modules
+                .iter()
+                .map(|module| PrefetchModuleProgress {
+                    status: PrefetchStatus::Pending,
+                    result: None,
+                    module: module.clone(),
+                })
+                .collect(),
+        };
+        let progress = Arc::new(Mutex::new(progress));
+        self.inner
+            .prefetches
+            .lock()
+            .unwrap()
+            .insert(prefetch_id, Arc::clone(&progress));
+        metric!(counter("prefetch.started") += 1);
+
+        let native = self.inner.native.clone();
+        let prefetches = Arc::clone(&self.inner.prefetches);
+        let prefetch_future = async move {
+            let native = &native;
+            let requests = modules.into_iter().enumerate().map(|(index, module)| {
+                let request = PrefetchModule {
+                    scope: scope.clone(),
+                    sources: sources.clone(),
+                    module,
+                };
+                async move { (index, native.prefetch_module(request).await) }
+            });
+            let mut results = stream::iter(requests).buffer_unordered(PREFETCH_CONCURRENCY);
+
+            while let Some((index, result)) = results.next().await {
+                metric!(
+                    counter("prefetch.modules") += 1,
+                    "status" => result.debug_status.name(),
+                );
+                let mut state = progress.lock().unwrap();
+                state.completed += 1;
+                if let Some(module) = state.modules.get_mut(index) {
+                    module.status = PrefetchStatus::Completed;
+                    module.result = Some(result);
+                }
+            }
+            progress.lock().unwrap().status = PrefetchStatus::Completed;
+
+            // Keep the progress around for clients to poll it.
+            tokio::time::sleep(PREFETCH_RETENTION).await;
+            prefetches.lock().unwrap().remove(&prefetch_id);
+        };
+
+        let hub = sentry::Hub::new_from_top(sentry::Hub::current());
+        self.inner.cpu_pool.spawn(prefetch_future.bind_hub(hub));
+
+        prefetch_id
+    }
+
+    /// Removes all cached files of a debug file, see [`PurgeCaches`].
+    pub async fn purge_caches(&self, request: PurgeCaches) -> PurgedCaches {
+        self.inner.native.purge_caches(request).await
+    }
+
+    /// Fetches a debug file and reports its features, see [`InspectObject`].
+    pub async fn inspect_object(&self, request: InspectObject) -> InspectedObject {
+        self.inner.native.inspect_object(request).await
+    }
+
+    /// Generates the Breakpad symbols of a debug file, see [`DumpBreakpadSymbols`].
+    pub async fn breakpad_symbols(
+        &self,
+        request: DumpBreakpadSymbols,
+    ) -> CacheEntry<ByteView<'static>> {
+        self.inner.native.breakpad_symbols(request).await
+    }
+
+    /// Returns the progress of a prefetch started with [`prefetch_modules`](Self::prefetch_modules).
+    pub fn prefetch_progress(&self, prefetch_id: RequestId) -> Option<PrefetchProgress> {
+        let progress = self
+            .inner
+            .prefetches
+            .lock()
+            .unwrap()
+            .get(&prefetch_id)
+            .cloned()?;
+        let progress = progress.lock().unwrap().clone();
+        Some(progress)
+    }
+
     /// Creates a new request to symbolicate stacktraces.
     ///
     /// Returns an `Err` if the [`RequestService`] is already processing the
@@ -273,16 +526,17 @@ impl RequestService {
             slf.native
                 .symbolicate(request)
                 .await
-                .map(CompletedResponse::Native)
+                .map(|response| CompletedResponse::Native(Box::new(response)))
         })
     }
 
     pub fn symbolicate_js_stacktraces(
         &self,
         request: SymbolicateJsStacktraces,
+        options: RequestOptions,
     ) -> Result<RequestId, MaxRequestsError> {
         let slf = self.inner.clone();
-        self.create_symbolication_request("symbolicate_js", RequestOptions::default(), async move {
+        self.create_symbolication_request("symbolicate_js", options, async move {
             Ok(CompletedResponse::Js(slf.js.symbolicate_js(request).await))
         })
     }
@@ -303,6 +557,24 @@ impl RequestService {
         )
     }
 
+    /// Creates a new request to symbolicate a MetricKit diagnostic payload.
+    ///
+    /// Returns an `Err` if the [`RequestService`] is already processing the
+    /// maximum number of requests, as configured by the `max_concurrent_requests` option.
+    pub fn symbolicate_metrickit(
+        &self,
+        request: SymbolicateMetricKitPayload,
+        options: RequestOptions,
+    ) -> Result<RequestId, MaxRequestsError> {
+        let slf = self.inner.clone();
+        self.create_symbolication_request("symbolicate_metrickit", options, async move {
+            slf.native
+                .symbolicate_metrickit(request)
+                .await
+                .map(CompletedResponse::MetricKit)
+        })
+    }
+
     /// Creates a new request to process a minidump.
     ///
     /// Returns an `Err` if the [`RequestService`] is already processing the
@@ -317,11 +589,97 @@ impl RequestService {
         options: RequestOptions,
     ) -> Result<RequestId, MaxRequestsError> {
         let slf = self.inner.clone();
+        let stackwalk_options = options.stackwalk_options();
         self.create_symbolication_request("minidump_stackwalk", options, async move {
             slf.native
-                .process_minidump(platform, scope, minidump_file, sources, scraping)
+                .process_minidump(
+                    platform,
+                    scope,
+                    minidump_file,
+                    sources,
+                    scraping,
+                    stackwalk_options,
+                )
+                .await
+                .map(|response| CompletedResponse::Native(Box::new(response)))
+        })
+    }
+
+    /// Creates a new request to process an ELF core dump.
+    ///
+    /// Returns an `Err` if the [`RequestService`] is already processing the
+    /// maximum number of requests, as configured by the `max_concurrent_requests` option.
+    pub fn process_coredump(
+        &self,
+        platform: Option<Platform>,
+        scope: Scope,
+        coredump_file: TempPath,
+        sources: Arc<[SourceConfig]>,
+        scraping: ScrapingConfig,
+        options: RequestOptions,
+    ) -> Result<RequestId, MaxRequestsError> {
+        let slf = self.inner.clone();
+        let stackwalk_options = options.stackwalk_options();
+        self.create_symbolication_request("coredump_stackwalk", options, async move {
+            slf.native
+                .process_coredump(
+                    platform,
+                    scope,
+                    coredump_file,
+                    sources,
+                    scraping,
+                    stackwalk_options,
+                )
+                .await
+                .map(|response| CompletedResponse::Native(Box::new(response)))
+        })
+    }
+
+    /// Creates a new request to process a Windows Error Reporting archive.
+    ///
+    /// Returns an `Err` if the [`RequestService`] is already processing the
+    /// maximum number of requests, as configured by the `max_concurrent_requests` option.
+    pub fn process_wer_archive(
+        &self,
+        platform: Option<Platform>,
+        scope: Scope,
+        archive_file: TempPath,
+        sources: Arc<[SourceConfig]>,
+        scraping: ScrapingConfig,
+        options: RequestOptions,
+    ) -> Result<RequestId, MaxRequestsError> {
+        let slf = self.inner.clone();
+        let stackwalk_options = options.stackwalk_options();
+        self.create_symbolication_request("wer_stackwalk", options, async move {
+            slf.native
+                .process_wer_archive(
+                    platform,
+                    scope,
+                    archive_file,
+                    sources,
+                    scraping,
+                    stackwalk_options,
+                )
                 .await
-                .map(CompletedResponse::Native)
+                .map(|response| CompletedResponse::Native(Box::new(response)))
+        })
+    }
+
+    /// Creates a new request to stackwalk the threads of a process from their stack memory.
+    ///
+    /// Returns an `Err` if the [`RequestService`] is already processing the
+    /// maximum number of requests, as configured by the `max_concurrent_requests` option.
+    pub fn process_stack_memory(
+        &self,
+        request: StackwalkStackMemory,
+        options: RequestOptions,
+    ) -> Result<RequestId, MaxRequestsError> {
+        let slf = self.inner.clone();
+        self.create_symbolication_request("stack_memory_stackwalk", options, async move {
+            slf.native
+                .process_stack_memory(request)
+                .await
+                .map(|response| CompletedResponse::Native(Box::new(response)))
         })
     }
 
@@ -343,7 +701,7 @@ impl RequestService {
             slf.native
                 .process_apple_crash_report(platform, scope, apple_crash_report, sources, scraping)
                 .await
-                .map(CompletedResponse::Native)
+                .map(|response| CompletedResponse::Native(Box::new(response)))
         })
     }
 
@@ -450,8 +808,28 @@ impl RequestService {
             let response = match response {
                 Ok(Ok(mut response)) => {
                     if !options.dif_candidates {
-                        if let CompletedResponse::Native(ref mut res) = response {
-                            clear_dif_candidates(res)
+                        match response {
+                            CompletedResponse::Native(ref mut res) => {
+                                clear_dif_candidates(&mut res.modules)
+                            }
+                            CompletedResponse::MetricKit(ref mut res) => {
+                                clear_dif_candidates(&mut res.modules)
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let Some(demangling) = &options.demangling {
+                        match response {
+                            CompletedResponse::Native(ref mut res) => {
+                                res.apply_demangling(demangling)
+                            }
+                            CompletedResponse::MetricKit(ref mut res) => {
+                                res.apply_demangling(demangling)
+                            }
+                            CompletedResponse::Js(ref mut res) => {
+                                demangle_js_frames(res, demangling)
+                            }
+                            _ => {}
                         }
                     }
                     sentry::end_session_with_status(SessionStatus::Exited);
@@ -508,6 +886,12 @@ impl RequestService {
 /// The maximum delay we allow for polling a finished request before dropping it.
 const MAX_POLL_DELAY: Duration = Duration::from_secs(90);
 
+/// The number of modules of a prefetch which are fetched concurrently.
+const PREFETCH_CONCURRENCY: usize = 10;
+
+/// How long the progress of a completed prefetch can be polled.
+const PREFETCH_RETENTION: Duration = Duration::from_secs(10 * 60);
+
 /// An error returned when symbolicator receives a request while already processing
 /// the maximum number of requests.
 #[derive(Debug, Clone, thiserror::Error)]
@@ -116,6 +116,7 @@ async fn main() -> Result<()> {
             let source = Arc::new(SentrySourceConfig {
                 id: SourceId::new("sentry:project"),
                 token: auth_token.clone(),
+                organizations: Vec::new(),
                 url: base_url
                     .join(&format!("projects/{org}/{project}/artifact-lookup/"))
                     .unwrap(),
@@ -137,15 +138,22 @@ async fn main() -> Result<()> {
             tracing::info!("symbolicating event");
 
             let res = native.symbolicate(request).await?;
-            CompletedResponse::NativeSymbolication(res)
+            CompletedResponse::NativeSymbolication(Box::new(res))
         }
         Payload::Minidump(minidump_path) => {
             let dsym_sources = prepare_dsym_sources(mode, &symbolicator_config, symbols);
             tracing::info!("symbolicating minidump");
             let res = native
-                .process_minidump(None, scope, minidump_path, dsym_sources, Default::default())
+                .process_minidump(
+                    None,
+                    scope,
+                    minidump_path,
+                    dsym_sources,
+                    Default::default(),
+                    Default::default(),
+                )
                 .await?;
-            CompletedResponse::NativeSymbolication(res)
+            CompletedResponse::NativeSymbolication(Box::new(res))
         }
         Payload::Event(event) => anyhow::bail!(
             "Cannot symbolicate event: invalid platform {}",
@@ -186,6 +194,7 @@ fn prepare_dsym_sources(
         let project_source = SourceConfig::Sentry(Arc::new(SentrySourceConfig {
             id: SourceId::new("sentry:project"),
             token: auth_token.clone(),
+            organizations: Vec::new(),
             url: base_url
                 .join(&format!("projects/{org}/{project}/files/dsyms/"))
                 .unwrap(),
@@ -206,6 +215,9 @@ fn prepare_dsym_sources(
                     casing: Default::default(),
                 },
                 is_public: false,
+                limits: Default::default(),
+                priority: 0,
+                loose_matching: false,
             },
         };
         dsym_sources.push(SourceConfig::Filesystem(local_source.into()));
@@ -718,6 +730,9 @@ mod event {
             source_link: value.source_link,
             in_app: value.in_app,
             trust: value.trust,
+            registers: Default::default(),
+            address_tag: None,
+            variables: Vec::new(),
         })
     }
 
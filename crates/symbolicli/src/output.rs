@@ -9,20 +9,20 @@ use symbolicator_native::interface::{
 
 #[derive(Debug, Clone)]
 pub enum CompletedResponse {
-    NativeSymbolication(CompletedSymbolicationResponse),
+    NativeSymbolication(Box<CompletedSymbolicationResponse>),
     JsSymbolication(CompletedJsSymbolicationResponse),
 }
 
 pub fn print_compact(response: CompletedResponse) {
     match response {
-        CompletedResponse::NativeSymbolication(response) => print_compact_native(response),
+        CompletedResponse::NativeSymbolication(response) => print_compact_native(*response),
         CompletedResponse::JsSymbolication(response) => print_compact_js(response),
     }
 }
 
 pub fn print_pretty(response: CompletedResponse) {
     match response {
-        CompletedResponse::NativeSymbolication(response) => print_pretty_native(response),
+        CompletedResponse::NativeSymbolication(response) => print_pretty_native(*response),
         CompletedResponse::JsSymbolication(response) => print_pretty_js(response),
     }
 }
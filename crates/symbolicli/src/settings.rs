@@ -220,8 +220,18 @@ impl Settings {
             }
 
             let mut caches = CacheConfigs::default();
-            caches.downloaded.retry_misses_after = Some(Duration::ZERO);
-            caches.derived.retry_misses_after = Some(Duration::ZERO);
+            for retry_after in [
+                &mut caches.downloaded.retry_misses_after,
+                &mut caches.downloaded.retry_download_errors_after,
+                &mut caches.downloaded.retry_timeouts_after,
+                &mut caches.downloaded.retry_permission_denied_after,
+                &mut caches.derived.retry_misses_after,
+                &mut caches.derived.retry_download_errors_after,
+                &mut caches.derived.retry_timeouts_after,
+                &mut caches.derived.retry_permission_denied_after,
+            ] {
+                *retry_after = Some(Duration::ZERO);
+            }
 
             Config {
                 sources: Arc::from(sources),